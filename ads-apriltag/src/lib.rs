@@ -329,6 +329,23 @@ impl Family {
         let name = unsafe { (*self.0).name };
         FamilyType::from_name(name)
     }
+
+    /// Render tag `id` with one pixel per bit, including the white border.
+    ///
+    /// Returns `None` if the family has no tag with this id.
+    pub fn to_image(&self, id: u32) -> Option<ImageU8Owned> {
+        if id >= unsafe { (*self.0).ncodes } {
+            return None;
+        }
+        unsafe {
+            let im = apriltag_sys::apriltag_to_image(self.0, id);
+            let (width, height, stride) = ((*im).width, (*im).height, (*im).stride);
+            let len = height as usize * stride as usize;
+            let data = std::slice::from_raw_parts((*im).buf, len).to_vec();
+            apriltag_sys::image_u8_destroy(im);
+            ImageU8Owned::new(width, height, stride, data)
+        }
+    }
 }
 
 impl Drop for Family {
@@ -422,6 +439,13 @@ impl Detection {
     pub fn center(&self) -> &[f64] {
         unsafe { &(*self.0).c }
     }
+    /// The corners of the tag in pixel coordinates.
+    ///
+    /// These wrap counter-clockwise around the tag, starting at the corner
+    /// which is bottom left when the tag is upright.
+    pub fn corners(&self) -> &[[f64; 2]; 4] {
+        unsafe { &(*self.0).p }
+    }
 }
 
 impl std::fmt::Debug for Detection {
@@ -89,6 +89,13 @@ pub struct MainbrainConfig {
     /// Defaults to [DEFAULT_OUTPUT_BASE_DIRNAME].
     #[serde(default = "default_output_base_dirname")]
     pub output_base_dirname: std::path::PathBuf,
+    /// Unfinished recording (`.braid` directory) to resume, optional. Can
+    /// contain shell variables.
+    ///
+    /// If Braid stopped without finishing a recording, e.g. due to a crash,
+    /// set this to resume the recording once all cameras are synchronized.
+    /// Tracking continues from the state last saved in the directory.
+    pub resume_recording: Option<std::path::PathBuf>,
     /// Parameters for Kalman filter and data association
     #[serde(default = "braid_types::default_tracking_params_full_3d")]
     pub tracking_params: braid_types::TrackingParams,
@@ -131,6 +138,11 @@ pub struct MainbrainConfig {
     /// Address of HTTP port for model server emitting realtime tracking results
     #[serde(default = "default_model_server_addr")]
     pub model_server_addr: std::net::SocketAddr,
+    /// Address (`IP:PORT`) to which the realtime tracking results are
+    /// additionally sent as UDP datagrams, optional. This may be a multicast
+    /// address. Each datagram contains a CBOR-encoded
+    /// [braid_types::PoseUdpPacket].
+    pub model_server_udp_addr: Option<std::net::SocketAddr>,
     /// Save rows to data2d_distorted where nothing detected (saves timestamps)
     #[serde(default = "default_true")]
     pub save_empty_data2d: bool,
@@ -148,6 +160,12 @@ pub struct MainbrainConfig {
     /// sending data to disk.
     #[serde(default = "default_write_buffer_size_num_messages")]
     pub write_buffer_size_num_messages: usize,
+    /// Closed-loop rules triggered by the 3D tracking output.
+    #[serde(default)]
+    pub closed_loop: braid_types::ClosedLoopConfig,
+    /// Detection of calibration drift, for example from a bumped camera.
+    #[serde(default)]
+    pub calibration_drift: braid_types::CalibrationDriftConfig,
 }
 
 impl std::default::Default for MainbrainConfig {
@@ -155,6 +173,7 @@ impl std::default::Default for MainbrainConfig {
         Self {
             cal_fname: None,
             output_base_dirname: default_output_base_dirname(),
+            resume_recording: None,
             tracking_params: braid_types::default_tracking_params_full_3d(),
             // Raising the mainbrain thread priority is currently disabled.
             // sched_policy_priority: None,
@@ -162,11 +181,14 @@ impl std::default::Default for MainbrainConfig {
             lowlatency_camdata_udp_port: Default::default(),
             http_api_server_addr: default_http_api_server_addr(),
             model_server_addr: default_model_server_addr(),
+            model_server_udp_addr: None,
             save_empty_data2d: true,
             secret_base64: None,
             acquisition_duration_allowed_imprecision_msec:
                 braid_types::DEFAULT_ACQUISITION_DURATION_ALLOWED_IMPRECISION_MSEC,
             write_buffer_size_num_messages: default_write_buffer_size_num_messages(),
+            closed_loop: Default::default(),
+            calibration_drift: Default::default(),
         }
     }
 }
@@ -232,6 +254,18 @@ impl BraidConfig {
         // fixup self.mainbrain.output_base_dirname
         fixup_relative_path(&mut self.mainbrain.output_base_dirname, &dirname)?;
 
+        // fixup self.mainbrain.resume_recording
+        if let Some(resume_recording) = self.mainbrain.resume_recording.as_mut() {
+            fixup_relative_path(resume_recording, &dirname)?;
+        }
+
+        // fixup mesh filenames of self.mainbrain.closed_loop.rules
+        for rule in self.mainbrain.closed_loop.rules.iter_mut() {
+            if let Some(braid_types::Volume3D::Mesh { filename }) = rule.volume.as_mut() {
+                fixup_relative_path(filename, &dirname)?;
+            }
+        }
+
         // fixup self.cameras.camera_settings_filename
         for camera_config in self.cameras.iter_mut() {
             if let Some(ref mut camera_settings_filename) =
@@ -293,3 +327,42 @@ pub fn parse_config_file<P: AsRef<std::path::Path>>(fname: P) -> Result<BraidCon
     cfg.fixup_relative_paths(fname.as_ref())?;
     Ok(cfg)
 }
+
+#[test]
+fn test_parse_device_timestamp_config() {
+    use braid_types::DeviceTimestampConfig;
+
+    // Configurations written before the frame rate could be set.
+    let cfg: BraidConfig = toml::from_str(
+        r#"
+        [[cameras]]
+        name = "cam1"
+
+        [trigger]
+        trigger_type = "DeviceTimestamp"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        cfg.trigger,
+        TriggerType::DeviceTimestamp(DeviceTimestampConfig { framerate: None })
+    );
+
+    let cfg: BraidConfig = toml::from_str(
+        r#"
+        [[cameras]]
+        name = "cam1"
+
+        [trigger]
+        trigger_type = "DeviceTimestamp"
+        framerate = 100.0
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        cfg.trigger,
+        TriggerType::DeviceTimestamp(DeviceTimestampConfig {
+            framerate: Some(100.0)
+        })
+    );
+}
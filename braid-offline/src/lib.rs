@@ -253,6 +253,7 @@ where
                 // to ROS-compatible names. E.g. real name "Basler-1234" ROS name
                 // "Basler_1234".
                 let mut cams = ci.cameras.clone();
+                let mut time_offsets = ci.time_offsets.clone();
                 let mut found = 0;
                 let mut count = 0;
                 for cam_id_in_calibration in cams.cams_by_name().keys() {
@@ -280,9 +281,17 @@ where
                     } else {
                         braid_mvg::MultiCameraSystem::new(new_cams)
                     };
+                    time_offsets = time_offsets
+                        .into_iter()
+                        .map(|(orig_name, offset)| {
+                            (RawCamName::new(orig_name).as_str().to_string(), offset)
+                        })
+                        .collect();
                 }
                 let water = ci.water;
                 flydra_mvg::FlydraMultiCameraSystem::from_system(cams, water)
+                    .with_refractive_interfaces(ci.refractive_interfaces.clone())
+                    .with_time_offsets(time_offsets)
             }
             (None, None) => {
                 return Err(Error::NoCalibrationFound);
@@ -489,6 +498,7 @@ where
             per_cam_data,
             print_stats: true,
             save_performance_histograms,
+            resume: false,
         };
 
         coord_processor
@@ -679,7 +689,7 @@ where
             info!("send_pose server at {}", addr);
             coord_processor.add_listener(data_tx);
 
-            let model_server_future = new_model_server(data_rx, addr);
+            let model_server_future = new_model_server(data_rx, None, addr);
             Some(tokio::spawn(async { model_server_future.await }))
         }
         None => None,
@@ -245,6 +245,7 @@ where
         original_recording_time: None,
         save_empty_data2d: false, // We do filtering below, but is this correct?
         saving_program_name: env!("CARGO_PKG_NAME").to_string(),
+        tracking_params_history: vec![],
     };
     let metadata_buf = serde_yaml::to_string(&metadata).unwrap();
 
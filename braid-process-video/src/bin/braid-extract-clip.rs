@@ -0,0 +1,197 @@
+use clap::Parser;
+use eyre::{self as anyhow, Result, WrapErr};
+use std::path::{Path, PathBuf};
+
+use braid_process_video::{
+    run_config, BraidRetrackVideoConfig, OutputConfig, Validate, VideoOutputConfig,
+    VideoSourceConfig,
+};
+
+/// Extract synchronized video clips around a Braid event.
+///
+/// The clip is specified either by an object ID (the clip is centered on the
+/// birth of the object) or by an explicit range of braidz frame numbers.
+/// Reprojected 3D positions and the braidz frame number are drawn on the
+/// output.
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Cli {
+    /// Input braidz file
+    braidz: PathBuf,
+
+    /// Input video files, one per camera
+    #[arg(required = true)]
+    videos: Vec<PathBuf>,
+
+    /// Extract the clip around the birth of this object
+    #[arg(long, conflicts_with_all = ["start_frame", "stop_frame"])]
+    obj_id: Option<u32>,
+
+    /// Number of frames to include before the birth of the object
+    #[arg(long, default_value_t = 100)]
+    pre_frames: u64,
+
+    /// Number of frames to include after the birth of the object
+    #[arg(long, default_value_t = 100)]
+    post_frames: u64,
+
+    /// Extend the clip to the death of the object (plus `--post-frames`)
+    #[arg(long, requires = "obj_id")]
+    whole_trajectory: bool,
+
+    /// Draw all objects, not only `--obj-id`
+    #[arg(long, requires = "obj_id")]
+    all_objects: bool,
+
+    /// First braidz frame of the clip
+    #[arg(long)]
+    start_frame: Option<i64>,
+
+    /// Last braidz frame of the clip (inclusive)
+    #[arg(long)]
+    stop_frame: Option<i64>,
+
+    /// Save one clip per camera rather than a composite of all cameras
+    #[arg(long)]
+    per_camera: bool,
+
+    /// Directory into which the clips are saved
+    #[arg(short, long, default_value = ".")]
+    output_dir: PathBuf,
+
+    #[arg(short, long)]
+    time_dilation_factor: Option<f32>,
+
+    /// Set to disable showing progress bar
+    #[arg(long)]
+    no_progress: bool,
+}
+
+/// Find the braidz frame range for `obj_id`.
+fn obj_frame_range(braidz: &Path, obj_id: u32) -> Result<(i64, i64)> {
+    let archive = braidz_parser::braidz_parse_path(braidz)
+        .with_context(|| format!("opening braidz archive {}", braidz.display()))?;
+    let kests = archive
+        .kalman_estimates_table
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("no 3D tracking data in {}", braidz.display()))?;
+    let (first, last) = kests
+        .iter()
+        .filter(|row| row.obj_id == obj_id)
+        .map(|row| row.frame.0 as i64)
+        .fold(None, |acc, f| match acc {
+            None => Some((f, f)),
+            Some((first, last)) => Some((f.min(first), f.max(last))),
+        })
+        .ok_or_else(|| anyhow::anyhow!("obj_id {obj_id} not found in {}", braidz.display()))?;
+    Ok((first, last))
+}
+
+fn path_to_string(p: &Path) -> Result<String> {
+    p.to_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("path \"{}\" is not UTF8", p.display()))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    std::panic::set_hook(Box::new(tracing_panic::panic_hook));
+
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+
+    env_tracing_logger::init();
+
+    let cli = Cli::parse();
+
+    let (start, stop) = match cli.obj_id {
+        Some(obj_id) => {
+            let (birth, death) = obj_frame_range(&cli.braidz, obj_id)?;
+            tracing::info!("obj_id {obj_id}: frames {birth} to {death}");
+            let end = if cli.whole_trajectory { death } else { birth };
+            (
+                Some((birth - cli.pre_frames as i64).max(0)),
+                Some(end + cli.post_frames as i64),
+            )
+        }
+        None => {
+            if cli.start_frame.is_none() && cli.stop_frame.is_none() {
+                anyhow::bail!("Either --obj-id or a frame range must be given.");
+            }
+            (cli.start_frame, cli.stop_frame)
+        }
+    };
+
+    let obj_ids = match cli.obj_id {
+        Some(obj_id) if !cli.all_objects => Some(vec![obj_id]),
+        _ => None,
+    };
+
+    let clip_name = match cli.obj_id {
+        Some(obj_id) => format!("obj{obj_id}"),
+        None => format!(
+            "frames{}-{}",
+            start.map(|x| x.to_string()).unwrap_or_default(),
+            stop.map(|x| x.to_string()).unwrap_or_default()
+        ),
+    };
+
+    let video_output = |filename: PathBuf| -> Result<OutputConfig> {
+        let mut v = VideoOutputConfig {
+            filename: path_to_string(&filename)?,
+            ..Default::default()
+        };
+        v.video_options.time_dilation_factor = cli.time_dilation_factor;
+        v.video_options.stamp_braidz_frame = true;
+        Ok(OutputConfig::Video(v))
+    };
+
+    // Each job is a set of input videos rendered into one output.
+    let jobs: Vec<(Vec<&PathBuf>, PathBuf)> = if cli.per_camera {
+        cli.videos
+            .iter()
+            .map(|video| {
+                let stem = video
+                    .file_name()
+                    .and_then(|x| x.to_str())
+                    .and_then(|x| x.split('.').next())
+                    .unwrap_or("video");
+                let out = cli.output_dir.join(format!("{clip_name}-{stem}.mp4"));
+                (vec![video], out)
+            })
+            .collect()
+    } else {
+        let out = cli.output_dir.join(format!("{clip_name}.mp4"));
+        vec![(cli.videos.iter().collect(), out)]
+    };
+
+    for (videos, output_filename) in jobs {
+        let input_video = videos
+            .into_iter()
+            .map(|v| {
+                Ok(VideoSourceConfig {
+                    filename: path_to_string(v)?,
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let cfg = BraidRetrackVideoConfig {
+            input_braidz: Some(path_to_string(&cli.braidz)?),
+            input_video,
+            output: vec![video_output(output_filename)?],
+            start_braidz_frame: start,
+            stop_braidz_frame: stop,
+            obj_ids: obj_ids.clone(),
+            ..Default::default()
+        };
+        let basedir: Option<&Path> = None;
+        let cfg = cfg.validate(basedir)?;
+
+        for output in run_config(&cfg, !cli.no_progress).await? {
+            tracing::info!("saved {}", output.display());
+        }
+    }
+    Ok(())
+}
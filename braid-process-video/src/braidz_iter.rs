@@ -80,8 +80,15 @@ impl BraidArchiveNoVideoData {
     ) -> Result<Self> {
         let kalman_estimates_table = archive.kalman_estimates_table.clone();
         let recon = archive.calibration_info.as_ref().map(|x| {
-            let CalibrationInfo { water, cameras } = x;
+            let CalibrationInfo {
+                water,
+                refractive_interfaces,
+                time_offsets,
+                cameras,
+            } = x;
             flydra_mvg::FlydraMultiCameraSystem::from_system(cameras.clone(), *water)
+                .with_refractive_interfaces(refractive_interfaces.clone())
+                .with_time_offsets(time_offsets.clone())
         });
         let my_iter = Box::new(archive.iter_data2d_distorted()?);
         let my_iter: Box<dyn Iterator<Item = Result<Data2dDistortedRow, csv::Error>>> = my_iter;
@@ -307,8 +314,15 @@ impl<'a> BraidArchiveSyncVideoData<'a> {
             .collect();
 
         let recon = archive.calibration_info.as_ref().map(|x| {
-            let CalibrationInfo { water, cameras } = x;
+            let CalibrationInfo {
+                water,
+                refractive_interfaces,
+                time_offsets,
+                cameras,
+            } = x;
             flydra_mvg::FlydraMultiCameraSystem::from_system(cameras.clone(), *water)
+                .with_refractive_interfaces(refractive_interfaces.clone())
+                .with_time_offsets(time_offsets.clone())
         });
         let kests = IndexedKEsts::new(archive.kalman_estimates_table);
 
@@ -246,6 +246,11 @@ pub struct VideoOutputOptions {
     /// Save SVG and PNG intermediate images used to generate movies.
     #[serde(default)]
     pub save_debug_images: bool,
+    /// Stamp the braidz frame number and visible object IDs onto each frame.
+    ///
+    /// This requires an input braidz file.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub stamp_braidz_frame: bool,
 }
 
 impl VideoOutputOptions {
@@ -280,6 +285,16 @@ pub struct BraidRetrackVideoConfig {
     pub max_num_frames: Option<usize>,
     /// Every `log_interval_frames` a status message will be displayed.
     pub log_interval_frames: Option<usize>,
+    /// The first braidz frame number to render, skipping prior frames.
+    ///
+    /// This requires an input braidz file.
+    pub start_braidz_frame: Option<i64>,
+    /// The last braidz frame number to render (inclusive).
+    ///
+    /// This requires an input braidz file.
+    pub stop_braidz_frame: Option<i64>,
+    /// If set, only reproject the 3D positions of these object IDs.
+    pub obj_ids: Option<Vec<u32>>,
     pub input_braidz: Option<String>,
     #[serde(default)]
     pub input_video: Vec<VideoSourceConfig>,
@@ -297,6 +312,9 @@ impl Default for BraidRetrackVideoConfig {
             skip_n_first_output_frames: None,
             max_num_frames: None,
             log_interval_frames: None,
+            start_braidz_frame: None,
+            stop_braidz_frame: None,
+            obj_ids: None,
             input_braidz: None,
             output: vec![OutputConfig::default()],
             input_video: vec![
@@ -322,6 +340,18 @@ impl Validate for BraidRetrackVideoConfig {
         // Validate `input_braidz`.
         let input_braidz = base_join(self.input_braidz, basedir.as_ref())?;
 
+        // Validate `start_braidz_frame` and `stop_braidz_frame`.
+        if input_braidz.is_none()
+            && (self.start_braidz_frame.is_some() || self.stop_braidz_frame.is_some())
+        {
+            anyhow::bail!("A braidz frame range requires an input braidz file.");
+        }
+        if let (Some(start), Some(stop)) = (self.start_braidz_frame, self.stop_braidz_frame) {
+            if start > stop {
+                anyhow::bail!("start_braidz_frame ({start}) is after stop_braidz_frame ({stop}).");
+            }
+        }
+
         // Validate `output`.
         let output = self
             .output
@@ -443,3 +473,34 @@ fn test_default_config_is_valid_and_serializable() -> Result<()> {
     toml::to_string_pretty(&cfg.valid())?;
     Ok(())
 }
+
+#[test]
+fn test_braidz_frame_range_validation() {
+    let basedir: Option<String> = None;
+    let cfg = BraidRetrackVideoConfig {
+        start_braidz_frame: Some(10),
+        ..Default::default()
+    };
+    assert!(cfg.validate(basedir.as_ref()).is_err());
+
+    let cfg = BraidRetrackVideoConfig {
+        input_braidz: Some("a.braidz".into()),
+        start_braidz_frame: Some(10),
+        stop_braidz_frame: Some(5),
+        ..Default::default()
+    };
+    assert!(cfg.validate(basedir.as_ref()).is_err());
+
+    let cfg = BraidRetrackVideoConfig {
+        input_braidz: Some("a.braidz".into()),
+        start_braidz_frame: Some(5),
+        stop_braidz_frame: Some(10),
+        obj_ids: Some(vec![3]),
+        ..Default::default()
+    };
+    let cfg = cfg.validate(basedir).unwrap();
+    let buf = toml::to_string_pretty(cfg.valid()).unwrap();
+    let cfg2: BraidRetrackVideoConfig = toml::from_str(&buf).unwrap();
+    assert_eq!(cfg2.start_braidz_frame, Some(5));
+    assert_eq!(cfg2.obj_ids, Some(vec![3]));
+}
@@ -25,8 +25,8 @@ mod synced_iter;
 mod config;
 pub(crate) use config::FeatureDetectionMethod;
 pub use config::{
-    BraidRetrackVideoConfig, BraidzOutputConfig, OutputConfig, Valid, Validate, VideoOutputConfig,
-    VideoSourceConfig,
+    BraidRetrackVideoConfig, BraidzOutputConfig, DebugOutputConfig, OutputConfig, Valid, Validate,
+    VideoOutputConfig, VideoSourceConfig,
 };
 
 mod auto_config_generator;
@@ -89,6 +89,7 @@ impl SyncedPictures {
         &self,
         cam: &CameraSource,
         recon: &Option<FlydraMultiCameraSystem<f64>>,
+        obj_ids: Option<&[u32]>,
     ) -> Vec<(NotNan<f64>, NotNan<f64>)> {
         let recon = match recon {
             Some(recon) => recon,
@@ -108,6 +109,7 @@ impl SyncedPictures {
             Some(braidz_info) => braidz_info
                 .kalman_estimates
                 .iter()
+                .filter(|kest_row| obj_ids.map_or(true, |ids| ids.contains(&kest_row.obj_id)))
                 .filter_map(|kest_row| {
                     let pt3d = braid_mvg::PointWorldFrame {
                         coords: nalgebra::Point3::new(kest_row.x, kest_row.y, kest_row.z),
@@ -788,7 +790,12 @@ pub async fn run_config(
 
             match output {
                 OutputConfig::Video(v) => Ok(OutputStorage::Video(Box::new(
-                    output_video::VideoStorage::new(&v, &output_filename, &sources)?,
+                    output_video::VideoStorage::new(
+                        &v,
+                        &output_filename,
+                        &sources,
+                        cfg.obj_ids.clone(),
+                    )?,
                 ))),
                 OutputConfig::DebugTxt(_) => Ok(OutputStorage::Debug(DebugStorage {
                     path: output_filename.clone(),
@@ -814,6 +821,27 @@ pub async fn run_config(
 
     let mut output_storage: Vec<_> = output_storage.into_iter().collect::<Result<Vec<_>>>()?;
 
+    // Restrict to the requested range of braidz frames.
+    let moment_iter: Box<dyn Iterator<Item = _>> =
+        match (cfg.start_braidz_frame, cfg.stop_braidz_frame) {
+            (None, None) => moment_iter,
+            (start, stop) => {
+                let in_range = move |f: i64| {
+                    start.map_or(true, |start| f >= start) && stop.map_or(true, |stop| f <= stop)
+                };
+                Box::new(moment_iter.filter(move |m| {
+                    match m {
+                        // Errors are passed on to be raised below.
+                        Err(_) => true,
+                        Ok(m) => m
+                            .braidz_info
+                            .as_ref()
+                            .is_some_and(|b| in_range(b.frame_num)),
+                    }
+                }))
+            }
+        };
+
     // Trim to maximum number of frames.
     let moment_iter = match cfg.max_num_frames {
         Some(max_num_frames) => Box::new(moment_iter.take(max_num_frames)),
@@ -851,7 +879,14 @@ pub async fn run_config(
 
         for output in output_storage.iter_mut() {
             if let OutputStorage::Debug(d) = output {
-                writeln!(d.fd, "output frame {} ----------", out_fno)?;
+                match &synced_data.braidz_info {
+                    Some(b) => writeln!(
+                        d.fd,
+                        "output frame {} (braidz frame {}) ----------",
+                        out_fno, b.frame_num
+                    )?,
+                    None => writeln!(d.fd, "output frame {} ----------", out_fno)?,
+                }
             }
         }
 
@@ -1021,7 +1056,7 @@ fn gather_frame_data<'a>(
 
         cam_render_data
             .reprojected_points
-            .extend(synced_data.project_kests(source, &synced_data.recon));
+            .extend(synced_data.project_kests(source, &synced_data.recon, cfg.obj_ids.as_deref()));
 
         if !wrote_debug {
             for output in output_storage.iter_mut() {
@@ -38,10 +38,17 @@ impl BraidStorage {
         let recon = match cfg.processing_config.camera_calibration_source {
             CameraCalibrationSource::None => None,
             CameraCalibrationSource::CopyExisting => {
-                let braidz_types::CalibrationInfo { water, cameras } = braidz_calibration.unwrap();
-                Some(flydra_mvg::FlydraMultiCameraSystem::from_system(
-                    cameras, water,
-                ))
+                let braidz_types::CalibrationInfo {
+                    water,
+                    refractive_interfaces,
+                    time_offsets,
+                    cameras,
+                } = braidz_calibration.unwrap();
+                Some(
+                    flydra_mvg::FlydraMultiCameraSystem::from_system(cameras, water)
+                        .with_refractive_interfaces(refractive_interfaces)
+                        .with_time_offsets(time_offsets),
+                )
             }
         };
 
@@ -126,6 +133,7 @@ impl BraidStorage {
             per_cam_data: braidz_per_cam_save_data,
             print_stats: true,
             save_performance_histograms: false,
+            resume: false,
         };
 
         coord_processor
@@ -2,6 +2,7 @@ use chrono::{DateTime, FixedOffset};
 use eyre::{self as anyhow, Result};
 use std::io::Write;
 
+use machine_vision_formats::pixel_format::RGB8;
 use strand_cam_remote_control::{Mp4Codec, Mp4RecordingConfig};
 use strand_dynamic_frame::DynamicFrameOwned;
 
@@ -22,6 +23,10 @@ pub(crate) struct VideoStorage<'lib> {
     pub(crate) cum_width: usize,
     pub(crate) cum_height: usize,
     pub(crate) usvg_opt: usvg::Options,
+    /// Font used to stamp the braidz frame number, if enabled.
+    pub(crate) stamp_font: Option<rusttype::Font<'static>>,
+    /// If set, only these object IDs are listed when stamping.
+    pub(crate) obj_ids: Option<Vec<u32>>,
 }
 
 impl<'lib> VideoStorage<'lib> {
@@ -29,6 +34,7 @@ impl<'lib> VideoStorage<'lib> {
         v: &crate::config::VideoOutputConfig,
         output_filename: &std::path::Path,
         sources: &[crate::CameraSource],
+        obj_ids: Option<Vec<u32>>,
     ) -> Result<Self> {
         // compute output width and height
         let cum_width: usize = sources.iter().map(|s| s.per_cam_render.width).sum();
@@ -108,6 +114,15 @@ impl<'lib> VideoStorage<'lib> {
         // usvg_opt.resources_dir = std::fs::canonicalize(&args[1]).ok().and_then(|p| p.parent().map(|p| p.to_path_buf()));
         usvg_opt.fontdb.load_system_fonts();
 
+        let stamp_font = if v.video_options.stamp_braidz_frame {
+            Some(
+                rusttype::Font::try_from_bytes(ttf_firacode::REGULAR)
+                    .ok_or_else(|| anyhow::anyhow!("could not load font"))?,
+            )
+        } else {
+            None
+        };
+
         Ok(Self {
             path: output_filename.to_path_buf(),
             mp4_writer,
@@ -122,6 +137,8 @@ impl<'lib> VideoStorage<'lib> {
             cum_width,
             cum_height,
             usvg_opt,
+            stamp_font,
+            obj_ids,
         })
     }
 
@@ -312,11 +329,44 @@ impl<'lib> VideoStorage<'lib> {
 
         // Save the pixmap into the MP4 file being saved.
         let dyframe = DynamicFrameOwned::from_static_ref(&rasterized);
+        let dyframe = match (&self.stamp_font, &synced_data.braidz_info) {
+            (Some(font), Some(braidz_info)) => {
+                let mut rgb = dyframe.into_pixel_format::<RGB8>()?;
+                let text = self.stamp_text(braidz_info);
+                font_drawing::stamp_frame(&mut rgb, font, &text)?;
+                DynamicFrameOwned::from_static(rgb)
+            }
+            _ => dyframe,
+        };
         self.mp4_writer.write_dynamic(&dyframe.borrow(), save_ts)?;
 
         Ok(())
     }
 
+    /// Text stamped onto each frame: the braidz frame and visible objects.
+    fn stamp_text(&self, braidz_info: &crate::BraidzFrameInfo) -> String {
+        let obj_ids: Vec<String> = braidz_info
+            .kalman_estimates
+            .iter()
+            .map(|row| row.obj_id)
+            .filter(|obj_id| {
+                self.obj_ids
+                    .as_ref()
+                    .map_or(true, |ids| ids.contains(obj_id))
+            })
+            .map(|obj_id| obj_id.to_string())
+            .collect();
+        if obj_ids.is_empty() {
+            format!("frame {}", braidz_info.frame_num)
+        } else {
+            format!(
+                "frame {} obj_id {}",
+                braidz_info.frame_num,
+                obj_ids.join(",")
+            )
+        }
+    }
+
     pub(crate) async fn close(self) -> Result<()> {
         Ok(())
     }
@@ -1,8 +1,8 @@
 use eyre::{self as anyhow};
 
 use braid_process_video::{
-    BraidRetrackVideoConfig, BraidzOutputConfig, OutputConfig, Valid, Validate, VideoOutputConfig,
-    VideoSourceConfig,
+    BraidRetrackVideoConfig, BraidzOutputConfig, DebugOutputConfig, OutputConfig, Valid, Validate,
+    VideoOutputConfig, VideoSourceConfig,
 };
 
 const BASE_URL: &str = "https://strawlab-cdn.com/assets/flycube6-videos";
@@ -56,6 +56,14 @@ fn get_files(
     dirname: &str,
     max_num_frames: Option<usize>,
 ) -> anyhow::Result<Valid<BraidRetrackVideoConfig>> {
+    let basedir: Option<String> = None;
+    get_config(dirname, max_num_frames)?.validate(basedir)
+}
+
+fn get_config(
+    dirname: &str,
+    max_num_frames: Option<usize>,
+) -> anyhow::Result<BraidRetrackVideoConfig> {
     let file_list = parse_file_list(dirname)?;
 
     let outdir = format!("tests/downloaded-data/{}", dirname);
@@ -97,16 +105,13 @@ fn get_files(
         }),
     ];
 
-    let cfg = BraidRetrackVideoConfig {
+    Ok(BraidRetrackVideoConfig {
         input_braidz,
         input_video,
         output,
         max_num_frames,
         ..Default::default()
-    };
-
-    let basedir: Option<String> = None;
-    cfg.validate(basedir)
+    })
 }
 
 fn init_logging() {
@@ -162,3 +167,42 @@ async fn test_fc6_flies_100fps_2_cams_mp4() -> anyhow::Result<()> {
     do_config(&get_files(dirname, Some(100))?).await?;
     Ok(())
 }
+
+#[ignore]
+#[tokio::test]
+async fn test_braidz_frame_range() -> anyhow::Result<()> {
+    init_logging();
+    let dirname = "fc6-led-100fps-2-cams-dark";
+
+    let mut cfg = get_config(dirname, None)?;
+    let input_braidz = braidz_parser::braidz_parse_path(cfg.input_braidz.as_ref().unwrap())?;
+    let first_frame = input_braidz.data2d_distorted.as_ref().unwrap().frame_lim[0] as i64;
+    let (start, stop) = (first_frame + 20, first_frame + 49);
+    let debug_fname = format!("tests/rendered/{dirname}-frame-range.txt");
+    cfg.start_braidz_frame = Some(start);
+    cfg.stop_braidz_frame = Some(stop);
+    cfg.output = vec![
+        OutputConfig::Video(VideoOutputConfig {
+            filename: format!("tests/rendered/{dirname}-frame-range.mp4"),
+            video_options: Default::default(),
+        }),
+        OutputConfig::DebugTxt(DebugOutputConfig {
+            filename: debug_fname.clone(),
+        }),
+    ];
+    let basedir: Option<String> = None;
+    braid_process_video::run_config(&cfg.validate(basedir)?, false).await?;
+
+    // Every rendered braidz frame is in the requested range.
+    let debug_txt = std::fs::read_to_string(&debug_fname)?;
+    let frames: Vec<i64> = debug_txt
+        .lines()
+        .filter_map(|line| line.split("(braidz frame ").nth(1))
+        .map(|rest| rest.split(')').next().unwrap().parse().unwrap())
+        .collect();
+    assert!(!frames.is_empty());
+    assert!(frames.iter().all(|f| (start..=stop).contains(f)));
+    assert_eq!(frames.first(), Some(&start));
+    assert_eq!(frames.last(), Some(&stop));
+    Ok(())
+}
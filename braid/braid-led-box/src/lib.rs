@@ -0,0 +1,139 @@
+//! Connection to the LED box.
+//!
+//! Used by closed-loop rules in Braid and by `braid-protocol`, so that both
+//! switch the LED box channels in the same way.
+
+use futures::{sink::SinkExt, stream::StreamExt};
+use tokio::sync::mpsc::Sender;
+use tokio_serial::SerialPortBuilderExt;
+use tokio_util::codec::Decoder;
+use tracing::{debug, error, info};
+
+use json_lines::codec::JsonLinesCodec;
+use strand_led_box_comms::{DeviceState, FromDevice, OnState, ToDevice};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("serial port error: {0}")]
+    Serial(#[from] tokio_serial::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("communication error: {0}")]
+    Comms(String),
+    #[error(
+        "Failed connecting to LED Box. Is your firmware version correct? (Needed version: {})",
+        strand_led_box_comms::COMM_VERSION
+    )]
+    Version,
+    #[error("invalid channel {0}")]
+    InvalidChannel(u8),
+    #[error("LED box disconnected")]
+    Disconnected,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A connected LED box.
+///
+/// The state of all channels is kept so that switching one channel leaves the
+/// others unchanged.
+pub struct LedBox {
+    tx: Sender<ToDevice>,
+    state: DeviceState,
+}
+
+impl LedBox {
+    /// Open the LED box and spawn tasks to communicate with it.
+    ///
+    /// All channels are switched off initially.
+    pub async fn open(serial_device: &str) -> Result<Self> {
+        let tx = open_led_box(serial_device).await?;
+        Ok(Self {
+            tx,
+            state: DeviceState::default(),
+        })
+    }
+
+    /// Switch `channel` (1-4) on or off.
+    ///
+    /// `intensity` defaults to [strand_led_box_comms::MAX_INTENSITY].
+    pub async fn set_channel(
+        &mut self,
+        channel: u8,
+        on: bool,
+        intensity: Option<u16>,
+    ) -> Result<()> {
+        let ch = match channel {
+            1 => &mut self.state.ch1,
+            2 => &mut self.state.ch2,
+            3 => &mut self.state.ch3,
+            4 => &mut self.state.ch4,
+            _ => return Err(Error::InvalidChannel(channel)),
+        };
+        ch.on_state = if on {
+            OnState::ConstantOn
+        } else {
+            OnState::Off
+        };
+        ch.intensity = intensity.unwrap_or(strand_led_box_comms::MAX_INTENSITY);
+        self.tx
+            .send(ToDevice::DeviceState(self.state))
+            .await
+            .map_err(|_| Error::Disconnected)
+    }
+}
+
+async fn open_led_box(serial_device: &str) -> Result<Sender<ToDevice>> {
+    info!("opening LED box \"{serial_device}\"");
+    #[allow(unused_mut)]
+    let mut port =
+        tokio_serial::new(serial_device, strand_led_box_comms::BAUD_RATE).open_native_async()?;
+
+    #[cfg(unix)]
+    port.set_exclusive(false)?;
+
+    let (mut writer, mut reader) = JsonLinesCodec::default().framed(port).split();
+
+    // Clear potential initially present bytes from stream...
+    let _ = tokio::time::timeout(std::time::Duration::from_millis(50), reader.next()).await;
+
+    writer
+        .send(ToDevice::VersionRequest)
+        .await
+        .map_err(|e| Error::Comms(e.to_string()))?;
+    match tokio::time::timeout(std::time::Duration::from_millis(50), reader.next()).await {
+        Ok(Some(Ok(FromDevice::VersionResponse(strand_led_box_comms::COMM_VERSION)))) => {}
+        _ => return Err(Error::Version),
+    }
+
+    // Switch all channels off initially.
+    writer
+        .send(ToDevice::DeviceState(DeviceState::default()))
+        .await
+        .map_err(|e| Error::Comms(e.to_string()))?;
+
+    tokio::spawn(async move {
+        while let Some(msg) = reader.next().await {
+            match msg {
+                Ok(FromDevice::StateWasSet) => {}
+                Ok(msg) => debug!("LED box message: {msg:?}"),
+                Err(e) => {
+                    error!("LED box error: {e}");
+                    break;
+                }
+            }
+        }
+    });
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(20);
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if let Err(e) = writer.send(msg).await {
+                error!("LED box error: {e}");
+                break;
+            }
+        }
+    });
+
+    Ok(tx)
+}
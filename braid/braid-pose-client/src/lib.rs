@@ -0,0 +1,245 @@
+//! Client for the realtime tracking results of Braid (the Braid pose API).
+//!
+//! This connects to the event stream of the Braid model server (by default at
+//! `http://127.0.0.1:8397/`), reconnects if the connection is lost and delivers
+//! the messages as typed [ToListener] values. The objects currently being
+//! tracked are kept up to date in [LiveObjects].
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), braid_pose_client::Error> {
+//! use braid_pose_client::{PoseClient, PoseEvent, SendType};
+//!
+//! let mut client = PoseClient::new("http://127.0.0.1:8397/")?;
+//! loop {
+//!     match client.next_event().await? {
+//!         PoseEvent::Connected => println!("connected"),
+//!         PoseEvent::Disconnected => println!("disconnected, reconnecting"),
+//!         PoseEvent::Message(msg) => {
+//!             if let SendType::EndOfFrame(frame) = msg.msg {
+//!                 println!("frame {frame}: {} objects", client.live_objects().len());
+//!             }
+//!         }
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use tracing::{debug, warn};
+
+use event_stream_types::EventStreamParser;
+
+pub use braid_types::{SendKalmanEstimatesRow, SendType, ToListener, BRAID_POSE_API_VERSION};
+
+/// Path of the event stream on the model server.
+const EVENTS_PATH: &str = "events";
+
+/// The event type of pose API messages in the event stream.
+const BRAID_EVENT_NAME: &str = "braid";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid URL: {0}")]
+    InvalidUri(#[from] http::uri::InvalidUri),
+    #[error("unsupported Braid pose API version {0} (expected {BRAID_POSE_API_VERSION})")]
+    UnsupportedVersion(u16),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An event returned by [PoseClient::next_event].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoseEvent {
+    /// Connected (or reconnected) to the model server.
+    Connected,
+    /// The connection was lost. [PoseClient::live_objects] has been cleared.
+    Disconnected,
+    /// A message of the pose API.
+    Message(ToListener),
+}
+
+/// The objects currently being tracked, by obj_id.
+#[derive(Debug, Clone, Default)]
+pub struct LiveObjects {
+    objects: BTreeMap<u32, SendKalmanEstimatesRow>,
+}
+
+impl LiveObjects {
+    /// Update with a message: insert on birth and update, remove on death.
+    pub fn update(&mut self, msg: &SendType) {
+        match msg {
+            SendType::Birth(row) | SendType::Update(row) => {
+                self.objects.insert(row.obj_id, row.clone());
+            }
+            SendType::Death(obj_id) => {
+                self.objects.remove(obj_id);
+            }
+            SendType::EndOfFrame(_) | SendType::CalibrationFlydraXml(_) => {}
+        }
+    }
+
+    /// The latest estimate of object `obj_id`, if it is alive.
+    pub fn get(&self, obj_id: u32) -> Option<&SendKalmanEstimatesRow> {
+        self.objects.get(&obj_id)
+    }
+
+    /// Iterate over the live objects in order of obj_id.
+    pub fn iter(&self) -> impl Iterator<Item = &SendKalmanEstimatesRow> {
+        self.objects.values()
+    }
+
+    /// Number of live objects.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Whether there are no live objects.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Remove all objects.
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+}
+
+/// Client of the model server event stream.
+pub struct PoseClient {
+    events_uri: http::Uri,
+    reconnect_delay: Duration,
+    body: Option<hyper::body::Incoming>,
+    parser: EventStreamParser,
+    live_objects: LiveObjects,
+    calibration: Option<String>,
+}
+
+impl PoseClient {
+    /// Create a client of the model server at `url`, e.g.
+    /// `http://127.0.0.1:8397/`.
+    ///
+    /// This does not connect. The connection is made by [Self::next_event].
+    pub fn new(url: &str) -> Result<Self> {
+        let base = url.trim_end_matches('/');
+        let events_uri = format!("{base}/{EVENTS_PATH}").parse()?;
+        Ok(Self {
+            events_uri,
+            reconnect_delay: Duration::from_secs(1),
+            body: None,
+            parser: EventStreamParser::default(),
+            live_objects: LiveObjects::default(),
+            calibration: None,
+        })
+    }
+
+    /// Set the delay between connection attempts. The default is one second.
+    pub fn set_reconnect_delay(&mut self, delay: Duration) {
+        self.reconnect_delay = delay;
+    }
+
+    /// The objects currently being tracked.
+    pub fn live_objects(&self) -> &LiveObjects {
+        &self.live_objects
+    }
+
+    /// The latest multicamera calibration received, as flydra XML.
+    pub fn calibration_flydra_xml(&self) -> Option<&str> {
+        self.calibration.as_deref()
+    }
+
+    /// Whether currently connected to the model server.
+    pub fn is_connected(&self) -> bool {
+        self.body.is_some()
+    }
+
+    /// Wait for the next event.
+    ///
+    /// If not connected, this tries to connect until it succeeds. Errors are
+    /// returned only for messages which cannot be understood.
+    pub async fn next_event(&mut self) -> Result<PoseEvent> {
+        loop {
+            while let Some(msg) = self.parser.next_message() {
+                if msg.event.as_deref() != Some(BRAID_EVENT_NAME) {
+                    debug!("ignoring event {:?}", msg.event);
+                    continue;
+                }
+                let msg = self.parse(&msg.data)?;
+                self.live_objects.update(&msg.msg);
+                if let SendType::CalibrationFlydraXml(xml) = &msg.msg {
+                    self.calibration = Some(xml.clone());
+                }
+                return Ok(PoseEvent::Message(msg));
+            }
+
+            let Some(body) = self.body.as_mut() else {
+                match self.connect().await {
+                    Ok(body) => {
+                        self.body = Some(body);
+                        return Ok(PoseEvent::Connected);
+                    }
+                    Err(e) => {
+                        debug!("connecting to {} failed: {e}", self.events_uri);
+                        tokio::time::sleep(self.reconnect_delay).await;
+                        continue;
+                    }
+                }
+            };
+
+            match body.frame().await {
+                Some(Ok(frame)) => {
+                    if let Ok(data) = frame.into_data() {
+                        self.parser.push(&data);
+                    }
+                }
+                Some(Err(e)) => {
+                    warn!("connection to {} lost: {e}", self.events_uri);
+                    return Ok(self.disconnected());
+                }
+                None => {
+                    debug!("connection to {} closed", self.events_uri);
+                    return Ok(self.disconnected());
+                }
+            }
+        }
+    }
+
+    fn parse(&self, data: &str) -> Result<ToListener> {
+        // Check the version before parsing the message itself so that a
+        // version mismatch is reported as such.
+        let value: serde_json::Value = serde_json::from_str(data)?;
+        let v = value.get("v").and_then(|v| v.as_u64()).unwrap_or(0);
+        if v != u64::from(BRAID_POSE_API_VERSION) {
+            return Err(Error::UnsupportedVersion(v.try_into().unwrap_or(u16::MAX)));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn disconnected(&mut self) -> PoseEvent {
+        self.body = None;
+        self.parser = EventStreamParser::default();
+        self.live_objects.clear();
+        PoseEvent::Disconnected
+    }
+
+    async fn connect(
+        &self,
+    ) -> std::result::Result<hyper::body::Incoming, Box<dyn std::error::Error + Send + Sync>> {
+        let client =
+            hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+                .build_http::<Empty<Bytes>>();
+        let req = http::Request::builder()
+            .uri(self.events_uri.clone())
+            .header(http::header::ACCEPT, "text/event-stream")
+            .body(Empty::new())?;
+        let response = client.request(req).await?;
+        if !response.status().is_success() {
+            return Err(format!("unexpected status {}", response.status()).into());
+        }
+        Ok(response.into_body())
+    }
+}
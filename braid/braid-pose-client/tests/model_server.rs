@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use braid_pose_client::{PoseClient, PoseEvent, SendKalmanEstimatesRow, SendType};
+use braid_types::SyncFno;
+use flydra2::TimeDataPassthrough;
+use tokio::sync::mpsc::Sender;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn row(obj_id: u32, frame: u64, x: f64) -> SendKalmanEstimatesRow {
+    SendKalmanEstimatesRow {
+        obj_id,
+        frame: SyncFno(frame),
+        x,
+        y: 0.1,
+        z: 0.2,
+        xvel: 0.0,
+        yvel: 0.0,
+        zvel: 0.0,
+        P00: 1.0,
+        P01: 0.0,
+        P02: 0.0,
+        P11: 1.0,
+        P12: 0.0,
+        P22: 1.0,
+        P33: 1.0,
+        P44: 1.0,
+        P55: 1.0,
+    }
+}
+
+fn free_addr() -> std::net::SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn send(data_tx: &Sender<(SendType, TimeDataPassthrough)>, msg: SendType, frame: u64) {
+    let tdpt = TimeDataPassthrough::new(SyncFno(frame), &None);
+    data_tx.send((msg, tdpt)).await.unwrap();
+}
+
+async fn next_event(client: &mut PoseClient) -> PoseEvent {
+    tokio::time::timeout(TIMEOUT, client.next_event())
+        .await
+        .expect("timeout waiting for event")
+        .unwrap()
+}
+
+async fn next_msg(client: &mut PoseClient) -> SendType {
+    match next_event(client).await {
+        PoseEvent::Message(msg) => {
+            assert_eq!(msg.v, braid_pose_client::BRAID_POSE_API_VERSION);
+            msg.msg
+        }
+        ev => panic!("expected message, got {ev:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_model_server() {
+    let addr = free_addr();
+    let (data_tx, data_rx) = tokio::sync::mpsc::channel(50);
+    tokio::spawn(flydra2::new_model_server(data_rx, None, addr));
+
+    let url = format!("http://{addr}/");
+    let mut client = PoseClient::new(&url).unwrap();
+    client.set_reconnect_delay(Duration::from_millis(10));
+    assert!(!client.is_connected());
+    assert_eq!(next_event(&mut client).await, PoseEvent::Connected);
+    assert!(client.is_connected());
+
+    let calibration = "<multi_camera_reconstructor/>".to_string();
+    send(
+        &data_tx,
+        SendType::CalibrationFlydraXml(calibration.clone()),
+        0,
+    )
+    .await;
+    assert_eq!(
+        next_msg(&mut client).await,
+        SendType::CalibrationFlydraXml(calibration.clone())
+    );
+    assert_eq!(client.calibration_flydra_xml(), Some(calibration.as_str()));
+
+    send(&data_tx, SendType::Birth(row(1, 10, 0.0)), 10).await;
+    send(&data_tx, SendType::Birth(row(2, 10, 1.0)), 10).await;
+    send(&data_tx, SendType::EndOfFrame(SyncFno(10)), 10).await;
+    assert_eq!(
+        next_msg(&mut client).await,
+        SendType::Birth(row(1, 10, 0.0))
+    );
+    assert_eq!(
+        next_msg(&mut client).await,
+        SendType::Birth(row(2, 10, 1.0))
+    );
+    assert_eq!(
+        next_msg(&mut client).await,
+        SendType::EndOfFrame(SyncFno(10))
+    );
+    assert_eq!(client.live_objects().len(), 2);
+
+    send(&data_tx, SendType::Update(row(1, 11, 0.5)), 11).await;
+    send(&data_tx, SendType::Death(2), 11).await;
+    send(&data_tx, SendType::EndOfFrame(SyncFno(11)), 11).await;
+    assert_eq!(
+        next_msg(&mut client).await,
+        SendType::Update(row(1, 11, 0.5))
+    );
+    assert_eq!(next_msg(&mut client).await, SendType::Death(2));
+    assert_eq!(
+        next_msg(&mut client).await,
+        SendType::EndOfFrame(SyncFno(11))
+    );
+    let live: Vec<_> = client.live_objects().iter().cloned().collect();
+    assert_eq!(live, vec![row(1, 11, 0.5)]);
+
+    // A newly connecting client first receives the stored calibration.
+    let mut client2 = PoseClient::new(&url).unwrap();
+    assert_eq!(next_event(&mut client2).await, PoseEvent::Connected);
+    assert_eq!(
+        next_msg(&mut client2).await,
+        SendType::CalibrationFlydraXml(calibration)
+    );
+}
+
+#[tokio::test]
+async fn test_reconnect() {
+    // A server which sends one message per connection and then closes it.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let data = serde_json::json!({
+            "v": braid_pose_client::BRAID_POSE_API_VERSION,
+            "msg": SendType::Birth(row(7, 1, 0.0)),
+            "latency": null,
+            "synced_frame": 1,
+            "trigger_timestamp": null,
+        });
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n\
+            event: braid\ndata: {data}\n\n"
+        );
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // Read the request headers.
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap();
+                assert!(n > 0);
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+
+    let mut client = PoseClient::new(&format!("http://{addr}")).unwrap();
+    client.set_reconnect_delay(Duration::from_millis(10));
+    for _ in 0..2 {
+        assert_eq!(next_event(&mut client).await, PoseEvent::Connected);
+        match next_event(&mut client).await {
+            PoseEvent::Message(msg) => {
+                assert_eq!(msg.msg, SendType::Birth(row(7, 1, 0.0)));
+                assert!(msg.latency.is_nan());
+            }
+            ev => panic!("expected message, got {ev:?}"),
+        }
+        assert_eq!(client.live_objects().len(), 1);
+        assert_eq!(next_event(&mut client).await, PoseEvent::Disconnected);
+        assert!(client.live_objects().is_empty());
+        assert!(!client.is_connected());
+    }
+}
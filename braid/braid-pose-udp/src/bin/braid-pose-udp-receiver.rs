@@ -0,0 +1,62 @@
+use clap::Parser;
+use std::net::SocketAddr;
+
+use braid_pose_udp::{LatencyStats, PoseReceiver};
+
+/// Receive the Braid pose API over UDP and print latency statistics.
+///
+/// Set `model_server_udp_addr` in the `[mainbrain]` section of the Braid
+/// configuration to the same address.
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Cli {
+    /// Address on which to listen. May be a multicast address.
+    #[arg(default_value = "0.0.0.0:8398")]
+    addr: SocketAddr,
+
+    /// Interval between printing statistics, in seconds
+    #[arg(long, default_value_t = 1.0)]
+    interval: f64,
+
+    /// Print each received message
+    #[arg(long)]
+    print_messages: bool,
+}
+
+fn main() -> eyre::Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+
+    let cli = Cli::parse();
+    let interval = std::time::Duration::from_secs_f64(cli.interval);
+
+    let mut receiver = PoseReceiver::new(cli.addr)?;
+    receiver.socket().set_read_timeout(Some(interval))?;
+    tracing::info!("listening on {}", cli.addr);
+
+    let mut stats = LatencyStats::default();
+    let mut last_print = std::time::Instant::now();
+    loop {
+        match receiver.recv() {
+            Ok(packet) => {
+                stats.update_now(&packet);
+                if cli.print_messages {
+                    println!("{packet:?}");
+                }
+            }
+            Err(braid_pose_udp::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => tracing::warn!("{e}"),
+        }
+        if last_print.elapsed() >= interval {
+            tracing::info!("{stats}");
+            stats.reset();
+            last_print = std::time::Instant::now();
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Receive the Braid pose API over UDP.
+//!
+//! Braid sends its realtime tracking results over UDP when
+//! `model_server_udp_addr` is set in the `[mainbrain]` section of the Braid
+//! configuration. Each datagram contains one CBOR-encoded
+//! [braid_types::PoseUdpPacket]. Compared to the HTTP event stream of the model
+//! server, this avoids JSON parsing and TCP head-of-line blocking.
+//!
+//! [LatencyStats] measures the end-to-end latency from the trigger timestamp
+//! to reception. This is only meaningful if the clocks of the Braid computer
+//! and the receiving computer are synchronized (e.g. with PTP or NTP).
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+pub use braid_types::PoseUdpPacket;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("CBOR decoding error: {0}")]
+    Cbor(#[from] serde_cbor::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Receives [PoseUdpPacket]s.
+pub struct PoseReceiver {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+}
+
+impl PoseReceiver {
+    /// Listen on `addr`.
+    ///
+    /// If `addr` is a multicast address, listen on its port on all interfaces
+    /// and join the multicast group.
+    pub fn new(addr: SocketAddr) -> Result<Self> {
+        let socket = match addr.ip() {
+            IpAddr::V4(group) if group.is_multicast() => {
+                let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, addr.port()))?;
+                socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+                socket
+            }
+            IpAddr::V6(group) if group.is_multicast() => {
+                let socket = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, addr.port()))?;
+                socket.join_multicast_v6(&group, 0)?;
+                socket
+            }
+            _ => UdpSocket::bind(addr)?,
+        };
+        Ok(Self {
+            socket,
+            buf: vec![0; MAX_DATAGRAM_SIZE],
+        })
+    }
+
+    /// The underlying socket, e.g. to set a read timeout.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Block until the next packet is received and decode it.
+    pub fn recv(&mut self) -> Result<PoseUdpPacket> {
+        let n = self.socket.recv(&mut self.buf)?;
+        Ok(serde_cbor::from_slice(&self.buf[..n])?)
+    }
+}
+
+/// Minimum, mean and maximum of a set of values.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    n: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Summary {
+    fn push(&mut self, value: f64) {
+        if self.n == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.n += 1;
+        self.sum += value;
+    }
+
+    /// Number of values.
+    pub fn n(&self) -> u64 {
+        self.n
+    }
+
+    /// Mean value, or `None` if there are no values.
+    pub fn mean(&self) -> Option<f64> {
+        (self.n > 0).then(|| self.sum / self.n as f64)
+    }
+
+    /// Minimum value, or `None` if there are no values.
+    pub fn min(&self) -> Option<f64> {
+        (self.n > 0).then_some(self.min)
+    }
+
+    /// Maximum value, or `None` if there are no values.
+    pub fn max(&self) -> Option<f64> {
+        (self.n > 0).then_some(self.max)
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.min(), self.mean(), self.max()) {
+            (Some(min), Some(mean), Some(max)) => write!(
+                f,
+                "min {:.2} / mean {:.2} / max {:.2} msec",
+                min * 1000.0,
+                mean * 1000.0,
+                max * 1000.0
+            ),
+            _ => write!(f, "no data"),
+        }
+    }
+}
+
+/// Latency and packet loss statistics of received packets.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    /// Number of packets received.
+    pub n_received: u64,
+    /// Number of packets lost, according to gaps in the sequence numbers.
+    pub n_lost: u64,
+    /// Number of packets received out of order.
+    pub n_out_of_order: u64,
+    last_seq: Option<u64>,
+    /// Time from the trigger timestamp to reception, in seconds.
+    pub end_to_end: Summary,
+    /// Time from the trigger timestamp to sending by Braid, in seconds.
+    pub braid: Summary,
+    /// Time from sending by Braid to reception, in seconds.
+    pub network: Summary,
+}
+
+impl LatencyStats {
+    /// Update with `packet`, received at `now` (seconds since the UNIX epoch).
+    pub fn update(&mut self, packet: &PoseUdpPacket, now: f64) {
+        self.n_received += 1;
+        match self.last_seq {
+            Some(last) if packet.seq > last => {
+                self.n_lost += packet.seq - last - 1;
+                self.last_seq = Some(packet.seq);
+            }
+            Some(_) if packet.seq != 0 => {
+                self.n_out_of_order += 1;
+            }
+            // First packet or sender restarted.
+            _ => {
+                self.last_seq = Some(packet.seq);
+            }
+        }
+
+        if let Some(trigger_timestamp) = &packet.trigger_timestamp {
+            let end_to_end = now - trigger_timestamp.as_f64();
+            self.end_to_end.push(end_to_end);
+            if packet.latency.is_finite() {
+                self.braid.push(packet.latency);
+                self.network.push(end_to_end - packet.latency);
+            }
+        }
+    }
+
+    /// Update with `packet`, received now.
+    pub fn update_now(&mut self, packet: &PoseUdpPacket) {
+        let now = strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+        self.update(packet, now);
+    }
+
+    /// Clear the statistics but keep track of the sequence number.
+    pub fn reset(&mut self) {
+        *self = Self {
+            last_seq: self.last_seq,
+            ..Default::default()
+        };
+    }
+}
+
+impl std::fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} received, {} lost, {} out of order; end-to-end: {}; braid: {}; network: {}",
+            self.n_received,
+            self.n_lost,
+            self.n_out_of_order,
+            self.end_to_end,
+            self.braid,
+            self.network
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use braid_types::{FlydraFloatTimestampLocal, SendType, SyncFno, Triggerbox};
+
+    fn packet(seq: u64, trigger_timestamp: Option<f64>, latency: f64) -> PoseUdpPacket {
+        PoseUdpPacket {
+            v: braid_types::BRAID_POSE_API_VERSION,
+            seq,
+            msg: SendType::EndOfFrame(SyncFno(seq)),
+            latency,
+            synced_frame: SyncFno(seq),
+            trigger_timestamp: trigger_timestamp
+                .map(FlydraFloatTimestampLocal::<Triggerbox>::from_f64),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut receiver = PoseReceiver::new("127.0.0.1:0".parse().unwrap()).unwrap();
+        let dest = receiver.socket().local_addr().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let expected = packet(42, Some(1000.0), 0.002);
+        sender
+            .send_to(&serde_cbor::to_vec(&expected).unwrap(), dest)
+            .unwrap();
+        let received = receiver.recv().unwrap();
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut stats = LatencyStats::default();
+        stats.update(&packet(10, Some(100.0), 0.002), 100.005);
+        stats.update(&packet(11, None, f64::NAN), 100.010);
+        // Packets 12 and 13 are lost.
+        stats.update(&packet(14, Some(100.010), 0.003), 100.014);
+        stats.update(&packet(13, Some(100.008), 0.003), 100.015);
+
+        assert_eq!(stats.n_received, 4);
+        assert_eq!(stats.n_lost, 2);
+        assert_eq!(stats.n_out_of_order, 1);
+        assert_eq!(stats.end_to_end.n(), 3);
+        assert!((stats.end_to_end.max().unwrap() - 0.007).abs() < 1e-6);
+        assert!((stats.braid.mean().unwrap() - (0.008 / 3.0)).abs() < 1e-6);
+        assert!((stats.network.min().unwrap() - 0.001).abs() < 1e-6);
+
+        stats.reset();
+        assert_eq!(stats.n_received, 0);
+        stats.update(&packet(15, None, f64::NAN), 100.02);
+        assert_eq!(stats.n_lost, 0);
+    }
+}
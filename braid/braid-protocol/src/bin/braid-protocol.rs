@@ -0,0 +1,52 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+use braid_protocol::{MockMainbrain, Protocol, ProtocolRunner, Target};
+
+/// Run an experiment protocol (a `.toml` or `.yaml` file) against Braid.
+///
+/// Each step is logged to the `textlog.csv` file of the Braid recording.
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Cli {
+    /// The protocol file.
+    protocol: PathBuf,
+
+    /// URL of Braid, as printed by Braid on startup (including the token).
+    #[arg(long, required_unless_present = "dry_run")]
+    braid_url: Option<String>,
+
+    /// Do not connect to Braid or the LED box, but print the steps and the
+    /// resulting textlog without waiting.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+
+    let cli = Cli::parse();
+    let protocol = Protocol::from_path(&cli.protocol)?;
+
+    let target = if cli.dry_run {
+        Target::Mock(MockMainbrain::new())
+    } else {
+        // `required_unless_present` ensures the URL is given.
+        Target::connect(cli.braid_url.as_deref().unwrap()).await?
+    };
+
+    let mut runner = ProtocolRunner::new(target);
+    runner.run(&protocol).await?;
+
+    if let Target::Mock(mock) = runner.target() {
+        println!("textlog messages:");
+        for message in mock.textlog() {
+            println!("{message}");
+        }
+    }
+    Ok(())
+}
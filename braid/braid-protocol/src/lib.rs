@@ -0,0 +1,688 @@
+//! Run declarative experiment protocols against Braid.
+//!
+//! A protocol is a list of steps, written in TOML or YAML, which are executed
+//! in order against a running Braid (the "mainbrain"). A step may be delayed
+//! until a given time after the start of the protocol (`at`, in seconds) and
+//! may wait for an event, such as an object being tracked. Each step is logged
+//! to the `textlog.csv` file of the current Braid recording.
+//!
+//! ```toml
+//! name = "looming stimulus"
+//! led_box_device = "/dev/ttyACM0"
+//!
+//! [[steps]]
+//! action = { type = "StartRecording" }
+//!
+//! [[steps]]
+//! action = { type = "SetExperimentUuid" }
+//!
+//! [[steps]]
+//! action = { type = "WaitForObject", timeout = 60.0 }
+//!
+//! [[steps]]
+//! action = { type = "LedBox", channel = 1, on = true }
+//!
+//! [[steps]]
+//! action = { type = "PostTriggerMp4Recording" }
+//!
+//! [[steps]]
+//! at = 120.0
+//! action = { type = "StopRecording" }
+//! ```
+//!
+//! With a [MockMainbrain], the protocol is dry-run: nothing is sent to Braid
+//! or the LED box, waits take no time and events are assumed to happen
+//! immediately.
+
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use braid_http_session::MainbrainSession;
+use braid_led_box::LedBox;
+use braid_pose_client::{PoseClient, PoseEvent, SendType};
+use braid_types::BraidHttpApiCallback;
+use strand_bui_backend_session_types::BuiServerAddrInfo;
+
+/// Default URL of the Braid model server, used for event-driven steps.
+pub const DEFAULT_POSE_URL: &str = "http://127.0.0.1:8397/";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("unknown protocol file extension (expected .toml, .yaml or .yml): {0}")]
+    UnknownExtension(String),
+    #[error("invalid protocol: {0}")]
+    InvalidProtocol(String),
+    #[error("invalid Braid URL: {0}")]
+    InvalidUrl(String),
+    #[error("Braid session error: {0}")]
+    BuiBackendSession(#[from] strand_bui_backend_session::Error),
+    #[error("Braid HTTP error: {0}")]
+    MainbrainSession(#[from] braid_http_session::Error),
+    #[error("Braid pose API error: {0}")]
+    PoseClient(#[from] braid_pose_client::Error),
+    #[error("LED box error: {0}")]
+    LedBox(#[from] braid_led_box::Error),
+    #[error("timeout in step {index} ({action})")]
+    Timeout { index: usize, action: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An experiment protocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Protocol {
+    /// Name of the protocol, included in the log of each step.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Serial device of the LED box (e.g. `/dev/ttyACM0`). Required if any
+    /// step uses the LED box.
+    #[serde(default)]
+    pub led_box_device: Option<String>,
+    /// URL of the Braid model server, used for event-driven steps. Defaults
+    /// to [DEFAULT_POSE_URL].
+    #[serde(default)]
+    pub pose_url: Option<String>,
+    /// The steps, executed in order.
+    pub steps: Vec<Step>,
+}
+
+/// A step of a [Protocol].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// If given, wait until this time (in seconds since the start of the
+    /// protocol) before performing the action. If this time has already
+    /// passed, the action is performed immediately.
+    #[serde(default)]
+    pub at: Option<f64>,
+    /// The action to perform.
+    pub action: Action,
+}
+
+/// The action of a [Step].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Action {
+    /// Start saving tracking data (the `.braidz` file).
+    StartRecording,
+    /// Stop saving tracking data.
+    StopRecording,
+    /// Start saving MP4 files in all cameras.
+    StartMp4Recording,
+    /// Stop saving MP4 files in all cameras.
+    StopMp4Recording,
+    /// Set the experiment UUID of the current recording.
+    SetExperimentUuid {
+        /// The UUID. If not given, a new random UUID is used.
+        #[serde(default)]
+        uuid: Option<String>,
+    },
+    /// Set the number of frames buffered in each camera for post-triggering.
+    SetPostTriggerBufferSize {
+        /// Number of frames.
+        frames: usize,
+    },
+    /// Save the buffered frames, and the following ones, to MP4 files in all
+    /// cameras.
+    PostTriggerMp4Recording,
+    /// Switch a channel of the LED box given in [Protocol::led_box_device].
+    LedBox {
+        /// Channel number (1-4).
+        channel: u8,
+        /// Whether to switch the channel on or off.
+        on: bool,
+        /// LED intensity. Defaults to the maximum intensity.
+        #[serde(default)]
+        intensity: Option<u16>,
+    },
+    /// Wait for a duration.
+    Wait {
+        /// Duration in seconds.
+        duration: f64,
+    },
+    /// Wait until Braid is tracking at least one object.
+    WaitForObject {
+        /// If given, fail if no object is tracked within this time (in
+        /// seconds).
+        #[serde(default)]
+        timeout: Option<f64>,
+    },
+    /// Only log a message.
+    Log {
+        /// The message.
+        message: String,
+    },
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::StartRecording => "StartRecording",
+            Action::StopRecording => "StopRecording",
+            Action::StartMp4Recording => "StartMp4Recording",
+            Action::StopMp4Recording => "StopMp4Recording",
+            Action::SetExperimentUuid { .. } => "SetExperimentUuid",
+            Action::SetPostTriggerBufferSize { .. } => "SetPostTriggerBufferSize",
+            Action::PostTriggerMp4Recording => "PostTriggerMp4Recording",
+            Action::LedBox { .. } => "LedBox",
+            Action::Wait { .. } => "Wait",
+            Action::WaitForObject { .. } => "WaitForObject",
+            Action::Log { .. } => "Log",
+        }
+    }
+}
+
+fn check_seconds(index: usize, what: &str, value: f64) -> Result<()> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(Error::InvalidProtocol(format!(
+            "step {index}: {what} must be a non-negative number of seconds, not {value}"
+        )));
+    }
+    Ok(())
+}
+
+impl Protocol {
+    /// Parse a protocol in TOML format.
+    pub fn from_toml(buf: &str) -> Result<Self> {
+        let protocol: Self = toml::from_str(buf)?;
+        protocol.validate()?;
+        Ok(protocol)
+    }
+
+    /// Parse a protocol in YAML format.
+    pub fn from_yaml(buf: &str) -> Result<Self> {
+        let protocol: Self = serde_yaml::from_str(buf)?;
+        protocol.validate()?;
+        Ok(protocol)
+    }
+
+    /// Read a protocol file. The format is determined by the extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let buf = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&buf),
+            Some("yaml") | Some("yml") => Self::from_yaml(&buf),
+            _ => Err(Error::UnknownExtension(path.display().to_string())),
+        }
+    }
+
+    /// Check the protocol for errors which can be found before running it.
+    pub fn validate(&self) -> Result<()> {
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Some(at) = step.at {
+                check_seconds(index, "at", at)?;
+            }
+            match &step.action {
+                Action::LedBox {
+                    channel, intensity, ..
+                } => {
+                    if !(1..=4).contains(channel) {
+                        return Err(Error::InvalidProtocol(format!(
+                            "step {index}: LED box channel must be 1-4, not {channel}"
+                        )));
+                    }
+                    if intensity.unwrap_or(0) > strand_led_box_comms::MAX_INTENSITY {
+                        return Err(Error::InvalidProtocol(format!(
+                            "step {index}: LED intensity must be at most {}",
+                            strand_led_box_comms::MAX_INTENSITY
+                        )));
+                    }
+                    if self.led_box_device.is_none() {
+                        return Err(Error::InvalidProtocol(format!(
+                            "step {index} uses the LED box but no `led_box_device` is set"
+                        )));
+                    }
+                }
+                Action::Wait { duration } => check_seconds(index, "duration", *duration)?,
+                Action::WaitForObject {
+                    timeout: Some(timeout),
+                } => check_seconds(index, "timeout", *timeout)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn uses_led_box(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|s| matches!(s.action, Action::LedBox { .. }))
+    }
+}
+
+/// A stand-in for Braid which records the messages sent to it.
+///
+/// Like Braid, it keeps textlog messages only while recording.
+#[derive(Debug, Clone, Default)]
+pub struct MockMainbrain {
+    messages: Vec<BraidHttpApiCallback>,
+    textlog: Vec<String>,
+    is_recording: bool,
+}
+
+impl MockMainbrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All messages received.
+    pub fn messages(&self) -> &[BraidHttpApiCallback] {
+        &self.messages
+    }
+
+    /// The messages which would have been saved in `textlog.csv`.
+    pub fn textlog(&self) -> &[String] {
+        &self.textlog
+    }
+
+    fn handle(&mut self, msg: BraidHttpApiCallback) {
+        info!("dry run: {msg:?}");
+        match &msg {
+            BraidHttpApiCallback::DoRecordCsvTables(value) => self.is_recording = *value,
+            BraidHttpApiCallback::LogMessage(message) if self.is_recording => {
+                self.textlog.push(message.clone());
+            }
+            _ => {}
+        }
+        self.messages.push(msg);
+    }
+}
+
+/// Where the messages of a protocol are sent.
+#[derive(Debug)]
+pub enum Target {
+    /// A running Braid.
+    Mainbrain(MainbrainSession),
+    /// Dry run.
+    Mock(MockMainbrain),
+}
+
+impl Target {
+    /// Open a session with Braid at `url` (as printed by Braid on startup,
+    /// including the token).
+    pub async fn connect(url: &str) -> Result<Self> {
+        let addr_info = BuiServerAddrInfo::parse_url_with_token(url)
+            .map_err(|_| Error::InvalidUrl(url.to_string()))?;
+        let jar = Arc::new(RwLock::new(cookie_store::CookieStore::new(None)));
+        let session = braid_http_session::create_mainbrain_session(addr_info, jar).await?;
+        Ok(Target::Mainbrain(session))
+    }
+
+    async fn send(&mut self, msg: BraidHttpApiCallback) -> Result<()> {
+        match self {
+            Target::Mainbrain(session) => session.post_callback_message(msg).await?,
+            Target::Mock(mock) => mock.handle(msg),
+        }
+        Ok(())
+    }
+
+    fn is_dry_run(&self) -> bool {
+        matches!(self, Target::Mock(_))
+    }
+}
+
+/// Time since the start of the protocol.
+enum Clock {
+    Real(tokio::time::Instant),
+    /// In a dry run, waiting only advances the time.
+    Virtual(Duration),
+}
+
+impl Clock {
+    fn elapsed(&self) -> Duration {
+        match self {
+            Clock::Real(start) => start.elapsed(),
+            Clock::Virtual(now) => *now,
+        }
+    }
+
+    async fn sleep_until(&mut self, t: Duration) {
+        match self {
+            Clock::Real(start) => tokio::time::sleep_until(*start + t).await,
+            Clock::Virtual(now) => *now = (*now).max(t),
+        }
+    }
+}
+
+/// The log entry of a step, saved as JSON in the textlog.
+#[derive(Debug, Serialize)]
+struct StepLog<'a> {
+    protocol: Option<&'a str>,
+    index: usize,
+    elapsed: f64,
+    action: &'a Action,
+}
+
+/// Executes protocols.
+pub struct ProtocolRunner {
+    target: Target,
+    pose_client: Option<PoseClient>,
+    led_box: Option<LedBox>,
+}
+
+impl ProtocolRunner {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            pose_client: None,
+            led_box: None,
+        }
+    }
+
+    /// The target, e.g. to inspect a [MockMainbrain] after a dry run.
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+
+    /// Run the protocol. Returns at the first failing step.
+    pub async fn run(&mut self, protocol: &Protocol) -> Result<()> {
+        protocol.validate()?;
+        let dry_run = self.target.is_dry_run();
+
+        if !dry_run && protocol.uses_led_box() && self.led_box.is_none() {
+            // `validate()` ensures the device is set.
+            let device = protocol.led_box_device.as_deref().unwrap();
+            self.led_box = Some(LedBox::open(device).await?);
+        }
+
+        let mut clock = if dry_run {
+            Clock::Virtual(Duration::ZERO)
+        } else {
+            Clock::Real(tokio::time::Instant::now())
+        };
+
+        info!(
+            "running protocol {:?} with {} steps{}",
+            protocol.name.as_deref().unwrap_or_default(),
+            protocol.steps.len(),
+            if dry_run { " (dry run)" } else { "" }
+        );
+
+        for (index, step) in protocol.steps.iter().enumerate() {
+            if let Some(at) = step.at {
+                clock.sleep_until(Duration::from_secs_f64(at)).await;
+            }
+            let action = resolve(&step.action);
+            info!(
+                "step {index} at {:.3} s: {action:?}",
+                clock.elapsed().as_secs_f64()
+            );
+
+            // Steps are logged when they are done, except stopping the
+            // recording because the textlog is closed afterwards.
+            let log_first = matches!(action, Action::StopRecording);
+            if log_first {
+                self.log_step(protocol, index, clock.elapsed(), &action)
+                    .await?;
+            }
+            self.perform(protocol, index, &action, &mut clock).await?;
+            if !log_first {
+                self.log_step(protocol, index, clock.elapsed(), &action)
+                    .await?;
+            }
+        }
+        info!("protocol done");
+        Ok(())
+    }
+
+    async fn log_step(
+        &mut self,
+        protocol: &Protocol,
+        index: usize,
+        elapsed: Duration,
+        action: &Action,
+    ) -> Result<()> {
+        let entry = StepLog {
+            protocol: protocol.name.as_deref(),
+            index,
+            elapsed: elapsed.as_secs_f64(),
+            action,
+        };
+        let message = serde_json::json!({ "protocol_step": entry }).to_string();
+        self.target
+            .send(BraidHttpApiCallback::LogMessage(message))
+            .await
+    }
+
+    async fn perform(
+        &mut self,
+        protocol: &Protocol,
+        index: usize,
+        action: &Action,
+        clock: &mut Clock,
+    ) -> Result<()> {
+        use BraidHttpApiCallback as Cb;
+        match action {
+            Action::StartRecording => self.target.send(Cb::DoRecordCsvTables(true)).await?,
+            Action::StopRecording => self.target.send(Cb::DoRecordCsvTables(false)).await?,
+            Action::StartMp4Recording => self.target.send(Cb::DoRecordMp4Files(true)).await?,
+            Action::StopMp4Recording => self.target.send(Cb::DoRecordMp4Files(false)).await?,
+            Action::SetExperimentUuid { uuid } => {
+                // `resolve()` ensures the UUID is set.
+                let uuid = uuid.clone().unwrap();
+                self.target.send(Cb::SetExperimentUuid(uuid)).await?;
+            }
+            Action::SetPostTriggerBufferSize { frames } => {
+                self.target
+                    .send(Cb::SetPostTriggerBufferSize(*frames))
+                    .await?
+            }
+            Action::PostTriggerMp4Recording => {
+                self.target.send(Cb::PostTriggerMp4Recording).await?
+            }
+            Action::LedBox {
+                channel,
+                on,
+                intensity,
+            } => {
+                if let Some(led_box) = self.led_box.as_mut() {
+                    led_box.set_channel(*channel, *on, *intensity).await?;
+                } else {
+                    info!("dry run: LED box channel {channel} on: {on}");
+                }
+            }
+            Action::Wait { duration } => {
+                let until = clock.elapsed() + Duration::from_secs_f64(*duration);
+                clock.sleep_until(until).await;
+            }
+            Action::WaitForObject { timeout } => {
+                if self.target.is_dry_run() {
+                    info!("dry run: assuming an object is tracked");
+                    return Ok(());
+                }
+                let pose_url = protocol.pose_url.as_deref().unwrap_or(DEFAULT_POSE_URL);
+                if self.pose_client.is_none() {
+                    self.pose_client = Some(PoseClient::new(pose_url)?);
+                }
+                let client = self.pose_client.as_mut().unwrap();
+                let wait = wait_for_object(client);
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(Duration::from_secs_f64(*timeout), wait)
+                        .await
+                        .map_err(|_| Error::Timeout {
+                            index,
+                            action: action.name().to_string(),
+                        })??,
+                    None => wait.await?,
+                }
+            }
+            Action::Log { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Fill in values which are chosen when the step is performed.
+fn resolve(action: &Action) -> Action {
+    match action {
+        Action::SetExperimentUuid { uuid: None } => Action::SetExperimentUuid {
+            uuid: Some(uuid::Uuid::new_v4().simple().to_string()),
+        },
+        action => action.clone(),
+    }
+}
+
+async fn wait_for_object(client: &mut PoseClient) -> Result<()> {
+    loop {
+        if !client.live_objects().is_empty() {
+            return Ok(());
+        }
+        match client.next_event().await? {
+            PoseEvent::Connected => debug!("connected to model server"),
+            PoseEvent::Disconnected => debug!("disconnected from model server"),
+            PoseEvent::Message(msg) => {
+                if let SendType::Birth(row) = msg.msg {
+                    debug!("object {} born", row.obj_id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TOML_PROTOCOL: &str = r#"
+name = "test"
+led_box_device = "/dev/null"
+
+[[steps]]
+action = { type = "StartRecording" }
+
+[[steps]]
+action = { type = "SetExperimentUuid", uuid = "abc" }
+
+[[steps]]
+action = { type = "Wait", duration = 2 }
+
+[[steps]]
+action = { type = "LedBox", channel = 2, on = true }
+
+[[steps]]
+at = 10.0
+action = { type = "PostTriggerMp4Recording" }
+
+[[steps]]
+action = { type = "StopRecording" }
+"#;
+
+    const YAML_PROTOCOL: &str = r#"
+name: test
+led_box_device: /dev/null
+steps:
+  - action: { type: StartRecording }
+  - action: { type: SetExperimentUuid, uuid: abc }
+  - action: { type: Wait, duration: 2 }
+  - action: { type: LedBox, channel: 2, on: true }
+  - at: 10.0
+    action: { type: PostTriggerMp4Recording }
+  - action: { type: StopRecording }
+"#;
+
+    #[test]
+    fn test_parse() {
+        let protocol = Protocol::from_toml(TOML_PROTOCOL).unwrap();
+        assert_eq!(protocol, Protocol::from_yaml(YAML_PROTOCOL).unwrap());
+        assert_eq!(protocol.steps.len(), 6);
+        assert_eq!(protocol.steps[4].at, Some(10.0));
+        assert_eq!(
+            protocol.steps[3].action,
+            Action::LedBox {
+                channel: 2,
+                on: true,
+                intensity: None
+            }
+        );
+
+        let bad = TOML_PROTOCOL.replace("channel = 2", "channel = 5");
+        assert!(matches!(
+            Protocol::from_toml(&bad),
+            Err(Error::InvalidProtocol(_))
+        ));
+        let bad = TOML_PROTOCOL.replace("led_box_device = \"/dev/null\"", "");
+        assert!(matches!(
+            Protocol::from_toml(&bad),
+            Err(Error::InvalidProtocol(_))
+        ));
+        let bad = TOML_PROTOCOL.replace("duration = 2", "duration = -2");
+        assert!(matches!(
+            Protocol::from_toml(&bad),
+            Err(Error::InvalidProtocol(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let protocol = Protocol::from_toml(TOML_PROTOCOL).unwrap();
+        let mut runner = ProtocolRunner::new(Target::Mock(MockMainbrain::new()));
+        runner.run(&protocol).await.unwrap();
+        let Target::Mock(mock) = runner.target() else {
+            panic!("expected mock");
+        };
+
+        let sent: Vec<_> = mock
+            .messages()
+            .iter()
+            .filter(|m| !matches!(m, BraidHttpApiCallback::LogMessage(_)))
+            .map(|m| format!("{m:?}"))
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                "DoRecordCsvTables(true)",
+                "SetExperimentUuid(\"abc\")",
+                "PostTriggerMp4Recording",
+                "DoRecordCsvTables(false)",
+            ]
+        );
+
+        // Every step is in the textlog, with the time it was done.
+        let textlog: Vec<serde_json::Value> = mock
+            .textlog()
+            .iter()
+            .map(|m| serde_json::from_str(m).unwrap())
+            .collect();
+        assert_eq!(textlog.len(), 6);
+        let elapsed: Vec<f64> = textlog
+            .iter()
+            .map(|v| v["protocol_step"]["elapsed"].as_f64().unwrap())
+            .collect();
+        assert_eq!(elapsed, vec![0.0, 0.0, 2.0, 2.0, 10.0, 10.0]);
+        assert_eq!(textlog[3]["protocol_step"]["action"]["type"], "LedBox");
+        assert_eq!(textlog[5]["protocol_step"]["index"], 5);
+    }
+
+    #[tokio::test]
+    async fn test_random_uuid() {
+        let protocol = Protocol::from_yaml(
+            "steps:\n  - action: { type: StartRecording }\n  - action: { type: SetExperimentUuid }\n",
+        )
+        .unwrap();
+        let mut runner = ProtocolRunner::new(Target::Mock(MockMainbrain::new()));
+        runner.run(&protocol).await.unwrap();
+        let Target::Mock(mock) = runner.target() else {
+            panic!("expected mock");
+        };
+        let BraidHttpApiCallback::SetExperimentUuid(uuid) = &mock.messages()[2] else {
+            panic!("expected uuid, got {:?}", mock.messages()[2]);
+        };
+        assert_eq!(uuid.len(), 32);
+        // The chosen UUID is logged.
+        assert!(mock.textlog()[1].contains(uuid.as_str()));
+    }
+}
@@ -11,7 +11,8 @@ use wasm_bindgen_futures::JsFuture;
 use web_sys::{EventSource, MessageEvent};
 
 use braid_types::{
-    BraidHttpApiCallback, BraidHttpApiSharedState, BuiServerInfo, CamInfo, TriggerType,
+    BraidHttpApiCallback, BraidHttpApiSharedState, BuiServerInfo, CalibrationDriftAlert, CamInfo,
+    TrackingParams, TriggerType,
 };
 use strand_cam_bui_types::RecordingPath;
 
@@ -49,9 +50,78 @@ struct Model {
     recording_path: Option<RecordingPath>,
     fake_mp4_recording_path: Option<RecordingPath>,
     post_trigger_buffer_size_local: TypedInputStorage<usize>,
+    tracking_params_local: TrackingParamsStorage,
     _listeners: Vec<EventListener>,
 }
 
+/// Local values of the tracking parameters editable in the frontend.
+struct TrackingParamsStorage {
+    motion_noise_scale: TypedInputStorage<f64>,
+    initial_position_std_meters: TypedInputStorage<f64>,
+    initial_vel_std_meters_per_sec: TypedInputStorage<f64>,
+    ekf_observation_covariance_pixels: TypedInputStorage<f64>,
+    accept_observation_min_likelihood: TypedInputStorage<f64>,
+    max_position_std_meters: TypedInputStorage<f32>,
+    num_observations_to_visibility: TypedInputStorage<u8>,
+}
+
+impl TrackingParamsStorage {
+    fn empty() -> Self {
+        Self {
+            motion_noise_scale: TypedInputStorage::empty(),
+            initial_position_std_meters: TypedInputStorage::empty(),
+            initial_vel_std_meters_per_sec: TypedInputStorage::empty(),
+            ekf_observation_covariance_pixels: TypedInputStorage::empty(),
+            accept_observation_min_likelihood: TypedInputStorage::empty(),
+            max_position_std_meters: TypedInputStorage::empty(),
+            num_observations_to_visibility: TypedInputStorage::empty(),
+        }
+    }
+
+    fn set_if_not_focused(&mut self, tp: &TrackingParams) {
+        self.motion_noise_scale
+            .set_if_not_focused(tp.motion_noise_scale);
+        self.initial_position_std_meters
+            .set_if_not_focused(tp.initial_position_std_meters);
+        self.initial_vel_std_meters_per_sec
+            .set_if_not_focused(tp.initial_vel_std_meters_per_sec);
+        self.ekf_observation_covariance_pixels
+            .set_if_not_focused(tp.ekf_observation_covariance_pixels);
+        self.accept_observation_min_likelihood
+            .set_if_not_focused(tp.accept_observation_min_likelihood);
+        self.max_position_std_meters
+            .set_if_not_focused(tp.max_position_std_meters);
+        self.num_observations_to_visibility
+            .set_if_not_focused(tp.num_observations_to_visibility);
+    }
+}
+
+/// A change to a single tracking parameter.
+enum TrackingParamEdit {
+    MotionNoiseScale(f64),
+    InitialPositionStdMeters(f64),
+    InitialVelStdMetersPerSec(f64),
+    EkfObservationCovariancePixels(f64),
+    AcceptObservationMinLikelihood(f64),
+    MaxPositionStdMeters(f32),
+    NumObservationsToVisibility(u8),
+}
+
+impl TrackingParamEdit {
+    fn apply(self, tp: &mut TrackingParams) {
+        use TrackingParamEdit::*;
+        match self {
+            MotionNoiseScale(v) => tp.motion_noise_scale = v,
+            InitialPositionStdMeters(v) => tp.initial_position_std_meters = v,
+            InitialVelStdMetersPerSec(v) => tp.initial_vel_std_meters_per_sec = v,
+            EkfObservationCovariancePixels(v) => tp.ekf_observation_covariance_pixels = v,
+            AcceptObservationMinLikelihood(v) => tp.accept_observation_min_likelihood = v,
+            MaxPositionStdMeters(v) => tp.max_position_std_meters = v,
+            NumObservationsToVisibility(v) => tp.num_observations_to_visibility = v,
+        }
+    }
+}
+
 // -----------------------------------------------------------------------------
 
 enum Msg {
@@ -62,6 +132,7 @@ enum Msg {
     SendMessageFetchState(FetchState),
     SetPostTriggerBufferSize(usize),
     PostTriggerMp4Recording,
+    EditTrackingParam(TrackingParamEdit),
     RenderView,
 }
 
@@ -137,6 +208,7 @@ impl Component for Model {
             recording_path: None,
             fake_mp4_recording_path: None,
             post_trigger_buffer_size_local: TypedInputStorage::empty(),
+            tracking_params_local: TrackingParamsStorage::empty(),
             _listeners,
         }
     }
@@ -158,6 +230,8 @@ impl Component for Model {
 
                 self.post_trigger_buffer_size_local
                     .set_if_not_focused(data_result.post_trigger_buffer_size);
+                self.tracking_params_local
+                    .set_if_not_focused(&data_result.tracking_params);
 
                 self.shared = Some(data_result);
 
@@ -197,6 +271,17 @@ impl Component for Model {
             Msg::PostTriggerMp4Recording => {
                 return self.send_to_all_cams(ctx, BraidHttpApiCallback::PostTriggerMp4Recording);
             }
+            Msg::EditTrackingParam(edit) => {
+                if let Some(shared) = &self.shared {
+                    let mut tracking_params = shared.tracking_params.clone();
+                    edit.apply(&mut tracking_params);
+                    return self.send_to_all_cams(
+                        ctx,
+                        BraidHttpApiCallback::SetTrackingParams(tracking_params),
+                    );
+                }
+                return false;
+            }
         }
         true
     }
@@ -264,6 +349,75 @@ impl Model {
         }
     }
 
+    fn view_tracking_params(&self, ctx: &Context<Self>) -> Html {
+        use TrackingParamEdit::*;
+        let local = &self.tracking_params_local;
+        html! {
+            <div class="wrap-collapsible">
+                <CheckboxLabel label="Tracking Parameters" initially_checked=false />
+                <div>
+                    <p>{"Changes take effect immediately in the running tracker and are logged in the saved data."}</p>
+                    <div>
+                        <label>{"motion_noise_scale "}
+                            <TypedInput<f64>
+                                storage={local.motion_noise_scale.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(MotionNoiseScale(v)))}
+                                />
+                        </label>
+                    </div>
+                    <div>
+                        <label>{"initial_position_std_meters "}
+                            <TypedInput<f64>
+                                storage={local.initial_position_std_meters.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(InitialPositionStdMeters(v)))}
+                                />
+                        </label>
+                    </div>
+                    <div>
+                        <label>{"initial_vel_std_meters_per_sec "}
+                            <TypedInput<f64>
+                                storage={local.initial_vel_std_meters_per_sec.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(InitialVelStdMetersPerSec(v)))}
+                                />
+                        </label>
+                    </div>
+                    <div>
+                        <label>{"ekf_observation_covariance_pixels "}
+                            <TypedInput<f64>
+                                storage={local.ekf_observation_covariance_pixels.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(EkfObservationCovariancePixels(v)))}
+                                />
+                        </label>
+                    </div>
+                    <div>
+                        <label>{"accept_observation_min_likelihood "}
+                            <TypedInput<f64>
+                                storage={local.accept_observation_min_likelihood.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(AcceptObservationMinLikelihood(v)))}
+                                />
+                        </label>
+                    </div>
+                    <div>
+                        <label>{"max_position_std_meters "}
+                            <TypedInput<f32>
+                                storage={local.max_position_std_meters.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(MaxPositionStdMeters(v)))}
+                                />
+                        </label>
+                    </div>
+                    <div>
+                        <label>{"num_observations_to_visibility "}
+                            <TypedInput<u8>
+                                storage={local.num_observations_to_visibility.clone()}
+                                on_send_valid={ctx.link().callback(|v| Msg::EditTrackingParam(NumObservationsToVisibility(v)))}
+                                />
+                        </label>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
     fn view_shared(&self, ctx: &Context<Self>) -> Html {
         if let Some(ref value) = self.shared {
             let clock_model_ready = if value.needs_clock_model {
@@ -312,8 +466,10 @@ impl Model {
             html! {
                 <div>
                     {fake_sync_warning}
+                    {view_calibration_drift(&value.calibration_drift)}
                     <div>
                         {record_widget}
+                        {self.view_tracking_params(ctx)}
                         {view_clock_model(&value)}
                         {view_calibration(&value.calibration_filename)}
                         {view_cam_list(&value.connected_cameras)}
@@ -374,6 +530,43 @@ fn view_clock_model(shared: &BraidHttpApiSharedState) -> Html {
     }
 }
 
+fn view_calibration_drift(alerts: &[CalibrationDriftAlert]) -> Html {
+    let all_rendered: Vec<Html> = alerts
+        .iter()
+        .map(|alert| {
+            let corrected = if let Some(corrected) = &alert.corrected_extrinsics {
+                let saved = match &corrected.calibration_filename {
+                    Some(fname) => format!(" Corrected calibration saved to {fname}."),
+                    None => String::new(),
+                };
+                format!(
+                    " Estimated movement: {:.4} and {:.2}°.{saved}",
+                    corrected.camcenter_shift, corrected.rotation_degrees
+                )
+            } else {
+                String::new()
+            };
+            html! {
+                <div>
+                    {format!(
+                        "⚠ Camera {} may have moved: median reprojection distance increased \
+                        from {:.2} to {:.2} pixels at frame {}.{corrected} ⚠",
+                        alert.cam_name,
+                        alert.baseline_median_reproj_dist,
+                        alert.recent_median_reproj_dist,
+                        alert.frame,
+                    )}
+                </div>
+            }
+        })
+        .collect();
+    html! {
+        <>
+            {all_rendered}
+        </>
+    }
+}
+
 fn view_calibration(calibration_filename: &Option<String>) -> Html {
     if let Some(ref fname) = calibration_filename {
         html! {
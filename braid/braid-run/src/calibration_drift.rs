@@ -0,0 +1,217 @@
+//! Alert on calibration drift, for example from a bumped camera.
+//!
+//! Drift is detected by [flydra2::DriftDetector]. This module logs each change
+//! to the braidz textlog and keeps the cameras with drift in the shared state,
+//! from where they are sent on the event stream and shown in the frontend. If
+//! configured, the pose of a camera with drift is estimated and a corrected
+//! calibration is saved in the output directory.
+
+use std::path::PathBuf;
+
+use tokio::sync::mpsc::{Receiver, WeakSender};
+use tracing::{debug, error, info, warn};
+
+use braid_mvg::{Camera, MultiCameraSystem};
+use braid_types::{CalibrationDriftAlert, CalibrationDriftConfig, CorrectedExtrinsics, TextlogRow};
+use flydra2::{AssociatedObservation, DriftDetector, DriftEvent, SaveToDiskMsg};
+use flydra_mvg::FlydraMultiCameraSystem;
+
+use eyre::Result;
+
+use crate::mainbrain::SharedStore;
+
+pub(crate) struct CalibrationDriftMonitor {
+    detector: DriftDetector,
+    /// The calibration, if the pose of cameras with drift is estimated.
+    recon: Option<FlydraMultiCameraSystem<f64>>,
+    output_base_dirname: PathBuf,
+    shared_store: SharedStore,
+    braidz_write_tx_weak: WeakSender<SaveToDiskMsg>,
+}
+
+impl CalibrationDriftMonitor {
+    pub(crate) fn new(
+        cfg: &CalibrationDriftConfig,
+        recon: Option<&FlydraMultiCameraSystem<f64>>,
+        output_base_dirname: PathBuf,
+        shared_store: SharedStore,
+        braidz_write_tx_weak: WeakSender<SaveToDiskMsg>,
+    ) -> Self {
+        let recon = match recon {
+            Some(recon) if cfg.estimate_extrinsics && recon.has_refractive_boundary() => {
+                warn!(
+                    "Camera poses are not estimated on calibration drift with refractive \
+                    boundaries."
+                );
+                None
+            }
+            Some(recon) if cfg.estimate_extrinsics => Some(recon.clone()),
+            _ => None,
+        };
+        Self {
+            detector: DriftDetector::new(cfg),
+            recon,
+            output_base_dirname,
+            shared_store,
+            braidz_write_tx_weak,
+        }
+    }
+
+    /// Check the observations for drift until `data_rx` is closed.
+    pub(crate) async fn run(mut self, mut data_rx: Receiver<Vec<AssociatedObservation>>) {
+        while let Some(observations) = data_rx.recv().await {
+            for event in self.detector.process(&observations) {
+                match event {
+                    DriftEvent::Detected {
+                        mut alert,
+                        observations,
+                    } => {
+                        warn!(
+                            "Calibration drift of camera \"{}\" at frame {}: median \
+                            reprojection distance increased from {:.2} to {:.2} pixels.",
+                            alert.cam_name,
+                            alert.frame,
+                            alert.baseline_median_reproj_dist,
+                            alert.recent_median_reproj_dist
+                        );
+                        alert.corrected_extrinsics = self.correct(&alert, observations).await;
+                        self.log(&alert, "calibration_drift_detected").await;
+                        {
+                            let mut tracker = self.shared_store.write().unwrap();
+                            tracker.modify(|shared| {
+                                shared
+                                    .calibration_drift
+                                    .retain(|a| a.cam_name != alert.cam_name);
+                                shared.calibration_drift.push(alert);
+                            });
+                        }
+                    }
+                    DriftEvent::Resolved(alert) => {
+                        info!(
+                            "Calibration drift of camera \"{}\" resolved at frame {}.",
+                            alert.cam_name, alert.frame
+                        );
+                        self.log(&alert, "calibration_drift_resolved").await;
+                        {
+                            let mut tracker = self.shared_store.write().unwrap();
+                            tracker.modify(|shared| {
+                                shared
+                                    .calibration_drift
+                                    .retain(|a| a.cam_name != alert.cam_name)
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        debug!("calibration drift monitor done");
+    }
+
+    async fn log(&self, alert: &CalibrationDriftAlert, key: &str) {
+        if let Some(braidz_write_tx) = self.braidz_write_tx_weak.upgrade() {
+            let message = serde_json::json!({ key: alert }).to_string();
+            let mainbrain_timestamp =
+                strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+            let row = TextlogRow {
+                mainbrain_timestamp,
+                cam_id: alert.cam_name.as_str().to_string(),
+                host_timestamp: mainbrain_timestamp,
+                message,
+            };
+            // Ignore errors: the writer may be shutting down.
+            let _ = braidz_write_tx.send(SaveToDiskMsg::Textlog(row)).await;
+        }
+    }
+
+    /// Estimate the pose of the camera and save the corrected calibration.
+    async fn correct(
+        &self,
+        alert: &CalibrationDriftAlert,
+        observations: Vec<AssociatedObservation>,
+    ) -> Option<CorrectedExtrinsics> {
+        let recon = self.recon.as_ref()?;
+        let cam = recon.system().cam_by_name(alert.cam_name.as_str())?.clone();
+        let cam2 = cam.clone();
+        let result =
+            tokio::task::spawn_blocking(move || flydra2::estimate_extrinsics(&cam2, &observations))
+                .await;
+        let (estimated, median_reproj_dist) = match result {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => {
+                warn!(
+                    "Could not estimate pose of camera \"{}\": {e}",
+                    alert.cam_name
+                );
+                return None;
+            }
+            Err(e) => {
+                warn!(
+                    "Pose estimation of camera \"{}\" failed: {e}",
+                    alert.cam_name
+                );
+                return None;
+            }
+        };
+
+        let camcenter = *estimated.extrinsics().camcenter();
+        let camcenter_shift = (camcenter - cam.extrinsics().camcenter()).norm();
+        let rotation_degrees = cam
+            .extrinsics()
+            .rotation()
+            .rotation_to(estimated.extrinsics().rotation())
+            .angle()
+            .to_degrees();
+        info!(
+            "Estimated pose of camera \"{}\": moved by {camcenter_shift:.4} and rotated by \
+            {rotation_degrees:.2}°, median reprojection distance {median_reproj_dist:.2} pixels.",
+            alert.cam_name
+        );
+
+        let calibration_filename = match self.save_calibration(recon, alert, estimated) {
+            Ok(path) => {
+                info!("Saved corrected calibration to \"{}\".", path.display());
+                Some(path.display().to_string())
+            }
+            Err(e) => {
+                error!("Could not save corrected calibration: {e:?}");
+                None
+            }
+        };
+
+        Some(CorrectedExtrinsics {
+            camcenter: [camcenter.x, camcenter.y, camcenter.z],
+            camcenter_shift,
+            rotation_degrees,
+            median_reproj_dist,
+            calibration_filename,
+        })
+    }
+
+    fn save_calibration(
+        &self,
+        recon: &FlydraMultiCameraSystem<f64>,
+        alert: &CalibrationDriftAlert,
+        estimated: Camera<f64>,
+    ) -> Result<PathBuf> {
+        // Replace only the camera with drift, keeping the water, refractive
+        // interfaces and time offsets of the calibration.
+        let mut cams = recon.system().cams_by_name().clone();
+        cams.insert(alert.cam_name.as_str().to_string(), estimated);
+        let system = match recon.system().comment() {
+            Some(comment) => MultiCameraSystem::new_with_comment(cams, comment.clone()),
+            None => MultiCameraSystem::new(cams),
+        };
+        let corrected = FlydraMultiCameraSystem::from_system(system, recon.water())
+            .with_refractive_interfaces(recon.refractive_interfaces().to_vec())
+            .with_time_offsets(recon.time_offsets().clone());
+
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let cam_name = alert.cam_name.as_str().replace(['/', '\\'], "_");
+        let path = self
+            .output_base_dirname
+            .join(format!("calibration-drift-{stamp}-{cam_name}.xml"));
+        let fd = std::fs::File::create(&path)?;
+        corrected.to_flydra_xml(fd)?;
+        Ok(path)
+    }
+}
@@ -34,6 +34,11 @@ pub(crate) async fn callback_handler(
                 let cam_settings_data = cam_info.cam_settings_data.unwrap();
                 let camera_periodic_signal_period_usec =
                     cam_info.camera_periodic_signal_period_usec;
+                // If the camera connects again (e.g. because its Strand
+                // Camera was restarted), its old session is invalid.
+                app_state
+                    .strand_cam_http_session_handler
+                    .forget_session(&cam_info.raw_cam_name);
                 let mut cam_manager3 = app_state.cam_manager.clone();
                 cam_manager3
                     .register_new_camera(
@@ -55,7 +60,10 @@ pub(crate) async fn callback_handler(
                     )
                     .is_some()
                 {
-                    panic!("camera {} already known", cam_info.raw_cam_name.as_str());
+                    debug!(
+                        "replaced data of reconnected camera {}",
+                        cam_info.raw_cam_name.as_str()
+                    );
                 }
             }
             UpdateCurrentImage(image_info) => {
@@ -168,6 +176,54 @@ pub(crate) async fn callback_handler(
                     debug!("Already saving, not initiating again.");
                 }
             }
+            SetTrackingParams(tracking_params) => {
+                debug!("got SetTrackingParams({tracking_params:?})");
+
+                {
+                    let tracker = app_state.shared_store.read().unwrap();
+                    (*tracker)
+                        .as_ref()
+                        .tracking_params
+                        .check_runtime_change(&tracking_params)
+                        .map_err(|msg| (StatusCode::BAD_REQUEST, msg))?;
+                }
+
+                app_state
+                    .tracking_params_tx
+                    .send(tracking_params.clone())
+                    .await
+                    .map_err(|_e| {
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "sending tracking parameters to tracker failed",
+                        )
+                    })?;
+
+                {
+                    let mut tracker = app_state.shared_store.write().unwrap();
+                    tracker.modify(|store| {
+                        store.tracking_params = tracking_params.clone();
+                    });
+                }
+            }
+            LogMessage(message) => {
+                debug!("got LogMessage({message})");
+                if let Some(braidz_write_tx) = app_state.braidz_write_tx_weak.upgrade() {
+                    let mainbrain_timestamp =
+                        strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+                    let row = braid_types::TextlogRow {
+                        mainbrain_timestamp,
+                        cam_id: "mainbrain".to_string(),
+                        host_timestamp: mainbrain_timestamp,
+                        message,
+                    };
+                    // `braidz_write_tx` will be dropped after this scope.
+                    braidz_write_tx
+                        .send(flydra2::SaveToDiskMsg::Textlog(row))
+                        .await
+                        .unwrap();
+                }
+            }
         }
         Ok::<_, (StatusCode, &'static str)>(())
     };
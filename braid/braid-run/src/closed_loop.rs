@@ -0,0 +1,149 @@
+//! Perform the actions of closed-loop rules.
+//!
+//! The rules themselves are evaluated by [flydra2::ClosedLoopEngine]. This
+//! module performs the resulting actions and logs each firing to the braidz
+//! textlog.
+
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{Receiver, Sender, WeakSender},
+};
+use tracing::{debug, error, info};
+
+use braid_led_box::LedBox;
+use braid_types::{ClosedLoopAction, ClosedLoopConfig, TextlogRow};
+use flydra2::{ClosedLoopEngine, RuleFiring, SaveToDiskMsg, SendType, TimeDataPassthrough};
+
+use eyre::{self, Result, WrapErr};
+
+pub(crate) struct ClosedLoopRunner {
+    engine: ClosedLoopEngine,
+    led_box: Option<LedBox>,
+    udp_socket: Option<UdpSocket>,
+    rule_firing_tx: Option<Sender<RuleFiring>>,
+    braidz_write_tx_weak: WeakSender<SaveToDiskMsg>,
+}
+
+impl ClosedLoopRunner {
+    /// Load the rules and open the outputs they need.
+    ///
+    /// If any rule sends to the event stream, also returns the receiver to
+    /// pass to the model server.
+    pub(crate) async fn new(
+        cfg: &ClosedLoopConfig,
+        braidz_write_tx_weak: WeakSender<SaveToDiskMsg>,
+    ) -> Result<(Self, Option<Receiver<RuleFiring>>)> {
+        let engine = ClosedLoopEngine::new(&cfg.rules)?;
+
+        let has_action = |f: fn(&ClosedLoopAction) -> bool| cfg.rules.iter().any(|r| f(&r.action));
+
+        let led_box = if has_action(|a| matches!(a, ClosedLoopAction::LedBox { .. })) {
+            let device = cfg.led_box_device.as_ref().ok_or_else(|| {
+                eyre::eyre!("closed-loop rules use the LED box but no `led_box_device` is set")
+            })?;
+            Some(LedBox::open(device).await?)
+        } else {
+            None
+        };
+
+        let udp_socket = if has_action(|a| matches!(a, ClosedLoopAction::Udp { .. })) {
+            Some(UdpSocket::bind("0.0.0.0:0").await?)
+        } else {
+            None
+        };
+
+        let (rule_firing_tx, rule_firing_rx) =
+            if has_action(|a| matches!(a, ClosedLoopAction::EventStream)) {
+                let (tx, rx) = tokio::sync::mpsc::channel(50);
+                (Some(tx), Some(rx))
+            } else {
+                (None, None)
+            };
+
+        info!("loaded {} closed-loop rule(s)", cfg.rules.len());
+
+        Ok((
+            Self {
+                engine,
+                led_box,
+                udp_socket,
+                rule_firing_tx,
+                braidz_write_tx_weak,
+            },
+            rule_firing_rx,
+        ))
+    }
+
+    /// Evaluate the rules on the tracking output until `data_rx` is closed.
+    pub(crate) async fn run(mut self, mut data_rx: Receiver<(SendType, TimeDataPassthrough)>) {
+        while let Some((msg, _tdpt)) = data_rx.recv().await {
+            for (firing, action) in self.engine.process(&msg) {
+                // Perform the action first: logging must not add latency.
+                if let Err(e) = self.perform(&firing, &action).await {
+                    error!("closed-loop rule \"{}\" failed: {e:?}", firing.rule);
+                }
+                self.log_firing(&firing);
+            }
+        }
+        debug!("closed-loop runner done");
+    }
+
+    fn log_firing(&self, firing: &RuleFiring) {
+        info!(
+            "closed-loop rule \"{}\" fired for obj_id {} at frame {}",
+            firing.rule, firing.obj_id, firing.frame
+        );
+        if let Some(braidz_write_tx) = self.braidz_write_tx_weak.upgrade() {
+            let message = serde_json::json!({ "closed_loop_rule_fired": firing }).to_string();
+            let mainbrain_timestamp =
+                strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+            let row = TextlogRow {
+                mainbrain_timestamp,
+                cam_id: "mainbrain".to_string(),
+                host_timestamp: mainbrain_timestamp,
+                message,
+            };
+            // Ignore errors: the writer is not keeping up or is shutting
+            // down.
+            let _ = braidz_write_tx.try_send(SaveToDiskMsg::Textlog(row));
+        }
+    }
+
+    async fn perform(&mut self, firing: &RuleFiring, action: &ClosedLoopAction) -> Result<()> {
+        match action {
+            ClosedLoopAction::LedBox {
+                channel,
+                on,
+                intensity,
+            } => {
+                self.led_box
+                    .as_mut()
+                    .unwrap()
+                    .set_channel(*channel, *on, *intensity)
+                    .await?;
+            }
+            ClosedLoopAction::Udp { addr } => {
+                let buf = serde_json::to_vec(firing)?;
+                self.udp_socket
+                    .as_ref()
+                    .unwrap()
+                    .send_to(&buf, addr.as_str())
+                    .await
+                    .with_context(|| format!("sending to {addr}"))?;
+            }
+            ClosedLoopAction::EventStream => {
+                // Drop the firing rather than block tracking if the model
+                // server is not keeping up.
+                if let Err(e) = self
+                    .rule_firing_tx
+                    .as_ref()
+                    .unwrap()
+                    .try_send(firing.clone())
+                {
+                    debug!("not sending rule firing to event stream: {e}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
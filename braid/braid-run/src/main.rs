@@ -7,8 +7,11 @@ use braid_config_data::parse_config_file;
 use braid_types::{BraidCameraConfig, RawCamName, StartCameraBackend, TriggerType};
 use strand_bui_backend_session_types::BuiServerAddrInfo;
 
+mod calibration_drift;
 mod callback_handling;
+mod closed_loop;
 mod mainbrain;
+mod metrics;
 mod multicam_http_session_handler;
 
 #[derive(Debug, Parser)]
@@ -131,7 +134,7 @@ async fn main() -> Result<()> {
             false,
             braid_types::StartSoftwareFrameRateLimit::Enable(cfg.framerate),
         ),
-        TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp => {
+        TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp(_) => {
             (false, braid_types::StartSoftwareFrameRateLimit::NoChange)
         }
     };
@@ -51,7 +51,11 @@ pub(crate) const APP_INFO: AppInfo = AppInfo {
 const COOKIE_SECRET_KEY: &str = "cookie-secret-base64";
 pub(crate) const STRAND_CAM_COOKIE_KEY: &str = "strand-cam-cookie";
 
-type SharedStore = Arc<RwLock<ChangeTracker<BraidHttpApiSharedState>>>;
+/// A camera from which no frame was received for this long is considered to
+/// have dropped out. Tracking continues without it until it rejoins.
+const CAMERA_DROPOUT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+pub(crate) type SharedStore = Arc<RwLock<ChangeTracker<BraidHttpApiSharedState>>>;
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum MainbrainError {
@@ -92,6 +96,9 @@ pub(crate) struct BraidAppState {
     pub(crate) cam_manager: flydra2::ConnectedCamerasManager,
     pub(crate) output_base_dirname: PathBuf,
     pub(crate) braidz_write_tx_weak: tokio::sync::mpsc::WeakSender<flydra2::SaveToDiskMsg>,
+    pub(crate) tracking_params_tx: tokio::sync::mpsc::Sender<braid_types::TrackingParams>,
+    pub(crate) live_stats_collector: LiveStatsCollector,
+    pub(crate) pose_metrics: crate::metrics::PoseMetrics,
 }
 
 async fn events_handler(
@@ -340,6 +347,7 @@ async fn launch_braid_http_backend(
             axum::routing::post(crate::callback_handling::callback_handler)
                 .layer(axum::extract::DefaultBodyLimit::max(100_000_000)),
         )
+        .route("/metrics", get(crate::metrics::metrics_handler))
         .fallback_service(serve_dir)
         .layer(
             tower::ServiceBuilder::new()
@@ -394,6 +402,7 @@ fn compute_trigger_timestamp(
 
 struct SendConnectedCamToBuiBackend {
     shared_store: SharedStore,
+    braidz_write_tx_weak: tokio::sync::mpsc::WeakSender<flydra2::SaveToDiskMsg>,
 }
 
 impl flydra2::ConnectedCamCallback for SendConnectedCamToBuiBackend {
@@ -401,6 +410,27 @@ impl flydra2::ConnectedCamCallback for SendConnectedCamToBuiBackend {
         let mut tracker = self.shared_store.write().unwrap();
         tracker.modify(|shared| shared.connected_cameras = new_cam_list.clone());
     }
+
+    fn on_cam_gap(&self, gap: &flydra2::CameraGap) {
+        // Mark the gap in the textlog of the recording, if any.
+        if let Some(braidz_write_tx) = self.braidz_write_tx_weak.upgrade() {
+            let message = serde_json::json!({ "camera_gap": gap }).to_string();
+            let mainbrain_timestamp =
+                strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+            let row = braid_types::TextlogRow {
+                mainbrain_timestamp,
+                cam_id: gap.raw_cam_name().as_str().to_string(),
+                host_timestamp: mainbrain_timestamp,
+                message,
+            };
+            tokio::spawn(async move {
+                // Ignore errors: the writer may be shutting down.
+                let _ = braidz_write_tx
+                    .send(flydra2::SaveToDiskMsg::Textlog(row))
+                    .await;
+            });
+        }
+    }
 }
 
 fn display_qr_url(url: &str) {
@@ -459,6 +489,7 @@ pub(crate) async fn do_run_forever(
 
     let save_empty_data2d: bool = mainbrain_config.save_empty_data2d;
     let write_buffer_size_num_messages = mainbrain_config.write_buffer_size_num_messages;
+    let resume_recording = mainbrain_config.resume_recording.clone();
 
     info!("saving to directory: {}", output_base_dirname.display());
 
@@ -519,7 +550,7 @@ pub(crate) async fn do_run_forever(
     let ignore_latency = false;
     let mut coord_processor = CoordProcessor::new(
         CoordProcessorConfig {
-            tracking_params,
+            tracking_params: tracking_params.clone(),
             save_empty_data2d,
             ignore_latency,
             mini_arena_debug_image_dir: None,
@@ -530,6 +561,17 @@ pub(crate) async fn do_run_forever(
         flydra2::BraidMetadataBuilder::saving_program_name(saving_program_name),
     )?;
 
+    if let Some(resume_recording) = &resume_recording {
+        let checkpoint =
+            flydra2::TrackingCheckpoint::load(resume_recording).with_context(|| {
+                format!(
+                    "loading tracking checkpoint to resume \"{}\"",
+                    resume_recording.display()
+                )
+            })?;
+        coord_processor.resume_from(checkpoint);
+    }
+
     // Here is what we do on quit:
     // 1) Stop saving data, convert .braid dir to .braidz, close files.
     // 2) Fire a DoQuit message to all cameras and wait for them to quit.
@@ -575,14 +617,14 @@ pub(crate) async fn do_run_forever(
             let (tx, rx) = tokio::sync::mpsc::channel(20);
             (Some(tx), Some(rx))
         }
-        TriggerType::FakeSync(_) | TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp => {
+        TriggerType::FakeSync(_) | TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp(_) => {
             (None, None)
         }
     };
 
     let needs_clock_model = match &trigger_cfg {
         TriggerType::TriggerboxV1(_) | TriggerType::FakeSync(_) => true,
-        TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp => false,
+        TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp(_) => false,
     };
 
     let sync_pulse_pause_started: Option<std::time::Instant> = None;
@@ -602,6 +644,8 @@ pub(crate) async fn do_run_forever(
         flydra_app_name,
         all_expected_cameras_are_synced: false,
         needs_clock_model,
+        tracking_params,
+        calibration_drift: Vec::new(),
     };
     let shared_store = ChangeTracker::new(shared);
     let mut shared_store_changes_rx = shared_store.get_changes(1);
@@ -656,6 +700,9 @@ pub(crate) async fn do_run_forever(
 
     let time_model_arc = Arc::new(RwLock::new(None));
 
+    let live_stats_collector = LiveStatsCollector::new(shared_store.clone());
+    let pose_metrics = crate::metrics::PoseMetrics::new();
+
     // Create our app state.
     let app_state = BraidAppState {
         shared_store: shared_store.clone(),
@@ -668,9 +715,12 @@ pub(crate) async fn do_run_forever(
         next_connection_id: Arc::new(RwLock::new(0)),
         expected_framerate_arc: expected_framerate_arc.clone(),
         braidz_write_tx_weak,
+        tracking_params_tx: coord_processor.tracking_params_sender(),
         cam_manager: cam_manager.clone(),
         output_base_dirname,
         strand_cam_http_session_handler: strand_cam_http_session_handler.clone(),
+        live_stats_collector: live_stats_collector.clone(),
+        pose_metrics: pose_metrics.clone(),
     };
 
     // This future will send state updates to all connected event listeners.
@@ -691,6 +741,7 @@ pub(crate) async fn do_run_forever(
     {
         let sender = SendConnectedCamToBuiBackend {
             shared_store: shared_store.clone(),
+            braidz_write_tx_weak: coord_processor.braidz_write_tx.downgrade(),
         };
         let old_callback = cam_manager.set_cam_changed_callback(Box::new(sender));
         assert!(old_callback.is_none());
@@ -780,7 +831,7 @@ pub(crate) async fn do_run_forever(
                         };
                     });
                 }
-                TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp => {
+                TriggerType::PtpSync(_) | TriggerType::DeviceTimestamp(_) => {
                     // no central clock model
                     panic!("No need for clock model.");
                 }
@@ -880,14 +931,18 @@ pub(crate) async fn do_run_forever(
                 *expected_framerate = Some(framerate as f32);
             }
         }
-        TriggerType::DeviceTimestamp => {
+        TriggerType::DeviceTimestamp(cfg) => {
             signal_triggerbox_connected.store(true, Ordering::SeqCst);
+
+            if let Some(framerate) = cfg.framerate {
+                let mut expected_framerate = expected_framerate_arc.write().unwrap();
+                *expected_framerate = Some(framerate as f32);
+            }
         }
     };
 
     let expected_framerate_arc9 = expected_framerate_arc.clone();
 
-    let live_stats_collector = LiveStatsCollector::new(tracker.clone());
     let tracker2 = tracker.clone();
 
     // decode UDP frames
@@ -930,6 +985,9 @@ pub(crate) async fn do_run_forever(
     // Signal cameras are synchronized
 
     let valve2 = valve.clone();
+    let expected_framerate_arc2 = expected_framerate_arc.clone();
+    let braidz_write_tx_weak2 = coord_processor.braidz_write_tx.downgrade();
+    let per_cam_data_arc2 = per_cam_data_arc.clone();
     let _sync_done_jh = tokio::spawn(async move {
         let interval_stream = tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(
             std::time::Duration::from_secs(1),
@@ -940,14 +998,43 @@ pub(crate) async fn do_run_forever(
             if sync_done {
                 info!("All cameras done synchronizing.");
 
-                // Send message to listeners.
-                let mut tracker = shared_store.write().unwrap();
-                tracker.modify(|shared| shared.all_expected_cameras_are_synced = true);
+                {
+                    // Send message to listeners.
+                    let mut tracker = shared_store.write().unwrap();
+                    tracker.modify(|shared| shared.all_expected_cameras_are_synced = true);
+                }
+
+                if let Some(resume_recording) = resume_recording {
+                    start_saving_csv_tables(
+                        resume_recording,
+                        chrono::Local::now(),
+                        true,
+                        expected_framerate_arc2,
+                        braidz_write_tx_weak2,
+                        per_cam_data_arc2,
+                        shared_store,
+                    )
+                    .await;
+                }
                 break;
             }
         }
     });
 
+    // Detect cameras which stopped sending frames.
+
+    let cam_manager2 = cam_manager.clone();
+    let valve2 = valve.clone();
+    let _dropout_jh = tokio::spawn(async move {
+        let interval_stream = tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(
+            std::time::Duration::from_millis(500),
+        ));
+        let mut interval_stream = valve2.wrap(interval_stream);
+        while let Some(_now) = interval_stream.next().await {
+            cam_manager2.check_for_dropouts(CAMERA_DROPOUT_TIMEOUT);
+        }
+    });
+
     let strand_cam_http_session_handler2 = strand_cam_http_session_handler.clone();
     let cam_manager2 = cam_manager.clone();
     let live_stats_collector2 = live_stats_collector.clone();
@@ -1002,11 +1089,13 @@ pub(crate) async fn do_run_forever(
                 tokio::spawn(fut_no_err);
             };
 
+            let clock_model = time_model_arc.read().unwrap().clone();
             let synced_frame = cam_manager2.got_new_frame_live(
                 &packet,
                 &sync_pulse_pause_started_arc,
                 send_new_frame_offset,
                 &trigger_cfg,
+                clock_model.as_ref(),
             );
 
             let cam_num = cam_manager.cam_num(&raw_cam_name);
@@ -1033,10 +1122,10 @@ pub(crate) async fn do_run_forever(
 
             let (synced_frame, trigger_timestamp) = match synced_frame {
                 Some(synced_frame) => {
+                    live_stats_collector2.register_synced_frame(&raw_cam_name, synced_frame);
                     let trigger_timestamp = match &trigger_cfg {
                         TriggerType::TriggerboxV1(_) | TriggerType::FakeSync(_) => {
-                            let time_model = time_model_arc.read().unwrap();
-                            compute_trigger_timestamp(&time_model, synced_frame)
+                            compute_trigger_timestamp(&clock_model, synced_frame)
                         }
                         TriggerType::PtpSync(_) => {
                             // In case where we trust camera sync data, use
@@ -1051,8 +1140,10 @@ pub(crate) async fn do_run_forever(
                                 device_timestamp_chrono.into()
                             })
                         }
-                        TriggerType::DeviceTimestamp => {
-                            todo!();
+                        TriggerType::DeviceTimestamp(_) => {
+                            // The camera computes the trigger timestamp from
+                            // its device timestamp using its own clock model.
+                            packet.timestamp.clone()
                         }
                     };
                     (synced_frame, trigger_timestamp)
@@ -1095,17 +1186,63 @@ pub(crate) async fn do_run_forever(
 
     let (data_tx, data_rx) = tokio::sync::mpsc::channel(50);
 
+    let rule_firing_rx = if mainbrain_config.closed_loop.is_empty() {
+        None
+    } else {
+        let (runner, rule_firing_rx) = crate::closed_loop::ClosedLoopRunner::new(
+            &mainbrain_config.closed_loop,
+            coord_processor.braidz_write_tx.downgrade(),
+        )
+        .await?;
+        let (closed_loop_tx, closed_loop_rx) = tokio::sync::mpsc::channel(50);
+        coord_processor.add_lossy_listener(closed_loop_tx);
+        tokio::spawn(runner.run(closed_loop_rx));
+        rule_firing_rx
+    };
+
     let model_pose_server_addr = mainbrain_config.model_server_addr;
-    tokio::spawn(flydra2::new_model_server(data_rx, model_pose_server_addr));
+    tokio::spawn(flydra2::new_model_server(
+        data_rx,
+        rule_firing_rx,
+        model_pose_server_addr,
+    ));
 
     {
         let mut tracker = tracker2.write().unwrap();
         tracker.modify(|shared| shared.model_server_addr = Some(model_pose_server_addr))
     }
 
+    if let Some(udp_addr) = mainbrain_config.model_server_udp_addr {
+        let udp_sender = flydra2::UdpPoseSender::new(udp_addr)
+            .await
+            .with_context(|| format!("binding socket to send pose to {udp_addr}"))?;
+        let (udp_tx, udp_rx) = tokio::sync::mpsc::channel(50);
+        coord_processor.add_listener(udp_tx);
+        tokio::spawn(udp_sender.run(udp_rx));
+    }
+
     let expected_framerate: Option<f32> = *expected_framerate_arc9.read().unwrap();
     info!("expected_framerate: {:?}", expected_framerate);
 
+    {
+        let (metrics_tx, metrics_rx) = tokio::sync::mpsc::channel(50);
+        coord_processor.add_listener(metrics_tx);
+        tokio::spawn(pose_metrics.run(metrics_rx));
+    }
+
+    if mainbrain_config.calibration_drift.enabled {
+        let monitor = crate::calibration_drift::CalibrationDriftMonitor::new(
+            &mainbrain_config.calibration_drift,
+            coord_processor.recon.as_ref(),
+            mainbrain_config.output_base_dirname.clone(),
+            tracker2.clone(),
+            coord_processor.braidz_write_tx.downgrade(),
+        );
+        let (drift_tx, drift_rx) = tokio::sync::mpsc::channel(100);
+        coord_processor.add_observation_listener(drift_tx);
+        tokio::spawn(monitor.run(drift_rx));
+    }
+
     coord_processor.add_listener(data_tx);
     let coord_proc_fut = coord_processor.consume_stream(flydra2_stream, expected_framerate);
 
@@ -1136,7 +1273,7 @@ pub(crate) async fn do_run_forever(
 }
 
 #[derive(Clone)]
-struct LiveStatsCollector {
+pub(crate) struct LiveStatsCollector {
     shared: SharedStore,
     collected: Arc<RwLock<BTreeMap<RawCamName, LiveStatsAccum>>>,
 }
@@ -1146,6 +1283,19 @@ struct LiveStatsAccum {
     start: std::time::Instant,
     n_frames: usize,
     n_points: usize,
+    metrics: CameraMetrics,
+    last_synced_frame: Option<SyncFno>,
+}
+
+/// Totals for a camera since it first sent data, served at `/metrics`.
+#[derive(Debug, Clone)]
+pub(crate) struct CameraMetrics {
+    pub(crate) total_frames: u64,
+    pub(crate) total_points: u64,
+    /// Frames missing in the sequence of synchronized frame numbers.
+    pub(crate) dropped_frames: u64,
+    /// Frame rate over the last collection interval.
+    pub(crate) fps: f64,
 }
 
 impl LiveStatsAccum {
@@ -1154,11 +1304,29 @@ impl LiveStatsAccum {
             start: std::time::Instant::now(),
             n_frames: 0,
             n_points: 0,
+            metrics: CameraMetrics {
+                total_frames: 0,
+                total_points: 0,
+                dropped_frames: 0,
+                fps: f64::NAN,
+            },
+            last_synced_frame: None,
         }
     }
     fn update(&mut self, n_points: usize) {
         self.n_frames += 1;
         self.n_points += n_points;
+        self.metrics.total_frames += 1;
+        self.metrics.total_points += n_points as u64;
+    }
+    fn update_synced_frame(&mut self, frame: SyncFno) {
+        if let Some(last) = self.last_synced_frame {
+            if frame.0 > last.0 + 1 {
+                self.metrics.dropped_frames += frame.0 - last.0 - 1;
+            }
+        }
+        // After resynchronization, frame numbers start again from zero.
+        self.last_synced_frame = Some(frame);
     }
     fn get_results_and_reset(&mut self) -> braid_types::RecentStats {
         let recent = braid_types::RecentStats {
@@ -1166,6 +1334,7 @@ impl LiveStatsAccum {
             frames_collected: self.n_frames,
             points_detected: self.n_points,
         };
+        self.metrics.fps = self.n_frames as f64 / self.start.elapsed().as_secs_f64();
         self.start = std::time::Instant::now();
         self.n_frames = 0;
         self.n_points = 0;
@@ -1179,6 +1348,22 @@ impl LiveStatsCollector {
         Self { shared, collected }
     }
 
+    /// The metrics of all cameras which have sent data.
+    pub(crate) fn camera_metrics(&self) -> Vec<(RawCamName, CameraMetrics)> {
+        let collected = self.collected.read().unwrap();
+        collected
+            .iter()
+            .map(|(name, accum)| (name.clone(), accum.metrics.clone()))
+            .collect()
+    }
+
+    fn register_synced_frame(&self, name: &RawCamName, frame: SyncFno) {
+        let mut collected = self.collected.write().unwrap();
+        if let Some(entry) = collected.get_mut(name) {
+            entry.update_synced_frame(frame);
+        }
+    }
+
     fn register_new_frame_data(&self, name: &RawCamName, n_points: usize) {
         let to_send = {
             // scope for lock on self.collected
@@ -1221,43 +1406,20 @@ pub(crate) async fn toggle_saving_csv_tables(
     shared_data: SharedStore,
 ) {
     if start_saving {
-        let expected_framerate: Option<f32> = *expected_framerate_arc.read().unwrap();
         let local: chrono::DateTime<chrono::Local> = chrono::Local::now();
         let dirname = local.format("%Y%m%d_%H%M%S.braid").to_string();
         let mut my_dir = output_base_dirname.clone();
         my_dir.push(dirname);
-        let per_cam_data = {
-            // small scope for read lock
-            let per_cam_data_ref = per_cam_data_arc.read().unwrap();
-            (*per_cam_data_ref).clone()
-        };
-        let cfg = flydra2::StartSavingCsvConfig {
-            out_dir: my_dir.clone(),
-            local: Some(local),
-            git_rev: env!("GIT_HASH").to_string(),
-            fps: expected_framerate,
-            per_cam_data,
-            print_stats: false,
-            save_performance_histograms: true,
-        };
-
-        if let Some(braidz_write_tx) = braidz_write_tx_weak.upgrade() {
-            // `braidz_write_tx` will be dropped after this scope.
-            braidz_write_tx
-                .send(flydra2::SaveToDiskMsg::StartSavingCsv(cfg))
-                .await
-                .unwrap();
-            info!("saving data to \"{}\"", my_dir.display());
-        } else {
-            error!("data writing thread lost. Not saving data as requested");
-        }
-
-        {
-            let mut tracker = shared_data.write().unwrap();
-            tracker.modify(|store| {
-                store.csv_tables_dirname = Some(RecordingPath::new(my_dir.display().to_string()));
-            });
-        }
+        start_saving_csv_tables(
+            my_dir,
+            local,
+            false,
+            expected_framerate_arc,
+            braidz_write_tx_weak,
+            per_cam_data_arc,
+            shared_data,
+        )
+        .await;
     } else {
         if let Some(braidz_write_tx) = braidz_write_tx_weak.upgrade() {
             // `braidz_write_tx` will be dropped after this scope.
@@ -1279,6 +1441,52 @@ pub(crate) async fn toggle_saving_csv_tables(
     }
 }
 
+/// Start saving to `my_dir`, appending to its files if `resume` is true.
+async fn start_saving_csv_tables(
+    my_dir: std::path::PathBuf,
+    local: chrono::DateTime<chrono::Local>,
+    resume: bool,
+    expected_framerate_arc: Arc<RwLock<Option<f32>>>,
+    braidz_write_tx_weak: tokio::sync::mpsc::WeakSender<flydra2::SaveToDiskMsg>,
+    per_cam_data_arc: Arc<RwLock<BTreeMap<RawCamName, PerCamSaveData>>>,
+    shared_data: SharedStore,
+) {
+    let expected_framerate: Option<f32> = *expected_framerate_arc.read().unwrap();
+    let per_cam_data = {
+        // small scope for read lock
+        let per_cam_data_ref = per_cam_data_arc.read().unwrap();
+        (*per_cam_data_ref).clone()
+    };
+    let cfg = flydra2::StartSavingCsvConfig {
+        out_dir: my_dir.clone(),
+        local: Some(local),
+        git_rev: env!("GIT_HASH").to_string(),
+        fps: expected_framerate,
+        per_cam_data,
+        print_stats: false,
+        save_performance_histograms: true,
+        resume,
+    };
+
+    if let Some(braidz_write_tx) = braidz_write_tx_weak.upgrade() {
+        // `braidz_write_tx` will be dropped after this scope.
+        braidz_write_tx
+            .send(flydra2::SaveToDiskMsg::StartSavingCsv(cfg))
+            .await
+            .unwrap();
+        info!("saving data to \"{}\"", my_dir.display());
+    } else {
+        error!("data writing thread lost. Not saving data as requested");
+    }
+
+    {
+        let mut tracker = shared_data.write().unwrap();
+        tracker.modify(|store| {
+            store.csv_tables_dirname = Some(RecordingPath::new(my_dir.display().to_string()));
+        });
+    }
+}
+
 async fn synchronize_cameras(
     triggerbox_cmd: Option<tokio::sync::mpsc::Sender<braid_triggerbox::Cmd>>,
     fake_sync: bool,
@@ -1332,3 +1540,273 @@ fn to_event_frame(state: &BraidHttpApiSharedState) -> String {
     let frame_string = format!("event: {BRAID_EVENT_NAME}\ndata: {buf}\n\n");
     frame_string
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    use std::{collections::BTreeSet, sync::Mutex, time::Duration};
+
+    use braid_triggerbox::{make_trig_fps_cmd, Cmd, TriggerClockInfoRow, TriggerboxDevice};
+    use braid_triggerbox_emulator::{Emulator, EmulatorConfig};
+    use braid_types::{
+        BuiServerInfo, FlydraRawUdpPacket, HostClock, TriggerType, TriggerboxConfig,
+    };
+    use flydra2::ConnectedCamerasManager;
+
+    /// Host time now, in seconds, as used by the clock model.
+    fn host_time_now() -> f64 {
+        FlydraFloatTimestampLocal::<HostClock>::from(chrono::Utc::now()).as_f64()
+    }
+
+    /// A frame of the simulated camera.
+    struct Frame {
+        /// Pulse number of the trigger pulse which exposed the frame.
+        pulse: u32,
+        synced_frame: Option<u64>,
+        /// Host time at which the frame was received.
+        received: f64,
+    }
+
+    /// Synchronize a simulated camera triggered by the emulator and check the
+    /// timestamps of its frames computed from the clock model.
+    #[tokio::test]
+    async fn test_synchronize_camera() {
+        let drift_ppm = 5000.0;
+        let emulator = Arc::new(
+            Emulator::start(EmulatorConfig {
+                drift_ppm,
+                drift_random_walk_ppm: 0.0,
+                max_reply_latency: Duration::from_micros(100),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+
+        // As in Braid, the callback keeps the current clock model.
+        let time_model_arc: Arc<RwLock<Option<strand_cam_bui_types::ClockModel>>> =
+            Default::default();
+        let num_clock_models = Arc::new(Mutex::new(0usize));
+        let on_new_clock_model = {
+            let time_model_arc = time_model_arc.clone();
+            let num_clock_models = num_clock_models.clone();
+            Box::new(move |cm: Option<braid_triggerbox::ClockModel>| {
+                if cm.is_some() {
+                    *num_clock_models.lock().unwrap() += 1;
+                }
+                *time_model_arc.write().unwrap() = cm.map(|x| strand_cam_bui_types::ClockModel {
+                    gain: x.gain,
+                    offset: x.offset,
+                    n_measurements: x.n_measurements,
+                    residuals: x.residuals,
+                });
+            })
+        };
+        let device_fname = emulator.device_path().to_str().unwrap().to_string();
+
+        let (tx, cmd_rx) = tokio::sync::mpsc::channel(20);
+        let (data_tx, mut data_rx) = tokio::sync::mpsc::channel(20);
+        let (rate_cmd, rate_actual) = make_trig_fps_cmd(100.0);
+        tx.send(Cmd::StopPulsesAndReset).await.unwrap();
+        tx.send(rate_cmd).await.unwrap();
+        tx.send(Cmd::StartPulses).await.unwrap();
+
+        let triggerbox = TriggerboxDevice::new(
+            on_new_clock_model,
+            device_fname,
+            cmd_rx,
+            Some(data_tx),
+            None,
+            Duration::from_millis(20),
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap();
+        let join_handle = tokio::spawn(triggerbox.run_forever(Duration::from_millis(100)));
+        let rows: Arc<Mutex<Vec<TriggerClockInfoRow>>> = Default::default();
+        let rows_join_handle = {
+            let rows = rows.clone();
+            tokio::spawn(async move {
+                while let Some(row) = data_rx.recv().await {
+                    rows.lock().unwrap().push(row);
+                }
+            })
+        };
+
+        let cam_name = RawCamName::new("cam1".to_string());
+        let signal_all_cams_synced = Arc::new(AtomicBool::new(false));
+        let mut ccm = ConnectedCamerasManager::new(
+            &None,
+            BTreeSet::from([cam_name.clone()]),
+            Arc::new(AtomicBool::new(false)),
+            signal_all_cams_synced.clone(),
+            None,
+        );
+        ccm.register_new_camera(&cam_name, &BuiServerInfo::NoServer, None)
+            .unwrap();
+        let sync_pulse_pause_started_arc: Arc<RwLock<Option<std::time::Instant>>> =
+            Default::default();
+
+        // The camera exposes a frame on each trigger pulse. Its frame counter is
+        // not reset when the triggerbox is.
+        let quit = Arc::new(AtomicBool::new(false));
+        let camera = {
+            let emulator = emulator.clone();
+            let ccm = ccm.clone();
+            let sync_pulse_pause_started_arc = sync_pulse_pause_started_arc.clone();
+            let time_model_arc = time_model_arc.clone();
+            let quit = quit.clone();
+            std::thread::spawn(move || {
+                let trigger_cfg = TriggerType::TriggerboxV1(TriggerboxConfig::default());
+                let mut frames = Vec::new();
+                let mut cam_frame = 1000;
+                let mut last_pulse: Option<u32> = None;
+                while !quit.load(Ordering::SeqCst) {
+                    std::thread::sleep(Duration::from_millis(1));
+                    let pulse = emulator.last_trigger_pulse();
+                    let first = match (pulse, last_pulse) {
+                        (None, _) => {
+                            last_pulse = None;
+                            continue;
+                        }
+                        (Some(_), Some(last)) => last + 1,
+                        (Some(_), None) => 2,
+                    };
+                    let pulse = pulse.unwrap();
+                    for pulse in first..=pulse {
+                        cam_frame += 1;
+                        let received = chrono::Utc::now();
+                        let packet = FlydraRawUdpPacket {
+                            cam_name: cam_name.as_str().to_string(),
+                            timestamp: None,
+                            cam_received_time: received.into(),
+                            device_timestamp: None,
+                            block_id: None,
+                            framenumber: cam_frame,
+                            points: vec![],
+                        };
+                        let clock_model = time_model_arc.read().unwrap().clone();
+                        let synced_frame = ccm
+                            .got_new_frame_live(
+                                &packet,
+                                &sync_pulse_pause_started_arc,
+                                |_| {},
+                                &trigger_cfg,
+                                clock_model.as_ref(),
+                            )
+                            .map(|fno| fno.0);
+                        frames.push(Frame {
+                            pulse,
+                            synced_frame,
+                            received: FlydraFloatTimestampLocal::<HostClock>::from(received)
+                                .as_f64(),
+                        });
+                    }
+                    last_pulse = Some(pulse);
+                }
+                frames
+            })
+        };
+
+        let wait_for_clock_models = |n: usize| {
+            let num_clock_models = num_clock_models.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(20), async {
+                    while *num_clock_models.lock().unwrap() < n {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                })
+                .await
+                .unwrap();
+            }
+        };
+        wait_for_clock_models(1).await;
+        assert!(!signal_all_cams_synced.load(Ordering::SeqCst));
+
+        synchronize_cameras(
+            Some(tx.clone()),
+            false,
+            sync_pulse_pause_started_arc.clone(),
+            ccm.clone(),
+            time_model_arc.clone(),
+        )
+        .await
+        .unwrap();
+        let sync_end_host_time = host_time_now();
+        let num_rows_at_sync = rows.lock().unwrap().len();
+        let num_clock_models_at_sync = *num_clock_models.lock().unwrap();
+        wait_for_clock_models(num_clock_models_at_sync + 20).await;
+        assert!(signal_all_cams_synced.load(Ordering::SeqCst));
+
+        quit.store(true, Ordering::SeqCst);
+        let frames = camera.join().unwrap();
+        join_handle.abort();
+        rows_join_handle.abort();
+        let model = time_model_arc.read().unwrap().clone().unwrap();
+
+        let expected_gain = 1.0 / (rate_actual * (1.0 + drift_ppm * 1e-6));
+        let rel_err = (model.gain - expected_gain).abs() / expected_gain;
+        assert!(
+            rel_err < 0.1 * drift_ppm * 1e-6,
+            "gain {} expected {expected_gain}",
+            model.gain
+        );
+
+        // The camera is synchronized by its first frame after the pause, after
+        // which the synced frame number is the pulse number.
+        let synced: Vec<_> = frames
+            .iter()
+            .skip_while(|frame| frame.synced_frame.is_none())
+            .collect();
+        assert_eq!(synced[0].synced_frame, Some(2));
+        assert!(synced.len() > 100);
+        for frame in synced.iter() {
+            assert_eq!(frame.synced_frame, Some(frame.pulse.into()));
+        }
+
+        // The trigger timestamp of each frame is slightly before the frame is
+        // received. The limits allow for the polling interval of the simulated
+        // camera and for scheduling delays but are below the 10 msec error of a
+        // single frame.
+        let mut delays: Vec<f64> = synced
+            .iter()
+            .filter(|frame| frame.received > sync_end_host_time)
+            .map(|frame| {
+                let trigger_timestamp =
+                    frame.synced_frame.unwrap() as f64 * model.gain + model.offset;
+                frame.received - trigger_timestamp
+            })
+            .collect();
+        delays.sort_by(|a, b| a.total_cmp(b));
+        let median = delays[delays.len() / 2];
+        let p90 = delays[delays.len() * 9 / 10];
+        assert!(-1e-3 < median && median < 5e-3, "median delay {median}");
+        assert!(p90 < 8e-3, "90th percentile delay {p90}");
+
+        // The clock measurements saved in the braidz file are described by the
+        // same model.
+        let rows = rows.lock().unwrap();
+        let rows: Vec<_> = rows[num_rows_at_sync..]
+            .iter()
+            .filter(|row| row.framecount > 0)
+            .map(|row| braid_types::TriggerClockInfoRow {
+                start_timestamp: row.start_timestamp.into(),
+                framecount: row.framecount,
+                tcnt: row.tcnt,
+                stop_timestamp: row.stop_timestamp.into(),
+            })
+            .collect();
+        assert!(rows.len() > 10);
+        for row in rows.iter() {
+            let midpoint = (row.start_timestamp.as_f64() + row.stop_timestamp.as_f64()) / 2.0;
+            let pulse = row.framecount as f64 + row.tcnt as f64 / 255.0;
+            let err = midpoint - (pulse * model.gain + model.offset);
+            assert!(err.abs() < 5e-3, "clock measurement error {err}");
+        }
+
+        Arc::try_unwrap(emulator)
+            .unwrap_or_else(|_| panic!("emulator still in use"))
+            .stop()
+            .unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! Metrics in the Prometheus text format, served at `/metrics`.
+
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::State, response::IntoResponse};
+use tokio::sync::mpsc::Receiver;
+
+use flydra2::{SendType, TimeDataPassthrough};
+use strand_metrics::{Histogram, MetricType, MetricsEncoder};
+
+use crate::mainbrain::{BraidAppState, CameraMetrics};
+
+/// Upper bounds of the reconstruction latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0,
+];
+
+/// Metrics computed from the tracking output.
+#[derive(Clone)]
+pub(crate) struct PoseMetrics {
+    inner: Arc<Mutex<PoseMetricsInner>>,
+}
+
+struct PoseMetricsInner {
+    live_objects: BTreeSet<u32>,
+    reconstruction_latency: Histogram,
+}
+
+impl PoseMetrics {
+    pub(crate) fn new() -> Self {
+        let inner = PoseMetricsInner {
+            live_objects: BTreeSet::new(),
+            reconstruction_latency: Histogram::new(LATENCY_BUCKETS),
+        };
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Update the metrics from the tracking output until `data_rx` is closed.
+    pub(crate) async fn run(self, mut data_rx: Receiver<(SendType, TimeDataPassthrough)>) {
+        while let Some((msg, tdpt)) = data_rx.recv().await {
+            let mut inner = self.inner.lock().unwrap();
+            match msg {
+                SendType::Birth(row) | SendType::Update(row) => {
+                    inner.live_objects.insert(row.obj_id);
+                    // Time since the frame was acquired. Unlike
+                    // `reconstruct_latency_usec.hlog`, this is measured before
+                    // the estimate is queued to be written to disk.
+                    if let Some(tt) = tdpt.trigger_timestamp() {
+                        let now =
+                            strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+                        inner.reconstruction_latency.observe(now - tt.as_f64());
+                    }
+                }
+                SendType::Death(obj_id) => {
+                    inner.live_objects.remove(&obj_id);
+                }
+                SendType::EndOfFrame(_) | SendType::CalibrationFlydraXml(_) => {}
+            }
+        }
+    }
+}
+
+pub(crate) async fn metrics_handler(
+    State(app_state): State<BraidAppState>,
+    session_key: axum_token_auth::SessionKey,
+) -> impl IntoResponse {
+    session_key.is_present();
+    let mut enc = MetricsEncoder::new();
+
+    let cameras = app_state.live_stats_collector.camera_metrics();
+    let per_camera: [(&str, &str, MetricType, fn(&CameraMetrics) -> f64); 4] = [
+        (
+            "braid_camera_frames_total",
+            "Number of frames received from the camera.",
+            MetricType::Counter,
+            |c| c.total_frames as f64,
+        ),
+        (
+            "braid_camera_dropped_frames_total",
+            "Number of frames missing in the sequence received from the camera.",
+            MetricType::Counter,
+            |c| c.dropped_frames as f64,
+        ),
+        (
+            "braid_camera_points_total",
+            "Number of 2D points detected by the camera.",
+            MetricType::Counter,
+            |c| c.total_points as f64,
+        ),
+        (
+            "braid_camera_fps",
+            "Recent frame rate received from the camera.",
+            MetricType::Gauge,
+            |c| c.fps,
+        ),
+    ];
+    for (name, help, metric_type, value) in per_camera {
+        enc.family(name, help, metric_type);
+        for (cam_name, cam) in cameras.iter() {
+            enc.sample(name, &[("camera", cam_name.as_str())], value(cam));
+        }
+    }
+
+    let n_connected = {
+        let tracker = app_state.shared_store.read().unwrap();
+        tracker.as_ref().connected_cameras.len()
+    };
+    enc.single(
+        "braid_cameras_connected",
+        "Number of connected cameras.",
+        MetricType::Gauge,
+        n_connected as f64,
+    );
+
+    {
+        let pose = app_state.pose_metrics.inner.lock().unwrap();
+        enc.single(
+            "braid_live_objects",
+            "Number of objects currently tracked.",
+            MetricType::Gauge,
+            pose.live_objects.len() as f64,
+        );
+        enc.histogram(
+            "braid_reconstruction_latency_seconds",
+            "Time from frame acquisition until 3D reconstruction.",
+            &[],
+            &pose.reconstruction_latency,
+        );
+    }
+
+    if let Some(braidz_write_tx) = app_state.braidz_write_tx_weak.upgrade() {
+        let capacity = braidz_write_tx.max_capacity();
+        enc.single(
+            "braid_write_queue_depth",
+            "Number of messages waiting to be written to disk.",
+            MetricType::Gauge,
+            (capacity - braidz_write_tx.capacity()) as f64,
+        );
+        enc.single(
+            "braid_write_queue_capacity",
+            "Maximum number of messages waiting to be written to disk \
+            (`write_buffer_size_num_messages`).",
+            MetricType::Gauge,
+            capacity as f64,
+        );
+    }
+
+    (
+        [(http::header::CONTENT_TYPE, strand_metrics::CONTENT_TYPE)],
+        enc.finish(),
+    )
+}
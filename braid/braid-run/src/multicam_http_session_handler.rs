@@ -107,6 +107,12 @@ impl StrandCamHttpSessionHandler {
         }
     }
 
+    /// Forget the session of a camera, e.g. because its Strand Camera was
+    /// restarted. A new session is opened when needed.
+    pub(crate) fn forget_session(&self, cam_name: &RawCamName) {
+        self.name_to_session.write().unwrap().remove(cam_name);
+    }
+
     async fn post(
         &self,
         cam_name: &RawCamName,
@@ -64,6 +64,11 @@ pub const CALIBRATION_XML_FNAME: &str = "calibration.xml";
 pub const BRAID_METADATA_YML_FNAME: &str = "braid_metadata.yml";
 /// Markdown filename for README documentation.
 pub const README_MD_FNAME: &str = "README.md";
+/// JSON filename for the tracking state saved while recording.
+///
+/// This is present in a `.braid` directory only if the recording was not
+/// finished, e.g. because Braid crashed.
+pub const TRACKING_CHECKPOINT_JSON_FNAME: &str = "tracking_checkpoint.json";
 /// Directory name for saved images.
 pub const IMAGES_DIRNAME: &str = "images";
 /// Directory name for camera settings.
@@ -583,6 +588,10 @@ pub struct BraidHttpApiSharedState {
     pub flydra_app_name: String,
     /// Whether all expected cameras are synchronized.
     pub all_expected_cameras_are_synced: bool,
+    /// Tracking parameters currently in use.
+    pub tracking_params: TrackingParams,
+    /// Cameras for which calibration drift is currently detected.
+    pub calibration_drift: Vec<CalibrationDriftAlert>,
 }
 
 /// Statistics for recent camera activity.
@@ -699,7 +708,7 @@ pub struct TextlogRow {
 /// that 2**Q**(τ) = **Q**(2τ). In other words, two successive additions of this
 /// covariance will have an identical effect to a single addtion for twice the
 /// time interval.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct TrackingParams {
     /// This is used to scale the state noise covariance matrix **Q** as
@@ -739,6 +748,23 @@ pub struct TrackingParams {
     pub mini_arena_config: MiniArenaConfig,
 }
 
+impl TrackingParams {
+    /// Check whether a running tracker can switch from these parameters to
+    /// `new`.
+    ///
+    /// Switching between full 3D and flat 3D tracking or changing the mini
+    /// arena configuration requires restarting the tracker.
+    pub fn check_runtime_change(&self, new: &TrackingParams) -> Result<(), &'static str> {
+        if self.hypothesis_test_params.is_some() != new.hypothesis_test_params.is_some() {
+            return Err("cannot switch between full 3D and flat 3D tracking at runtime");
+        }
+        if self.mini_arena_config != new.mini_arena_config {
+            return Err("cannot change mini arena configuration at runtime");
+        }
+        Ok(())
+    }
+}
+
 /// Locator for determining which mini arena contains a point.
 pub struct MiniArenaLocator {
     /// The index number of the mini arena. None if the point is not in a mini arena.
@@ -958,7 +984,7 @@ pub fn default_tracking_params_flat_3d() -> TrackingParams {
 }
 
 /// Parameters for hypothesis testing in track initialization.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct HypothesisTestParams {
     /// Minimum number of cameras required for track initialization.
     pub minimum_number_of_cameras: u8,
@@ -977,6 +1003,213 @@ pub fn make_hypothesis_test_full3d_default() -> HypothesisTestParams {
     }
 }
 
+/// Configuration of closed-loop rules evaluated on the live 3D tracking
+/// output.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ClosedLoopConfig {
+    /// Serial device of the LED box used by [ClosedLoopAction::LedBox]
+    /// actions (e.g. `/dev/ttyACM0`).
+    #[serde(default)]
+    pub led_box_device: Option<String>,
+    /// The rules. Each rule is evaluated for every tracked object.
+    #[serde(default)]
+    pub rules: Vec<ClosedLoopRule>,
+}
+
+impl ClosedLoopConfig {
+    /// Return true if no rules are configured.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// A single closed-loop rule: when `trigger` happens, perform `action`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ClosedLoopRule {
+    /// Name of the rule, used when logging firings.
+    pub name: String,
+    /// The volume to which this rule is restricted.
+    ///
+    /// Required for [ClosedLoopTrigger::Enter] and [ClosedLoopTrigger::Leave].
+    /// Optional for the speed triggers, which then only fire inside the
+    /// volume.
+    #[serde(default)]
+    pub volume: Option<Volume3D>,
+    /// The event causing the rule to fire.
+    pub trigger: ClosedLoopTrigger,
+    /// What to do when the rule fires.
+    pub action: ClosedLoopAction,
+}
+
+/// A 3D volume in the coordinate frame of the calibration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum Volume3D {
+    /// An axis-aligned box.
+    Box {
+        /// The corner with the smallest coordinates.
+        min: [f64; 3],
+        /// The corner with the largest coordinates.
+        max: [f64; 3],
+    },
+    /// A sphere.
+    Sphere {
+        /// The center of the sphere.
+        center: [f64; 3],
+        /// The radius of the sphere.
+        radius: f64,
+    },
+    /// A cylinder with its axis parallel to the Z axis.
+    Cylinder {
+        /// The center of the bottom face.
+        base_center: [f64; 3],
+        /// The radius of the cylinder.
+        radius: f64,
+        /// The height of the cylinder, extending upwards from `base_center`.
+        height: f64,
+    },
+    /// A closed triangle mesh loaded from a Wavefront `.obj` file.
+    ///
+    /// The file must contain texture coordinates. Only the first object in
+    /// the file is used.
+    Mesh {
+        /// Path of the `.obj` file. Relative paths are relative to the Braid
+        /// configuration file.
+        filename: std::path::PathBuf,
+    },
+}
+
+/// The event causing a [ClosedLoopRule] to fire.
+///
+/// Rules fire on transitions only, once per object and transition.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum ClosedLoopTrigger {
+    /// The object enters the volume. An object born inside the volume counts
+    /// as entering.
+    Enter,
+    /// The object leaves the volume. Objects which are lost while inside the
+    /// volume do not trigger this.
+    Leave,
+    /// The speed of the object rises above `speed`.
+    SpeedAbove {
+        /// Speed threshold, in calibration units (usually meters) per second.
+        speed: f64,
+    },
+    /// The speed of the object falls below `speed`.
+    SpeedBelow {
+        /// Speed threshold, in calibration units (usually meters) per second.
+        speed: f64,
+    },
+}
+
+/// The action performed when a [ClosedLoopRule] fires.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum ClosedLoopAction {
+    /// Switch a channel of the LED box given in
+    /// [ClosedLoopConfig::led_box_device].
+    LedBox {
+        /// Channel number (1-4).
+        channel: u8,
+        /// Whether to switch the channel on or off.
+        on: bool,
+        /// LED intensity. Defaults to the maximum intensity.
+        #[serde(default)]
+        intensity: Option<u16>,
+    },
+    /// Send the firing as a JSON-encoded UDP packet.
+    Udp {
+        /// Destination address (`IP:PORT`). May be a multicast address.
+        addr: String,
+    },
+    /// Send the firing on the event stream of the model server as an event
+    /// of type `braid-rule`.
+    EventStream,
+}
+
+/// Configuration of the detection of calibration drift during live tracking.
+///
+/// For each camera, the reprojection distances of the observations used to
+/// update tracked objects are collected. The first
+/// [Self::baseline_num_observations] form the baseline. Each following window
+/// of [Self::window_num_observations] is compared to the baseline with a
+/// one-sided Mann-Whitney U test. Drift is reported if the reprojection
+/// distances of the window are significantly larger and their median
+/// increased by at least [Self::min_median_increase_pixels].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalibrationDriftConfig {
+    /// Whether to detect calibration drift.
+    pub enabled: bool,
+    /// Number of observations per camera forming the baseline.
+    pub baseline_num_observations: usize,
+    /// Number of observations per camera in each window compared to the
+    /// baseline.
+    pub window_num_observations: usize,
+    /// Minimum z-score of the Mann-Whitney U test to report drift.
+    ///
+    /// Successive observations of an object are correlated, so this is much
+    /// higher than for independent samples.
+    pub min_z_score: f64,
+    /// Minimum increase of the median reprojection distance, in pixels, to
+    /// report drift.
+    pub min_median_increase_pixels: f64,
+    /// Whether to estimate the pose of a camera for which drift was detected.
+    ///
+    /// The calibration with the estimated pose is saved in the output
+    /// directory. Not supported with refractive boundaries.
+    pub estimate_extrinsics: bool,
+}
+
+impl Default for CalibrationDriftConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            baseline_num_observations: 5000,
+            window_num_observations: 1000,
+            min_z_score: 5.0,
+            min_median_increase_pixels: 1.0,
+            estimate_extrinsics: false,
+        }
+    }
+}
+
+/// A camera for which calibration drift was detected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalibrationDriftAlert {
+    /// The raw camera name.
+    pub cam_name: RawCamName,
+    /// The last frame of the window in which drift was detected.
+    pub frame: SyncFno,
+    /// Median reprojection distance of the baseline, in pixels.
+    pub baseline_median_reproj_dist: f64,
+    /// Median reprojection distance of the window, in pixels.
+    pub recent_median_reproj_dist: f64,
+    /// z-score of the Mann-Whitney U test.
+    pub z_score: f64,
+    /// The estimated pose of the camera, if computed.
+    pub corrected_extrinsics: Option<CorrectedExtrinsics>,
+}
+
+/// The pose of a camera estimated after calibration drift was detected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CorrectedExtrinsics {
+    /// Estimated camera center.
+    pub camcenter: [f64; 3],
+    /// Distance of the estimated camera center from the calibrated one.
+    pub camcenter_shift: f64,
+    /// Angle between the estimated and calibrated orientation, in degrees.
+    pub rotation_degrees: f64,
+    /// Median reprojection distance of the window with the estimated pose, in
+    /// pixels.
+    pub median_reproj_dist: f64,
+    /// Path of the calibration file with the estimated pose, if saved.
+    pub calibration_filename: Option<String>,
+}
+
 /// Information about a connected camera.
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct CamInfo {
@@ -1017,6 +1250,10 @@ pub enum BraidHttpApiCallback {
     SetPostTriggerBufferSize(usize),
     /// Initiate MKV recording using post trigger
     PostTriggerMp4Recording,
+    /// Change the tracking parameters of the running tracker
+    SetTrackingParams(TrackingParams),
+    /// Append a message to the textlog of the current recording (if any)
+    LogMessage(String),
 }
 
 /// Wrapper for per-camera data.
@@ -1059,6 +1296,11 @@ pub use synced_frame::SyncFno;
 mod cam_num;
 pub use cam_num::CamNum;
 
+mod pose_api;
+pub use crate::pose_api::{
+    PoseUdpPacket, SendKalmanEstimatesRow, SendType, ToListener, BRAID_POSE_API_VERSION,
+};
+
 mod timestamp;
 pub use crate::timestamp::{
     triggerbox_time, FlydraFloatTimestampLocal, HostClock, Source, Triggerbox,
@@ -1177,6 +1419,22 @@ impl Default for FakeSyncConfig {
     }
 }
 
+/// Configuration for synchronization using device timestamps.
+///
+/// The device clocks of all cameras must already be synchronized to each other
+/// (e.g. by the camera vendor's clock synchronization).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceTimestampConfig {
+    /// Frame rate at which the cameras acquire.
+    ///
+    /// Device timestamps are converted to synchronized frame numbers by
+    /// dividing by the corresponding frame period. If not set, the frame rate
+    /// is estimated from the device timestamps of the first frames.
+    #[serde(default)]
+    pub framerate: Option<f64>,
+}
+
 /// Camera synchronization method configuration.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
@@ -1188,7 +1446,7 @@ pub enum TriggerType {
     /// Cameras are synchronized using PTP (Precision Time Protocol, IEEE 1588).
     PtpSync(PtpSyncConfig),
     /// Cameras are synchronized using device timestamps.
-    DeviceTimestamp,
+    DeviceTimestamp(DeviceTimestampConfig),
     /// Cameras are not synchronized, but we pretend they are.
     FakeSync(FakeSyncConfig),
 }
@@ -0,0 +1,140 @@
+//! Types of the Braid pose API.
+//!
+//! These are the realtime tracking results sent by the model server, either
+//! as JSON in an HTTP event stream or as CBOR over UDP.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FlydraFloatTimestampLocal, KalmanEstimatesRow, SyncFno, Triggerbox};
+
+/// Version of the Braid pose API.
+///
+/// Bump when [SendType] or the messages containing it change. (Search for the
+/// string ZP4q.)
+pub const BRAID_POSE_API_VERSION: u16 = 3;
+
+/// Kalman filter state estimate sent in the Braid pose API.
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SendKalmanEstimatesRow {
+    /// Object ID being tracked.
+    pub obj_id: u32,
+    /// Synchronized frame number.
+    pub frame: SyncFno,
+    /// X position estimate in meters.
+    pub x: f64,
+    /// Y position estimate in meters.
+    pub y: f64,
+    /// Z position estimate in meters.
+    pub z: f64,
+    /// X velocity estimate in meters per second.
+    pub xvel: f64,
+    /// Y velocity estimate in meters per second.
+    pub yvel: f64,
+    /// Z velocity estimate in meters per second.
+    pub zvel: f64,
+    /// Covariance matrix element P\[0,0\].
+    pub P00: f64,
+    /// Covariance matrix element P\[0,1\].
+    pub P01: f64,
+    /// Covariance matrix element P\[0,2\].
+    pub P02: f64,
+    /// Covariance matrix element P\[1,1\].
+    pub P11: f64,
+    /// Covariance matrix element P\[1,2\].
+    pub P12: f64,
+    /// Covariance matrix element P\[2,2\].
+    pub P22: f64,
+    /// Covariance matrix element P\[3,3\].
+    pub P33: f64,
+    /// Covariance matrix element P\[4,4\].
+    pub P44: f64,
+    /// Covariance matrix element P\[5,5\].
+    pub P55: f64,
+}
+
+impl From<KalmanEstimatesRow> for SendKalmanEstimatesRow {
+    fn from(orig: KalmanEstimatesRow) -> SendKalmanEstimatesRow {
+        SendKalmanEstimatesRow {
+            obj_id: orig.obj_id,
+            frame: orig.frame,
+            x: orig.x,
+            y: orig.y,
+            z: orig.z,
+            xvel: orig.xvel,
+            yvel: orig.yvel,
+            zvel: orig.zvel,
+            P00: orig.P00,
+            P01: orig.P01,
+            P02: orig.P02,
+            P11: orig.P11,
+            P12: orig.P12,
+            P22: orig.P22,
+            P33: orig.P33,
+            P44: orig.P44,
+            P55: orig.P55,
+        }
+    }
+}
+
+/// A message of the Braid pose API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SendType {
+    // IMPORTANT NOTE: if you change this type, be sure to change the version
+    // value `v`. Search for the string ZP4q and `Braid pose API`.
+    /// A new object was born.
+    Birth(SendKalmanEstimatesRow),
+    /// An existing object was updated.
+    Update(SendKalmanEstimatesRow),
+    /// The object with this obj_id died.
+    Death(u32),
+
+    /// All messages for this frame have been sent.
+    EndOfFrame(SyncFno),
+    /// the multicamera calibration serialized into a flydra xml file
+    CalibrationFlydraXml(String),
+}
+
+/// A Braid pose API message sent in the event stream of the model server.
+///
+/// Each message is sent JSON-encoded as an event of type `braid`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToListener {
+    // IMPORTANT NOTE: if you change this type, be sure to change the version
+    // value `v`. Search for the string ZP4q and `Braid pose API`.
+    /// Version of the Braid pose API, [BRAID_POSE_API_VERSION].
+    pub v: u16,
+    /// The message.
+    pub msg: SendType,
+    /// Time from the trigger timestamp until sending, in seconds. NaN (`null`
+    /// in JSON) if the trigger timestamp is unknown.
+    #[serde(deserialize_with = "crate::invalid_nan")]
+    pub latency: f64,
+    /// Synchronized frame number.
+    pub synced_frame: SyncFno,
+    /// The timestamp when the trigger pulse fired.
+    #[serde(with = "crate::timestamp_opt_f64")]
+    pub trigger_timestamp: Option<FlydraFloatTimestampLocal<Triggerbox>>,
+}
+
+/// A Braid pose API message sent as a UDP datagram.
+///
+/// Each datagram contains one of these, encoded as CBOR.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoseUdpPacket {
+    /// Version of the Braid pose API, [BRAID_POSE_API_VERSION].
+    pub v: u16,
+    /// Sequence number, incremented by one for each datagram sent. Gaps
+    /// indicate lost datagrams.
+    pub seq: u64,
+    /// The message.
+    pub msg: SendType,
+    /// Time from the trigger timestamp until sending, in seconds. NaN if the
+    /// trigger timestamp is unknown.
+    pub latency: f64,
+    /// Synchronized frame number.
+    pub synced_frame: SyncFno,
+    /// The timestamp when the trigger pulse fired.
+    #[serde(with = "crate::timestamp_opt_f64")]
+    pub trigger_timestamp: Option<FlydraFloatTimestampLocal<Triggerbox>>,
+}
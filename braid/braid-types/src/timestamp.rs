@@ -120,7 +120,10 @@ pub fn triggerbox_time(
     let frame: u64 = frame.try_into().unwrap();
     if let Some(frame_offset) = frame_offset {
         if let Some(cm) = clock_model {
-            let ts: f64 = ((frame - frame_offset) as f64) * cm.gain + cm.offset;
+            // The frame offset of a camera which rejoined with a reset frame
+            // counter is "negative" and thus stored wrapped around.
+            let synced_frame = frame.wrapping_sub(frame_offset) as i64;
+            let ts: f64 = (synced_frame as f64) * cm.gain + cm.offset;
             let ts = FlydraFloatTimestampLocal::<Triggerbox>::from_f64(ts);
             return Some(ts);
         }
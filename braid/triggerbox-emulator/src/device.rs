@@ -0,0 +1,224 @@
+//! Model of the triggerbox hardware.
+
+use crate::protocol::{self, Command, Prescaler};
+
+/// Pulse number of the first pulse leaving the device after pulses are
+/// started.
+///
+/// As with the hardware, no pulse is output when the counter first advances.
+const FIRST_OUTPUT_PULSE: u32 = 2;
+
+/// Small deterministic pseudo-random number generator (xorshift64*).
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Self((seed ^ 0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform sample in `[0, 1)`.
+    pub(crate) fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Sample from the standard normal distribution.
+    fn normal(&mut self) -> f64 {
+        // Box-Muller transform
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// The oscillator of the triggerbox.
+///
+/// The oscillator runs slightly fast or slow compared to the host clock and
+/// its frequency error wanders over time, like a real crystal.
+#[derive(Debug)]
+struct Oscillator {
+    /// Current frequency error in parts per million.
+    ppm: f64,
+    random_walk_ppm: f64,
+    /// Host time of the last update, in seconds.
+    last_host_secs: f64,
+    /// Elapsed device time at `last_host_secs`, in seconds.
+    device_secs: f64,
+}
+
+impl Oscillator {
+    /// Advance to `host_secs` and return the elapsed device time.
+    fn advance(&mut self, host_secs: f64, rng: &mut Rng) -> f64 {
+        let dt = host_secs - self.last_host_secs;
+        if dt > 0.0 {
+            self.device_secs += dt * (1.0 + self.ppm * 1e-6);
+            self.ppm += self.random_walk_ppm * dt.sqrt() * rng.normal();
+            self.last_host_secs = host_secs;
+        }
+        self.device_secs
+    }
+}
+
+/// Emulated triggerbox state.
+#[derive(Debug)]
+pub(crate) struct Device {
+    oscillator: Oscillator,
+    /// Pulse period in seconds of device time.
+    period: f64,
+    /// Device time at which pulses were started, or `None` if stopped.
+    started: Option<f64>,
+    /// Pulse number and phase when pulses were last stopped.
+    stopped_at: (u32, u8),
+    pub(crate) rng: Rng,
+}
+
+impl Device {
+    pub(crate) fn new(drift_ppm: f64, drift_random_walk_ppm: f64, seed: u64) -> Self {
+        Self {
+            oscillator: Oscillator {
+                ppm: drift_ppm,
+                random_walk_ppm: drift_random_walk_ppm,
+                last_host_secs: 0.0,
+                device_secs: 0.0,
+            },
+            // Power-on default of the firmware: 100 Hz
+            period: 8.0 * 20_000.0 / protocol::OSCILLATOR_HZ,
+            started: None,
+            stopped_at: (0, 0),
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Pulse number and `tcnt` at device time `device_secs`.
+    fn counter(&self, device_secs: f64) -> (u32, u8) {
+        match self.started {
+            None => self.stopped_at,
+            Some(start) => {
+                let n_periods = ((device_secs - start) / self.period).max(0.0);
+                let tcnt = (n_periods.fract() * 255.0) as u8;
+                (n_periods.trunc() as u32, tcnt)
+            }
+        }
+    }
+
+    /// Pulse number of the last trigger pulse output at host time
+    /// `host_secs`, or `None` if no pulse was output since pulses were
+    /// started.
+    pub(crate) fn last_output_pulse(&mut self, host_secs: f64) -> Option<u32> {
+        let device_secs = self.oscillator.advance(host_secs, &mut self.rng);
+        self.started?;
+        let (pulse_number, _) = self.counter(device_secs);
+        (pulse_number >= FIRST_OUTPUT_PULSE).then_some(pulse_number)
+    }
+
+    /// Handle `cmd` received at host time `host_secs` and return the reply.
+    pub(crate) fn handle(&mut self, cmd: Command, host_secs: f64) -> Option<Vec<u8>> {
+        let device_secs = self.oscillator.advance(host_secs, &mut self.rng);
+        match cmd {
+            Command::Version => Some(protocol::encode_version()),
+            Command::StopPulsesAndReset => {
+                tracing::debug!("stopping pulses");
+                self.started = None;
+                self.stopped_at = (0, 0);
+                None
+            }
+            Command::StartPulses => {
+                tracing::debug!("starting pulses");
+                if self.started.is_none() {
+                    // Continue counting from the stopped pulse number.
+                    let (pulse_number, _) = self.stopped_at;
+                    self.started = Some(device_secs - pulse_number as f64 * self.period);
+                }
+                None
+            }
+            Command::SetClock { top, prescaler } => {
+                self.set_clock(top, prescaler, device_secs);
+                None
+            }
+            Command::SetAout { aout0, aout1 } => {
+                tracing::debug!("analog output: {aout0}, {aout1}");
+                None
+            }
+            Command::Query(query_id) => {
+                let (pulse_number, tcnt) = self.counter(device_secs);
+                Some(protocol::encode_query_reply(query_id, pulse_number, tcnt))
+            }
+        }
+    }
+
+    fn set_clock(&mut self, top: u16, prescaler: Prescaler, device_secs: f64) {
+        let counter = self.counter(device_secs);
+        self.period = prescaler.divisor() * (f64::from(top) + 1.0) / protocol::OSCILLATOR_HZ;
+        tracing::info!("pulse frequency set to {} Hz", 1.0 / self.period);
+        if self.started.is_some() {
+            // Keep the pulse number continuous across the change.
+            let (pulse_number, _) = counter;
+            self.started = Some(device_secs - pulse_number as f64 * self.period);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Command::*;
+
+    fn query(device: &mut Device, host_secs: f64) -> (u32, u8) {
+        let reply = device.handle(Query(1), host_secs).unwrap();
+        assert_eq!(&reply[..2], b"P\x01");
+        (
+            u32::from_le_bytes(reply[2..6].try_into().unwrap()),
+            reply[6],
+        )
+    }
+
+    #[test]
+    fn test_pulses_and_reset() {
+        let mut device = Device::new(0.0, 0.0, 0);
+        let top = 20_000 - 1; // 100 Hz
+        assert_eq!(
+            device.handle(
+                SetClock {
+                    top,
+                    prescaler: Prescaler::Scale8
+                },
+                0.0
+            ),
+            None
+        );
+        assert_eq!(query(&mut device, 1.0), (0, 0));
+        device.handle(StartPulses, 1.0);
+        assert_eq!(query(&mut device, 1.505), (50, 127));
+        device.handle(StopPulsesAndReset, 2.0);
+        assert_eq!(query(&mut device, 3.0), (0, 0));
+        assert_eq!(device.last_output_pulse(3.0), None);
+        device.handle(StartPulses, 3.0);
+        assert_eq!(device.last_output_pulse(3.015), None);
+        assert_eq!(device.last_output_pulse(3.025), Some(2));
+        assert_eq!(query(&mut device, 4.005).0, 100);
+    }
+
+    #[test]
+    fn test_drift() {
+        let mut device = Device::new(100.0, 0.0, 0);
+        device.handle(StartPulses, 0.0);
+        // After 1000 seconds, a device running 100 ppm fast has generated 10
+        // additional pulses at 100 Hz.
+        assert_eq!(query(&mut device, 1000.004).0, 100_010);
+    }
+
+    #[test]
+    fn test_rng_uniform() {
+        let mut rng = Rng::new(42);
+        let mean = (0..10_000).map(|_| rng.uniform()).sum::<f64>() / 10_000.0;
+        assert!((mean - 0.5).abs() < 0.02);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use crate::{device::Device, protocol::Parser, pty::Pty, EmulatorConfig, Error, Result};
+
+/// A running triggerbox emulator.
+///
+/// The emulator runs in a background thread until it is stopped or dropped.
+pub struct Emulator {
+    device_path: PathBuf,
+    device: Arc<Mutex<Device>>,
+    t0: Instant,
+    quit: Arc<AtomicBool>,
+    join_handle: Option<std::thread::JoinHandle<Result<()>>>,
+}
+
+impl Emulator {
+    /// Allocate a pseudo-terminal and start emulating a triggerbox on it.
+    pub fn start(cfg: EmulatorConfig) -> Result<Self> {
+        let pty = Pty::open()?;
+        let device_path = pty.slave_path.clone();
+        tracing::info!("triggerbox emulator listening on {}", device_path.display());
+        let device = Arc::new(Mutex::new(Device::new(
+            cfg.drift_ppm,
+            cfg.drift_random_walk_ppm,
+            cfg.seed,
+        )));
+        let t0 = Instant::now();
+        let quit = Arc::new(AtomicBool::new(false));
+        let join_handle = {
+            let device = device.clone();
+            let quit = quit.clone();
+            std::thread::Builder::new()
+                .name("triggerbox-emulator".to_string())
+                .spawn(move || run(pty, cfg, device, t0, quit))?
+        };
+        Ok(Self {
+            device_path,
+            device,
+            t0,
+            quit,
+            join_handle: Some(join_handle),
+        })
+    }
+
+    /// Path of the emulated serial device.
+    pub fn device_path(&self) -> &Path {
+        &self.device_path
+    }
+
+    /// Pulse number of the last trigger pulse output by the emulated device.
+    ///
+    /// Returns `None` while pulses are stopped and after they are started
+    /// until the first pulse. As with the hardware, the first pulse has pulse
+    /// number 2. Polling this simulates a camera triggered by the device.
+    pub fn last_trigger_pulse(&self) -> Option<u32> {
+        let host_secs = self.t0.elapsed().as_secs_f64();
+        self.device.lock().unwrap().last_output_pulse(host_secs)
+    }
+
+    /// Stop the emulator and return any error which occurred while running.
+    pub fn stop(mut self) -> Result<()> {
+        self.stop_inner()
+    }
+
+    fn stop_inner(&mut self) -> Result<()> {
+        self.quit.store(true, Ordering::SeqCst);
+        match self.join_handle.take() {
+            Some(join_handle) => join_handle.join().map_err(|_| Error::ThreadPanicked)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop_inner() {
+            tracing::error!("triggerbox emulator: {e}");
+        }
+    }
+}
+
+fn run(
+    mut pty: Pty,
+    cfg: EmulatorConfig,
+    device: Arc<Mutex<Device>>,
+    t0: Instant,
+    quit: Arc<AtomicBool>,
+) -> Result<()> {
+    let mut parser = Parser::default();
+    let mut buf = [0u8; 256];
+    while !quit.load(Ordering::SeqCst) {
+        if !pty.poll_readable(50)? {
+            continue;
+        }
+        let n = pty.master.read(&mut buf)?;
+        parser.push(&buf[..n]);
+        while let Some(cmd) = parser.next_command() {
+            tracing::trace!("received {cmd:?}");
+            // The device samples its counter when the command arrives. The
+            // reply is then delayed on its way to the host.
+            let host_secs = t0.elapsed().as_secs_f64();
+            let reply = {
+                let mut device = device.lock().unwrap();
+                device
+                    .handle(cmd, host_secs)
+                    .map(|reply| (reply, device.rng.uniform()))
+            };
+            if let Some((reply, u)) = reply {
+                let latency = cfg.max_reply_latency.mul_f64(u);
+                std::thread::sleep(latency);
+                pty.master.write_all(&reply)?;
+                pty.master.flush()?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Software emulator of the [Straw Lab
+//! triggerbox](https://github.com/strawlab/triggerbox).
+//!
+//! The emulator speaks the triggerbox serial protocol on a pseudo-terminal, so
+//! that Braid can be run with `trigger_type = "TriggerboxV1"` without hardware.
+//! Set `device_fname` in the Braid configuration to the path returned by
+//! [Emulator::device_path] (or to a symlink to it).
+//!
+//! The emulated oscillator runs slightly fast or slow relative to the host
+//! clock and its frequency error slowly wanders. Replies to queries are
+//! delayed by a random latency. Thus, the clock model fitting and
+//! synchronization code of Braid sees realistic timestamps.
+//!
+//! Only Linux is supported.
+
+use std::time::Duration;
+
+pub mod protocol;
+
+#[cfg(target_os = "linux")]
+mod device;
+#[cfg(target_os = "linux")]
+mod emulator;
+#[cfg(target_os = "linux")]
+mod pty;
+
+#[cfg(target_os = "linux")]
+pub use emulator::Emulator;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("emulator thread panicked")]
+    ThreadPanicked,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Configuration of the emulated triggerbox.
+#[derive(Debug, Clone)]
+pub struct EmulatorConfig {
+    /// Frequency error of the emulated oscillator in parts per million.
+    ///
+    /// Positive values make the triggerbox run fast relative to the host.
+    pub drift_ppm: f64,
+    /// Random walk of the frequency error in ppm per square root second.
+    pub drift_random_walk_ppm: f64,
+    /// Maximum latency before replying to a query.
+    ///
+    /// The actual latency of each reply is uniformly distributed between zero
+    /// and this value.
+    pub max_reply_latency: Duration,
+    /// Seed of the pseudo-random number generator.
+    pub seed: u64,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            drift_ppm: 30.0,
+            drift_random_walk_ppm: 0.1,
+            max_reply_latency: Duration::from_millis(2),
+            seed: 0,
+        }
+    }
+}
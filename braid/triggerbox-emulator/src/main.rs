@@ -0,0 +1,76 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Emulate a Straw Lab triggerbox on a pseudo-terminal.
+#[derive(Parser)]
+#[command(author, version, about)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct Cli {
+    /// Create a symlink at this path pointing to the emulated device
+    #[arg(long)]
+    link: Option<PathBuf>,
+
+    /// Frequency error of the emulated oscillator, in parts per million
+    #[arg(long, default_value_t = 30.0, allow_negative_numbers = true)]
+    drift_ppm: f64,
+
+    /// Random walk of the frequency error, in ppm per square root second
+    #[arg(long, default_value_t = 0.1)]
+    drift_random_walk_ppm: f64,
+
+    /// Maximum latency before replying to a query, in microseconds
+    #[arg(long, default_value_t = 2000)]
+    max_reply_latency_usec: u64,
+
+    /// Seed of the pseudo-random number generator
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    use braid_triggerbox_emulator::{Emulator, EmulatorConfig};
+
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+
+    let cli = Cli::parse();
+
+    let cfg = EmulatorConfig {
+        drift_ppm: cli.drift_ppm,
+        drift_random_walk_ppm: cli.drift_random_walk_ppm,
+        max_reply_latency: std::time::Duration::from_micros(cli.max_reply_latency_usec),
+        seed: cli.seed,
+    };
+    let emulator = Emulator::start(cfg)?;
+
+    if let Some(link) = &cli.link {
+        if link
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            // Remove stale link from a previous run.
+            std::fs::remove_file(link)?;
+        }
+        std::os::unix::fs::symlink(emulator.device_path(), link)?;
+        tracing::info!("{} -> {}", link.display(), emulator.device_path().display());
+    }
+
+    tokio::signal::ctrl_c().await?;
+    tracing::info!("quitting");
+
+    if let Some(link) = &cli.link {
+        std::fs::remove_file(link)?;
+    }
+    emulator.stop()?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() -> eyre::Result<()> {
+    let _cli = Cli::parse();
+    eyre::bail!("The triggerbox emulator is only supported on Linux.");
+}
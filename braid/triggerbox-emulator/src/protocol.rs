@@ -0,0 +1,180 @@
+//! Serial protocol of the triggerbox firmware.
+//!
+//! Commands sent by the host:
+//!
+//! - `V?`: query the firmware version. Reply: `V=<version>\r\n`.
+//! - `S0`: stop pulses and reset the pulse counter.
+//! - `S1`: start pulses.
+//! - `T=<top: u16><prescaler key: u8>`: set the pulse period to
+//!   `prescaler * (top + 1)` ticks of the 16 MHz oscillator. The prescaler key
+//!   is `b'1'` for a prescaler of 8 and `b'2'` for a prescaler of 64.
+//! - `O=<aout0: u16><aout1: u16>`: set the analog outputs.
+//! - `P<query id: u8>`: query the pulse counter. Reply:
+//!   `P<query id: u8><pulse number: u32><tcnt: u8>` where `tcnt / 255` is the
+//!   fraction of the current pulse period which has elapsed.
+//!
+//! All multi-byte integers are little endian.
+
+/// Firmware version reported to the host.
+pub const FIRMWARE_VERSION: u8 = 14;
+
+/// Frequency of the triggerbox oscillator.
+pub const OSCILLATOR_HZ: f64 = 16e6;
+
+/// Timer prescaler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prescaler {
+    Scale8,
+    Scale64,
+}
+
+impl Prescaler {
+    fn from_key(key: u8) -> Option<Self> {
+        match key {
+            b'1' => Some(Self::Scale8),
+            b'2' => Some(Self::Scale64),
+            _ => None,
+        }
+    }
+
+    pub fn divisor(&self) -> f64 {
+        match self {
+            Self::Scale8 => 8.0,
+            Self::Scale64 => 64.0,
+        }
+    }
+}
+
+/// A command received from the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Version,
+    StopPulsesAndReset,
+    StartPulses,
+    SetClock { top: u16, prescaler: Prescaler },
+    SetAout { aout0: u16, aout1: u16 },
+    Query(u8),
+}
+
+/// Incremental parser of the byte stream sent by the host.
+#[derive(Debug, Default)]
+pub struct Parser {
+    buf: Vec<u8>,
+}
+
+impl Parser {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Return the next complete command, if any.
+    ///
+    /// Unknown bytes are skipped with a warning.
+    pub fn next_command(&mut self) -> Option<Command> {
+        loop {
+            let (cmd, len) = match self.buf.as_slice() {
+                [] => return None,
+                [b'V', rest @ ..] => match rest {
+                    [] => return None,
+                    [b'?', ..] => (Some(Command::Version), 2),
+                    _ => (None, 1),
+                },
+                [b'S', rest @ ..] => match rest {
+                    [] => return None,
+                    [b'0', ..] => (Some(Command::StopPulsesAndReset), 2),
+                    [b'1', ..] => (Some(Command::StartPulses), 2),
+                    _ => (None, 1),
+                },
+                [b'T', rest @ ..] => match rest {
+                    [] | [b'=', ..] if rest.len() < 4 => return None,
+                    [b'=', t0, t1, key, ..] => {
+                        let top = u16::from_le_bytes([*t0, *t1]);
+                        match Prescaler::from_key(*key) {
+                            Some(prescaler) => (Some(Command::SetClock { top, prescaler }), 5),
+                            None => (None, 1),
+                        }
+                    }
+                    _ => (None, 1),
+                },
+                [b'O', rest @ ..] => match rest {
+                    [] | [b'=', ..] if rest.len() < 5 => return None,
+                    [b'=', a0, a1, b0, b1, ..] => {
+                        let aout0 = u16::from_le_bytes([*a0, *a1]);
+                        let aout1 = u16::from_le_bytes([*b0, *b1]);
+                        (Some(Command::SetAout { aout0, aout1 }), 6)
+                    }
+                    _ => (None, 1),
+                },
+                [b'P', rest @ ..] => match rest {
+                    [] => return None,
+                    [qi, ..] => (Some(Command::Query(*qi)), 2),
+                },
+                _ => (None, 1),
+            };
+            if cmd.is_none() {
+                tracing::warn!("Ignoring unexpected byte {:?} from host.", self.buf[0]);
+            }
+            self.buf.drain(..len);
+            if cmd.is_some() {
+                return cmd;
+            }
+        }
+    }
+}
+
+/// Encode the reply to a version query.
+pub fn encode_version() -> Vec<u8> {
+    format!("V={FIRMWARE_VERSION}\r\n").into_bytes()
+}
+
+/// Encode the reply to a pulse counter query.
+pub fn encode_query_reply(query_id: u8, pulse_number: u32, tcnt: u8) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(7);
+    buf.push(b'P');
+    buf.push(query_id);
+    buf.extend_from_slice(&pulse_number.to_le_bytes());
+    buf.push(tcnt);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let mut parser = Parser::default();
+        let mut data = b"V?S0T=".to_vec();
+        data.extend_from_slice(&1234u16.to_le_bytes());
+        data.extend_from_slice(b"1S1P\x07");
+        // Feed one byte at a time to check that partial commands are kept.
+        let mut cmds = Vec::new();
+        for byte in data {
+            parser.push(&[byte]);
+            while let Some(cmd) = parser.next_command() {
+                cmds.push(cmd);
+            }
+        }
+        assert_eq!(
+            cmds,
+            vec![
+                Command::Version,
+                Command::StopPulsesAndReset,
+                Command::SetClock {
+                    top: 1234,
+                    prescaler: Prescaler::Scale8
+                },
+                Command::StartPulses,
+                Command::Query(7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_garbage() {
+        let mut parser = Parser::default();
+        parser.push(b"\r\nxS9P\x01");
+        assert_eq!(parser.next_command(), Some(Command::Query(1)));
+        assert_eq!(parser.next_command(), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Pseudo-terminal allocation.
+
+use std::{
+    ffi::CStr,
+    fs::File,
+    io,
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, FromRawFd},
+    },
+    path::PathBuf,
+};
+
+pub(crate) struct Pty {
+    pub(crate) master: File,
+    /// The slave side is kept open so that reading the master does not fail
+    /// while no host is connected.
+    _slave: File,
+    pub(crate) slave_path: PathBuf,
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl Pty {
+    pub(crate) fn open() -> io::Result<Self> {
+        // SAFETY: the file descriptor is checked and immediately owned by a
+        // `File`. `ptsname_r` writes a NUL terminated string into `name_buf`.
+        let (master, slave_path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            check(libc::grantpt(fd))?;
+            check(libc::unlockpt(fd))?;
+            let mut name_buf = [0 as libc::c_char; 128];
+            let ret = libc::ptsname_r(fd, name_buf.as_mut_ptr(), name_buf.len());
+            if ret != 0 {
+                return Err(io::Error::from_raw_os_error(ret));
+            }
+            let name = CStr::from_ptr(name_buf.as_ptr());
+            let slave_path = PathBuf::from(name.to_string_lossy().into_owned());
+            (master, slave_path)
+        };
+
+        let slave = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&slave_path)?;
+        set_raw(&slave)?;
+
+        Ok(Self {
+            master,
+            _slave: slave,
+            slave_path,
+        })
+    }
+
+    /// Wait up to `timeout_msec` for data from the host.
+    pub(crate) fn poll_readable(&self, timeout_msec: i32) -> io::Result<bool> {
+        let mut pfd = libc::pollfd {
+            fd: self.master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pfd` is a valid pollfd for the duration of the call.
+        let ret = unsafe { libc::poll(&mut pfd, 1, timeout_msec) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            return Err(err);
+        }
+        Ok(ret > 0 && (pfd.revents & libc::POLLIN) != 0)
+    }
+}
+
+/// Disable echo and line buffering, as the host would for a serial port.
+fn set_raw(file: &File) -> io::Result<()> {
+    let fd = file.as_raw_fd();
+    // SAFETY: `termios` is plain data and is initialized by `tcgetattr`.
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        check(libc::tcgetattr(fd, &mut termios))?;
+        libc::cfmakeraw(&mut termios);
+        check(libc::tcsetattr(fd, libc::TCSANOW, &termios))?;
+    }
+    Ok(())
+}
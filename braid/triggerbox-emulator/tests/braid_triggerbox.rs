@@ -0,0 +1,80 @@
+//! Run the `braid-triggerbox` host driver against the emulator.
+#![cfg(target_os = "linux")]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use braid_triggerbox::{make_trig_fps_cmd, ClockModel, Cmd, TriggerboxDevice};
+use braid_triggerbox_emulator::{Emulator, EmulatorConfig};
+
+#[tokio::test]
+async fn test_clock_model_with_drift() {
+    let drift_ppm = 5000.0;
+    let emulator = Emulator::start(EmulatorConfig {
+        drift_ppm,
+        drift_random_walk_ppm: 0.0,
+        max_reply_latency: Duration::from_micros(500),
+        ..Default::default()
+    })
+    .unwrap();
+    let device_fname = emulator.device_path().to_str().unwrap().to_string();
+
+    let clock_models: Arc<Mutex<Vec<ClockModel>>> = Default::default();
+    let on_new_clock_model = {
+        let clock_models = clock_models.clone();
+        Box::new(move |cm: Option<ClockModel>| {
+            if let Some(cm) = cm {
+                clock_models.lock().unwrap().push(cm);
+            }
+        })
+    };
+
+    let (tx, cmd_rx) = tokio::sync::mpsc::channel(20);
+    let (data_tx, mut data_rx) = tokio::sync::mpsc::channel(20);
+    let (rate_cmd, rate_actual) = make_trig_fps_cmd(100.0);
+    tx.send(Cmd::StopPulsesAndReset).await.unwrap();
+    tx.send(rate_cmd).await.unwrap();
+    tx.send(Cmd::StartPulses).await.unwrap();
+
+    let triggerbox = TriggerboxDevice::new(
+        on_new_clock_model,
+        device_fname,
+        cmd_rx,
+        Some(data_tx),
+        None,
+        Duration::from_millis(20),
+        Duration::from_millis(100),
+    )
+    .await
+    .unwrap();
+    let join_handle = tokio::spawn(triggerbox.run_forever(Duration::from_millis(100)));
+
+    let first_row = tokio::time::timeout(Duration::from_secs(10), data_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut last_row = first_row;
+    while clock_models.lock().unwrap().len() < 20 {
+        last_row = tokio::time::timeout(Duration::from_secs(10), data_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    assert!(last_row.framecount > 0);
+    join_handle.abort();
+
+    // The emulated triggerbox runs fast, so each pulse takes less host time.
+    // The tolerance is a fraction of the drift so that a gain with the drift
+    // missing or of the wrong sign fails.
+    let expected_gain = 1.0 / (rate_actual * (1.0 + drift_ppm * 1e-6));
+    let gain = clock_models.lock().unwrap().last().unwrap().gain;
+    let rel_err = (gain - expected_gain).abs() / expected_gain;
+    assert!(
+        rel_err < 0.2 * drift_ppm * 1e-6,
+        "gain {gain} expected {expected_gain}"
+    );
+
+    emulator.stop().unwrap();
+}
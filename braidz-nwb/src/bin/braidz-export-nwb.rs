@@ -0,0 +1,64 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+struct Opt {
+    /// Output NWB filename. Defaults to "<INPUT>.nwb"
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Session description stored in the NWB file.
+    #[arg(long)]
+    session_description: Option<String>,
+
+    /// Identifier stored in the NWB file. Defaults to a random UUID.
+    #[arg(long)]
+    identifier: Option<String>,
+
+    /// Input filenames (one .braidz file and optionally .mp4 files)
+    inputs: Vec<PathBuf>,
+}
+
+fn main() -> eyre::Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+    let opt = Opt::parse();
+
+    let (braidz_inputs, other_inputs): (Vec<_>, Vec<_>) = opt
+        .inputs
+        .into_iter()
+        .partition(|x| x.as_os_str().to_string_lossy().ends_with(".braidz"));
+    if braidz_inputs.len() != 1 {
+        eyre::bail!(
+            "expected exactly one .braidz file, found {}",
+            braidz_inputs.len()
+        );
+    }
+    let input_braidz = &braidz_inputs[0];
+    if let Some(x) = other_inputs
+        .iter()
+        .find(|x| !x.as_os_str().to_string_lossy().ends_with(".mp4"))
+    {
+        eyre::bail!(
+            "expected only mp4 inputs beyond one .braidz file, found {}",
+            x.display()
+        );
+    }
+
+    let output = opt.output.unwrap_or_else(|| {
+        let mut output = input_braidz.as_os_str().to_owned();
+        output.push(".nwb");
+        output.into()
+    });
+
+    let opts = braidz_nwb::ExportOptions {
+        session_description: opt.session_description,
+        identifier: opt.identifier,
+    };
+    braidz_nwb::braidz_to_nwb(input_braidz, &other_inputs, &output, &opts)?;
+    tracing::info!("Saved {}", output.display());
+    Ok(())
+}
@@ -0,0 +1,419 @@
+//! Export `.braidz` files (and, optionally, their MP4 videos) to [Neurodata
+//! Without Borders (NWB)](https://nwb.org) files.
+//!
+//! See the README for a description of the layout of the resulting file.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, WrapErr};
+use ndarray::{Array1, Array2};
+
+use braid_types::KalmanEstimatesRow;
+use frame_source::FrameSourceBuilder;
+
+mod nwb;
+use nwb::TimeSeriesInfo;
+
+mod timing;
+use timing::FrameTimeModel;
+
+/// Options for [braidz_to_nwb].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Stored as `session_description`. Defaults to a description derived from
+    /// the input filename.
+    pub session_description: Option<String>,
+    /// Stored as `identifier`. Defaults to a random UUID.
+    pub identifier: Option<String>,
+}
+
+/// A single 2D detection.
+struct Detection {
+    frame: i64,
+    timestamp: Option<f64>,
+    values: [f64; 5],
+}
+
+const DETECTION_COLUMNS: &str = "x, y, area, slope, eccentricity";
+
+/// Convert the `.braidz` file at `braidz_path` into an NWB file at `output`.
+///
+/// Each path in `mp4_inputs` is added as an external `ImageSeries`. The camera
+/// name is determined from the MP4 filename.
+pub fn braidz_to_nwb<P: AsRef<Path>, Q: AsRef<Path>>(
+    braidz_path: P,
+    mp4_inputs: &[PathBuf],
+    output: Q,
+    opts: &ExportOptions,
+) -> Result<()> {
+    let braidz_path = braidz_path.as_ref();
+    let output = output.as_ref();
+    let mut archive = braidz_parser::braidz_parse_path(braidz_path)
+        .with_context(|| format!("Parsing file {}", braidz_path.display()))?;
+
+    // Read the 2D detections and collect (frame, timestamp) pairs for the
+    // timing model.
+    let mut detections: BTreeMap<String, Vec<Detection>> = BTreeMap::new();
+    let mut trigger_times: BTreeMap<i64, f64> = BTreeMap::new();
+    let mut host_times: BTreeMap<i64, f64> = BTreeMap::new();
+    // Per camera and frame, the triggerbox timestamp minus the host timestamp.
+    let mut clock_diffs: BTreeMap<String, BTreeMap<i64, f64>> = BTreeMap::new();
+    {
+        let camn2camid = archive.cam_info.camn2camid.clone();
+        for row in archive.iter_data2d_distorted()? {
+            let row = row?;
+            let timestamp = row.timestamp.as_ref().map(|t| t.as_f64());
+            if let Some(t) = timestamp {
+                trigger_times.entry(row.frame).or_insert(t);
+            }
+            host_times
+                .entry(row.frame)
+                .or_insert(row.cam_received_timestamp.as_f64());
+            if let (Some(t), Some(cam_name)) = (timestamp, camn2camid.get(&row.camn)) {
+                clock_diffs
+                    .entry(cam_name.clone())
+                    .or_default()
+                    .entry(row.frame)
+                    .or_insert(t - row.cam_received_timestamp.as_f64());
+            }
+            if row.x.is_nan() {
+                // Frame without detection.
+                continue;
+            }
+            let Some(cam_name) = camn2camid.get(&row.camn) else {
+                tracing::warn!("Unknown camera number {}. Skipping row.", row.camn.0);
+                continue;
+            };
+            detections
+                .entry(cam_name.clone())
+                .or_default()
+                .push(Detection {
+                    frame: row.frame,
+                    timestamp,
+                    values: [row.x, row.y, row.area, row.slope, row.eccentricity],
+                });
+        }
+    }
+    for dets in detections.values_mut() {
+        dets.sort_by_key(|d| d.frame);
+    }
+
+    let time_model = {
+        let trigger_times: Vec<_> = trigger_times.into_iter().collect();
+        let host_times: Vec<_> = host_times.into_iter().collect();
+        if trigger_times.is_empty() {
+            tracing::warn!("No triggerbox timestamps. Using host clock timestamps.");
+        }
+        let times = if trigger_times.is_empty() {
+            &host_times
+        } else {
+            &trigger_times
+        };
+        FrameTimeModel::fit(times).or_else(|| {
+            times
+                .first()
+                .map(|(f, t)| FrameTimeModel::from_fps(*f, *t, archive.expected_fps))
+        })
+    };
+
+    let session_start_time: chrono::DateTime<chrono::FixedOffset> =
+        match &archive.metadata.original_recording_time {
+            Some(t) => t.fixed_offset(),
+            None => {
+                let t0 = time_model
+                    .as_ref()
+                    .and_then(|m| {
+                        archive
+                            .data2d_distorted
+                            .as_ref()
+                            .map(|d| m.timestamp(d.frame_lim[0] as i64))
+                    })
+                    .unwrap_or(0.0);
+                strand_datetime_conversion::f64_to_datetime(t0).fixed_offset()
+            }
+        };
+    let t_ref = strand_datetime_conversion::datetime_to_f64(&session_start_time);
+    let to_session_time = |frame: i64, timestamp: Option<f64>| -> f64 {
+        timestamp
+            .or_else(|| time_model.as_ref().map(|m| m.timestamp(frame)))
+            .unwrap_or(f64::NAN)
+            - t_ref
+    };
+
+    // Create the file with the required top-level layout.
+    let file = hdf5::File::create(output)
+        .with_context(|| format!("Creating output file {}", output.display()))?;
+    nwb::str_attr(&file, "nwb_version", nwb::NWB_VERSION)?;
+    nwb::set_neurodata_type(&file, "NWBFile")?;
+
+    let identifier = opts
+        .identifier
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let session_description = opts.session_description.clone().unwrap_or_else(|| {
+        format!(
+            "Braid tracking data from {}",
+            braidz_path
+                .file_name()
+                .map(|x| x.to_string_lossy())
+                .unwrap_or_default()
+        )
+    });
+    let start_str = session_start_time.to_rfc3339();
+    let now_str = chrono::Local::now().fixed_offset().to_rfc3339();
+    nwb::str_dataset(&file, "identifier", &identifier)?;
+    nwb::str_dataset(&file, "session_description", &session_description)?;
+    nwb::str_dataset(&file, "session_start_time", &start_str)?;
+    nwb::str_dataset(&file, "timestamps_reference_time", &start_str)?;
+    nwb::str_array_dataset(&file, "file_create_date", &[&now_str])?;
+
+    let acquisition = file.create_group("acquisition")?;
+    file.create_group("analysis")?;
+    let processing = file.create_group("processing")?;
+    let stimulus = file.create_group("stimulus")?;
+    stimulus.create_group("presentation")?;
+    stimulus.create_group("templates")?;
+    let general = file.create_group("general")?;
+    let devices = general.create_group("devices")?;
+
+    {
+        let md = &archive.metadata;
+        let mut notes = format!(
+            "Saved by {} (git revision {}). Expected frame rate: {} frames per second.",
+            md.saving_program_name, md.git_revision, archive.expected_fps
+        );
+        if let Some(n) = archive.calibration_info.as_ref().and_then(|c| c.water) {
+            notes.push_str(&format!(" Refractive index at z<0: {n}."));
+        }
+        if let Some(ci) = archive.calibration_info.as_ref() {
+            for iface in ci.refractive_interfaces.iter() {
+                let normal = iface.normal();
+                notes.push_str(&format!(
+                    " Refractive interface with normal ({}, {}, {}) at offset {}: n={} to n={}.",
+                    normal.x,
+                    normal.y,
+                    normal.z,
+                    iface.offset(),
+                    iface.n1(),
+                    iface.n2()
+                ));
+            }
+        }
+        nwb::str_dataset(&general, "notes", &notes)?;
+    }
+
+    // Cameras
+    for cam_name in archive.cam_info.camid2camn.keys() {
+        let device = nwb::typed_group(&devices, cam_name, "Device")?;
+        let cam = archive
+            .calibration_info
+            .as_ref()
+            .and_then(|c| c.cameras.cam_by_name(cam_name));
+        let description = match cam {
+            Some(cam) => serde_json::to_string(&cam.to_pymvg(cam_name))?,
+            None => "uncalibrated camera".to_string(),
+        };
+        nwb::str_attr(&device, "description", &description)?;
+    }
+
+    let behavior = nwb::typed_group(&processing, "behavior", "ProcessingModule")?;
+    nwb::str_attr(&behavior, "description", "Tracking data from Braid")?;
+
+    // 3D tracking
+    if let Some(kest) = &archive.kalman_estimates_table {
+        let mut by_obj: BTreeMap<u32, Vec<&KalmanEstimatesRow>> = BTreeMap::new();
+        for row in kest.iter() {
+            by_obj.entry(row.obj_id).or_default().push(row);
+        }
+        let position = nwb::typed_group(&behavior, "Position", "Position")?;
+        let velocity = nwb::typed_group(&behavior, "Velocity", "BehavioralTimeSeries")?;
+        for (obj_id, rows) in by_obj.iter() {
+            let name = format!("obj_id_{obj_id}");
+            let timestamps: Vec<f64> = rows
+                .iter()
+                .map(|r| {
+                    to_session_time(r.frame.0 as i64, r.timestamp.as_ref().map(|t| t.as_f64()))
+                })
+                .collect();
+            let pos = Array2::from_shape_fn((rows.len(), 3), |(i, j)| {
+                let r = rows[i];
+                [r.x, r.y, r.z][j]
+            });
+            let vel = Array2::from_shape_fn((rows.len(), 3), |(i, j)| {
+                let r = rows[i];
+                [r.xvel, r.yvel, r.zvel][j]
+            });
+            let series = nwb::time_series(
+                &position,
+                &name,
+                &TimeSeriesInfo {
+                    neurodata_type: "SpatialSeries",
+                    description: &format!("Kalman filter position estimate of object {obj_id}"),
+                    comments: "columns: x, y, z",
+                    unit: "meters",
+                },
+                Some(pos.view()),
+                &timestamps,
+            )?;
+            nwb::str_dataset(
+                &series,
+                "reference_frame",
+                "Braid calibration world coordinates",
+            )?;
+            nwb::time_series(
+                &velocity,
+                &name,
+                &TimeSeriesInfo {
+                    neurodata_type: "TimeSeries",
+                    description: &format!("Kalman filter velocity estimate of object {obj_id}"),
+                    comments: "columns: xvel, yvel, zvel",
+                    unit: "meters/second",
+                },
+                Some(vel.view()),
+                &timestamps,
+            )?;
+        }
+    }
+
+    // 2D detections
+    let detections_group = nwb::typed_group(&behavior, "Detections2D", "BehavioralTimeSeries")?;
+    for (cam_name, dets) in detections.iter() {
+        let timestamps: Vec<f64> = dets
+            .iter()
+            .map(|d| to_session_time(d.frame, d.timestamp))
+            .collect();
+        let data = Array2::from_shape_fn((dets.len(), 5), |(i, j)| dets[i].values[j]);
+        let series = nwb::time_series(
+            &detections_group,
+            cam_name,
+            &TimeSeriesInfo {
+                neurodata_type: "TimeSeries",
+                description: &format!(
+                    "2D detections (distorted pixel coordinates) of camera {cam_name}"
+                ),
+                comments: &format!("columns: {DETECTION_COLUMNS}"),
+                unit: "pixels",
+            },
+            Some(data.view()),
+            &timestamps,
+        )?;
+        let frames: Array1<i64> = dets.iter().map(|d| d.frame).collect();
+        series
+            .new_dataset_builder()
+            .with_data(&frames)
+            .create("braid_frame")?;
+    }
+
+    // Videos
+    let clock_offsets: BTreeMap<String, f64> = clock_diffs
+        .into_iter()
+        .filter_map(|(cam_name, diffs)| {
+            timing::median(diffs.into_values().collect()).map(|d| (cam_name, d))
+        })
+        .collect();
+    for mp4_path in mp4_inputs.iter() {
+        add_video(
+            &acquisition,
+            &devices,
+            mp4_path,
+            output,
+            t_ref,
+            &clock_offsets,
+        )
+        .with_context(|| format!("Adding video {}", mp4_path.display()))?;
+    }
+
+    file.close()?;
+    Ok(())
+}
+
+/// Add the MP4 video at `mp4_path` as an external `ImageSeries`.
+///
+/// The frame timestamps in the video are from the host clock. They are
+/// converted to triggerbox time by the camera's entry in `clock_offsets`, the
+/// median difference of the triggerbox and host timestamps of its frames in
+/// the `.braidz` file. Without an entry, the host clock timestamps are kept.
+fn add_video(
+    acquisition: &hdf5::Group,
+    devices: &hdf5::Group,
+    mp4_path: &Path,
+    output: &Path,
+    t_ref: f64,
+    clock_offsets: &BTreeMap<String, f64>,
+) -> Result<()> {
+    let (file_name, cam_name) = braidz_types::camera_name_from_filename(mp4_path);
+    let clock_offset = cam_name
+        .as_ref()
+        .and_then(|c| clock_offsets.get(c))
+        .copied();
+    let comments = if clock_offset.is_some() {
+        "Timestamps are from the clock of the computer which recorded the video, \
+        converted to triggerbox time."
+    } else {
+        tracing::warn!(
+            "No triggerbox timestamps for camera of {}. Using host clock timestamps.",
+            mp4_path.display()
+        );
+        "Timestamps are from the clock of the computer which recorded the video."
+    };
+
+    let mut src = FrameSourceBuilder::new(mp4_path)
+        .do_decode_h264(false)
+        .build_source()?;
+    let frame0_time = src
+        .frame0_time()
+        .ok_or_else(|| eyre::eyre!("no start time in video"))?;
+    let t0 = strand_datetime_conversion::datetime_to_f64(&frame0_time) - t_ref
+        + clock_offset.unwrap_or(0.0);
+    let (width, height) = (src.width(), src.height());
+    let mut timestamps = Vec::new();
+    for frame in src.iter() {
+        let frame = frame?;
+        timestamps.push(t0 + frame.timestamp().unwrap_duration().as_secs_f64());
+    }
+    tracing::info!("{}: {} frames", mp4_path.display(), timestamps.len());
+
+    // Refer to the video relative to the NWB file, if possible.
+    let mp4_abs = std::fs::canonicalize(mp4_path)?;
+    let output_abs = std::fs::canonicalize(output)?;
+    let external_file = if mp4_abs.parent() == output_abs.parent() {
+        file_name.clone()
+    } else {
+        mp4_abs.to_string_lossy().to_string()
+    };
+
+    let name = file_name.trim_end_matches(".mp4");
+    let series = nwb::time_series(
+        acquisition,
+        name,
+        &TimeSeriesInfo {
+            neurodata_type: "ImageSeries",
+            description: &format!(
+                "Video from camera {}",
+                cam_name.as_deref().unwrap_or("(unknown)")
+            ),
+            comments,
+            unit: "",
+        },
+        None,
+        &timestamps,
+    )?;
+    let ext = nwb::str_array_dataset(&series, "external_file", &[&external_file])?;
+    ext.new_attr_builder()
+        .with_data(&[0i32])
+        .create("starting_frame")?;
+    nwb::str_dataset(&series, "format", "external")?;
+    series
+        .new_dataset_builder()
+        .with_data(&[width as i32, height as i32])
+        .create("dimension")?;
+    if let Some(cam_name) = cam_name {
+        if devices.link_exists(&cam_name) {
+            series.link_soft(&format!("/general/devices/{cam_name}"), "device")?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Helpers to write the HDF5 layout defined by the NWB core schema.
+//!
+//! See https://nwb-schema.readthedocs.io/en/latest/format.html
+
+use eyre::Result;
+use hdf5::types::VarLenUnicode;
+use ndarray::{arr0, Array1, ArrayView2};
+
+pub(crate) const NWB_VERSION: &str = "2.7.0";
+
+fn vlu(value: &str) -> Result<VarLenUnicode> {
+    Ok(value.parse()?)
+}
+
+/// Write a scalar string attribute.
+pub(crate) fn str_attr(loc: &hdf5::Location, name: &str, value: &str) -> Result<()> {
+    loc.new_attr::<VarLenUnicode>()
+        .shape(())
+        .create(name)?
+        .write_scalar(&vlu(value)?)?;
+    Ok(())
+}
+
+/// Write a scalar attribute.
+pub(crate) fn scalar_attr<T: hdf5::H5Type>(
+    loc: &hdf5::Location,
+    name: &str,
+    value: T,
+) -> Result<()> {
+    loc.new_attr::<T>()
+        .shape(())
+        .create(name)?
+        .write_scalar(&value)?;
+    Ok(())
+}
+
+/// Set the attributes which mark an HDF5 object as an NWB neurodata type.
+pub(crate) fn set_neurodata_type(loc: &hdf5::Location, neurodata_type: &str) -> Result<()> {
+    str_attr(loc, "namespace", "core")?;
+    str_attr(loc, "neurodata_type", neurodata_type)?;
+    str_attr(loc, "object_id", &uuid::Uuid::new_v4().to_string())?;
+    Ok(())
+}
+
+/// Create a group with the given NWB neurodata type.
+pub(crate) fn typed_group(
+    parent: &hdf5::Group,
+    name: &str,
+    neurodata_type: &str,
+) -> Result<hdf5::Group> {
+    let group = parent.create_group(name)?;
+    set_neurodata_type(&group, neurodata_type)?;
+    Ok(group)
+}
+
+/// Write a scalar string dataset.
+pub(crate) fn str_dataset(parent: &hdf5::Group, name: &str, value: &str) -> Result<hdf5::Dataset> {
+    Ok(parent
+        .new_dataset_builder()
+        .with_data(&arr0(vlu(value)?))
+        .create(name)?)
+}
+
+/// Write a 1D string dataset.
+pub(crate) fn str_array_dataset(
+    parent: &hdf5::Group,
+    name: &str,
+    values: &[&str],
+) -> Result<hdf5::Dataset> {
+    let values = values
+        .iter()
+        .map(|v| vlu(v))
+        .collect::<Result<Array1<_>>>()?;
+    Ok(parent
+        .new_dataset_builder()
+        .with_data(&values)
+        .create(name)?)
+}
+
+/// Description of a `TimeSeries` (or derived type such as `SpatialSeries`).
+pub(crate) struct TimeSeriesInfo<'a> {
+    pub(crate) neurodata_type: &'a str,
+    pub(crate) description: &'a str,
+    pub(crate) comments: &'a str,
+    pub(crate) unit: &'a str,
+}
+
+/// Write a `TimeSeries` (or derived type) with explicit timestamps.
+///
+/// `data`, if given, has one row per timestamp. `timestamps` are in seconds
+/// relative to `timestamps_reference_time`.
+pub(crate) fn time_series(
+    parent: &hdf5::Group,
+    name: &str,
+    info: &TimeSeriesInfo,
+    data: Option<ArrayView2<f64>>,
+    timestamps: &[f64],
+) -> Result<hdf5::Group> {
+    let group = typed_group(parent, name, info.neurodata_type)?;
+    str_attr(&group, "description", info.description)?;
+    str_attr(&group, "comments", info.comments)?;
+
+    if let Some(data) = data {
+        let ds = group.new_dataset_builder().with_data(data).create("data")?;
+        scalar_attr(&ds, "conversion", 1.0f32)?;
+        scalar_attr(&ds, "offset", 0.0f32)?;
+        scalar_attr(&ds, "resolution", -1.0f32)?;
+        str_attr(&ds, "unit", info.unit)?;
+    }
+
+    let ts = group
+        .new_dataset_builder()
+        .with_data(timestamps)
+        .create("timestamps")?;
+    scalar_attr(&ts, "interval", 1i32)?;
+    str_attr(&ts, "unit", "seconds")?;
+    Ok(group)
+}
@@ -0,0 +1,122 @@
+/// Linear model of timestamp as a function of synchronized frame number.
+///
+/// Braid only computes triggerbox timestamps once its clock model has
+/// established itself, so the first frames after synchronization lack a
+/// timestamp. This model is used to fill in such gaps.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FrameTimeModel {
+    /// Seconds per frame.
+    gain: f64,
+    /// Timestamp (in seconds) of frame `frame0`.
+    offset: f64,
+    frame0: i64,
+}
+
+impl FrameTimeModel {
+    /// Fit the model by least squares to `(frame, timestamp)` pairs.
+    ///
+    /// Returns `None` if fewer than two distinct frames are present.
+    pub(crate) fn fit(data: &[(i64, f64)]) -> Option<Self> {
+        let frame0 = data.first()?.0;
+        let n = data.len() as f64;
+        // Center the data to keep precision with epoch timestamps.
+        let t0 = data[0].1;
+        let (sum_x, sum_y) = data.iter().fold((0.0, 0.0), |(sx, sy), (f, t)| {
+            (sx + (f - frame0) as f64, sy + (t - t0))
+        });
+        let mean_x = sum_x / n;
+        let mean_y = sum_y / n;
+        let (sxx, sxy) = data.iter().fold((0.0, 0.0), |(sxx, sxy), (f, t)| {
+            let dx = (f - frame0) as f64 - mean_x;
+            let dy = (t - t0) - mean_y;
+            (sxx + dx * dx, sxy + dx * dy)
+        });
+        if sxx == 0.0 {
+            return None;
+        }
+        let gain = sxy / sxx;
+        let offset = t0 + mean_y - gain * mean_x;
+        Some(Self {
+            gain,
+            offset,
+            frame0,
+        })
+    }
+
+    /// Create a model from a single known frame and the frame rate.
+    pub(crate) fn from_fps(frame0: i64, t0: f64, fps: f64) -> Self {
+        Self {
+            gain: 1.0 / fps,
+            offset: t0,
+            frame0,
+        }
+    }
+
+    /// Compute the timestamp (in seconds) of `frame`.
+    pub(crate) fn timestamp(&self, frame: i64) -> f64 {
+        self.offset + self.gain * (frame - self.frame0) as f64
+    }
+}
+
+/// The median of `values`, or `None` if empty.
+pub(crate) fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    if n == 0 {
+        None
+    } else if n % 2 == 1 {
+        Some(values[n / 2])
+    } else {
+        Some((values[n / 2 - 1] + values[n / 2]) / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_exact() {
+        let fps = 100.0;
+        let t0 = 1_700_000_000.123;
+        let data: Vec<_> = (10..1000)
+            .map(|f| (f, t0 + (f - 10) as f64 / fps))
+            .collect();
+        let model = FrameTimeModel::fit(&data).unwrap();
+        for f in [0, 10, 500, 2000] {
+            let expected = t0 + (f - 10) as f64 / fps;
+            assert!((model.timestamp(f) - expected).abs() < 1e-6);
+        }
+        let model2 = FrameTimeModel::from_fps(10, t0, fps);
+        assert!((model.timestamp(1234) - model2.timestamp(1234)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fit_jitter() {
+        let fps = 50.0;
+        let t0 = 1_700_000_000.0;
+        // Alternating +/- 1 ms jitter averages out.
+        let data: Vec<_> = (0..1000)
+            .map(|f| {
+                let jitter = if f % 2 == 0 { 0.001 } else { -0.001 };
+                (f, t0 + f as f64 / fps + jitter)
+            })
+            .collect();
+        let model = FrameTimeModel::fit(&data).unwrap();
+        assert!((model.timestamp(0) - t0).abs() < 1e-4);
+        assert!((model.timestamp(5000) - (t0 + 100.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fit_degenerate() {
+        assert!(FrameTimeModel::fit(&[]).is_none());
+        assert!(FrameTimeModel::fit(&[(5, 1.0), (5, 1.1)]).is_none());
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(vec![4.0, 1.0, 2.0, 3.0]), Some(2.5));
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use hdf5::types::VarLenUnicode;
+
+const URL_BASE: &str = "https://strawlab-cdn.com/assets/";
+const FNAME: &str = "20201104_174158.braidz";
+const SHA256SUM: &str = "d9e742336cf924f378e49055f3a709e52817ed90385c4f777f443952cf0557d6";
+
+fn member_names(file: &hdf5::File, path: &str) -> BTreeSet<String> {
+    file.group(path)
+        .unwrap()
+        .member_names()
+        .unwrap()
+        .into_iter()
+        .collect()
+}
+
+fn read_str_attr(loc: &hdf5::Location, name: &str) -> String {
+    loc.attr(name)
+        .unwrap()
+        .read_scalar::<VarLenUnicode>()
+        .unwrap()
+        .to_string()
+}
+
+/// Check that the time series at `path` has `n` finite timestamps and `n`
+/// rows of data with `ncols` columns.
+fn check_series(file: &hdf5::File, path: &str, neurodata_type: &str, n: usize, ncols: usize) {
+    let group = file.group(path).unwrap();
+    assert_eq!(read_str_attr(&group, "neurodata_type"), neurodata_type);
+    assert_eq!(group.dataset("data").unwrap().shape(), vec![n, ncols]);
+    let timestamps = group
+        .dataset("timestamps")
+        .unwrap()
+        .read_1d::<f64>()
+        .unwrap();
+    assert_eq!(timestamps.len(), n);
+    assert!(timestamps.iter().all(|t| t.is_finite()), "{path}");
+}
+
+#[test]
+fn test_export_nwb() {
+    download_verify::download_verify(
+        format!("{URL_BASE}/{FNAME}").as_str(),
+        FNAME,
+        &download_verify::Hash::Sha256(SHA256SUM.into()),
+    )
+    .unwrap();
+    let archive = braidz_parser::braidz_parse_path(FNAME).unwrap();
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let output = tmpdir.path().join(format!("{FNAME}.nwb"));
+    let opts = braidz_nwb::ExportOptions {
+        identifier: Some("test-identifier".into()),
+        ..Default::default()
+    };
+    braidz_nwb::braidz_to_nwb(FNAME, &[], &output, &opts).unwrap();
+
+    let file = hdf5::File::open(&output).unwrap();
+    assert_eq!(read_str_attr(&file, "neurodata_type"), "NWBFile");
+    assert_eq!(read_str_attr(&file, "nwb_version"), "2.7.0");
+    let top_level = member_names(&file, "/");
+    for name in [
+        "acquisition",
+        "analysis",
+        "file_create_date",
+        "general",
+        "identifier",
+        "processing",
+        "session_description",
+        "session_start_time",
+        "stimulus",
+        "timestamps_reference_time",
+    ] {
+        assert!(top_level.contains(name), "missing {name}");
+    }
+    let identifier = file
+        .dataset("identifier")
+        .unwrap()
+        .read_scalar::<VarLenUnicode>()
+        .unwrap();
+    assert_eq!(identifier.as_str(), "test-identifier");
+
+    // One device per camera.
+    let cam_names: BTreeSet<String> = archive.cam_info.camid2camn.keys().cloned().collect();
+    assert!(!cam_names.is_empty());
+    assert_eq!(member_names(&file, "general/devices"), cam_names);
+
+    // One position and velocity series per tracked object.
+    let mut num_rows: BTreeMap<String, usize> = BTreeMap::new();
+    for row in archive.kalman_estimates_table.iter().flatten() {
+        *num_rows
+            .entry(format!("obj_id_{}", row.obj_id))
+            .or_default() += 1;
+    }
+    if !num_rows.is_empty() {
+        let obj_names: BTreeSet<String> = num_rows.keys().cloned().collect();
+        assert_eq!(
+            member_names(&file, "processing/behavior/Position"),
+            obj_names
+        );
+        assert_eq!(
+            member_names(&file, "processing/behavior/Velocity"),
+            obj_names
+        );
+    }
+    for (name, n) in num_rows.iter() {
+        let path = format!("processing/behavior/Position/{name}");
+        check_series(&file, &path, "SpatialSeries", *n, 3);
+        let path = format!("processing/behavior/Velocity/{name}");
+        check_series(&file, &path, "TimeSeries", *n, 3);
+    }
+
+    // One series of 2D detections per camera with detections.
+    let detection_cams = member_names(&file, "processing/behavior/Detections2D");
+    assert!(!detection_cams.is_empty());
+    assert!(detection_cams.is_subset(&cam_names));
+    for cam_name in detection_cams.iter() {
+        let path = format!("processing/behavior/Detections2D/{cam_name}");
+        let n = file
+            .dataset(&format!("{path}/braid_frame"))
+            .unwrap()
+            .shape()[0];
+        check_series(&file, &path, "TimeSeries", n, 5);
+    }
+}
@@ -182,6 +182,7 @@ impl<R: Read + Seek> IncrementalParser<R, ArchiveOpened> {
                                     saving_program_name: "flydra".to_string(),
                                     schema: braid_types::BRAID_SCHEMA,
                                     save_empty_data2d: false,
+                                    tracking_params_history: vec![],
                                 });
                             }
 
@@ -244,6 +245,8 @@ impl<R: Read + Seek> IncrementalParser<R, ArchiveOpened> {
                         flydra_mvg::FlydraMultiCameraSystem::from_flydra_reconstructor(&recon)?;
                     Some(CalibrationInfo {
                         water: recon.water,
+                        refractive_interfaces: system.refractive_interfaces().to_vec(),
+                        time_offsets: system.time_offsets().clone(),
                         cameras: system.to_system(),
                     })
                 }
@@ -8,7 +8,7 @@ use hdrhistogram::serialization::interval_log;
 use ordered_float::NotNan;
 
 use braid_types::{
-    CamInfoRow, CamNum, Data2dDistortedRow, FlydraFloatTimestampLocal, HostClock,
+    CamInfoRow, CamNum, Data2dDistortedRow, DataAssocRow, FlydraFloatTimestampLocal, HostClock,
     KalmanEstimatesRow, TextlogRow, TrackingParams, Triggerbox,
 };
 
@@ -354,6 +354,24 @@ impl<'a, R: Read + Seek> BraidzArchive<R> {
         Ok(rdr2.into_deserialize().early_eof_ok())
     }
 
+    /// Iterate over the rows of the `data_association` table.
+    ///
+    /// Each row links a row of the `data2d_distorted` table, given by its
+    /// frame, camera and point index, to the object it was used to track. This
+    /// takes a mutable reference because the read location in the archive is
+    /// changed during operation.
+    pub fn iter_data_association(
+        &'a mut self,
+    ) -> Result<impl Iterator<Item = Result<DataAssocRow, csv::Error>> + 'a, Error> {
+        let data_fname = self
+            .archive
+            .path_starter()
+            .join(braid_types::DATA_ASSOCIATE_CSV_FNAME);
+        let rdr = open_maybe_gzipped(data_fname)?;
+        let rdr2 = csv::Reader::from_reader(rdr);
+        Ok(rdr2.into_deserialize().early_eof_ok())
+    }
+
     /// Iterate over synchronized frames in `data2d_distorted` table.
     ///
     /// This sorts the data by looking ahead up to `bufsize` rows. Furthermore,
@@ -224,12 +224,10 @@ impl OfflineBraidzRerunLogger {
         let camname = camname.unwrap();
         let cam_data = self.by_camname.get(&camname).unwrap();
 
-        let undist_cache = if let Some(intrinsics) = &cam_data.nl_intrinsics {
+        let undist_cache = if cam_data.nl_intrinsics.is_some() {
             let calibration = cam_data.calibration.as_ref().unwrap();
-            Some(undistort_image::UndistortionCache::new(
-                intrinsics,
-                calibration.width(),
-                calibration.height(),
+            Some(undistort_image::UndistortionCache::from_camera(
+                calibration,
             )?)
         } else {
             None
@@ -334,16 +332,19 @@ impl OfflineBraidzRerunLogger {
             }
         };
 
-        if let (Some(nl_intrinsics), Some(path_base)) = (
-            &cam_data.nl_intrinsics,
+        if let (Some(calibration), true, Some(path_base)) = (
+            &cam_data.calibration,
+            cam_data.nl_intrinsics.is_some(),
             cam_data.log_undistorted_2d_points.as_ref(),
         ) {
             let ent_path = format!("{path_base}/{DETECT_NAME}");
             if !row.x.is_nan() {
-                let pt2d = cam_geom::Pixels::new(nalgebra::Vector2::new(row.x, row.y).transpose());
-                let linearized = nl_intrinsics.undistort(&pt2d);
-                let x = linearized.data[0];
-                let y = linearized.data[1];
+                let pt2d = braid_mvg::DistortedPixel {
+                    coords: nalgebra::Point2::new(row.x, row.y),
+                };
+                let linearized = calibration.undistort(&pt2d);
+                let x = linearized.coords.x;
+                let y = linearized.coords.y;
                 self.rec
                     .log(ent_path.clone(), &Points2D::new([(x as f32, y as f32)]))?;
                 self.last_data2d.insert(ent_path, row.frame);
@@ -484,6 +485,9 @@ pub fn braidz_into_rec<R: Read + Seek>(
         if cal.water.is_some() {
             tracing::error!("omitting water");
         }
+        if !cal.refractive_interfaces.is_empty() {
+            tracing::error!("omitting refractive interfaces");
+        }
         for (cam_name, cam) in cal.cameras.cams_by_name().iter() {
             match rrd_logger.add_camera_calibration(cam_name, cam) {
                 Ok(()) => {}
@@ -17,6 +17,25 @@ pub struct BraidMetadata {
     /// when loading old files is "".
     #[serde(default = "default_saving_program_name")]
     pub saving_program_name: String,
+    /// The tracking parameters used during the recording.
+    ///
+    /// Tracking parameters can be changed while Braid is running. Each entry
+    /// holds one parameter set and the frames during which it was used. This
+    /// is empty for files saved before runtime changes were possible.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracking_params_history: Vec<TrackingParamsPeriod>,
+}
+
+/// Tracking parameters used during a range of frames.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrackingParamsPeriod {
+    /// The first frame with these parameters or `None` if used since the start
+    /// of the recording.
+    pub start_frame: Option<u64>,
+    /// The last frame (inclusive) with these parameters or `None` if used
+    /// until the end of the recording.
+    pub stop_frame: Option<u64>,
+    pub tracking_params: TrackingParams,
 }
 
 fn default_saving_program_name() -> String {
@@ -50,6 +69,9 @@ pub struct BraidzSummary {
 pub struct CalibrationSummary {
     /// If `Some(n)`, material with refractive index `n` at z<0.
     pub water: Option<f64>,
+    /// Refractive boundaries in addition to `water`.
+    #[serde(default)]
+    pub refractive_interfaces: Vec<flydra_mvg::RefractiveInterface<f64>>,
     /// All the cameras in this system.
     pub cameras: Vec<CameraSummary>,
 }
@@ -58,6 +80,7 @@ impl From<CalibrationInfo> for CalibrationSummary {
     fn from(orig: CalibrationInfo) -> Self {
         Self {
             water: orig.water,
+            refractive_interfaces: orig.refractive_interfaces,
             cameras: orig
                 .cameras
                 .cams_by_name()
@@ -118,6 +141,12 @@ pub struct HistogramSummary {
 pub struct CalibrationInfo {
     /// If `Some(n)`, material with refractive index `n` at z<0.
     pub water: Option<f64>,
+    /// Refractive boundaries in addition to `water`, such as aquarium walls.
+    #[serde(default)]
+    pub refractive_interfaces: Vec<flydra_mvg::RefractiveInterface<f64>>,
+    /// Time offsets, in seconds, of the cameras with a non-zero time offset.
+    #[serde(default)]
+    pub time_offsets: BTreeMap<String, f64>,
     /// All the cameras in this system.
     pub cameras: braid_mvg::MultiCameraSystem<f64>,
 }
@@ -517,10 +517,19 @@ fn detail_table_valid(fd: &ValidBraidzFile) -> Html {
     let num_cameras = format!("{}", num_cameras);
 
     let cal = match &summary.calibration_info {
-        Some(ci) => match &ci.water {
-            Some(n) => format!("present (water below z=0 with n={})", n),
-            None => "present".to_string(),
-        },
+        Some(ci) => {
+            let mut cal = match &ci.water {
+                Some(n) => format!("present (water below z=0 with n={})", n),
+                None => "present".to_string(),
+            };
+            if !ci.refractive_interfaces.is_empty() {
+                cal.push_str(&format!(
+                    " ({} additional refractive interfaces)",
+                    ci.refractive_interfaces.len()
+                ));
+            }
+            cal
+        }
         None => "not present".to_string(),
     };
 
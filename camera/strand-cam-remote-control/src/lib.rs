@@ -128,10 +128,22 @@ pub enum Mp4Codec {
     H264OpenH264(OpenH264Options),
     /// Encode data with LessAVC.
     H264LessAvc,
+    /// Encode data with LessAVC using a pool of encoder threads.
+    ///
+    /// Like [Mp4Codec::H264LessAvc], only 8-bit input is supported.
+    H264LessAvcThreaded(LessAvcThreadedOptions),
     /// Data is already encoded as a raw H264 stream.
     H264RawStream,
 }
 
+/// Options for the multi-threaded LessAVC encoder.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct LessAvcThreadedOptions {
+    /// Number of encoder threads. If `None`, use the available parallelism of
+    /// the machine.
+    pub num_threads: Option<usize>,
+}
+
 /// Options for OpenH264 encoder.
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
 pub struct OpenH264Options {
@@ -226,6 +238,15 @@ pub struct FfmpegRecordingConfig {
     pub h264_metadata: Option<H264Metadata>,
 }
 
+/// Configuration for a lossless OME-TIFF recording
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct TiffRecordingConfig {
+    /// Limits the recording to a maximum frame rate.
+    pub max_framerate: RecordingFrameRate,
+    /// Camera metadata saved in the OME-XML description.
+    pub metadata: Option<H264Metadata>,
+}
+
 /// Specify recording method and configuration
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum RecordingConfig {
@@ -233,6 +254,8 @@ pub enum RecordingConfig {
     Mp4(Mp4RecordingConfig),
     /// Record via y4m pipe to ffmpeg
     Ffmpeg(FfmpegRecordingConfig),
+    /// Record uncompressed frames to an OME-TIFF file
+    Tiff(TiffRecordingConfig),
 }
 
 impl Default for RecordingConfig {
@@ -248,10 +271,39 @@ impl RecordingConfig {
         match self {
             Mp4(c) => &c.max_framerate,
             Ffmpeg(c) => &c.max_framerate,
+            Tiff(c) => &c.max_framerate,
+        }
+    }
+
+    /// Returns the filename extension (without leading dot) of the saved file.
+    pub fn file_extension(&self) -> &'static str {
+        use RecordingConfig::*;
+        match self {
+            Mp4(_) | Ffmpeg(_) => "mp4",
+            Tiff(_) => "ome.tif",
         }
     }
 }
 
+/// Statistics of an ongoing recording.
+///
+/// These indicate whether the writer keeps up with the incoming frames.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct RecordingStats {
+    /// Number of frames queued for saving.
+    pub frames_queued: u64,
+    /// Number of frames dropped because the queue to the writer was full.
+    pub frames_dropped: u64,
+    /// Number of frames passed to the encoder.
+    pub frames_written: u64,
+    /// Number of encoder threads, if a multi-threaded encoder is used.
+    pub encoder_threads: Option<usize>,
+    /// Number of frames currently being encoded.
+    pub encoder_in_flight: usize,
+    /// Total duration, in seconds, the writer waited for a free encoder thread.
+    pub encoder_blocked_secs: f64,
+}
+
 /// Universal identifier for our H264 metadata.
 ///
 /// Generated with `uuid -v3 ns:URL https://strawlab.org/h264-metadata/`
@@ -462,8 +514,12 @@ pub enum CodecSelection {
     H264Nvenc,
     /// OpenH264 software encoder
     H264OpenH264,
+    /// LessAVC lossless software encoder, multi-threaded
+    H264LessAvc,
     /// Custom ffmpeg codec configuration
     Ffmpeg(FfmpegCodecArgs),
+    /// Uncompressed OME-TIFF (not an MP4 codec)
+    OmeTiff,
 }
 
 impl CodecSelection {
@@ -472,7 +528,7 @@ impl CodecSelection {
         use CodecSelection::*;
         match self {
             H264Nvenc => what == "nvenc",
-            H264OpenH264 => false,
+            H264OpenH264 | H264LessAvc | OmeTiff => false,
             Ffmpeg(args) => {
                 if let Some(codec) = &args.codec {
                     codec.contains(what)
@@ -490,9 +546,11 @@ impl std::fmt::Display for CodecSelection {
         let x = match self {
             H264Nvenc => "H264 NVENC",
             H264OpenH264 => "OpenH264",
+            H264LessAvc => "LessAVC (lossless)",
             Ffmpeg(args) => {
                 return std::fmt::Display::fmt(args, f);
             }
+            OmeTiff => "OME-TIFF (lossless)",
         };
         write!(f, "{x}")
     }
@@ -504,6 +562,7 @@ impl strand_cam_enum_iter::EnumIter for CodecSelection {
         vec![
             H264Nvenc,
             H264OpenH264,
+            H264LessAvc,
             // Don't give bare option as it seems less useful than specifying a codec.
             // Keep these in sync with the list in ffmpeg-writer.
             Ffmpeg(FfmpegCodecArgs {
@@ -538,6 +597,7 @@ impl strand_cam_enum_iter::EnumIter for CodecSelection {
                 ]),
                 ..Default::default()
             }),
+            OmeTiff,
         ]
     }
 }
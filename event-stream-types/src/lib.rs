@@ -212,6 +212,93 @@ where
     }
 }
 
+// parsing ---------------------------
+
+/// A message received in an event stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventStreamMessage {
+    /// The event type, if given.
+    pub event: Option<String>,
+    /// The data. Multiple data lines are joined with newlines.
+    pub data: String,
+}
+
+/// Incremental parser for the client side of an event stream.
+///
+/// Chunks of the body are added with [Self::push] and complete messages are
+/// taken with [Self::next_message]. Chunk boundaries need not coincide with
+/// message boundaries.
+#[derive(Debug, Default)]
+pub struct EventStreamParser {
+    buf: Vec<u8>,
+}
+
+impl EventStreamParser {
+    /// Add a chunk of the body.
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buf.extend(chunk.iter().filter(|b| **b != b'\r'));
+    }
+
+    /// Take the next complete message, if any.
+    ///
+    /// Messages without data (e.g. only comments) are skipped.
+    pub fn next_message(&mut self) -> Option<EventStreamMessage> {
+        loop {
+            let end = self.buf.windows(2).position(|w| w == b"\n\n")?;
+            let raw: Vec<u8> = self.buf.drain(..end + 2).collect();
+            let raw = String::from_utf8_lossy(&raw[..end]);
+
+            let mut event = None;
+            let mut data: Option<String> = None;
+            for line in raw.split('\n') {
+                if line.starts_with(':') {
+                    // comment
+                    continue;
+                }
+                let (field, value) = line.split_once(':').unwrap_or((line, ""));
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                match field {
+                    "event" => event = Some(value.to_string()),
+                    "data" => match &mut data {
+                        Some(data) => {
+                            data.push('\n');
+                            data.push_str(value);
+                        }
+                        None => data = Some(value.to_string()),
+                    },
+                    _ => {}
+                }
+            }
+            if let Some(data) = data {
+                return Some(EventStreamMessage { event, data });
+            }
+        }
+    }
+}
+
+#[test]
+fn test_event_stream_parser() {
+    let mut parser = EventStreamParser::default();
+    parser.push(b"event: braid\ndata: {\"a\":");
+    assert_eq!(parser.next_message(), None);
+    parser.push(b"1}\n\n: comment\n\ndata: x\r\ndata: y\r\n\r\nevent: e");
+    assert_eq!(
+        parser.next_message(),
+        Some(EventStreamMessage {
+            event: Some("braid".into()),
+            data: "{\"a\":1}".into(),
+        })
+    );
+    assert_eq!(
+        parser.next_message(),
+        Some(EventStreamMessage {
+            event: None,
+            data: "x\ny".into(),
+        })
+    );
+    assert_eq!(parser.next_message(), None);
+}
+
 // ----
 
 // This does not really belong here...
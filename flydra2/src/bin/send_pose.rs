@@ -16,7 +16,7 @@ async fn main() -> Result<()> {
 
     let (data_tx, data_rx) = tokio::sync::mpsc::channel(50);
 
-    let model_server_future = new_model_server(data_rx, addr);
+    let model_server_future = new_model_server(data_rx, None, addr);
 
     tokio::spawn(model_server_future);
 
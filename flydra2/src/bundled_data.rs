@@ -269,6 +269,12 @@ fn undistort_points_and_assign_arena(
                 coords: Point2::new(pt.x0_abs, pt.y0_abs),
             };
             let undist = cam.undistort(&distorted);
+            if !undist.coords.x.is_finite() {
+                // A fisheye camera sees this point at or beyond 90° from the
+                // optical axis, where the undistorted pixel coordinates used
+                // for tracking are undefined.
+                continue;
+            }
 
             let undistorted = Undistorted {
                 idx: numbered_raw_udp_point.idx,
@@ -0,0 +1,499 @@
+//! Detection of calibration drift from the live tracking.
+//!
+//! If a camera is moved after calibration, the observations from it no longer
+//! agree with the 3D estimates of the tracked objects and their reprojection
+//! distances increase. The [DriftDetector] collects the reprojection distances
+//! of the observations used for tracking per camera and compares windows of
+//! recent observations to a baseline. It is configured with
+//! [braid_types::CalibrationDriftConfig].
+//!
+//! Large movements cause the observations of a camera to be rejected by the
+//! data association, so these show as a camera no longer contributing to the
+//! tracking rather than as increased reprojection distances.
+
+use std::collections::BTreeMap;
+
+use nalgebra::{DMatrix, DVector, Isometry3, Point3, Vector6};
+
+use braid_mvg::{Camera, PointWorldFrame};
+use braid_types::{CalibrationDriftAlert, CalibrationDriftConfig, RawCamName};
+use bundle_adj::RobustLoss;
+
+use crate::{AssociatedObservation, Error, Result};
+
+/// A change of the drift state of a camera.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriftEvent {
+    /// The reprojection distances of the camera increased significantly.
+    Detected {
+        alert: CalibrationDriftAlert,
+        /// The observations of the window in which drift was detected.
+        observations: Vec<AssociatedObservation>,
+    },
+    /// The reprojection distances of a camera for which drift was detected
+    /// are again consistent with the baseline.
+    Resolved(CalibrationDriftAlert),
+}
+
+#[derive(Debug, Default)]
+struct CameraState {
+    /// Sorted once complete.
+    baseline: Vec<f64>,
+    baseline_median: f64,
+    window: Vec<AssociatedObservation>,
+    drifted: bool,
+}
+
+/// Detects calibration drift from the observations used for tracking.
+#[derive(Debug)]
+pub struct DriftDetector {
+    baseline_num_observations: usize,
+    window_num_observations: usize,
+    min_z_score: f64,
+    min_median_increase_pixels: f64,
+    cams: BTreeMap<RawCamName, CameraState>,
+}
+
+impl DriftDetector {
+    pub fn new(cfg: &CalibrationDriftConfig) -> Self {
+        Self {
+            baseline_num_observations: cfg.baseline_num_observations.max(1),
+            window_num_observations: cfg.window_num_observations.max(1),
+            min_z_score: cfg.min_z_score,
+            min_median_increase_pixels: cfg.min_median_increase_pixels,
+            cams: BTreeMap::new(),
+        }
+    }
+
+    /// Process the observations of a frame and return the changes of the
+    /// drift state.
+    pub fn process(&mut self, observations: &[AssociatedObservation]) -> Vec<DriftEvent> {
+        let mut events = vec![];
+        for obs in observations.iter() {
+            let cam = self.cams.entry(obs.cam_name.clone()).or_default();
+            if cam.baseline.len() < self.baseline_num_observations {
+                cam.baseline.push(obs.reproj_dist);
+                if cam.baseline.len() == self.baseline_num_observations {
+                    cam.baseline.sort_by(|a, b| a.total_cmp(b));
+                    cam.baseline_median = median_of_sorted(&cam.baseline);
+                }
+                continue;
+            }
+            cam.window.push(obs.clone());
+            if cam.window.len() < self.window_num_observations {
+                continue;
+            }
+
+            let window = std::mem::take(&mut cam.window);
+            let mut dists: Vec<f64> = window.iter().map(|o| o.reproj_dist).collect();
+            dists.sort_by(|a, b| a.total_cmp(b));
+            let recent_median = median_of_sorted(&dists);
+            let z_score = mann_whitney_z(&cam.baseline, &dists);
+            let drifted = z_score >= self.min_z_score
+                && recent_median - cam.baseline_median >= self.min_median_increase_pixels;
+
+            let alert = CalibrationDriftAlert {
+                cam_name: obs.cam_name.clone(),
+                frame: obs.frame,
+                baseline_median_reproj_dist: cam.baseline_median,
+                recent_median_reproj_dist: recent_median,
+                z_score,
+                corrected_extrinsics: None,
+            };
+            match (cam.drifted, drifted) {
+                (false, true) => events.push(DriftEvent::Detected {
+                    alert,
+                    observations: window,
+                }),
+                (true, false) => events.push(DriftEvent::Resolved(alert)),
+                _ => {}
+            }
+            cam.drifted = drifted;
+        }
+        events
+    }
+}
+
+fn median_of_sorted(sorted: &[f64]) -> f64 {
+    let n = sorted.len();
+    if n == 0 {
+        f64::NAN
+    } else if n % 2 == 1 {
+        sorted[n / 2]
+    } else {
+        (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+    }
+}
+
+/// z-score of the one-sided Mann-Whitney U test that `recent` tends to be
+/// larger than `baseline`, using the normal approximation.
+///
+/// Ties get their average rank. The variance is not corrected for ties, which
+/// are rare for reprojection distances.
+fn mann_whitney_z(baseline: &[f64], recent: &[f64]) -> f64 {
+    let n1 = baseline.len() as f64;
+    let n2 = recent.len() as f64;
+    let mut all: Vec<(f64, bool)> = baseline
+        .iter()
+        .map(|v| (*v, false))
+        .chain(recent.iter().map(|v| (*v, true)))
+        .collect();
+    all.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut rank_sum_recent = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j + 1 < all.len() && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        let num_recent = all[i..=j]
+            .iter()
+            .filter(|(_, is_recent)| *is_recent)
+            .count();
+        rank_sum_recent += rank * num_recent as f64;
+        i = j + 1;
+    }
+
+    let u = rank_sum_recent - n2 * (n2 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let sd = (n1 * n2 * (n1 + n2 + 1.0) / 12.0).sqrt();
+    (u - mean) / sd
+}
+
+/// Minimum number of observations to estimate a camera pose.
+const MIN_POSE_OBSERVATIONS: usize = 20;
+/// Reprojection distance, in pixels, above which observations are
+/// down-weighted.
+const HUBER_PIXELS: f64 = 2.0;
+/// Observations with a reprojection distance above this, in pixels, and
+/// above three times the median after the first fit are dropped for the
+/// second fit.
+const OUTLIER_PIXELS: f64 = 5.0;
+const MAX_ITERATIONS: usize = 50;
+/// Step for the numerical Jacobian.
+const EPS: f64 = 1e-6;
+
+/// Estimate the pose of a camera from observations of tracked objects.
+///
+/// Starting from the pose of `cam`, the distances between the observed and
+/// reprojected undistorted pixels are minimized with Levenberg-Marquardt and a
+/// Huber loss. The fit is repeated without outliers. The intrinsic parameters
+/// are kept. The positions of the objects are taken as correct, so most other
+/// cameras must be well calibrated.
+///
+/// Returns the camera with the estimated pose and the median reprojection
+/// distance of all observations.
+pub fn estimate_extrinsics(
+    cam: &Camera<f64>,
+    observations: &[AssociatedObservation],
+) -> Result<(Camera<f64>, f64)> {
+    if observations.len() < MIN_POSE_OBSERVATIONS {
+        return Err(Error::PoseEstimation("too few observations"));
+    }
+    let pose0 = *cam.extrinsics().pose();
+
+    let params = fit(cam, &pose0, observations, Vector6::zeros())?;
+    let mut dists = reproj_dists(&with_pose(cam, &perturb(&pose0, &params))?, observations);
+    let threshold = OUTLIER_PIXELS.max(3.0 * median(&dists));
+    let inliers: Vec<AssociatedObservation> = observations
+        .iter()
+        .zip(dists.iter())
+        .filter(|(_, dist)| **dist <= threshold)
+        .map(|(obs, _)| obs.clone())
+        .collect();
+    let params = if inliers.len() < observations.len() && inliers.len() >= MIN_POSE_OBSERVATIONS {
+        fit(cam, &pose0, &inliers, params)?
+    } else {
+        params
+    };
+
+    let estimated = with_pose(cam, &perturb(&pose0, &params))?;
+    dists = reproj_dists(&estimated, observations);
+    Ok((estimated, median(&dists)))
+}
+
+/// `pose` moved by the translation and axis-angle rotation in `params`.
+fn perturb(pose: &Isometry3<f64>, params: &Vector6<f64>) -> Isometry3<f64> {
+    let delta = Isometry3::new(
+        params.fixed_rows::<3>(0).into_owned(),
+        params.fixed_rows::<3>(3).into_owned(),
+    );
+    delta * pose
+}
+
+fn with_pose(cam: &Camera<f64>, pose: &Isometry3<f64>) -> Result<Camera<f64>> {
+    let extrinsics = cam_geom::ExtrinsicParameters::from_pose(pose);
+    let intrinsics = cam.intrinsics().clone();
+    Ok(match cam.fisheye_distortion() {
+        Some(distortion) => Camera::new_fisheye(
+            cam.width(),
+            cam.height(),
+            extrinsics,
+            intrinsics,
+            distortion.clone(),
+        )?,
+        None => Camera::new(cam.width(), cam.height(), extrinsics, intrinsics)?,
+    })
+}
+
+/// Reprojected minus observed undistorted pixel coordinates.
+fn residuals(cam: &Camera<f64>, observations: &[AssociatedObservation]) -> DVector<f64> {
+    let mut r = DVector::zeros(2 * observations.len());
+    for (i, obs) in observations.iter().enumerate() {
+        let pt = PointWorldFrame {
+            coords: Point3::from(obs.position),
+        };
+        let projected = cam.project_3d_to_pixel(&pt).coords;
+        r[2 * i] = projected.x - obs.undistorted[0];
+        r[2 * i + 1] = projected.y - obs.undistorted[1];
+    }
+    r
+}
+
+fn reproj_dists(cam: &Camera<f64>, observations: &[AssociatedObservation]) -> Vec<f64> {
+    residuals(cam, observations)
+        .as_slice()
+        .chunks_exact(2)
+        .map(|xy| xy[0].hypot(xy[1]))
+        .collect()
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    median_of_sorted(&sorted)
+}
+
+/// Levenberg-Marquardt minimization of the Huber loss over the pose.
+fn fit(
+    cam: &Camera<f64>,
+    pose0: &Isometry3<f64>,
+    observations: &[AssociatedObservation],
+    mut params: Vector6<f64>,
+) -> Result<Vector6<f64>> {
+    let eval = |params: &Vector6<f64>| -> Result<DVector<f64>> {
+        Ok(residuals(
+            &with_pose(cam, &perturb(pose0, params))?,
+            observations,
+        ))
+    };
+
+    let mut r = eval(&params)?;
+    let mut cost = huber_cost(&r);
+    let mut lambda = 1e-3;
+    for _ in 0..MAX_ITERATIONS {
+        let mut jac = DMatrix::zeros(r.len(), 6);
+        for j in 0..6 {
+            let mut p = params;
+            p[j] += EPS;
+            jac.set_column(j, &((eval(&p)? - &r) / EPS));
+        }
+        // Weighted normal equations of the Huber loss.
+        let mut weighted = jac.clone();
+        for i in 0..observations.len() {
+            let s = r[2 * i].powi(2) + r[2 * i + 1].powi(2);
+            let (_, w) = RobustLoss::Huber.eval(s, HUBER_PIXELS);
+            weighted.row_mut(2 * i).scale_mut(w);
+            weighted.row_mut(2 * i + 1).scale_mut(w);
+        }
+        let jtj = jac.tr_mul(&weighted);
+        let g = weighted.tr_mul(&r);
+
+        let mut improved = false;
+        while lambda < 1e10 {
+            let mut a = jtj.clone();
+            for k in 0..6 {
+                a[(k, k)] *= 1.0 + lambda;
+            }
+            let delta = a
+                .cholesky()
+                .ok_or(Error::PoseEstimation("degenerate observations"))?
+                .solve(&-&g);
+            let candidate = params + Vector6::from_column_slice(delta.as_slice());
+            let candidate_r = eval(&candidate)?;
+            let candidate_cost = huber_cost(&candidate_r);
+            if candidate_cost < cost {
+                let converged = cost - candidate_cost < 1e-12 * cost;
+                params = candidate;
+                r = candidate_r;
+                cost = candidate_cost;
+                lambda = (lambda / 10.0).max(1e-12);
+                improved = !converged;
+                break;
+            }
+            lambda *= 10.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+    Ok(params)
+}
+
+fn huber_cost(r: &DVector<f64>) -> f64 {
+    r.as_slice()
+        .chunks_exact(2)
+        .map(|xy| {
+            RobustLoss::Huber
+                .eval(xy[0].powi(2) + xy[1].powi(2), HUBER_PIXELS)
+                .0
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use braid_types::SyncFno;
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    fn obs(cam_name: &str, frame: u64, reproj_dist: f64) -> AssociatedObservation {
+        AssociatedObservation {
+            cam_name: RawCamName::new(cam_name.to_string()),
+            frame: SyncFno(frame),
+            obj_id: 1,
+            undistorted: [0.0, 0.0],
+            reproj_dist,
+            position: [0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Quasi-random numbers uniformly distributed in [0, 1).
+    fn uniform(i: usize) -> f64 {
+        (i as f64 * 0.618_033_988_749_895).fract()
+    }
+
+    fn cfg() -> CalibrationDriftConfig {
+        CalibrationDriftConfig {
+            baseline_num_observations: 1000,
+            window_num_observations: 200,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mann_whitney_z() {
+        let baseline: Vec<f64> = (0..500).map(uniform).collect();
+        let same: Vec<f64> = (500..700).map(uniform).collect();
+        let larger: Vec<f64> = same.iter().map(|v| v + 0.5).collect();
+        assert!(mann_whitney_z(&baseline, &same).abs() < 2.0);
+        assert!(mann_whitney_z(&baseline, &larger) > 10.0);
+        assert!(mann_whitney_z(&larger, &baseline) < -10.0);
+    }
+
+    #[test]
+    fn test_detect_and_resolve() {
+        let mut detector = DriftDetector::new(&cfg());
+        let mut frame = 0;
+        let mut run = |detector: &mut DriftDetector, n: usize, offset: f64| {
+            let mut events = vec![];
+            for _ in 0..n {
+                frame += 1;
+                let i = frame as usize;
+                let observations = [
+                    obs("cam1", frame, 0.5 + uniform(i)),
+                    obs("cam2", frame, 0.5 + offset + uniform(i + 17)),
+                ];
+                events.extend(detector.process(&observations));
+            }
+            events
+        };
+
+        // Baseline and windows without change.
+        assert_eq!(run(&mut detector, 2000, 0.0), vec![]);
+
+        // cam2 is moved.
+        let events = run(&mut detector, 400, 2.0);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            DriftEvent::Detected {
+                alert,
+                observations,
+            } => {
+                assert_eq!(alert.cam_name.as_str(), "cam2");
+                assert!(alert.recent_median_reproj_dist - alert.baseline_median_reproj_dist > 1.5);
+                assert!(alert.z_score > 10.0);
+                assert_eq!(observations.len(), 200);
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+
+        // Drift is reported only once.
+        assert_eq!(run(&mut detector, 400, 2.0), vec![]);
+
+        // cam2 is moved back.
+        let events = run(&mut detector, 200, 0.0);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            DriftEvent::Resolved(alert) => assert_eq!(alert.cam_name.as_str(), "cam2"),
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_small_increase_ignored() {
+        // Significant, but less than `min_median_increase_pixels`.
+        let mut detector = DriftDetector::new(&cfg());
+        let mut events = vec![];
+        for i in 0..2000 {
+            let offset = if i < 1000 { 0.0 } else { 0.3 };
+            events.extend(detector.process(&[obs("cam1", i as u64, 0.5 + uniform(i) + offset)]));
+        }
+        assert_eq!(events, vec![]);
+    }
+
+    fn camera(camcenter: Vector3<f64>, rotation: UnitQuaternion<f64>) -> Camera<f64> {
+        let cam = braid_mvg::test_util::camera_looking_at_origin(camcenter);
+        let pose = Isometry3::from_parts(nalgebra::Translation3::identity(), rotation)
+            * cam.extrinsics().pose();
+        Camera::new(
+            640,
+            480,
+            cam_geom::ExtrinsicParameters::from_pose(&pose),
+            cam.intrinsics().clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_estimate_extrinsics() {
+        let calibrated = camera(Vector3::new(1.5, 0.2, 0.8), UnitQuaternion::identity());
+        let moved = camera(
+            Vector3::new(1.51, 0.19, 0.805),
+            UnitQuaternion::from_euler_angles(0.01, -0.005, 0.02),
+        );
+
+        let mut observations = vec![];
+        for i in 0..200 {
+            let position = [
+                0.4 * (uniform(i) - 0.5),
+                0.4 * (uniform(i + 1000) - 0.5),
+                0.2 * (uniform(i + 2000) - 0.5),
+            ];
+            let pt = PointWorldFrame {
+                coords: Point3::from(position),
+            };
+            let px = moved.project_3d_to_pixel(&pt).coords;
+            // Some outliers from wrong data association.
+            let outlier = if i % 20 == 0 { 30.0 } else { 0.0 };
+            observations.push(AssociatedObservation {
+                cam_name: RawCamName::new("cam1".to_string()),
+                frame: SyncFno(i as u64),
+                obj_id: 1,
+                undistorted: [px.x + outlier, px.y],
+                reproj_dist: 0.0,
+                position,
+            });
+        }
+
+        let (estimated, median_reproj_dist) =
+            estimate_extrinsics(&calibrated, &observations).unwrap();
+        assert!(median_reproj_dist < 1e-3, "{median_reproj_dist}");
+        let shift = estimated.extrinsics().camcenter() - moved.extrinsics().camcenter();
+        assert!(shift.norm() < 1e-4, "{shift}");
+
+        assert!(estimate_extrinsics(&calibrated, &observations[..5]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Snapshots of the tracking state saved while recording.
+//!
+//! If Braid stops without finishing a recording (e.g. because it crashed), the
+//! last snapshot allows a restarted Braid to resume the recording with the
+//! same live objects and continuing obj_ids.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use braid_types::{
+    DATA2D_DISTORTED_CSV_FNAME, KALMAN_ESTIMATES_CSV_FNAME, TRACKING_CHECKPOINT_JSON_FNAME,
+};
+
+use crate::{file_error, Result};
+
+/// How often the tracking state is saved.
+pub(crate) const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The tracking state after processing a frame.
+///
+/// While recording, this is periodically saved to
+/// [braid_types::TRACKING_CHECKPOINT_JSON_FNAME] in the `.braid` directory.
+/// The file is removed when the recording is finished.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackingCheckpoint {
+    /// The last frame processed.
+    pub frame: u64,
+    /// When the checkpoint was made, in seconds since the Unix epoch.
+    pub saved_at: f64,
+    /// The obj_id which will be given to the next new object.
+    pub next_obj_id: u32,
+    /// The live models, one `Vec` per mini arena.
+    pub(crate) mini_arenas: Vec<Vec<ModelCheckpoint>>,
+}
+
+/// The state of a single live model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ModelCheckpoint {
+    pub(crate) obj_id: u32,
+    pub(crate) start_frame: u64,
+    /// Number of observations so far if not yet visible.
+    pub(crate) gestation_age: Option<u8>,
+    /// The frame of the estimate.
+    pub(crate) frame: u64,
+    /// The estimated state `[x, y, z, xvel, yvel, zvel]`.
+    pub(crate) state: [f64; 6],
+    /// The covariance of the estimate (rows).
+    pub(crate) covariance: [[f64; 6]; 6],
+}
+
+impl TrackingCheckpoint {
+    /// Load the checkpoint saved in the `.braid` directory `dir`.
+    ///
+    /// Data saved after the checkpoint was made is already in the directory.
+    /// [Self::next_obj_id] and [Self::frame] are therefore advanced past the
+    /// obj_ids and frames of the saved kalman estimates and 2D detections so
+    /// that they are not reused after resuming.
+    pub fn load<P: AsRef<std::path::Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let path = dir.join(TRACKING_CHECKPOINT_JSON_FNAME);
+        let buf = std::fs::read(&path)
+            .map_err(|e| file_error("reading", path.display().to_string(), e))?;
+        let mut checkpoint: Self = serde_json::from_slice(&buf)
+            .map_err(|e| file_error("parsing", path.display().to_string(), e))?;
+
+        let kalman_path = dir.join(format!("{KALMAN_ESTIMATES_CSV_FNAME}.gz"));
+        let maxima = saved_column_maxima(&kalman_path, &["obj_id", "frame"])?;
+        if let Some(obj_id) = maxima[0] {
+            checkpoint.next_obj_id = checkpoint.next_obj_id.max(obj_id as u32 + 1);
+        }
+        let data2d_path = dir.join(format!("{DATA2D_DISTORTED_CSV_FNAME}.gz"));
+        let data2d_maxima = saved_column_maxima(&data2d_path, &["frame"])?;
+        for frame in [maxima[1], data2d_maxima[0]].into_iter().flatten() {
+            checkpoint.frame = checkpoint.frame.max(frame);
+        }
+        Ok(checkpoint)
+    }
+
+    /// Save the checkpoint in the `.braid` directory `dir`.
+    ///
+    /// The file is replaced atomically so that a crash while saving leaves the
+    /// previous checkpoint intact.
+    pub(crate) fn save(&self, dir: &std::path::Path) -> Result<()> {
+        let path = dir.join(TRACKING_CHECKPOINT_JSON_FNAME);
+        let tmp_path = path.with_extension("json.tmp");
+        {
+            let mut fd = std::fs::File::create(&tmp_path)?;
+            fd.write_all(&serde_json::to_vec(self)?)?;
+            fd.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Number of live models in all mini arenas.
+    pub fn num_objects(&self) -> usize {
+        self.mini_arenas.iter().map(Vec::len).sum()
+    }
+
+    /// The offset added to the frame numbers after resuming.
+    ///
+    /// Frame numbers restart when Braid is restarted. The offset places the
+    /// first frame after resuming after the checkpoint frame, accounting for
+    /// the time elapsed since the checkpoint (`now`, in seconds since the Unix
+    /// epoch) at the frame rate `fps`, if known.
+    pub(crate) fn frame_offset(&self, fps: Option<f32>, now: f64) -> u64 {
+        let elapsed_frames = match fps {
+            Some(fps) if now > self.saved_at => ((now - self.saved_at) * fps as f64).ceil() as u64,
+            _ => 0,
+        };
+        self.frame + 1 + elapsed_frames
+    }
+}
+
+/// The maximum of each of `columns` in the gzipped CSV file at `path`.
+///
+/// As when resuming the file, the `.prev` copy of an interrupted resume is
+/// used if present. Reading stops at the first error, as caused by a file
+/// truncated by a crash. Missing files and columns give `None`.
+fn saved_column_maxima(path: &std::path::Path, columns: &[&str]) -> Result<Vec<Option<u64>>> {
+    let mut maxima = vec![None; columns.len()];
+    let mut prev_path = path.as_os_str().to_owned();
+    prev_path.push(".prev");
+    let prev_path = std::path::PathBuf::from(prev_path);
+    let path = if prev_path.exists() {
+        prev_path
+    } else if path.exists() {
+        path.to_path_buf()
+    } else {
+        return Ok(maxima);
+    };
+    let fd = std::fs::File::open(&path)
+        .map_err(|e| file_error("opening", path.display().to_string(), e))?;
+    let Ok(decoder) = libflate::gzip::Decoder::new(fd) else {
+        // The gzip header was not completely written.
+        return Ok(maxima);
+    };
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(decoder);
+    let Ok(headers) = rdr.headers() else {
+        return Ok(maxima);
+    };
+    let idxs: Vec<Option<usize>> = columns
+        .iter()
+        .map(|name| headers.iter().position(|h| h == *name))
+        .collect();
+    for record in rdr.records() {
+        let Ok(record) = record else {
+            break;
+        };
+        for (max, idx) in maxima.iter_mut().zip(idxs.iter()) {
+            let value = idx
+                .and_then(|i| record.get(i))
+                .and_then(|v| v.parse::<u64>().ok());
+            if let Some(value) = value {
+                *max = Some(max.map_or(value, |m: u64| m.max(value)));
+            }
+        }
+    }
+    Ok(maxima)
+}
+
+#[test]
+fn test_checkpoint_roundtrip() {
+    let root = tempfile::tempdir().unwrap();
+    let checkpoint = TrackingCheckpoint {
+        frame: 1234,
+        saved_at: 1_700_000_000.5,
+        next_obj_id: 42,
+        mini_arenas: vec![vec![ModelCheckpoint {
+            obj_id: 41,
+            start_frame: 1200,
+            gestation_age: None,
+            frame: 1234,
+            state: [0.1, 0.2, 0.3, 0.0, 0.0, -0.1],
+            covariance: [[0.01; 6]; 6],
+        }]],
+    };
+    checkpoint.save(root.path()).unwrap();
+    let loaded = TrackingCheckpoint::load(root.path()).unwrap();
+    assert_eq!(loaded, checkpoint);
+    assert_eq!(loaded.num_objects(), 1);
+
+    // 2 seconds at 100 fps since the checkpoint.
+    assert_eq!(checkpoint.frame_offset(Some(100.0), 1_700_000_002.5), 1435);
+    assert_eq!(checkpoint.frame_offset(None, 1_700_000_002.5), 1235);
+}
+
+#[test]
+fn test_load_advances_past_saved_data() {
+    let root = tempfile::tempdir().unwrap();
+    let checkpoint = TrackingCheckpoint {
+        frame: 1234,
+        saved_at: 1_700_000_000.5,
+        next_obj_id: 42,
+        mini_arenas: vec![],
+    };
+    checkpoint.save(root.path()).unwrap();
+
+    // Objects 42 and 43 were born after the checkpoint. The kalman estimates
+    // end with a partial row, and the 2D detections continue for longer.
+    let write_gz = |fname: &str, contents: &[u8]| {
+        let fd = std::fs::File::create(root.path().join(format!("{fname}.gz"))).unwrap();
+        let mut encoder = libflate::gzip::Encoder::new(fd).unwrap();
+        encoder.write_all(contents).unwrap();
+        encoder.finish().into_result().unwrap();
+    };
+    write_gz(
+        KALMAN_ESTIMATES_CSV_FNAME,
+        b"obj_id,frame,x\n41,1234,0.1\n42,1236,0.2\n43,1240,0.3\n9",
+    );
+    write_gz(
+        DATA2D_DISTORTED_CSV_FNAME,
+        b"camn,frame,x\n0,1240,1.0\n0,1250,2.0\n",
+    );
+
+    let loaded = TrackingCheckpoint::load(root.path()).unwrap();
+    assert_eq!(loaded.next_obj_id, 44);
+    assert_eq!(loaded.frame, 1250);
+    assert_eq!(loaded.frame_offset(None, 1_700_000_002.5), 1251);
+}
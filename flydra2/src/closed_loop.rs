@@ -0,0 +1,459 @@
+//! Closed-loop rules triggered by the 3D tracking output.
+//!
+//! The rules are configured with [braid_types::ClosedLoopConfig]. The
+//! [ClosedLoopEngine] consumes the same messages as the model server and
+//! returns a [RuleFiring] for each rule which fires. Performing the actions is
+//! left to the caller.
+//!
+//! Rules fire on transitions only. When an object is born, its initial state
+//! is established without firing, except for [ClosedLoopTrigger::Enter] rules,
+//! which fire for objects born inside their volume.
+
+use std::collections::BTreeMap;
+
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use braid_types::{ClosedLoopAction, ClosedLoopRule, ClosedLoopTrigger, SyncFno, Volume3D};
+
+use crate::{file_error, Error, Result, SendKalmanEstimatesRow, SendType};
+
+/// A firing of a closed-loop rule.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleFiring {
+    /// Name of the rule which fired.
+    pub rule: String,
+    /// The object which caused the rule to fire.
+    pub obj_id: u32,
+    /// The frame at which the rule fired.
+    pub frame: SyncFno,
+    /// The trigger of the rule.
+    pub trigger: ClosedLoopTrigger,
+    /// X position of the object.
+    pub x: f64,
+    /// Y position of the object.
+    pub y: f64,
+    /// Z position of the object.
+    pub z: f64,
+    /// Speed of the object.
+    pub speed: f64,
+}
+
+/// A [Volume3D] prepared for containment tests.
+#[derive(Debug)]
+enum Shape {
+    Box {
+        min: Point3<f64>,
+        max: Point3<f64>,
+    },
+    Sphere {
+        center: Point3<f64>,
+        radius: f64,
+    },
+    Cylinder {
+        base_center: Point3<f64>,
+        radius: f64,
+        height: f64,
+    },
+    Mesh(Vec<[Point3<f64>; 3]>),
+}
+
+impl Shape {
+    fn new(volume: &Volume3D) -> Result<Self> {
+        Ok(match volume {
+            Volume3D::Box { min, max } => Shape::Box {
+                min: (*min).into(),
+                max: (*max).into(),
+            },
+            Volume3D::Sphere { center, radius } => Shape::Sphere {
+                center: (*center).into(),
+                radius: *radius,
+            },
+            Volume3D::Cylinder {
+                base_center,
+                radius,
+                height,
+            } => Shape::Cylinder {
+                base_center: (*base_center).into(),
+                radius: *radius,
+                height: *height,
+            },
+            Volume3D::Mesh { filename } => {
+                let fname = filename.display().to_string();
+                let buf = std::fs::read(filename)
+                    .map_err(|e| file_error("reading mesh", fname.clone(), e))?;
+                let mut objects = simple_obj_parse::obj_parse(&buf)
+                    .map_err(|e| file_error("parsing mesh", fname.clone(), e))?;
+                if objects.is_empty() {
+                    return Err(Error::InvalidClosedLoopRule {
+                        rule: fname,
+                        reason: "mesh file contains no objects",
+                    });
+                }
+                let (_name, mesh) = objects.remove(0);
+                let triangles = mesh
+                    .indices
+                    .iter()
+                    .map(|idx| idx.map(|i| Point3::from(mesh.coords[i as usize])))
+                    .collect();
+                Shape::Mesh(triangles)
+            }
+        })
+    }
+
+    fn contains(&self, p: &Point3<f64>) -> bool {
+        match self {
+            Shape::Box { min, max } => (0..3).all(|i| min[i] <= p[i] && p[i] <= max[i]),
+            Shape::Sphere { center, radius } => (p - center).norm() <= *radius,
+            Shape::Cylinder {
+                base_center,
+                radius,
+                height,
+            } => {
+                let d = p - base_center;
+                (0.0..=*height).contains(&d.z) && d.xy().norm() <= *radius
+            }
+            Shape::Mesh(triangles) => {
+                // Count the crossings of a ray starting at `p`. The direction
+                // is arbitrary but chosen to be unlikely to pass exactly
+                // through edges or vertices of typical meshes.
+                let dir = Vector3::new(1.0, 0.318_309_886, 0.141_592_654);
+                let n_crossings = triangles
+                    .iter()
+                    .filter(|tri| ray_hits_triangle(p, &dir, tri))
+                    .count();
+                n_crossings % 2 == 1
+            }
+        }
+    }
+}
+
+/// Möller–Trumbore ray-triangle intersection.
+fn ray_hits_triangle(orig: &Point3<f64>, dir: &Vector3<f64>, tri: &[Point3<f64>; 3]) -> bool {
+    let e1 = tri[1] - tri[0];
+    let e2 = tri[2] - tri[0];
+    let h = dir.cross(&e2);
+    let a = e1.dot(&h);
+    if a.abs() <= f64::EPSILON * e1.norm() * e2.norm() {
+        // Ray is parallel to the triangle.
+        return false;
+    }
+    let f = 1.0 / a;
+    let s = orig - tri[0];
+    let u = f * s.dot(&h);
+    if !(0.0..=1.0).contains(&u) {
+        return false;
+    }
+    let q = s.cross(&e1);
+    let v = f * dir.dot(&q);
+    if v < 0.0 || u + v > 1.0 {
+        return false;
+    }
+    let t = f * e2.dot(&q);
+    t > 0.0
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    rule: ClosedLoopRule,
+    shape: Option<Shape>,
+}
+
+impl CompiledRule {
+    fn new(rule: &ClosedLoopRule) -> Result<Self> {
+        let invalid = |reason| Error::InvalidClosedLoopRule {
+            rule: rule.name.clone(),
+            reason,
+        };
+        match (&rule.trigger, &rule.volume) {
+            (ClosedLoopTrigger::Enter | ClosedLoopTrigger::Leave, None) => {
+                return Err(invalid("entering or leaving requires a volume"));
+            }
+            (
+                ClosedLoopTrigger::SpeedAbove { speed } | ClosedLoopTrigger::SpeedBelow { speed },
+                _,
+            ) if speed.is_nan() || *speed < 0.0 => {
+                return Err(invalid("speed threshold must be non-negative"));
+            }
+            _ => {}
+        }
+        if let ClosedLoopAction::LedBox { channel, .. } = &rule.action {
+            if !(1..=4).contains(channel) {
+                return Err(invalid("LED box channel must be between 1 and 4"));
+            }
+        }
+        let shape = rule.volume.as_ref().map(Shape::new).transpose()?;
+        Ok(Self {
+            rule: rule.clone(),
+            shape,
+        })
+    }
+
+    /// Evaluate the condition whose rising edge fires this rule.
+    fn condition(&self, pos: &Point3<f64>, speed: f64) -> bool {
+        let inside = self.shape.as_ref().map(|s| s.contains(pos)).unwrap_or(true);
+        match &self.rule.trigger {
+            ClosedLoopTrigger::Enter => inside,
+            ClosedLoopTrigger::Leave => !inside,
+            ClosedLoopTrigger::SpeedAbove { speed: thresh } => inside && speed > *thresh,
+            ClosedLoopTrigger::SpeedBelow { speed: thresh } => inside && speed < *thresh,
+        }
+    }
+}
+
+/// Evaluates closed-loop rules on the tracking output.
+#[derive(Debug)]
+pub struct ClosedLoopEngine {
+    rules: Vec<CompiledRule>,
+    /// For each live object, the last value of each rule's condition.
+    state: BTreeMap<u32, Vec<bool>>,
+}
+
+impl ClosedLoopEngine {
+    /// Create a new engine. This loads any meshes and validates the rules.
+    pub fn new(rules: &[ClosedLoopRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(CompiledRule::new)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            rules,
+            state: BTreeMap::new(),
+        })
+    }
+
+    /// Process a message from the tracker and return the rules which fired,
+    /// together with their actions.
+    pub fn process(&mut self, msg: &SendType) -> Vec<(RuleFiring, ClosedLoopAction)> {
+        match msg {
+            SendType::Birth(row) => self.update(row, true),
+            SendType::Update(row) => self.update(row, false),
+            SendType::Death(obj_id) => {
+                self.state.remove(obj_id);
+                vec![]
+            }
+            SendType::EndOfFrame(_) | SendType::CalibrationFlydraXml(_) => vec![],
+        }
+    }
+
+    fn update(
+        &mut self,
+        row: &SendKalmanEstimatesRow,
+        is_birth: bool,
+    ) -> Vec<(RuleFiring, ClosedLoopAction)> {
+        let pos = Point3::new(row.x, row.y, row.z);
+        let speed = Vector3::new(row.xvel, row.yvel, row.zvel).norm();
+        let current: Vec<bool> = self
+            .rules
+            .iter()
+            .map(|r| r.condition(&pos, speed))
+            .collect();
+
+        let previous = match self.state.get(&row.obj_id) {
+            Some(previous) if !is_birth => previous.clone(),
+            _ => {
+                // Establish the initial state. Only entering fires at birth.
+                self.rules
+                    .iter()
+                    .zip(current.iter())
+                    .map(|(r, c)| match r.rule.trigger {
+                        ClosedLoopTrigger::Enter => false,
+                        _ => *c,
+                    })
+                    .collect()
+            }
+        };
+
+        let firings = self
+            .rules
+            .iter()
+            .zip(previous.iter().zip(current.iter()))
+            .filter(|(_, (prev, cur))| !**prev && **cur)
+            .map(|(r, _)| {
+                let firing = RuleFiring {
+                    rule: r.rule.name.clone(),
+                    obj_id: row.obj_id,
+                    frame: row.frame,
+                    trigger: r.rule.trigger.clone(),
+                    x: row.x,
+                    y: row.y,
+                    z: row.z,
+                    speed,
+                };
+                (firing, r.rule.action.clone())
+            })
+            .collect();
+
+        self.state.insert(row.obj_id, current);
+        firings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(obj_id: u32, frame: u64, pos: [f64; 3], vel: [f64; 3]) -> SendKalmanEstimatesRow {
+        SendKalmanEstimatesRow {
+            obj_id,
+            frame: SyncFno(frame),
+            x: pos[0],
+            y: pos[1],
+            z: pos[2],
+            xvel: vel[0],
+            yvel: vel[1],
+            zvel: vel[2],
+            P00: 0.0,
+            P01: 0.0,
+            P02: 0.0,
+            P11: 0.0,
+            P12: 0.0,
+            P22: 0.0,
+            P33: 0.0,
+            P44: 0.0,
+            P55: 0.0,
+        }
+    }
+
+    fn rule(name: &str, volume: Option<Volume3D>, trigger: ClosedLoopTrigger) -> ClosedLoopRule {
+        ClosedLoopRule {
+            name: name.into(),
+            volume,
+            trigger,
+            action: ClosedLoopAction::EventStream,
+        }
+    }
+
+    fn names(firings: Vec<(RuleFiring, ClosedLoopAction)>) -> Vec<String> {
+        firings.into_iter().map(|(f, _)| f.rule).collect()
+    }
+
+    #[test]
+    fn test_shapes() {
+        let b = Shape::new(&Volume3D::Box {
+            min: [0.0, 0.0, 0.0],
+            max: [1.0, 2.0, 3.0],
+        })
+        .unwrap();
+        assert!(b.contains(&Point3::new(0.5, 1.5, 2.5)));
+        assert!(!b.contains(&Point3::new(0.5, 2.5, 2.5)));
+
+        let s = Shape::new(&Volume3D::Sphere {
+            center: [1.0, 1.0, 1.0],
+            radius: 0.5,
+        })
+        .unwrap();
+        assert!(s.contains(&Point3::new(1.3, 1.3, 1.0)));
+        assert!(!s.contains(&Point3::new(1.4, 1.4, 1.0)));
+
+        let c = Shape::new(&Volume3D::Cylinder {
+            base_center: [0.0, 0.0, 0.0],
+            radius: 1.0,
+            height: 2.0,
+        })
+        .unwrap();
+        assert!(c.contains(&Point3::new(0.6, 0.6, 1.9)));
+        assert!(!c.contains(&Point3::new(0.8, 0.8, 1.0)));
+        assert!(!c.contains(&Point3::new(0.0, 0.0, -0.1)));
+    }
+
+    #[test]
+    fn test_mesh() {
+        // A unit cube with texture coordinates.
+        const CUBE: &[u8] = b"o cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vt 0 0
+f 1/1 4/1 3/1 2/1
+f 5/1 6/1 7/1 8/1
+f 1/1 2/1 6/1 5/1
+f 2/1 3/1 7/1 6/1
+f 3/1 4/1 8/1 7/1
+f 4/1 1/1 5/1 8/1
+";
+        let dir = tempfile::tempdir().unwrap();
+        let filename = dir.path().join("cube.obj");
+        std::fs::write(&filename, CUBE).unwrap();
+        let mesh = Shape::new(&Volume3D::Mesh { filename }).unwrap();
+        assert!(mesh.contains(&Point3::new(0.5, 0.5, 0.5)));
+        assert!(mesh.contains(&Point3::new(0.9, 0.1, 0.2)));
+        assert!(!mesh.contains(&Point3::new(1.5, 0.5, 0.5)));
+        assert!(!mesh.contains(&Point3::new(-0.5, 0.5, 0.5)));
+        assert!(!mesh.contains(&Point3::new(0.5, 0.5, 1.01)));
+    }
+
+    #[test]
+    fn test_enter_leave() {
+        let sphere = Volume3D::Sphere {
+            center: [0.0, 0.0, 0.0],
+            radius: 1.0,
+        };
+        let mut engine = ClosedLoopEngine::new(&[
+            rule("enter", Some(sphere.clone()), ClosedLoopTrigger::Enter),
+            rule("leave", Some(sphere), ClosedLoopTrigger::Leave),
+        ])
+        .unwrap();
+        let zero = [0.0; 3];
+
+        // Born outside: nothing fires.
+        let fired = engine.process(&SendType::Birth(row(1, 10, [2.0, 0.0, 0.0], zero)));
+        assert!(fired.is_empty());
+        // Moving inside fires "enter" once.
+        let fired = engine.process(&SendType::Update(row(1, 11, [0.5, 0.0, 0.0], zero)));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].0.rule, "enter");
+        assert_eq!(fired[0].0.frame, SyncFno(11));
+        assert_eq!(fired[0].0.obj_id, 1);
+        let fired = engine.process(&SendType::Update(row(1, 12, [0.4, 0.0, 0.0], zero)));
+        assert!(fired.is_empty());
+        // Moving outside fires "leave".
+        let fired = engine.process(&SendType::Update(row(1, 13, [1.5, 0.0, 0.0], zero)));
+        assert_eq!(names(fired), vec!["leave"]);
+
+        // Born inside fires "enter" but not "leave".
+        let fired = engine.process(&SendType::Birth(row(2, 14, zero, zero)));
+        assert_eq!(names(fired), vec!["enter"]);
+        // Dying inside does not fire "leave".
+        assert!(engine.process(&SendType::Death(2)).is_empty());
+        assert!(engine.state.get(&2).is_none());
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut engine = ClosedLoopEngine::new(&[
+            rule("fast", None, ClosedLoopTrigger::SpeedAbove { speed: 0.5 }),
+            rule("slow", None, ClosedLoopTrigger::SpeedBelow { speed: 0.1 }),
+        ])
+        .unwrap();
+        let zero = [0.0; 3];
+
+        // Born slow: "slow" does not fire at birth.
+        assert!(engine
+            .process(&SendType::Birth(row(1, 0, zero, zero)))
+            .is_empty());
+        let fired = engine.process(&SendType::Update(row(1, 1, zero, [0.3, 0.3, 0.3])));
+        assert_eq!(names(fired), vec!["fast"]);
+        let fired = engine.process(&SendType::Update(row(1, 2, zero, [0.3, 0.3, 0.3])));
+        assert!(fired.is_empty());
+        let fired = engine.process(&SendType::Update(row(1, 3, zero, [0.0, 0.05, 0.0])));
+        assert_eq!(names(fired), vec!["slow"]);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(ClosedLoopEngine::new(&[rule("a", None, ClosedLoopTrigger::Enter)]).is_err());
+        let mut r = rule("b", None, ClosedLoopTrigger::SpeedAbove { speed: 1.0 });
+        r.action = ClosedLoopAction::LedBox {
+            channel: 5,
+            on: true,
+            intensity: None,
+        };
+        assert!(ClosedLoopEngine::new(&[r]).is_err());
+    }
+}
@@ -1,15 +1,17 @@
-use std::collections::{BTreeMap, BTreeSet};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex, RwLock,
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{safe_u8, CamInfoRow, MyFloat};
 use braid_types::{
-    BuiServerInfo, CamInfo, CamNum, ConnectedCameraSyncState, PtpStamp, PtpSyncConfig, RawCamName,
-    RecentStats, SyncFno, TriggerType, TRIGGERBOX_SYNC_SECONDS,
+    BuiServerInfo, CamInfo, CamNum, ConnectedCameraSyncState, DeviceTimestampConfig, PtpStamp,
+    PtpSyncConfig, RawCamName, RecentStats, SyncFno, TriggerType, TRIGGERBOX_SYNC_SECONDS,
 };
+use strand_cam_bui_types::ClockModel;
 
 pub(crate) trait HasCameraList {
     fn camera_list(&self) -> CameraList;
@@ -45,15 +47,147 @@ pub struct ConnectedCameraInfo {
     http_camserver_info: BuiServerInfo,
     frames_during_sync: u64,
     _camera_periodic_signal_period_usec: Option<f64>,
+    /// Most recent synchronized frame number computed from device timestamps.
+    last_device_fno: Option<u64>,
+    /// Most recent synchronized frame number.
+    last_synced_frame: Option<u64>,
+    /// When the most recent frame was received.
+    last_frame_received: Option<std::time::Instant>,
+    /// The camera dropped out and has not yet been synchronized again.
+    absent: bool,
+    /// Estimates the frame offset of a camera rejoining while the triggerbox
+    /// keeps running. `None` if the camera waits for a synchronization pause.
+    rejoin: Option<RejoinEstimator>,
+    /// Smoothed time from the trigger pulse to the reception of a frame, in
+    /// seconds. Only known with a triggerbox clock model.
+    latency: Option<f64>,
 }
 
 impl ConnectedCameraInfo {
+    /// Weight of each new frame in the latency estimate.
+    const LATENCY_GAIN: f64 = 0.05;
+
+    fn new(
+        cam_num: CamNum,
+        raw_cam_name: RawCamName,
+        http_camserver_info: &BuiServerInfo,
+        camera_periodic_signal_period_usec: Option<f64>,
+    ) -> Self {
+        Self {
+            cam_num,
+            raw_cam_name,
+            sync_state: ConnectedCameraSyncState::Unsynchronized,
+            http_camserver_info: http_camserver_info.clone(),
+            frames_during_sync: 0,
+            _camera_periodic_signal_period_usec: camera_periodic_signal_period_usec,
+            last_device_fno: None,
+            last_synced_frame: None,
+            last_frame_received: None,
+            absent: false,
+            rejoin: None,
+            latency: None,
+        }
+    }
+
     fn copy_to_caminfo(&self) -> CamInfoRow {
         CamInfoRow {
             camn: self.cam_num,
             cam_id: self.raw_cam_name.as_str().to_string(),
         }
     }
+
+    /// Mark the camera as absent until it is synchronized again.
+    ///
+    /// Returns the gap if the camera was not already absent. If
+    /// `without_pause` is true, the camera re-acquires its frame offset while
+    /// the triggerbox keeps running rather than during a synchronization
+    /// pause. The cam_num is kept so that saved data remains consistent.
+    fn drop_out(&mut self, without_pause: bool) -> Option<CameraGap> {
+        let gap = (!self.absent).then(|| CameraGap::DroppedOut {
+            cam_name: self.raw_cam_name.clone(),
+            last_frame: self.last_synced_frame.map(SyncFno),
+        });
+        self.sync_state = ConnectedCameraSyncState::Unsynchronized;
+        self.frames_during_sync = 0;
+        self.last_device_fno = None;
+        self.absent = true;
+        self.rejoin = without_pause.then(RejoinEstimator::default);
+        gap
+    }
+}
+
+/// A camera stopped or resumed contributing to tracking.
+///
+/// Tracking continues with the remaining cameras while a camera is absent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraGap {
+    /// The camera stopped sending frames or registered again (e.g. because its
+    /// Strand Camera was restarted).
+    DroppedOut {
+        cam_name: RawCamName,
+        /// The last synchronized frame received from the camera.
+        last_frame: Option<SyncFno>,
+    },
+    /// The camera was synchronized again and contributes to tracking.
+    Rejoined {
+        cam_name: RawCamName,
+        /// The first synchronized frame received from the camera.
+        frame: Option<SyncFno>,
+    },
+}
+
+impl CameraGap {
+    pub fn raw_cam_name(&self) -> &RawCamName {
+        match self {
+            Self::DroppedOut { cam_name, .. } | Self::Rejoined { cam_name, .. } => cam_name,
+        }
+    }
+}
+
+/// Estimates the frame offset of a camera rejoining while the triggerbox keeps
+/// running.
+///
+/// Each received frame gives an estimate of the frame offset from its
+/// reception time. The offset is accepted once the estimates of several
+/// consecutive frames agree.
+#[derive(Debug, Default)]
+struct RejoinEstimator {
+    offsets: VecDeque<f64>,
+    warned_no_latency: bool,
+}
+
+impl RejoinEstimator {
+    /// Number of consecutive frames used for the estimate.
+    const N_FRAMES: usize = 20;
+    /// Maximum spread of the estimates, in frames.
+    const MAX_SPREAD: f64 = 0.8;
+
+    /// Add the estimated frame offset of a frame.
+    ///
+    /// The offset can be negative, e.g. for a camera whose frame counter was
+    /// reset by a restart. It is returned as the two's complement `u64` so that
+    /// `cam_frame.wrapping_sub(frame0)` gives the synchronized frame number.
+    fn push(&mut self, offset: f64) -> Option<u64> {
+        self.offsets.push_back(offset);
+        if self.offsets.len() > Self::N_FRAMES {
+            self.offsets.pop_front();
+        }
+        if self.offsets.len() < Self::N_FRAMES {
+            return None;
+        }
+        let min = self.offsets.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self
+            .offsets
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max - min > Self::MAX_SPREAD {
+            return None;
+        }
+        let mean = self.offsets.iter().sum::<f64>() / self.offsets.len() as f64;
+        Some(mean.round() as i64 as u64)
+    }
 }
 
 #[derive(Debug)]
@@ -65,10 +199,111 @@ struct ConnectedCamerasManagerInner {
     all_expected_cameras_are_present: bool,
     all_expected_cameras_are_synced: bool,
     first_frame_arrived: BTreeSet<RawCamName>,
+    device_clock_sync: Option<DeviceClockSync>,
+    device_framerate_estimate: Option<FramerateEstimate>,
+}
+
+/// Maps timestamps from cameras with synchronized device clocks onto
+/// synchronized frame numbers.
+///
+/// The first timestamp received from any camera defines the frame grid.
+/// Timestamps are then rounded to the nearest frame on this grid, so timing
+/// jitter less than half a frame period is tolerated and dropped frames leave
+/// gaps in the frame numbers. The phase of the grid slowly follows the
+/// received timestamps, which compensates for a small difference between the
+/// configured and actual frame rate.
+#[derive(Debug)]
+struct DeviceClockSync {
+    /// Device timestamp (in nanoseconds) of frame zero.
+    t0: u64,
+    period_nsec: f64,
+    /// Smoothed offset of the received frames from the grid, in periods.
+    phase: f64,
+}
+
+impl DeviceClockSync {
+    /// Weight of each new frame in the phase estimate.
+    const PHASE_GAIN: f64 = 0.01;
+
+    fn new(first_device_timestamp: u64, framerate: f64) -> Self {
+        let period_nsec = 1e9 / framerate;
+        // Frame zero is one period before the first frame so that a slightly
+        // earlier frame from another camera does not get a negative frame
+        // number.
+        let t0 = first_device_timestamp.saturating_sub(period_nsec.round() as u64);
+        Self {
+            t0,
+            period_nsec,
+            phase: 0.0,
+        }
+    }
+
+    /// Compute the synchronized frame number of `device_timestamp`.
+    ///
+    /// Returns `None` if the timestamp precedes frame zero.
+    fn frame_number(&mut self, device_timestamp: u64) -> Option<u64> {
+        let elapsed = device_timestamp.checked_sub(self.t0)?;
+        let n_periods = elapsed as f64 / self.period_nsec;
+        let fno = (n_periods - self.phase).round();
+        if fno < 0.0 {
+            return None;
+        }
+        self.phase += Self::PHASE_GAIN * (n_periods - fno - self.phase);
+        Some(fno as u64)
+    }
+}
+
+/// Estimates the frame rate from the device timestamps of one camera.
+///
+/// This is used with device timestamp synchronization if no frame rate is
+/// configured. The camera frame counter is used so that dropped frames do not
+/// bias the estimate.
+#[derive(Debug)]
+struct FramerateEstimate {
+    raw_cam_name: RawCamName,
+    first_device_timestamp: u64,
+    first_framenumber: i32,
+}
+
+impl FramerateEstimate {
+    /// Number of frames spanned by the estimate.
+    const NUM_FRAMES: i32 = 100;
+
+    fn new(raw_cam_name: RawCamName, first_device_timestamp: u64, first_framenumber: i32) -> Self {
+        Self {
+            raw_cam_name,
+            first_device_timestamp,
+            first_framenumber,
+        }
+    }
+
+    /// The frame rate, if the frame is at least [Self::NUM_FRAMES] frames
+    /// after the first frame of the same camera.
+    fn framerate(
+        &self,
+        raw_cam_name: &RawCamName,
+        device_timestamp: u64,
+        framenumber: i32,
+    ) -> Option<f64> {
+        if raw_cam_name != &self.raw_cam_name {
+            return None;
+        }
+        let n_frames = framenumber.checked_sub(self.first_framenumber)?;
+        if n_frames < Self::NUM_FRAMES {
+            return None;
+        }
+        let elapsed = device_timestamp.checked_sub(self.first_device_timestamp)?;
+        if elapsed == 0 {
+            return None;
+        }
+        Some(1e9 * n_frames as f64 / elapsed as f64)
+    }
 }
 
 pub trait ConnectedCamCallback: Send {
     fn on_cam_changed(&self, _: Vec<CamInfo>);
+    /// Called when a camera drops out or rejoins.
+    fn on_cam_gap(&self, _: &CameraGap) {}
 }
 
 /// keeps track of connected camera state
@@ -88,6 +323,9 @@ pub struct ConnectedCamerasManager {
 }
 
 impl HasCameraList for ConnectedCamerasManager {
+    /// The cameras expected to contribute to each frame.
+    ///
+    /// Cameras which dropped out are not waited for until they rejoin.
     fn camera_list(&self) -> CameraList {
         let inner: BTreeSet<u8> = self
             .inner
@@ -95,6 +333,7 @@ impl HasCameraList for ConnectedCamerasManager {
             .unwrap()
             .ccis
             .values()
+            .filter(|cci| !cci.absent)
             .map(|cci| cci.cam_num.0)
             .collect();
         CameraList { inner }
@@ -154,6 +393,8 @@ impl ConnectedCamerasManager {
                 all_expected_cameras_are_present: false,
                 all_expected_cameras_are_synced: false,
                 first_frame_arrived: BTreeSet::new(),
+                device_clock_sync: None,
+                device_framerate_estimate: None,
             })),
             on_cam_change_func: Arc::new(Mutex::new(None)),
             recon: recon.clone(),
@@ -185,6 +426,8 @@ impl ConnectedCamerasManager {
             inner.next_cam_num = next_cam_num.into();
             let old_ccis = std::mem::take(&mut inner.ccis);
             inner.not_yet_connected = not_yet_connected;
+            inner.device_clock_sync = None;
+            inner.device_framerate_estimate = None;
             old_ccis
         };
 
@@ -237,6 +480,26 @@ impl ConnectedCamerasManager {
         }
     }
 
+    fn notify_cam_gap_listeners(&self, gap: &CameraGap) {
+        match gap {
+            CameraGap::DroppedOut {
+                cam_name,
+                last_frame,
+            } => warn!(
+                "Camera \"{}\" dropped out after frame {last_frame:?}. Tracking continues without it.",
+                cam_name.as_str()
+            ),
+            CameraGap::Rejoined { cam_name, frame } => info!(
+                "Camera \"{}\" rejoined at frame {frame:?}.",
+                cam_name.as_str()
+            ),
+        }
+        let mutex_guard = self.on_cam_change_func.lock().unwrap();
+        if let Some(cb) = mutex_guard.as_ref() {
+            cb.on_cam_gap(gap)
+        }
+    }
+
     /// Alternative constructor for use in case of a single camera.
     ///
     /// See `new` and `register_new_camera` for the case when multiple cameras
@@ -290,14 +553,12 @@ impl ConnectedCamerasManager {
 
             inner.ccis.insert(
                 raw_cam_name.clone(),
-                ConnectedCameraInfo {
+                ConnectedCameraInfo::new(
                     cam_num,
                     raw_cam_name,
-                    sync_state: ConnectedCameraSyncState::Unsynchronized,
-                    http_camserver_info: http_camserver_info.clone(),
-                    frames_during_sync: 0,
-                    _camera_periodic_signal_period_usec: camera_periodic_signal_period_usec,
-                },
+                    http_camserver_info,
+                    camera_periodic_signal_period_usec,
+                ),
             );
         }
         this
@@ -310,6 +571,11 @@ impl ConnectedCamerasManager {
 
     /// This is called to register a camera when it connects to the mainbrain.
     ///
+    /// If a camera with this name is already connected (e.g. because its
+    /// Strand Camera was restarted), it keeps its camera number and is treated
+    /// as absent until it is synchronized again. If other cameras are
+    /// synchronized, it re-acquires its frame offset while they keep tracking.
+    ///
     /// See `new_single_cam` for the case when only a single camera will be
     /// added.
     pub fn register_new_camera(
@@ -324,49 +590,55 @@ impl ConnectedCamerasManager {
             );
         }
         let raw_cam_name = raw_cam_name.clone();
-        let cam_num = {
+        let (cam_num, gap) = {
             // This scope is for the write lock on self.inner. Keep it minimal.
             let mut inner = self.inner.write().unwrap();
 
-            if inner.ccis.contains_key(&raw_cam_name) {
-                tracing::error!(
-                    "Camera \"{raw_cam_name}\" has already connected but is attempting to connect again."
+            let others_synchronized = inner
+                .ccis
+                .values()
+                .any(|cci| cci.raw_cam_name != raw_cam_name && cci.sync_state.is_synchronized());
+            if let Some(cci) = inner.ccis.get_mut(&raw_cam_name) {
+                warn!(
+                    "Camera \"{raw_cam_name}\" has already connected and is connecting again. \
+                    Keeping camera number {}.",
+                    cci.cam_num
                 );
-                return Err("camera already connected");
-            }
-
-            let cam_num = if let Some(pre_existing) = inner.not_yet_connected.remove(&raw_cam_name)
-            {
-                debug!(
-                    "registering camera {}, which is in existing calibration",
-                    raw_cam_name.as_str()
-                );
-                pre_existing
+                cci.http_camserver_info = http_camserver_info.clone();
+                let gap = cci.drop_out(others_synchronized);
+                (cci.cam_num, gap)
             } else {
-                if self.recon.is_some() {
-                    tracing::warn!(
-                        "Camera {} connected, but this is not in existing calibration.",
-                        raw_cam_name.as_str()
-                    );
-                }
-                // unknown (and thus un-calibrated) camera
-                let cam_num_inner = inner.next_cam_num;
-                inner.next_cam_num.0 += 1;
-                cam_num_inner
-            };
-
-            inner.ccis.insert(
-                raw_cam_name.clone(),
-                ConnectedCameraInfo {
-                    cam_num,
-                    raw_cam_name: raw_cam_name.clone(),
-                    sync_state: ConnectedCameraSyncState::Unsynchronized,
-                    http_camserver_info: http_camserver_info.clone(),
-                    frames_during_sync: 0,
-                    _camera_periodic_signal_period_usec: camera_periodic_signal_period_usec,
-                },
-            );
-            cam_num
+                let cam_num =
+                    if let Some(pre_existing) = inner.not_yet_connected.remove(&raw_cam_name) {
+                        debug!(
+                            "registering camera {}, which is in existing calibration",
+                            raw_cam_name.as_str()
+                        );
+                        pre_existing
+                    } else {
+                        if self.recon.is_some() {
+                            tracing::warn!(
+                                "Camera {} connected, but this is not in existing calibration.",
+                                raw_cam_name.as_str()
+                            );
+                        }
+                        // unknown (and thus un-calibrated) camera
+                        let cam_num_inner = inner.next_cam_num;
+                        inner.next_cam_num.0 += 1;
+                        cam_num_inner
+                    };
+
+                inner.ccis.insert(
+                    raw_cam_name.clone(),
+                    ConnectedCameraInfo::new(
+                        cam_num,
+                        raw_cam_name.clone(),
+                        http_camserver_info,
+                        camera_periodic_signal_period_usec,
+                    ),
+                );
+                (cam_num, None)
+            }
         };
         info!(
             "register_new_camera got camera name \"{}\", \
@@ -375,11 +647,53 @@ impl ConnectedCamerasManager {
             cam_num
         );
         self.notify_cam_changed_listeners();
+        if let Some(gap) = gap {
+            self.notify_cam_gap_listeners(&gap);
+        }
         Ok(())
     }
 
+    /// Mark cameras from which no frame was received for `timeout` as absent.
+    ///
+    /// Absent cameras are not waited for when bundling frames, so tracking
+    /// continues with the remaining cameras. When frames arrive again, the
+    /// camera re-acquires its frame offset and rejoins. Call this
+    /// periodically.
+    pub fn check_for_dropouts(&self, timeout: std::time::Duration) {
+        self.check_for_dropouts_at(std::time::Instant::now(), timeout)
+    }
+
+    fn check_for_dropouts_at(&self, now: std::time::Instant, timeout: std::time::Duration) {
+        let gaps: Vec<CameraGap> = {
+            // This scope is for the write lock on self.inner. Keep it minimal.
+            let mut inner = self.inner.write().unwrap();
+            inner
+                .ccis
+                .values_mut()
+                .filter(|cci| {
+                    cci.sync_state.is_synchronized()
+                        && cci.last_frame_received.is_some_and(|last_frame_received| {
+                            now.saturating_duration_since(last_frame_received) >= timeout
+                        })
+                })
+                .filter_map(|cci| cci.drop_out(true))
+                .collect()
+        };
+        if gaps.is_empty() {
+            return;
+        }
+        self.notify_cam_changed_listeners();
+        for gap in gaps.iter() {
+            self.notify_cam_gap_listeners(gap);
+        }
+    }
+
     /// Register that a new frame was received
     ///
+    /// `clock_model` is the current triggerbox clock model, if any. It is
+    /// used to re-acquire the frame offset of cameras which rejoin while the
+    /// triggerbox keeps running.
+    ///
     /// Returns synced frame number
     pub fn got_new_frame_live<F>(
         &self,
@@ -387,6 +701,7 @@ impl ConnectedCamerasManager {
         sync_pulse_pause_started_arc: &Arc<RwLock<Option<std::time::Instant>>>,
         send_new_frame_offset: F,
         trigger_cfg: &TriggerType,
+        clock_model: Option<&ClockModel>,
     ) -> Option<SyncFno>
     where
         F: FnMut(u64),
@@ -396,16 +711,21 @@ impl ConnectedCamerasManager {
                 packet,
                 sync_pulse_pause_started_arc,
                 TRIGGERBOX_SYNC_SECONDS,
+                clock_model,
+            ),
+            TriggerType::FakeSync(_) => self.got_new_frame_live_triggerbox(
+                packet,
+                sync_pulse_pause_started_arc,
+                0,
+                clock_model,
             ),
-            TriggerType::FakeSync(_) => {
-                self.got_new_frame_live_triggerbox(packet, sync_pulse_pause_started_arc, 0)
-            }
             TriggerType::PtpSync(ptpcfg) => self.got_new_frame_live_ptp(packet, ptpcfg)?,
-            TriggerType::DeviceTimestamp => {
-                todo!();
+            TriggerType::DeviceTimestamp(cfg) => {
+                self.got_new_frame_live_device_timestamp(packet, cfg)?
             }
         };
-        self.finish_got_new_frame_live(sync_data, send_new_frame_offset)
+        let received = packet.cam_received_time.as_f64();
+        self.finish_got_new_frame_live(sync_data, received, clock_model, send_new_frame_offset)
     }
 
     /// Register that a new frame was received if we are using the triggerbox (or fake sync).
@@ -414,6 +734,7 @@ impl ConnectedCamerasManager {
         packet: &braid_types::FlydraRawUdpPacket,
         sync_pulse_pause_started_arc: &Arc<RwLock<Option<std::time::Instant>>>,
         sync_time_min_sec: u64,
+        clock_model: Option<&ClockModel>,
     ) -> SyncData {
         assert!(packet.framenumber >= 0);
 
@@ -427,12 +748,16 @@ impl ConnectedCamerasManager {
         let mut new_frame0 = None;
         let mut got_frame_during_sync_time = false;
         let mut do_check_if_all_cameras_present = false;
+        let mut do_rejoin = false;
         {
             let inner = self.inner.read().unwrap();
             if let Some(cci) = inner.ccis.get(&raw_cam_name) {
                 // We know this camera already.
                 use crate::ConnectedCameraSyncState::*;
                 match cci.sync_state {
+                    Unsynchronized if cci.rejoin.is_some() => {
+                        do_rejoin = true;
+                    }
                     Unsynchronized => {
                         do_check_if_all_cameras_present = true;
                         let sync_pulse_pause_started = sync_pulse_pause_started_arc.read().unwrap();
@@ -455,9 +780,13 @@ impl ConnectedCamerasManager {
                         }
                     }
                     Synchronized(frame0) => {
-                        if cam_frame >= frame0 {
+                        // The frame offset of a camera which rejoined with a
+                        // reset frame counter is "negative", so use wrapping
+                        // arithmetic. Frames from before synchronization then
+                        // wrap to values above `i64::MAX`.
+                        let corrected_frame_number = cam_frame.wrapping_sub(frame0);
+                        if i64::try_from(corrected_frame_number).is_ok() {
                             // The camera is already synchronized, return synced frame number
-                            let corrected_frame_number = cam_frame - frame0;
 
                             // if corrected_frame_number > crate::TRIGGERBOX_FIRST_PULSE {
                             if corrected_frame_number == u64::MAX {
@@ -488,6 +817,16 @@ impl ConnectedCamerasManager {
             // we should ignore this new data.
         }
 
+        if do_rejoin {
+            let received = packet.cam_received_time.as_f64();
+            if let Some(frame0) =
+                self.rejoin_frame0(&raw_cam_name, cam_frame, received, clock_model)
+            {
+                new_frame0 = Some(frame0);
+                synced_frame = Some(cam_frame.wrapping_sub(frame0));
+            }
+        }
+
         if got_frame_during_sync_time {
             let frames_during_sync = {
                 // This scope is for the write lock on self.inner. Keep it minimal.
@@ -520,6 +859,48 @@ impl ConnectedCamerasManager {
         }
     }
 
+    /// Estimate the frame offset of a camera rejoining while the triggerbox
+    /// keeps running.
+    ///
+    /// The synchronized frame number of each frame is estimated from its
+    /// reception time using the triggerbox clock model and the latency of the
+    /// camera (or, if unknown, the mean latency of the other cameras). Returns
+    /// the frame offset once enough consistent estimates are available.
+    fn rejoin_frame0(
+        &self,
+        raw_cam_name: &RawCamName,
+        cam_frame: u64,
+        received: f64,
+        clock_model: Option<&ClockModel>,
+    ) -> Option<u64> {
+        let clock_model = clock_model?;
+        let mut inner = self.inner.write().unwrap();
+        let other_latencies: Vec<f64> = inner
+            .ccis
+            .values()
+            .filter(|cci| &cci.raw_cam_name != raw_cam_name)
+            .filter_map(|cci| cci.latency)
+            .collect();
+        let cci = inner.ccis.get_mut(raw_cam_name)?;
+        let latency = cci.latency.or_else(|| {
+            (!other_latencies.is_empty())
+                .then(|| other_latencies.iter().sum::<f64>() / other_latencies.len() as f64)
+        });
+        let rejoin = cci.rejoin.as_mut()?;
+        let Some(latency) = latency else {
+            if !rejoin.warned_no_latency {
+                warn!(
+                    "Cannot synchronize camera \"{}\" while tracking: frame latency unknown.",
+                    raw_cam_name.as_str()
+                );
+                rejoin.warned_no_latency = true;
+            }
+            return None;
+        };
+        let synced_frame_estimate = (received - latency - clock_model.offset) / clock_model.gain;
+        rejoin.push(cam_frame as f64 - synced_frame_estimate)
+    }
+
     /// Register that a new frame was received if we are using PTP
     fn got_new_frame_live_ptp(
         &self,
@@ -603,9 +984,89 @@ impl ConnectedCamerasManager {
         }
     }
 
+    /// Register that a new frame was received if we are using device timestamps.
+    fn got_new_frame_live_device_timestamp(
+        &self,
+        packet: &braid_types::FlydraRawUdpPacket,
+        cfg: &DeviceTimestampConfig,
+    ) -> Option<SyncData> {
+        let raw_cam_name = RawCamName::new(packet.cam_name.clone());
+        let Some(device_timestamp) = packet.device_timestamp else {
+            error!(
+                "No device timestamp for frame from camera \"{}\". Dropping.",
+                raw_cam_name.as_str()
+            );
+            return None;
+        };
+
+        let mut inner_guard = self.inner.write().unwrap();
+        let inner: &mut ConnectedCamerasManagerInner = &mut inner_guard;
+        // If we do not know the camera, it is starting up (or shutting down).
+        // Ignore this frame.
+        let cci = inner.ccis.get_mut(&raw_cam_name)?;
+
+        if inner.device_clock_sync.is_none() {
+            let framerate = match cfg.framerate {
+                Some(framerate) => framerate,
+                None => {
+                    let estimate = inner.device_framerate_estimate.get_or_insert_with(|| {
+                        FramerateEstimate::new(
+                            raw_cam_name.clone(),
+                            device_timestamp,
+                            packet.framenumber,
+                        )
+                    });
+                    // Frames are dropped until the frame rate is known.
+                    let framerate =
+                        estimate.framerate(&raw_cam_name, device_timestamp, packet.framenumber)?;
+                    info!("Frame rate estimated from device timestamps: {framerate:.3} fps");
+                    framerate
+                }
+            };
+            inner.device_clock_sync = Some(DeviceClockSync::new(device_timestamp, framerate));
+        }
+        let clock_sync = inner.device_clock_sync.as_mut().unwrap();
+        let Some(raw_fno) = clock_sync.frame_number(device_timestamp) else {
+            tracing::warn!(
+                "Frame from camera \"{}\" precedes the first synchronized frame. Dropping.",
+                raw_cam_name.as_str()
+            );
+            return None;
+        };
+        tracing::trace!(device_timestamp, raw_fno);
+
+        if let Some(last_fno) = cci.last_device_fno {
+            if raw_fno <= last_fno {
+                tracing::warn!(
+                    "Camera \"{}\": device timestamp {device_timestamp} maps to frame \
+                    {raw_fno} but frame {last_fno} was already received. Is the configured \
+                    frame rate correct? Dropping.",
+                    raw_cam_name.as_str()
+                );
+                return None;
+            }
+        }
+        cci.last_device_fno = Some(raw_fno);
+
+        use crate::ConnectedCameraSyncState::*;
+        let (new_frame0, do_check_if_all_cameras_present) = match &cci.sync_state {
+            Unsynchronized => (Some(0), true),
+            Synchronized(_frame0) => (None, false),
+        };
+
+        Some(SyncData {
+            new_frame0,
+            raw_cam_name,
+            do_check_if_all_cameras_present,
+            synced_frame: Some(raw_fno),
+        })
+    }
+
     fn finish_got_new_frame_live<F>(
         &self,
         sync_data: SyncData,
+        received: f64,
+        clock_model: Option<&ClockModel>,
         mut send_new_frame_offset: F,
     ) -> Option<SyncFno>
     where
@@ -617,22 +1078,48 @@ impl ConnectedCamerasManager {
             do_check_if_all_cameras_present,
             synced_frame,
         } = sync_data;
-        let mut do_check_if_all_cameras_synchronized = false;
-        if let Some(frame0) = new_frame0 {
-            // Perform the book-keeping associated with synchronization.
-            {
-                // This scope is for the write lock on self.inner. Keep it minimal.
-                let mut inner = self.inner.write().unwrap();
-                match inner.ccis.get_mut(&raw_cam_name) {
-                    Some(cci) => {
+        let mut rejoined = None;
+        {
+            // This scope is for the write lock on self.inner. Keep it minimal.
+            let mut inner = self.inner.write().unwrap();
+            match inner.ccis.get_mut(&raw_cam_name) {
+                Some(cci) => {
+                    cci.last_frame_received = Some(std::time::Instant::now());
+                    if let Some(frame0) = new_frame0 {
+                        // Perform the book-keeping associated with synchronization.
                         cci.sync_state = ConnectedCameraSyncState::Synchronized(frame0);
+                        if cci.absent {
+                            cci.absent = false;
+                            cci.rejoin = None;
+                            rejoined = Some(CameraGap::Rejoined {
+                                cam_name: raw_cam_name.clone(),
+                                frame: synced_frame.map(SyncFno),
+                            });
+                        }
                     }
-                    None => {
+                    if let Some(synced_frame) = synced_frame {
+                        cci.last_synced_frame = Some(synced_frame);
+                        if let Some(cm) = clock_model {
+                            let latency = received - (synced_frame as f64 * cm.gain + cm.offset);
+                            cci.latency = Some(match cci.latency {
+                                Some(prev) => {
+                                    prev + ConnectedCameraInfo::LATENCY_GAIN * (latency - prev)
+                                }
+                                None => latency,
+                            });
+                        }
+                    }
+                }
+                None => {
+                    if new_frame0.is_some() {
                         panic!("reached impossible code.");
                     }
                 }
             }
+        }
 
+        let mut do_check_if_all_cameras_synchronized = false;
+        if let Some(frame0) = new_frame0 {
             self.notify_cam_changed_listeners();
 
             // Do notifications associated with synchronization.
@@ -644,6 +1131,9 @@ impl ConnectedCamerasManager {
             );
             do_check_if_all_cameras_synchronized = true;
         }
+        if let Some(gap) = rejoined {
+            self.notify_cam_gap_listeners(&gap);
+        }
 
         if do_check_if_all_cameras_present
             && !self.inner.read().unwrap().all_expected_cameras_are_present
@@ -780,3 +1270,395 @@ fn test_camera_list() {
     let c2 = CameraList::new(&[4, 3, 2, 5]);
     assert!(c1 != c2);
 }
+
+#[cfg(test)]
+mod test_device_timestamp {
+    use super::*;
+    use braid_types::FlydraRawUdpPacket;
+
+    const PERIOD_NSEC: u64 = 10_000_000;
+    const T_START: u64 = 1_700_000_000_000_000_000;
+
+    fn trigger_cfg() -> TriggerType {
+        TriggerType::DeviceTimestamp(DeviceTimestampConfig {
+            framerate: Some(100.0),
+        })
+    }
+
+    fn new_manager(cam_names: &[&str]) -> (ConnectedCamerasManager, Arc<AtomicBool>) {
+        let all_expected_cameras = cam_names
+            .iter()
+            .map(|name| RawCamName::new(name.to_string()))
+            .collect();
+        let signal_all_cams_synced = Arc::new(AtomicBool::new(false));
+        let mut ccm = ConnectedCamerasManager::new(
+            &None,
+            all_expected_cameras,
+            Arc::new(AtomicBool::new(false)),
+            signal_all_cams_synced.clone(),
+            None,
+        );
+        for name in cam_names {
+            ccm.register_new_camera(
+                &RawCamName::new(name.to_string()),
+                &BuiServerInfo::NoServer,
+                None,
+            )
+            .unwrap();
+        }
+        (ccm, signal_all_cams_synced)
+    }
+
+    fn packet(
+        cam_name: &str,
+        framenumber: i32,
+        device_timestamp: Option<u64>,
+    ) -> FlydraRawUdpPacket {
+        FlydraRawUdpPacket {
+            cam_name: cam_name.to_string(),
+            timestamp: None,
+            cam_received_time: chrono::Utc::now().into(),
+            device_timestamp,
+            block_id: None,
+            framenumber,
+            points: vec![],
+        }
+    }
+
+    fn synced_frame(ccm: &ConnectedCamerasManager, packet: &FlydraRawUdpPacket) -> Option<u64> {
+        let sync_pulse_pause_started_arc = Arc::new(RwLock::new(None));
+        ccm.got_new_frame_live(
+            packet,
+            &sync_pulse_pause_started_arc,
+            |_| {},
+            &trigger_cfg(),
+            None,
+        )
+        .map(|fno| fno.0)
+    }
+
+    /// Deterministic timing jitter in the range of +/- 2 msec.
+    fn jitter(i: u64, cam: u64) -> i64 {
+        ((i * 7 + cam * 3) % 5) as i64 * 1_000_000 - 2_000_000
+    }
+
+    #[test]
+    fn test_jitter_and_late_start() {
+        let (ccm, signal_all_cams_synced) = new_manager(&["a", "b"]);
+        let mut prev = None;
+        for i in 0..200u64 {
+            let nominal = T_START + i * PERIOD_NSEC;
+            let ts_a = nominal.checked_add_signed(jitter(i, 0)).unwrap();
+            let fno_a = synced_frame(&ccm, &packet("a", i as i32, Some(ts_a))).unwrap();
+            if let Some(prev) = prev {
+                assert_eq!(fno_a, prev + 1);
+            }
+            prev = Some(fno_a);
+
+            // Camera "b" starts 5 frames later with its own frame counter.
+            if i >= 5 {
+                let ts_b = nominal.checked_add_signed(jitter(i, 1)).unwrap();
+                let fno_b = synced_frame(&ccm, &packet("b", (i - 5) as i32, Some(ts_b))).unwrap();
+                assert_eq!(fno_a, fno_b);
+            }
+        }
+        assert!(signal_all_cams_synced.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_dropped_frames() {
+        let (ccm, _) = new_manager(&["a"]);
+        let fno0 = synced_frame(&ccm, &packet("a", 0, Some(T_START))).unwrap();
+        let fno1 = synced_frame(&ccm, &packet("a", 1, Some(T_START + PERIOD_NSEC))).unwrap();
+        assert_eq!(fno1, fno0 + 1);
+        // Three frames dropped. The camera frame counter does not matter.
+        let fno2 = synced_frame(&ccm, &packet("a", 2, Some(T_START + 5 * PERIOD_NSEC))).unwrap();
+        assert_eq!(fno2, fno0 + 5);
+    }
+
+    #[test]
+    fn test_framerate_mismatch() {
+        // The actual frame rate is slightly higher than configured. Over 2000
+        // frames, this accumulates to one whole frame period.
+        let (ccm, _) = new_manager(&["a", "b"]);
+        let actual_period_nsec = PERIOD_NSEC - 5_000;
+        let mut prev = None;
+        for i in 0..2000u64 {
+            let ts = T_START + i * actual_period_nsec;
+            let fno_a = synced_frame(&ccm, &packet("a", i as i32, Some(ts))).unwrap();
+            let fno_b = synced_frame(&ccm, &packet("b", i as i32, Some(ts + 1_000_000))).unwrap();
+            assert_eq!(fno_a, fno_b);
+            if let Some(prev) = prev {
+                assert_eq!(fno_a, prev + 1);
+            }
+            prev = Some(fno_a);
+        }
+    }
+
+    #[test]
+    fn test_estimated_framerate() {
+        let (ccm, signal_all_cams_synced) = new_manager(&["a", "b"]);
+        let trigger_cfg = TriggerType::DeviceTimestamp(DeviceTimestampConfig::default());
+        let sync_pulse_pause_started_arc = Arc::new(RwLock::new(None));
+        let synced_frame = |packet: &FlydraRawUdpPacket| {
+            ccm.got_new_frame_live(
+                packet,
+                &sync_pulse_pause_started_arc,
+                |_| {},
+                &trigger_cfg,
+                None,
+            )
+            .map(|fno| fno.0)
+        };
+
+        // The actual frame rate differs from the 100 fps used elsewhere.
+        let actual_period_nsec = PERIOD_NSEC + 123_000;
+        let mut prev = None;
+        for i in 0..2000u64 {
+            let ts = T_START + i * actual_period_nsec;
+            let fno_a = synced_frame(&packet("a", i as i32, Some(ts)));
+            let fno_b = synced_frame(&packet("b", i as i32, Some(ts + 1_000_000)));
+            if i < FramerateEstimate::NUM_FRAMES as u64 {
+                // Frames are dropped while the frame rate is estimated.
+                assert_eq!(fno_a, None);
+                assert_eq!(fno_b, None);
+                continue;
+            }
+            let fno_a = fno_a.unwrap();
+            assert_eq!(Some(fno_a), fno_b);
+            if let Some(prev) = prev {
+                assert_eq!(fno_a, prev + 1);
+            }
+            prev = Some(fno_a);
+        }
+        assert!(signal_all_cams_synced.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_invalid_packets_dropped() {
+        let (ccm, _) = new_manager(&["a", "b"]);
+        // No device timestamp.
+        assert_eq!(synced_frame(&ccm, &packet("a", 0, None)), None);
+        // Unknown camera.
+        assert_eq!(synced_frame(&ccm, &packet("c", 0, Some(T_START))), None);
+
+        let fno0 = synced_frame(&ccm, &packet("a", 0, Some(T_START))).unwrap();
+        // Frame well before frame zero.
+        assert_eq!(
+            synced_frame(&ccm, &packet("b", 0, Some(T_START - 10 * PERIOD_NSEC))),
+            None
+        );
+        // Duplicate timestamp.
+        assert_eq!(synced_frame(&ccm, &packet("a", 1, Some(T_START))), None);
+        let fno1 = synced_frame(&ccm, &packet("a", 2, Some(T_START + PERIOD_NSEC))).unwrap();
+        assert_eq!(fno1, fno0 + 1);
+    }
+}
+
+#[cfg(test)]
+mod test_rejoin {
+    use super::*;
+    use braid_types::{FakeSyncConfig, FlydraFloatTimestampLocal, FlydraRawUdpPacket};
+    use std::{
+        cell::RefCell,
+        time::{Duration, Instant},
+    };
+
+    struct GapRecorder(Arc<Mutex<Vec<CameraGap>>>);
+
+    impl ConnectedCamCallback for GapRecorder {
+        fn on_cam_changed(&self, _: Vec<CamInfo>) {}
+        fn on_cam_gap(&self, gap: &CameraGap) {
+            self.0.lock().unwrap().push(gap.clone());
+        }
+    }
+
+    fn new_manager(cam_names: &[&str]) -> (ConnectedCamerasManager, Arc<Mutex<Vec<CameraGap>>>) {
+        let all_expected_cameras = cam_names
+            .iter()
+            .map(|name| RawCamName::new(name.to_string()))
+            .collect();
+        let mut ccm = ConnectedCamerasManager::new(
+            &None,
+            all_expected_cameras,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+            None,
+        );
+        for name in cam_names {
+            register(&mut ccm, name);
+        }
+        let gaps = Arc::new(Mutex::new(Vec::new()));
+        ccm.set_cam_changed_callback(Box::new(GapRecorder(gaps.clone())));
+        (ccm, gaps)
+    }
+
+    fn register(ccm: &mut ConnectedCamerasManager, name: &str) {
+        ccm.register_new_camera(
+            &RawCamName::new(name.to_string()),
+            &BuiServerInfo::NoServer,
+            None,
+        )
+        .unwrap();
+    }
+
+    fn packet(
+        cam_name: &str,
+        framenumber: u64,
+        received: f64,
+        device_timestamp: Option<u64>,
+    ) -> FlydraRawUdpPacket {
+        FlydraRawUdpPacket {
+            cam_name: cam_name.to_string(),
+            timestamp: None,
+            cam_received_time: FlydraFloatTimestampLocal::from_f64(received),
+            device_timestamp,
+            block_id: None,
+            framenumber: framenumber.try_into().unwrap(),
+            points: vec![],
+        }
+    }
+
+    fn cam_nums(ccm: &ConnectedCamerasManager, cam_names: &[&str]) -> CameraList {
+        let cam_nums: Vec<u8> = cam_names
+            .iter()
+            .map(|name| ccm.cam_num(&RawCamName::new(name.to_string())).unwrap().0)
+            .collect();
+        CameraList::new(&cam_nums)
+    }
+
+    #[test]
+    fn test_dropout_and_rejoin_device_timestamp() {
+        const PERIOD_NSEC: u64 = 10_000_000;
+        const T_START: u64 = 1_700_000_000_000_000_000;
+        let trigger_cfg = TriggerType::DeviceTimestamp(DeviceTimestampConfig {
+            framerate: Some(100.0),
+        });
+        let sync_pulse_pause_started_arc = Arc::new(RwLock::new(None));
+
+        let (ccm, gaps) = new_manager(&["a", "b"]);
+        let synced_frame = |name: &str, i: u64| {
+            let ts = T_START + i * PERIOD_NSEC;
+            let packet = packet(name, i, 0.0, Some(ts));
+            ccm.got_new_frame_live(
+                &packet,
+                &sync_pulse_pause_started_arc,
+                |_| {},
+                &trigger_cfg,
+                None,
+            )
+            .map(|fno| fno.0)
+        };
+
+        let mut last_b = None;
+        for i in 0..10 {
+            synced_frame("a", i).unwrap();
+            last_b = synced_frame("b", i);
+        }
+        assert_eq!(ccm.camera_list(), cam_nums(&ccm, &["a", "b"]));
+
+        // Camera "b" stops sending frames.
+        let b_stopped = Instant::now();
+        std::thread::sleep(Duration::from_millis(5));
+        for i in 10..20 {
+            synced_frame("a", i).unwrap();
+        }
+        let timeout = Duration::from_secs(3600);
+        ccm.check_for_dropouts_at(b_stopped + timeout, timeout);
+        assert_eq!(ccm.camera_list(), cam_nums(&ccm, &["a"]));
+        let b = RawCamName::new("b".into());
+        assert_eq!(
+            gaps.lock().unwrap().as_slice(),
+            &[CameraGap::DroppedOut {
+                cam_name: b.clone(),
+                last_frame: last_b.map(SyncFno),
+            }]
+        );
+
+        // Camera "b" resumes and immediately contributes again.
+        let fno_a = synced_frame("a", 20).unwrap();
+        let fno_b = synced_frame("b", 20).unwrap();
+        assert_eq!(fno_a, fno_b);
+        assert_eq!(ccm.camera_list(), cam_nums(&ccm, &["a", "b"]));
+        assert_eq!(
+            gaps.lock().unwrap().last(),
+            Some(&CameraGap::Rejoined {
+                cam_name: b,
+                frame: Some(SyncFno(fno_b)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejoin_triggerbox() {
+        const GAIN: f64 = 0.01;
+        const OFFSET: f64 = 1_700_000_000.0;
+        let trigger_cfg = TriggerType::FakeSync(FakeSyncConfig { framerate: 100.0 });
+        let clock_model = ClockModel {
+            gain: GAIN,
+            offset: OFFSET,
+            residuals: 0.0,
+            n_measurements: 0,
+        };
+        let sync_pulse_pause_started_arc = Arc::new(RwLock::new(Some(Instant::now())));
+        std::thread::sleep(Duration::from_millis(1));
+
+        let (ccm, gaps) = new_manager(&["a", "b"]);
+        let frame_offsets = RefCell::new(BTreeMap::new());
+        let got_frame = |name: &str, cam_frame: u64, synced: u64| {
+            // Deterministic jitter of the latency in the range of +/- 0.4 msec.
+            let jitter = ((synced * 7) % 5) as f64 * 0.0002 - 0.0004;
+            let received = OFFSET + synced as f64 * GAIN + 0.003 + jitter;
+            let packet = packet(name, cam_frame, received, None);
+            ccm.got_new_frame_live(
+                &packet,
+                &sync_pulse_pause_started_arc,
+                |frame0| {
+                    frame_offsets.borrow_mut().insert(name.to_string(), frame0);
+                },
+                &trigger_cfg,
+                Some(&clock_model),
+            )
+            .map(|fno| fno.0)
+        };
+
+        // Initial synchronization. The frame counters of the cameras differ.
+        let first = crate::TRIGGERBOX_FIRST_PULSE;
+        for synced in first..first + 50 {
+            assert_eq!(got_frame("a", synced + 98, synced), Some(synced));
+            assert_eq!(got_frame("b", synced + 498, synced), Some(synced));
+        }
+        assert_eq!(ccm.camera_list(), cam_nums(&ccm, &["a", "b"]));
+
+        // Camera "b" is restarted. Its frame counter starts again at zero
+        // while the triggerbox keeps running.
+        register(&mut ccm.clone(), "b");
+        assert_eq!(ccm.camera_list(), cam_nums(&ccm, &["a"]));
+        for (i, synced) in (100..130).enumerate() {
+            assert_eq!(got_frame("a", synced + 98, synced), Some(synced));
+            let expected = (i + 1 >= RejoinEstimator::N_FRAMES).then_some(synced);
+            assert_eq!(got_frame("b", i as u64, synced), expected);
+        }
+        assert_eq!(ccm.camera_list(), cam_nums(&ccm, &["a", "b"]));
+        assert_eq!(
+            frame_offsets.borrow().get("b"),
+            Some(&0u64.wrapping_sub(100))
+        );
+
+        let b = RawCamName::new("b".into());
+        let rejoin_frame = 100 + RejoinEstimator::N_FRAMES as u64 - 1;
+        assert_eq!(
+            gaps.lock().unwrap().as_slice(),
+            &[
+                CameraGap::DroppedOut {
+                    cam_name: b.clone(),
+                    last_frame: Some(SyncFno(first + 49)),
+                },
+                CameraGap::Rejoined {
+                    cam_name: b,
+                    frame: Some(SyncFno(rejoin_frame)),
+                },
+            ]
+        );
+    }
+}
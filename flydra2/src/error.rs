@@ -51,6 +51,10 @@ pub enum Error {
     InvalidHypothesisTestingParameters,
     #[error("insufficient data to calculate FPS")]
     InsufficientDataToCalculateFps,
+    #[error("invalid closed-loop rule \"{rule}\": {reason}")]
+    InvalidClosedLoopRule { rule: String, reason: &'static str },
+    #[error("cannot estimate camera pose: {0}")]
+    PoseEstimation(&'static str),
     #[error(transparent)]
     FileError(#[from] FileErrorInner),
     #[error(transparent)]
@@ -31,11 +31,14 @@ use braid_types::{
 };
 
 mod connected_camera_manager;
-pub use connected_camera_manager::{ConnectedCamCallback, ConnectedCamerasManager};
+pub use connected_camera_manager::{CameraGap, ConnectedCamCallback, ConnectedCamerasManager};
 
 mod write_data;
 pub use write_data::BraidMetadataBuilder;
 
+mod checkpoint;
+pub use checkpoint::TrackingCheckpoint;
+
 mod bundled_data;
 mod contiguous_stream;
 mod frame_bundler;
@@ -49,7 +52,14 @@ mod tracking_core;
 mod mini_arenas;
 
 mod model_server;
-pub use crate::model_server::{new_model_server, SendKalmanEstimatesRow, SendType};
+pub use crate::model_server::{new_model_server, UdpPoseSender};
+pub use braid_types::{SendKalmanEstimatesRow, SendType};
+
+mod closed_loop;
+pub use crate::closed_loop::{ClosedLoopEngine, RuleFiring};
+
+mod calibration_drift;
+pub use crate::calibration_drift::{estimate_extrinsics, DriftDetector, DriftEvent};
 
 use crate::contiguous_stream::make_contiguous;
 use crate::frame_bundler::bundle_frames;
@@ -75,16 +85,47 @@ pub(crate) fn generate_observation_model<R>(
 where
     R: RealField + Copy + Default + serde::Serialize,
 {
-    let pt3d: PointWorldFrame<R> = to_world_point(state);
-    // Deals with water if needed.
+    let pt3d: PointWorldFrame<R> = observed_world_point(state, cam.time_offset());
+    // Deals with water and other refractive interfaces if needed.
     let mat2x3 = cam.linearize_numerically_at(&pt3d, nalgebra::convert(0.001))?;
+
+    let r: R = nalgebra::convert(ekf_observation_covariance_pixels);
+    let mut observation_noise_covariance = OMatrix::<R, U2, U2>::identity() * r;
+    if cam.is_fisheye() {
+        // The observations are undistorted pixels, but the noise is in the
+        // distorted image. The undistortion of a fisheye lens stretches the
+        // image away from the optical axis, so transform the noise with the
+        // Jacobian of the undistortion at the expected observation.
+        let distorted = cam.project_3d_to_distorted_pixel(&pt3d);
+        let j = cam.linearize_undistortion_numerically_at(&distorted, nalgebra::convert(0.5));
+        // Beyond 90° from the optical axis there is no undistorted pixel.
+        if j.iter().all(|v| v.is_finite()) {
+            observation_noise_covariance = j * observation_noise_covariance * j.transpose();
+        }
+    }
+
     Ok(CameraObservationModel::new(
         cam.clone(),
         mat2x3,
-        ekf_observation_covariance_pixels,
+        observation_noise_covariance,
     ))
 }
 
+/// The position seen by a camera with time offset `time_offset` (in seconds),
+/// assuming constant velocity.
+pub(crate) fn observed_world_point<R: RealField + Copy>(
+    state: &OVector<R, U6>,
+    time_offset: R,
+) -> PointWorldFrame<R> {
+    PointWorldFrame {
+        coords: Point3::new(
+            state.x + state[3] * time_offset,
+            state.y + state[4] * time_offset,
+            state.z + state[5] * time_offset,
+        ),
+    }
+}
+
 // We use a 6 dimensional state vector:
 // [x,y,z,xvel,yvel,zvel].
 #[derive(Debug)]
@@ -105,18 +146,19 @@ where
     fn new(
         cam: flydra_mvg::MultiCamera<R>,
         a: OMatrix<R, U2, U3>,
-        ekf_observation_covariance_pixels: f64,
+        observation_noise_covariance: OMatrix<R, U2, U2>,
     ) -> Self {
+        // The observed position depends on the velocity if the camera has a
+        // time offset.
         let observation_matrix = {
             let mut o = OMatrix::<R, U2, U6>::zeros();
             o.fixed_columns_mut::<3>(0).copy_from(&a);
+            o.fixed_columns_mut::<3>(3)
+                .copy_from(&(a * cam.time_offset()));
             o
         };
         let observation_matrix_transpose = observation_matrix.transpose();
 
-        let r = nalgebra::convert(ekf_observation_covariance_pixels);
-        let zero = nalgebra::convert(0.0);
-        let observation_noise_covariance = OMatrix::<R, U2, U2>::new(r, zero, zero, r);
         Self {
             cam,
             observation_matrix,
@@ -147,8 +189,8 @@ where
         &self.observation_noise_covariance
     }
     fn predict_observation(&self, state: &OVector<R, U6>) -> OVector<R, U2> {
-        // TODO: update to handle water here. See tag "laksdfjasl".
-        let pt = to_world_point(state);
+        // Refraction at water and other interfaces is handled by the camera. See tag "laksdfjasl".
+        let pt = observed_world_point(state, self.cam.time_offset());
         let undistored = self.cam.project_3d_to_pixel(&pt);
         OMatrix::<R, U1, U2>::new(undistored.coords[0], undistored.coords[1]).transpose()
         // This doesn't compile for some reason:
@@ -304,6 +346,14 @@ impl FrameData {
         }
     }
 
+    /// Add `offset` to the synchronized frame number.
+    fn add_frame_offset(&mut self, offset: u64) {
+        let frame = SyncFno(self.synced_frame.0 + offset);
+        self.synced_frame = frame;
+        self.time_delta.frame = frame;
+        self.tdpt.frame = frame;
+    }
+
     #[inline]
     fn make_time_delta(
         synced_frame: SyncFno,
@@ -455,6 +505,24 @@ pub struct KalmanEstimateRecord {
     pub record: KalmanEstimatesRow,
     pub data_assoc_rows: Vec<DataAssocRow>,
     pub mean_reproj_dist_100x: Option<u64>,
+    /// The observations used for this estimate. These are not saved but sent
+    /// to the listeners added with [CoordProcessor::add_observation_listener].
+    pub observations: Vec<AssociatedObservation>,
+}
+
+/// An observation used to update the estimate of a tracked object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssociatedObservation {
+    pub cam_name: RawCamName,
+    pub frame: SyncFno,
+    pub obj_id: u32,
+    /// Undistorted pixel coordinates of the observation.
+    pub undistorted: [f64; 2],
+    /// Reprojection distance. Calculated on undistorted pixel coords.
+    pub reproj_dist: f64,
+    /// Position of the object after the update with all observations of this
+    /// frame.
+    pub position: [f64; 3],
 }
 
 #[derive(Debug)]
@@ -468,6 +536,13 @@ pub enum SaveToDiskMsg {
     Textlog(TextlogRow),
     TriggerClockInfo(TriggerClockInfoRow),
     SetExperimentUuid(String),
+    SetTrackingParams {
+        /// The first frame tracked with the new parameters.
+        frame: SyncFno,
+        tracking_params: Arc<TrackingParams>,
+    },
+    /// Save the tracking state so that the recording can be resumed.
+    TrackingCheckpoint(TrackingCheckpoint),
 }
 
 /// Acts like a `csv::Writer` but buffers and orders by frame.
@@ -658,6 +733,9 @@ pub struct StartSavingCsvConfig {
     pub per_cam_data: BTreeMap<RawCamName, braid_types::PerCamSaveData>,
     pub print_stats: bool,
     pub save_performance_histograms: bool,
+    /// Append to the files of an unfinished recording in `out_dir` rather than
+    /// creating new files.
+    pub resume: bool,
 }
 
 #[derive(Debug)]
@@ -707,7 +785,11 @@ pub struct CoordProcessor {
     pub braidz_write_tx: SingletonSender<SaveToDiskMsg>,
     pub writer_join_handle: tokio::task::JoinHandle<Result<()>>,
     model_servers: Vec<tokio::sync::mpsc::Sender<(SendType, TimeDataPassthrough)>>,
+    lossy_model_servers: Vec<tokio::sync::mpsc::Sender<(SendType, TimeDataPassthrough)>>,
+    observation_listeners: Vec<tokio::sync::mpsc::Sender<Vec<AssociatedObservation>>>,
     tracking_params: Arc<TrackingParams>,
+    tracking_params_tx: tokio::sync::mpsc::Sender<TrackingParams>,
+    tracking_params_rx: tokio::sync::mpsc::Receiver<TrackingParams>,
     /// Images of the "mini arenas" in use.
     ///
     /// One per camera when we have calibrations to do tracking. Empty
@@ -720,6 +802,8 @@ pub struct CoordProcessor {
         Vec<crate::tracking_core::ModelCollection<crate::tracking_core::CollectionFrameDone>>,
     >,
     next_obj_id: Arc<Mutex<u32>>,
+    /// The checkpoint from which to resume tracking, if any.
+    resume_checkpoint: Option<TrackingCheckpoint>,
 }
 
 impl CoordProcessor {
@@ -781,16 +865,23 @@ impl CoordProcessor {
             }
         });
 
+        let (tracking_params_tx, tracking_params_rx) = tokio::sync::mpsc::channel(10);
+
         Ok(Self {
             cam_manager,
             recon,
             braidz_write_tx: SingletonSender(braidz_write_tx),
             writer_join_handle,
             tracking_params,
+            tracking_params_tx,
+            tracking_params_rx,
             model_servers: vec![],
+            lossy_model_servers: vec![],
+            observation_listeners: vec![],
             model_collections: None,
             mini_arena_images,
             next_obj_id: Arc::new(Mutex::new(0)),
+            resume_checkpoint: None,
         })
     }
 
@@ -816,6 +907,10 @@ impl CoordProcessor {
             .collect()
     }
 
+    /// Add a listener for the tracking output.
+    ///
+    /// Tracking waits for the listener if its channel is full, so the listener
+    /// receives every update.
     pub fn add_listener(
         &mut self,
         model_server: tokio::sync::mpsc::Sender<(SendType, TimeDataPassthrough)>,
@@ -823,6 +918,64 @@ impl CoordProcessor {
         self.model_servers.push(model_server);
     }
 
+    /// Add a listener for the tracking output which may miss updates.
+    ///
+    /// Unlike with [Self::add_listener], tracking updates are dropped if the
+    /// channel is full so that a slow listener, such as the closed-loop rules,
+    /// does not delay tracking.
+    pub fn add_lossy_listener(
+        &mut self,
+        model_server: tokio::sync::mpsc::Sender<(SendType, TimeDataPassthrough)>,
+    ) {
+        self.lossy_model_servers.push(model_server);
+    }
+
+    /// Add a listener for the observations used to update tracked objects.
+    ///
+    /// The observations of each frame are sent in one message. As with
+    /// [Self::add_lossy_listener], messages are dropped if the channel is full
+    /// so that a slow listener does not delay tracking.
+    pub fn add_observation_listener(
+        &mut self,
+        listener: tokio::sync::mpsc::Sender<Vec<AssociatedObservation>>,
+    ) {
+        self.observation_listeners.push(listener);
+    }
+
+    /// Resume tracking from a checkpoint of an unfinished recording.
+    ///
+    /// The live objects of the checkpoint are restored when the first frame is
+    /// processed and new objects continue with the obj_ids of the checkpoint.
+    /// Frame numbers are offset to continue after the checkpoint frame (see
+    /// [TrackingCheckpoint]).
+    pub fn resume_from(&mut self, checkpoint: TrackingCheckpoint) {
+        *self.next_obj_id.lock().unwrap() = checkpoint.next_obj_id;
+        self.resume_checkpoint = Some(checkpoint);
+    }
+
+    fn checkpoint(&self, frame: SyncFno) -> TrackingCheckpoint {
+        TrackingCheckpoint {
+            frame: frame.0,
+            saved_at: strand_datetime_conversion::datetime_to_f64(&chrono::Local::now()),
+            next_obj_id: *self.next_obj_id.lock().unwrap(),
+            mini_arenas: self
+                .model_collections
+                .iter()
+                .flatten()
+                .map(|mc| mc.checkpoint())
+                .collect(),
+        }
+    }
+
+    /// Get a sender with which to change the tracking parameters while running.
+    ///
+    /// New parameters take effect at the next frame boundary. Parameters which
+    /// fail [TrackingParams::check_runtime_change] are ignored with an error
+    /// message.
+    pub fn tracking_params_sender(&self) -> tokio::sync::mpsc::Sender<TrackingParams> {
+        self.tracking_params_tx.clone()
+    }
+
     /// Consume the CoordProcessor and the input stream.
     ///
     /// Returns a future that completes when done. This is basically the "main
@@ -844,12 +997,28 @@ impl CoordProcessor {
         let mut prev_frame = SyncFno(0);
         use futures::stream::StreamExt;
 
+        let frame_offset = match &self.resume_checkpoint {
+            Some(checkpoint) => {
+                let now = strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+                let frame_offset = checkpoint.frame_offset(expected_framerate, now);
+                info!(
+                    "Resuming from checkpoint at frame {} with {} objects. Frame numbers are offset by {}.",
+                    checkpoint.frame,
+                    checkpoint.num_objects(),
+                    frame_offset
+                );
+                frame_offset
+            }
+            None => 0,
+        };
+        let mut models_to_restore = self.resume_checkpoint.take().map(|c| c.mini_arenas);
+
         // As first step, save raw incoming data. The raw data is saved by
         // cloning each packet and sending this to the writing task. A new
         // `Stream<Item = StreamItem>` is returned which simply moves the items
         // from the original stream.
-        let stream1 = Box::pin(frame_data_rx.then(|si: StreamItem| async {
-            match &si {
+        let stream1 = Box::pin(frame_data_rx.then(|mut si: StreamItem| async {
+            match &mut si {
                 StreamItem::EOF => {}
                 StreamItem::Packet(fdp) => {
                     if fdp.frame_data.synced_frame.0 == u64::MAX {
@@ -862,6 +1031,9 @@ impl CoordProcessor {
                         // only raises the issue slightly earlier.
                         panic!("Impossible frame number with frame data {fdp:?}");
                     }
+                    if frame_offset != 0 {
+                        fdp.frame_data.add_frame_offset(frame_offset);
+                    }
 
                     self.braidz_write_tx
                         .send(SaveToDiskMsg::Data2dDistorted(fdp.clone()))
@@ -902,6 +1074,14 @@ impl CoordProcessor {
                 .await
                 .expect("send calibration");
             }
+            for ms in self.lossy_model_servers.iter() {
+                if let Err(e) = ms.try_send((
+                    SendType::CalibrationFlydraXml(flydra_xml_str.to_string()),
+                    dummy_time.clone(),
+                )) {
+                    debug!("not sending calibration to listener: {e}");
+                }
+            }
         }
 
         // Start the frame bundler.
@@ -922,7 +1102,26 @@ impl CoordProcessor {
 
         // In this inner loop, we handle each incoming datum. We spend the vast majority
         // of the runtime in this loop.
+        let mut last_checkpoint: Option<std::time::Instant> = None;
         while let Some(bundle) = contiguous_stream.next().await {
+            // Save the state after the previous frame.
+            match last_checkpoint {
+                None => {
+                    // No frame processed yet.
+                    last_checkpoint = Some(std::time::Instant::now());
+                }
+                Some(t) if t.elapsed() >= checkpoint::CHECKPOINT_INTERVAL => {
+                    self.braidz_write_tx
+                        .send(SaveToDiskMsg::TrackingCheckpoint(
+                            self.checkpoint(prev_frame),
+                        ))
+                        .await
+                        .unwrap();
+                    last_checkpoint = Some(std::time::Instant::now());
+                }
+                Some(_) => {}
+            }
+
             assert!(
                 bundle.frame() >= prev_frame,
                 "Frame number decreasing? The previously received frame was {}, but now have {}",
@@ -931,6 +1130,48 @@ impl CoordProcessor {
             );
             prev_frame = bundle.frame();
 
+            // Switch tracking parameters at the frame boundary.
+            while let Ok(tracking_params) = self.tracking_params_rx.try_recv() {
+                if let Err(msg) = self.tracking_params.check_runtime_change(&tracking_params) {
+                    error!("Not changing tracking parameters: {msg}");
+                    continue;
+                }
+                info!(
+                    "Changing TrackingParams at frame {}: {:?}",
+                    bundle.frame(),
+                    tracking_params
+                );
+                let tracking_params = Arc::new(tracking_params);
+                if let Some(model_collections) = self.model_collections.as_mut() {
+                    let fps = expected_framerate.expect("expected_framerate must be set");
+                    for mc in model_collections.iter_mut() {
+                        mc.set_tracking_params(tracking_params.clone(), fps);
+                    }
+                }
+                self.tracking_params = tracking_params.clone();
+                self.braidz_write_tx
+                    .send(SaveToDiskMsg::SetTrackingParams {
+                        frame: bundle.frame(),
+                        tracking_params,
+                    })
+                    .await
+                    .unwrap();
+            }
+
+            if let Some(models) = models_to_restore.take() {
+                if let Some(model_collections) = self.model_collections.as_mut() {
+                    if models.len() == model_collections.len() {
+                        for (mc, models) in model_collections.iter_mut().zip(models) {
+                            mc.restore(models, bundle.frame());
+                        }
+                    } else {
+                        error!(
+                            "Number of mini arenas changed. Not restoring objects from checkpoint."
+                        );
+                    }
+                }
+            }
+
             // Undistort incoming points and assign to mini arenas.
             let undistorted = if let Some(recon) = &self.recon {
                 bundle.undistort_and_split_to_mini_arenas(
@@ -995,8 +1236,12 @@ impl CoordProcessor {
                     })
                     .unzip::<_, _, Vec<_>, Vec<_>>();
 
+                let mut observations = Vec::new();
                 for (send_msgs, save_msgs) in combined.into_iter() {
-                    for msg in save_msgs.into_iter() {
+                    for mut msg in save_msgs.into_iter() {
+                        if let SaveToDiskMsg::KalmanEstimate(ke) = &mut msg {
+                            observations.append(&mut ke.observations);
+                        }
                         self.braidz_write_tx.send(msg).await.unwrap();
                     }
                     for ms in self.model_servers.iter() {
@@ -1004,6 +1249,22 @@ impl CoordProcessor {
                             ms.send(msg.clone()).await.unwrap();
                         }
                     }
+                    for ms in self.lossy_model_servers.iter() {
+                        for msg in send_msgs.iter() {
+                            // Drop the message rather than block tracking if
+                            // the listener is not keeping up.
+                            if let Err(e) = ms.try_send(msg.clone()) {
+                                debug!("not sending to listener: {e}");
+                            }
+                        }
+                    }
+                }
+                if !observations.is_empty() {
+                    for listener in self.observation_listeners.iter() {
+                        // Ignore errors: the listener is not keeping up or
+                        // has quit.
+                        let _ = listener.try_send(observations.clone());
+                    }
                 }
 
                 self.model_collections = Some(model_collections);
@@ -1079,3 +1340,79 @@ fn test_csv_nan() {
         assert_eq!(count, 1);
     }
 }
+
+#[test]
+fn test_observation_model_time_offset() {
+    use adskalman::ObservationModel;
+
+    let cam = braid_mvg::test_util::camera_looking_at_origin(nalgebra::Vector3::new(2.0, 0.0, 1.0));
+    let recon = flydra_mvg::FlydraMultiCameraSystem::new(
+        std::collections::BTreeMap::from([("cam".to_string(), cam)]),
+        None,
+    )
+    .with_time_offsets(std::collections::BTreeMap::from([(
+        "cam".to_string(),
+        0.004,
+    )]));
+    let cam = recon.cam_by_name("cam").unwrap();
+
+    let state = Vector6::new(0.01, 0.02, 0.03, 1.0, -2.0, 0.5);
+    let model = generate_observation_model(&cam, &state, 1.0).unwrap();
+
+    // The camera sees the object where it is 4 ms after the frame time.
+    let expected = cam.project_3d_to_pixel(&PointWorldFrame {
+        coords: Point3::new(0.014, 0.012, 0.032),
+    });
+    let predicted = model.predict_observation(&state);
+    approx::assert_relative_eq!(predicted, expected.coords.coords, epsilon = 1e-9);
+
+    // The observation matrix includes the dependence on the velocity.
+    let delta = Vector6::new(0.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+    let actual = model.predict_observation(&(state + delta)) - predicted;
+    approx::assert_relative_eq!(model.H() * delta, actual, epsilon = 1e-3);
+    assert!(actual.norm() > 1.0);
+}
+
+#[test]
+fn test_observation_model_fisheye_noise() {
+    use adskalman::ObservationModel;
+
+    let camcenter = nalgebra::Vector3::new(2.0, 0.0, 1.0);
+    let pinhole = braid_mvg::test_util::camera_looking_at_origin(camcenter);
+    let fisheye = braid_mvg::Camera::new_fisheye(
+        640,
+        480,
+        pinhole.extrinsics().clone(),
+        pinhole.intrinsics().clone(),
+        braid_mvg::fisheye::FisheyeDistortion::zero(),
+    )
+    .unwrap();
+    let recon = flydra_mvg::FlydraMultiCameraSystem::new(
+        BTreeMap::from([
+            ("pinhole".to_string(), pinhole),
+            ("fisheye".to_string(), fisheye),
+        ]),
+        None,
+    );
+
+    // An object far from the optical axis.
+    let position = nalgebra::Vector3::new(0.0, 1.5, 0.0);
+    let state = Vector6::new(position.x, position.y, position.z, 0.0, 0.0, 0.0);
+
+    let cam = recon.cam_by_name("pinhole").unwrap();
+    let model = generate_observation_model(&cam, &state, 2.0).unwrap();
+    assert_eq!(model.R(), &(OMatrix::<f64, U2, U2>::identity() * 2.0));
+
+    // The equidistant fisheye sees the object at radius `f * theta`, which
+    // undistorts to `f * tan(theta)`. The noise is stretched radially by
+    // `1 / cos^2(theta)` and tangentially by `tan(theta) / theta`.
+    let theta = (-camcenter).angle(&(position - camcenter));
+    let radial = 1.0 / theta.cos().powi(2);
+    let tangential = theta.tan() / theta;
+    let cam = recon.cam_by_name("fisheye").unwrap();
+    let model = generate_observation_model(&cam, &state, 2.0).unwrap();
+    let mut variances: Vec<f64> = model.R().symmetric_eigenvalues().iter().copied().collect();
+    variances.sort_by(f64::total_cmp);
+    approx::assert_relative_eq!(variances[0], 2.0 * tangential.powi(2), max_relative = 1e-4);
+    approx::assert_relative_eq!(variances[1], 2.0 * radial.powi(2), max_relative = 1e-4);
+}
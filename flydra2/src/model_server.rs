@@ -1,16 +1,15 @@
 use flydra_mvg::FlydraMultiCameraSystem;
 use num_traits::Float;
 use std::sync::{Arc, RwLock};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use http_body::Frame;
-use serde::{Deserialize, Serialize};
 
 use event_stream_types::{AcceptsEventStream, EventBroadcaster};
 
-use crate::{Result, TimeDataPassthrough};
+use crate::{Result, RuleFiring, TimeDataPassthrough};
 
-use braid_types::{FlydraFloatTimestampLocal, SyncFno, Triggerbox};
+use braid_types::{PoseUdpPacket, SendType, ToListener, BRAID_POSE_API_VERSION};
 
 const EVENTS_PATH: &str = "/events";
 
@@ -71,80 +70,14 @@ impl Default for ModelServerAppState {
     }
 }
 
-#[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct SendKalmanEstimatesRow {
-    pub obj_id: u32,
-    pub frame: SyncFno,
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-    pub xvel: f64,
-    pub yvel: f64,
-    pub zvel: f64,
-    pub P00: f64,
-    pub P01: f64,
-    pub P02: f64,
-    pub P11: f64,
-    pub P12: f64,
-    pub P22: f64,
-    pub P33: f64,
-    pub P44: f64,
-    pub P55: f64,
-}
-
-impl From<braid_types::KalmanEstimatesRow> for SendKalmanEstimatesRow {
-    fn from(orig: braid_types::KalmanEstimatesRow) -> SendKalmanEstimatesRow {
-        SendKalmanEstimatesRow {
-            obj_id: orig.obj_id,
-            frame: orig.frame,
-            x: orig.x,
-            y: orig.y,
-            z: orig.z,
-            xvel: orig.xvel,
-            yvel: orig.yvel,
-            zvel: orig.zvel,
-            P00: orig.P00,
-            P01: orig.P01,
-            P02: orig.P02,
-            P11: orig.P11,
-            P12: orig.P12,
-            P22: orig.P22,
-            P33: orig.P33,
-            P44: orig.P44,
-            P55: orig.P55,
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum SendType {
-    // IMPORTANT NOTE: if you change this type, be sure to change the version
-    // value `v`. Search for the string ZP4q and `Braid pose API`.
-    Birth(SendKalmanEstimatesRow),
-    Update(SendKalmanEstimatesRow),
-    Death(u32), // obj_id
-
-    EndOfFrame(SyncFno),
-    /// the multicamera calibration serialized into a flydra xml file
-    CalibrationFlydraXml(String),
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ToListener {
-    // IMPORTANT NOTE: if you change this type, be sure to change the version
-    // value `v`. Search for the string ZP4q and `Braid pose API`.
-    /// version
-    v: u16,
-    msg: SendType,
-    latency: f64,
-    synced_frame: SyncFno,
-    #[serde(with = "braid_types::timestamp_opt_f64")]
-    trigger_timestamp: Option<FlydraFloatTimestampLocal<Triggerbox>>,
-}
-
+/// Run the model server.
+///
+/// Tracking data from `data_rx` is sent as events of type `braid`. If
+/// `rule_firing_rx` is given, closed-loop rule firings received on it are sent
+/// as events of type `braid-rule`.
 pub async fn new_model_server(
     mut data_rx: tokio::sync::mpsc::Receiver<(SendType, TimeDataPassthrough)>,
+    mut rule_firing_rx: Option<tokio::sync::mpsc::Receiver<RuleFiring>>,
     addr: std::net::SocketAddr,
 ) -> Result<()> {
     let app_state = ModelServerAppState::default();
@@ -206,7 +139,13 @@ pub async fn new_model_server(
 
         // Wait for the next update time to arrive ...
         loop {
-            let opt_new_data = data_rx.recv().await;
+            let opt_new_data = tokio::select! {
+                data = data_rx.recv() => data,
+                Some(firing) = recv_rule_firing(&mut rule_firing_rx) => {
+                    send_rule_firing(&firing, &app_state).await;
+                    continue;
+                }
+            };
             match &opt_new_data {
                 Some(data) => {
                     if let (SendType::CalibrationFlydraXml(calib), tdpt) = &data {
@@ -256,12 +195,13 @@ pub async fn new_model_server(
                                     let intrinsics: cam_geom::IntrinsicParametersPerspective<_> =
                                         params.into();
                                     // TODO: confirm that `intrinsics` is equal to `cam.intrinsics()`.
-                                    let pinhole = braid_mvg::rerun_io::cam_geom_to_rr_pinhole_archetype(
-                                        &intrinsics,
-                                        w,
-                                        h,
-                                    )
-                                    .unwrap();
+                                    let pinhole =
+                                        braid_mvg::rerun_io::cam_geom_to_rr_pinhole_archetype(
+                                            &intrinsics,
+                                            w,
+                                            h,
+                                        )
+                                        .unwrap();
                                     rec.log(raw_path, &pinhole).unwrap();
                                 }
                             }
@@ -333,19 +273,24 @@ fn extrinsics_f64<F: nalgebra::RealField + Float>(
     cam_geom::ExtrinsicParameters::from_rotation_and_camcenter(rotation, camcenter)
 }
 
-fn get_body(data: &(SendType, TimeDataPassthrough)) -> String {
-    let (msg, tdpt) = data;
-    let latency: f64 = if let Some(ref tt) = tdpt.trigger_timestamp() {
+/// Time since the trigger timestamp, or NaN if it is unknown.
+fn latency(tdpt: &TimeDataPassthrough) -> f64 {
+    if let Some(ref tt) = tdpt.trigger_timestamp() {
         let now_f64 = strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
         now_f64 - tt.as_f64()
     } else {
         f64::NAN
-    };
+    }
+}
+
+fn get_body(data: &(SendType, TimeDataPassthrough)) -> String {
+    let (msg, tdpt) = data;
+    let latency = latency(tdpt);
 
     // Send updates after each observation for lowest-possible latency.
     let data = ToListener {
         // Braid pose API
-        v: 3, // <- Bump when ToListener or SendType definition changes ZP4q
+        v: BRAID_POSE_API_VERSION, // <- Bump when ToListener or SendType definition changes ZP4q
         msg: msg.clone(),
         latency,
         synced_frame: tdpt.synced_frame(),
@@ -359,6 +304,65 @@ fn get_body(data: &(SendType, TimeDataPassthrough)) -> String {
     buf
 }
 
+/// Sends the Braid pose API over UDP.
+///
+/// Each message is sent to the destination, which may be a unicast or
+/// multicast address, as a CBOR-encoded [PoseUdpPacket] in its own datagram.
+pub struct UdpPoseSender {
+    socket: tokio::net::UdpSocket,
+    dest: std::net::SocketAddr,
+}
+
+impl UdpPoseSender {
+    /// Bind a socket for sending to `dest`.
+    pub async fn new(dest: std::net::SocketAddr) -> Result<Self> {
+        let bind_addr: std::net::SocketAddr = if dest.is_ipv4() {
+            (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = tokio::net::UdpSocket::bind(bind_addr).await?;
+        Ok(Self { socket, dest })
+    }
+
+    /// Send each message from `data_rx` until the channel is closed.
+    pub async fn run(
+        self,
+        mut data_rx: tokio::sync::mpsc::Receiver<(SendType, TimeDataPassthrough)>,
+    ) {
+        let dest = self.dest;
+        info!("Sending Braid pose API over UDP to {dest}");
+
+        let mut seq = 0;
+        while let Some((msg, tdpt)) = data_rx.recv().await {
+            let packet = PoseUdpPacket {
+                v: BRAID_POSE_API_VERSION,
+                seq,
+                msg,
+                latency: latency(&tdpt),
+                synced_frame: tdpt.synced_frame(),
+                trigger_timestamp: tdpt.trigger_timestamp(),
+            };
+            seq += 1;
+            let buf = match serde_cbor::to_vec(&packet) {
+                Ok(buf) => buf,
+                Err(e) => {
+                    warn!("Failed encoding pose datagram: {e}");
+                    continue;
+                }
+            };
+            if let Err(e) = self.socket.send_to(&buf, dest).await {
+                // Do not stop on errors such as a too large calibration message
+                // or a temporarily unreachable destination.
+                warn!(
+                    "Failed sending {} byte pose datagram to {dest}: {e}",
+                    buf.len()
+                );
+            }
+        }
+    }
+}
+
 async fn send_msg(
     data: &(SendType, TimeDataPassthrough),
     app_state: &ModelServerAppState,
@@ -367,3 +371,18 @@ async fn send_msg(
     app_state.event_broadcaster.broadcast_frame(buf).await;
     Ok(())
 }
+
+async fn recv_rule_firing(
+    rx: &mut Option<tokio::sync::mpsc::Receiver<RuleFiring>>,
+) -> Option<RuleFiring> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn send_rule_firing(firing: &RuleFiring, app_state: &ModelServerAppState) {
+    let buf = serde_json::to_string(firing).unwrap();
+    let buf = format!("event: braid-rule\ndata: {buf}\n\n");
+    app_state.event_broadcaster.broadcast_frame(buf).await;
+}
@@ -1,5 +1,5 @@
 use std::{collections::BTreeMap, sync::Arc};
-use tracing::trace;
+use tracing::{debug, trace};
 
 use nalgebra::core::dimension::{U2, U6};
 use nalgebra::{Matrix6, OMatrix, OVector, Point3, RealField, Vector6};
@@ -23,12 +23,13 @@ use braid_types::{
 
 use crate::bundled_data::{MiniArenaPointPerCam, PerMiniArenaAllCamsOneFrameUndistorted};
 use crate::{
+    checkpoint::ModelCheckpoint,
     mini_arenas::MiniArenaIndex,
     model_server::{SendKalmanEstimatesRow, SendType},
     new_object_test_2d::NewObjectTestFlat3D,
     new_object_test_3d::NewObjectTestFull3D,
-    to_world_point, CameraObservationModel, ConnectedCamerasManager, HypothesisTestResult,
-    KalmanEstimateRecord, MyFloat, SaveToDiskMsg, TimeDataPassthrough,
+    to_world_point, AssociatedObservation, CameraObservationModel, ConnectedCamerasManager,
+    HypothesisTestResult, KalmanEstimateRecord, MyFloat, SaveToDiskMsg, TimeDataPassthrough,
 };
 
 // -----------------------------------------------------------------------------
@@ -79,6 +80,10 @@ struct ModelFrameWithObservationLikes {
 struct DataAssocInfo {
     pt_idx: u8,
     cam_num: CamNum,
+    cam_name: RawCamName,
+    /// Undistorted pixel coords of the observation. `None` for the
+    /// observations from which the object was born.
+    undistorted: Option<[MyFloat; 2]>,
     /// Reprojection distance. Calculated on undistorted pixel coords.
     reproj_dist: MyFloat,
 }
@@ -150,7 +155,7 @@ struct LMInner {
     /// The unique object id for this model
     obj_id: u32,
     /// Initial start frame number
-    _start_frame: SyncFno,
+    start_frame: SyncFno,
 }
 
 impl LivingModel<ModelFrameStarted> {
@@ -168,8 +173,11 @@ impl LivingModel<ModelFrameStarted> {
 
         let prior = &self.state.prior;
 
-        // TODO: update to handle water here. See tag "laksdfjasl".
-        let undistorted = camera.project_3d_to_pixel(&to_world_point(prior.state()));
+        // Refraction at water and other interfaces is handled by the camera. See tag "laksdfjasl".
+        let undistorted = camera.project_3d_to_pixel(&crate::observed_world_point(
+            prior.state(),
+            camera.time_offset(),
+        ));
 
         //  - linearize observation_model about prior
         let obs_model = crate::generate_observation_model(
@@ -348,6 +356,25 @@ impl LivingModel<ModelFramePosteriors> {
             Some(mean_reproj_dist_100x)
         };
 
+        let state = self.state.posterior.estimate.state();
+        let observations: Vec<_> = self
+            .state
+            .data_assoc_this_timestamp
+            .iter()
+            .filter_map(|da_info| {
+                da_info
+                    .undistorted
+                    .map(|undistorted| AssociatedObservation {
+                        cam_name: da_info.cam_name.clone(),
+                        frame,
+                        obj_id,
+                        undistorted,
+                        reproj_dist: da_info.reproj_dist,
+                        position: [state[0], state[1], state[2]],
+                    })
+            })
+            .collect();
+
         let data_assoc_rows: Vec<_> = self
             .state
             .data_assoc_this_timestamp
@@ -421,6 +448,7 @@ impl LivingModel<ModelFramePosteriors> {
                         record: no_obs_record,
                         data_assoc_rows: vec![],
                         mean_reproj_dist_100x: None,
+                        observations: vec![],
                     });
                     result_save_msgs.push(msg);
                 }
@@ -431,6 +459,7 @@ impl LivingModel<ModelFramePosteriors> {
                     record,
                     data_assoc_rows,
                     mean_reproj_dist_100x,
+                    observations,
                 }));
             }
             self.last_observation_offset = self.posteriors.len();
@@ -525,10 +554,33 @@ pub(crate) fn initialize_model_collection(
     cam_manager: ConnectedCamerasManager,
     mini_arena_idx: MiniArenaIndex,
 ) -> ModelCollection<CollectionFrameDone> {
+    let (new_obj, motion_model) = new_obj_and_motion_model(&params, &recon, fps);
+
+    ModelCollection {
+        state: CollectionFrameDone { models: vec![] },
+        mcinner: MCInner {
+            mini_arena_idx,
+            params,
+            recon,
+            new_obj,
+            motion_model,
+            cam_manager,
+        },
+    }
+}
+
+fn new_obj_and_motion_model(
+    params: &Arc<TrackingParams>,
+    recon: &flydra_mvg::FlydraMultiCameraSystem<MyFloat>,
+    fps: f32,
+) -> (
+    Box<dyn HypothesisTest + Send + Sync>,
+    MotionModel3DFixedDt<MyFloat>,
+) {
     let motion_noise_scale = params.motion_noise_scale;
     let dt = 1.0 / fps as f64;
 
-    let (new_obj, motion_model) = if params.hypothesis_test_params.is_some() {
+    if params.hypothesis_test_params.is_some() {
         // full 3d tracking
         let new_obj = NewObjectTestFull3D::new(recon.clone(), params.clone());
         let motion_model_generator = ConstantVelocity3DModel::new(motion_noise_scale);
@@ -544,18 +596,6 @@ pub(crate) fn initialize_model_collection(
             Box::new(new_obj) as Box<dyn HypothesisTest + Send + Sync>,
             motion_model_generator.calc_for_dt(dt),
         )
-    };
-
-    ModelCollection {
-        state: CollectionFrameDone { models: vec![] },
-        mcinner: MCInner {
-            mini_arena_idx,
-            params,
-            recon,
-            new_obj,
-            motion_model,
-            cam_manager,
-        },
     }
 }
 
@@ -576,6 +616,83 @@ pub(crate) struct MCInner {
 }
 
 impl ModelCollection<CollectionFrameDone> {
+    /// Use new tracking parameters, starting with the next frame.
+    ///
+    /// Existing models are kept. The caller must ensure the change is allowed
+    /// with [TrackingParams::check_runtime_change].
+    pub(crate) fn set_tracking_params(&mut self, params: Arc<TrackingParams>, fps: f32) {
+        let (new_obj, motion_model) = new_obj_and_motion_model(&params, &self.mcinner.recon, fps);
+        self.mcinner.params = params;
+        self.mcinner.new_obj = new_obj;
+        self.mcinner.motion_model = motion_model;
+    }
+
+    /// Save the most recent estimate of each model.
+    pub(crate) fn checkpoint(&self) -> Vec<ModelCheckpoint> {
+        self.state
+            .models
+            .iter()
+            .map(|x| {
+                let last = &x.posteriors[x.posteriors.len() - 1];
+                let state = last.estimate.state();
+                let covar = last.estimate.covariance();
+                ModelCheckpoint {
+                    obj_id: x.lmi.obj_id,
+                    start_frame: x.lmi.start_frame.0,
+                    gestation_age: x.gestation_age,
+                    frame: last.frame().0,
+                    state: std::array::from_fn(|i| state[i]),
+                    covariance: std::array::from_fn(|i| std::array::from_fn(|j| covar[(i, j)])),
+                }
+            })
+            .collect()
+    }
+
+    /// Restore models saved with [Self::checkpoint] before processing `frame`.
+    ///
+    /// The motion model is applied for the frames missed since the checkpoint.
+    /// Models whose uncertainty thereby exceeds `max_position_std_meters` are
+    /// not restored.
+    pub(crate) fn restore(&mut self, models: Vec<ModelCheckpoint>, frame: SyncFno) {
+        let max_variance = self.mcinner.params.max_position_std_meters.powi(2) as f64;
+        for model in models {
+            let mut estimate = StateAndCovariance::new(
+                Vector6::from_column_slice(&model.state),
+                Matrix6::from_fn(|i, j| model.covariance[i][j]),
+            );
+            let mut estimate_frame = model.frame;
+            let mut lost = false;
+            while estimate_frame + 1 < frame.0 {
+                estimate = self.mcinner.motion_model.predict(&estimate);
+                estimate_frame += 1;
+                if covariance_size(estimate.covariance()) > max_variance {
+                    lost = true;
+                    break;
+                }
+            }
+            if lost {
+                debug!(
+                    "not restoring obj_id {}: uncertainty too large",
+                    model.obj_id
+                );
+                continue;
+            }
+            self.state.models.push(LivingModel {
+                gestation_age: model.gestation_age,
+                state: ModelFrameDone {},
+                posteriors: vec![StampedEstimate {
+                    estimate,
+                    tdpt: TimeDataPassthrough::new(SyncFno(estimate_frame), &None),
+                }],
+                last_observation_offset: 0,
+                lmi: LMInner {
+                    obj_id: model.obj_id,
+                    start_frame: SyncFno(model.start_frame),
+                },
+            });
+        }
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     pub(crate) fn predict_motion(self) -> ModelCollection<CollectionFrameStarted> {
         let mcinner = self.mcinner;
@@ -833,6 +950,8 @@ impl ModelCollection<CollectionFrameWithObservationLikes> {
                             let assoc = DataAssocInfo {
                                 pt_idx: undist_pt.idx,
                                 cam_num,
+                                cam_name: cam_name.clone(),
+                                undistorted: Some([undist_pt.x, undist_pt.y]),
                                 reproj_dist,
                             };
 
@@ -1004,6 +1123,8 @@ impl ModelCollection<CollectionFramePosteriors> {
                         DataAssocInfo {
                             pt_idx,
                             cam_num,
+                            cam_name: ci.raw_cam_name.clone(),
+                            undistorted: None,
                             reproj_dist: ci.reproj_dist,
                         }
                     })
@@ -1026,7 +1147,7 @@ impl ModelCollection<CollectionFramePosteriors> {
                     last_observation_offset: 0,
                     lmi: LMInner {
                         obj_id,
-                        _start_frame: tdpt.frame,
+                        start_frame: tdpt.frame,
                     },
                 };
 
@@ -1113,3 +1234,85 @@ fn convert_pt(input: &braid_types::FlydraRawUdpPoint) -> braid_mvg::DistortedPix
         coords: nalgebra::geometry::Point2::new(input.x0_abs, input.y0_abs),
     }
 }
+
+#[test]
+fn test_checkpoint_restore() {
+    use std::sync::atomic::AtomicBool;
+
+    let recon = flydra_mvg::FlydraMultiCameraSystem::from_system(
+        braid_mvg::test_util::camera_ring(3, 2.0, 1.0),
+        None,
+    );
+    let params = Arc::new(braid_types::default_tracking_params_full_3d());
+    let new_collection = || {
+        let cam_manager = ConnectedCamerasManager::new(
+            &Some(recon.clone()),
+            Default::default(),
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(AtomicBool::new(true)),
+            None,
+        );
+        initialize_model_collection(
+            params.clone(),
+            recon.clone(),
+            100.0,
+            cam_manager,
+            MiniArenaIndex::new(0),
+        )
+    };
+    let model = |obj_id, state: [f64; 6], vel_variance: f64| ModelCheckpoint {
+        obj_id,
+        start_frame: 100,
+        gestation_age: None,
+        frame: 200,
+        state,
+        covariance: std::array::from_fn(|i| {
+            std::array::from_fn(|j| match (i == j, i < 3) {
+                (true, true) => 1e-5,
+                (true, false) => vel_variance,
+                (false, _) => 0.0,
+            })
+        }),
+    };
+    let models = vec![
+        model(1, [0.1, 0.2, 0.3, 1.0, 0.0, -0.5], 1e-5),
+        model(2, [-0.1, 0.0, 0.2, 0.0, 0.3, 0.0], 1e-5),
+        // The velocity of this model is too uncertain to predict its
+        // position for long.
+        model(3, [0.0, 0.0, 0.1, 0.0, 0.0, 0.0], 1.0),
+    ];
+
+    // Resuming with the next frame restores the models unchanged.
+    let mut mc = new_collection();
+    mc.restore(models.clone(), SyncFno(201));
+    let checkpoint = crate::TrackingCheckpoint {
+        frame: 200,
+        saved_at: 1_700_000_000.0,
+        next_obj_id: 4,
+        mini_arenas: vec![mc.checkpoint()],
+    };
+    assert_eq!(checkpoint.mini_arenas[0], models);
+
+    let dir = tempfile::tempdir().unwrap();
+    checkpoint.save(dir.path()).unwrap();
+    let loaded = crate::TrackingCheckpoint::load(dir.path()).unwrap();
+    assert_eq!(loaded, checkpoint);
+
+    // Resuming later predicts the motion over the 10 missed frames and drops
+    // the model which became too uncertain.
+    let mut mc = new_collection();
+    mc.restore(loaded.mini_arenas[0].clone(), SyncFno(211));
+    let restored = mc.checkpoint();
+    let obj_ids: Vec<u32> = restored.iter().map(|m| m.obj_id).collect();
+    assert_eq!(obj_ids, vec![1, 2]);
+    for (orig, restored) in models.iter().zip(restored.iter()) {
+        assert_eq!(restored.frame, 210);
+        assert_eq!(restored.start_frame, orig.start_frame);
+        for i in 0..3 {
+            let expected = orig.state[i] + 0.1 * orig.state[i + 3];
+            approx::assert_relative_eq!(restored.state[i], expected, epsilon = 1e-12);
+            approx::assert_relative_eq!(restored.state[i + 3], orig.state[i + 3]);
+            assert!(restored.covariance[i][i] > orig.covariance[i][i]);
+        }
+    }
+}
@@ -1,19 +1,27 @@
-use libflate::{finish::AutoFinishUnchecked, gzip::Encoder};
-use std::{io::Write, sync::Arc};
+use libflate::{
+    finish::AutoFinishUnchecked,
+    gzip::{Decoder, Encoder},
+};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    path::Path,
+    sync::Arc,
+};
 use tracing::info;
 
 use braid_types::{
-    CamInfoRow, MyFloat, TextlogRow, TrackingParams, BRAID_SCHEMA, CAM_SETTINGS_DIRNAME,
+    CamInfoRow, CamNum, MyFloat, TextlogRow, TrackingParams, BRAID_SCHEMA, CAM_SETTINGS_DIRNAME,
     FEATURE_DETECT_SETTINGS_DIRNAME, IMAGES_DIRNAME, RECONSTRUCT_LATENCY_HLOG_FNAME,
     REPROJECTION_DIST_HLOG_FNAME,
 };
 
-use braidz_types::BraidMetadata;
+use braidz_types::{BraidMetadata, TrackingParamsPeriod};
 
 use crate::{
     finish_histogram, histogram_record, save_hlog, ConnectedCamerasManager, ExperimentInfoRow,
     FrameDataAndPoints, HistogramWritingState, KalmanEstimateRecord, OrderingWriter, Result,
-    SaveToDiskMsg, StartSavingCsvConfig, TrackingParamsSaver,
+    SaveToDiskMsg, StartSavingCsvConfig, SyncFno, TrackingParamsSaver,
 };
 
 struct WritingState {
@@ -29,11 +37,15 @@ struct WritingState {
     #[allow(dead_code)]
     readme_fd: Option<std::fs::File>,
     save_empty_data2d: bool,
+    /// Maps the camera numbers of this session to those of the recording
+    /// being resumed. Empty if not resuming.
+    camn_remap: BTreeMap<CamNum, CamNum>,
     // kalman_estimates_wtr: Option<csv::Writer<Box<dyn std::io::Write>>>,
     kalman_estimates_wtr: Option<OrderingWriter>,
     data_assoc_wtr: Option<csv::Writer<Box<dyn std::io::Write + Send>>>,
     data_2d_wtr: csv::Writer<Box<dyn std::io::Write + Send>>,
     textlog_wtr: csv::Writer<Box<dyn std::io::Write + Send>>,
+    metadata: BraidMetadata,
     trigger_clock_info_wtr: csv::Writer<Box<dyn std::io::Write + Send>>,
     experiment_info_wtr: csv::Writer<Box<dyn std::io::Write + Send>>,
     writer_stats: Option<(usize, usize)>,
@@ -93,9 +105,11 @@ impl WritingState {
         // Until we obtain the readme file handle, we have a small race
         // condition where another process could also open this directory.
 
-        let readme_fd = {
-            let readme_path = output_dirname.join(braid_types::README_MD_FNAME);
-
+        let readme_path = output_dirname.join(braid_types::README_MD_FNAME);
+        let readme_fd = if cfg.resume {
+            info!("resuming recording in \"{}\"", output_dirname.display());
+            Some(std::fs::OpenOptions::new().append(true).open(readme_path)?)
+        } else {
             let mut fd = std::fs::File::create(readme_path)?;
 
             // Start and end it with some newlines so the text is more
@@ -109,10 +123,13 @@ impl WritingState {
             Some(fd)
         };
 
-        {
-            let braid_metadata_path = output_dirname.join(braid_types::BRAID_METADATA_YML_FNAME);
-
-            let metadata = match metadata_builder {
+        let braid_metadata_path = output_dirname.join(braid_types::BRAID_METADATA_YML_FNAME);
+        let metadata = if cfg.resume && braid_metadata_path.exists() {
+            // Keep the metadata of the recording being resumed.
+            let buf = std::fs::read_to_string(&braid_metadata_path)?;
+            serde_yaml::from_str(&buf)?
+        } else {
+            let mut metadata = match metadata_builder {
                 BraidMetadataBuilder::GenerateNew(parts) => {
                     BraidMetadata {
                         schema: BRAID_SCHEMA, // BraidMetadataSchemaTag
@@ -120,15 +137,19 @@ impl WritingState {
                         original_recording_time: local,
                         save_empty_data2d,
                         saving_program_name: parts.saving_program_name,
+                        tracking_params_history: vec![],
                     }
                 }
                 BraidMetadataBuilder::Existing(metadata) => metadata,
             };
-            let metadata_buf = serde_yaml::to_string(&metadata).unwrap();
-
-            let mut fd = std::fs::File::create(braid_metadata_path)?;
-            fd.write_all(metadata_buf.as_bytes()).unwrap();
-        }
+            metadata.tracking_params_history = vec![TrackingParamsPeriod {
+                start_frame: None,
+                stop_frame: None,
+                tracking_params: (*tracking_params).clone(),
+            }];
+            write_metadata(&output_dirname, &metadata)?;
+            metadata
+        };
 
         // write images
         {
@@ -193,9 +214,22 @@ impl WritingState {
         }
 
         // write cam info (pairs of CamNum and cam name)
-        {
+        let camn_remap = {
             let mut csv_path = output_dirname.clone();
             csv_path.push(format!("{}.gz", braid_types::CAM_INFO_CSV_FNAME));
+            let (cam_info_rows, camn_remap) = if cfg.resume {
+                let mut buf = Vec::new();
+                if csv_path.exists() {
+                    copy_complete_lines(Decoder::new(std::fs::File::open(&csv_path)?)?, &mut buf)?;
+                }
+                let mut prev_rows = Vec::new();
+                for row in csv::Reader::from_reader(buf.as_slice()).into_deserialize() {
+                    prev_rows.push(row?);
+                }
+                remap_cam_info(prev_rows, cam_info_rows)
+            } else {
+                (cam_info_rows, BTreeMap::new())
+            };
             let fd = std::fs::File::create(&csv_path)?;
             let fd: Box<dyn std::io::Write + Send> =
                 Box::new(AutoFinishUnchecked::new(Encoder::new(fd)?));
@@ -203,7 +237,8 @@ impl WritingState {
             for row in cam_info_rows.iter() {
                 cam_info_wtr.serialize(row)?;
             }
-        }
+            camn_remap
+        };
 
         // write calibration
         if let Some(ref recon) = recon {
@@ -244,28 +279,43 @@ impl WritingState {
             };
             let message2 = serde_json::to_string(&tps)?;
 
-            let textlog: Vec<TextlogRow> = vec![
-                TextlogRow {
+            let textlog: Vec<TextlogRow> = if cfg.resume {
+                // The braidz parser expects a single message with the
+                // `tracking_params` key, so this uses a different key.
+                let message = serde_json::to_string(&serde_json::json!({
+                    "resumed_recording": {
+                        "fps": fps,
+                        "tracking_params": tps,
+                    }
+                }))?;
+                vec![TextlogRow {
                     mainbrain_timestamp,
                     cam_id: "mainbrain".to_string(),
                     host_timestamp: mainbrain_timestamp,
                     message,
-                },
-                TextlogRow {
-                    mainbrain_timestamp,
-                    cam_id: "mainbrain".to_string(),
-                    host_timestamp: mainbrain_timestamp,
-                    message: message2,
-                },
-            ];
+                }]
+            } else {
+                vec![
+                    TextlogRow {
+                        mainbrain_timestamp,
+                        cam_id: "mainbrain".to_string(),
+                        host_timestamp: mainbrain_timestamp,
+                        message,
+                    },
+                    TextlogRow {
+                        mainbrain_timestamp,
+                        cam_id: "mainbrain".to_string(),
+                        host_timestamp: mainbrain_timestamp,
+                        message: message2,
+                    },
+                ]
+            };
 
             // We do not stream this to .gz because we want to maximize chances
             // that it is completely flushed to disk even in event of a panic.
             let mut csv_path = output_dirname.clone();
             csv_path.push(braid_types::TEXTLOG_CSV_FNAME);
-            let fd = std::fs::File::create(&csv_path)?;
-            let mut textlog_wtr =
-                csv::Writer::from_writer(Box::new(fd) as Box<dyn std::io::Write + Send>);
+            let mut textlog_wtr = create_csv_writer(&csv_path, false, cfg.resume)?;
             for row in textlog.iter() {
                 textlog_wtr.serialize(row)?;
             }
@@ -278,10 +328,9 @@ impl WritingState {
         let kalman_estimates_wtr = if let Some(ref _recon) = recon {
             let mut csv_path = output_dirname.clone();
             csv_path.push(format!("{}.gz", braid_types::KALMAN_ESTIMATES_CSV_FNAME));
-            let fd = std::fs::File::create(&csv_path)?;
-            let fd: Box<dyn std::io::Write + Send> =
-                Box::new(AutoFinishUnchecked::new(Encoder::new(fd)?));
-            Some(OrderingWriter::new(csv::Writer::from_writer(fd)))
+            Some(OrderingWriter::new(create_csv_writer(
+                &csv_path, true, cfg.resume,
+            )?))
         } else {
             None
         };
@@ -289,10 +338,7 @@ impl WritingState {
         let trigger_clock_info_wtr = {
             let mut csv_path = output_dirname.clone();
             csv_path.push(format!("{}.gz", braid_types::TRIGGER_CLOCK_INFO_CSV_FNAME));
-            let fd = std::fs::File::create(&csv_path)?;
-            let fd: Box<dyn std::io::Write + Send> =
-                Box::new(AutoFinishUnchecked::new(Encoder::new(fd)?));
-            csv::Writer::from_writer(fd)
+            create_csv_writer(&csv_path, true, cfg.resume)?
         };
 
         let experiment_info_wtr = {
@@ -300,17 +346,13 @@ impl WritingState {
             // that it is completely flushed to disk even in event of a panic.
             let mut csv_path = output_dirname.clone();
             csv_path.push(braid_types::EXPERIMENT_INFO_CSV_FNAME);
-            let fd = std::fs::File::create(&csv_path)?;
-            csv::Writer::from_writer(Box::new(fd) as Box<dyn std::io::Write + Send>)
+            create_csv_writer(&csv_path, false, cfg.resume)?
         };
 
         let data_assoc_wtr = if let Some(ref _recon) = recon {
             let mut csv_path = output_dirname.clone();
             csv_path.push(format!("{}.gz", braid_types::DATA_ASSOCIATE_CSV_FNAME));
-            let fd = std::fs::File::create(&csv_path)?;
-            let fd: Box<dyn std::io::Write + Send> =
-                Box::new(AutoFinishUnchecked::new(Encoder::new(fd)?));
-            Some(csv::Writer::from_writer(fd))
+            Some(create_csv_writer(&csv_path, true, cfg.resume)?)
         } else {
             None
         };
@@ -318,10 +360,7 @@ impl WritingState {
         let data_2d_wtr = {
             let mut csv_path = output_dirname.clone();
             csv_path.push(format!("{}.gz", braid_types::DATA2D_DISTORTED_CSV_FNAME));
-            let fd = std::fs::File::create(&csv_path)?;
-            let fd: Box<dyn std::io::Write + Send> =
-                Box::new(AutoFinishUnchecked::new(Encoder::new(fd)?));
-            csv::Writer::from_writer(fd)
+            create_csv_writer(&csv_path, true, cfg.resume)?
         };
 
         let writer_stats = if cfg.print_stats { Some((0, 0)) } else { None };
@@ -345,10 +384,12 @@ impl WritingState {
             output_dirname,
             readme_fd,
             save_empty_data2d,
+            camn_remap,
             kalman_estimates_wtr,
             data_assoc_wtr,
             data_2d_wtr,
             textlog_wtr,
+            metadata,
             trigger_clock_info_wtr,
             experiment_info_wtr,
             writer_stats,
@@ -360,13 +401,57 @@ impl WritingState {
     }
 
     fn save_data_2d_distorted(&mut self, fdp: FrameDataAndPoints) -> Result<usize> {
-        let data2d_distorted = fdp.into_save(self.save_empty_data2d);
-        for row in data2d_distorted.iter() {
-            self.data_2d_wtr.serialize(row)?;
+        let mut data2d_distorted = fdp.into_save(self.save_empty_data2d);
+        for row in data2d_distorted.iter_mut() {
+            row.camn = self.remap_camn(row.camn);
+            self.data_2d_wtr.serialize(&*row)?;
         }
         Ok(data2d_distorted.len())
     }
 
+    /// The camera number saved for the camera number `camn` of this session.
+    fn remap_camn(&self, camn: CamNum) -> CamNum {
+        self.camn_remap.get(&camn).copied().unwrap_or(camn)
+    }
+
+    /// Record that the tracking parameters changed at `frame`.
+    fn set_tracking_params(
+        &mut self,
+        frame: SyncFno,
+        tracking_params: &TrackingParams,
+    ) -> Result<()> {
+        if let Some(last) = self.metadata.tracking_params_history.last_mut() {
+            last.stop_frame = Some(frame.0.saturating_sub(1));
+        }
+        self.metadata
+            .tracking_params_history
+            .push(TrackingParamsPeriod {
+                start_frame: Some(frame.0),
+                stop_frame: None,
+                tracking_params: tracking_params.clone(),
+            });
+        write_metadata(&self.output_dirname, &self.metadata)?;
+
+        // Note that the braidz parser expects a single message with the
+        // `tracking_params` key, so this uses a different key.
+        let message = serde_json::to_string(&serde_json::json!({
+            "tracking_params_change": {
+                "frame": frame.0,
+                "tracking_params": tracking_params,
+            }
+        }))?;
+        let mainbrain_timestamp =
+            strand_datetime_conversion::datetime_to_f64(&chrono::Local::now());
+        self.textlog_wtr.serialize(TextlogRow {
+            mainbrain_timestamp,
+            cam_id: "mainbrain".to_string(),
+            host_timestamp: mainbrain_timestamp,
+            message,
+        })?;
+        self.textlog_wtr.flush()?;
+        Ok(())
+    }
+
     fn flush_all(&mut self) -> Result<()> {
         if let Some(ref mut kew) = self.kalman_estimates_wtr {
             kew.flush()?;
@@ -413,6 +498,17 @@ impl Drop for WritingState {
         // doesn't accidentally overwrite our real data.
         let output_dirname = std::mem::take(&mut self.output_dirname);
 
+        // The recording is finished, so it cannot be resumed.
+        let checkpoint_path = output_dirname.join(braid_types::TRACKING_CHECKPOINT_JSON_FNAME);
+        if checkpoint_path.exists() {
+            if let Err(e) = std::fs::remove_file(&checkpoint_path) {
+                tracing::warn!(
+                    "Could not remove checkpoint \"{}\": {e}",
+                    checkpoint_path.display()
+                );
+            }
+        }
+
         let now_system = std::time::SystemTime::now();
         {
             if let Some(reconstruction_latency_usec) = &mut self.reconstruction_latency_usec {
@@ -494,6 +590,124 @@ impl Drop for WritingState {
     }
 }
 
+/// Create a CSV writer for `path`, optionally gzip compressed.
+///
+/// If `resume` is true, the complete rows of an existing file are kept and no
+/// header is written unless the file was empty. A file left by a crash may end
+/// with a partial row or, if compressed, an unfinished gzip stream. Such
+/// incomplete data is discarded.
+fn create_csv_writer(
+    path: &Path,
+    compress: bool,
+    resume: bool,
+) -> Result<csv::Writer<Box<dyn std::io::Write + Send>>> {
+    // Move the existing file aside so it is not lost if we crash while copying.
+    let mut prev_path = path.as_os_str().to_owned();
+    prev_path.push(".prev");
+    let prev_path = std::path::PathBuf::from(prev_path);
+    let have_prev = if resume && prev_path.exists() {
+        // A previous attempt to resume was interrupted while copying.
+        true
+    } else if resume && path.exists() {
+        std::fs::rename(path, &prev_path)?;
+        true
+    } else {
+        false
+    };
+
+    let fd = std::fs::File::create(path)?;
+    let mut fd: Box<dyn std::io::Write + Send> = if compress {
+        Box::new(AutoFinishUnchecked::new(Encoder::new(fd)?))
+    } else {
+        Box::new(fd)
+    };
+
+    let mut n_bytes = 0;
+    if have_prev {
+        let prev = std::fs::File::open(&prev_path)?;
+        n_bytes = if compress {
+            match Decoder::new(prev) {
+                Ok(decoder) => copy_complete_lines(decoder, &mut fd)?,
+                // The gzip header was not completely written.
+                Err(_) => 0,
+            }
+        } else {
+            copy_complete_lines(prev, &mut fd)?
+        };
+        fd.flush()?;
+        std::fs::remove_file(&prev_path)?;
+    }
+
+    Ok(csv::WriterBuilder::new()
+        .has_headers(n_bytes == 0)
+        .from_writer(fd))
+}
+
+/// Copy `src` up to its last newline to `dst`. Returns the number of bytes
+/// copied.
+///
+/// Reading stops at the first error, as caused by a truncated file.
+fn copy_complete_lines<R: Read, W: Write + ?Sized>(mut src: R, dst: &mut W) -> Result<u64> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut pending = Vec::new();
+    let mut n_copied = 0;
+    loop {
+        let n = match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                tracing::warn!("Discarding data after read error: {e}");
+                break;
+            }
+        };
+        pending.extend_from_slice(&buf[..n]);
+        if let Some(pos) = pending.iter().rposition(|b| *b == b'\n') {
+            dst.write_all(&pending[..=pos])?;
+            n_copied += pos as u64 + 1;
+            pending.drain(..=pos);
+        }
+    }
+    Ok(n_copied)
+}
+
+/// Combine the cameras of a recording being resumed with those of this session.
+///
+/// Returns the rows to save and the mapping from camera numbers of this session
+/// to those saved. Cameras not in the previous session get new numbers.
+fn remap_cam_info(
+    prev_rows: Vec<CamInfoRow>,
+    cur_rows: Vec<CamInfoRow>,
+) -> (Vec<CamInfoRow>, BTreeMap<CamNum, CamNum>) {
+    let mut next_camn = prev_rows.iter().map(|r| r.camn.0 + 1).max().unwrap_or(0);
+    let mut rows = prev_rows;
+    let mut camn_remap = BTreeMap::new();
+    for cur in cur_rows {
+        let camn = match rows.iter().find(|r| r.cam_id == cur.cam_id) {
+            Some(prev) => prev.camn,
+            None => {
+                let camn = CamNum(next_camn);
+                next_camn += 1;
+                rows.push(CamInfoRow {
+                    camn,
+                    cam_id: cur.cam_id,
+                });
+                camn
+            }
+        };
+        camn_remap.insert(cur.camn, camn);
+    }
+    (rows, camn_remap)
+}
+
+fn write_metadata(output_dirname: &std::path::Path, metadata: &BraidMetadata) -> Result<()> {
+    let braid_metadata_path = output_dirname.join(braid_types::BRAID_METADATA_YML_FNAME);
+    let metadata_buf = serde_yaml::to_string(metadata)?;
+    let mut fd = std::fs::File::create(braid_metadata_path)?;
+    fd.write_all(metadata_buf.as_bytes())?;
+    Ok(())
+}
+
 /// Listen to a Receiver for messages and save the data to disk.
 ///
 /// This function only exits upon error or when the Sender counterpart to the
@@ -504,7 +718,7 @@ pub(crate) fn writer_task_main(
     mut braidz_write_rx: tokio::sync::mpsc::Receiver<SaveToDiskMsg>,
     cam_manager: ConnectedCamerasManager,
     recon: Option<flydra_mvg::FlydraMultiCameraSystem<MyFloat>>,
-    tracking_params: Arc<TrackingParams>,
+    mut tracking_params: Arc<TrackingParams>,
     save_empty_data2d: bool,
     metadata_builder: BraidMetadataBuilder,
     ignore_latency: bool,
@@ -530,6 +744,7 @@ pub(crate) fn writer_task_main(
                     record,
                     data_assoc_rows,
                     mean_reproj_dist_100x,
+                    observations: _,
                 } = ke;
                 let trigger_timestamp = record.timestamp.clone();
 
@@ -541,8 +756,9 @@ pub(crate) fn writer_task_main(
                             count.1 += 1
                         }
                     }
-                    if let Some(ref mut daw) = ws.data_assoc_wtr {
-                        for row in data_assoc_rows.iter() {
+                    for mut row in data_assoc_rows.into_iter() {
+                        row.cam_num = ws.remap_camn(row.cam_num);
+                        if let Some(ref mut daw) = ws.data_assoc_wtr {
                             daw.serialize(row)?;
                         }
                     }
@@ -657,6 +873,25 @@ pub(crate) fn writer_task_main(
                 }
                 // simply drop data if no file opened
             }
+            SetTrackingParams {
+                frame,
+                tracking_params: new_tracking_params,
+            } => {
+                // Subsequent recordings start with the new parameters.
+                tracking_params = new_tracking_params;
+                if let Some(ref mut ws) = writing_state {
+                    ws.set_tracking_params(frame, &tracking_params)?;
+                }
+            }
+            TrackingCheckpoint(checkpoint) => {
+                if let Some(ref mut ws) = writing_state {
+                    // Flush first so that the saved data is at least as recent
+                    // as the checkpoint.
+                    ws.flush_all()?;
+                    checkpoint.save(&ws.output_dirname)?;
+                }
+                // simply drop checkpoint if no file opened
+            }
         }
 
         if let Some(ref mut ws) = writing_state {
@@ -691,6 +926,7 @@ mod test {
                 per_cam_data: Default::default(),
                 print_stats: false,
                 save_performance_histograms: false,
+                resume: false,
             };
 
             let cam_manager = ConnectedCamerasManager::new(
@@ -730,6 +966,159 @@ mod test {
         std::fs::remove_dir_all(root).unwrap();
     }
 
+    #[test]
+    fn test_tracking_params_history() {
+        let root = tempfile::tempdir().unwrap();
+        let braid_root = root.path().join("test.braid");
+
+        let cfg = StartSavingCsvConfig {
+            out_dir: braid_root.clone(),
+            local: None,
+            git_rev: "<impossible git rev>".into(),
+            fps: None,
+            per_cam_data: Default::default(),
+            print_stats: false,
+            save_performance_histograms: false,
+            resume: false,
+        };
+        let cam_manager = ConnectedCamerasManager::new(
+            &None,
+            std::collections::BTreeSet::new(),
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(AtomicBool::new(true)),
+            None,
+        );
+        let tracking_params = Arc::new(braid_types::default_tracking_params_full_3d());
+        let mut ws = WritingState::new(
+            cfg,
+            cam_manager.sample(),
+            &None,
+            tracking_params.clone(),
+            false,
+            BraidMetadataBuilder::saving_program_name(format!("{}:{}", file!(), line!())),
+        )
+        .unwrap();
+
+        let mut tracking_params2 = (*tracking_params).clone();
+        tracking_params2.motion_noise_scale *= 2.0;
+        ws.set_tracking_params(braid_types::SyncFno(100), &tracking_params2)
+            .unwrap();
+
+        let buf = std::fs::read_to_string(braid_root.join(braid_types::BRAID_METADATA_YML_FNAME))
+            .unwrap();
+        let metadata: BraidMetadata = serde_yaml::from_str(&buf).unwrap();
+        assert_eq!(
+            metadata.tracking_params_history,
+            vec![
+                TrackingParamsPeriod {
+                    start_frame: None,
+                    stop_frame: Some(99),
+                    tracking_params: (*tracking_params).clone(),
+                },
+                TrackingParamsPeriod {
+                    start_frame: Some(100),
+                    stop_frame: None,
+                    tracking_params: tracking_params2,
+                },
+            ]
+        );
+
+        let textlog =
+            std::fs::read_to_string(braid_root.join(braid_types::TEXTLOG_CSV_FNAME)).unwrap();
+        assert!(textlog.contains("tracking_params_change"));
+
+        std::mem::drop(ws);
+    }
+
+    #[test]
+    fn test_resume_csv() {
+        let root = tempfile::tempdir().unwrap();
+
+        let write_rows = |path: &Path, compress: bool, resume: bool, camns: &[u8]| {
+            let mut wtr = create_csv_writer(path, compress, resume).unwrap();
+            for camn in camns {
+                wtr.serialize(CamInfoRow {
+                    camn: CamNum(*camn),
+                    cam_id: format!("cam{camn}"),
+                })
+                .unwrap();
+            }
+        };
+        let read_camns = |path: &Path, compress: bool| -> Vec<u8> {
+            let fd = std::fs::File::open(path).unwrap();
+            let rdr: Box<dyn Read> = if compress {
+                Box::new(Decoder::new(fd).unwrap())
+            } else {
+                Box::new(fd)
+            };
+            let rows: Vec<CamInfoRow> = csv::Reader::from_reader(rdr)
+                .into_deserialize()
+                .collect::<std::result::Result<_, _>>()
+                .unwrap();
+            rows.iter().map(|r| r.camn.0).collect()
+        };
+
+        for compress in [false, true] {
+            let path = root.path().join(format!("rows{compress}.csv"));
+            write_rows(&path, compress, false, &[0, 1, 2]);
+            if !compress {
+                // A row partially written during a crash.
+                let mut fd = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .unwrap();
+                fd.write_all(b"3,ca").unwrap();
+            }
+            write_rows(&path, compress, true, &[4]);
+            assert_eq!(read_camns(&path, compress), vec![0, 1, 2, 4]);
+        }
+
+        // A compressed file cut short during a crash, missing only the gzip
+        // trailer or also part of the compressed data.
+        for trailer_only in [true, false] {
+            let path = root.path().join(format!("truncated{trailer_only}.csv.gz"));
+            write_rows(&path, true, false, &[0, 1, 2]);
+            let len = std::fs::metadata(&path).unwrap().len();
+            let truncated_len = if trailer_only { len - 8 } else { len / 2 };
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_len(truncated_len)
+                .unwrap();
+            write_rows(&path, true, true, &[4]);
+
+            let camns = read_camns(&path, true);
+            if trailer_only {
+                assert_eq!(camns, vec![0, 1, 2, 4]);
+            } else {
+                // Complete rows which could be decompressed are kept.
+                let (last, kept) = camns.split_last().unwrap();
+                assert_eq!(*last, 4);
+                assert!([0, 1, 2].starts_with(kept), "{camns:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_remap_cam_info() {
+        let row = |camn, cam_id: &str| CamInfoRow {
+            camn: CamNum(camn),
+            cam_id: cam_id.into(),
+        };
+        let (rows, remap) = remap_cam_info(
+            vec![row(0, "a"), row(1, "b")],
+            vec![row(0, "b"), row(1, "c")],
+        );
+        let rows: Vec<_> = rows.into_iter().map(|r| (r.camn.0, r.cam_id)).collect();
+        assert_eq!(
+            rows,
+            vec![(0, "a".into()), (1, "b".into()), (2, "c".to_string())]
+        );
+        assert_eq!(remap.get(&CamNum(0)), Some(&CamNum(1)));
+        assert_eq!(remap.get(&CamNum(1)), Some(&CamNum(2)));
+    }
+
     /// Ensure that .braidz files can exceed 4GB.
     #[ignore]
     #[test]
@@ -779,6 +1168,7 @@ mod test {
                 per_cam_data: Default::default(),
                 print_stats: false,
                 save_performance_histograms: false,
+                resume: false,
             };
 
             let cam_manager = ConnectedCamerasManager::new(
@@ -638,15 +638,20 @@ fn perform_calibration(cli: Cli) -> eyre::Result<()> {
 
         let mut cams_by_name = std::collections::BTreeMap::new();
 
-        for (name, ba_cam) in cam_names.iter().zip(ba.cams().iter()) {
+        for (cam_i, (name, ba_cam)) in cam_names.iter().zip(ba.cams().iter()).enumerate() {
             let old_cam = cal_result.cam_system.cam_by_name(name).unwrap();
             let e = ba_cam.extrinsics().clone();
             let mut i = ba_cam.intrinsics().clone();
-            if undistort_prior_to_bundle_adj {
-                // use original distortion
-                i.distortion = old_cam.intrinsics().distortion.clone();
-            }
-            let cam = braid_mvg::Camera::new(old_cam.width(), old_cam.height(), e, i)?;
+            let (w, h) = (old_cam.width(), old_cam.height());
+            let cam = if let Some(d) = ba.fisheye_distortion().get(cam_i) {
+                braid_mvg::Camera::new_fisheye(w, h, e, i, d.clone())?
+            } else {
+                if undistort_prior_to_bundle_adj {
+                    // use original distortion
+                    i.distortion = old_cam.intrinsics().distortion.clone();
+                }
+                braid_mvg::Camera::new(w, h, e, i)?
+            };
             cams_by_name.insert(name.clone(), cam);
         }
         let ba_system = flydra_mvg::FlydraMultiCameraSystem::new(cams_by_name, None);
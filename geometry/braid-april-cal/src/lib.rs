@@ -272,6 +272,21 @@ fn gather_points_per_cam(
     }
 }
 
+/// Match the 2D detections of one camera with 3D fiducial coordinates.
+///
+/// Detections of the same tag are averaged. Tags without 3D coordinates are
+/// ignored.
+pub fn corresponding_points(
+    fiducial_3d_coords: &[Fiducial3DCoords],
+    cam_data: &[AprilDetection],
+) -> Vec<AprilTagCorrespondingPoint<f64>> {
+    let object_points: BTreeMap<u32, [f64; 3]> = fiducial_3d_coords
+        .iter()
+        .map(|row| (row.id, [row.x, row.y, row.z]))
+        .collect();
+    gather_points_per_cam(&object_points, cam_data).unwrap_or_default()
+}
+
 struct CamSolution<'a> {
     final_cam: braid_mvg::Camera<f64>,
     points: &'a [AprilTagCorrespondingPoint<f64>],
@@ -0,0 +1,153 @@
+use std::fmt::Write;
+
+use crate::svg::{self, escape};
+use crate::CalibrationReport;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+.warning { color: #a40; }
+.figures { display: flex; flex-wrap: wrap; gap: 1.5em; }
+figure { margin: 0; }
+figcaption { font-size: 0.85em; color: #555; max-width: 360px; }
+";
+
+fn fmt_opt(value: Option<f64>, precision: usize) -> String {
+    match value {
+        Some(value) => format!("{value:.precision$}"),
+        None => "-".to_string(),
+    }
+}
+
+pub(crate) fn render(report: &CalibrationReport) -> String {
+    let mut html = String::new();
+    let title = escape(&report.title);
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>"
+    )
+    .unwrap();
+    writeln!(html, "<h1>{title}</h1>").unwrap();
+
+    html.push_str("<h2>Problems</h2>\n");
+    if report.warnings.is_empty() {
+        html.push_str("<p>No problems found.</p>\n");
+    } else {
+        html.push_str("<ul>\n");
+        for warning in report.warnings.iter() {
+            writeln!(html, "<li class=\"warning\">{}</li>", escape(warning)).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str("<h2>Reprojection error</h2>\n");
+    html.push_str(
+        "<table>\n<tr><th>camera</th><th>resolution</th><th>points</th><th>mean (px)</th>\
+         <th>median (px)</th><th>95th percentile (px)</th><th>max (px)</th></tr>\n",
+    );
+    for cam in report.cameras.iter() {
+        let stats = cam.stats.as_ref();
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}×{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&cam.name),
+            cam.width,
+            cam.height,
+            cam.residuals.len(),
+            fmt_opt(stats.map(|s| s.mean), 3),
+            fmt_opt(stats.map(|s| s.median), 3),
+            fmt_opt(stats.map(|s| s.p95), 3),
+            fmt_opt(stats.map(|s| s.max), 3),
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+
+    for cam in report.cameras.iter().filter(|cam| cam.stats.is_some()) {
+        let (field, magnification) = svg::residual_field(cam);
+        writeln!(
+            html,
+            "<h3>{}</h3>\n<div class=\"figures\">",
+            escape(&cam.name)
+        )
+        .unwrap();
+        writeln!(
+            html,
+            "<figure>{}<figcaption>Histogram of reprojection errors.</figcaption></figure>",
+            svg::histogram(&cam.distances())
+        )
+        .unwrap();
+        writeln!(
+            html,
+            "<figure>{field}<figcaption>Residuals over the image, from the observed \
+             (gray) towards the reprojected pixel, magnified {magnification}×.</figcaption></figure>",
+        )
+        .unwrap();
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("<h2>Camera layout</h2>\n<div class=\"figures\">\n");
+    for (axes, caption) in [
+        ([0, 1], "Top view (x right, y up)."),
+        ([0, 2], "Front view (x right, z up)."),
+        ([1, 2], "Side view (y right, z up)."),
+    ] {
+        writeln!(
+            html,
+            "<figure>{}<figcaption>{caption}</figcaption></figure>",
+            svg::frusta_view(&report.frusta, &report.points, axes)
+        )
+        .unwrap();
+    }
+    html.push_str("</div>\n");
+
+    html.push_str("<h2>Camera pairs</h2>\n");
+    html.push_str(
+        "<table>\n<tr><th>cameras</th><th>baseline</th><th>shared points</th>\
+         <th>median triangulation angle (°)</th></tr>\n",
+    );
+    for pair in report.pairs.iter() {
+        writeln!(
+            html,
+            "<tr><td>{} – {}</td><td>{:.3}</td><td>{}</td><td>{}</td></tr>",
+            escape(&pair.cams.0),
+            escape(&pair.cams.1),
+            pair.baseline,
+            pair.num_shared_points,
+            fmt_opt(pair.median_triangulation_angle, 1),
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Coverage of the tracking volume</h2>\n");
+    if report.coverage.is_empty() {
+        html.push_str("<p>No points to define the tracking volume.</p>\n");
+    } else {
+        html.push_str(
+            "<p>Horizontal slices through the bounding box of all points. Each cell \
+             shows the best triangulation angle of any two cameras seeing its center, \
+             from red (parallel rays) to green (30° or more). Gray cells are seen by \
+             fewer than two cameras.</p>\n<div class=\"figures\">\n",
+        );
+        for slice in report.coverage.iter() {
+            writeln!(
+                html,
+                "<figure>{}<figcaption>z = {:.3}; x from {:.3} to {:.3}, y from {:.3} to {:.3}.</figcaption></figure>",
+                svg::coverage_map(slice),
+                slice.z,
+                slice.x_range[0],
+                slice.x_range[1],
+                slice.y_range[0],
+                slice.y_range[1],
+            )
+            .unwrap();
+        }
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
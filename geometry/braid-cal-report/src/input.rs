@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use braid_april_cal::{
+    corresponding_points, get_apriltag_cfg, AprilDetection, AprilTagCorrespondingPoint,
+    Fiducial3DCoords,
+};
+use braid_mvg::{DistortedPixel, MultiCameraSystem};
+use camino::Utf8Path;
+use nalgebra::Point2;
+
+use crate::{CorrespondingPoints, Error, Result};
+
+fn read_csv<T: serde::de::DeserializeOwned>(path: &Utf8Path) -> Result<Vec<T>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .from_path(path)?;
+    let rows = rdr.deserialize().collect::<std::result::Result<_, _>>()?;
+    Ok(rows)
+}
+
+/// Collect corresponding points from April Tag detections.
+///
+/// `fiducial_3d_coords` is a CSV file with the 3D coordinates of the tags, as
+/// used by `braid-april-cal-cli`. `detections_dir` contains the
+/// `apriltags<date>_<time>_<cam-name>.csv` detection files saved by Strand
+/// Camera. Detections with a non-zero hamming distance are ignored.
+pub fn points_from_apriltags(
+    fiducial_3d_coords: &Utf8Path,
+    detections_dir: &Utf8Path,
+) -> Result<CorrespondingPoints> {
+    let fiducial_3d_coords: Vec<Fiducial3DCoords> = read_csv(fiducial_3d_coords)?;
+
+    let mut result = BTreeMap::new();
+    for entry in detections_dir.read_dir_utf8()? {
+        let entry = entry?;
+        let fname = entry.file_name();
+        if !(fname.starts_with("apriltags") || fname.ends_with(".apriltag.csv"))
+            || !fname.ends_with(".csv")
+            || !entry.file_type()?.is_file()
+        {
+            continue;
+        }
+        let cfg = get_apriltag_cfg(std::fs::File::open(entry.path())?)?;
+        let detections: Vec<AprilDetection> = read_csv::<AprilDetection>(entry.path())?
+            .into_iter()
+            .filter(|row| row.hamming == 0)
+            .collect();
+        let points = corresponding_points(&fiducial_3d_coords, &detections);
+        if result.insert(cfg.camera_name.clone(), points).is_some() {
+            return Err(Error::DuplicateCamera(cfg.camera_name));
+        }
+    }
+
+    if result.values().all(Vec::is_empty) {
+        return Err(Error::NoPoints);
+    }
+    Ok(result)
+}
+
+/// Collect corresponding points from a `.braidz` file of a single point.
+///
+/// Frames in which at least two cameras detect exactly one point are used.
+/// The 3D point is triangulated with `system`, so the reprojection errors
+/// include the triangulation error as in a calibration from a moving LED.
+pub fn points_from_braidz(
+    braidz: &Utf8Path,
+    system: &MultiCameraSystem<f64>,
+) -> Result<CorrespondingPoints> {
+    let mut archive = braidz_parser::braidz_parse_path(braidz)?;
+    let camn2camid = archive.cam_info.camn2camid.clone();
+
+    let mut by_frame: BTreeMap<i64, Vec<(String, [f64; 2])>> = BTreeMap::new();
+    for row in archive.iter_data2d_distorted()? {
+        let row = row?;
+        if row.x.is_nan() || row.y.is_nan() {
+            continue;
+        }
+        let Some(cam_name) = camn2camid.get(&row.camn) else {
+            continue;
+        };
+        by_frame
+            .entry(row.frame)
+            .or_default()
+            .push((cam_name.clone(), [row.x, row.y]));
+    }
+
+    let mut result: CorrespondingPoints = BTreeMap::new();
+    let mut id: i32 = 0;
+    for detections in by_frame.values() {
+        let mut count: BTreeMap<&str, usize> = BTreeMap::new();
+        for (cam_name, _) in detections.iter() {
+            *count.entry(cam_name).or_default() += 1;
+        }
+        let single: Vec<&(String, [f64; 2])> = detections
+            .iter()
+            .filter(|(cam_name, _)| count[cam_name.as_str()] == 1)
+            .collect();
+        if single.len() < 2 {
+            continue;
+        }
+
+        let mut undistorted = Vec::with_capacity(single.len());
+        for (cam_name, xy) in single.iter() {
+            let cam = system
+                .cam_by_name(cam_name)
+                .ok_or_else(|| Error::UnknownCamera(cam_name.clone()))?;
+            let distorted = DistortedPixel {
+                coords: Point2::new(xy[0], xy[1]),
+            };
+            undistorted.push((cam_name.clone(), cam.undistort(&distorted)));
+        }
+        let pt = match system.find3d(&undistorted) {
+            Ok(pt) => pt.coords,
+            Err(e) => {
+                tracing::debug!("could not triangulate point: {e}");
+                continue;
+            }
+        };
+
+        for (cam_name, xy) in single.into_iter() {
+            result
+                .entry(cam_name.clone())
+                .or_default()
+                .push(AprilTagCorrespondingPoint {
+                    id,
+                    object_point: [pt.x, pt.y, pt.z],
+                    image_point: *xy,
+                });
+        }
+        id = id.checked_add(1).ok_or(Error::TooManyPoints)?;
+    }
+
+    if result.is_empty() {
+        return Err(Error::NoPoints);
+    }
+    Ok(result)
+}
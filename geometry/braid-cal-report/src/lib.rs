@@ -0,0 +1,515 @@
+//! Reports on the quality of a multi-camera calibration.
+//!
+//! A [CalibrationReport] is computed from a calibration and corresponding 3D
+//! and 2D points for each camera. These come either from April Tag detections
+//! with known 3D coordinates ([points_from_apriltags]) or from a `.braidz`
+//! recording of a single point, such as an LED, moved through the tracking
+//! volume ([points_from_braidz]). The report is written as a self-contained
+//! HTML file with SVG figures by [CalibrationReport::to_html].
+//!
+//! Refraction (e.g. at a water surface) is not modelled: all geometry is
+//! computed as if the cameras and points were in air.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use braid_april_cal::{compute_mean_reproj_dist, AprilTagCorrespondingPoint};
+use braid_mvg::{Camera, DistortedPixel, MultiCameraSystem, PointWorldFrame, UndistortedPixel};
+use nalgebra::{Point2, Point3, Vector3};
+
+mod html;
+mod input;
+mod svg;
+
+pub use input::{points_from_apriltags, points_from_braidz};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Mvg(#[from] braid_mvg::MvgError),
+    #[error("{0}")]
+    Braidz(#[from] braidz_parser::Error),
+    #[error("{0}")]
+    AprilCal(#[from] braid_april_cal::MyError),
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("camera \"{0}\" is not in the calibration")]
+    UnknownCamera(String),
+    #[error("multiple april tag detection files for camera \"{0}\"")]
+    DuplicateCamera(String),
+    #[error("no points with both 3D and 2D coordinates found")]
+    NoPoints,
+    #[error("too many points")]
+    TooManyPoints,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The corresponding 3D and 2D points of each camera, keyed by camera name.
+///
+/// Points with the same `id` in several cameras are the same 3D point.
+pub type CorrespondingPoints = BTreeMap<String, Vec<AprilTagCorrespondingPoint<f64>>>;
+
+/// Options for [CalibrationReport::new].
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Cameras sharing fewer points than this with every other camera are
+    /// flagged as poorly overlapping.
+    pub min_shared_points: usize,
+    /// Number of cells along each horizontal axis of the coverage maps.
+    pub coverage_grid_size: usize,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            min_shared_points: 10,
+            coverage_grid_size: 24,
+        }
+    }
+}
+
+/// Summary statistics of reprojection distances, in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReprojStats {
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+/// Reprojection errors of a single camera.
+#[derive(Debug, Clone)]
+pub struct CameraReport {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// The observed and the reprojected (distorted) pixel of each point.
+    pub residuals: Vec<([f64; 2], [f64; 2])>,
+    /// `None` if the camera has no points.
+    pub stats: Option<ReprojStats>,
+}
+
+impl CameraReport {
+    /// Reprojection distance of each point, in pixels.
+    pub fn distances(&self) -> Vec<f64> {
+        self.residuals
+            .iter()
+            .map(|(observed, reprojected)| {
+                nalgebra::distance(&Point2::from(*observed), &Point2::from(*reprojected))
+            })
+            .collect()
+    }
+}
+
+/// Geometry shared by two cameras.
+#[derive(Debug, Clone)]
+pub struct CameraPairReport {
+    pub cams: (String, String),
+    /// Distance between the camera centers.
+    pub baseline: f64,
+    /// Number of points observed by both cameras.
+    pub num_shared_points: usize,
+    /// Median angle, in degrees, between the rays of both cameras to the
+    /// shared points.
+    pub median_triangulation_angle: Option<f64>,
+}
+
+/// A camera drawn as pyramid from its center to the image corners.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    pub name: String,
+    pub center: [f64; 3],
+    pub corners: [[f64; 3]; 4],
+}
+
+/// Coverage of one cell of a [CoverageSlice].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoverageCell {
+    /// Number of cameras seeing the cell center.
+    pub num_cams: usize,
+    /// The largest triangulation angle, in degrees, of any pair of cameras
+    /// seeing the cell center. Angles above 90° are folded to `180° - angle`
+    /// as they constrain the position equally well.
+    pub best_angle: Option<f64>,
+}
+
+/// Coverage of a horizontal slice through the tracking volume.
+#[derive(Debug, Clone)]
+pub struct CoverageSlice {
+    pub z: f64,
+    pub x_range: [f64; 2],
+    pub y_range: [f64; 2],
+    /// `grid_size` × `grid_size` cells, row by row with increasing y.
+    pub cells: Vec<CoverageCell>,
+    pub grid_size: usize,
+}
+
+/// A report on the quality of a multi-camera calibration.
+#[derive(Debug, Clone)]
+pub struct CalibrationReport {
+    pub title: String,
+    pub cameras: Vec<CameraReport>,
+    pub pairs: Vec<CameraPairReport>,
+    pub frusta: Vec<Frustum>,
+    /// The unique 3D points.
+    pub points: Vec<[f64; 3]>,
+    /// Empty if there are no points.
+    pub coverage: Vec<CoverageSlice>,
+    /// Problems found with the calibration, such as poorly overlapping
+    /// cameras.
+    pub warnings: Vec<String>,
+}
+
+impl CalibrationReport {
+    /// Compute the report.
+    ///
+    /// All cameras in `points` must be part of `system`. Cameras of `system`
+    /// without points are reported as such.
+    pub fn new(
+        title: &str,
+        system: &MultiCameraSystem<f64>,
+        points: &CorrespondingPoints,
+        opts: &ReportOptions,
+    ) -> Result<Self> {
+        for name in points.keys() {
+            if system.cam_by_name(name).is_none() {
+                return Err(Error::UnknownCamera(name.clone()));
+            }
+        }
+
+        let mut unique_points = BTreeMap::new();
+        for pts in points.values() {
+            for pt in pts.iter() {
+                unique_points.entry(pt.id).or_insert(pt.object_point);
+            }
+        }
+
+        let cameras = system
+            .cams_by_name()
+            .iter()
+            .map(|(name, cam)| {
+                camera_report(
+                    name,
+                    cam,
+                    points.get(name).map(Vec::as_slice).unwrap_or(&[]),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let ids: BTreeMap<&str, BTreeSet<i32>> = points
+            .iter()
+            .map(|(name, pts)| (name.as_str(), pts.iter().map(|pt| pt.id).collect()))
+            .collect();
+        let names: Vec<&String> = system.cams_by_name().keys().collect();
+        let mut pairs = Vec::new();
+        for (i, name_a) in names.iter().enumerate() {
+            for name_b in names[i + 1..].iter() {
+                let cc_a = camcenter(&system.cams_by_name()[*name_a]);
+                let cc_b = camcenter(&system.cams_by_name()[*name_b]);
+                let shared: Vec<i32> = match (ids.get(name_a.as_str()), ids.get(name_b.as_str())) {
+                    (Some(a), Some(b)) => a.intersection(b).copied().collect(),
+                    _ => Vec::new(),
+                };
+                let mut angles: Vec<f64> = shared
+                    .iter()
+                    .map(|id| triangulation_angle(&cc_a, &cc_b, &Vector3::from(unique_points[id])))
+                    .collect();
+                pairs.push(CameraPairReport {
+                    cams: ((*name_a).clone(), (*name_b).clone()),
+                    baseline: (cc_a - cc_b).norm(),
+                    num_shared_points: shared.len(),
+                    median_triangulation_angle: percentile(&mut angles, 0.5),
+                });
+            }
+        }
+
+        let points3d: Vec<[f64; 3]> = unique_points.values().copied().collect();
+        let frusta = frusta(system, &points3d);
+        let coverage = coverage(system, &points3d, opts.coverage_grid_size);
+        let warnings = warnings(&cameras, &pairs, &coverage, opts);
+
+        Ok(Self {
+            title: title.to_string(),
+            cameras,
+            pairs,
+            frusta,
+            points: points3d,
+            coverage,
+            warnings,
+        })
+    }
+
+    /// Render the report as a self-contained HTML document.
+    pub fn to_html(&self) -> String {
+        html::render(self)
+    }
+}
+
+fn camera_report(
+    name: &str,
+    cam: &Camera<f64>,
+    points: &[AprilTagCorrespondingPoint<f64>],
+) -> CameraReport {
+    let residuals = points
+        .iter()
+        .map(|pt| {
+            let world_pt = PointWorldFrame {
+                coords: Point3::from(pt.object_point),
+            };
+            let reprojected = cam.project_3d_to_distorted_pixel(&world_pt).coords;
+            (pt.image_point, [reprojected.x, reprojected.y])
+        })
+        .collect();
+    let mut result = CameraReport {
+        name: name.to_string(),
+        width: cam.width(),
+        height: cam.height(),
+        residuals,
+        stats: None,
+    };
+    if !points.is_empty() {
+        let mut dists = result.distances();
+        result.stats = Some(ReprojStats {
+            mean: compute_mean_reproj_dist(cam, points),
+            median: percentile(&mut dists, 0.5).unwrap(),
+            p95: percentile(&mut dists, 0.95).unwrap(),
+            max: percentile(&mut dists, 1.0).unwrap(),
+        });
+    }
+    result
+}
+
+/// The value at fraction `p` of the sorted values, or `None` if there are no
+/// values.
+fn percentile(values: &mut [f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let idx = (p * (values.len() - 1) as f64).round() as usize;
+    Some(values[idx])
+}
+
+fn camcenter(cam: &Camera<f64>) -> Vector3<f64> {
+    cam.extrinsics().camcenter().coords
+}
+
+/// Angle, in degrees, at `pt` between the rays to two camera centers.
+fn triangulation_angle(cc_a: &Vector3<f64>, cc_b: &Vector3<f64>, pt: &Vector3<f64>) -> f64 {
+    (cc_a - pt).angle(&(cc_b - pt)).to_degrees()
+}
+
+/// Whether `pt` is in front of `cam` and projects into its image.
+fn sees(cam: &Camera<f64>, pt: &Vector3<f64>) -> bool {
+    let cc = camcenter(cam);
+    let principal = UndistortedPixel {
+        coords: Point2::new(cam.intrinsics().cx(), cam.intrinsics().cy()),
+    };
+    let forward = cam
+        .project_pixel_to_3d_with_dist(&principal, 1.0)
+        .coords
+        .coords
+        - cc;
+    if forward.dot(&(pt - cc)) <= 0.0 {
+        return false;
+    }
+    let world_pt = PointWorldFrame {
+        coords: Point3::from(*pt),
+    };
+    let (w, h) = (cam.width() as f64, cam.height() as f64);
+    // Strong distortion can map points far outside the image back into it, so
+    // also require the undistorted projection to be near the image.
+    let undistorted = cam.project_3d_to_pixel(&world_pt).coords;
+    if undistorted.x < -0.5 * w
+        || undistorted.x > 1.5 * w
+        || undistorted.y < -0.5 * h
+        || undistorted.y > 1.5 * h
+    {
+        return false;
+    }
+    let distorted = cam.project_3d_to_distorted_pixel(&world_pt).coords;
+    distorted.x >= 0.0 && distorted.x < w && distorted.y >= 0.0 && distorted.y < h
+}
+
+fn frusta(system: &MultiCameraSystem<f64>, points: &[[f64; 3]]) -> Vec<Frustum> {
+    system
+        .cams_by_name()
+        .iter()
+        .map(|(name, cam)| {
+            let cc = camcenter(cam);
+            // Draw the frustum up to the typical distance of the points.
+            let mut dists: Vec<f64> = points
+                .iter()
+                .map(|pt| (Vector3::from(*pt) - cc).norm())
+                .collect();
+            let depth = percentile(&mut dists, 0.5).unwrap_or(1.0);
+            let (w, h) = (cam.width() as f64, cam.height() as f64);
+            let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)].map(|(x, y)| {
+                let px = DistortedPixel {
+                    coords: Point2::new(x, y),
+                };
+                let pt = cam
+                    .project_distorted_pixel_to_3d_with_dist(&px, depth)
+                    .coords;
+                [pt.x, pt.y, pt.z]
+            });
+            Frustum {
+                name: name.clone(),
+                center: [cc.x, cc.y, cc.z],
+                corners,
+            }
+        })
+        .collect()
+}
+
+fn coverage(
+    system: &MultiCameraSystem<f64>,
+    points: &[[f64; 3]],
+    grid_size: usize,
+) -> Vec<CoverageSlice> {
+    if points.is_empty() || grid_size == 0 {
+        return Vec::new();
+    }
+    let mut lo = points[0];
+    let mut hi = points[0];
+    for pt in points.iter() {
+        for i in 0..3 {
+            lo[i] = lo[i].min(pt[i]);
+            hi[i] = hi[i].max(pt[i]);
+        }
+    }
+    let mut zs: Vec<f64> = points.iter().map(|pt| pt[2]).collect();
+    let zs = if hi[2] > lo[2] {
+        vec![lo[2], percentile(&mut zs, 0.5).unwrap(), hi[2]]
+    } else {
+        vec![lo[2]]
+    };
+
+    let cams: Vec<(Vector3<f64>, &Camera<f64>)> = system
+        .cams_by_name()
+        .values()
+        .map(|cam| (camcenter(cam), cam))
+        .collect();
+    let cell_center =
+        |i: usize, dim: usize| lo[dim] + (hi[dim] - lo[dim]) * (i as f64 + 0.5) / grid_size as f64;
+
+    zs.into_iter()
+        .map(|z| {
+            let mut cells = Vec::with_capacity(grid_size * grid_size);
+            for iy in 0..grid_size {
+                for ix in 0..grid_size {
+                    let pt = Vector3::new(cell_center(ix, 0), cell_center(iy, 1), z);
+                    let seeing: Vec<&Vector3<f64>> = cams
+                        .iter()
+                        .filter(|(_, cam)| sees(cam, &pt))
+                        .map(|(cc, _)| cc)
+                        .collect();
+                    let mut best_angle: Option<f64> = None;
+                    for (i, cc_a) in seeing.iter().enumerate() {
+                        for cc_b in seeing[i + 1..].iter() {
+                            let angle = triangulation_angle(cc_a, cc_b, &pt);
+                            let angle = angle.min(180.0 - angle);
+                            best_angle = Some(best_angle.map_or(angle, |b| b.max(angle)));
+                        }
+                    }
+                    cells.push(CoverageCell {
+                        num_cams: seeing.len(),
+                        best_angle,
+                    });
+                }
+            }
+            CoverageSlice {
+                z,
+                x_range: [lo[0], hi[0]],
+                y_range: [lo[1], hi[1]],
+                cells,
+                grid_size,
+            }
+        })
+        .collect()
+}
+
+fn warnings(
+    cameras: &[CameraReport],
+    pairs: &[CameraPairReport],
+    coverage: &[CoverageSlice],
+    opts: &ReportOptions,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for cam in cameras.iter() {
+        if cam.residuals.is_empty() {
+            warnings.push(format!("Camera \"{}\" has no observations.", cam.name));
+            continue;
+        }
+        let max_shared = pairs
+            .iter()
+            .filter(|pair| pair.cams.0 == cam.name || pair.cams.1 == cam.name)
+            .map(|pair| pair.num_shared_points)
+            .max()
+            .unwrap_or(0);
+        if max_shared < opts.min_shared_points {
+            warnings.push(format!(
+                "Camera \"{}\" shares at most {} points with any other camera (fewer than {}).",
+                cam.name, max_shared, opts.min_shared_points
+            ));
+        }
+    }
+
+    let mut means: Vec<f64> = cameras
+        .iter()
+        .filter_map(|cam| cam.stats.as_ref().map(|s| s.mean))
+        .collect();
+    if means.len() >= 3 {
+        let median = percentile(&mut means, 0.5).unwrap();
+        for cam in cameras.iter() {
+            if let Some(stats) = &cam.stats {
+                if stats.mean > 2.0 * median {
+                    warnings.push(format!(
+                        "Camera \"{}\" has a mean reprojection error of {:.2} pixels, more than \
+                         twice the median over all cameras ({:.2} pixels).",
+                        cam.name, stats.mean, median
+                    ));
+                }
+            }
+        }
+    }
+
+    let num_cells: usize = coverage.iter().map(|slice| slice.cells.len()).sum();
+    let num_poor = coverage
+        .iter()
+        .flat_map(|slice| slice.cells.iter())
+        .filter(|cell| cell.num_cams < 2)
+        .count();
+    if num_cells > 0 && num_poor * 20 >= num_cells {
+        warnings.push(format!(
+            "{:.0}% of the tracking volume is seen by fewer than two cameras.",
+            100.0 * num_poor as f64 / num_cells as f64
+        ));
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&mut [], 0.5), None);
+        let mut values = [3.0, 1.0, 2.0, 5.0, 4.0];
+        assert_eq!(percentile(&mut values, 0.0), Some(1.0));
+        assert_eq!(percentile(&mut values, 0.5), Some(3.0));
+        assert_eq!(percentile(&mut values, 1.0), Some(5.0));
+    }
+
+    #[test]
+    fn test_triangulation_angle() {
+        let pt = Vector3::zeros();
+        let angle = triangulation_angle(&Vector3::x(), &Vector3::y(), &pt);
+        assert!((angle - 90.0).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,102 @@
+use camino::Utf8PathBuf;
+use clap::Parser;
+use eyre::{self, Context, Result};
+
+use braid_cal_report::{
+    points_from_apriltags, points_from_braidz, CalibrationReport, ReportOptions,
+};
+
+/// Write an HTML report on the quality of a multi-camera calibration.
+///
+/// The calibration is checked against either a `.braidz` recording of a single
+/// point (such as an LED) or April Tag detections with known 3D coordinates.
+#[derive(Parser, Debug, Default)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Calibration XML file. If not given, the calibration saved in the
+    /// `--braidz` file is used.
+    #[arg(long)]
+    calibration: Option<Utf8PathBuf>,
+
+    /// Input braidz file with 2D detections of a single point.
+    #[arg(long, conflicts_with = "apriltags_2d_detections_dir")]
+    braidz: Option<Utf8PathBuf>,
+
+    /// CSV file with April Tags 3D fiducial coordinates.
+    #[arg(long, requires = "apriltags_2d_detections_dir")]
+    apriltags_3d_fiducial_coords: Option<Utf8PathBuf>,
+
+    /// Directory containing `apriltags<date>_<time>_<cam-name>.csv` files.
+    #[arg(long, requires = "apriltags_3d_fiducial_coords")]
+    apriltags_2d_detections_dir: Option<Utf8PathBuf>,
+
+    /// Output HTML filename.
+    #[arg(long)]
+    output: Utf8PathBuf,
+
+    /// Cameras sharing fewer points than this with every other camera are
+    /// reported as poorly overlapping.
+    #[arg(long, default_value_t = 10)]
+    min_shared_points: usize,
+}
+
+fn main() -> Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+    let cli = Cli::parse();
+    let output = cli.output.clone();
+    write_report(cli)?;
+    println!("Saved calibration report: {output}");
+    Ok(())
+}
+
+fn write_report(cli: Cli) -> Result<()> {
+    let system = match (&cli.calibration, &cli.braidz) {
+        (Some(calibration), _) => {
+            flydra_mvg::FlydraMultiCameraSystem::<f64>::from_path(calibration)
+                .with_context(|| format!("while reading calibration {calibration}"))?
+                .to_system()
+        }
+        (None, Some(braidz)) => {
+            braidz_parser::braidz_parse_path(braidz)
+                .with_context(|| format!("while reading {braidz}"))?
+                .calibration_info
+                .ok_or_else(|| eyre::eyre!("no calibration in {braidz}"))?
+                .cameras
+        }
+        (None, None) => eyre::bail!("--calibration is required without --braidz"),
+    };
+
+    let points = match (
+        &cli.braidz,
+        &cli.apriltags_3d_fiducial_coords,
+        &cli.apriltags_2d_detections_dir,
+    ) {
+        (Some(braidz), _, _) => points_from_braidz(braidz, &system)?,
+        (None, Some(coords), Some(detections_dir)) => {
+            points_from_apriltags(coords, detections_dir)?
+        }
+        _ => eyre::bail!(
+            "Either --braidz or --apriltags-3d-fiducial-coords and \
+             --apriltags-2d-detections-dir are required."
+        ),
+    };
+
+    let title = match &cli.calibration {
+        Some(calibration) => format!("Calibration report: {calibration}"),
+        None => format!("Calibration report: {}", cli.braidz.as_ref().unwrap()),
+    };
+    let opts = ReportOptions {
+        min_shared_points: cli.min_shared_points,
+        ..Default::default()
+    };
+    let report = CalibrationReport::new(&title, &system, &points, &opts)?;
+    for warning in report.warnings.iter() {
+        tracing::warn!("{warning}");
+    }
+    std::fs::write(&cli.output, report.to_html())
+        .with_context(|| format!("while writing {}", cli.output))?;
+    Ok(())
+}
@@ -0,0 +1,321 @@
+//! Minimal SVG figures.
+
+use std::fmt::Write;
+
+use crate::{CameraReport, CoverageCell, CoverageSlice, Frustum};
+
+/// Maximum number of points or residual arrows drawn in one figure.
+const MAX_DRAWN: usize = 2000;
+
+/// Triangulation angle, in degrees, shown in full green in coverage maps.
+const GOOD_ANGLE: f64 = 30.0;
+
+pub(crate) fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Every n-th element such that at most [MAX_DRAWN] are returned.
+fn subsample<T>(items: &[T]) -> impl Iterator<Item = &T> {
+    let step = items.len().div_ceil(MAX_DRAWN).max(1);
+    items.iter().step_by(step)
+}
+
+/// Round to one significant digit.
+fn round_nice(value: f64) -> f64 {
+    if value <= 0.0 || !value.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(value.log10().floor());
+    (value / magnitude).round().max(1.0) * magnitude
+}
+
+/// Histogram of reprojection distances in pixels.
+pub(crate) fn histogram(values: &[f64]) -> String {
+    const W: f64 = 360.0;
+    const H: f64 = 180.0;
+    const MARGIN: f64 = 30.0;
+    const NUM_BINS: usize = 20;
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    // Outliers would squeeze all other bins together, so values above the
+    // 99th percentile are counted in the last bin.
+    let upper = sorted
+        .get(((sorted.len() as f64 - 1.0) * 0.99).round() as usize)
+        .copied()
+        .unwrap_or(1.0)
+        .max(1e-3);
+    let mut counts = [0usize; NUM_BINS];
+    for value in values.iter() {
+        let bin = ((value / upper) * NUM_BINS as f64) as usize;
+        counts[bin.min(NUM_BINS - 1)] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{W}" height="{H}" viewBox="0 0 {W} {H}">"#
+    )
+    .unwrap();
+    let plot_w = W - 2.0 * MARGIN;
+    let plot_h = H - 2.0 * MARGIN;
+    let bar_w = plot_w / NUM_BINS as f64;
+    for (i, count) in counts.iter().enumerate() {
+        let h = plot_h * *count as f64 / max_count as f64;
+        writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="steelblue"><title>{count}</title></rect>"#,
+            MARGIN + i as f64 * bar_w,
+            MARGIN + plot_h - h,
+            bar_w - 1.0,
+            h,
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        r#"<line x1="{MARGIN}" y1="{y}" x2="{x2}" y2="{y}" stroke="black"/>"#,
+        y = MARGIN + plot_h,
+        x2 = MARGIN + plot_w,
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{MARGIN}" y="{y}" font-size="11">0</text><text x="{x}" y="{y}" font-size="11" text-anchor="end">≥{upper:.2} px</text>"#,
+        y = H - 10.0,
+        x = MARGIN + plot_w,
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{MARGIN}" y="{y}" font-size="11">max. count {max_count}</text>"#,
+        y = MARGIN - 8.0,
+    )
+    .unwrap();
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Residuals drawn as arrows from the observed to the reprojected pixel.
+///
+/// Returns the SVG and the magnification of the arrows.
+pub(crate) fn residual_field(cam: &CameraReport) -> (String, f64) {
+    const W: f64 = 360.0;
+
+    let (width, height) = (cam.width.max(1) as f64, cam.height.max(1) as f64);
+    let scale = W / width;
+    let h = height * scale;
+
+    // Magnify so that typical residuals are 5% of the image width.
+    let mut dists = cam.distances();
+    dists.sort_by(|a, b| a.total_cmp(b));
+    let typical = dists
+        .get(((dists.len() as f64 - 1.0) * 0.95).round() as usize)
+        .copied()
+        .unwrap_or(0.0);
+    let magnification = round_nice(0.05 * width / typical).max(1.0);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{W}" height="{h:.0}" viewBox="0 0 {W} {h:.1}">"#
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect x="0" y="0" width="{W}" height="{h:.1}" fill="white" stroke="black"/>"#
+    )
+    .unwrap();
+    for (observed, reprojected) in subsample(&cam.residuals) {
+        let x1 = observed[0] * scale;
+        let y1 = observed[1] * scale;
+        let x2 = (observed[0] + (reprojected[0] - observed[0]) * magnification) * scale;
+        let y2 = (observed[1] + (reprojected[1] - observed[1]) * magnification) * scale;
+        writeln!(
+            svg,
+            r#"<circle cx="{x1:.1}" cy="{y1:.1}" r="1" fill="gray"/><line x1="{x1:.1}" y1="{y1:.1}" x2="{x2:.1}" y2="{y2:.1}" stroke="crimson" stroke-width="0.8"/>"#
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    (svg, magnification)
+}
+
+/// Orthographic view of the camera frusta and the points along one world
+/// axis. `axes` are the world axes shown horizontally and vertically.
+pub(crate) fn frusta_view(frusta: &[Frustum], points: &[[f64; 3]], axes: [usize; 2]) -> String {
+    const W: f64 = 360.0;
+    const MARGIN: f64 = 20.0;
+
+    let all = frusta
+        .iter()
+        .flat_map(|f| std::iter::once(&f.center).chain(f.corners.iter()))
+        .chain(points.iter());
+    let mut lo = [f64::INFINITY; 2];
+    let mut hi = [f64::NEG_INFINITY; 2];
+    for pt in all {
+        for (i, axis) in axes.iter().enumerate() {
+            if pt[*axis].is_finite() {
+                lo[i] = lo[i].min(pt[*axis]);
+                hi[i] = hi[i].max(pt[*axis]);
+            }
+        }
+    }
+    if lo[0] > hi[0] || lo[1] > hi[1] {
+        lo = [0.0; 2];
+        hi = [1.0; 2];
+    }
+    let extent = (hi[0] - lo[0]).max(hi[1] - lo[1]).max(1e-9);
+    let scale = (W - 2.0 * MARGIN) / extent;
+    let h = (hi[1] - lo[1]) * scale + 2.0 * MARGIN;
+    // World up is drawn upwards.
+    let to_svg = |pt: &[f64; 3]| {
+        (
+            MARGIN + (pt[axes[0]] - lo[0]) * scale,
+            h - MARGIN - (pt[axes[1]] - lo[1]) * scale,
+        )
+    };
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{W}" height="{h:.0}" viewBox="0 0 {W} {h:.1}">"#
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect x="0" y="0" width="{W}" height="{h:.1}" fill="white" stroke="black"/>"#
+    )
+    .unwrap();
+    for pt in subsample(points) {
+        let (x, y) = to_svg(pt);
+        writeln!(
+            svg,
+            r#"<circle cx="{x:.1}" cy="{y:.1}" r="1.2" fill="gray"/>"#
+        )
+        .unwrap();
+    }
+    for frustum in frusta.iter() {
+        let (cx, cy) = to_svg(&frustum.center);
+        let corners: Vec<(f64, f64)> = frustum.corners.iter().map(to_svg).collect();
+        for (x, y) in corners.iter() {
+            writeln!(
+                svg,
+                r#"<line x1="{cx:.1}" y1="{cy:.1}" x2="{x:.1}" y2="{y:.1}" stroke="navy" stroke-width="0.8"/>"#
+            )
+            .unwrap();
+        }
+        let polygon: Vec<String> = corners
+            .iter()
+            .map(|(x, y)| format!("{x:.1},{y:.1}"))
+            .collect();
+        writeln!(
+            svg,
+            r#"<polygon points="{}" fill="none" stroke="navy" stroke-width="0.8"/>"#,
+            polygon.join(" ")
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{cx:.1}" y="{:.1}" font-size="10" text-anchor="middle">{}</text>"#,
+            cy - 4.0,
+            escape(&frustum.name)
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn coverage_color(cell: &CoverageCell) -> String {
+    match cell.best_angle {
+        Some(angle) if cell.num_cams >= 2 => {
+            // Red for parallel rays to green for good triangulation angles.
+            let hue = 120.0 * (angle / GOOD_ANGLE).clamp(0.0, 1.0);
+            format!("hsl({hue:.0},70%,50%)")
+        }
+        _ => "#bbbbbb".to_string(),
+    }
+}
+
+/// Map of the best triangulation angle in a horizontal slice.
+pub(crate) fn coverage_map(slice: &CoverageSlice) -> String {
+    const W: f64 = 360.0;
+
+    let n = slice.grid_size;
+    let dx = (slice.x_range[1] - slice.x_range[0]).max(1e-9);
+    let dy = (slice.y_range[1] - slice.y_range[0]).max(1e-9);
+    let cell_w = W / n as f64;
+    let cell_h = (cell_w * dy / dx).clamp(2.0, 40.0);
+    let h = cell_h * n as f64;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{W}" height="{h:.0}" viewBox="0 0 {W} {h:.1}">"#
+    )
+    .unwrap();
+    for (i, cell) in slice.cells.iter().enumerate() {
+        let (ix, iy) = (i % n, i / n);
+        let angle = match cell.best_angle {
+            Some(angle) => format!(", best angle {angle:.1}°"),
+            None => String::new(),
+        };
+        writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"><title>{} cameras{angle}</title></rect>"#,
+            ix as f64 * cell_w,
+            // Increasing y is drawn upwards.
+            h - (iy + 1) as f64 * cell_h,
+            cell_w,
+            cell_h,
+            coverage_color(cell),
+            cell.num_cams,
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("cam<1> & \"2\""), "cam&lt;1&gt; &amp; &quot;2&quot;");
+    }
+
+    #[test]
+    fn test_round_nice() {
+        assert_eq!(round_nice(0.0), 1.0);
+        assert_eq!(round_nice(23.0), 20.0);
+        assert_eq!(round_nice(0.46), 0.5);
+        assert_eq!(round_nice(870.0), 900.0);
+    }
+
+    #[test]
+    fn test_histogram_counts_all_values() {
+        let values: Vec<f64> = (0..100).map(|i| i as f64 / 10.0).collect();
+        let svg = histogram(&values);
+        let total: usize = svg
+            .split("<title>")
+            .skip(1)
+            .map(|s| s.split('<').next().unwrap().parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(total, values.len());
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use braid_april_cal::AprilTagCorrespondingPoint;
+use braid_cal_report::{points_from_apriltags, CalibrationReport, ReportOptions};
+use braid_mvg::{test_util::camera_ring, Camera, MultiCameraSystem, PointWorldFrame};
+use camino::Utf8PathBuf;
+use nalgebra::Point3;
+
+fn make_system() -> MultiCameraSystem<f64> {
+    camera_ring(4, 2.0, 1.0)
+}
+
+fn object_points() -> Vec<[f64; 3]> {
+    let mut result = Vec::new();
+    for x in [-0.1, 0.0, 0.1] {
+        for y in [-0.1, 0.0, 0.1] {
+            for z in [-0.05, 0.05] {
+                result.push([x, y, z]);
+            }
+        }
+    }
+    result
+}
+
+fn project(cam: &Camera<f64>, object_point: &[f64; 3]) -> [f64; 2] {
+    let px = cam.project_3d_to_distorted_pixel(&PointWorldFrame {
+        coords: Point3::from(*object_point),
+    });
+    [px.coords.x, px.coords.y]
+}
+
+#[test]
+fn test_report() {
+    let system = make_system();
+    let mut points = BTreeMap::new();
+    for name in ["cam1", "cam2", "cam3"] {
+        let cam = system.cam_by_name(name).unwrap();
+        let mut pts = Vec::new();
+        for (id, object_point) in object_points().iter().enumerate() {
+            let mut image_point = project(cam, object_point);
+            if name == "cam3" {
+                // A calibration error of 3 pixels.
+                image_point[0] += 3.0;
+            }
+            pts.push(AprilTagCorrespondingPoint {
+                id: id as i32,
+                object_point: *object_point,
+                image_point,
+            });
+        }
+        points.insert(name.to_string(), pts);
+    }
+
+    let report =
+        CalibrationReport::new("test", &system, &points, &ReportOptions::default()).unwrap();
+
+    let by_name: BTreeMap<&str, _> = report
+        .cameras
+        .iter()
+        .map(|cam| (cam.name.as_str(), cam))
+        .collect();
+    let stats = by_name["cam1"].stats.as_ref().unwrap();
+    assert!(stats.max < 1e-6);
+    let stats = by_name["cam3"].stats.as_ref().unwrap();
+    approx::assert_relative_eq!(stats.mean, 3.0, epsilon = 1e-6);
+    assert!(by_name["cam4"].stats.is_none());
+
+    assert_eq!(report.pairs.len(), 6);
+    let pair = report
+        .pairs
+        .iter()
+        .find(|pair| pair.cams == ("cam1".to_string(), "cam3".to_string()))
+        .unwrap();
+    assert_eq!(pair.num_shared_points, object_points().len());
+    approx::assert_relative_eq!(pair.baseline, 4.0, epsilon = 1e-9);
+    let angle = pair.median_triangulation_angle.unwrap();
+    assert!(angle > 120.0 && angle < 180.0, "angle {angle}");
+
+    // All cameras look at the origin, so the center of the volume is covered
+    // by all of them.
+    let slice = &report.coverage[1];
+    let n = slice.grid_size;
+    assert_eq!(slice.cells[n / 2 * n + n / 2].num_cams, 4);
+
+    assert!(report
+        .warnings
+        .iter()
+        .any(|w| w.contains("\"cam4\" has no")));
+    assert!(report
+        .warnings
+        .iter()
+        .any(|w| w.contains("\"cam3\" has a mean")));
+    assert!(!report.warnings.iter().any(|w| w.contains("\"cam1\"")));
+
+    let html = report.to_html();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    for name in ["cam1", "cam2", "cam3", "cam4"] {
+        assert!(html.contains(name));
+    }
+}
+
+#[test]
+fn test_points_from_apriltags() {
+    let system = make_system();
+    let tmpdir = tempfile::tempdir().unwrap();
+    let root = Utf8PathBuf::from_path_buf(tmpdir.path().to_path_buf()).unwrap();
+
+    let coords = root.join("coords.csv");
+    let mut buf = "id,x,y,z\n".to_string();
+    for (id, pt) in object_points().iter().enumerate() {
+        buf.push_str(&format!("{id},{},{},{}\n", pt[0], pt[1], pt[2]));
+    }
+    std::fs::write(&coords, buf).unwrap();
+
+    for name in ["cam1", "cam2"] {
+        let cam = system.cam_by_name(name).unwrap();
+        let mut buf = format!(
+            "# -- start of yaml config --\n\
+             # created_at: 2024-10-17T16:44:18+02:00\n\
+             # camera_name: {name}\n\
+             # camera_width_pixels: 640\n\
+             # camera_height_pixels: 480\n\
+             # -- end of yaml config --\n\
+             frame,hamming,id,h02,h12\n"
+        );
+        for (id, pt) in object_points().iter().enumerate() {
+            let [u, v] = project(cam, pt);
+            buf.push_str(&format!("0,0,{id},{u},{v}\n"));
+            // Detections with bit errors are ignored.
+            buf.push_str(&format!("1,1,{id},{},{}\n", u + 50.0, v));
+        }
+        std::fs::write(
+            root.join(format!("apriltags20241017_164418_{name}.csv")),
+            buf,
+        )
+        .unwrap();
+    }
+
+    let points = points_from_apriltags(&coords, &root).unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!(points["cam1"].len(), object_points().len());
+
+    let report =
+        CalibrationReport::new("test", &system, &points, &ReportOptions::default()).unwrap();
+    for cam in report
+        .cameras
+        .iter()
+        .filter(|cam| cam.name != "cam3" && cam.name != "cam4")
+    {
+        assert!(cam.stats.as_ref().unwrap().max < 1e-6);
+    }
+}
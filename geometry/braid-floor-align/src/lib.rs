@@ -0,0 +1,474 @@
+//! Alignment of a calibration to the floor.
+//!
+//! After self-calibration, for example with MultiCamSelfCal, the world
+//! coordinate frame of a calibration is arbitrary. [align_to_floor] finds the
+//! similarity transform which puts the floor at z=0 with the z axis pointing
+//! up, given 3D points tracked on or at a fixed height above the floor, such
+//! as the positions of a walking animal or of a wand moved over the floor.
+//!
+//! The dominant plane of the points is found with RANSAC, so that points off
+//! the floor, for example of a flying animal, are ignored. The z axis points
+//! towards the side of the floor with the cameras. The scale can be fixed from
+//! the known length of a wand with a marker at each end, or from the surveyed
+//! positions of April Tags, which also fix the origin and the orientation
+//! around the z axis. Otherwise the scale and the horizontal directions of the
+//! calibration are kept.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use braid_mvg::DistortedPixel;
+use braidz_parser::BraidzArchive;
+use camino::Utf8Path;
+use flydra_mvg::FlydraMultiCameraSystem;
+use nalgebra::{Matrix3, Point2, Rotation3, Unit, Vector2, Vector3};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Braidz(#[from] braidz_parser::Error),
+    #[error("{0}")]
+    FlydraMvg(#[from] flydra_mvg::FlydraMvgError),
+    #[error("{0}")]
+    CalReport(#[from] braid_cal_report::Error),
+    #[error("no kalman estimates found")]
+    NoTrajectories,
+    #[error("only {0} points, at least 3 are required to fit a plane")]
+    TooFewPoints(usize),
+    #[error("the points do not span a plane")]
+    Degenerate,
+    #[error("no frames with exactly two tracked objects found")]
+    NoWandFrames,
+    #[error("only {0} markers at distinct horizontal positions, at least 2 are required")]
+    TooFewMarkers(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The plane of points `x` with `normal.dot(x) == offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plane {
+    pub normal: Unit<Vector3<f64>>,
+    pub offset: f64,
+}
+
+impl Plane {
+    /// Signed distance of `pt` from the plane, positive in the direction of
+    /// the normal.
+    pub fn distance(&self, pt: &Vector3<f64>) -> f64 {
+        self.normal.dot(pt) - self.offset
+    }
+
+    /// The least-squares plane through `points`.
+    fn fit<'a>(points: impl Iterator<Item = &'a Vector3<f64>> + Clone) -> Result<Self> {
+        let (eigen, centroid) = principal_axes(points)?;
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| eigen.eigenvalues[a].total_cmp(&eigen.eigenvalues[b]));
+        if eigen.eigenvalues[order[1]] <= 1e-12 * eigen.eigenvalues[order[2]] {
+            return Err(Error::Degenerate);
+        }
+        let normal = Unit::new_normalize(eigen.eigenvectors.column(order[0]).into_owned());
+        Ok(Self {
+            offset: normal.dot(&centroid),
+            normal,
+        })
+    }
+}
+
+/// Options for [align_to_floor].
+#[derive(Debug, Clone)]
+pub struct FloorAlignOptions {
+    /// Points within this distance of the floor plane are on the floor. The
+    /// distance is relative to the size of the cloud of points, the standard
+    /// deviation along its longest axis.
+    pub inlier_threshold: f64,
+    /// Height of the tracked points above the floor, in units of the aligned
+    /// calibration.
+    pub height: f64,
+    /// Number of planes through random triples of points tried in the search
+    /// for the dominant plane.
+    pub num_iterations: usize,
+}
+
+impl Default for FloorAlignOptions {
+    fn default() -> Self {
+        Self {
+            inlier_threshold: 0.01,
+            height: 0.0,
+            num_iterations: 1000,
+        }
+    }
+}
+
+/// A marker at a surveyed position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub id: u32,
+    /// Position of the marker in the frame of the calibration.
+    pub position: Vector3<f64>,
+    /// Surveyed position of the marker in the world frame.
+    pub surveyed: Vector3<f64>,
+}
+
+/// The reference for the scale of the aligned calibration.
+#[derive(Debug, Clone, Default)]
+pub enum ScaleReference {
+    /// Keep the scale and the horizontal directions of the calibration.
+    #[default]
+    Unchanged,
+    /// A wand of known length, with the positions of its ends in the frame of
+    /// the calibration.
+    Wand {
+        length: f64,
+        ends: Vec<[Vector3<f64>; 2]>,
+    },
+    /// Markers at surveyed positions. The horizontal positions of the markers
+    /// fix the scale, the origin and the orientation around the z axis.
+    Markers(Vec<Marker>),
+}
+
+/// The similarity transform `x' = scale * rotation * x + translation`, as
+/// used by [FlydraMultiCameraSystem::align].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Similarity {
+    pub scale: f64,
+    pub rotation: Matrix3<f64>,
+    pub translation: Vector3<f64>,
+}
+
+impl Similarity {
+    pub fn transform(&self, pt: &Vector3<f64>) -> Vector3<f64> {
+        self.scale * self.rotation * pt + self.translation
+    }
+}
+
+/// The result of [align_to_floor].
+#[derive(Debug, Clone)]
+pub struct FloorAlignment {
+    /// Transform from the frame of the calibration to the aligned frame.
+    pub transform: Similarity,
+    /// The floor plane found, in the frame of the calibration.
+    pub plane: Plane,
+    pub num_points: usize,
+    /// Number of points on the floor plane.
+    pub num_floor_points: usize,
+    /// Root mean square distance of the points on the floor plane from the
+    /// height [FloorAlignOptions::height], in units of the aligned
+    /// calibration.
+    pub floor_rms_dist: f64,
+    /// Maximum distance of the points on the floor plane from the height
+    /// [FloorAlignOptions::height], in units of the aligned calibration.
+    pub floor_max_dist: f64,
+    /// Lengths of the wand after alignment, if the wand was used.
+    pub wand_lengths: Vec<f64>,
+    /// Difference between the aligned and the surveyed position of each
+    /// marker, if markers were used.
+    pub marker_errors: BTreeMap<u32, Vector3<f64>>,
+}
+
+/// Positions of all tracked objects in a `.braidz` file.
+pub fn points_from_braidz<R: Read + Seek>(archive: &BraidzArchive<R>) -> Result<Vec<Vector3<f64>>> {
+    let kests = archive
+        .kalman_estimates_table
+        .as_ref()
+        .ok_or(Error::NoTrajectories)?;
+    Ok(kests
+        .iter()
+        .map(|row| Vector3::new(row.x, row.y, row.z))
+        .filter(|pt| pt.iter().all(|x| x.is_finite()))
+        .collect())
+}
+
+/// Positions of the ends of a wand in a `.braidz` file, from the frames in
+/// which exactly two objects are tracked.
+pub fn wand_ends_from_braidz<R: Read + Seek>(
+    archive: &BraidzArchive<R>,
+) -> Result<Vec<[Vector3<f64>; 2]>> {
+    let kests = archive
+        .kalman_estimates_table
+        .as_ref()
+        .ok_or(Error::NoTrajectories)?;
+    let mut by_frame: BTreeMap<u64, Vec<Vector3<f64>>> = BTreeMap::new();
+    for row in kests.iter() {
+        by_frame
+            .entry(row.frame.0)
+            .or_default()
+            .push(Vector3::new(row.x, row.y, row.z));
+    }
+    let ends: Vec<_> = by_frame
+        .into_values()
+        .filter_map(|pts| match pts.as_slice() {
+            [a, b] => Some([*a, *b]),
+            _ => None,
+        })
+        .collect();
+    if ends.is_empty() {
+        return Err(Error::NoWandFrames);
+    }
+    Ok(ends)
+}
+
+/// Triangulate April Tags with surveyed positions.
+///
+/// The arguments are as for [braid_cal_report::points_from_apriltags]. Tags
+/// detected by fewer than two cameras are skipped.
+pub fn markers_from_apriltags(
+    recon: &FlydraMultiCameraSystem<f64>,
+    fiducial_3d_coords: &Utf8Path,
+    detections_dir: &Utf8Path,
+) -> Result<Vec<Marker>> {
+    let points = braid_cal_report::points_from_apriltags(fiducial_3d_coords, detections_dir)?;
+    let mut by_id: BTreeMap<i32, (Vector3<f64>, Vec<(String, DistortedPixel<f64>)>)> =
+        BTreeMap::new();
+    for (cam_name, points) in points.into_iter() {
+        for pt in points.into_iter() {
+            let entry = by_id
+                .entry(pt.id)
+                .or_insert_with(|| (Vector3::from(pt.object_point), Vec::new()));
+            entry.1.push((
+                cam_name.clone(),
+                DistortedPixel {
+                    coords: Point2::from(pt.image_point),
+                },
+            ));
+        }
+    }
+
+    let mut result = Vec::new();
+    for (id, (surveyed, detections)) in by_id.into_iter() {
+        if detections.len() < 2 {
+            tracing::warn!("April Tag {id} detected by fewer than two cameras, skipping.");
+            continue;
+        }
+        let position = recon.find3d_distorted(&detections)?.point().coords.coords;
+        result.push(Marker {
+            id: id as u32,
+            position,
+            surveyed,
+        });
+    }
+    Ok(result)
+}
+
+/// Points used when searching for the dominant plane. All points are used to
+/// refine it.
+const MAX_SEARCH_POINTS: usize = 20_000;
+
+/// Find the transform which aligns the calibration `recon` to the floor.
+///
+/// `points` are positions, in the frame of the calibration, of objects on the
+/// floor or at the height [FloorAlignOptions::height] above it. Other points
+/// are allowed as long as most points are on the floor.
+pub fn align_to_floor(
+    recon: &FlydraMultiCameraSystem<f64>,
+    points: &[Vector3<f64>],
+    scale_reference: &ScaleReference,
+    opts: &FloorAlignOptions,
+) -> Result<FloorAlignment> {
+    let cam_centers: Vec<Vector3<f64>> = recon
+        .system()
+        .cams_by_name()
+        .values()
+        .map(|cam| *cam.extrinsics().camcenter())
+        .collect();
+    let cam_centroid = cam_centers.iter().sum::<Vector3<f64>>() / cam_centers.len().max(1) as f64;
+
+    let (mut plane, floor_points) = find_floor_plane(points, opts)?;
+    // Point the normal towards the cameras.
+    if plane.distance(&cam_centroid) < 0.0 {
+        plane = Plane {
+            normal: -plane.normal,
+            offset: -plane.offset,
+        };
+    }
+
+    // Rotate the normal to the z axis with the smallest possible rotation, so
+    // that the horizontal directions change as little as possible.
+    let level = Rotation3::rotation_between(plane.normal.as_ref(), &Vector3::z())
+        .unwrap_or_else(|| Rotation3::from_axis_angle(&Vector3::x_axis(), std::f64::consts::PI))
+        .into_inner();
+
+    // After leveling and scaling, the floor points are at z = scale * offset.
+    let (scale, rotation, translation_xy) = match scale_reference {
+        ScaleReference::Unchanged => (1.0, level, Vector2::zeros()),
+        ScaleReference::Wand { length, ends } => {
+            if ends.is_empty() {
+                return Err(Error::NoWandFrames);
+            }
+            let lengths = ends.iter().map(|[a, b]| (a - b).norm()).collect();
+            (length / median(lengths), level, Vector2::zeros())
+        }
+        ScaleReference::Markers(markers) => {
+            let leveled: Vec<Vector2<f64>> =
+                markers.iter().map(|m| (level * m.position).xy()).collect();
+            let surveyed: Vec<Vector2<f64>> = markers.iter().map(|m| m.surveyed.xy()).collect();
+            let (scale, angle, translation_xy) =
+                align_points_2d(&leveled, &surveyed).ok_or(Error::TooFewMarkers(markers.len()))?;
+            let yaw = Rotation3::from_axis_angle(&Vector3::z_axis(), angle).into_inner();
+            (scale, yaw * level, translation_xy)
+        }
+    };
+    let transform = Similarity {
+        scale,
+        rotation,
+        translation: Vector3::new(
+            translation_xy.x,
+            translation_xy.y,
+            opts.height - scale * plane.offset,
+        ),
+    };
+
+    let floor_dists: Vec<f64> = floor_points
+        .iter()
+        .map(|&i| (transform.transform(&points[i]).z - opts.height).abs())
+        .collect();
+    let floor_rms_dist =
+        (floor_dists.iter().map(|d| d * d).sum::<f64>() / floor_dists.len() as f64).sqrt();
+    let floor_max_dist = floor_dists.iter().copied().fold(0.0, f64::max);
+
+    let wand_lengths = match scale_reference {
+        ScaleReference::Wand { ends, .. } => ends
+            .iter()
+            .map(|[a, b]| (transform.transform(a) - transform.transform(b)).norm())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let marker_errors = match scale_reference {
+        ScaleReference::Markers(markers) => markers
+            .iter()
+            .map(|m| (m.id, transform.transform(&m.position) - m.surveyed))
+            .collect(),
+        _ => BTreeMap::new(),
+    };
+
+    Ok(FloorAlignment {
+        transform,
+        plane,
+        num_points: points.len(),
+        num_floor_points: floor_points.len(),
+        floor_rms_dist,
+        floor_max_dist,
+        wand_lengths,
+        marker_errors,
+    })
+}
+
+/// Find the dominant plane of `points` with RANSAC and return it with the
+/// indices of the points on it.
+fn find_floor_plane(
+    points: &[Vector3<f64>],
+    opts: &FloorAlignOptions,
+) -> Result<(Plane, Vec<usize>)> {
+    if points.len() < 3 {
+        return Err(Error::TooFewPoints(points.len()));
+    }
+    let (eigen, _) = principal_axes(points.iter())?;
+    let size = eigen.eigenvalues.max().sqrt();
+    let threshold = opts.inlier_threshold * size;
+
+    let stride = points.len().div_ceil(MAX_SEARCH_POINTS);
+    let search: Vec<&Vector3<f64>> = points.iter().step_by(stride).collect();
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut best: Option<(usize, Plane)> = None;
+    for _ in 0..opts.num_iterations {
+        let a = search[rng.next_index(search.len())];
+        let b = search[rng.next_index(search.len())];
+        let c = search[rng.next_index(search.len())];
+        let normal = (b - a).cross(&(c - a));
+        if normal.norm() <= 1e-9 * size * size {
+            continue;
+        }
+        let normal = Unit::new_normalize(normal);
+        let plane = Plane {
+            offset: normal.dot(a),
+            normal,
+        };
+        let count = search
+            .iter()
+            .filter(|pt| plane.distance(pt).abs() <= threshold)
+            .count();
+        if best.as_ref().is_none_or(|(n, _)| count > *n) {
+            best = Some((count, plane));
+        }
+    }
+    let (_, mut plane) = best.ok_or(Error::Degenerate)?;
+
+    // Refine the plane with least squares on all points near it.
+    let mut inliers = Vec::new();
+    for _ in 0..2 {
+        inliers = (0..points.len())
+            .filter(|&i| plane.distance(&points[i]).abs() <= threshold)
+            .collect();
+        if inliers.len() < 3 {
+            return Err(Error::TooFewPoints(inliers.len()));
+        }
+        plane = Plane::fit(inliers.iter().map(|&i| &points[i]))?;
+    }
+    Ok((plane, inliers))
+}
+
+/// The eigendecomposition of the covariance of `points` and their centroid.
+fn principal_axes<'a>(
+    points: impl Iterator<Item = &'a Vector3<f64>> + Clone,
+) -> Result<(nalgebra::SymmetricEigen<f64, nalgebra::U3>, Vector3<f64>)> {
+    let n = points.clone().count();
+    if n < 3 {
+        return Err(Error::TooFewPoints(n));
+    }
+    let centroid = points.clone().sum::<Vector3<f64>>() / n as f64;
+    let cov = points
+        .map(|pt| {
+            let d = pt - centroid;
+            d * d.transpose()
+        })
+        .sum::<Matrix3<f64>>()
+        / n as f64;
+    Ok((cov.symmetric_eigen(), centroid))
+}
+
+/// Find the 2D similarity transform `y = scale * rot(angle) * x + t` which
+/// best maps `x` to `y` in the least-squares sense.
+///
+/// Returns `None` if the points in `x` coincide.
+fn align_points_2d(x: &[Vector2<f64>], y: &[Vector2<f64>]) -> Option<(f64, f64, Vector2<f64>)> {
+    let n = x.len() as f64;
+    let mu_x = x.iter().sum::<Vector2<f64>>() / n;
+    let mu_y = y.iter().sum::<Vector2<f64>>() / n;
+    let (mut dot, mut cross, mut var_x) = (0.0, 0.0, 0.0);
+    for (xi, yi) in x.iter().zip(y.iter()) {
+        let (xc, yc) = (xi - mu_x, yi - mu_y);
+        dot += xc.dot(&yc);
+        cross += xc.perp(&yc);
+        var_x += xc.norm_squared();
+    }
+    if x.len() < 2 || var_x <= 0.0 {
+        return None;
+    }
+    let angle = cross.atan2(dot);
+    let scale = dot.hypot(cross) / var_x;
+    let rot = nalgebra::Rotation2::new(angle);
+    Some((scale, angle, mu_y - scale * (rot * mu_x)))
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    }
+}
+
+/// A small random number generator with a fixed seed, so that the results are
+/// reproducible.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_index(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
@@ -0,0 +1,159 @@
+use camino::Utf8PathBuf;
+use clap::Parser;
+use eyre::{self, Context, Result};
+
+use braid_floor_align::{
+    align_to_floor, markers_from_apriltags, points_from_braidz, wand_ends_from_braidz,
+    FloorAlignOptions, ScaleReference,
+};
+use flydra_mvg::FlydraMultiCameraSystem;
+
+/// Align a calibration to the floor using objects tracked on the floor.
+///
+/// The dominant plane of the tracked points is put at z=0, or at `--height`
+/// below the points, with the z axis pointing towards the cameras. The scale
+/// can be fixed with a wand of known length or with surveyed April Tags.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Input braidz file with objects tracked on the floor.
+    braidz: Utf8PathBuf,
+
+    /// Save the aligned calibration to this XML file.
+    #[arg(long)]
+    output_calibration: Utf8PathBuf,
+
+    /// Calibration XML file. If not given, the calibration saved in the
+    /// braidz file is used.
+    #[arg(long)]
+    calibration: Option<Utf8PathBuf>,
+
+    /// Height of the tracked points above the floor, in units of the aligned
+    /// calibration.
+    #[arg(long, default_value_t = 0.0)]
+    height: f64,
+
+    /// Points within this distance of the floor plane are on the floor,
+    /// relative to the size of the cloud of tracked points.
+    #[arg(long, default_value_t = 0.01)]
+    inlier_threshold: f64,
+
+    /// Length of the wand, in units of the aligned calibration. The two ends
+    /// of the wand are taken from frames with exactly two tracked objects.
+    #[arg(long)]
+    wand_length: Option<f64>,
+
+    /// CSV file with the surveyed 3D fiducial coordinates of April Tags.
+    #[arg(
+        long,
+        conflicts_with = "wand_length",
+        requires = "apriltags_2d_detections_dir"
+    )]
+    apriltags_3d_fiducial_coords: Option<Utf8PathBuf>,
+
+    /// Directory containing `apriltags<date>_<time>_<cam-name>.csv` files.
+    #[arg(long, requires = "apriltags_3d_fiducial_coords")]
+    apriltags_2d_detections_dir: Option<Utf8PathBuf>,
+}
+
+fn main() -> Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+    let cli = Cli::parse();
+
+    let archive = braidz_parser::braidz_parse_path(&cli.braidz)
+        .with_context(|| format!("while reading {}", cli.braidz))?;
+    let recon = match &cli.calibration {
+        Some(calibration) => FlydraMultiCameraSystem::<f64>::from_path(calibration)
+            .with_context(|| format!("while reading calibration {calibration}"))?,
+        None => {
+            let ci = archive
+                .calibration_info
+                .clone()
+                .ok_or_else(|| eyre::eyre!("no calibration in {}", cli.braidz))?;
+            FlydraMultiCameraSystem::from_system(ci.cameras, ci.water)
+                .with_refractive_interfaces(ci.refractive_interfaces)
+                .with_time_offsets(ci.time_offsets)
+        }
+    };
+    if recon.water().is_some() {
+        tracing::warn!(
+            "The water surface is always at z=0, which is the floor in the aligned calibration."
+        );
+    }
+
+    let points = points_from_braidz(&archive)?;
+    let scale_reference = if let Some(length) = cli.wand_length {
+        ScaleReference::Wand {
+            length,
+            ends: wand_ends_from_braidz(&archive)?,
+        }
+    } else if let (Some(coords), Some(detections_dir)) = (
+        &cli.apriltags_3d_fiducial_coords,
+        &cli.apriltags_2d_detections_dir,
+    ) {
+        ScaleReference::Markers(markers_from_apriltags(&recon, coords, detections_dir)?)
+    } else {
+        ScaleReference::Unchanged
+    };
+    let opts = FloorAlignOptions {
+        inlier_threshold: cli.inlier_threshold,
+        height: cli.height,
+        ..Default::default()
+    };
+    let alignment = align_to_floor(&recon, &points, &scale_reference, &opts)?;
+
+    let xform = &alignment.transform;
+    println!("Found alignment transform: -------");
+    println!("scale: {}", xform.scale);
+    println!("rotation:{}", xform.rotation);
+    println!("translation:{}", xform.translation);
+
+    println!(
+        "Floor: {} of {} points on the floor plane.",
+        alignment.num_floor_points, alignment.num_points
+    );
+    println!(
+        "Floor residuals: RMS {:.5}, maximum {:.5}",
+        alignment.floor_rms_dist, alignment.floor_max_dist
+    );
+    if !alignment.wand_lengths.is_empty() {
+        let n = alignment.wand_lengths.len() as f64;
+        let mean = alignment.wand_lengths.iter().sum::<f64>() / n;
+        let std = (alignment
+            .wand_lengths
+            .iter()
+            .map(|l| (l - mean).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+        println!(
+            "Wand: {} frames, aligned length mean {mean:.5}, standard deviation {std:.5}",
+            alignment.wand_lengths.len()
+        );
+    }
+    if !alignment.marker_errors.is_empty() {
+        println!(
+            "{:>8} {:>10} {:>10} {:>10} {:>10}",
+            "tag", "dx", "dy", "dz", "distance"
+        );
+        for (id, err) in alignment.marker_errors.iter() {
+            println!(
+                "{id:>8} {:>10.5} {:>10.5} {:>10.5} {:>10.5}",
+                err.x,
+                err.y,
+                err.z,
+                err.norm()
+            );
+        }
+    }
+
+    let aligned = recon.align(xform.scale, xform.rotation, xform.translation)?;
+    let fd = std::fs::File::create_new(&cli.output_calibration)
+        .with_context(|| format!("while creating {}", cli.output_calibration))?;
+    aligned.to_flydra_xml(fd)?;
+    println!("Saved aligned calibration: {}", cli.output_calibration);
+    Ok(())
+}
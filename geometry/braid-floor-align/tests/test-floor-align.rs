@@ -0,0 +1,138 @@
+use braid_floor_align::{align_to_floor, FloorAlignOptions, Marker, ScaleReference, Similarity};
+use braid_mvg::test_util::camera_ring;
+use flydra_mvg::FlydraMultiCameraSystem;
+use nalgebra::{Rotation3, Unit, Vector3};
+
+const HEIGHT: f64 = 0.01;
+
+/// The transform from the world frame to the frame of an unaligned
+/// calibration.
+fn unaligned_frame() -> Similarity {
+    let axis = Unit::new_normalize(Vector3::new(1.0, 2.0, 3.0));
+    Similarity {
+        scale: 0.5,
+        rotation: Rotation3::from_axis_angle(&axis, 0.7).into_inner(),
+        translation: Vector3::new(1.0, -2.0, 0.5),
+    }
+}
+
+fn make_system(xform: &Similarity) -> FlydraMultiCameraSystem<f64> {
+    FlydraMultiCameraSystem::from_system(camera_ring(3, 2.0, 1.5), None)
+        .align(xform.scale, xform.rotation, xform.translation)
+        .unwrap()
+}
+
+/// Points of an animal walking at height `HEIGHT` and some points of it
+/// jumping, in the world frame.
+fn world_points() -> (Vec<Vector3<f64>>, usize) {
+    let mut points = Vec::new();
+    for i in 0..40 {
+        for j in 0..40 {
+            let x = -0.5 + i as f64 / 39.0;
+            let y = -0.4 + j as f64 / 39.0 * 0.8;
+            points.push(Vector3::new(x, y, HEIGHT));
+        }
+    }
+    let num_floor = points.len();
+    for i in 0..300 {
+        let t = i as f64 / 300.0;
+        points.push(Vector3::new(
+            -0.5 + t,
+            0.3 * (t * 20.0).sin(),
+            0.1 + 0.3 * (t * 7.0).sin().abs(),
+        ));
+    }
+    (points, num_floor)
+}
+
+#[test]
+fn test_align_to_floor() {
+    let unaligned = unaligned_frame();
+    let recon = make_system(&unaligned);
+    let (points, num_floor) = world_points();
+    let points: Vec<_> = points.iter().map(|pt| unaligned.transform(pt)).collect();
+    let opts = FloorAlignOptions {
+        height: HEIGHT,
+        ..Default::default()
+    };
+
+    // Without scale reference, the floor is at z=0 and the scale is kept.
+    let alignment = align_to_floor(&recon, &points, &ScaleReference::Unchanged, &opts).unwrap();
+    assert_eq!(alignment.num_points, points.len());
+    assert_eq!(alignment.num_floor_points, num_floor);
+    assert!(alignment.floor_max_dist < 1e-9);
+    assert!((alignment.transform.scale - 1.0).abs() < 1e-12);
+    let aligned = recon
+        .align(
+            alignment.transform.scale,
+            alignment.transform.rotation,
+            alignment.transform.translation,
+        )
+        .unwrap();
+    for cam in aligned.system().cams_by_name().values() {
+        // The cameras are 1.5 world units above the floor, at half scale.
+        let expected = HEIGHT + 0.5 * (1.5 - HEIGHT);
+        let z = cam.extrinsics().camcenter().z;
+        assert!((z - expected).abs() < 1e-9, "camera height {z}");
+    }
+
+    // A wand of length 0.3 fixes the scale.
+    let ends = (0..50)
+        .map(|i| {
+            let angle = i as f64 * 0.3;
+            let a = Vector3::new(0.1 * angle.sin(), 0.1 * angle.cos(), HEIGHT);
+            let b = a + 0.3 * Vector3::new(angle.cos(), angle.sin(), 0.0);
+            [unaligned.transform(&a), unaligned.transform(&b)]
+        })
+        .collect();
+    let scale_reference = ScaleReference::Wand { length: 0.3, ends };
+    let alignment = align_to_floor(&recon, &points, &scale_reference, &opts).unwrap();
+    assert!((alignment.transform.scale - 2.0).abs() < 1e-9);
+    assert_eq!(alignment.wand_lengths.len(), 50);
+    assert!(alignment
+        .wand_lengths
+        .iter()
+        .all(|l| (l - 0.3).abs() < 1e-9));
+    for pt in points[..num_floor].iter() {
+        assert!((alignment.transform.transform(pt).z - HEIGHT).abs() < 1e-9);
+    }
+
+    // Surveyed markers fix the scale, origin and orientation.
+    let markers = [
+        (0, Vector3::new(-0.5, -0.5, 0.0)),
+        (1, Vector3::new(0.5, -0.5, 0.0)),
+        (2, Vector3::new(0.5, 0.5, 0.0)),
+        (3, Vector3::new(-0.5, 0.5, 0.0)),
+    ]
+    .into_iter()
+    .map(|(id, surveyed)| Marker {
+        id,
+        position: unaligned.transform(&surveyed),
+        surveyed,
+    })
+    .collect();
+    let scale_reference = ScaleReference::Markers(markers);
+    let alignment = align_to_floor(&recon, &points, &scale_reference, &opts).unwrap();
+    assert_eq!(alignment.marker_errors.len(), 4);
+    assert!(alignment
+        .marker_errors
+        .values()
+        .all(|err| err.norm() < 1e-9));
+    for (pt, world) in points.iter().zip(world_points().0.iter()) {
+        let dist = (alignment.transform.transform(pt) - world).norm();
+        assert!(dist < 1e-9, "distance {dist}");
+    }
+}
+
+#[test]
+fn test_too_few_points() {
+    let recon = make_system(&unaligned_frame());
+    let points = vec![Vector3::zeros(), Vector3::x()];
+    assert!(align_to_floor(
+        &recon,
+        &points,
+        &ScaleReference::Unchanged,
+        &Default::default()
+    )
+    .is_err());
+}
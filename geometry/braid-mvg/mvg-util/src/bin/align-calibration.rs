@@ -139,8 +139,7 @@ fn align_cal(opt: Opt) -> Result<PathBuf> {
     let mean_dist = nalgebra::Vector::<f64, Dyn, _>::from_vec(dvec).mean();
     println!("{mean_dist}");
 
-    let system = unaligned_calibration.system().align(s, rot, t)?;
-    let aligned = FlydraMultiCameraSystem::from_system(system, unaligned_calibration.water());
+    let aligned = unaligned_calibration.align(s, rot, t)?;
 
     let mut out_fd = std::fs::File::create_new(&output_aligned_cal).with_context(|| {
         format!(
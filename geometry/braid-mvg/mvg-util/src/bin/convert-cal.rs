@@ -0,0 +1,173 @@
+use clap::{Parser, ValueEnum};
+use eyre::{bail, Context, Result};
+use flydra_mvg::FlydraMultiCameraSystem;
+use std::path::{Path, PathBuf};
+
+use braid_mvg::MultiCameraSystem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Flydra XML file
+    FlydraXml,
+    /// PyMVG JSON file
+    Pymvg,
+    /// MultiCamSelfCal result directory (input only)
+    Mcsc,
+    /// COLMAP text model directory (cameras.txt and images.txt)
+    Colmap,
+    /// Directory of ROS camera_info YAML files with extrinsics
+    CameraInfo,
+    /// JSON file for the Blender import and export scripts
+    Blender,
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "convert-cal", version)]
+struct Opt {
+    /// Input calibration (file or directory)
+    input: PathBuf,
+
+    /// Output calibration (file or directory)
+    output: PathBuf,
+
+    /// Format of the input. If not given, a directory is read as
+    /// MultiCamSelfCal result, a .json or .pymvg file as PyMVG and any other
+    /// file as flydra XML.
+    #[arg(long, value_enum)]
+    from: Option<Format>,
+
+    /// Format of the output
+    #[arg(long, value_enum)]
+    to: Format,
+
+    /// With `--to blender`, also write the Blender import and export scripts
+    /// next to the output.
+    #[arg(long)]
+    write_blender_scripts: bool,
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    convert(&opt)?;
+    println!("Saved calibration: {}", opt.output.display());
+    Ok(())
+}
+
+fn read(path: &Path, format: Option<Format>) -> Result<FlydraMultiCameraSystem<f64>> {
+    let system = match format {
+        None => return Ok(FlydraMultiCameraSystem::from_path(path)?),
+        Some(Format::FlydraXml) => {
+            return Ok(FlydraMultiCameraSystem::from_flydra_xml(
+                std::fs::File::open(path)?,
+            )?)
+        }
+        Some(Format::Mcsc) => return Ok(FlydraMultiCameraSystem::from_mcsc_dir(path)?),
+        Some(Format::Pymvg) => MultiCameraSystem::from_pymvg_json(std::fs::File::open(path)?)?,
+        Some(Format::Colmap) => MultiCameraSystem::from_colmap_dir(path)?,
+        Some(Format::CameraInfo) => MultiCameraSystem::from_camera_info_dir(path)?,
+        Some(Format::Blender) => MultiCameraSystem::from_blender_json(std::fs::File::open(path)?)?,
+    };
+    Ok(FlydraMultiCameraSystem::from_system(system, None))
+}
+
+/// Approximate cameras with skew or rectification, as loaded from flydra XML
+/// files, by cameras which can be exported to COLMAP and Blender.
+fn approximate_standard(system: &MultiCameraSystem<f64>) -> Result<MultiCameraSystem<f64>> {
+    let mut cams = system.cams_by_name().clone();
+    for (name, cam) in cams.iter_mut() {
+        if cam.is_standard() {
+            continue;
+        }
+        let (approx, max_dist) = cam.approximate_standard()?;
+        println!(
+            "Camera \"{name}\" approximated without skew and rectification, maximum error \
+            {max_dist:.3} pixels."
+        );
+        *cam = approx;
+    }
+    Ok(MultiCameraSystem::new(cams))
+}
+
+fn convert(opt: &Opt) -> Result<()> {
+    let calibration = read(&opt.input, opt.from)
+        .with_context(|| format!("while reading calibration at {}", opt.input.display()))?;
+
+    if opt.to != Format::FlydraXml && calibration.has_refractive_boundary() {
+        bail!("Refractive boundaries can only be saved in flydra XML files.");
+    }
+    if opt.write_blender_scripts && opt.to != Format::Blender {
+        bail!("--write-blender-scripts requires --to blender");
+    }
+
+    let create = |path: &Path| {
+        std::fs::File::create_new(path)
+            .with_context(|| format!("While creating output file {}", path.display()))
+    };
+
+    match opt.to {
+        Format::FlydraXml => calibration.to_flydra_xml(create(&opt.output)?)?,
+        Format::Pymvg => calibration
+            .system()
+            .to_pymvg_writer(&mut create(&opt.output)?)?,
+        Format::Mcsc => bail!("MultiCamSelfCal results cannot be written"),
+        Format::Colmap => approximate_standard(calibration.system())?.to_colmap_dir(&opt.output)?,
+        Format::CameraInfo => calibration.system().to_camera_info_dir(&opt.output)?,
+        Format::Blender => {
+            let system = approximate_standard(calibration.system())?;
+            if system.cams_by_name().values().any(|cam| {
+                cam.fisheye_distortion().is_some() || !cam.intrinsics().distortion.is_linear()
+            }) {
+                println!("Lens distortion is not saved for Blender.");
+            }
+            system.to_blender_json(create(&opt.output)?)?;
+            if opt.write_blender_scripts {
+                let dir = opt.output.parent().unwrap_or(Path::new(""));
+                for (fname, script) in [
+                    ("import_braid_cameras.py", braid_mvg::blender::IMPORT_SCRIPT),
+                    ("export_braid_cameras.py", braid_mvg::blender::EXPORT_SCRIPT),
+                ] {
+                    let path = dir.join(fname);
+                    std::fs::write(&path, script)
+                        .with_context(|| format!("While writing {}", path.display()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_convert_cal() -> Result<()> {
+    let input = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("alignment-test-data")
+        .join("20241017_164418-unaligned.xml");
+    let orig = FlydraMultiCameraSystem::<f64>::from_path(&input)?;
+
+    let out_root = tempfile::tempdir()?; // will be deleted on drop
+    for (to, fname) in [
+        (Format::Pymvg, "cal.json"),
+        (Format::Colmap, "colmap"),
+        (Format::CameraInfo, "camera_info"),
+        (Format::Blender, "cameras.json"),
+    ] {
+        let output = out_root.path().join(fname);
+        let opt = Opt {
+            input: input.clone(),
+            output: output.clone(),
+            from: None,
+            to,
+            write_blender_scripts: to == Format::Blender,
+        };
+        convert(&opt)?;
+
+        let loaded = read(&output, Some(to))?;
+        assert_eq!(loaded.len(), orig.len());
+        for (name, cam) in orig.system().cams_by_name() {
+            let cam2 = loaded.system().cam_by_name(name).unwrap();
+            let dist = (cam.extrinsics().camcenter() - cam2.extrinsics().camcenter()).norm();
+            assert!(dist < 1e-6, "camera center of {name} moved by {dist}");
+        }
+    }
+    assert!(out_root.path().join("import_braid_cameras.py").exists());
+    Ok(())
+}
@@ -0,0 +1,252 @@
+// Copyright 2016-2025 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Approximation of cameras by standard OpenCV cameras.
+//!
+//! Calibrations from the flydra XML format and from MultiCamSelfCal have a
+//! projection matrix `P` with skew which differs from the camera matrix `K`,
+//! and a rectification matrix which maps between them. Other software expects
+//! cameras with `P = [K | 0]`, no skew and no rectification.
+
+use nalgebra::{DMatrix, DVector, Point2, RealField};
+
+use crate::fisheye::FisheyeDistortion;
+use crate::{Camera, DistortedPixel, Distortion, MvgError, Result, RosOpenCvIntrinsics};
+
+/// Number of grid points along each image axis used in the fit.
+const GRID_SIZE: usize = 20;
+const MAX_ITERATIONS: usize = 100;
+
+impl<R: RealField + Copy> Camera<R> {
+    /// Whether the camera has no skew and no rectification and its projection
+    /// matrix is `[K | 0]`.
+    pub fn is_standard(&self) -> bool {
+        let intrinsics = self.intrinsics();
+        let eps = na_f::<R>(1.0e-7);
+        let k = &intrinsics.k;
+        let expected_p = k.insert_column(3, R::zero());
+        intrinsics.rect.is_identity(eps)
+            && k[(0, 1)].abs() <= eps * k[(0, 0)].abs()
+            && (intrinsics.p - expected_p).amax() <= eps * k[(0, 0)].abs()
+    }
+
+    /// Approximate the camera by a camera with no skew and no rectification
+    /// and with projection matrix `[K | 0]`.
+    ///
+    /// Such cameras are used by COLMAP, Blender and most other software. The
+    /// extrinsic parameters and the distortion model are kept. The focal
+    /// lengths, principal point and distortion coefficients are fit to the
+    /// distorted pixel coordinates of a grid of points over the image.
+    ///
+    /// Returns the approximation and the largest distance, in pixels, between
+    /// the distorted pixel coordinates of the grid points in both cameras. If
+    /// the camera is already standard, it is returned with distance zero.
+    pub fn approximate_standard(&self) -> Result<(Self, R)> {
+        if self.is_standard() {
+            return Ok((self.clone(), R::zero()));
+        }
+
+        // Points in front of the camera at the grid of distorted pixels.
+        let mut targets = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+        let mut points = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+        let (w, h) = (na_f::<R>(self.width() as f64), na_f(self.height() as f64));
+        for i in 0..GRID_SIZE {
+            for j in 0..GRID_SIZE {
+                let frac = |n: usize| na_f::<R>(n as f64 / (GRID_SIZE - 1) as f64);
+                let target = DistortedPixel {
+                    coords: Point2::new(frac(i) * (w - R::one()), frac(j) * (h - R::one())),
+                };
+                let point = self.project_distorted_pixel_to_3d_with_dist(&target, R::one());
+                if point.coords.coords.iter().all(|x| x.is_finite()) {
+                    targets.push(target);
+                    points.push(point);
+                }
+            }
+        }
+
+        let residuals = |params: &DVector<R>| -> Result<DVector<R>> {
+            let cam = self.standard_with_params(params)?;
+            let mut r = DVector::zeros(2 * targets.len());
+            for (i, (target, point)) in targets.iter().zip(points.iter()).enumerate() {
+                let projected = cam.project_3d_to_distorted_pixel(point).coords;
+                r[2 * i] = projected.x - target.coords.x;
+                r[2 * i + 1] = projected.y - target.coords.y;
+            }
+            Ok(r)
+        };
+
+        // Levenberg-Marquardt minimization with a numerical Jacobian, starting
+        // from `K` without skew.
+        let k = &self.intrinsics().k;
+        let mut params = vec![k[(0, 0)], k[(1, 1)], k[(0, 2)], k[(1, 2)]];
+        match self.fisheye_distortion() {
+            Some(fisheye) => params.extend_from_slice(&fisheye.opencv_vec()),
+            None => {
+                let d = &self.intrinsics().distortion;
+                params.extend_from_slice(&[
+                    d.radial1(),
+                    d.radial2(),
+                    d.tangential1(),
+                    d.tangential2(),
+                    d.radial3(),
+                ]);
+            }
+        }
+        let mut params = DVector::from_vec(params);
+        let n = params.len();
+
+        let mut r = residuals(&params)?;
+        let mut cost = r.norm_squared();
+        let mut lambda = na_f::<R>(1e-3);
+        for _ in 0..MAX_ITERATIONS {
+            let mut jac = DMatrix::zeros(r.len(), n);
+            for j in 0..n {
+                let eps = na_f::<R>(1e-7) * params[j].abs().max(R::one());
+                let mut p = params.clone();
+                p[j] += eps;
+                jac.set_column(j, &((residuals(&p)? - &r) / eps));
+            }
+            let jtj = jac.tr_mul(&jac);
+            let g = jac.tr_mul(&r);
+
+            let mut improved = false;
+            while lambda < na_f(1e10) {
+                let mut a = jtj.clone();
+                for i in 0..n {
+                    a[(i, i)] *= R::one() + lambda;
+                }
+                let delta = a.cholesky().ok_or(MvgError::SvdFailed)?.solve(&-&g);
+                let candidate = &params + delta;
+                let candidate_r = residuals(&candidate)?;
+                let candidate_cost = candidate_r.norm_squared();
+                if candidate_cost < cost {
+                    let converged = cost - candidate_cost < na_f::<R>(1e-12) * cost;
+                    params = candidate;
+                    r = candidate_r;
+                    cost = candidate_cost;
+                    lambda = (lambda / na_f(10.0)).max(na_f(1e-12));
+                    improved = !converged;
+                    break;
+                }
+                lambda *= na_f(10.0);
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        let max_dist = r
+            .as_slice()
+            .chunks_exact(2)
+            .map(|xy| xy[0].hypot(xy[1]))
+            .fold(R::zero(), |a, b| a.max(b));
+        Ok((self.standard_with_params(&params)?, max_dist))
+    }
+
+    /// A standard camera with the extrinsics of this camera and focal
+    /// lengths, principal point and distortion coefficients `params`.
+    fn standard_with_params(&self, params: &DVector<R>) -> Result<Self> {
+        let (fx, fy, cx, cy) = (params[0], params[1], params[2], params[3]);
+        let extrinsics = self.extrinsics().clone();
+        match self.fisheye_distortion() {
+            Some(_) => {
+                let intrinsics = RosOpenCvIntrinsics::from_params(fx, R::zero(), fy, cx, cy);
+                let distortion = FisheyeDistortion::new(params[4], params[5], params[6], params[7]);
+                Camera::new_fisheye(
+                    self.width(),
+                    self.height(),
+                    extrinsics,
+                    intrinsics,
+                    distortion,
+                )
+            }
+            None => {
+                let distortion =
+                    Distortion::from_opencv_vec(params.fixed_rows::<5>(4).into_owned());
+                let intrinsics = RosOpenCvIntrinsics::from_params_with_distortion(
+                    fx,
+                    R::zero(),
+                    fy,
+                    cx,
+                    cy,
+                    distortion,
+                );
+                Camera::new(self.width(), self.height(), extrinsics, intrinsics)
+            }
+        }
+    }
+}
+
+#[inline]
+fn na_f<R: RealField>(x: f64) -> R {
+    nalgebra::convert(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_test_system;
+
+    #[test]
+    fn test_standard_unchanged() {
+        for cam in get_test_system().cams_by_name().values() {
+            assert!(cam.is_standard());
+            let (approx, max_dist) = cam.approximate_standard().unwrap();
+            assert_eq!(max_dist, 0.0);
+            assert_eq!(approx.intrinsics(), cam.intrinsics());
+        }
+    }
+
+    #[test]
+    fn test_approximate_flydra_xml() {
+        // A camera as loaded from the flydra XML format, in which the
+        // rectification matrix maps between the undistortion parameters in
+        // `K` and the linear camera model in `P`.
+        #[rustfmt::skip]
+        let p = nalgebra::Matrix3x4::new(
+            830.46, -1.06, 647.58, 0.0,
+            0.0, 832.12, 513.56, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+        );
+        let (fx, fy, cx, cy) = (812.49, 812.49, 658.25, 515.05);
+        #[rustfmt::skip]
+        let k = nalgebra::Matrix3::new(
+            fx, 0.0, cx,
+            0.0, fy, cy,
+            0.0, 0.0, 1.0,
+        );
+        #[rustfmt::skip]
+        let rect_t = nalgebra::Matrix3::new(
+            p[(0, 0)] / fx, 0.0, (p[(0, 2)] - cx) / fx,
+            0.0, p[(1, 1)] / fy, (p[(1, 2)] - cy) / fy,
+            0.0, 0.0, 1.0,
+        );
+        let distortion =
+            Distortion::from_opencv_vec(nalgebra::Vector5::new(-0.21, 0.12, 0.001, -0.0005, 0.0));
+        let intrinsics =
+            RosOpenCvIntrinsics::from_components(p, k, distortion, rect_t.transpose()).unwrap();
+        let cam = Camera::new(
+            1280,
+            1024,
+            get_test_system()
+                .cam_by_name("cam2-brown")
+                .unwrap()
+                .extrinsics()
+                .clone(),
+            intrinsics,
+        )
+        .unwrap();
+        assert!(!cam.is_standard());
+
+        let (approx, max_dist) = cam.approximate_standard().unwrap();
+        assert!(approx.is_standard());
+        assert!(max_dist < 3.0, "max_dist {max_dist}");
+        // The principal point moves from `K` towards `P`.
+        let pp = approx.intrinsics().k[(0, 2)];
+        assert!(pp < cx && pp > p[(0, 2)] - 5.0, "principal point {pp}");
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright 2016-2025 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Import and export of cameras for [Blender](https://www.blender.org).
+//!
+//! Cameras are exchanged as a JSON file with, for each camera, the properties
+//! of a Blender camera object: its location and rotation quaternion in the
+//! world, its lens (focal length) and sensor size in millimeters, sensor fit
+//! and lens shift, and the render resolution and pixel aspect ratio. The
+//! Python scripts [`IMPORT_SCRIPT`] and [`EXPORT_SCRIPT`] create the cameras in
+//! Blender from such a file and write such a file from the cameras in Blender.
+//!
+//! The world frame is not changed, so a calibration with z up matches
+//! Blender's convention. Blender cameras look along their -Z axis with Y up,
+//! whereas cameras in this crate look along +Z with Y down, so the camera
+//! frame is rotated by 180° about X. The principal point becomes a lens shift
+//! and different focal lengths in x and y become a pixel aspect ratio.
+//!
+//! Blender cameras have no lens distortion and no skew. The exported cameras
+//! are the linear part of the camera model, given by the projection matrix
+//! `P`. Images rendered in Blender thus correspond to undistorted images.
+//! Only cameras for which [`Camera::is_standard`] is true can be exported;
+//! others can first be approximated with [`Camera::approximate_standard`].
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use nalgebra::{Point3, Quaternion, RealField, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{interchange_error, Camera, MultiCameraSystem, Result, RosOpenCvIntrinsics};
+
+/// Python script to create cameras in Blender from an exported JSON file.
+///
+/// Run it as `blender --python import_braid_cameras.py -- cameras.json`. The
+/// render resolution is a property of the Blender scene and is set from the
+/// first camera.
+pub const IMPORT_SCRIPT: &str = include_str!("blender/import_braid_cameras.py");
+
+/// Python script to write a JSON file for import from the cameras in Blender.
+///
+/// Run it as `blender scene.blend --background --python
+/// export_braid_cameras.py -- cameras.json`.
+pub const EXPORT_SCRIPT: &str = include_str!("blender/export_braid_cameras.py");
+
+/// Sensor width of exported cameras in millimeters, Blender's default.
+const SENSOR_WIDTH: f64 = 36.0;
+/// Sensor height of exported cameras in millimeters, Blender's default.
+const SENSOR_HEIGHT: f64 = 24.0;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BlenderCameras {
+    cameras: Vec<BlenderCamera>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BlenderCamera {
+    name: String,
+    location: [f64; 3],
+    /// Rotation quaternion as `[w, x, y, z]`, as in Blender.
+    rotation_quaternion: [f64; 4],
+    lens: f64,
+    sensor_width: f64,
+    sensor_height: f64,
+    sensor_fit: SensorFit,
+    shift_x: f64,
+    shift_y: f64,
+    resolution_x: usize,
+    resolution_y: usize,
+    pixel_aspect_x: f64,
+    pixel_aspect_y: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum SensorFit {
+    Auto,
+    Horizontal,
+    Vertical,
+}
+
+impl<R> MultiCameraSystem<R>
+where
+    R: RealField + Default + Serialize + Copy,
+{
+    /// Write the cameras as JSON for import into Blender with
+    /// [`IMPORT_SCRIPT`].
+    ///
+    /// Lens distortion is not exported.
+    pub fn to_blender_json<W: Write>(&self, writer: W) -> Result<()> {
+        let cameras = self
+            .cams_by_name()
+            .iter()
+            .map(|(name, cam)| to_blender(name, cam))
+            .collect::<Result<Vec<_>>>()?;
+        serde_json::to_writer_pretty(writer, &BlenderCameras { cameras })?;
+        Ok(())
+    }
+
+    /// Read cameras from JSON written in Blender with [`EXPORT_SCRIPT`].
+    pub fn from_blender_json<Rd: Read>(reader: Rd) -> Result<Self> {
+        let data: BlenderCameras = serde_json::from_reader(reader)?;
+        let mut cams = BTreeMap::new();
+        for bcam in data.cameras.iter() {
+            if cams
+                .insert(bcam.name.clone(), from_blender(bcam)?)
+                .is_some()
+            {
+                return Err(interchange_error(format!(
+                    "more than one camera \"{}\"",
+                    bcam.name
+                )));
+            }
+        }
+        Ok(Self::new(cams))
+    }
+}
+
+/// Rotation from the camera frame of this crate to Blender's camera frame.
+fn flip<R: RealField + Copy>() -> UnitQuaternion<R> {
+    UnitQuaternion::from_axis_angle(&Vector3::x_axis(), R::pi())
+}
+
+fn to_blender<R: RealField + Copy>(name: &str, cam: &Camera<R>) -> Result<BlenderCamera> {
+    if !cam.is_standard() {
+        return Err(interchange_error(format!(
+            "camera \"{name}\" has skew or rectification, which Blender does not support \
+            (see `Camera::approximate_standard`)"
+        )));
+    }
+    let p = cam.intrinsics().p.map(f64_of);
+    let (fx, fy, cx, cy) = (p[(0, 0)], p[(1, 1)], p[(0, 2)], p[(1, 2)]);
+    let (width, height) = (cam.width() as f64, cam.height() as f64);
+
+    // fy / fx = pixel_aspect_x / pixel_aspect_y, with both at least 1.
+    let (pixel_aspect_x, pixel_aspect_y) = if fy >= fx {
+        (fy / fx, 1.0)
+    } else {
+        (1.0, fx / fy)
+    };
+    let ycor = pixel_aspect_y / pixel_aspect_x;
+    // With horizontal sensor fit, the sensor width spans the image width and
+    // lens shift is in units of the image width. Blender's image coordinates
+    // have their origin at the corner of the first pixel and y up.
+    let lens = fx * SENSOR_WIDTH / width;
+    let shift_x = (width / 2.0 - 0.5 - cx) / width;
+    let shift_y = (cy + 0.5 - height / 2.0) * ycor / width;
+
+    let pose = cam.extrinsics().pose();
+    let camera_to_world = pose.rotation.inverse() * flip();
+    let q = camera_to_world.quaternion().coords.map(f64_of);
+    let c = cam.extrinsics().camcenter().map(f64_of);
+
+    Ok(BlenderCamera {
+        name: name.to_string(),
+        location: [c.x, c.y, c.z],
+        rotation_quaternion: [q.w, q.x, q.y, q.z],
+        lens,
+        sensor_width: SENSOR_WIDTH,
+        sensor_height: SENSOR_HEIGHT,
+        sensor_fit: SensorFit::Horizontal,
+        shift_x,
+        shift_y,
+        resolution_x: cam.width(),
+        resolution_y: cam.height(),
+        pixel_aspect_x,
+        pixel_aspect_y,
+    })
+}
+
+fn from_blender<R: RealField + Copy>(bcam: &BlenderCamera) -> Result<Camera<R>> {
+    let (width, height) = (bcam.resolution_x as f64, bcam.resolution_y as f64);
+    let ycor = bcam.pixel_aspect_y / bcam.pixel_aspect_x;
+
+    // As in Blender's `BKE_camera_params_compute_viewplane`.
+    let horizontal = match bcam.sensor_fit {
+        SensorFit::Auto => width >= ycor * height,
+        SensorFit::Horizontal => true,
+        SensorFit::Vertical => false,
+    };
+    let sensor_size = if bcam.sensor_fit == SensorFit::Vertical {
+        bcam.sensor_height
+    } else {
+        bcam.sensor_width
+    };
+    let viewfac = if horizontal { width } else { ycor * height };
+
+    let fx = bcam.lens * viewfac / sensor_size;
+    let fy = fx / ycor;
+    let cx = width / 2.0 - bcam.shift_x * viewfac - 0.5;
+    let cy = height / 2.0 + bcam.shift_y * viewfac / ycor - 0.5;
+    let intrinsics =
+        RosOpenCvIntrinsics::from_params(na_f(fx), R::zero(), na_f(fy), na_f(cx), na_f(cy));
+
+    let [w, x, y, z] = bcam.rotation_quaternion.map(na_f::<R>);
+    let camera_to_world =
+        UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)) * flip().inverse();
+    let [x, y, z] = bcam.location.map(na_f::<R>);
+    let extrinsics = cam_geom::ExtrinsicParameters::from_rotation_and_camcenter(
+        camera_to_world.inverse(),
+        Point3::new(x, y, z),
+    );
+    Camera::new(bcam.resolution_x, bcam.resolution_y, extrinsics, intrinsics)
+}
+
+#[inline]
+fn f64_of<R: RealField>(x: R) -> f64 {
+    nalgebra::try_convert(x).unwrap()
+}
+
+#[inline]
+fn na_f<R: RealField>(x: f64) -> R {
+    nalgebra::convert(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_projections, get_test_system};
+    use crate::PointWorldFrame;
+
+    #[test]
+    fn test_blender_roundtrip() {
+        let mut cams = BTreeMap::new();
+        for (name, cam) in get_test_system().cams_by_name() {
+            // Only the linear part of the cameras is exported.
+            let intrinsics = RosOpenCvIntrinsics::from_components(
+                cam.intrinsics().p,
+                cam.intrinsics().p.fixed_view::<3, 3>(0, 0).into_owned(),
+                crate::Distortion::zero(),
+                cam.intrinsics().rect,
+            )
+            .unwrap();
+            let linear = Camera::new(
+                cam.width(),
+                cam.height(),
+                cam.extrinsics().clone(),
+                intrinsics,
+            )
+            .unwrap();
+            cams.insert(name.clone(), linear);
+        }
+        // A camera with fy < fx.
+        let intrinsics = RosOpenCvIntrinsics::from_params(800.0, 0.0, 780.0, 300.0, 200.0);
+        let cam = Camera::new(
+            600,
+            400,
+            crate::extrinsics::make_default_extrinsics(),
+            intrinsics,
+        )
+        .unwrap();
+        cams.insert("cam4-aspect".to_string(), cam);
+        let system = MultiCameraSystem::new(cams);
+
+        let mut buf = Vec::new();
+        system.to_blender_json(&mut buf).unwrap();
+        let loaded = MultiCameraSystem::<f64>::from_blender_json(&buf[..]).unwrap();
+        assert_same_projections(&system, &loaded);
+    }
+
+    #[test]
+    fn test_blender_conventions() {
+        // A camera 10 units above the origin with no rotation looks down and
+        // has world +y up in the image.
+        let json = r#"{"cameras": [{
+            "name": "top",
+            "location": [0.0, 0.0, 10.0],
+            "rotation_quaternion": [1.0, 0.0, 0.0, 0.0],
+            "lens": 50.0,
+            "sensor_width": 36.0,
+            "sensor_height": 24.0,
+            "sensor_fit": "AUTO",
+            "shift_x": 0.0,
+            "shift_y": 0.0,
+            "resolution_x": 1000,
+            "resolution_y": 500,
+            "pixel_aspect_x": 1.0,
+            "pixel_aspect_y": 1.0
+        }]}"#;
+        let system = MultiCameraSystem::<f64>::from_blender_json(json.as_bytes()).unwrap();
+        let cam = system.cam_by_name("top").unwrap();
+        let f = 50.0 * 1000.0 / 36.0;
+        let project = |x, y| {
+            cam.project_3d_to_pixel(&PointWorldFrame {
+                coords: Point3::new(x, y, 0.0),
+            })
+            .coords
+        };
+        approx::assert_relative_eq!(
+            project(0.0, 0.0),
+            nalgebra::Point2::new(499.5, 249.5),
+            epsilon = 1e-9
+        );
+        approx::assert_relative_eq!(
+            project(1.0, 0.0),
+            nalgebra::Point2::new(499.5 + f / 10.0, 249.5),
+            epsilon = 1e-9
+        );
+        approx::assert_relative_eq!(
+            project(0.0, 1.0),
+            nalgebra::Point2::new(499.5, 249.5 - f / 10.0),
+            epsilon = 1e-9
+        );
+    }
+}
@@ -0,0 +1,59 @@
+"""Write the cameras of a Blender scene for import into Braid.
+
+Run
+
+    blender scene.blend --background --python export_braid_cameras.py -- cameras.json
+
+and then convert the cameras with `convert-cal --input-format blender
+cameras.json cal.xml`. The resolution and pixel aspect ratio of each camera are
+taken from its custom properties, as set by `import_braid_cameras.py`, or else
+from the render settings of the scene.
+"""
+
+import json
+import sys
+
+import bpy
+
+
+def export_cameras(filename):
+    scene = bpy.context.scene
+    render = scene.render
+    cameras = []
+    for obj in scene.objects:
+        if obj.type != "CAMERA":
+            continue
+        cam = obj.data
+        if cam.type != "PERSP":
+            print(f"Skipping camera {obj.name}, which is not a perspective camera")
+            continue
+        location, rotation, _scale = obj.matrix_world.decompose()
+        scale = render.resolution_percentage / 100
+        cameras.append(
+            {
+                "name": obj.name,
+                "location": list(location),
+                "rotation_quaternion": list(rotation),
+                "lens": cam.lens,
+                "sensor_width": cam.sensor_width,
+                "sensor_height": cam.sensor_height,
+                "sensor_fit": cam.sensor_fit,
+                "shift_x": cam.shift_x,
+                "shift_y": cam.shift_y,
+                "resolution_x": obj.get(
+                    "resolution_x", round(render.resolution_x * scale)
+                ),
+                "resolution_y": obj.get(
+                    "resolution_y", round(render.resolution_y * scale)
+                ),
+                "pixel_aspect_x": obj.get("pixel_aspect_x", render.pixel_aspect_x),
+                "pixel_aspect_y": obj.get("pixel_aspect_y", render.pixel_aspect_y),
+            }
+        )
+    with open(filename, "w") as f:
+        json.dump({"cameras": cameras}, f, indent=2)
+    print(f"Exported {len(cameras)} cameras to {filename}")
+
+
+if __name__ == "__main__":
+    export_cameras(sys.argv[sys.argv.index("--") + 1])
@@ -0,0 +1,53 @@
+"""Create cameras in Blender from a Braid calibration.
+
+Convert the calibration with `convert-cal --output-format blender cal.xml
+cameras.json` and then run
+
+    blender --python import_braid_cameras.py -- cameras.json
+
+The render resolution and pixel aspect ratio are properties of the scene in
+Blender. They are set from the first camera and stored as custom properties of
+each camera object.
+"""
+
+import json
+import sys
+
+import bpy
+
+
+def import_cameras(filename):
+    with open(filename) as f:
+        cameras = json.load(f)["cameras"]
+
+    scene = bpy.context.scene
+    for i, c in enumerate(cameras):
+        cam = bpy.data.cameras.new(c["name"])
+        cam.lens_unit = "MILLIMETERS"
+        cam.lens = c["lens"]
+        cam.sensor_width = c["sensor_width"]
+        cam.sensor_height = c["sensor_height"]
+        cam.sensor_fit = c["sensor_fit"]
+        cam.shift_x = c["shift_x"]
+        cam.shift_y = c["shift_y"]
+
+        obj = bpy.data.objects.new(c["name"], cam)
+        obj.location = c["location"]
+        obj.rotation_mode = "QUATERNION"
+        obj.rotation_quaternion = c["rotation_quaternion"]
+        for key in ("resolution_x", "resolution_y", "pixel_aspect_x", "pixel_aspect_y"):
+            obj[key] = c[key]
+        scene.collection.objects.link(obj)
+
+        if i == 0:
+            scene.camera = obj
+            scene.render.resolution_x = c["resolution_x"]
+            scene.render.resolution_y = c["resolution_y"]
+            scene.render.resolution_percentage = 100
+            scene.render.pixel_aspect_x = c["pixel_aspect_x"]
+            scene.render.pixel_aspect_y = c["pixel_aspect_y"]
+    print(f"Imported {len(cameras)} cameras from {filename}")
+
+
+if __name__ == "__main__":
+    import_cameras(sys.argv[sys.argv.index("--") + 1])
@@ -16,12 +16,14 @@ use na::{allocator::Allocator, DefaultAllocator, RealField};
 use nalgebra as na;
 use num_traits::{One, Zero};
 
+use cam_geom::coordinate_system::{CameraFrame, WorldFrame};
 use opencv_ros_camera::UndistortedPixels;
 
-use crate::pymvg_support::PymvgCamera;
+use crate::fisheye::FisheyeDistortion;
+use crate::pymvg_support::{PymvgCamera, PymvgDistortionModel};
 use crate::{
-    DistortedPixel, Distortion, ExtrinsicParameters, MvgError, PointWorldFrame, Result,
-    RosOpenCvIntrinsics, UndistortedPixel,
+    DistortedPixel, Distortion, ExtrinsicParameters, MvgError, PointCameraFrame, PointWorldFrame,
+    Result, RosOpenCvIntrinsics, UndistortedPixel,
 };
 
 #[derive(Clone, PartialEq)]
@@ -51,7 +53,9 @@ use crate::{
 /// - `s` is a scaling factor
 ///
 /// Lens distortion is supported via the
-/// [`opencv-ros-camera`](https://docs.rs/opencv-ros-camera) crate.
+/// [`opencv-ros-camera`](https://docs.rs/opencv-ros-camera) crate. Wide-angle
+/// lenses can instead use the Kannala-Brandt fisheye model, see
+/// [`Camera::new_fisheye`].
 ///
 /// The parameters for the intrinsic matrix (focal length, principal point, and
 /// skew) in addition to the distortion parameters together comprise the
@@ -77,6 +81,7 @@ pub struct Camera<R: RealField> {
     pub(crate) width: usize,
     pub(crate) height: usize,
     pub(crate) inner: cam_geom::Camera<R, RosOpenCvIntrinsics<R>>,
+    pub(crate) fisheye: Option<FisheyeDistortion<R>>,
     pub(crate) cache: CameraCache<R>,
 }
 
@@ -126,6 +131,43 @@ impl<R: RealField + Copy> Camera<R> {
         Self::new_from_cam_geom(width, height, inner)
     }
 
+    /// Create a new camera with Kannala-Brandt fisheye lens distortion.
+    ///
+    /// The linear part of the camera model is given by `intrinsics`, which
+    /// must not have Brown-Conrady distortion or a rectification matrix. Lens
+    /// distortion is then given by `distortion`. See [`crate::fisheye`] for
+    /// the model.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use braid_mvg::{Camera, extrinsics, make_default_intrinsics};
+    /// use braid_mvg::fisheye::FisheyeDistortion;
+    ///
+    /// let extrinsics = extrinsics::make_default_extrinsics::<f64>();
+    /// let intrinsics = make_default_intrinsics::<f64>();
+    /// let distortion = FisheyeDistortion::new(-0.01, 0.02, -0.01, 0.002);
+    /// let camera = Camera::new_fisheye(640, 480, extrinsics, intrinsics, distortion)?;
+    /// # Ok::<(), braid_mvg::MvgError>(())
+    /// ```
+    pub fn new_fisheye(
+        width: usize,
+        height: usize,
+        extrinsics: ExtrinsicParameters<R>,
+        intrinsics: RosOpenCvIntrinsics<R>,
+        distortion: FisheyeDistortion<R>,
+    ) -> Result<Self> {
+        if !intrinsics.distortion.is_linear() {
+            return Err(MvgError::MixedDistortionModels);
+        }
+        if !intrinsics.rect.is_identity(na::convert(1.0e-7)) {
+            return Err(MvgError::RectificationMatrixNotSupported);
+        }
+        let mut cam = Self::new(width, height, extrinsics, intrinsics)?;
+        cam.fisheye = Some(distortion);
+        Ok(cam)
+    }
+
     /// Create a new camera from a cam-geom Camera instance.
     ///
     /// This constructor wraps an existing cam-geom Camera with additional
@@ -176,6 +218,7 @@ impl<R: RealField + Copy> Camera<R> {
             width,
             height,
             inner,
+            fisheye: None,
             cache,
         })
     }
@@ -261,7 +304,7 @@ impl<R: RealField + Copy> Camera<R> {
     /// convert, if possible, into a 3x4 matrix
     pub fn as_pmat(&self) -> Option<&OMatrix<R, U3, U4>> {
         let d = &self.intrinsics().distortion;
-        if d.is_linear() {
+        if d.is_linear() && self.fisheye.is_none() {
             Some(&self.cache.m)
         } else {
             None
@@ -379,12 +422,14 @@ impl<R: RealField + Copy> Camera<R> {
         let pmat = &self.cache.m;
         let aligned_pmat = pmat * mi;
 
-        Self::from_pmat_with_distortion(
+        let mut aligned = Self::from_pmat_with_distortion(
             self.width,
             self.height,
             &aligned_pmat,
             self.intrinsics().distortion.clone(),
-        )
+        )?;
+        aligned.fisheye = self.fisheye.clone();
+        Ok(aligned)
     }
 
     /// return a copy of this camera looking in the opposite direction
@@ -415,15 +460,29 @@ impl<R: RealField + Copy> Camera<R> {
         let mut d = intinsics2.distortion.clone();
         *d.tangential2_mut() = -d.tangential2();
 
-        Some(Camera::new(self.width(), self.height(), extrinsics2, intinsics2).unwrap())
+        let mut flipped =
+            Camera::new(self.width(), self.height(), extrinsics2, intinsics2).unwrap();
+        // The fisheye model is radially symmetric and thus unchanged by
+        // mirroring.
+        flipped.fisheye = self.fisheye.clone();
+        Some(flipped)
     }
 
     /// Get the camera's intrinsic parameters.
+    ///
+    /// For a fisheye camera, these are only the linear part of the camera
+    /// model, without distortion. See [`Camera::fisheye_distortion`].
     #[inline]
     pub fn intrinsics(&self) -> &RosOpenCvIntrinsics<R> {
         self.inner.intrinsics()
     }
 
+    /// Get the camera's Kannala-Brandt fisheye distortion, if any.
+    #[inline]
+    pub fn fisheye_distortion(&self) -> Option<&FisheyeDistortion<R>> {
+        self.fisheye.as_ref()
+    }
+
     /// Get the camera's extrinsic parameters.
     #[inline]
     pub fn extrinsics(&self) -> &ExtrinsicParameters<R> {
@@ -455,14 +514,21 @@ impl<R: RealField + Copy> Camera<R> {
     /// # Ok::<(), braid_mvg::MvgError>(())
     /// ```
     pub fn to_pymvg(&self, name: &str) -> PymvgCamera<R> {
-        let d = &self.intrinsics().distortion;
-        let dvec = Vector5::new(
-            d.radial1(),
-            d.radial2(),
-            d.tangential1(),
-            d.tangential2(),
-            d.radial3(),
-        );
+        let (dvec, distortion_model) = if let Some(fisheye) = &self.fisheye {
+            let [k1, k2, k3, k4] = fisheye.opencv_vec();
+            let dvec = Vector5::new(k1, k2, k3, k4, R::zero());
+            (dvec, Some(PymvgDistortionModel::Equidistant))
+        } else {
+            let d = &self.intrinsics().distortion;
+            let dvec = Vector5::new(
+                d.radial1(),
+                d.radial2(),
+                d.tangential1(),
+                d.tangential2(),
+                d.radial3(),
+            );
+            (dvec, None)
+        };
         PymvgCamera {
             name: name.to_string(),
             width: self.width,
@@ -470,6 +536,7 @@ impl<R: RealField + Copy> Camera<R> {
             P: self.intrinsics().p,
             K: self.intrinsics().k,
             D: dvec,
+            distortion_model,
             R: self.intrinsics().rect,
             Q: *self.extrinsics().rotation().matrix(),
             translation: *self.extrinsics().translation(),
@@ -481,9 +548,21 @@ impl<R: RealField + Copy> Camera<R> {
 
         let rquat = right_handed_rotation_quat_new(&cam.Q)?;
         let extrinsics = crate::extrinsics::from_rquat_translation(rquat, cam.translation);
-        let distortion = Distortion::from_opencv_vec(cam.D);
-        let intrinsics = RosOpenCvIntrinsics::from_components(cam.P, cam.K, distortion, cam.R)?;
-        let cam = Self::new(cam.width, cam.height, extrinsics, intrinsics)?;
+        let cam = match cam.distortion_model {
+            None | Some(PymvgDistortionModel::PlumbBob) => {
+                let distortion = Distortion::from_opencv_vec(cam.D);
+                let intrinsics =
+                    RosOpenCvIntrinsics::from_components(cam.P, cam.K, distortion, cam.R)?;
+                Self::new(cam.width, cam.height, extrinsics, intrinsics)?
+            }
+            Some(PymvgDistortionModel::Equidistant) => {
+                let d = &cam.D;
+                let fisheye = FisheyeDistortion::new(d[0], d[1], d[2], d[3]);
+                let intrinsics =
+                    RosOpenCvIntrinsics::from_components(cam.P, cam.K, Distortion::zero(), cam.R)?;
+                Self::new_fisheye(cam.width, cam.height, extrinsics, intrinsics, fisheye)?
+            }
+        };
         Ok((name, cam))
     }
 
@@ -542,13 +621,82 @@ impl<R: RealField + Copy> Camera<R> {
     /// # Ok::<(), braid_mvg::MvgError>(())
     /// ```
     pub fn project_3d_to_distorted_pixel(&self, pt3d: &PointWorldFrame<R>) -> DistortedPixel<R> {
+        if let Some(fisheye) = &self.fisheye {
+            // Project in the camera frame so that points at or beyond 90° from
+            // the optical axis, which have no undistorted pixel, are handled.
+            let pt_world: cam_geom::Points<WorldFrame, R, U1, _> = pt3d.into();
+            let pt_cam: PointCameraFrame<R> = self.extrinsics().world_to_camera(&pt_world).into();
+            let normalized = fisheye.project(&pt_cam.coords.coords);
+            return self.normalized_to_distorted(&normalized);
+        }
         let undistorted = self.project_3d_to_pixel(pt3d);
+        self.distort(&undistorted)
+    }
+
+    /// Apply lens distortion to an undistorted pixel.
+    pub fn distort(&self, undistorted: &UndistortedPixel<R>) -> DistortedPixel<R> {
+        if let Some(fisheye) = &self.fisheye {
+            let normalized = self.undistorted_to_normalized(undistorted);
+            return self.normalized_to_distorted(&fisheye.distort(&normalized));
+        }
         let ud = UndistortedPixels {
             data: OMatrix::<R, U1, U2>::new(undistorted.coords[0], undistorted.coords[1]),
         };
         self.intrinsics().distort(&ud).into()
     }
 
+    /// Remove lens distortion from a distorted pixel.
+    ///
+    /// For a fisheye camera, pixels viewing at or beyond 90° from the optical
+    /// axis have no undistorted pixel and `NaN` coordinates are returned.
+    pub fn undistort(&self, distorted: &DistortedPixel<R>) -> UndistortedPixel<R> {
+        if let Some(fisheye) = &self.fisheye {
+            let normalized = self.distorted_to_normalized(distorted);
+            let coords = match fisheye.undistort(&normalized) {
+                Some(normalized) => self.normalized_to_undistorted(&normalized),
+                None => Point2::new(R::nan(), R::nan()),
+            };
+            return UndistortedPixel { coords };
+        }
+        let d: cam_geom::Pixels<R, U1, _> = distorted.into();
+        let u: UndistortedPixels<R, U1, _> = self.intrinsics().undistort(&d);
+        u.into()
+    }
+
+    /// Convert a distorted pixel to normalized distorted image coordinates
+    /// using `K`.
+    fn distorted_to_normalized(&self, distorted: &DistortedPixel<R>) -> na::Vector2<R> {
+        let k = &self.intrinsics().k;
+        let y = (distorted.coords.y - k[(1, 2)]) / k[(1, 1)];
+        let x = (distorted.coords.x - k[(0, 2)] - k[(0, 1)] * y) / k[(0, 0)];
+        na::Vector2::new(x, y)
+    }
+
+    fn normalized_to_distorted(&self, normalized: &na::Vector2<R>) -> DistortedPixel<R> {
+        let k = &self.intrinsics().k;
+        let x = k[(0, 0)] * normalized.x + k[(0, 1)] * normalized.y + k[(0, 2)];
+        let y = k[(1, 1)] * normalized.y + k[(1, 2)];
+        DistortedPixel {
+            coords: Point2::new(x, y),
+        }
+    }
+
+    /// Convert an undistorted pixel to normalized image coordinates using
+    /// `P`.
+    fn undistorted_to_normalized(&self, undistorted: &UndistortedPixel<R>) -> na::Vector2<R> {
+        let p = &self.intrinsics().p;
+        let y = (undistorted.coords.y - p[(1, 2)]) / p[(1, 1)];
+        let x = (undistorted.coords.x - p[(0, 2)] - p[(0, 1)] * y) / p[(0, 0)];
+        na::Vector2::new(x, y)
+    }
+
+    fn normalized_to_undistorted(&self, normalized: &na::Vector2<R>) -> Point2<R> {
+        let p = &self.intrinsics().p;
+        let x = p[(0, 0)] * normalized.x + p[(0, 1)] * normalized.y + p[(0, 2)];
+        let y = p[(1, 1)] * normalized.y + p[(1, 2)];
+        Point2::new(x, y)
+    }
+
     /// Back-project a 2D undistorted pixel to a 3D point at a given distance.
     ///
     /// This method performs the inverse camera projection, taking a 2D pixel
@@ -624,6 +772,17 @@ impl<R: RealField + Copy> Camera<R> {
         dist: R,
     ) -> PointWorldFrame<R> {
         use cam_geom::IntrinsicParameters;
+        if let Some(fisheye) = &self.fisheye {
+            let normalized = self.distorted_to_normalized(pt2d);
+            let ray = fisheye
+                .unproject(&normalized)
+                .unwrap_or_else(|| na::Vector3::new(R::nan(), R::nan(), R::nan()));
+            let pt_cam = PointCameraFrame {
+                coords: Point3::from(ray * dist),
+            };
+            let pt_cam: cam_geom::Points<CameraFrame, R, U1, _> = (&pt_cam).into();
+            return self.extrinsics().camera_to_world(&pt_cam).into();
+        }
         let ray_cam = self.intrinsics().pixel_to_camera(&pt2d.into());
         let pt_cam = ray_cam.point_on_ray_at_distance(dist);
         self.extrinsics().camera_to_world(&pt_cam).into()
@@ -644,6 +803,7 @@ impl<R: RealField + Copy> std::fmt::Debug for Camera<R> {
             .field("width", &self.width)
             .field("height", &self.height)
             .field("inner", &self.inner)
+            .field("fisheye", &self.fisheye)
             .finish()
     }
 }
@@ -668,6 +828,11 @@ impl<R: RealField + serde::Serialize + Copy> serde::Serialize for Camera<R> {
         state.serialize_field("height", &self.height)?;
         state.serialize_field("extrinsics", &self.extrinsics())?;
         state.serialize_field("intrinsics", &self.intrinsics())?;
+        if let Some(fisheye) = &self.fisheye {
+            state.serialize_field("fisheye", fisheye)?;
+        } else {
+            state.skip_field("fisheye")?;
+        }
         state.end()
     }
 }
@@ -687,6 +852,7 @@ impl<'de, R: RealField + serde::Deserialize<'de> + Copy> serde::Deserialize<'de>
             Height,
             Extrinsics,
             Intrinsics,
+            Fisheye,
         }
 
         struct CameraVisitor<'de, R2: RealField + serde::Deserialize<'de>>(
@@ -718,7 +884,8 @@ impl<'de, R: RealField + serde::Deserialize<'de> + Copy> serde::Deserialize<'de>
                 let intrinsics = seq
                     .next_element()?
                     .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                Camera::new(width, height, extrinsics, intrinsics)
+                let fisheye = seq.next_element()?.flatten();
+                new_camera(width, height, extrinsics, intrinsics, fisheye)
                     .map_err(|e| de::Error::custom(format!("failed creating Camera: {e}")))
             }
 
@@ -730,6 +897,7 @@ impl<'de, R: RealField + serde::Deserialize<'de> + Copy> serde::Deserialize<'de>
                 let mut height = None;
                 let mut extrinsics = None;
                 let mut intrinsics = None;
+                let mut fisheye = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Width => {
@@ -756,6 +924,12 @@ impl<'de, R: RealField + serde::Deserialize<'de> + Copy> serde::Deserialize<'de>
                             }
                             intrinsics = Some(map.next_value()?);
                         }
+                        Field::Fisheye => {
+                            if fisheye.is_some() {
+                                return Err(de::Error::duplicate_field("fisheye"));
+                            }
+                            fisheye = Some(map.next_value()?);
+                        }
                     }
                 }
                 let width = width.ok_or_else(|| de::Error::missing_field("width"))?;
@@ -764,12 +938,27 @@ impl<'de, R: RealField + serde::Deserialize<'de> + Copy> serde::Deserialize<'de>
                     extrinsics.ok_or_else(|| de::Error::missing_field("extrinsics"))?;
                 let intrinsics =
                     intrinsics.ok_or_else(|| de::Error::missing_field("intrinsics"))?;
-                Camera::new(width, height, extrinsics, intrinsics)
+                new_camera(width, height, extrinsics, intrinsics, fisheye)
                     .map_err(|e| de::Error::custom(format!("failed creating Camera: {e}")))
             }
         }
 
-        const FIELDS: &[&str] = &["width", "height", "extrinsics", "intrinsics"];
+        fn new_camera<R3: RealField + Copy>(
+            width: usize,
+            height: usize,
+            extrinsics: ExtrinsicParameters<R3>,
+            intrinsics: RosOpenCvIntrinsics<R3>,
+            fisheye: Option<FisheyeDistortion<R3>>,
+        ) -> Result<Camera<R3>> {
+            match fisheye {
+                Some(fisheye) => {
+                    Camera::new_fisheye(width, height, extrinsics, intrinsics, fisheye)
+                }
+                None => Camera::new(width, height, extrinsics, intrinsics),
+            }
+        }
+
+        const FIELDS: &[&str] = &["width", "height", "extrinsics", "intrinsics", "fisheye"];
         deserializer.deserialize_struct("Camera", FIELDS, CameraVisitor(std::marker::PhantomData))
     }
 }
@@ -852,7 +1041,7 @@ fn my_quat_angle<R: RealField + Copy>(quat: &na::UnitQuaternion<R>) -> R {
 }
 
 /// convert a 3x3 matrix into a valid right-handed rotation
-fn right_handed_rotation_quat_new<R: RealField + Copy>(
+pub(crate) fn right_handed_rotation_quat_new<R: RealField + Copy>(
     orig: &Matrix3<R>,
 ) -> Result<UnitQuaternion<R>> {
     let r1 = *orig;
@@ -925,7 +1114,7 @@ pub fn rq_decomposition<R: RealField + Copy>(
 
 #[cfg(test)]
 mod tests {
-    use crate::{DistortedPixel, PointWorldFrame};
+    use crate::{DistortedPixel, PointCameraFrame, PointWorldFrame};
     use na::core::dimension::{U3, U4};
     use na::core::{OMatrix, Vector4};
     use na::geometry::{Point2, Point3};
@@ -1113,4 +1302,107 @@ mod tests {
         let actual: crate::Camera<f64> = serde_json::from_str(&buf).unwrap();
         assert!(expected == actual);
     }
+
+    fn get_fisheye_camera() -> crate::Camera<f64> {
+        let extrinsics = crate::ExtrinsicParameters::from_view(
+            &na::Vector3::new(1.0, 2.0, 3.0),
+            &na::Vector3::new(1.5, 2.0, 1.0),
+            &na::Unit::new_normalize(na::Vector3::new(0.0, 0.0, 1.0)),
+        );
+        let intrinsics = crate::RosOpenCvIntrinsics::from_params(300.0, 0.5, 310.0, 640.0, 512.0);
+        let distortion = crate::fisheye::FisheyeDistortion::new(-0.013, 0.021, -0.012, 0.002);
+        crate::Camera::new_fisheye(1280, 1024, extrinsics, intrinsics, distortion).unwrap()
+    }
+
+    #[test]
+    fn test_fisheye_projection_roundtrip() {
+        use crate::UndistortedPixel;
+
+        let cam = get_fisheye_camera();
+        assert!(cam.as_pmat().is_none());
+
+        // Up to 100° from the optical axis, i.e. a 200° field of view.
+        for i in 0..=20 {
+            let theta = (i as f64 * 5.0).to_radians();
+            for phi in [0.3f64, 1.9, -2.8] {
+                let dist = 2.5;
+                let pt_cam = PointCameraFrame {
+                    coords: Point3::new(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        theta.cos(),
+                    ) * dist,
+                };
+                let pt_cam: cam_geom::Points<_, f64, na::U1, _> = (&pt_cam).into();
+                let pt: PointWorldFrame<f64> = cam.extrinsics().camera_to_world(&pt_cam).into();
+                let distorted = cam.project_3d_to_distorted_pixel(&pt);
+                let pt2 = cam.project_distorted_pixel_to_3d_with_dist(&distorted, dist);
+                approx::assert_relative_eq!(pt.coords, pt2.coords, epsilon = 1e-8);
+
+                let undistorted = cam.undistort(&distorted);
+                if theta.cos() > 1e-3 {
+                    let expected: UndistortedPixel<f64> = cam.project_3d_to_pixel(&pt);
+                    approx::assert_relative_eq!(
+                        undistorted.coords,
+                        expected.coords,
+                        epsilon = 1e-6,
+                        max_relative = 1e-9
+                    );
+                    let distorted2 = cam.distort(&undistorted);
+                    approx::assert_relative_eq!(
+                        distorted.coords,
+                        distorted2.coords,
+                        epsilon = 1e-6
+                    );
+                } else {
+                    assert!(undistorted.coords.x.is_nan());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fisheye_pymvg_and_serde() {
+        let cam = get_fisheye_camera();
+
+        let pymvg = cam.to_pymvg("fisheye");
+        let buf = serde_json::to_string(&pymvg).unwrap();
+        assert!(buf.contains("equidistant"));
+        let pymvg2: crate::pymvg_support::PymvgCamera<f64> = serde_json::from_str(&buf).unwrap();
+        let (name, cam2) = crate::Camera::from_pymvg(&pymvg2).unwrap();
+        assert_eq!(name, "fisheye");
+        assert!(cam2.fisheye_distortion().is_some());
+        assert!(is_similar(&cam, &cam2));
+
+        let buf = serde_json::to_string(&cam).unwrap();
+        let cam3: crate::Camera<f64> = serde_json::from_str(&buf).unwrap();
+        assert!(cam == cam3);
+
+        // A Brown-Conrady camera does not write the new fields.
+        let buf = serde_json::to_string(&crate::Camera::<f64>::default()).unwrap();
+        assert!(!buf.contains("fisheye"));
+        let buf = serde_json::to_string(&crate::Camera::<f64>::default().to_pymvg("c")).unwrap();
+        assert!(!buf.contains("distortion_model"));
+    }
+
+    #[test]
+    fn test_fisheye_align() {
+        let cam = get_fisheye_camera();
+        let rot = *na::Rotation3::from_euler_angles(0.1, -0.2, 0.3).matrix();
+        let t = na::Vector3::new(0.1, 0.2, -0.3);
+        let s = 2.0;
+        let aligned = cam.align(s, rot, t).unwrap();
+        assert!(aligned.fisheye_distortion().is_some());
+        let pt = PointWorldFrame {
+            coords: Point3::new(1.3, 2.2, 1.0),
+        };
+        let pt_aligned = PointWorldFrame {
+            coords: Point3::from(rot * pt.coords.coords * s + t),
+        };
+        approx::assert_relative_eq!(
+            cam.project_3d_to_distorted_pixel(&pt).coords,
+            aligned.project_3d_to_distorted_pixel(&pt_aligned).coords,
+            epsilon = 1e-6
+        );
+    }
 }
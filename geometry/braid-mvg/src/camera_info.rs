@@ -0,0 +1,308 @@
+// Copyright 2016-2025 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Import and export of the ROS `camera_info` YAML format.
+//!
+//! This is the format of the intrinsic calibrations written by the ROS camera
+//! calibrator and by Strand Camera's checkerboard calibration, and read by
+//! [`opencv_ros_camera::from_ros_yaml`]. It stores the camera matrix `K`, the
+//! distortion coefficients `D`, the rectification matrix `R` and the
+//! projection matrix `P` with the same conventions as this crate. Distortion
+//! models `plumb_bob` and `equidistant` (Kannala-Brandt [`crate::fisheye`]
+//! distortion) are supported.
+//!
+//! The format has no extrinsic parameters. They are stored in two additional
+//! keys, which are ignored by ROS: `extrinsic_rotation`, the 3x3
+//! world-to-camera rotation matrix, and `extrinsic_translation`, the 3x1
+//! world-to-camera translation, such that a world point `X` is at `R X + t` in
+//! the camera frame. A [`MultiCameraSystem`] is stored as a directory with one
+//! file per camera, named after the camera.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use nalgebra::storage::Storage;
+use nalgebra::{Dim, Matrix, Matrix3, OMatrix, Point3, RealField, Vector5, U3, U4};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::right_handed_rotation_quat_new;
+use crate::fisheye::FisheyeDistortion;
+use crate::{
+    interchange_error, Camera, Distortion, MultiCameraSystem, Result, RosOpenCvIntrinsics,
+};
+
+const PLUMB_BOB: &str = "plumb_bob";
+const EQUIDISTANT: &str = "equidistant";
+
+/// A matrix in ROS YAML files, stored in row-major order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct YamlMatrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl YamlMatrix {
+    fn from_matrix<R, RN, CN, S>(m: &Matrix<R, RN, CN, S>) -> Self
+    where
+        R: RealField + Copy,
+        RN: Dim,
+        CN: Dim,
+        S: Storage<R, RN, CN>,
+    {
+        let data = m
+            .row_iter()
+            .flat_map(|row| row.iter().map(|x| f64_of(*x)).collect::<Vec<_>>())
+            .collect();
+        Self {
+            rows: m.nrows(),
+            cols: m.ncols(),
+            data,
+        }
+    }
+
+    /// Get the data, checking the shape.
+    fn data(&self, name: &str, rows: usize, cols: usize) -> Result<&[f64]> {
+        if self.rows != rows || self.cols != cols || self.data.len() != rows * cols {
+            return Err(interchange_error(format!(
+                "expected {rows}x{cols} values in {name}"
+            )));
+        }
+        Ok(&self.data)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CameraInfo {
+    image_width: usize,
+    image_height: usize,
+    camera_name: String,
+    camera_matrix: YamlMatrix,
+    distortion_model: String,
+    distortion_coefficients: YamlMatrix,
+    rectification_matrix: YamlMatrix,
+    projection_matrix: YamlMatrix,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extrinsic_rotation: Option<YamlMatrix>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extrinsic_translation: Option<YamlMatrix>,
+}
+
+impl<R: RealField + Copy> Camera<R> {
+    /// Write the camera as ROS `camera_info` YAML with extrinsic parameters.
+    pub fn to_camera_info_yaml<W: Write>(&self, name: &str, writer: W) -> Result<()> {
+        let intrinsics = self.intrinsics();
+        let (distortion_model, d) = match self.fisheye_distortion() {
+            Some(fisheye) => (EQUIDISTANT, fisheye.opencv_vec().to_vec()),
+            None => {
+                let d = &intrinsics.distortion;
+                let d = vec![
+                    d.radial1(),
+                    d.radial2(),
+                    d.tangential1(),
+                    d.tangential2(),
+                    d.radial3(),
+                ];
+                (PLUMB_BOB, d)
+            }
+        };
+        let pose = self.extrinsics().pose();
+        let info = CameraInfo {
+            image_width: self.width(),
+            image_height: self.height(),
+            camera_name: name.to_string(),
+            camera_matrix: YamlMatrix::from_matrix(&intrinsics.k),
+            distortion_model: distortion_model.to_string(),
+            distortion_coefficients: YamlMatrix {
+                rows: 1,
+                cols: d.len(),
+                data: d.into_iter().map(f64_of).collect(),
+            },
+            rectification_matrix: YamlMatrix::from_matrix(&intrinsics.rect),
+            projection_matrix: YamlMatrix::from_matrix(&intrinsics.p),
+            extrinsic_rotation: Some(YamlMatrix::from_matrix(
+                pose.rotation.to_rotation_matrix().matrix(),
+            )),
+            extrinsic_translation: Some(YamlMatrix::from_matrix(&pose.translation.vector)),
+        };
+        serde_yaml::to_writer(writer, &info)?;
+        Ok(())
+    }
+
+    /// Read a camera and its name from ROS `camera_info` YAML with extrinsic
+    /// parameters.
+    pub fn from_camera_info_yaml<Rd: Read>(reader: Rd) -> Result<(String, Self)> {
+        let info: CameraInfo = serde_yaml::from_reader(reader)?;
+        let name = info.camera_name;
+
+        let k = Matrix3::from_row_slice(&to_r(info.camera_matrix.data("camera_matrix", 3, 3)?));
+        let rect = Matrix3::from_row_slice(&to_r(info.rectification_matrix.data(
+            "rectification_matrix",
+            3,
+            3,
+        )?));
+        let p = OMatrix::<R, U3, U4>::from_row_slice(&to_r(info.projection_matrix.data(
+            "projection_matrix",
+            3,
+            4,
+        )?));
+
+        let (rotation, translation) = match (&info.extrinsic_rotation, &info.extrinsic_translation)
+        {
+            (Some(rotation), Some(translation)) => (rotation, translation),
+            _ => {
+                return Err(interchange_error(format!(
+                    "camera \"{name}\" has no extrinsic parameters"
+                )))
+            }
+        };
+        let rotation = Matrix3::from_row_slice(&to_r(rotation.data("extrinsic_rotation", 3, 3)?));
+        let rquat = right_handed_rotation_quat_new(&rotation)?;
+        let t = to_r(translation.data("extrinsic_translation", 3, 1)?);
+        let extrinsics =
+            crate::extrinsics::from_rquat_translation(rquat, Point3::new(t[0], t[1], t[2]));
+
+        let d = to_r(&info.distortion_coefficients.data);
+        let cam = match info.distortion_model.as_str() {
+            PLUMB_BOB => {
+                if d.len() > 5 {
+                    return Err(interchange_error(format!(
+                        "expected at most 5 distortion coefficients for camera \"{name}\""
+                    )));
+                }
+                let mut dvec = Vector5::zeros();
+                dvec.rows_mut(0, d.len()).copy_from_slice(&d);
+                let distortion = Distortion::from_opencv_vec(dvec);
+                let intrinsics = RosOpenCvIntrinsics::from_components(p, k, distortion, rect)?;
+                Camera::new(info.image_width, info.image_height, extrinsics, intrinsics)?
+            }
+            EQUIDISTANT => {
+                if d.len() != 4 {
+                    return Err(interchange_error(format!(
+                        "expected 4 distortion coefficients for camera \"{name}\""
+                    )));
+                }
+                let distortion = FisheyeDistortion::new(d[0], d[1], d[2], d[3]);
+                let intrinsics =
+                    RosOpenCvIntrinsics::from_components(p, k, Distortion::zero(), rect)?;
+                Camera::new_fisheye(
+                    info.image_width,
+                    info.image_height,
+                    extrinsics,
+                    intrinsics,
+                    distortion,
+                )?
+            }
+            model => {
+                return Err(interchange_error(format!(
+                    "unsupported distortion model \"{model}\""
+                )))
+            }
+        };
+        Ok((name, cam))
+    }
+}
+
+impl<R> MultiCameraSystem<R>
+where
+    R: RealField + Default + Serialize + Copy,
+{
+    /// Export the camera system as ROS `camera_info` YAML files, one per
+    /// camera, in directory `dir`.
+    ///
+    /// The directory is created if needed.
+    pub fn to_camera_info_dir<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        for (name, cam) in self.cams_by_name().iter() {
+            let fd = std::fs::File::create(dir.join(format!("{name}.yaml")))?;
+            cam.to_camera_info_yaml(name, fd)?;
+        }
+        Ok(())
+    }
+
+    /// Import a camera system from the ROS `camera_info` YAML files in
+    /// directory `dir`.
+    pub fn from_camera_info_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut cams = BTreeMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(std::ffi::OsStr::new("yaml")) {
+                continue;
+            }
+            let (name, cam) = Camera::from_camera_info_yaml(std::fs::File::open(&path)?)?;
+            if cams.insert(name.clone(), cam).is_some() {
+                return Err(interchange_error(format!(
+                    "more than one file with camera \"{name}\""
+                )));
+            }
+        }
+        Ok(Self::new(cams))
+    }
+}
+
+fn to_r<R: RealField>(data: &[f64]) -> Vec<R> {
+    data.iter().map(|x| nalgebra::convert(*x)).collect()
+}
+
+#[inline]
+fn f64_of<R: RealField>(x: R) -> f64 {
+    nalgebra::try_convert(x).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_projections, get_test_system};
+
+    #[test]
+    fn test_camera_info_roundtrip() {
+        let system = get_test_system();
+        let mut cams = BTreeMap::new();
+        for (name, cam) in system.cams_by_name() {
+            let mut buf = Vec::new();
+            cam.to_camera_info_yaml(name, &mut buf).unwrap();
+            let (name2, cam2) = Camera::<f64>::from_camera_info_yaml(&buf[..]).unwrap();
+            assert_eq!(name, &name2);
+            cams.insert(name2, cam2);
+        }
+        let loaded = MultiCameraSystem::new(cams);
+        assert_same_projections(&system, &loaded);
+        for (name, cam) in system.cams_by_name() {
+            let cam2 = loaded.cam_by_name(name).unwrap();
+            assert_eq!(cam.intrinsics(), cam2.intrinsics());
+            assert_eq!(cam.fisheye_distortion(), cam2.fisheye_distortion());
+        }
+    }
+
+    #[test]
+    fn test_camera_info_ros_compatible() {
+        // A file from the ROS camera calibrator with added extrinsics.
+        let buf = format!(
+            "{}extrinsic_rotation:\n  rows: 3\n  cols: 3\n  data: [0, 1, 0, -1, 0, 0, 0, 0, 1]\n\
+            extrinsic_translation:\n  rows: 3\n  cols: 1\n  data: [0.1, 0.2, 0.3]\n",
+            include_str!("../tests/ros/camera.yaml")
+        );
+        let (name, cam) = Camera::<f64>::from_camera_info_yaml(buf.as_bytes()).unwrap();
+        let expected: opencv_ros_camera::NamedIntrinsicParameters<f64> =
+            opencv_ros_camera::from_ros_yaml(include_str!("../tests/ros/camera.yaml").as_bytes())
+                .unwrap();
+        assert_eq!(name, expected.name);
+        assert_eq!(cam.width(), expected.width);
+        assert_eq!(cam.intrinsics(), &expected.intrinsics);
+        approx::assert_relative_eq!(
+            cam.extrinsics().pose().translation.vector,
+            nalgebra::Vector3::new(0.1, 0.2, 0.3),
+            epsilon = 1e-12
+        );
+
+        // Without extrinsics, the camera cannot be used.
+        let buf = include_str!("../tests/ros/camera.yaml");
+        assert!(Camera::<f64>::from_camera_info_yaml(buf.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+// Copyright 2016-2025 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Import and export of the [COLMAP](https://colmap.github.io) text model
+//! format.
+//!
+//! A COLMAP model is a directory with the files `cameras.txt` (intrinsics),
+//! `images.txt` (extrinsics) and `points3D.txt` (the reconstructed points). A
+//! [`MultiCameraSystem`] is exported as one COLMAP camera and one image per
+//! camera. The image is named after the camera, so COLMAP expects the images of
+//! the cameras as, for example, `cam1.png`. On import, a known image file
+//! extension is removed from the image name to give the camera name.
+//!
+//! COLMAP uses the same camera frame as this crate (X right, Y down, Z forward)
+//! and also stores the world-to-camera rotation and translation. The only
+//! difference in conventions is the image coordinate system: in COLMAP, the
+//! center of the upper left pixel is at (0.5, 0.5), whereas it is at (0, 0)
+//! here, so the principal point is shifted by half a pixel.
+//!
+//! COLMAP describes lens distortion relative to the camera matrix `K` and has
+//! no rectification matrix or separate projection matrix `P`. Only cameras for
+//! which [`Camera::is_standard`] is true can be exported; others can first be
+//! approximated with [`Camera::approximate_standard`]. On import, `P` is set to
+//! `K`. Cameras are exported with the model `PINHOLE`, `OPENCV`, `FULL_OPENCV`
+//! or `OPENCV_FISHEYE`. The models `SIMPLE_PINHOLE`, `SIMPLE_RADIAL` and
+//! `RADIAL` can also be imported.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use nalgebra::{Point3, Quaternion, RealField, UnitQuaternion, Vector5};
+use serde::Serialize;
+
+use crate::fisheye::FisheyeDistortion;
+use crate::{
+    interchange_error, Camera, Distortion, MultiCameraSystem, Result, RosOpenCvIntrinsics,
+};
+
+/// File extensions removed from COLMAP image names to give camera names.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "tif", "tiff", "bmp"];
+
+impl<R> MultiCameraSystem<R>
+where
+    R: RealField + Default + Serialize + Copy,
+{
+    /// Export the camera system as a COLMAP text model in directory `dir`.
+    ///
+    /// The directory is created if needed. An empty `points3D.txt` is written
+    /// so that COLMAP can load the model.
+    pub fn to_colmap_dir<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let cameras_txt = std::fs::File::create(dir.join("cameras.txt"))?;
+        let images_txt = std::fs::File::create(dir.join("images.txt"))?;
+        self.to_colmap_text(cameras_txt, images_txt)?;
+        let mut points3d_txt = std::fs::File::create(dir.join("points3D.txt"))?;
+        writeln!(
+            points3d_txt,
+            "# 3D point list with one line of data per point:\n\
+            #   POINT3D_ID, X, Y, Z, R, G, B, ERROR, TRACK[] as (IMAGE_ID, POINT2D_IDX)\n\
+            # Number of points: 0, mean track length: 0"
+        )?;
+        Ok(())
+    }
+
+    /// Import a camera system from a COLMAP text model in directory `dir`.
+    pub fn from_colmap_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let cameras_txt = std::fs::File::open(dir.join("cameras.txt"))?;
+        let images_txt = std::fs::File::open(dir.join("images.txt"))?;
+        Self::from_colmap_text(cameras_txt, images_txt)
+    }
+
+    /// Write the contents of COLMAP's `cameras.txt` and `images.txt`.
+    pub fn to_colmap_text<W1: Write, W2: Write>(
+        &self,
+        mut cameras_txt: W1,
+        mut images_txt: W2,
+    ) -> Result<()> {
+        let n = self.cams_by_name().len();
+        writeln!(
+            cameras_txt,
+            "# Camera list with one line of data per camera:\n\
+            #   CAMERA_ID, MODEL, WIDTH, HEIGHT, PARAMS[]\n\
+            # Number of cameras: {n}"
+        )?;
+        writeln!(
+            images_txt,
+            "# Image list with two lines of data per image:\n\
+            #   IMAGE_ID, QW, QX, QY, QZ, TX, TY, TZ, CAMERA_ID, NAME\n\
+            #   POINTS2D[] as (X, Y, POINT3D_ID)\n\
+            # Number of images: {n}, mean observations per image: 0"
+        )?;
+        for (i, (name, cam)) in self.cams_by_name().iter().enumerate() {
+            // COLMAP camera and image IDs start at 1.
+            let id = i + 1;
+            if name.chars().any(char::is_whitespace) {
+                return Err(interchange_error(format!(
+                    "camera name \"{name}\" contains whitespace"
+                )));
+            }
+            let (model, params) = colmap_camera(name, cam)?;
+            let params: Vec<String> = params.iter().map(|x| format!("{x}")).collect();
+            writeln!(
+                cameras_txt,
+                "{id} {model} {} {} {}",
+                cam.width(),
+                cam.height(),
+                params.join(" ")
+            )?;
+
+            // The world-to-camera transformation.
+            let pose = cam.extrinsics().pose();
+            let q = pose.rotation.coords.map(f64_of);
+            let t = pose.translation.vector.map(f64_of);
+            writeln!(
+                images_txt,
+                "{id} {} {} {} {} {} {} {} {id} {name}\n",
+                q.w, q.x, q.y, q.z, t.x, t.y, t.z
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Read the contents of COLMAP's `cameras.txt` and `images.txt`.
+    pub fn from_colmap_text<R1: Read, R2: Read>(cameras_txt: R1, images_txt: R2) -> Result<Self> {
+        let mut intrinsics_by_id = BTreeMap::new();
+        for line in BufReader::new(cameras_txt).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 {
+                return Err(interchange_error(format!("invalid camera line: {line}")));
+            }
+            let params = fields[4..]
+                .iter()
+                .map(|x| parse_f64(x))
+                .collect::<Result<Vec<f64>>>()?;
+            let intrinsics = ColmapIntrinsics {
+                model: fields[1].to_string(),
+                width: parse(fields[2])?,
+                height: parse(fields[3])?,
+                params,
+            };
+            intrinsics_by_id.insert(fields[0].to_string(), intrinsics);
+        }
+
+        let mut cams = BTreeMap::new();
+        let mut lines = BufReader::new(images_txt).lines();
+        while let Some(line) = lines.next() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Each image line is followed by a line with the 2D points, which
+            // may be empty.
+            lines.next().transpose()?;
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 10 {
+                return Err(interchange_error(format!("invalid image line: {line}")));
+            }
+            let v = fields[1..8]
+                .iter()
+                .map(|x| parse_f64(x))
+                .collect::<Result<Vec<f64>>>()?;
+            let rquat = UnitQuaternion::from_quaternion(Quaternion::new(
+                na_f(v[0]),
+                na_f(v[1]),
+                na_f(v[2]),
+                na_f(v[3]),
+            ));
+            let translation = Point3::new(na_f(v[4]), na_f(v[5]), na_f(v[6]));
+            let extrinsics = crate::extrinsics::from_rquat_translation(rquat, translation);
+
+            let intrinsics = intrinsics_by_id.get(fields[8]).ok_or_else(|| {
+                interchange_error(format!("unknown camera ID {} of image", fields[8]))
+            })?;
+            let name = camera_name(fields[9]);
+            let cam = intrinsics.to_camera(extrinsics)?;
+            if cams.insert(name.clone(), cam).is_some() {
+                return Err(interchange_error(format!(
+                    "more than one image of camera \"{name}\""
+                )));
+            }
+        }
+        Ok(Self::new(cams))
+    }
+}
+
+/// Intrinsic parameters of a COLMAP camera.
+struct ColmapIntrinsics {
+    model: String,
+    width: usize,
+    height: usize,
+    params: Vec<f64>,
+}
+
+impl ColmapIntrinsics {
+    fn to_camera<R: RealField + Copy>(
+        &self,
+        extrinsics: cam_geom::ExtrinsicParameters<R>,
+    ) -> Result<Camera<R>> {
+        let expected_len = match self.model.as_str() {
+            "SIMPLE_PINHOLE" => 3,
+            "SIMPLE_RADIAL" | "PINHOLE" => 4,
+            "RADIAL" => 5,
+            "OPENCV" | "OPENCV_FISHEYE" => 8,
+            "FULL_OPENCV" => 12,
+            model => {
+                return Err(interchange_error(format!(
+                    "unsupported COLMAP camera model {model}"
+                )))
+            }
+        };
+        let p = &self.params;
+        if p.len() != expected_len {
+            return Err(interchange_error(format!(
+                "expected {expected_len} parameters for COLMAP camera model {}, found {}",
+                self.model,
+                p.len()
+            )));
+        }
+        // (fx, fy, cx, cy, remaining parameters)
+        let (fx, fy, cx, cy, d) = match self.model.as_str() {
+            "SIMPLE_PINHOLE" | "SIMPLE_RADIAL" | "RADIAL" => (p[0], p[0], p[1], p[2], &p[3..]),
+            _ => (p[0], p[1], p[2], p[3], &p[4..]),
+        };
+        let (cx, cy) = (cx - 0.5, cy - 0.5);
+
+        if self.model == "OPENCV_FISHEYE" {
+            let intrinsics =
+                RosOpenCvIntrinsics::from_params(na_f(fx), R::zero(), na_f(fy), na_f(cx), na_f(cy));
+            let distortion = FisheyeDistortion::new(na_f(d[0]), na_f(d[1]), na_f(d[2]), na_f(d[3]));
+            return Camera::new_fisheye(
+                self.width,
+                self.height,
+                extrinsics,
+                intrinsics,
+                distortion,
+            );
+        }
+
+        // OpenCV order: k1, k2, p1, p2, k3
+        let dvec: [f64; 5] = match self.model.as_str() {
+            "SIMPLE_RADIAL" => [d[0], 0.0, 0.0, 0.0, 0.0],
+            "RADIAL" => [d[0], d[1], 0.0, 0.0, 0.0],
+            "OPENCV" => [d[0], d[1], d[2], d[3], 0.0],
+            "FULL_OPENCV" => {
+                if d[5..].iter().any(|x| *x != 0.0) {
+                    return Err(interchange_error(
+                        "rational distortion (k4, k5, k6) is not supported".into(),
+                    ));
+                }
+                [d[0], d[1], d[2], d[3], d[4]]
+            }
+            _ => [0.0; 5],
+        };
+        let distortion = Distortion::from_opencv_vec(Vector5::from(dvec).map(na_f));
+        let intrinsics = RosOpenCvIntrinsics::from_params_with_distortion(
+            na_f(fx),
+            R::zero(),
+            na_f(fy),
+            na_f(cx),
+            na_f(cy),
+            distortion,
+        );
+        Camera::new(self.width, self.height, extrinsics, intrinsics)
+    }
+}
+
+/// Get the COLMAP camera model and its parameters.
+fn colmap_camera<R: RealField + Copy>(
+    name: &str,
+    cam: &Camera<R>,
+) -> Result<(&'static str, Vec<f64>)> {
+    if !cam.is_standard() {
+        return Err(interchange_error(format!(
+            "camera \"{name}\" has skew or rectification, which COLMAP does not support \
+            (see `Camera::approximate_standard`)"
+        )));
+    }
+    let k = cam.intrinsics().k.map(f64_of);
+    let mut params = vec![k[(0, 0)], k[(1, 1)], k[(0, 2)] + 0.5, k[(1, 2)] + 0.5];
+
+    if let Some(fisheye) = cam.fisheye_distortion() {
+        params.extend(fisheye.opencv_vec().map(f64_of));
+        return Ok(("OPENCV_FISHEYE", params));
+    }
+
+    let d = &intrinsics.distortion;
+    let dvec = [
+        d.radial1(),
+        d.radial2(),
+        d.tangential1(),
+        d.tangential2(),
+        d.radial3(),
+    ]
+    .map(f64_of);
+    if d.is_linear() {
+        Ok(("PINHOLE", params))
+    } else if dvec[4] == 0.0 {
+        params.extend(&dvec[..4]);
+        Ok(("OPENCV", params))
+    } else {
+        params.extend(dvec);
+        params.extend([0.0; 3]);
+        Ok(("FULL_OPENCV", params))
+    }
+}
+
+/// Remove a known image file extension from a COLMAP image name.
+fn camera_name(image_name: &str) -> String {
+    if let Some((stem, extension)) = image_name.rsplit_once('.') {
+        if IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()) {
+            return stem.to_string();
+        }
+    }
+    image_name.to_string()
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T> {
+    s.parse()
+        .map_err(|_| interchange_error(format!("cannot parse \"{s}\"")))
+}
+
+fn parse_f64(s: &str) -> Result<f64> {
+    parse(s)
+}
+
+#[inline]
+fn f64_of<R: RealField>(x: R) -> f64 {
+    nalgebra::try_convert(x).unwrap()
+}
+
+#[inline]
+fn na_f<R: RealField>(x: f64) -> R {
+    nalgebra::convert(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_projections, get_test_system};
+
+    #[test]
+    fn test_colmap_roundtrip() {
+        let system = get_test_system();
+        let mut cameras_txt = Vec::new();
+        let mut images_txt = Vec::new();
+        system
+            .to_colmap_text(&mut cameras_txt, &mut images_txt)
+            .unwrap();
+
+        let cameras = String::from_utf8(cameras_txt.clone()).unwrap();
+        assert!(cameras.contains("\n1 PINHOLE 640 480 1000 1010 331 250.75\n"));
+        assert!(cameras.contains(" FULL_OPENCV 1280 1024 "));
+        assert!(cameras.contains(" OPENCV_FISHEYE 1024 1024 "));
+
+        let loaded =
+            MultiCameraSystem::<f64>::from_colmap_text(&cameras_txt[..], &images_txt[..]).unwrap();
+        assert_same_projections(&system, &loaded);
+    }
+
+    #[test]
+    fn test_colmap_import() {
+        // A model as written by COLMAP, with a camera shared by two images.
+        let cameras_txt = "# Camera list with one line of data per camera:\n\
+            #   CAMERA_ID, MODEL, WIDTH, HEIGHT, PARAMS[]\n\
+            # Number of cameras: 1\n\
+            1 SIMPLE_RADIAL 640 480 500 320.5 240.5 -0.1\n";
+        let images_txt = "# Image list with two lines of data per image:\n\
+            #   IMAGE_ID, QW, QX, QY, QZ, TX, TY, TZ, CAMERA_ID, NAME\n\
+            #   POINTS2D[] as (X, Y, POINT3D_ID)\n\
+            # Number of images: 2, mean observations per image: 1\n\
+            1 1 0 0 0 0 0 2 1 left.jpg\n\
+            100.5 200.5 -1 310 220 7\n\
+            2 0 0 0 1 0.5 0 2 1 right.JPG\n\
+            \n";
+        let system = MultiCameraSystem::<f64>::from_colmap_text(
+            cameras_txt.as_bytes(),
+            images_txt.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            system.cams_by_name().keys().collect::<Vec<_>>(),
+            vec!["left", "right"]
+        );
+        let left = system.cam_by_name("left").unwrap();
+        approx::assert_relative_eq!(left.intrinsics().k[(0, 2)], 320.0);
+        approx::assert_relative_eq!(left.intrinsics().distortion.radial1(), -0.1);
+        approx::assert_relative_eq!(
+            left.extrinsics().camcenter(),
+            &Point3::new(0.0, 0.0, -2.0),
+            epsilon = 1e-12
+        );
+        // Rotated by 180° about z.
+        let right = system.cam_by_name("right").unwrap();
+        approx::assert_relative_eq!(
+            right.extrinsics().camcenter(),
+            &Point3::new(0.5, 0.0, -2.0),
+            epsilon = 1e-12
+        );
+    }
+}
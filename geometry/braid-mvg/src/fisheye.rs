@@ -0,0 +1,219 @@
+// Copyright 2016-2025 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Kannala-Brandt fisheye lens distortion.
+//!
+//! The Brown-Conrady model of [`opencv_ros_camera::Distortion`] is a
+//! polynomial in the radius of the undistorted, pinhole image point and breaks
+//! down for lenses with fields of view beyond about 100°. The Kannala-Brandt
+//! model (also called the "equidistant" model, as in OpenCV's `cv::fisheye`
+//! module) is instead a polynomial in the angle `θ` between the incoming ray
+//! and the optical axis:
+//!
+//! ```text
+//! θd = θ (1 + k1 θ² + k2 θ⁴ + k3 θ⁶ + k4 θ⁸)
+//! ```
+//!
+//! A point in the camera frame `(x, y, z)` with `r = sqrt(x² + y²)` and `θ =
+//! atan2(r, z)` is projected to the normalized image point `(θd/r) (x, y)`,
+//! which is then mapped to pixels by the intrinsic matrix `K`.
+//!
+//! Rays at or beyond 90° from the optical axis have no undistorted (pinhole)
+//! image point. They can be projected to and back-projected from distorted
+//! pixels but [`FisheyeDistortion::undistort`] returns `None` for them.
+
+use nalgebra::{RealField, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+const MAX_NEWTON_ITERATIONS: usize = 20;
+
+/// Kannala-Brandt fisheye distortion coefficients.
+///
+/// The coefficients are ordered as in OpenCV's `cv::fisheye` module.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FisheyeDistortion<R: RealField> {
+    /// Coefficient of `θ³`.
+    pub k1: R,
+    /// Coefficient of `θ⁵`.
+    pub k2: R,
+    /// Coefficient of `θ⁷`.
+    pub k3: R,
+    /// Coefficient of `θ⁹`.
+    pub k4: R,
+}
+
+impl<R: RealField + Copy> FisheyeDistortion<R> {
+    /// Create distortion from the coefficients `k1` through `k4`.
+    pub fn new(k1: R, k2: R, k3: R, k4: R) -> Self {
+        Self { k1, k2, k3, k4 }
+    }
+
+    /// Distortion with all coefficients zero, the ideal equidistant lens.
+    pub fn zero() -> Self {
+        Self::new(R::zero(), R::zero(), R::zero(), R::zero())
+    }
+
+    /// Create distortion from coefficients in OpenCV order.
+    pub fn from_opencv_vec(d: [R; 4]) -> Self {
+        Self::new(d[0], d[1], d[2], d[3])
+    }
+
+    /// Get the coefficients in OpenCV order.
+    pub fn opencv_vec(&self) -> [R; 4] {
+        [self.k1, self.k2, self.k3, self.k4]
+    }
+
+    /// Evaluate `θd(θ)` and its derivative.
+    fn theta_d(&self, theta: R) -> (R, R) {
+        let one = R::one();
+        let t2 = theta * theta;
+        let poly = one + t2 * (self.k1 + t2 * (self.k2 + t2 * (self.k3 + t2 * self.k4)));
+        let dpoly = t2
+            * (na_f::<R>(3.0) * self.k1
+                + t2 * (na_f::<R>(5.0) * self.k2
+                    + t2 * (na_f::<R>(7.0) * self.k3 + t2 * na_f::<R>(9.0) * self.k4)));
+        (theta * poly, one + dpoly)
+    }
+
+    /// Invert `θd(θ)` by Newton's method.
+    ///
+    /// Returns `None` if there is no solution in `[0, π]` at which the model
+    /// is monotonic.
+    fn theta(&self, theta_d: R) -> Option<R> {
+        let pi = R::pi();
+        let mut theta = if theta_d < pi { theta_d } else { pi };
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let (f, df) = self.theta_d(theta);
+            if df <= R::zero() {
+                return None;
+            }
+            let step = (f - theta_d) / df;
+            theta -= step;
+            if theta < R::zero() {
+                theta = R::zero();
+            }
+            if theta > pi {
+                theta = pi;
+            }
+            if step.abs() <= R::default_epsilon() * na_f::<R>(4.0) * (R::one() + theta) {
+                break;
+            }
+        }
+        let (f, df) = self.theta_d(theta);
+        let tol = R::default_epsilon().sqrt() * (R::one() + theta_d);
+        if df <= R::zero() || (f - theta_d).abs() > tol {
+            return None;
+        }
+        Some(theta)
+    }
+
+    /// Distort a point in the camera frame to a normalized image point.
+    ///
+    /// Unlike [Self::distort], this is defined for all directions except
+    /// straight backwards.
+    pub fn project(&self, pt: &Vector3<R>) -> Vector2<R> {
+        let r = (pt.x * pt.x + pt.y * pt.y).sqrt();
+        let theta = r.atan2(pt.z);
+        let (theta_d, _) = self.theta_d(theta);
+        if r <= R::default_epsilon() * pt.z.abs() {
+            // On the optical axis, `θd/r` tends to `1/z`.
+            return Vector2::new(pt.x, pt.y) / pt.z;
+        }
+        Vector2::new(pt.x, pt.y) * (theta_d / r)
+    }
+
+    /// Back-project a normalized distorted image point to a unit ray in the
+    /// camera frame.
+    pub fn unproject(&self, distorted: &Vector2<R>) -> Option<Vector3<R>> {
+        let theta_d = distorted.norm();
+        if theta_d <= R::default_epsilon() {
+            return Some(Vector3::new(distorted.x, distorted.y, R::one()).normalize());
+        }
+        let theta = self.theta(theta_d)?;
+        let xy = distorted * (theta.sin() / theta_d);
+        Some(Vector3::new(xy.x, xy.y, theta.cos()))
+    }
+
+    /// Distort a normalized undistorted (pinhole) image point.
+    pub fn distort(&self, undistorted: &Vector2<R>) -> Vector2<R> {
+        self.project(&Vector3::new(undistorted.x, undistorted.y, R::one()))
+    }
+
+    /// Undistort a normalized distorted image point.
+    ///
+    /// Returns `None` if the point corresponds to a ray at or beyond 90° from
+    /// the optical axis, which has no pinhole image point.
+    pub fn undistort(&self, distorted: &Vector2<R>) -> Option<Vector2<R>> {
+        let ray = self.unproject(distorted)?;
+        if ray.z <= R::default_epsilon().sqrt() {
+            return None;
+        }
+        Some(Vector2::new(ray.x / ray.z, ray.y / ray.z))
+    }
+}
+
+impl<R: RealField + Copy> Default for FisheyeDistortion<R> {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+#[inline]
+fn na_f<R: RealField>(x: f64) -> R {
+    nalgebra::convert(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_distortions() -> Vec<FisheyeDistortion<f64>> {
+        vec![
+            FisheyeDistortion::zero(),
+            FisheyeDistortion::new(-0.013, 0.021, -0.012, 0.002),
+            FisheyeDistortion::new(0.08, -0.02, 0.004, -0.0003),
+        ]
+    }
+
+    #[test]
+    fn test_fisheye_roundtrip() {
+        for d in test_distortions() {
+            // Up to 110° from the optical axis, i.e. a 220° field of view.
+            for i in 0..=22 {
+                let theta = (i as f64 * 5.0).to_radians();
+                for phi in [0.0f64, 0.7, 2.0, -2.5] {
+                    let ray = Vector3::new(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        theta.cos(),
+                    );
+                    let distorted = d.project(&(ray * 3.0));
+                    let ray2 = d.unproject(&distorted).unwrap();
+                    approx::assert_relative_eq!(ray, ray2, epsilon = 1e-10);
+
+                    if ray.z > 1e-3 {
+                        let undistorted = Vector2::new(ray.x / ray.z, ray.y / ray.z);
+                        approx::assert_relative_eq!(
+                            d.distort(&undistorted),
+                            distorted,
+                            epsilon = 1e-10
+                        );
+                        let undistorted2 = d.undistort(&distorted).unwrap();
+                        approx::assert_relative_eq!(
+                            undistorted,
+                            undistorted2,
+                            epsilon = 1e-9,
+                            max_relative = 1e-9
+                        );
+                    } else {
+                        assert!(d.undistort(&distorted).is_none());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -19,6 +19,9 @@
 //!   [`cam-geom`](https://docs.rs/cam-geom)
 //! - Lens distortion correction using OpenCV-compatible models based on
 //!   [`opencv-ros-camera`](https://docs.rs/opencv-ros-camera)
+//! - Kannala-Brandt [`fisheye`] lens distortion for wide-angle lenses
+//! - Import and export of calibrations in the formats of [`colmap`], ROS
+//!   [`camera_info`] and [`blender`]
 //! - Multi-camera system management and calibration
 //! - 3D point triangulation from multiple camera views
 //! - Point alignment algorithms (Kabsch-Umeyama, robust Arun)
@@ -180,6 +183,11 @@ pub enum MvgError {
     /// Unsupported camera or parameter type.
     #[error("unsupported type")]
     UnsupportedType,
+    /// Both Brown-Conrady and fisheye lens distortion were given.
+    ///
+    /// A fisheye camera's linear intrinsics must not have distortion.
+    #[error("both Brown-Conrady and fisheye distortion specified")]
+    MixedDistortionModels,
     /// Rerun.io does not support this camera intrinsics model.
     ///
     /// Only available when the `rerun-io` feature is enabled.
@@ -226,6 +234,13 @@ pub enum MvgError {
         /// The SVG error message.
         error: &'static str,
     },
+    /// Invalid or unsupported data when converting a calibration from or to
+    /// another program's format, such as COLMAP or Blender.
+    #[error("calibration interchange error: {}", error)]
+    InterchangeError {
+        /// A description of the problem.
+        error: String,
+    },
     /// Pseudo-inverse calculation error.
     #[error("PinvError: {}", error)]
     PinvError {
@@ -251,6 +266,10 @@ pub enum MvgError {
 /// Convenience type alias for results in multi-view geometry operations.
 pub type Result<M> = std::result::Result<M, MvgError>;
 
+fn interchange_error(error: String) -> MvgError {
+    MvgError::InterchangeError { error }
+}
+
 pub mod pymvg_support;
 
 /// Camera intrinsic parameter utilities and operations.
@@ -266,6 +285,12 @@ pub mod intrinsics;
 /// [`cam_geom::ExtrinsicParameters`].`
 pub mod extrinsics;
 
+pub mod fisheye;
+
+pub mod blender;
+pub mod camera_info;
+pub mod colmap;
+
 /// Point cloud alignment algorithms and utilities.
 ///
 /// This module implements various algorithms for aligning point clouds and
@@ -284,9 +309,18 @@ pub mod align_points;
 #[cfg_attr(docsrs, doc(cfg(feature = "rerun-io")))]
 pub mod rerun_io;
 
+/// Cameras and camera systems for tests.
+///
+/// **Note**: This module is only available when the `test-util` feature is enabled.
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod test_util;
+
 mod camera;
 pub use crate::camera::{rq_decomposition, Camera};
 
+mod approximate;
+
 mod multi_cam_system;
 pub use crate::multi_cam_system::MultiCameraSystem;
 
@@ -779,6 +813,73 @@ mod tests {
         result
     }
 
+    /// A system with a pinhole, a Brown-Conrady and a fisheye camera viewing
+    /// the origin.
+    pub(crate) fn get_test_system() -> MultiCameraSystem<f64> {
+        use crate::fisheye::FisheyeDistortion;
+        use na::{Vector3, Vector5};
+
+        let up = na::Unit::new_normalize(Vector3::new(0.0, 0.0, 1.0));
+        let lookat = Vector3::zeros();
+        let mut cams = std::collections::BTreeMap::new();
+
+        let extrinsics =
+            ExtrinsicParameters::from_view(&Vector3::new(1.0, -2.0, 1.5), &lookat, &up);
+        let intrinsics = RosOpenCvIntrinsics::from_params(1000.0, 0.0, 1010.0, 330.5, 250.25);
+        let cam = Camera::new(640, 480, extrinsics, intrinsics).unwrap();
+        cams.insert("cam1-pinhole".to_string(), cam);
+
+        let extrinsics =
+            ExtrinsicParameters::from_view(&Vector3::new(-2.0, -1.0, 2.0), &lookat, &up);
+        let distortion = Distortion::from_opencv_vec(Vector5::new(-0.2, 0.05, 0.001, -0.002, 0.01));
+        let intrinsics = RosOpenCvIntrinsics::from_params_with_distortion(
+            1500.0, 0.0, 1500.0, 641.0, 509.0, distortion,
+        );
+        let cam = Camera::new(1280, 1024, extrinsics, intrinsics).unwrap();
+        cams.insert("cam2-brown".to_string(), cam);
+
+        let extrinsics = ExtrinsicParameters::from_view(&Vector3::new(0.5, 0.5, 3.0), &lookat, &up);
+        let intrinsics = RosOpenCvIntrinsics::from_params(300.0, 0.0, 300.0, 512.0, 511.0);
+        let distortion = FisheyeDistortion::new(-0.013, 0.021, -0.012, 0.002);
+        let cam = Camera::new_fisheye(1024, 1024, extrinsics, intrinsics, distortion).unwrap();
+        cams.insert("cam3-fisheye".to_string(), cam);
+
+        MultiCameraSystem::new(cams)
+    }
+
+    /// Check that both systems have the same cameras, which project points
+    /// near the origin to the same distorted pixels.
+    pub(crate) fn assert_same_projections(a: &MultiCameraSystem<f64>, b: &MultiCameraSystem<f64>) {
+        assert_eq!(
+            a.cams_by_name().keys().collect::<Vec<_>>(),
+            b.cams_by_name().keys().collect::<Vec<_>>()
+        );
+        for (name, cam_a) in a.cams_by_name() {
+            let cam_b = b.cam_by_name(name).unwrap();
+            assert_eq!(cam_a.width(), cam_b.width());
+            assert_eq!(cam_a.height(), cam_b.height());
+            approx::assert_relative_eq!(
+                cam_a.extrinsics().camcenter(),
+                cam_b.extrinsics().camcenter(),
+                epsilon = 1e-9
+            );
+            for x in [-0.3, 0.0, 0.2] {
+                for y in [-0.2, 0.0, 0.3] {
+                    for z in [-0.1, 0.0, 0.4] {
+                        let pt = PointWorldFrame {
+                            coords: Point3::new(x, y, z),
+                        };
+                        approx::assert_relative_eq!(
+                            cam_a.project_3d_to_distorted_pixel(&pt).coords,
+                            cam_b.project_3d_to_distorted_pixel(&pt).coords,
+                            epsilon = 1e-6
+                        );
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn get_test_cameras() -> Vec<(String, Camera<f64>)> {
         let mut result = Vec::new();
 
@@ -55,6 +55,10 @@ pub struct PymvgCamera<R: RealField> {
     #[serde(with = "array_of_arrays")]
     pub(crate) K: Matrix3<R>,
     pub(crate) D: Vector5<R>,
+    /// Interpretation of `D`. Absent in files written by PyMVG, which only
+    /// supports [`PymvgDistortionModel::PlumbBob`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) distortion_model: Option<PymvgDistortionModel>,
     #[serde(with = "array_of_arrays")]
     pub(crate) R: Matrix3<R>,
     #[serde(with = "array_of_arrays")]
@@ -62,6 +66,16 @@ pub struct PymvgCamera<R: RealField> {
     pub(crate) translation: Point3<R>,
 }
 
+/// Lens distortion model of a [`PymvgCamera`], named as in ROS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PymvgDistortionModel {
+    /// Brown-Conrady distortion with `D` holding `k1, k2, p1, p2, k3`.
+    PlumbBob,
+    /// Kannala-Brandt fisheye distortion with `D` holding `k1, k2, k3, k4, 0`.
+    Equidistant,
+}
+
 mod array_of_arrays {
     use super::*;
 
@@ -272,6 +272,9 @@ impl<R: RealField + Copy> crate::Camera<R> {
     /// The conversion will not succeed if the camera cannot be represented
     /// exactly in re_types.
     pub fn rr_pinhole_archetype(&self) -> Result<re_types::archetypes::Pinhole, MvgError> {
+        if self.fisheye.is_some() {
+            return Err(MvgError::RerunUnsupportedIntrinsics);
+        }
         let pinhole_projection = pinhole_projection_component(self.intrinsics())?;
         let resolution = self.rr_resolution_component();
         Ok(re_types::archetypes::Pinhole::new(pinhole_projection).with_resolution(resolution))
@@ -0,0 +1,55 @@
+// Copyright 2016-2025 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+
+use nalgebra::Vector3;
+use opencv_ros_camera::RosOpenCvIntrinsics;
+
+use crate::{make_default_intrinsics, Camera, MultiCameraSystem};
+
+/// Create a camera at `camcenter` looking at `lookat`, with the z axis up.
+pub fn camera_looking_at(
+    width: usize,
+    height: usize,
+    camcenter: Vector3<f64>,
+    lookat: Vector3<f64>,
+    intrinsics: RosOpenCvIntrinsics<f64>,
+) -> Camera<f64> {
+    let extrinsics =
+        cam_geom::ExtrinsicParameters::from_view(&camcenter, &lookat, &Vector3::z_axis());
+    Camera::new(width, height, extrinsics, intrinsics).unwrap()
+}
+
+/// Create a 640x480 camera at `camcenter` looking at the origin, with the z
+/// axis up and the intrinsics of [make_default_intrinsics].
+pub fn camera_looking_at_origin(camcenter: Vector3<f64>) -> Camera<f64> {
+    camera_looking_at(
+        640,
+        480,
+        camcenter,
+        Vector3::zeros(),
+        make_default_intrinsics(),
+    )
+}
+
+/// Create a system of cameras named `cam1`, `cam2`, ... on a horizontal circle
+/// with `radius` at `height`, all looking at the origin.
+///
+/// The first camera is on the x axis. The cameras are a quarter turn apart,
+/// or evenly spaced if there are more than four.
+pub fn camera_ring(num_cams: usize, radius: f64, height: f64) -> MultiCameraSystem<f64> {
+    let step = std::f64::consts::TAU / num_cams.max(4) as f64;
+    let cams = (0..num_cams)
+        .map(|i| {
+            let angle = i as f64 * step;
+            let camcenter = Vector3::new(radius * angle.cos(), radius * angle.sin(), height);
+            (format!("cam{}", i + 1), camera_looking_at_origin(camcenter))
+        })
+        .collect::<BTreeMap<_, _>>();
+    MultiCameraSystem::new(cams)
+}
@@ -0,0 +1,305 @@
+//! Estimation of per-camera time offsets from tracking data.
+//!
+//! Braid assumes that the images of all cameras with the same synchronized
+//! frame number were exposed simultaneously. Cameras with rolling shutters or
+//! misconfigured trigger delays expose their images systematically earlier or
+//! later. A camera exposing `dt` seconds after the frame time sees an object
+//! moving with velocity `v` at `x + v * dt` rather than at its position `x`,
+//! so its observations of fast objects have a reprojection error along the
+//! direction of motion.
+//!
+//! [estimate_time_offsets] finds, for each camera, the time offset which
+//! minimizes the reprojection distance of its observations of fast-moving
+//! objects given their tracked 3D positions and velocities. The observations
+//! are collected from a `.braidz` file by [observations_from_braidz]. The
+//! offsets can be stored in the calibration with
+//! [flydra_mvg::FlydraMultiCameraSystem::with_time_offsets], after which they
+//! are used in tracking.
+//!
+//! Each camera's observations contributed to the trajectories, which were
+//! tracked with the time offsets of the calibration at that time. The
+//! estimates are thus biased towards those offsets, more so with few cameras.
+//! Tracking again with the estimated offsets, for example with
+//! `braid-offline`, and estimating again reduces this bias.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek};
+
+use braid_mvg::PointWorldFrame;
+use braidz_parser::BraidzArchive;
+use flydra_mvg::FlydraMultiCameraSystem;
+use nalgebra::{Point2, Point3, Vector3};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Braidz(#[from] braidz_parser::Error),
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+    #[error("no kalman estimates found")]
+    NoTrajectories,
+    #[error("no observations associated with trajectories found")]
+    NoObservations,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An observation of a tracked object by a camera.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub cam_name: String,
+    /// Estimated position of the object at the frame time.
+    pub position: Vector3<f64>,
+    /// Estimated velocity of the object, in units per second.
+    pub velocity: Vector3<f64>,
+    /// Distorted pixel coordinates of the observation.
+    pub distorted: [f64; 2],
+}
+
+/// Options for [estimate_time_offsets].
+#[derive(Debug, Clone)]
+pub struct TimeOffsetOptions {
+    /// Only observations of objects with at least this speed, in units per
+    /// second, are used.
+    pub min_speed: f64,
+    /// The time offsets are searched between plus and minus this many frames.
+    pub max_offset_frames: f64,
+    /// Reprojection distances, in pixels, are limited to this value so that
+    /// wrong data associations have little influence.
+    pub max_reproj_dist: f64,
+    /// Time offsets are only estimated for cameras with at least this many
+    /// observations.
+    pub min_observations: usize,
+}
+
+impl Default for TimeOffsetOptions {
+    fn default() -> Self {
+        Self {
+            min_speed: 0.3,
+            max_offset_frames: 1.0,
+            max_reproj_dist: 10.0,
+            min_observations: 50,
+        }
+    }
+}
+
+/// The estimated time offset of a camera.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraTimeOffset {
+    pub cam_name: String,
+    /// Number of observations of fast objects used.
+    pub num_observations: usize,
+    /// Time, in seconds, by which the camera exposes its images after the
+    /// synchronized frame time. See [flydra_mvg::MultiCamera::time_offset].
+    pub time_offset: f64,
+    /// The time offset in units of frames.
+    pub time_offset_frames: f64,
+    /// Median reprojection distance, in pixels, without time offset.
+    pub median_reproj_dist_zero: f64,
+    /// Median reprojection distance, in pixels, with the estimated time
+    /// offset.
+    pub median_reproj_dist: f64,
+    /// Whether the best time offset is at the limit of the searched range, in
+    /// which case the actual offset may be larger.
+    pub at_limit: bool,
+}
+
+/// Collect the observations of tracked objects from a `.braidz` file.
+///
+/// Each row of the `data_association` table gives an observation at the
+/// position and velocity of the object in the `kalman_estimates` table.
+pub fn observations_from_braidz<R: Read + Seek>(
+    archive: &mut BraidzArchive<R>,
+) -> Result<Vec<Observation>> {
+    let kests = archive
+        .kalman_estimates_table
+        .as_ref()
+        .ok_or(Error::NoTrajectories)?;
+    let mut states = HashMap::with_capacity(kests.len());
+    for row in kests.iter() {
+        let position = Vector3::new(row.x, row.y, row.z);
+        let velocity = Vector3::new(row.xvel, row.yvel, row.zvel);
+        states.insert((row.obj_id, row.frame.0), (position, velocity));
+    }
+    let camn2camid = archive.cam_info.camn2camid.clone();
+
+    let mut obj_ids = HashMap::new();
+    for row in archive.iter_data_association()? {
+        let row = row?;
+        obj_ids.insert((row.frame.0, row.cam_num, row.pt_idx), row.obj_id);
+    }
+
+    let mut result = Vec::new();
+    for row in archive.iter_data2d_distorted()? {
+        let row = row?;
+        if row.frame < 0 || row.x.is_nan() || row.y.is_nan() {
+            continue;
+        }
+        let frame = row.frame as u64;
+        let Some(obj_id) = obj_ids.get(&(frame, row.camn, row.frame_pt_idx)) else {
+            continue;
+        };
+        let (Some((position, velocity)), Some(cam_name)) =
+            (states.get(&(*obj_id, frame)), camn2camid.get(&row.camn))
+        else {
+            continue;
+        };
+        result.push(Observation {
+            cam_name: cam_name.clone(),
+            position: *position,
+            velocity: *velocity,
+            distorted: [row.x, row.y],
+        });
+    }
+
+    if result.is_empty() {
+        return Err(Error::NoObservations);
+    }
+    Ok(result)
+}
+
+/// Number of time offsets evaluated before refining the best one.
+const NUM_GRID_STEPS: usize = 40;
+/// Number of golden-section iterations refining the best time offset.
+const NUM_REFINE_ITERATIONS: usize = 40;
+
+/// Estimate the time offset of each camera with enough observations.
+///
+/// `fps` is the frame rate of the recording. The time offsets in `recon` are
+/// not used: the returned offsets are relative to the synchronized frame time.
+/// Cameras with fewer than [TimeOffsetOptions::min_observations] observations
+/// of fast objects, or which are not in the calibration, are not included in
+/// the result. The returned offsets are named as in the calibration.
+pub fn estimate_time_offsets(
+    recon: &FlydraMultiCameraSystem<f64>,
+    observations: &[Observation],
+    fps: f64,
+    opts: &TimeOffsetOptions,
+) -> Result<Vec<CameraTimeOffset>> {
+    let mut by_cam: BTreeMap<&str, Vec<&Observation>> = BTreeMap::new();
+    for obs in observations.iter() {
+        if obs.velocity.norm() >= opts.min_speed {
+            by_cam.entry(obs.cam_name.as_str()).or_default().push(obs);
+        }
+    }
+
+    let max_offset = opts.max_offset_frames / fps;
+    let mut result = Vec::new();
+    for (cam_name, observations) in by_cam.into_iter() {
+        if observations.len() < opts.min_observations {
+            tracing::warn!(
+                "Only {} observations of fast objects by camera \"{cam_name}\", not estimating \
+                its time offset.",
+                observations.len()
+            );
+            continue;
+        }
+        let Some(cam) = calibration_camera(recon, cam_name) else {
+            tracing::warn!(
+                "Camera \"{cam_name}\" is not in the calibration, not estimating its time offset."
+            );
+            continue;
+        };
+
+        let reproj_dists = |time_offset: f64| -> Vec<f64> {
+            observations
+                .iter()
+                .map(|obs| {
+                    let pt = PointWorldFrame {
+                        coords: Point3::from(obs.position + obs.velocity * time_offset),
+                    };
+                    let projected = cam.project_3d_to_distorted_pixel(&pt).coords;
+                    nalgebra::distance(&projected, &Point2::from(obs.distorted))
+                })
+                .collect()
+        };
+        let cost = |time_offset: f64| -> f64 {
+            reproj_dists(time_offset)
+                .iter()
+                .map(|dist| dist.min(opts.max_reproj_dist).powi(2))
+                .sum()
+        };
+
+        // Search a grid for the best time offset, then refine around it.
+        let step = 2.0 * max_offset / NUM_GRID_STEPS as f64;
+        let best_step = (0..=NUM_GRID_STEPS)
+            .map(|i| (i, cost(-max_offset + i as f64 * step)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0;
+        let at_limit = best_step == 0 || best_step == NUM_GRID_STEPS;
+        let best = -max_offset + best_step as f64 * step;
+        let time_offset = golden_section_min(
+            cost,
+            (best - step).max(-max_offset),
+            (best + step).min(max_offset),
+        );
+
+        result.push(CameraTimeOffset {
+            cam_name: cam.name().to_string(),
+            num_observations: observations.len(),
+            time_offset,
+            time_offset_frames: time_offset * fps,
+            median_reproj_dist_zero: median(reproj_dists(0.0)),
+            median_reproj_dist: median(reproj_dists(time_offset)),
+            at_limit,
+        });
+    }
+    Ok(result)
+}
+
+/// Find the camera named `cam_name` in the calibration.
+///
+/// Older recordings use ROS-compatible camera names, e.g. "Basler_1234" for
+/// the camera "Basler-1234" in the calibration, so these names also match.
+fn calibration_camera(
+    recon: &FlydraMultiCameraSystem<f64>,
+    cam_name: &str,
+) -> Option<flydra_mvg::MultiCamera<f64>> {
+    if let Some(cam) = recon.cam_by_name(cam_name) {
+        return Some(cam);
+    }
+    let ros_name = as_ros_camid(cam_name);
+    let calib_name = recon
+        .cam_names()
+        .find(|name| as_ros_camid(name) == ros_name)?;
+    recon.cam_by_name(calib_name)
+}
+
+fn as_ros_camid(raw_name: &str) -> String {
+    raw_name.replace(['-', ' ', '/'], "_")
+}
+
+/// Find the minimum of `f` between `a` and `b`.
+fn golden_section_min<F: Fn(f64) -> f64>(f: F, mut a: f64, mut b: f64) -> f64 {
+    let inv_phi = (5.0f64.sqrt() - 1.0) / 2.0;
+    let mut c = b - inv_phi * (b - a);
+    let mut d = a + inv_phi * (b - a);
+    let (mut fc, mut fd) = (f(c), f(d));
+    for _ in 0..NUM_REFINE_ITERATIONS {
+        if fc < fd {
+            b = d;
+            d = c;
+            fd = fc;
+            c = b - inv_phi * (b - a);
+            fc = f(c);
+        } else {
+            a = c;
+            c = d;
+            fc = fd;
+            d = a + inv_phi * (b - a);
+            fd = f(d);
+        }
+    }
+    (a + b) / 2.0
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    }
+}
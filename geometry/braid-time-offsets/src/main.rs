@@ -0,0 +1,115 @@
+use camino::Utf8PathBuf;
+use clap::Parser;
+use eyre::{self, Context, Result};
+
+use braid_time_offsets::{estimate_time_offsets, observations_from_braidz, TimeOffsetOptions};
+use flydra_mvg::FlydraMultiCameraSystem;
+
+/// Estimate the time offset of each camera from the trajectories of fast
+/// objects in a `.braidz` file.
+///
+/// A camera with a positive time offset exposes its images after the
+/// synchronized frame time, for example because of a trigger delay or a
+/// rolling shutter.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Input braidz file with tracked objects.
+    braidz: Utf8PathBuf,
+
+    /// Calibration XML file. If not given, the calibration saved in the
+    /// braidz file is used.
+    #[arg(long)]
+    calibration: Option<Utf8PathBuf>,
+
+    /// Save the calibration with the estimated time offsets to this XML file.
+    #[arg(long)]
+    output_calibration: Option<Utf8PathBuf>,
+
+    /// Minimum speed of objects, in calibration units per second.
+    #[arg(long, default_value_t = 0.3)]
+    min_speed: f64,
+
+    /// Maximum time offset, in frames.
+    #[arg(long, default_value_t = 1.0)]
+    max_offset_frames: f64,
+
+    /// Reprojection distances, in pixels, are limited to this value.
+    #[arg(long, default_value_t = 10.0)]
+    max_reproj_dist: f64,
+
+    /// Minimum number of observations of fast objects by a camera.
+    #[arg(long, default_value_t = 50)]
+    min_observations: usize,
+}
+
+fn main() -> Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    env_tracing_logger::init();
+    let cli = Cli::parse();
+
+    let mut archive = braidz_parser::braidz_parse_path(&cli.braidz)
+        .with_context(|| format!("while reading {}", cli.braidz))?;
+    let recon = match &cli.calibration {
+        Some(calibration) => FlydraMultiCameraSystem::<f64>::from_path(calibration)
+            .with_context(|| format!("while reading calibration {calibration}"))?,
+        None => {
+            let ci = archive
+                .calibration_info
+                .clone()
+                .ok_or_else(|| eyre::eyre!("no calibration in {}", cli.braidz))?;
+            FlydraMultiCameraSystem::from_system(ci.cameras, ci.water)
+                .with_refractive_interfaces(ci.refractive_interfaces)
+                .with_time_offsets(ci.time_offsets)
+        }
+    };
+    let fps = archive.expected_fps;
+
+    let observations = observations_from_braidz(&mut archive)?;
+    let opts = TimeOffsetOptions {
+        min_speed: cli.min_speed,
+        max_offset_frames: cli.max_offset_frames,
+        max_reproj_dist: cli.max_reproj_dist,
+        min_observations: cli.min_observations,
+    };
+    let offsets = estimate_time_offsets(&recon, &observations, fps, &opts)?;
+
+    println!(
+        "{:<24} {:>12} {:>12} {:>12} {:>22}",
+        "camera", "observations", "offset (ms)", "offset (fr)", "median reproj. (px)"
+    );
+    for offset in offsets.iter() {
+        println!(
+            "{:<24} {:>12} {:>12.3} {:>12.3} {:>10.3} -> {:>8.3}",
+            offset.cam_name,
+            offset.num_observations,
+            offset.time_offset * 1000.0,
+            offset.time_offset_frames,
+            offset.median_reproj_dist_zero,
+            offset.median_reproj_dist,
+        );
+        if offset.at_limit {
+            tracing::warn!(
+                "Time offset of camera \"{}\" at the limit of the searched range. Consider \
+                increasing --max-offset-frames.",
+                offset.cam_name
+            );
+        }
+    }
+
+    if let Some(output_calibration) = &cli.output_calibration {
+        // Cameras without an estimate keep their previous time offset.
+        let mut time_offsets = recon.time_offsets().clone();
+        for offset in offsets.iter() {
+            time_offsets.insert(offset.cam_name.clone(), offset.time_offset);
+        }
+        let recon = recon.with_time_offsets(time_offsets);
+        let fd = std::fs::File::create_new(output_calibration)
+            .with_context(|| format!("while creating {output_calibration}"))?;
+        recon.to_flydra_xml(fd)?;
+        println!("Saved calibration with time offsets: {output_calibration}");
+    }
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use braid_mvg::{test_util::camera_ring, MultiCameraSystem, PointWorldFrame};
+use braid_time_offsets::{
+    estimate_time_offsets, observations_from_braidz, Observation, TimeOffsetOptions,
+};
+use flydra_mvg::FlydraMultiCameraSystem;
+use nalgebra::{Point3, Vector3};
+
+const FPS: f64 = 100.0;
+
+fn make_system() -> FlydraMultiCameraSystem<f64> {
+    FlydraMultiCameraSystem::from_system(camera_ring(3, 2.0, 1.0), None)
+}
+
+/// Observations of an object circling at 2 units per second, with `cam2`
+/// exposing 4 ms late.
+fn observations(recon: &FlydraMultiCameraSystem<f64>) -> Vec<Observation> {
+    let time_offsets = BTreeMap::from([("cam2", 0.004)]);
+    let (radius, omega) = (0.2, 10.0);
+    let mut result = Vec::new();
+    for frame in 0..300 {
+        let t = frame as f64 / FPS;
+        let state_at = |t: f64| {
+            let position = Vector3::new(
+                radius * (omega * t).cos(),
+                radius * (omega * t).sin(),
+                0.05 * (omega * t / 3.0).sin(),
+            );
+            let velocity = Vector3::new(
+                -radius * omega * (omega * t).sin(),
+                radius * omega * (omega * t).cos(),
+                0.05 * omega / 3.0 * (omega * t / 3.0).cos(),
+            );
+            (position, velocity)
+        };
+        let (position, velocity) = state_at(t);
+        for cam in recon.cameras() {
+            let time_offset = time_offsets.get(cam.name()).copied().unwrap_or(0.0);
+            let (exposed, _) = state_at(t + time_offset);
+            let px = cam.project_3d_to_distorted_pixel(&PointWorldFrame {
+                coords: Point3::from(exposed),
+            });
+            result.push(Observation {
+                cam_name: cam.name().to_string(),
+                position,
+                velocity,
+                distorted: [px.coords.x, px.coords.y],
+            });
+        }
+    }
+    result
+}
+
+#[test]
+fn test_estimate_time_offsets() {
+    let recon = make_system();
+    let observations = observations(&recon);
+    let offsets =
+        estimate_time_offsets(&recon, &observations, FPS, &TimeOffsetOptions::default()).unwrap();
+    assert_eq!(offsets.len(), 3);
+    for offset in offsets.iter() {
+        let expected = if offset.cam_name == "cam2" {
+            0.004
+        } else {
+            0.0
+        };
+        assert!(
+            (offset.time_offset - expected).abs() < 2e-4,
+            "{}: {} != {expected}",
+            offset.cam_name,
+            offset.time_offset
+        );
+        assert!((offset.time_offset_frames - expected * FPS).abs() < 2e-2);
+        assert!(!offset.at_limit);
+        assert!(offset.median_reproj_dist < 0.2);
+        if offset.cam_name == "cam2" {
+            assert!(offset.median_reproj_dist_zero > 1.0);
+        }
+    }
+
+    // With the estimated offsets, the observations of cam2 are predicted.
+    let time_offsets = offsets
+        .iter()
+        .map(|offset| (offset.cam_name.clone(), offset.time_offset))
+        .collect();
+    let recon = recon.with_time_offsets(time_offsets);
+    let cam2 = recon.cam_by_name("cam2").unwrap();
+    assert!((cam2.time_offset() - 0.004).abs() < 2e-4);
+
+    // Slow objects and cameras with few observations are ignored.
+    let opts = TimeOffsetOptions {
+        min_speed: 10.0,
+        ..Default::default()
+    };
+    let offsets = estimate_time_offsets(&recon, &observations, FPS, &opts).unwrap();
+    assert!(offsets.is_empty());
+}
+
+#[test]
+fn test_camera_names() {
+    // The calibration uses the original camera names, the observations the
+    // ROS-compatible names of older recordings.
+    let recon = make_system();
+    let renamed = recon
+        .system()
+        .cams_by_name()
+        .iter()
+        .map(|(name, cam)| (name.replace("cam", "Basler-"), cam.clone()))
+        .collect();
+    let renamed = FlydraMultiCameraSystem::from_system(MultiCameraSystem::new(renamed), None);
+    let mut observations = observations(&recon);
+    for obs in observations.iter_mut() {
+        obs.cam_name = obs.cam_name.replace("cam", "Basler_");
+    }
+    // Cameras which are not in the calibration are skipped.
+    let unknown = observations
+        .iter()
+        .filter(|obs| obs.cam_name == "Basler_1")
+        .map(|obs| Observation {
+            cam_name: "Basler_9".to_string(),
+            ..obs.clone()
+        })
+        .collect::<Vec<_>>();
+    observations.extend(unknown);
+
+    let offsets =
+        estimate_time_offsets(&renamed, &observations, FPS, &TimeOffsetOptions::default()).unwrap();
+    let names: Vec<_> = offsets.iter().map(|o| o.cam_name.as_str()).collect();
+    assert_eq!(names, ["Basler-1", "Basler-2", "Basler-3"]);
+    assert!((offsets[1].time_offset - 0.004).abs() < 2e-4);
+}
+
+#[test]
+fn test_observations_from_braidz() {
+    let dir = tempfile::tempdir().unwrap();
+    let braidz = dir.path().join("fixture.braidz");
+    std::fs::create_dir(&braidz).unwrap();
+
+    let mut textlog = csv::Writer::from_path(braidz.join(braid_types::TEXTLOG_CSV_FNAME)).unwrap();
+    let tracking_params = serde_json::json!({
+        "tracking_params": braid_types::default_tracking_params_full_3d(),
+    });
+    for message in [
+        "MainBrain running at 100 fps, ()".to_string(),
+        tracking_params.to_string(),
+    ] {
+        textlog
+            .serialize(braid_types::TextlogRow {
+                mainbrain_timestamp: 1.7e9,
+                cam_id: "mainbrain".to_string(),
+                host_timestamp: 1.7e9,
+                message,
+            })
+            .unwrap();
+    }
+    textlog.flush().unwrap();
+
+    let tables = [
+        (
+            braid_types::CAM_INFO_CSV_FNAME,
+            "camn,cam_id\n0,cam1\n1,cam2\n",
+        ),
+        (
+            braid_types::KALMAN_ESTIMATES_CSV_FNAME,
+            "obj_id,frame,timestamp,x,y,z,xvel,yvel,zvel,\
+            P00,P01,P02,P11,P12,P22,P33,P44,P55\n\
+            1,10,,0.1,0.2,0.3,1.0,0.0,0.0,0,0,0,0,0,0,0,0,0\n\
+            1,11,,0.11,0.2,0.3,1.0,0.5,0.0,0,0,0,0,0,0,0,0,0\n\
+            2,11,,-0.5,0.0,0.2,0.0,0.0,-1.0,0,0,0,0,0,0,0,0,0\n",
+        ),
+        (
+            braid_types::DATA_ASSOCIATE_CSV_FNAME,
+            "obj_id,frame,cam_num,pt_idx\n\
+            1,10,0,0\n\
+            1,10,1,0\n\
+            1,11,1,0\n\
+            1,11,0,0\n\
+            2,11,1,1\n\
+            1,12,0,0\n",
+        ),
+        (
+            braid_types::DATA2D_DISTORTED_CSV_FNAME,
+            "camn,frame,timestamp,cam_received_timestamp,device_timestamp,block_id,\
+            x,y,area,slope,eccentricity,frame_pt_idx,cur_val,mean_val,sumsqf_val\n\
+            0,10,,1.7e9,,,100.0,200.0,1,0,0,0,0,0,0\n\
+            1,10,,1.7e9,,,110.0,210.0,1,0,0,0,0,0,0\n\
+            1,10,,1.7e9,,,300.0,300.0,1,0,0,1,0,0,0\n\
+            0,11,,1.7e9,,,nan,nan,nan,nan,nan,0,0,0,0\n\
+            1,11,,1.7e9,,,120.0,220.0,1,0,0,0,0,0,0\n\
+            1,11,,1.7e9,,,400.0,100.0,1,0,0,1,0,0,0\n\
+            0,12,,1.7e9,,,130.0,230.0,1,0,0,0,0,0,0\n",
+        ),
+    ];
+    for (fname, contents) in tables {
+        std::fs::write(braidz.join(fname), contents).unwrap();
+    }
+
+    // Unassociated, NaN and untracked detections give no observation.
+    let mut archive = braidz_parser::braidz_parse_path(&braidz).unwrap();
+    let observations = observations_from_braidz(&mut archive).unwrap();
+    let expected = [
+        ("cam1", [0.1, 0.2, 0.3], [1.0, 0.0, 0.0], [100.0, 200.0]),
+        ("cam2", [0.1, 0.2, 0.3], [1.0, 0.0, 0.0], [110.0, 210.0]),
+        ("cam2", [0.11, 0.2, 0.3], [1.0, 0.5, 0.0], [120.0, 220.0]),
+        ("cam2", [-0.5, 0.0, 0.2], [0.0, 0.0, -1.0], [400.0, 100.0]),
+    ]
+    .map(|(cam_name, position, velocity, distorted)| Observation {
+        cam_name: cam_name.to_string(),
+        position: Vector3::from(position),
+        velocity: Vector3::from(velocity),
+        distorted,
+    });
+    assert_eq!(observations, expected);
+}
@@ -1,4 +1,4 @@
-use bundle_adj::ModelType;
+use bundle_adj::{ModelType, RobustLoss};
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::Parser;
 use eyre::{self, Context, Result};
@@ -34,10 +34,21 @@ struct Cli {
     #[arg(long)]
     use_nth_observation: Option<u16>,
 
-    /// If set, keep the intermediate MCSC calibration directory.
+    /// If set, keep the intermediate MCSC calibration directory. (Only used
+    /// with `--octave`.)
     #[arg(long)]
     keep: bool,
 
+    /// Use the MultiCamSelfCal (MCSC) toolbox in Octave rather than the
+    /// built-in self-calibration.
+    #[arg(long)]
+    octave: bool,
+
+    /// Maximum reprojection error, in pixels, of an observation used by the
+    /// built-in self-calibration. (Default: 3.0)
+    #[arg(long)]
+    inlier_threshold: Option<f64>,
+
     /// Do not perform bundle adjustment
     #[arg(long)]
     no_bundle_adjustment: bool,
@@ -50,6 +61,25 @@ struct Cli {
     #[arg(long, value_enum, default_value_t)]
     bundle_adjustment_intrinsics_source: BAIntrinsicsSource,
 
+    /// Robust loss applied to reprojection errors in bundle adjustment
+    #[arg(long, value_enum, default_value_t)]
+    bundle_adjustment_loss: RobustLoss,
+
+    /// Reprojection error, in pixels, above which the robust loss treats an
+    /// observation as an outlier. (Default: 2.0)
+    #[arg(long)]
+    bundle_adjustment_loss_scale: Option<f64>,
+
+    /// Use the sparse bundle adjustment solver, which is much faster with
+    /// many points.
+    #[arg(long)]
+    sparse_bundle_adjustment: bool,
+
+    /// Save the reprojection error of each observation after bundle
+    /// adjustment to this CSV file.
+    #[arg(long)]
+    bundle_adjustment_residuals: Option<Utf8PathBuf>,
+
     /// Log data to rerun viewer at this socket address. (The typical address is
     /// "127.0.0.1:9876".) DEPRECATED. Use `rerun_url` instead.
     #[arg(long, hide = true)]
@@ -328,28 +358,6 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
         eyre::bail!("No points detected.");
     }
 
-    let undo_radial = radfiles.len() == num_cameras;
-
-    let cfg = McscCfg {
-        num_cameras,
-        undo_radial,
-        use_nth_observation,
-    };
-
-    let res = DatMat::new(num_cameras, 2, res)?;
-
-    let mcsc_data = McscConfigDir {
-        id_mat: visibility.clone().into(),
-        radfiles,
-        cfg,
-        camera_order,
-        res,
-        points: observations.clone(),
-    };
-
-    #[allow(unused_variables)]
-    let mut output_root_guard = None; // will cleanup on drop
-
     let input_str = opt
         .input
         .as_os_str()
@@ -358,50 +366,24 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
     let input_base_name = input_str
         .strip_suffix(".braidz")
         .ok_or_else(|| eyre::eyre!("expected input filename to end with '.braidz'."))?;
-    let out_dir_name = if opt.keep {
-        Utf8PathBuf::from(format!("{}.mcsc", input_base_name))
-    } else {
-        let output_root = tempfile::tempdir()?;
-        let out_dir_name = Utf8Path::from_path(output_root.path()).unwrap().to_owned();
-        #[allow(unused_assignments)]
-        {
-            output_root_guard = Some(output_root);
-        }
-        out_dir_name
-    };
     let xml_out_name = Utf8PathBuf::from(format!("{}-unaligned.xml", input_base_name));
+    let pymvg_out_name = Utf8PathBuf::from(format!("{}-unaligned.json", input_base_name));
 
-    mcsc_data.save_to_path(&out_dir_name)?;
-
-    println!("Saved to directory \"{out_dir_name}\".");
-
-    if std::fs::exists(&xml_out_name)? {
-        eyre::bail!("XML calibration output file (\"{xml_out_name}\") exists. Will not overwrite.");
-    }
-
-    let (_mcsc_root, mcsc_base) = match std::env::var_os("MCSC_ROOT") {
-        Some(v) => (None, std::path::PathBuf::from(v)),
-        None => {
-            // unpack MCSC into mcsc_root
-            let mcsc_root = tempfile::tempdir()?;
-            let mcsc_dir_name = std::path::PathBuf::from(mcsc_root.path());
-            let mcsc_base = mcsc_structs::unpack_mcsc_into(&mcsc_dir_name)?;
-            (Some(mcsc_root), mcsc_base)
+    for out_name in [&xml_out_name, &pymvg_out_name] {
+        if std::fs::exists(out_name)? {
+            eyre::bail!("Calibration output file (\"{out_name}\") exists. Will not overwrite.");
         }
-    };
-    let mcsc_base = Utf8PathBuf::from_path_buf(mcsc_base).unwrap();
-
-    let gocal_abs = mcsc_base.join("MultiCamSelfCal/gocal.m");
-
-    let resultdir = out_dir_name.join("result");
-    copy_dir_all(&out_dir_name, &resultdir)?;
+    }
 
-    // Create output XML file prior to running Octave. This way, in case there
-    // is a problem opening it, we don't wait for Octave to finish.
+    // Create output files prior to calibrating. This way, in case there is a
+    // problem opening them, we don't wait for the calibration to finish.
     let mut out_fd = DeleteUnfinished::new(&xml_out_name)
         .with_context(|| format!("While creating XML calibration output file {xml_out_name}"))?;
+    let mut pymvg_out_fd = DeleteUnfinished::new(&pymvg_out_name).with_context(|| {
+        format!("While creating pymvg calibration output file {pymvg_out_name}")
+    })?;
 
-    // Connect to rerun prior to running Octave.
+    // Connect to rerun prior to calibrating.
 
     let rerun_url = if let Some(socket_addr_str) = opt.rerun {
         tracing::warn!("'--rerun' CLI argument is deprecated in favor of '--rerun-url'.");
@@ -425,144 +407,169 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
         None
     };
 
-    let config_arg = format!(
-        "--config={resultdir}",
-        resultdir = std::path::absolute(&resultdir)?.display()
-    );
-    let args = vec![gocal_abs.as_os_str(), config_arg.as_ref()];
-    let current_dir = gocal_abs.parent().unwrap();
-    if !std::process::Command::new("octave")
-        .args(args)
-        .current_dir(current_dir)
-        .status()?
-        .success()
-    {
-        eyre::bail!("octave failed");
-    }
+    // Downsample data if needed. (MCSC does its own downsampling.)
+    let (ds_visibility, ds_observations) =
+        downsample(&visibility, &observations, use_nth_observation)?;
 
-    println!("Octave MCSC completed.");
+    // Initial calibration and 3D points. For each point, `inliers` holds
+    // whether the observation by each camera is used.
+    let (initial_system, points0, inliers) = if opt.octave {
+        let undo_radial = radfiles.len() == num_cameras;
 
-    // Do our own bundle adjustment here.
+        let cfg = McscCfg {
+            num_cameras,
+            undo_radial,
+            use_nth_observation,
+        };
 
-    // Load initial guess of camera positions and 3D world points from MCSC results.
-    let (mcsc_system, points4cals) = {
-        let flydra_mvg::McscDirData {
-            cameras,
-            points4cals,
-        } = flydra_mvg::read_mcsc_dir::<f64, _>(&resultdir)
-            .with_context(|| format!("while reading calibration at {resultdir}"))?;
-        let mut cams = BTreeMap::new();
-        for orig_cam in cameras.iter() {
-            let epsilon = 1e2;
-            let (name, cam) = flydra_mvg::from_flydra_with_limited_skew(orig_cam, epsilon)?;
-            cams.insert(name, cam);
+        let res = DatMat::new(num_cameras, 2, res)?;
+
+        let mcsc_data = McscConfigDir {
+            id_mat: visibility.clone().into(),
+            radfiles,
+            cfg,
+            camera_order: camera_order.clone(),
+            res,
+            points: observations.clone(),
+        };
+
+        let mcsc_system = run_octave_mcsc(mcsc_data, opt.keep, input_base_name)?;
+
+        // Triangulate the 3D points from all observations.
+        let mut points0 = Vec::with_capacity(ds_visibility.ncols());
+        let mut inliers: Vec<Vec<bool>> = Vec::with_capacity(ds_visibility.ncols());
+        for j in 0..ds_visibility.ncols() {
+            let pts: Vec<_> = camera_order
+                .iter()
+                .enumerate()
+                .filter(|(i, _name)| ds_visibility[(*i, j)])
+                .map(|(i, name)| {
+                    let coords = nalgebra::Point2::new(
+                        ds_observations[(i * 3, j)],
+                        ds_observations[(i * 3 + 1, j)],
+                    );
+                    (name.clone(), braid_mvg::DistortedPixel { coords })
+                })
+                .collect();
+            let pt = if pts.len() >= 2 {
+                Some(mcsc_system.find3d_distorted(&pts)?.point().coords)
+            } else {
+                None
+            };
+            points0.push(pt);
+            inliers.push((0..num_cameras).map(|i| ds_visibility[(i, j)]).collect());
         }
+        (mcsc_system, points0, inliers)
+    } else {
+        let specs: Vec<_> = camera_order
+            .iter()
+            .enumerate()
+            .map(|(i, name)| mcsc_native::CameraSpec {
+                name: name.clone(),
+                width: res[i * 2],
+                height: res[i * 2 + 1],
+                intrinsics: checkerboard_intrinsics.as_ref().map(|ci| ci[i].clone()),
+            })
+            .collect();
+        let pts2d: Vec<Vec<_>> = (0..ds_visibility.ncols())
+            .map(|j| {
+                (0..num_cameras)
+                    .map(|i| {
+                        ds_visibility[(i, j)]
+                            .then(|| (ds_observations[(i * 3, j)], ds_observations[(i * 3 + 1, j)]))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut cfg = mcsc_native::Config::default();
+        if let Some(inlier_threshold) = opt.inlier_threshold {
+            cfg.inlier_threshold = inlier_threshold;
+        }
+        let cal = mcsc_native::self_calibrate(&specs, &pts2d, &cfg)?;
+        println!(
+            "Self-calibration completed. RMS reprojection error: {:.2} pixels.",
+            cal.rms_reprojection_error(&pts2d)
+        );
 
+        let cams = camera_order
+            .iter()
+            .cloned()
+            .zip(cal.cameras.into_iter())
+            .collect();
         (
             flydra_mvg::FlydraMultiCameraSystem::new(cams, None),
-            points4cals,
+            cal.points,
+            cal.inliers,
         )
     };
 
-    let multi_cam_system = if !opt.no_bundle_adjustment {
-        if true {
-            // There is some kind of matrix indexing bug when not all 3d points
-            // are visible from all cameras. Need to fix this.
-            todo!("bundle adjustment code needs to be fixed");
+    // Store each (u,v) observation pair. This will be reshaped later to a 2xN
+    // matrix.
+    let mut observed: Vec<f64> = Vec::new();
+    let mut cam_idx: Vec<u8> = Vec::new();
+    let mut pt_idx = Vec::new();
+    let mut point_locs: Vec<f64> = Vec::new();
+    let mut labels3d = Vec::new();
+    for (j, (pt, pt_inliers)) in points0.iter().zip(inliers.iter()).enumerate() {
+        let Some(pt) = pt else {
+            continue;
+        };
+        if !pt_inliers.iter().any(|x| *x) {
+            continue;
         }
+        for (i, is_inlier) in pt_inliers.iter().enumerate() {
+            if *is_inlier {
+                observed.push(ds_observations[(i * 3, j)]);
+                observed.push(ds_observations[(i * 3 + 1, j)]);
+                cam_idx.push(i.try_into()?);
+                pt_idx.push(labels3d.len());
+            }
+        }
+        point_locs.extend(pt.coords.iter());
+        labels3d.push(format!("{j}"));
+    }
+    if labels3d.is_empty() {
+        eyre::bail!("No 3D points reconstructed.");
+    }
+
+    // Reshape observations to 2xN matrix.
+    let observed = nalgebra::Matrix2xX::<f64>::from_column_slice(&observed);
+    let points0 = nalgebra::Matrix3xX::<f64>::from_column_slice(&point_locs);
+
+    if opt.octave {
+        println!("# Results of MCSC");
+    } else {
+        println!("# Results of self-calibration");
+    }
+    print_reproj_and_params(
+        &initial_system,
+        &camera_order,
+        &points0,
+        &observed,
+        &cam_idx,
+        &pt_idx,
+    )?;
+
+    let multi_cam_system = if !opt.no_bundle_adjustment {
         let model_type = opt.bundle_adjustment_model;
         let isrc = opt.bundle_adjustment_intrinsics_source;
 
         println!("Performing bundle adjustment {model_type:?} {isrc:?}");
 
         // Create BundleAdjuster
-        let (visibility, observations, ba, start_ba_system) = {
-            // Downsample data if needed.
-            let (visibility, observations) = if use_nth_observation == 1 {
-                (visibility, observations)
-            } else {
-                // observations.save("orig.dat")?;
-                let use_nth_observation: usize = use_nth_observation.into();
-                let ncams = visibility.nrows();
-                let npts = visibility.ncols() / use_nth_observation;
-
-                let mut v2_vals = Vec::with_capacity(ncams * npts);
-                for i in 0..ncams {
-                    for j in 0..npts {
-                        v2_vals.push(visibility[(i, j * use_nth_observation)]);
-                    }
-                }
-
-                let mut o2_vals = Vec::with_capacity(ncams * npts * 3);
-                for j in 0..npts {
-                    for i in 0..ncams {
-                        o2_vals.push(observations[(i * 3, j * use_nth_observation)]);
-                        o2_vals.push(observations[(i * 3 + 1, j * use_nth_observation)]);
-                        o2_vals.push(observations[(i * 3 + 2, j * use_nth_observation)]);
-                    }
-                }
-
-                let v2 = DatMat::new(ncams, npts, v2_vals)?;
-                let o2 = DatMat::new(npts, ncams * 3, o2_vals)?.transpose();
-                (v2, o2)
-            };
-
-            // `visibility` is MxN where M is num cameras and N is num 3d world points.
-            assert_eq!(
-                visibility.nrows(),
-                mcsc_system.system().cams_by_name().len()
-            );
-            assert_eq!(visibility.nrows() * 3, observations.nrows());
-
-            // Store each (u,v) observation pair. This will be reshaped later to a 2xN matrix.
-            let mut observed: Vec<f64> = Vec::new();
-            let mut cam_idx = Vec::new();
-            let mut pt_idx = Vec::new();
-
-            let mut point_locs: std::collections::BTreeMap<usize, [f64; 3]> = Default::default();
-
-            for i in 0..visibility.nrows() {
-                let qq = &points4cals[i];
-
-                let obs_start_idx = i * 3;
-                for j in 0..visibility.ncols() {
-                    if visibility[(i, j)] {
-                        let obs_u = observations[(obs_start_idx, j)];
-                        let obs_v = observations[(obs_start_idx + 1, j)];
-                        observed.push(obs_u);
-                        observed.push(obs_v);
-                        cam_idx.push(i.try_into().unwrap());
-                        pt_idx.push(j);
-                        println!("cam {i} pt {j}: {obs_u:.2}, {obs_v:.2}");
-
-                        let xyz = [qq[(j, 0)], qq[(j, 1)], qq[(j, 2)]];
-                        let prev_xyz = point_locs.entry(j).or_insert_with(|| xyz.clone());
-                        for ii in 0..3 {
-                            if !approx::relative_eq!(xyz[ii], prev_xyz[ii]) {
-                                todo!("return error: MCSC returned different 3D points for the same 3D point?");
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Reshape observations to 2xN matrix.
-            let observed = nalgebra::Matrix2xX::<f64>::from_column_slice(&observed);
-            assert_eq!(observed.ncols(), cam_idx.len());
-            assert_eq!(pt_idx.len(), cam_idx.len());
-
-            // Use MCSC camera positions as initial camera guess.
+        let (ba, start_ba_system) = {
+            // Use initial camera positions as initial camera guess.
             let mut cams0 = Vec::new();
-            let mut cam_names = Vec::new();
             let mut cam_dims = Vec::new();
             let mut cams_by_name_ba: BTreeMap<_, _> = Default::default();
-            // Use extrinsics from MCSC as starting point.
-            // For intrinsics, it depends on our model_type.
-            for (i, (name, mcsc_cam)) in mcsc_system.system().cams_by_name().iter().enumerate() {
-                cam_names.push(name.clone());
-                cam_dims.push((mcsc_cam.width(), mcsc_cam.height()));
+            // Use initial extrinsics as starting point. For intrinsics, it
+            // depends on our model_type.
+            for (i, name) in camera_order.iter().enumerate() {
+                let initial_cam = &initial_system.system().cams_by_name()[name];
+                cam_dims.push((initial_cam.width(), initial_cam.height()));
                 // Remove potential skew from calibration.
-                let cam = mcsc_cam.as_ref();
+                let cam = initial_cam.as_ref();
                 let extrin = cam.extrinsics().clone();
                 let intrin = match &isrc {
                     BAIntrinsicsSource::CheckerboardCal => {
@@ -582,7 +589,12 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
                         let skew = 0.0;
                         let cx = intrin_mcsc.cx();
                         let cy = intrin_mcsc.cy();
-                        let distortion = intrin_mcsc.distortion.clone();
+                        let distortion = if model_type == ModelType::Fisheye4 {
+                            // The fisheye distortion is estimated from zero.
+                            opencv_ros_camera::Distortion::zero()
+                        } else {
+                            intrin_mcsc.distortion.clone()
+                        };
                         let intrin = RosOpenCvIntrinsics::from_params_with_distortion(
                             f, skew, f, cx, cy, distortion,
                         );
@@ -594,50 +606,64 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
                 cams_by_name_ba.insert(
                     name.clone(),
                     braid_mvg::Camera::new_from_cam_geom(
-                        mcsc_cam.width(),
-                        mcsc_cam.height(),
+                        initial_cam.width(),
+                        initial_cam.height(),
                         cam_fixed,
                     )?,
                 );
             }
             let start_ba_system = flydra_mvg::FlydraMultiCameraSystem::new(cams_by_name_ba, None);
 
-            let mut points0 = nalgebra::Matrix3xX::<f64>::zeros(visibility.ncols());
-            let mut labels3d = Vec::with_capacity(visibility.ncols());
-            for j in 0..visibility.ncols() {
-                match point_locs.get(&j) {
-                    Some(xyz) => {
-                        for ii in 0..3 {
-                            points0[(ii, j)] = xyz[ii];
-                        }
-                        labels3d.push(format!("{j}"));
-                    }
-                    None => {
-                        todo!("return error");
-                    }
-                }
-            }
-
-            // Print results of MCSC.
-            println!("# Results of MCSC");
-            print_reproj_and_params(&mcsc_system, &points0, &visibility, &observations)?;
-
-            let ba = bundle_adj::BundleAdjuster::new(
-                observed, cam_idx, pt_idx, cam_names, cam_dims, cams0, points0, labels3d,
-                model_type, rec, false,
+            let mut ba = bundle_adj::BundleAdjuster::new(
+                observed.clone(),
+                cam_idx.clone(),
+                pt_idx.clone(),
+                camera_order.clone(),
+                cam_dims,
+                cams0,
+                points0,
+                labels3d,
+                model_type,
+                rec,
+                false,
             )?;
-            (visibility, observations, ba, start_ba_system)
+            ba.set_loss(
+                opt.bundle_adjustment_loss,
+                opt.bundle_adjustment_loss_scale.unwrap_or(2.0),
+            )?;
+            (ba, start_ba_system)
         };
 
         let residuals_pre = ba.residuals().unwrap();
         // dbg!(&residuals_pre);
         println!("# Results prior to bundle adjustment");
-        print_reproj_and_params(&start_ba_system, ba.points(), &visibility, &observations)?;
-        let (ba, report) = levenberg_marquardt::LevenbergMarquardt::new().minimize(ba);
-        println!("{:?}", report);
-        if !report.termination.was_successful() {
-            eyre::bail!("Bundle adjustment did not succeed.");
+        print_reproj_and_params(
+            &start_ba_system,
+            &camera_order,
+            ba.points(),
+            &observed,
+            &cam_idx,
+            &pt_idx,
+        )?;
+        let ba = if opt.sparse_bundle_adjustment {
+            let (ba, report) = bundle_adj::SparseSolver::new().minimize(ba);
+            println!("{:?}", report);
+            if !report.termination.was_successful() {
+                eyre::bail!("Bundle adjustment did not succeed.");
+            };
+            ba
+        } else {
+            let (ba, report) = levenberg_marquardt::LevenbergMarquardt::new().minimize(ba);
+            println!("{:?}", report);
+            if !report.termination.was_successful() {
+                eyre::bail!("Bundle adjustment did not succeed.");
+            };
+            ba
         };
+        if let Some(residuals_fname) = &opt.bundle_adjustment_residuals {
+            save_residuals(&ba, &camera_order, residuals_fname)?;
+            println!("Residuals saved to {residuals_fname}");
+        }
         // dbg!(ba.points().column(0).as_slice());
         let residuals_post = ba.residuals().unwrap();
         // dbg!(&residuals_post);
@@ -648,18 +674,17 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
         );
 
         let mut cams_by_name = std::collections::BTreeMap::new();
-        let mut cam_names = Vec::new();
-        for ((name, old_cam), ba_cam) in mcsc_system
-            .system()
-            .cams_by_name()
-            .iter()
-            .zip(ba.cams().iter())
-        {
+        for (cam_i, (name, ba_cam)) in camera_order.iter().zip(ba.cams().iter()).enumerate() {
+            let old_cam = &initial_system.system().cams_by_name()[name];
             let e = ba_cam.extrinsics().clone();
             let i = ba_cam.intrinsics().clone();
-            let cam = braid_mvg::Camera::new(old_cam.width(), old_cam.height(), e, i)?;
+            let (w, h) = (old_cam.width(), old_cam.height());
+            let cam = if let Some(d) = ba.fisheye_distortion().get(cam_i) {
+                braid_mvg::Camera::new_fisheye(w, h, e, i, d.clone())?
+            } else {
+                braid_mvg::Camera::new(w, h, e, i)?
+            };
             cams_by_name.insert(name.clone(), cam);
-            cam_names.push(name.clone());
         }
         let ba_system = flydra_mvg::FlydraMultiCameraSystem::new(cams_by_name, None);
 
@@ -667,53 +692,192 @@ fn braiz_mcsc(opt: Cli) -> Result<Utf8PathBuf> {
         println!(
             "# Results of bundle adjustment (model: {model_type:?}, intrinsics source: {isrc:?})"
         );
-        print_reproj_and_params(&ba_system, ba.points(), &visibility, &observations)?;
+        print_reproj_and_params(
+            &ba_system,
+            &camera_order,
+            ba.points(),
+            &observed,
+            &cam_idx,
+            &pt_idx,
+        )?;
         ba_system
     } else {
-        mcsc_system
+        initial_system
     };
     multi_cam_system.to_flydra_xml(&mut out_fd.inner())?;
     out_fd.close()?;
+    multi_cam_system
+        .system()
+        .to_pymvg_writer(pymvg_out_fd.inner())?;
+    pymvg_out_fd.close()?;
+    println!("Unaligned pymvg calibration saved to {pymvg_out_name}");
 
     Ok(xml_out_name)
 }
 
+/// Save the reprojection error of each observation as CSV.
+fn save_residuals(
+    ba: &bundle_adj::BundleAdjuster<f64>,
+    camera_order: &[String],
+    fname: &Utf8Path,
+) -> Result<()> {
+    use std::io::Write;
+    let mut fd = std::io::BufWriter::new(fs::File::create(fname)?);
+    writeln!(fd, "camera,point,dx,dy,error,weight")?;
+    for res in ba.observation_residuals() {
+        writeln!(
+            fd,
+            "{},{},{},{},{},{}",
+            camera_order[usize::from(res.cam_idx)],
+            ba.labels3d()[res.pt_idx],
+            res.residual.x,
+            res.residual.y,
+            res.residual.norm(),
+            res.weight
+        )?;
+    }
+    fd.flush()?;
+    Ok(())
+}
+
+/// Use only every `n`th point.
+fn downsample(
+    visibility: &DatMat<bool>,
+    observations: &DatMat<f64>,
+    n: u16,
+) -> Result<(DatMat<bool>, DatMat<f64>)> {
+    if n == 1 {
+        return Ok((visibility.clone(), observations.clone()));
+    }
+    // observations.save("orig.dat")?;
+    let use_nth_observation: usize = n.into();
+    let ncams = visibility.nrows();
+    let npts = visibility.ncols() / use_nth_observation;
+
+    let mut v2_vals = Vec::with_capacity(ncams * npts);
+    for i in 0..ncams {
+        for j in 0..npts {
+            v2_vals.push(visibility[(i, j * use_nth_observation)]);
+        }
+    }
+
+    let mut o2_vals = Vec::with_capacity(ncams * npts * 3);
+    for j in 0..npts {
+        for i in 0..ncams {
+            o2_vals.push(observations[(i * 3, j * use_nth_observation)]);
+            o2_vals.push(observations[(i * 3 + 1, j * use_nth_observation)]);
+            o2_vals.push(observations[(i * 3 + 2, j * use_nth_observation)]);
+        }
+    }
+
+    let v2 = DatMat::new(ncams, npts, v2_vals)?;
+    let o2 = DatMat::new(npts, ncams * 3, o2_vals)?.transpose();
+    Ok((v2, o2))
+}
+
+/// Calibrate with the MultiCamSelfCal (MCSC) toolbox in Octave.
+fn run_octave_mcsc(
+    mcsc_data: McscConfigDir,
+    keep: bool,
+    input_base_name: &str,
+) -> Result<flydra_mvg::FlydraMultiCameraSystem<f64>> {
+    #[allow(unused_variables)]
+    let mut output_root_guard = None; // will cleanup on drop
+
+    let out_dir_name = if keep {
+        Utf8PathBuf::from(format!("{}.mcsc", input_base_name))
+    } else {
+        let output_root = tempfile::tempdir()?;
+        let out_dir_name = Utf8Path::from_path(output_root.path()).unwrap().to_owned();
+        #[allow(unused_assignments)]
+        {
+            output_root_guard = Some(output_root);
+        }
+        out_dir_name
+    };
+
+    mcsc_data.save_to_path(&out_dir_name)?;
+
+    println!("Saved to directory \"{out_dir_name}\".");
+
+    let (_mcsc_root, mcsc_base) = match std::env::var_os("MCSC_ROOT") {
+        Some(v) => (None, std::path::PathBuf::from(v)),
+        None => {
+            // unpack MCSC into mcsc_root
+            let mcsc_root = tempfile::tempdir()?;
+            let mcsc_dir_name = std::path::PathBuf::from(mcsc_root.path());
+            let mcsc_base = mcsc_structs::unpack_mcsc_into(&mcsc_dir_name)?;
+            (Some(mcsc_root), mcsc_base)
+        }
+    };
+    let mcsc_base = Utf8PathBuf::from_path_buf(mcsc_base).unwrap();
+
+    let gocal_abs = mcsc_base.join("MultiCamSelfCal/gocal.m");
+
+    let resultdir = out_dir_name.join("result");
+    copy_dir_all(&out_dir_name, &resultdir)?;
+
+    let config_arg = format!(
+        "--config={resultdir}",
+        resultdir = std::path::absolute(&resultdir)?.display()
+    );
+    let args = vec![gocal_abs.as_os_str(), config_arg.as_ref()];
+    let current_dir = gocal_abs.parent().unwrap();
+    if !std::process::Command::new("octave")
+        .args(args)
+        .current_dir(current_dir)
+        .status()?
+        .success()
+    {
+        eyre::bail!("octave failed");
+    }
+
+    println!("Octave MCSC completed.");
+
+    // Load camera calibrations from MCSC results.
+    let flydra_mvg::McscDirData { cameras, .. } =
+        flydra_mvg::read_mcsc_dir::<f64, _>(&resultdir)
+            .with_context(|| format!("while reading calibration at {resultdir}"))?;
+    let mut cams = BTreeMap::new();
+    for orig_cam in cameras.iter() {
+        let epsilon = 1e2;
+        let (name, cam) = flydra_mvg::from_flydra_with_limited_skew(orig_cam, epsilon)?;
+        cams.insert(name, cam);
+    }
+
+    Ok(flydra_mvg::FlydraMultiCameraSystem::new(cams, None))
+}
+
 fn print_reproj_and_params(
     system: &flydra_mvg::FlydraMultiCameraSystem<f64>,
+    camera_order: &[String],
     points: &nalgebra::Matrix3xX<f64>,
-    visibility: &DatMat<bool>,
-    observations: &DatMat<f64>,
+    observed: &nalgebra::Matrix2xX<f64>,
+    cam_idx: &[u8],
+    pt_idx: &[usize],
 ) -> Result<()> {
     println!(
             "CamId           name           std     mean  #inliers    fx      skew    fy      cx      cy      k1      k2      k3      p1      p2"
         );
-    assert_eq!(system.len(), visibility.nrows());
-    for (i, (name, cam)) in system.system().cams_by_name().iter().enumerate() {
-        // for i in 0..visibility.nrows() {
-        // let cam = &ba.cams()[i];
+    assert_eq!(system.len(), camera_order.len());
+    for (i, name) in camera_order.iter().enumerate() {
+        let cam = &system.system().cams_by_name()[name];
         let mut cam_dists = Vec::new();
-        let obs_start_idx = i * 3;
-        for j in 0..visibility.ncols() {
-            if visibility[(i, j)] {
-                let obs_u = observations[(obs_start_idx, j)];
-                let obs_v = observations[(obs_start_idx + 1, j)];
-                // let pt = ba.points().column(j);
-                let pt = points.column(j);
-                let pts = cam_geom::Points::new(pt.transpose());
-                let predicted = cam.as_ref().world_to_pixel(&pts).data.transpose();
-                let dx = obs_u - predicted.x;
-                let dy = obs_v - predicted.y;
-                let dist = (dx * dx + dy * dy).sqrt();
-                cam_dists.push(dist);
-                if false {
-                    // if i == 0 && j == 0 {
-                    dbg!("camera loaded from MCSC");
-                    dbg!(cam);
-                    dbg!(pt.as_slice());
-                    dbg!(predicted);
-                    dbg!((obs_u, obs_v));
-                }
+        for ((obs, this_cam_idx), this_pt_idx) in observed
+            .column_iter()
+            .zip(cam_idx.iter())
+            .zip(pt_idx.iter())
+        {
+            if usize::from(*this_cam_idx) != i {
+                continue;
             }
+            let pt = points.column(*this_pt_idx);
+            let pts = cam_geom::Points::new(pt.transpose());
+            let predicted = cam.as_ref().world_to_pixel(&pts).data.transpose();
+            let dx = obs[0] - predicted.x;
+            let dy = obs[1] - predicted.y;
+            let dist = (dx * dx + dy * dy).sqrt();
+            cam_dists.push(dist);
         }
         let count = cam_dists.len();
         let cam_dists = polars::prelude::Float64Chunked::from_vec("cam_dists".into(), cam_dists);
@@ -842,6 +1006,7 @@ mod test {
         let opt = Cli {
             input,
             checkerboard_cal_dir,
+            octave: true,
             no_bundle_adjustment: true,
             ..Default::default()
         };
@@ -1,7 +1,14 @@
-use nalgebra::{self as na, Dyn, Owned, UnitQuaternion};
+use braid_mvg::fisheye::FisheyeDistortion;
+use nalgebra::{self as na, Dyn, Owned, UnitQuaternion, U2};
 use num_traits::Float;
 use opencv_ros_camera::RosOpenCvIntrinsics;
 
+mod loss;
+mod schur;
+
+pub use loss::RobustLoss;
+pub use schur::{SparseReport, SparseSolver, SparseTermination};
+
 type NCamsType = u8;
 
 pub const RR_CAM_BASE_PATH: &str = "world/camera";
@@ -51,6 +58,12 @@ pub struct BundleAdjuster<F: na::RealField + Float> {
     /// This is updated every iteration.
     cams: Vec<cam_geom::Camera<F, RosOpenCvIntrinsics<F>>>,
 
+    /// The fisheye distortion of each camera with [ModelType::Fisheye4],
+    /// otherwise empty.
+    ///
+    /// This is updated every iteration.
+    fisheye: Vec<FisheyeDistortion<F>>,
+
     /// The world coordinate (3D) points, of which there are `n`.
     ///
     /// This is updated every iteration.
@@ -76,6 +89,32 @@ pub struct BundleAdjuster<F: na::RealField + Float> {
     rr_tick: i64,
 
     force_rerun_distorted: bool,
+
+    /// The loss applied to the reprojection error of each observation.
+    loss: RobustLoss,
+    /// The scale of [Self::loss], in pixels.
+    loss_scale: F,
+
+    /// Cameras whose parameters are held fixed.
+    fixed_cams: Vec<bool>,
+    /// 3D world points whose positions are held fixed.
+    fixed_points: Vec<bool>,
+}
+
+/// The reprojection error of a single observation.
+#[derive(Debug, Clone)]
+pub struct ObservationResidual<F: na::RealField> {
+    /// The index of the camera doing the observation.
+    pub cam_idx: NCamsType,
+    /// The index of the 3D world point being observed.
+    pub pt_idx: usize,
+    /// The observed minus the predicted pixel coordinates.
+    pub residual: na::Vector2<F>,
+    /// The weight of the observation, `rho'(s)`, given by the robust loss.
+    ///
+    /// This is one for observations treated as inliers and approaches zero
+    /// for outliers.
+    pub weight: F,
 }
 
 /// What parameters are optimized during bundle adjustment.
@@ -101,6 +140,12 @@ pub enum ModelType {
     /// intrinsic model has a separate focal length for x and y directions.
     #[default]
     ExtrinsicsOnly,
+    /// Tunes the 3D world points, the camera extrinsic parameters, and the
+    /// camera intrinsic parameters including 4 distortion terms in the
+    /// Kannala-Brandt fisheye distortion model (see [braid_mvg::fisheye]). The
+    /// intrinsic model has a single focal length (not fx and fy). The
+    /// jacobian is computed numerically.
+    Fisheye4,
 }
 
 struct ModelTypeInfo {
@@ -143,6 +188,12 @@ impl ModelType {
                 num_extrinsic_params: 6,
                 num_fixed_params: 9, // fx, fy, cx, cy + 5 distortion
             },
+            ModelType::Fisheye4 => ModelTypeInfo {
+                num_distortion_params: 4,
+                num_intrinsic_params: 3 + 4,
+                num_extrinsic_params: 6,
+                num_fixed_params: 0,
+            },
         }
     }
 }
@@ -155,6 +206,10 @@ impl<F: na::RealField + Float> BundleAdjuster<F> {
     /// N indices, `cam_idx` and `pt_idx`, respectively. The initial camera
     /// calibrations and initial world 3D point locations are specified as
     /// `cams0` and `points0`, respectively.
+    ///
+    /// With [ModelType::Fisheye4], the cameras in `cams0` must not have
+    /// distortion. The initial fisheye distortion is zero unless set with
+    /// [Self::set_fisheye_distortion].
     pub fn new(
         observed: na::Matrix2xX<F>,
         cam_idx: Vec<NCamsType>,
@@ -238,6 +293,11 @@ impl<F: na::RealField + Float> BundleAdjuster<F> {
                         return Err(Error::InconsistentData("fx must equal fy"));
                     }
                 }
+                if model_type == ModelType::Fisheye4 && !cam.intrinsics().distortion.is_linear() {
+                    return Err(Error::InconsistentData(
+                        "fisheye cameras must not have Brown-Conrady distortion",
+                    ));
+                }
                 let p = to_params(cam, model_type);
                 debug_assert_eq!(p.len(), model_type.info().num_cam_params());
                 params_cache.extend(p);
@@ -254,6 +314,8 @@ impl<F: na::RealField + Float> BundleAdjuster<F> {
             params_cache
         };
 
+        let num_cams = cams0.len();
+        let num_pts = points0.ncols();
         let mut myself = Self {
             model_type,
             num_cams: cams0.len().try_into().unwrap(),
@@ -265,6 +327,7 @@ impl<F: na::RealField + Float> BundleAdjuster<F> {
             cam_names,
             cam_dims,
             cams: cams0.to_vec(),
+            fisheye: Vec::new(),
             points: points0,
             labels3d,
             params_cache: params_cache.clone(),
@@ -273,16 +336,223 @@ impl<F: na::RealField + Float> BundleAdjuster<F> {
             did_show_rerun_warning: false,
             rr_tick: 0,
             force_rerun_distorted,
+            loss: RobustLoss::default(),
+            loss_scale: F::one(),
+            fixed_cams: vec![false; num_cams],
+            fixed_points: vec![false; num_pts],
         };
         // call once to log initial data to rerun
-        levenberg_marquardt::LeastSquaresProblem::set_params(&mut myself, &params_cache);
+        myself.set_full_params(&params_cache);
         Ok(myself)
     }
 
+    /// Set the loss applied to the reprojection error of each observation.
+    ///
+    /// `scale` is in pixels and must be positive. See [RobustLoss].
+    pub fn set_loss(&mut self, loss: RobustLoss, scale: F) -> Result<()> {
+        if !Float::is_finite(scale) || scale <= F::zero() {
+            return Err(Error::InconsistentData("loss scale must be positive"));
+        }
+        self.loss = loss;
+        self.loss_scale = scale;
+        Ok(())
+    }
+
+    /// Set the fisheye distortion of each camera with [ModelType::Fisheye4].
+    pub fn set_fisheye_distortion(&mut self, fisheye: &[FisheyeDistortion<F>]) -> Result<()> {
+        if self.model_type != ModelType::Fisheye4 {
+            return Err(Error::InconsistentData("model type is not fisheye"));
+        }
+        if fisheye.len() != usize(self.num_cams) {
+            return Err(Error::InconsistentData("fisheye distortion shape"));
+        }
+        let num_cam_params = self.model_type.info().num_cam_params();
+        let mut params = self.params_cache.clone();
+        for (i, d) in fisheye.iter().enumerate() {
+            let start = i * num_cam_params + 3;
+            params.as_mut_slice()[start..start + 4].copy_from_slice(&d.opencv_vec());
+        }
+        self.set_full_params(&params);
+        Ok(())
+    }
+
+    /// Hold the parameters of the cameras with the given indices fixed.
+    ///
+    /// This replaces any previously fixed cameras.
+    pub fn set_fixed_cams(&mut self, cam_idx: &[NCamsType]) -> Result<()> {
+        let mut fixed = vec![false; usize(self.num_cams)];
+        for i in cam_idx {
+            *fixed
+                .get_mut(usize(*i))
+                .ok_or(Error::InconsistentData("fixed camera index"))? = true;
+        }
+        self.fixed_cams = fixed;
+        Ok(())
+    }
+
+    /// Hold the 3D world points with the given indices fixed.
+    ///
+    /// This replaces any previously fixed points.
+    pub fn set_fixed_points(&mut self, pt_idx: &[usize]) -> Result<()> {
+        let mut fixed = vec![false; self.num_pts];
+        for i in pt_idx {
+            *fixed
+                .get_mut(*i)
+                .ok_or(Error::InconsistentData("fixed point index"))? = true;
+        }
+        self.fixed_points = fixed;
+        Ok(())
+    }
+
+    /// The reprojection error of each observation, in the order given to
+    /// [Self::new].
+    ///
+    /// This is useful to identify outliers.
+    pub fn observation_residuals(&self) -> Vec<ObservationResidual<F>> {
+        (0..self.observed.ncols())
+            .map(|obs_idx| {
+                let residual = self.observation_residual(obs_idx);
+                let (_, weight) = self.loss.eval(residual.norm_squared(), self.loss_scale);
+                ObservationResidual {
+                    cam_idx: self.cam_idx[obs_idx],
+                    pt_idx: self.pt_idx[obs_idx],
+                    residual,
+                    weight,
+                }
+            })
+            .collect()
+    }
+
+    /// Half the sum of the robust loss of all observations.
+    ///
+    /// This is the cost minimized by bundle adjustment.
+    pub fn cost(&self) -> F {
+        let half: F = na::convert(0.5);
+        (0..self.observed.ncols())
+            .map(|obs_idx| {
+                let s = self.observation_residual(obs_idx).norm_squared();
+                self.loss.eval(s, self.loss_scale).0
+            })
+            .fold(F::zero(), |acc, x| acc + x)
+            * half
+    }
+
+    /// The observed minus the predicted pixel coordinates of an observation.
+    fn observation_residual(&self, obs_idx: usize) -> na::Vector2<F> {
+        let cam_idx = usize(self.cam_idx[obs_idx]);
+        let pt = self.points.column(self.pt_idx[obs_idx]).into_owned();
+        let predicted = world_to_pixel(&self.cams[cam_idx], self.fisheye.get(cam_idx), &pt);
+        self.observed.column(obs_idx) - predicted
+    }
+
+    /// The residual of an observation and its jacobian.
+    ///
+    /// The columns of the jacobian are the parameters of the camera followed
+    /// by the coordinates of the 3D world point.
+    fn observation_jacobian(&self, obs_idx: usize) -> (na::Vector2<F>, na::OMatrix<F, U2, Dyn>) {
+        if self.model_type == ModelType::Fisheye4 {
+            return (
+                self.observation_residual(obs_idx),
+                self.numerical_observation_jacobian(obs_idx),
+            );
+        }
+        let num_cam_params = self.model_type.info().num_cam_params();
+        let cam_idx = self.cam_idx[obs_idx];
+        let pt_idx = self.pt_idx[obs_idx];
+        let mut j = na::OMatrix::<F, Dyn, Dyn>::zeros(2, num_cam_params + 3);
+        self.model_type.eval_cam_jacobians(
+            self,
+            cam_idx,
+            pt_idx,
+            &mut j,
+            ((0, 0), (2, num_cam_params)),
+        );
+        self.eval_pt_jacobians(cam_idx, pt_idx, &mut j, ((0, num_cam_params), (2, 3)));
+        let j = j.fixed_rows::<2>(0).into_owned();
+        (self.observation_residual(obs_idx), j)
+    }
+
+    /// The jacobian of the residual of an observation by central differences.
+    ///
+    /// The columns are as in [Self::observation_jacobian].
+    fn numerical_observation_jacobian(&self, obs_idx: usize) -> na::OMatrix<F, U2, Dyn> {
+        let num_cam_params = self.model_type.info().num_cam_params();
+        let cam_idx = usize(self.cam_idx[obs_idx]);
+        let pt_idx = self.pt_idx[obs_idx];
+        let start = cam_idx * num_cam_params;
+        let cam_params = &self.params_cache.as_slice()[start..start + num_cam_params];
+        let pt = self.points.column(pt_idx);
+
+        let mut x: Vec<F> = cam_params.iter().chain(pt.iter()).copied().collect();
+        let predict = |x: &[F]| {
+            let (cam_params, pt) = x.split_at(num_cam_params);
+            let cam = to_cam(cam_params, self.model_type, &[]);
+            let fisheye = fisheye_from_params(cam_params);
+            world_to_pixel(&cam, Some(&fisheye), &na::Vector3::from_column_slice(pt))
+        };
+
+        let two: F = na::convert(2.0);
+        let rel_step: F = Float::cbrt(F::epsilon());
+        let mut j = na::OMatrix::<F, U2, Dyn>::zeros(num_cam_params + 3);
+        for k in 0..x.len() {
+            let orig = x[k];
+            let h = rel_step * Float::max(Float::abs(orig), F::one());
+            x[k] = orig + h;
+            let plus = predict(&x);
+            x[k] = orig - h;
+            let minus = predict(&x);
+            x[k] = orig;
+            // The residual is the observed minus the predicted pixel.
+            j.set_column(k, &((minus - plus) / (two * h)));
+        }
+        j
+    }
+
+    /// For each parameter, its index in the vector of free (not fixed)
+    /// parameters, or `None` if it is fixed.
+    fn free_param_index(&self) -> Vec<Option<usize>> {
+        let num_cam_params = self.model_type.info().num_cam_params();
+        let is_fixed = self
+            .fixed_cams
+            .iter()
+            .flat_map(|fixed| std::iter::repeat(*fixed).take(num_cam_params))
+            .chain(
+                self.fixed_points
+                    .iter()
+                    .flat_map(|fixed| std::iter::repeat(*fixed).take(3)),
+            );
+        let mut n = 0;
+        is_fixed
+            .map(|fixed| {
+                if fixed {
+                    None
+                } else {
+                    n += 1;
+                    Some(n - 1)
+                }
+            })
+            .collect()
+    }
+
+    fn has_fixed_params(&self) -> bool {
+        self.fixed_cams
+            .iter()
+            .chain(self.fixed_points.iter())
+            .any(|x| *x)
+    }
+
     pub fn cams(&self) -> &[cam_geom::Camera<F, RosOpenCvIntrinsics<F>>] {
         &self.cams
     }
 
+    /// The fisheye distortion of each camera with [ModelType::Fisheye4],
+    /// otherwise empty.
+    ///
+    /// The cameras returned by [Self::cams] then have no distortion.
+    pub fn fisheye_distortion(&self) -> &[FisheyeDistortion<F>] {
+        &self.fisheye
+    }
+
     pub fn points(&self) -> &na::Matrix3xX<F> {
         &self.points
     }
@@ -292,6 +562,32 @@ impl<F: na::RealField + Float> BundleAdjuster<F> {
     }
 }
 
+/// Project a 3D world point to pixel coordinates, with fisheye distortion if
+/// given.
+fn world_to_pixel<F: na::RealField + Float>(
+    cam: &cam_geom::Camera<F, RosOpenCvIntrinsics<F>>,
+    fisheye: Option<&FisheyeDistortion<F>>,
+    pt: &na::Vector3<F>,
+) -> na::Vector2<F> {
+    let pts = cam_geom::Points::new(pt.transpose());
+    let Some(fisheye) = fisheye else {
+        return cam.world_to_pixel(&pts).data.transpose();
+    };
+    let pt_cam = cam.extrinsics().world_to_camera(&pts).data.transpose();
+    let n = fisheye.project(&pt_cam);
+    let i = cam.intrinsics();
+    na::Vector2::new(
+        i.fx() * n.x + i.skew() * n.y + i.cx(),
+        i.fy() * n.y + i.cy(),
+    )
+}
+
+/// The fisheye distortion in a (partial) parameter vector of
+/// [ModelType::Fisheye4].
+fn fisheye_from_params<F: na::RealField + Float>(params: &[F]) -> FisheyeDistortion<F> {
+    FisheyeDistortion::new(params[3], params[4], params[5], params[6])
+}
+
 /// Create a (partial) vector of fixed parameters from a camera.
 fn to_fixed_params<F: na::RealField + Float>(
     cam: &cam_geom::Camera<F, RosOpenCvIntrinsics<F>>,
@@ -300,7 +596,7 @@ fn to_fixed_params<F: na::RealField + Float>(
     let i = cam.intrinsics();
     match model_type {
         ModelType::OpenCV5 | ModelType::OpenCV4 => Vec::with_capacity(0),
-        ModelType::Linear => Vec::with_capacity(0),
+        ModelType::Linear | ModelType::Fisheye4 => Vec::with_capacity(0),
         ModelType::ExtrinsicsOnly => {
             let mut p = vec![i.fx(), i.fy(), i.cx(), i.cy()];
             p.extend(i.distortion.opencv_vec().as_slice());
@@ -348,6 +644,15 @@ fn to_params<F: na::RealField + Float>(
             p.extend(&[cc.x, cc.y, cc.z]);
             p
         }
+        ModelType::Fisheye4 => {
+            // The fisheye distortion is not part of the camera and is set by
+            // `BundleAdjuster::set_fisheye_distortion`.
+            let mut p = vec![i.fx(), i.cx(), i.cy()];
+            p.extend(&[F::zero(); 4]);
+            p.extend(&[abc.x, abc.y, abc.z]);
+            p.extend(&[cc.x, cc.y, cc.z]);
+            p
+        }
     }
 }
 
@@ -372,7 +677,7 @@ fn to_cam<F: na::RealField + Float>(
             (&mut distortion[0..nd]).copy_from_slice(&params[3..3 + nd]);
             (fx, fy, cx, cy)
         }
-        ModelType::Linear => {
+        ModelType::Linear | ModelType::Fisheye4 => {
             let fx = params[0];
             let fy = fx;
             let cx = params[1];
@@ -496,14 +801,10 @@ fn test_extrinsics_f64() {
     approx::assert_relative_eq!(orig.pose(), converted.pose(), epsilon = 1e-7);
 }
 
-impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn, Dyn>
-    for BundleAdjuster<F>
-{
-    type ParameterStorage = Owned<F, Dyn>;
-    type ResidualStorage = Owned<F, Dyn>;
-    type JacobianStorage = Owned<F, Dyn, Dyn>;
-
-    fn set_params(&mut self, x: &na::DVector<F>) {
+impl<F: na::RealField + Float> BundleAdjuster<F> {
+    /// Set all parameters, including fixed ones, and update the cameras and
+    /// points.
+    fn set_full_params(&mut self, x: &na::DVector<F>) {
         let allow_rerun_undistorted = !self.force_rerun_distorted;
         if let Some(rec) = &self.rec {
             rec.set_time_sequence("tick", self.rr_tick);
@@ -535,6 +836,14 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
             let cam = to_cam(params, self.model_type, fixed_params);
             cams.push(cam);
         }
+        let fisheye: Vec<_> = if self.model_type == ModelType::Fisheye4 {
+            cam_params
+                .chunks_exact(num_cam_params)
+                .map(fisheye_from_params)
+                .collect()
+        } else {
+            Vec::new()
+        };
 
         let points = na::Matrix3xX::from_column_slice(point_params);
 
@@ -557,10 +866,11 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
             .unwrap();
 
             // For each camera
-            for ((cam, cam_name), cam_dims) in cams
+            for (cam_i, ((cam, cam_name), cam_dims)) in cams
                 .iter()
                 .zip(self.cam_names.iter())
                 .zip(self.cam_dims.iter())
+                .enumerate()
             {
                 // Log pinhole in rerun 3D space.
                 use braid_mvg::rerun_io::AsRerunTransform3D;
@@ -575,7 +885,8 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
                 let (w, h) = cam_dims;
 
                 let i = cam.intrinsics();
-                if allow_rerun_undistorted && !i.distortion.is_linear() {
+                let has_distortion = !i.distortion.is_linear() || !fisheye.is_empty();
+                if allow_rerun_undistorted && has_distortion {
                     // Drop distortions to log to rerun. See https://github.com/rerun-io/rerun/issues/2499
                     if !self.did_show_rerun_warning {
                         tracing::warn!("Not showing distortions in rerun. See https://github.com/rerun-io/rerun/issues/2499");
@@ -597,9 +908,12 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
                 if allow_rerun_undistorted {
                     // TODO: confirm that `intrinsics_linear` is equal to
                     // `cam.intrinsics()`. Probably it won't be while 2499 is open.
-                    let pinhole =
-                        braid_mvg::rerun_io::cam_geom_to_rr_pinhole_archetype(&intrinsics_linear, *w, *h)
-                            .unwrap();
+                    let pinhole = braid_mvg::rerun_io::cam_geom_to_rr_pinhole_archetype(
+                        &intrinsics_linear,
+                        *w,
+                        *h,
+                    )
+                    .unwrap();
                     rec.log(raw_path.as_str(), &pinhole).unwrap();
                 }
                 let cam_linear = cam_geom::Camera::new(intrinsics_linear, extrinsics.clone());
@@ -625,7 +939,7 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
                     let predicted = if allow_rerun_undistorted {
                         cam_linear.world_to_pixel(&pts).data.transpose()
                     } else {
-                        cam.world_to_pixel(&pts).data.transpose()
+                        world_to_pixel(cam, fisheye.get(cam_i), &pt.into_owned())
                     };
                     xy.push((
                         predicted[(0, 0)].to_f32().unwrap(),
@@ -657,11 +971,44 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
         }
 
         self.cams = cams;
+        self.fisheye = fisheye;
         self.points = points;
     }
+}
+
+impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn, Dyn>
+    for BundleAdjuster<F>
+{
+    type ParameterStorage = Owned<F, Dyn>;
+    type ResidualStorage = Owned<F, Dyn>;
+    type JacobianStorage = Owned<F, Dyn, Dyn>;
+
+    fn set_params(&mut self, x: &na::DVector<F>) {
+        if !self.has_fixed_params() {
+            self.set_full_params(x);
+            return;
+        }
+        // Only the free parameters are seen by the optimizer.
+        let mut full = self.params_cache.clone();
+        for (dest, idx) in full.iter_mut().zip(self.free_param_index()) {
+            if let Some(idx) = idx {
+                *dest = x[idx];
+            }
+        }
+        self.set_full_params(&full);
+    }
 
     fn params(&self) -> na::DVector<F> {
-        self.params_cache.clone()
+        if !self.has_fixed_params() {
+            return self.params_cache.clone();
+        }
+        let values: Vec<F> = self
+            .params_cache
+            .iter()
+            .zip(self.free_param_index())
+            .filter_map(|(value, idx)| idx.map(|_| *value))
+            .collect();
+        values.into()
     }
 
     fn residuals(&self) -> Option<na::DVector<F>> {
@@ -673,9 +1020,8 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
             .zip(self.pt_idx.iter())
         {
             let cam = &self.cams[usize(*cam_idx)];
-            let pt = self.points.column(*pt_idx);
-            let pts = cam_geom::Points::new(pt.transpose());
-            let predicted = cam.world_to_pixel(&pts).data.transpose();
+            let pt = self.points.column(*pt_idx).into_owned();
+            let predicted = world_to_pixel(cam, self.fisheye.get(usize(*cam_idx)), &pt);
             let diff = obs - predicted;
             if false {
                 dbg!(self.rr_tick);
@@ -699,8 +1045,10 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
                 ));
             }
             // panic!("done");
-            residuals.push(diff.x);
-            residuals.push(diff.y);
+            // Scale so that the squared norm is the robust loss.
+            let (g, _) = self.loss.scaling(&diff, self.loss_scale);
+            residuals.push(diff.x * g);
+            residuals.push(diff.y * g);
         }
         debug_assert_eq!(residuals.len(), self.nresid);
         let residuals = na::DVector::from_column_slice(&residuals);
@@ -710,7 +1058,9 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
 
     fn jacobian(&self) -> Option<na::Matrix<F, Dyn, Dyn, Self::JacobianStorage>> {
         let num_cam_params = self.model_type.info().num_cam_params();
-        let mut j = na::OMatrix::<F, Dyn, Dyn>::zeros(self.nresid, self.params_cache.len());
+        let free_index = self.free_param_index();
+        let num_free = free_index.iter().flatten().count();
+        let mut j = na::OMatrix::<F, Dyn, Dyn>::zeros(self.nresid, num_free);
 
         // Iterate over all observed points.
         for (obs_idx, (cam_idx, pt_idx)) in self.cam_idx.iter().zip(self.pt_idx.iter()).enumerate()
@@ -718,23 +1068,22 @@ impl<F: na::RealField + Float> levenberg_marquardt::LeastSquaresProblem<F, Dyn,
             // we have 2 entries in the residual vector per observation
             let ridx = obs_idx * 2;
 
-            // Identify the sub-matrix for the camera parameters.
-            let cam_start = (ridx, usize(*cam_idx) * num_cam_params);
-            let cam_geom = (2, num_cam_params);
-
-            // Identify the sub-matrix for the 3D world coordinate point.
+            // Identify the columns for the camera parameters and the 3D world
+            // coordinate point.
+            let cam_col = usize(*cam_idx) * num_cam_params;
             let pt_col = usize(self.num_cams) * num_cam_params + pt_idx * 3;
-            let pt_start = (ridx, pt_col);
-            let pt_geom = (2, 3);
-            // Compute jacobian for these camera parameters and world point.
-            self.model_type.eval_cam_jacobians(
-                &self,
-                *cam_idx,
-                *pt_idx,
-                &mut j,
-                (cam_start, cam_geom),
-            );
-            self.eval_pt_jacobians(*cam_idx, *pt_idx, &mut j, (pt_start, pt_geom));
+            let cols = (cam_col..cam_col + num_cam_params).chain(pt_col..pt_col + 3);
+
+            // Compute jacobian for these camera parameters and world point
+            // and copy it to the columns of the free parameters.
+            let (r, j_obs) = self.observation_jacobian(obs_idx);
+            let j_obs = self.loss.scale_jacobian(&r, self.loss_scale, j_obs);
+            for (k, col) in cols.enumerate() {
+                if let Some(col) = free_index[col] {
+                    j[(ridx, col)] = j_obs[(0, k)];
+                    j[(ridx + 1, col)] = j_obs[(1, k)];
+                }
+            }
         }
         Some(j)
     }
@@ -932,6 +1281,9 @@ impl ModelType {
         let three: F = na::convert(3.0);
 
         match self {
+            ModelType::Fisheye4 => {
+                unreachable!("the fisheye jacobian is computed numerically");
+            }
             ModelType::OpenCV5 => {
                 let f = i.fx(); // we checked in the constructor that fx == fy
                 #[cfg_attr(any(), rustfmt::skip)]
@@ -1867,6 +2219,7 @@ impl ModelType {
 #[cfg(test)]
 mod test {
     use super::*;
+    use braid_mvg::test_util::{camera_looking_at, camera_ring};
     use rand::distributions::Distribution;
     use rand_distr::Normal;
 
@@ -1952,6 +2305,276 @@ mod test {
         assert!(report.termination.was_successful());
     }
 
+    #[test]
+    fn test_robust_jacobian() {
+        use levenberg_marquardt::LeastSquaresProblem;
+
+        let points = na::Matrix3xX::<f64>::from_column_slice(&[
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0,
+        ]);
+        let labels3d: Vec<String> = (0..points.ncols()).map(|i| format!("pt {i}")).collect();
+        let cam_params = [
+            [
+                1.0, 2.0, 3.0, 0.01, 0.001, -0.01, -0.001, 0.0001, 0.0, 1.0, 0.0, 7.0, 8.0, 9.0,
+            ],
+            [
+                1.1, 2.1, 3.1, 0.01, 0.001, -0.01, -0.001, 0.0001, 0.0, 0.0, 1.0, 7.1, 8.1, 9.1,
+            ],
+            [
+                1.2, 2.2, 3.2, 0.01, 0.001, -0.01, -0.001, 0.0001, 0.0, 0.4, 0.5, 7.2, 8.2, 9.2,
+            ],
+        ];
+        let cams: Vec<_> = cam_params
+            .iter()
+            .map(|p| to_cam(p, ModelType::OpenCV5, &[]))
+            .collect();
+        let cam_names = (0..cams.len()).map(|i| format!("Cam {i}")).collect();
+        let cam_dims = cams.iter().map(|_cam| (640, 480)).collect();
+
+        let mut observed_raw = vec![];
+        let mut cam_idx = vec![];
+        let mut pt_idx = vec![];
+        for (ipt_idx, point) in points.column_iter().enumerate() {
+            for (icam_idx, cam) in cams.iter().enumerate() {
+                let pts = cam_geom::Points::new(point.transpose());
+                let observed = cam.world_to_pixel(&pts).data;
+                observed_raw.push(observed.x);
+                observed_raw.push(observed.y);
+                cam_idx.push(icam_idx.try_into().unwrap());
+                pt_idx.push(ipt_idx);
+            }
+        }
+        let observed = na::Matrix2xX::from_column_slice(&observed_raw);
+        let noisy = &observed + standard_normal(observed.nrows(), observed.ncols());
+
+        let ba = BundleAdjuster::<f64>::new(
+            noisy,
+            cam_idx,
+            pt_idx,
+            cam_names,
+            cam_dims,
+            cams,
+            points,
+            labels3d,
+            ModelType::OpenCV5,
+            None,
+            false,
+        )
+        .unwrap();
+
+        for loss in [RobustLoss::Squared, RobustLoss::Huber, RobustLoss::Cauchy] {
+            let mut ba = ba.clone();
+            ba.set_loss(loss, 0.5).unwrap();
+            ba.set_fixed_cams(&[1]).unwrap();
+            ba.set_fixed_points(&[2]).unwrap();
+            let num_free = 2 * ModelType::OpenCV5.info().num_cam_params() + 3 * 3;
+            assert_eq!(ba.params().len(), num_free);
+
+            let jacobian_numerical =
+                levenberg_marquardt::differentiate_numerically(&mut ba).unwrap();
+            let jacobian_trait = ba.jacobian().unwrap();
+            approx::assert_relative_eq!(jacobian_numerical, jacobian_trait, epsilon = 1e-6);
+
+            // The squared norm of the residuals is twice the cost.
+            approx::assert_relative_eq!(
+                ba.residuals().unwrap().norm_squared(),
+                2.0 * ba.cost(),
+                epsilon = 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn test_fisheye_jacobian() {
+        use levenberg_marquardt::LeastSquaresProblem;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let cams: Vec<_> = (0..3)
+            .map(|i| {
+                let angle = i as f64 * 2.0;
+                let camcenter = na::Vector3::new(2.0 * angle.cos(), 2.0 * angle.sin(), 0.5);
+                let intrinsics = RosOpenCvIntrinsics::from_params(300.0, 0.0, 300.0, 640.0, 512.0);
+                let cam =
+                    camera_looking_at(1280, 1024, camcenter, na::Vector3::zeros(), intrinsics);
+                cam_geom::Camera::new(cam.intrinsics().clone(), cam.extrinsics().clone())
+            })
+            .collect();
+        let fisheye = vec![
+            FisheyeDistortion::new(-0.013, 0.021, -0.012, 0.002),
+            FisheyeDistortion::new(0.02, -0.01, 0.003, 0.0),
+            FisheyeDistortion::zero(),
+        ];
+        let points = na::Matrix3xX::<f64>::from_fn(10, |_, _| rng.gen_range(-0.5..0.5));
+        let labels3d: Vec<String> = (0..points.ncols()).map(|i| format!("pt {i}")).collect();
+
+        let mut observed_raw = vec![];
+        let mut cam_idx = vec![];
+        let mut pt_idx = vec![];
+        for (ipt_idx, point) in points.column_iter().enumerate() {
+            for (icam_idx, cam) in cams.iter().enumerate() {
+                let observed = world_to_pixel(cam, Some(&fisheye[icam_idx]), &point.into_owned());
+                observed_raw.push(observed.x);
+                observed_raw.push(observed.y);
+                cam_idx.push(icam_idx.try_into().unwrap());
+                pt_idx.push(ipt_idx);
+            }
+        }
+        let observed = na::Matrix2xX::from_column_slice(&observed_raw);
+
+        let mut ba = BundleAdjuster::<f64>::new(
+            observed.clone(),
+            cam_idx,
+            pt_idx,
+            (0..cams.len()).map(|i| format!("Cam {i}")).collect(),
+            cams.iter().map(|_cam| (1280, 1024)).collect(),
+            cams.clone(),
+            points,
+            labels3d,
+            ModelType::Fisheye4,
+            None,
+            false,
+        )
+        .unwrap();
+        ba.set_fisheye_distortion(&fisheye).unwrap();
+        assert_eq!(ba.fisheye_distortion(), &fisheye[..]);
+        assert!(ba.cost() < 1e-12);
+
+        ba.set_fixed_cams(&[0]).unwrap();
+        let noisy = &observed + standard_normal(observed.nrows(), observed.ncols());
+        ba.observed = noisy;
+        let jacobian_numerical = levenberg_marquardt::differentiate_numerically(&mut ba).unwrap();
+        let jacobian_trait = ba.jacobian().unwrap();
+        approx::assert_relative_eq!(jacobian_numerical, jacobian_trait, epsilon = 1e-4);
+
+        // Cameras with Brown-Conrady distortion are rejected.
+        let mut distorted = cams.clone();
+        let mut i = distorted[0].intrinsics().clone();
+        i.distortion = opencv_ros_camera::Distortion::from_opencv_vec(na::Vector5::new(
+            0.1, 0.0, 0.0, 0.0, 0.0,
+        ));
+        distorted[0] = cam_geom::Camera::new(i, distorted[0].extrinsics().clone());
+        assert!(BundleAdjuster::<f64>::new(
+            observed,
+            ba.cam_idx.clone(),
+            ba.pt_idx.clone(),
+            ba.cam_names.clone(),
+            ba.cam_dims.clone(),
+            distorted,
+            ba.points.clone(),
+            ba.labels3d.clone(),
+            ModelType::Fisheye4,
+            None,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sparse_solver() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1234);
+        let noise = Normal::new(0.0, 0.3).unwrap();
+
+        // Four cameras around the origin.
+        let intrinsics = RosOpenCvIntrinsics::from_params(1000.0, 0.0, 1000.0, 640.0, 512.0);
+        let cams: Vec<_> = camera_ring(4, 3.0, 1.0)
+            .cams_by_name()
+            .values()
+            .map(|cam| cam_geom::Camera::new(intrinsics.clone(), cam.extrinsics().clone()))
+            .collect();
+        let points = na::Matrix3xX::<f64>::from_fn(60, |_, _| rng.gen_range(-0.5..0.5));
+        let labels3d: Vec<String> = (0..points.ncols()).map(|i| format!("pt {i}")).collect();
+
+        // Observe every point in every camera. Every 20th observation is an
+        // outlier.
+        let mut observed_raw = vec![];
+        let mut cam_idx = vec![];
+        let mut pt_idx = vec![];
+        let mut is_outlier = vec![];
+        for (ipt_idx, point) in points.column_iter().enumerate() {
+            for (icam_idx, cam) in cams.iter().enumerate() {
+                let pts = cam_geom::Points::new(point.transpose());
+                let observed = cam.world_to_pixel(&pts).data;
+                let outlier = cam_idx.len() % 20 == 0;
+                let (dx, dy) = if outlier {
+                    (40.0, -30.0)
+                } else {
+                    (noise.sample(&mut rng), noise.sample(&mut rng))
+                };
+                observed_raw.push(observed.x + dx);
+                observed_raw.push(observed.y + dy);
+                cam_idx.push(icam_idx.try_into().unwrap());
+                pt_idx.push(ipt_idx);
+                is_outlier.push(outlier);
+            }
+        }
+        let observed = na::Matrix2xX::from_column_slice(&observed_raw);
+
+        // Perturb the initial guess, except for the first two cameras which
+        // are held fixed to define the coordinate frame.
+        let cams0: Vec<_> = cams
+            .iter()
+            .enumerate()
+            .map(|(i, cam)| {
+                if i < 2 {
+                    return cam.clone();
+                }
+                let camcenter = cam.extrinsics().camcenter() + na::Vector3::new(0.05, -0.05, 0.02);
+                let extrinsics = cam_geom::ExtrinsicParameters::from_rotation_and_camcenter(
+                    cam.extrinsics().pose().rotation,
+                    camcenter,
+                );
+                cam_geom::Camera::new(cam.intrinsics().clone(), extrinsics)
+            })
+            .collect();
+        let points0 = points.map(|x| x + rng.gen_range(-0.02..0.02));
+
+        let mut ba = BundleAdjuster::<f64>::new(
+            observed,
+            cam_idx,
+            pt_idx,
+            (0..cams.len()).map(|i| format!("Cam {i}")).collect(),
+            cams.iter().map(|_cam| (1280, 1024)).collect(),
+            cams0,
+            points0,
+            labels3d,
+            ModelType::ExtrinsicsOnly,
+            None,
+            false,
+        )
+        .unwrap();
+        ba.set_loss(RobustLoss::Cauchy, 2.0).unwrap();
+        ba.set_fixed_cams(&[0, 1]).unwrap();
+
+        let (sparse, report) = SparseSolver::new().minimize(ba.clone());
+        assert!(report.termination.was_successful(), "{report:?}");
+        assert!(report.final_cost < report.initial_cost);
+
+        // The outliers are found and the points recovered.
+        for (res, outlier) in sparse.observation_residuals().iter().zip(is_outlier) {
+            if outlier {
+                assert!(res.weight < 0.1);
+            } else {
+                assert!(res.residual.norm() < 2.0);
+            }
+        }
+        approx::assert_relative_eq!(sparse.points(), &points, epsilon = 5e-3);
+        for (expected, actual) in ba.cams().iter().zip(sparse.cams()).take(2) {
+            assert_eq!(
+                expected.extrinsics().camcenter(),
+                actual.extrinsics().camcenter()
+            );
+        }
+
+        // The dense solver finds the same solution.
+        let (dense, report) = levenberg_marquardt::LevenbergMarquardt::new().minimize(ba);
+        assert!(report.termination.was_successful());
+        approx::assert_relative_eq!(dense.cost(), sparse.cost(), max_relative = 1e-4);
+        approx::assert_relative_eq!(dense.points(), sparse.points(), epsilon = 1e-4);
+    }
+
     fn standard_normal<Real: na::RealField>(
         nrows: usize,
         ncols: usize,
@@ -0,0 +1,128 @@
+use nalgebra as na;
+use num_traits::Float;
+
+/// Loss applied to the reprojection error of each observation.
+///
+/// With a robust loss, observations with large reprojection errors (for
+/// example, mis-associated points) have less influence on the solution. The
+/// loss is a function `rho(s)` of the squared reprojection error `s` of an
+/// observation, in pixels squared, and is parameterized by a scale `c`, in
+/// pixels, above which errors are considered outliers.
+#[derive(Clone, Debug, PartialEq, Copy, clap::ValueEnum, Default)]
+pub enum RobustLoss {
+    /// Ordinary least squares, `rho(s) = s`. The scale is not used.
+    #[default]
+    Squared,
+    /// Quadratic for errors up to the scale and linear above it, `rho(s) = s`
+    /// for `s <= c^2`, otherwise `rho(s) = 2 c sqrt(s) - c^2`.
+    Huber,
+    /// Logarithmic growth for errors above the scale, `rho(s) = c^2 ln(1 +
+    /// s/c^2)`.
+    Cauchy,
+}
+
+impl RobustLoss {
+    /// Evaluate `rho(s)` and its derivative `rho'(s)`.
+    ///
+    /// `rho'(s)` is the weight of the observation in iteratively reweighted
+    /// least squares.
+    pub fn eval<F: na::RealField + Float>(&self, s: F, c: F) -> (F, F) {
+        let one = F::one();
+        match self {
+            RobustLoss::Squared => (s, one),
+            RobustLoss::Huber => {
+                let c2 = c * c;
+                if s <= c2 {
+                    (s, one)
+                } else {
+                    let r = Float::sqrt(s);
+                    (na::convert::<_, F>(2.0) * c * r - c2, c / r)
+                }
+            }
+            RobustLoss::Cauchy => {
+                let c2 = c * c;
+                let u = s / c2;
+                (c2 * Float::ln_1p(u), one / (one + u))
+            }
+        }
+    }
+
+    /// Factors to rescale a residual vector `r` and its jacobian `J`.
+    ///
+    /// Returns `(g, h)` such that the scaled residual `g r` has squared norm
+    /// `rho(|r|^2)` and its jacobian is `g J + h r r^T J`. Minimizing the sum
+    /// of the squared scaled residuals is thus minimizing the robust cost.
+    pub(crate) fn scaling<F: na::RealField + Float>(&self, r: &na::Vector2<F>, c: F) -> (F, F) {
+        let one = F::one();
+        let zero = F::zero();
+        let s = r.norm_squared();
+        match self {
+            RobustLoss::Squared => return (one, zero),
+            RobustLoss::Huber if s <= c * c => return (one, zero),
+            RobustLoss::Cauchy if s <= na::convert::<_, F>(1e-8) * c * c => {
+                // Limit as `s` goes to zero, where the expression below
+                // suffers from cancellation.
+                return (one, -one / (na::convert::<_, F>(2.0) * c * c));
+            }
+            _ => {}
+        }
+        let (rho, drho) = self.eval(s, c);
+        // g(s) = sqrt(rho(s) / s), h = 2 g'(s)
+        let g = Float::sqrt(rho / s);
+        let h = (drho * s - rho) / (s * s * g);
+        (g, h)
+    }
+
+    /// Scale the jacobian `j` of the residual vector `r`.
+    ///
+    /// See [Self::scaling].
+    pub(crate) fn scale_jacobian<F: na::RealField + Float>(
+        &self,
+        r: &na::Vector2<F>,
+        c: F,
+        j: na::OMatrix<F, na::U2, na::Dyn>,
+    ) -> na::OMatrix<F, na::U2, na::Dyn> {
+        let (g, h) = self.scaling(r, c);
+        if h == F::zero() {
+            return j * g;
+        }
+        let rrt_j = r * (r.transpose() * &j);
+        j * g + rrt_j * h
+    }
+}
+
+#[test]
+fn test_loss_scaling() {
+    // Compare the jacobian of the scaled residual with finite differences,
+    // where the jacobian of `r` is the identity.
+    for loss in [RobustLoss::Squared, RobustLoss::Huber, RobustLoss::Cauchy] {
+        for r in [
+            na::Vector2::new(0.1, -0.2),
+            na::Vector2::new(3.0, 4.0),
+            na::Vector2::new(-30.0, 2.0),
+        ] {
+            let c = 2.0;
+            let scaled = |r: &na::Vector2<f64>| r * loss.scaling(r, c).0;
+            let (g, h) = loss.scaling(&r, c);
+            approx::assert_relative_eq!(
+                scaled(&r).norm_squared(),
+                loss.eval(r.norm_squared(), c).0,
+                epsilon = 1e-12
+            );
+            let analytic = na::Matrix2::identity() * g + r * r.transpose() * h;
+            let eps = 1e-6;
+            for k in 0..2 {
+                let mut rp = r;
+                let mut rm = r;
+                rp[k] += eps;
+                rm[k] -= eps;
+                let numerical = (scaled(&rp) - scaled(&rm)) / (2.0 * eps);
+                approx::assert_relative_eq!(
+                    numerical,
+                    analytic.column(k).into_owned(),
+                    epsilon = 1e-6
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! Sparse Levenberg-Marquardt solver using the Schur complement.
+//!
+//! Each observation depends on the parameters of a single camera and a single
+//! 3D point, so the normal equations have a block structure:
+//!
+//! ```text
+//! [ U   W ] [dc]   [-gc]
+//! [ W^T V ] [dp] = [-gp]
+//! ```
+//!
+//! where `U` is block diagonal with one block per camera and `V` is block
+//! diagonal with one 3x3 block per point. The points are eliminated to give
+//! the reduced camera system `(U - W V^-1 W^T) dc = -gc + W V^-1 gp`, whose
+//! size depends only on the number of cameras. The point updates follow by
+//! back substitution. The cost of an iteration is thus linear in the number of
+//! points rather than cubic as with a dense jacobian.
+
+use nalgebra::{self as na, Dyn, U3};
+use num_traits::Float;
+
+use crate::{usize, BundleAdjuster};
+
+/// Minimum diagonal value used to scale the damping of a parameter.
+const MIN_DIAGONAL: f64 = 1e-6;
+
+/// Damping above which the solver gives up.
+const MAX_DAMPING: f64 = 1e32;
+
+/// Sparse Levenberg-Marquardt solver for [BundleAdjuster].
+///
+/// This is an alternative to [levenberg_marquardt::LevenbergMarquardt] which
+/// exploits the structure of bundle adjustment problems and therefore scales
+/// to many more 3D points.
+#[derive(Debug, Clone)]
+pub struct SparseSolver {
+    /// Maximum number of iterations.
+    pub max_iterations: usize,
+    /// Stop when a step reduces the cost by less than this fraction.
+    pub function_tolerance: f64,
+    /// Stop when the norm of a step is less than this fraction of the norm of
+    /// the parameters.
+    pub parameter_tolerance: f64,
+    /// Stop when the largest element of the gradient is less than this.
+    pub gradient_tolerance: f64,
+    /// Initial damping, relative to the diagonal of the normal equations.
+    pub initial_damping: f64,
+}
+
+impl Default for SparseSolver {
+    fn default() -> Self {
+        Self {
+            max_iterations: 100,
+            function_tolerance: 1e-10,
+            parameter_tolerance: 1e-10,
+            gradient_tolerance: 1e-12,
+            initial_damping: 1e-4,
+        }
+    }
+}
+
+/// Why [SparseSolver::minimize] stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SparseTermination {
+    /// The relative reduction of the cost was below the function tolerance.
+    FunctionTolerance,
+    /// The step size was below the parameter tolerance.
+    ParameterTolerance,
+    /// The gradient was below the gradient tolerance.
+    GradientTolerance,
+    /// The maximum number of iterations was reached.
+    MaxIterations,
+    /// No step reducing the cost could be found.
+    NoImprovement,
+    /// The cost or the parameters are not finite.
+    Numerical,
+}
+
+impl SparseTermination {
+    pub fn was_successful(&self) -> bool {
+        matches!(
+            self,
+            SparseTermination::FunctionTolerance
+                | SparseTermination::ParameterTolerance
+                | SparseTermination::GradientTolerance
+        )
+    }
+}
+
+/// Summary of a run of [SparseSolver::minimize].
+#[derive(Debug, Clone)]
+pub struct SparseReport<F> {
+    pub termination: SparseTermination,
+    /// Number of iterations, including rejected steps.
+    pub iterations: usize,
+    /// Cost, half the sum of the robust loss of all observations, before
+    /// optimization.
+    pub initial_cost: F,
+    /// Cost after optimization.
+    pub final_cost: F,
+}
+
+/// Off-diagonal blocks of the normal equations for a point, with the index of
+/// the free camera of each.
+type PointCamBlocks<F> = Vec<(usize, na::OMatrix<F, Dyn, U3>)>;
+
+/// The normal equations, split into camera and point blocks.
+struct NormalEquations<F: na::RealField> {
+    /// Diagonal blocks for the free cameras.
+    u: Vec<na::DMatrix<F>>,
+    /// Diagonal blocks for the free points.
+    v: Vec<na::Matrix3<F>>,
+    /// Off-diagonal blocks for each free point.
+    w: Vec<PointCamBlocks<F>>,
+    /// Gradient for the free cameras.
+    gc: Vec<na::DVector<F>>,
+    /// Gradient for the free points.
+    gp: Vec<na::Vector3<F>>,
+}
+
+impl SparseSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Minimize the cost of a bundle adjustment problem.
+    ///
+    /// Returns the problem with its parameters set to the solution.
+    pub fn minimize<F: na::RealField + Float>(
+        &self,
+        mut ba: BundleAdjuster<F>,
+    ) -> (BundleAdjuster<F>, SparseReport<F>) {
+        let ncp = ba.model_type.info().num_cam_params();
+        let num_cams = usize(ba.num_cams);
+        let pt_offset = num_cams * ncp;
+
+        // Indices of the free cameras and points in the reduced problem.
+        let free_index = |fixed: &[bool]| {
+            let mut n = 0;
+            let idx: Vec<Option<usize>> = fixed
+                .iter()
+                .map(|is_fixed| {
+                    if *is_fixed {
+                        None
+                    } else {
+                        n += 1;
+                        Some(n - 1)
+                    }
+                })
+                .collect();
+            (idx, n)
+        };
+        let (cam_index, n_free_cams) = free_index(&ba.fixed_cams);
+        let (pt_index, n_free_pts) = free_index(&ba.fixed_points);
+
+        let mut cost = ba.cost();
+        let initial_cost = cost;
+        let mut damping: F = na::convert(self.initial_damping);
+        let mut damping_factor: F = na::convert(2.0);
+        let min_diagonal: F = na::convert(MIN_DIAGONAL);
+        let max_damping: F = na::convert(MAX_DAMPING);
+        let zero = F::zero();
+        let one = F::one();
+        let two: F = na::convert(2.0);
+        let three: F = na::convert(3.0);
+
+        let mut iterations = 0;
+        let mut eqs = None;
+        let termination = loop {
+            if !Float::is_finite(cost) {
+                break SparseTermination::Numerical;
+            }
+            if iterations >= self.max_iterations {
+                break SparseTermination::MaxIterations;
+            }
+            iterations += 1;
+
+            // Only rebuild the normal equations after a successful step.
+            let ne: &NormalEquations<F> = eqs.get_or_insert_with(|| {
+                build_normal_equations(&ba, &cam_index, n_free_cams, &pt_index, n_free_pts)
+            });
+            let max_gradient = ne
+                .gc
+                .iter()
+                .flat_map(|g| g.iter())
+                .chain(ne.gp.iter().flat_map(|g| g.iter()))
+                .fold(zero, |acc, x| Float::max(acc, Float::abs(*x)));
+            if max_gradient <= na::convert(self.gradient_tolerance) {
+                break SparseTermination::GradientTolerance;
+            }
+
+            // Damp the diagonal blocks.
+            let damp = |x: F| damping * Float::max(x, min_diagonal);
+            let du: Vec<na::DVector<F>> = ne.u.iter().map(|u| u.diagonal().map(damp)).collect();
+            let dv: Vec<na::Vector3<F>> = ne.v.iter().map(|v| v.diagonal().map(damp)).collect();
+
+            let Some((dc, dp)) = solve(ne, &du, &dv, ncp) else {
+                // Not positive definite. Increase the damping and retry.
+                damping *= damping_factor;
+                damping_factor *= two;
+                if damping > max_damping {
+                    break SparseTermination::NoImprovement;
+                }
+                continue;
+            };
+
+            // Predicted cost reduction of the linearized problem,
+            // `(-d^T g + d^T D d) / 2` where `D` is the damping.
+            let mut predicted = zero;
+            for ((d, g), dd) in dc.iter().zip(ne.gc.iter()).zip(du.iter()) {
+                predicted += -d.dot(g) + d.dot(&d.component_mul(dd));
+            }
+            for ((d, g), dd) in dp.iter().zip(ne.gp.iter()).zip(dv.iter()) {
+                predicted += -d.dot(g) + d.dot(&d.component_mul(dd));
+            }
+            predicted /= two;
+
+            // Apply the step.
+            let old_params = ba.params_cache.clone();
+            let mut new_params = old_params.clone();
+            for (cam, idx) in cam_index.iter().enumerate() {
+                if let Some(idx) = idx {
+                    let mut p = new_params.rows_mut(cam * ncp, ncp);
+                    p += &dc[*idx];
+                }
+            }
+            for (pt, idx) in pt_index.iter().enumerate() {
+                if let Some(idx) = idx {
+                    let mut p = new_params.fixed_rows_mut::<3>(pt_offset + pt * 3);
+                    p += &dp[*idx];
+                }
+            }
+            let step_norm = Float::sqrt(
+                dc.iter().map(|d| d.norm_squared()).fold(zero, |a, b| a + b)
+                    + dp.iter().map(|d| d.norm_squared()).fold(zero, |a, b| a + b),
+            );
+            let xtol: F = na::convert(self.parameter_tolerance);
+            if step_norm <= xtol * (old_params.norm() + xtol) {
+                break SparseTermination::ParameterTolerance;
+            }
+
+            ba.set_full_params(&new_params);
+            let new_cost = ba.cost();
+            let actual = cost - new_cost;
+            if Float::is_finite(new_cost) && actual > zero {
+                // Accept the step and update the damping following Nielsen
+                // (1999).
+                let rho = if predicted > zero {
+                    actual / predicted
+                } else {
+                    one
+                };
+                let t = two * rho - one;
+                damping *= Float::max(one / three, one - t * t * t);
+                damping_factor = two;
+                eqs = None;
+                let converged = actual <= na::convert::<_, F>(self.function_tolerance) * cost;
+                cost = new_cost;
+                if converged {
+                    break SparseTermination::FunctionTolerance;
+                }
+            } else {
+                // Reject the step.
+                ba.set_full_params(&old_params);
+                damping *= damping_factor;
+                damping_factor *= two;
+                if damping > max_damping {
+                    break SparseTermination::NoImprovement;
+                }
+            }
+        };
+
+        let report = SparseReport {
+            termination,
+            iterations,
+            initial_cost,
+            final_cost: cost,
+        };
+        (ba, report)
+    }
+}
+
+/// Accumulate the normal equations at the current parameters.
+fn build_normal_equations<F: na::RealField + Float>(
+    ba: &BundleAdjuster<F>,
+    cam_index: &[Option<usize>],
+    n_free_cams: usize,
+    pt_index: &[Option<usize>],
+    n_free_pts: usize,
+) -> NormalEquations<F> {
+    let ncp = ba.model_type.info().num_cam_params();
+    let mut u = vec![na::DMatrix::zeros(ncp, ncp); n_free_cams];
+    let mut v = vec![na::Matrix3::zeros(); n_free_pts];
+    let mut w: Vec<PointCamBlocks<F>> = vec![Vec::new(); n_free_pts];
+    let mut gc = vec![na::DVector::zeros(ncp); n_free_cams];
+    let mut gp = vec![na::Vector3::zeros(); n_free_pts];
+
+    for (obs_idx, (cam, pt)) in ba.cam_idx.iter().zip(ba.pt_idx.iter()).enumerate() {
+        let ci = cam_index[usize(*cam)];
+        let pi = pt_index[*pt];
+        if ci.is_none() && pi.is_none() {
+            continue;
+        }
+        let (r, j) = ba.observation_jacobian(obs_idx);
+        // Weight by `rho'(s)` as in iteratively reweighted least squares.
+        // This gives the exact gradient of the robust cost and a positive
+        // semi-definite approximation of its Hessian.
+        let (_, weight) = ba.loss.eval(r.norm_squared(), ba.loss_scale);
+        let sqrt_weight = Float::sqrt(weight);
+        let r = r * sqrt_weight;
+        let j = j * sqrt_weight;
+        let jc = j.columns(0, ncp);
+        let jp = j.fixed_columns::<3>(ncp);
+        if let Some(ci) = ci {
+            u[ci] += jc.transpose() * jc;
+            gc[ci] += jc.transpose() * r;
+        }
+        if let Some(pi) = pi {
+            v[pi] += jp.transpose() * jp;
+            gp[pi] += jp.transpose() * r;
+            if let Some(ci) = ci {
+                let wij = jc.transpose() * jp;
+                // A camera may observe the same point more than once.
+                match w[pi].iter_mut().find(|(c, _)| *c == ci) {
+                    Some((_, existing)) => *existing += wij,
+                    None => w[pi].push((ci, wij)),
+                }
+            }
+        }
+    }
+    NormalEquations { u, v, w, gc, gp }
+}
+
+/// Solve the damped normal equations.
+///
+/// `du` and `dv` are the damping added to the diagonals of the camera and
+/// point blocks. Returns the camera and point steps, or `None` if the system
+/// is not positive definite.
+#[allow(clippy::type_complexity)]
+fn solve<F: na::RealField + Float>(
+    ne: &NormalEquations<F>,
+    du: &[na::DVector<F>],
+    dv: &[na::Vector3<F>],
+    ncp: usize,
+) -> Option<(Vec<na::DVector<F>>, Vec<na::Vector3<F>>)> {
+    let n_free_cams = ne.u.len();
+
+    // Invert the damped point blocks.
+    let v_inv: Vec<na::Matrix3<F>> =
+        ne.v.iter()
+            .zip(dv.iter())
+            .map(|(v, d)| (v + na::Matrix3::from_diagonal(d)).try_inverse())
+            .collect::<Option<_>>()?;
+
+    // Form the reduced camera system.
+    let n = n_free_cams * ncp;
+    let mut s = na::DMatrix::zeros(n, n);
+    let mut b = na::DVector::zeros(n);
+    for (i, (u, d)) in ne.u.iter().zip(du.iter()).enumerate() {
+        let mut block = s.view_mut((i * ncp, i * ncp), (ncp, ncp));
+        block += u;
+        block.set_diagonal(&(block.diagonal() + d));
+        b.rows_mut(i * ncp, ncp).copy_from(&-&ne.gc[i]);
+    }
+    for ((w, v_inv), gp) in ne.w.iter().zip(v_inv.iter()).zip(ne.gp.iter()) {
+        for (ci, wi) in w.iter() {
+            let y = wi * v_inv;
+            let mut bi = b.rows_mut(ci * ncp, ncp);
+            bi += &y * gp;
+            for (cj, wj) in w.iter() {
+                let mut block = s.view_mut((ci * ncp, cj * ncp), (ncp, ncp));
+                block -= &y * wj.transpose();
+            }
+        }
+    }
+
+    let dc_all = if n > 0 { s.cholesky()?.solve(&b) } else { b };
+    let dc: Vec<na::DVector<F>> = (0..n_free_cams)
+        .map(|i| dc_all.rows(i * ncp, ncp).into_owned())
+        .collect();
+
+    // Back substitute for the points.
+    let dp =
+        ne.w.iter()
+            .zip(v_inv.iter())
+            .zip(ne.gp.iter())
+            .map(|((w, v_inv), gp)| {
+                let mut rhs = -gp;
+                for (ci, wi) in w.iter() {
+                    rhs -= wi.transpose() * &dc[*ci];
+                }
+                v_inv * rhs
+            })
+            .collect();
+    Some((dc, dp))
+}
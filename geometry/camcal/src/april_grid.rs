@@ -0,0 +1,190 @@
+//! Grids of AprilTags as calibration target.
+
+use crate::{Coords2D, PlanarView};
+
+/// A rectangular grid of AprilTags.
+///
+/// The tags are numbered row by row starting at `first_id` in the top left
+/// corner when the grid is upright. Object points are in units of the tag
+/// size, which is the width of the black border of a tag, with x to the right
+/// and y down.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AprilGrid {
+    rows: usize,
+    cols: usize,
+    spacing: f64,
+    first_id: i32,
+}
+
+impl AprilGrid {
+    /// Create a new grid description.
+    ///
+    /// `spacing` is the gap between neighbouring tags as a fraction of the tag
+    /// size.
+    pub fn new(rows: usize, cols: usize, spacing: f64, first_id: i32) -> Self {
+        Self {
+            rows,
+            cols,
+            spacing,
+            first_id,
+        }
+    }
+
+    /// The corners of tag `id` on the target, in the order returned by
+    /// `ads_apriltag::Detection::corners`, or `None` if the tag is not part of
+    /// the grid.
+    pub fn tag_corners(&self, id: i32) -> Option<[Coords2D; 4]> {
+        let idx = usize::try_from(id.checked_sub(self.first_id)?).ok()?;
+        if idx >= self.rows * self.cols {
+            return None;
+        }
+        let pitch = 1.0 + self.spacing;
+        let x = (idx % self.cols) as f64 * pitch + 0.5;
+        let y = (idx / self.cols) as f64 * pitch + 0.5;
+        Some([
+            (x - 0.5, y + 0.5),
+            (x + 0.5, y + 0.5),
+            (x + 0.5, y - 0.5),
+            (x - 0.5, y - 0.5),
+        ])
+    }
+
+    /// Collect the corners of all detected tags of the grid into a view.
+    ///
+    /// Detections with ids not in the grid and ids detected more than once are
+    /// ignored. Filtering by tag family or decoding quality is left to the
+    /// caller. Returns `None` if no tag of the grid was found.
+    pub fn view(&self, detections: &[ads_apriltag::Detection]) -> Option<PlanarView> {
+        let mut view = PlanarView {
+            object_points: Vec::new(),
+            image_points: Vec::new(),
+        };
+        for det in detections {
+            if detections.iter().filter(|d| d.id() == det.id()).count() != 1 {
+                continue;
+            }
+            if let Some(corners) = self.tag_corners(det.id()) {
+                view.object_points.extend(corners);
+                view.image_points
+                    .extend(det.corners().iter().map(|p| (p[0], p[1])));
+            }
+        }
+        (!view.object_points.is_empty()).then_some(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zhang::{self, Pinhole};
+    use ads_apriltag::{Detector, Family, ImageU8, ImageU8Owned};
+    use nalgebra::{Matrix3, Rotation3, Vector3};
+
+    /// Width of the black border of a 36h11 tag, in bits.
+    const WIDTH_AT_BORDER: f64 = 8.0;
+
+    #[test]
+    fn test_detect_april_grid() {
+        let (width, height) = (800, 600);
+        let k = Pinhole {
+            fx: 700.0,
+            fy: 700.0,
+            cx: 400.0,
+            cy: 300.0,
+        };
+        let (rows, cols, spacing) = (3, 4, 0.3);
+        let grid = AprilGrid::new(rows, cols, spacing, 5);
+        let pitch = 1.0 + spacing;
+
+        // The center of the grid is 10 tag sizes in front of the camera.
+        let rotation = Rotation3::from_euler_angles(0.2, -0.15, 0.1);
+        let center = Vector3::new(
+            0.5 * (cols as f64 * pitch - spacing),
+            0.5 * (rows as f64 * pitch - spacing),
+            0.0,
+        );
+        let translation = Vector3::new(0.3, -0.2, 10.0) - rotation * center;
+        let kmat = Matrix3::new(k.fx, 0.0, k.cx, 0.0, k.fy, k.cy, 0.0, 0.0, 1.0);
+        let r = rotation.matrix();
+        let h = kmat
+            * Matrix3::from_columns(&[
+                r.column(0).clone_owned(),
+                r.column(1).clone_owned(),
+                translation,
+            ]);
+        let h_inv = h.try_inverse().unwrap();
+
+        // Render the grid with 4x4 samples per pixel.
+        let family = Family::new_tag_36h11();
+        let tags: Vec<ImageU8Owned> = (0..rows * cols)
+            .map(|i| family.to_image(5 + i as u32).unwrap())
+            .collect();
+        let target_value = |x: f64, y: f64| -> f64 {
+            let (col, row) = ((x / pitch).floor(), (y / pitch).floor());
+            let (tx, ty) = (x - col * pitch, y - row * pitch);
+            if col < 0.0
+                || row < 0.0
+                || col >= cols as f64
+                || row >= rows as f64
+                || tx >= 1.0
+                || ty >= 1.0
+            {
+                return 255.0;
+            }
+            let tag = &tags[row as usize * cols + col as usize];
+            let border = (tag.width() as f64 - WIDTH_AT_BORDER) / 2.0;
+            let bx = (tx * WIDTH_AT_BORDER + border) as usize;
+            let by = (ty * WIDTH_AT_BORDER + border) as usize;
+            tag.data()[by * tag.stride() as usize + bx] as f64
+        };
+        let mut data = vec![0u8; width * height];
+        for v in 0..height {
+            for u in 0..width {
+                let mut sum = 0.0;
+                for i in 0..4 {
+                    for j in 0..4 {
+                        let px = Vector3::new(
+                            u as f64 + (i as f64 - 1.5) / 4.0,
+                            v as f64 + (j as f64 - 1.5) / 4.0,
+                            1.0,
+                        );
+                        let pt = h_inv * px;
+                        sum += target_value(pt.x / pt.z, pt.y / pt.z);
+                    }
+                }
+                data[v * width + u] = (sum / 16.0).round() as u8;
+            }
+        }
+        let im = ImageU8Owned::new(width as i32, height as i32, width as i32, data).unwrap();
+
+        let mut detector = Detector::new();
+        detector.add_family(Family::new_tag_36h11());
+        detector.as_mut().quad_decimate = 1.0;
+        let detections = detector.detect(im.inner());
+        let mut ids: Vec<i32> = detections.as_slice().iter().map(|d| d.id()).collect();
+        ids.sort();
+        assert_eq!(ids, (5..5 + (rows * cols) as i32).collect::<Vec<_>>());
+
+        // The detected corners match the projections of the grid corners.
+        let view = grid.view(detections.as_slice()).unwrap();
+        assert_eq!(view.object_points.len(), 4 * rows * cols);
+        for (obj, img) in view.object_points.iter().zip(view.image_points.iter()) {
+            let p = h * Vector3::new(obj.0, obj.1, 1.0);
+            let dist = ((p.x / p.z - img.0).powi(2) + (p.y / p.z - img.1).powi(2)).sqrt();
+            assert!(dist < 1.0, "corner {obj:?} detected {dist} pixels away");
+        }
+
+        // The pose of the grid is recovered.
+        let pose =
+            zhang::pose_from_homography(&zhang::find_homography(&view).unwrap(), &k).unwrap();
+        let angle = pose.rotation.rotation_to(&rotation).angle();
+        assert!(angle < 0.01, "rotation error {angle} radians");
+        let dist = (pose.translation - translation).norm();
+        assert!(dist < 0.05, "translation error {dist}");
+
+        // Tags not in the grid are ignored.
+        assert!(AprilGrid::new(rows, cols, spacing, 100)
+            .view(detections.as_slice())
+            .is_none());
+    }
+}
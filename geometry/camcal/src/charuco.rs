@@ -0,0 +1,310 @@
+//! ChArUco boards as calibration target.
+
+use crate::{
+    checkerboard::{corner_subpix, Image, Pt, BORDER},
+    zhang, Coords2D, Error, PlanarView,
+};
+
+/// A ChArUco board with AprilTags as markers.
+///
+/// The board is a checkerboard of `squares_x` x `squares_y` squares with a
+/// black square in the top left corner. Each white square holds a marker in its
+/// center. The markers are numbered row by row starting at `first_id`. This is
+/// the layout of OpenCV's `CharucoBoard`, for example with the
+/// `DICT_APRILTAG_36h11` dictionary.
+///
+/// The calibration points are the inner corners of the checkerboard. Object
+/// points are in units of the square size, with x to the right and y down.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharucoBoard {
+    squares_x: usize,
+    squares_y: usize,
+    marker_size: f64,
+    first_id: i32,
+}
+
+impl CharucoBoard {
+    /// Create a new board description.
+    ///
+    /// `marker_size` is the width of the black border of a marker as a
+    /// fraction of the square size.
+    pub fn new(squares_x: usize, squares_y: usize, marker_size: f64, first_id: i32) -> Self {
+        Self {
+            squares_x,
+            squares_y,
+            marker_size,
+            first_id,
+        }
+    }
+
+    /// The square, as column and row, holding marker number `idx`.
+    fn marker_square(&self, idx: usize) -> Option<(usize, usize)> {
+        let mut remaining = idx;
+        for row in 0..self.squares_y {
+            // White squares are those with odd column plus row.
+            let first_col = (row + 1) % 2;
+            let n_markers = (self.squares_x + 1 - first_col) / 2;
+            if remaining < n_markers {
+                return Some((first_col + 2 * remaining, row));
+            }
+            remaining -= n_markers;
+        }
+        None
+    }
+
+    /// The corners of the marker in square `(col, row)`.
+    fn square_marker_corners(&self, col: usize, row: usize) -> [Coords2D; 4] {
+        let x = col as f64 + 0.5;
+        let y = row as f64 + 0.5;
+        let h = 0.5 * self.marker_size;
+        [
+            (x - h, y + h),
+            (x + h, y + h),
+            (x + h, y - h),
+            (x - h, y - h),
+        ]
+    }
+
+    /// The corners of marker `id` on the board, in the order returned by
+    /// `ads_apriltag::Detection::corners`, or `None` if the marker is not part
+    /// of the board.
+    pub fn marker_corners(&self, id: i32) -> Option<[Coords2D; 4]> {
+        let idx = usize::try_from(id.checked_sub(self.first_id)?).ok()?;
+        let (col, row) = self.marker_square(idx)?;
+        Some(self.square_marker_corners(col, row))
+    }
+
+    /// Find the inner corners of the board in a grayscale image.
+    ///
+    /// `detections` are the AprilTags found in the image. The location of each
+    /// inner corner next to a detected marker is predicted from the markers
+    /// around it and then refined to subpixel accuracy as in
+    /// [crate::find_chessboard_corners]. Detections with ids not on the board
+    /// and ids detected more than once are ignored. Returns `Ok(None)` if no
+    /// corner was found.
+    pub fn view(
+        &self,
+        mono: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        detections: &[ads_apriltag::Detection],
+    ) -> Result<Option<PlanarView>, Error> {
+        let (width, height) = (width as usize, height as usize);
+        if width < 2 || height < 2 || stride < width || mono.len() < stride * (height - 1) + width {
+            return Err(Error::InvalidImage);
+        }
+        if self.squares_x < 2 || self.squares_y < 2 {
+            return Err(Error::InvalidPattern);
+        }
+
+        // Image location of the marker corners, per square.
+        let mut markers: Vec<Option<[Coords2D; 4]>> = vec![None; self.squares_x * self.squares_y];
+        for det in detections {
+            if detections.iter().filter(|d| d.id() == det.id()).count() != 1 {
+                continue;
+            }
+            let Some(idx) = det
+                .id()
+                .checked_sub(self.first_id)
+                .and_then(|i| usize::try_from(i).ok())
+            else {
+                continue;
+            };
+            if let Some((col, row)) = self.marker_square(idx) {
+                markers[row * self.squares_x + col] = Some(det.corners().map(|p| (p[0], p[1])));
+            }
+        }
+
+        let full = Image::from_mono8(mono, width, height, stride);
+        let mut view = PlanarView {
+            object_points: Vec::new(),
+            image_points: Vec::new(),
+        };
+        for row in 1..self.squares_y {
+            for col in 1..self.squares_x {
+                // Two of the four squares around the corner hold markers.
+                let mut local = PlanarView {
+                    object_points: Vec::new(),
+                    image_points: Vec::new(),
+                };
+                for (c, r) in [
+                    (col - 1, row - 1),
+                    (col, row - 1),
+                    (col - 1, row),
+                    (col, row),
+                ] {
+                    if let Some(image_corners) = &markers[r * self.squares_x + c] {
+                        local.object_points.extend(self.square_marker_corners(c, r));
+                        local.image_points.extend(image_corners);
+                    }
+                }
+                if local.object_points.is_empty() {
+                    continue;
+                }
+                let Ok(homography) = zhang::find_homography(&local) else {
+                    continue;
+                };
+                let project = |x: f64, y: f64| {
+                    let p = homography * nalgebra::Vector3::new(x, y, 1.0);
+                    Pt::new((p.x / p.z) as f32, (p.y / p.z) as f32)
+                };
+
+                let (x, y) = (col as f64, row as f64);
+                let initial = project(x, y);
+                // Keep the refinement window within the white margin around
+                // the markers.
+                let square_pixels = project(x - 0.5, y)
+                    .dist(project(x + 0.5, y))
+                    .min(project(x, y - 0.5).dist(project(x, y + 0.5)));
+                let margin = 0.5 * (1.0 - self.marker_size as f32) * square_pixels;
+                let half_win = (margin as usize).clamp(2, 11);
+                let corner = corner_subpix(&full, initial, half_win);
+
+                let (w, h) = (width as f32, height as f32);
+                if !(BORDER < corner.x && corner.x < w - BORDER)
+                    || !(BORDER < corner.y && corner.y < h - BORDER)
+                {
+                    continue;
+                }
+                view.object_points.push((x, y));
+                view.image_points.push((corner.x as f64, corner.y as f64));
+            }
+        }
+        Ok((!view.object_points.is_empty()).then_some(view))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ads_apriltag::{Detector, Family, ImageU8, ImageU8Owned};
+    use nalgebra::{Matrix3, Rotation3, Vector3};
+
+    /// Width of the black border of a 36h11 tag, in bits.
+    const WIDTH_AT_BORDER: f64 = 8.0;
+
+    #[test]
+    fn test_marker_layout() {
+        let board = CharucoBoard::new(5, 4, 0.5, 10);
+        let center = |id| {
+            let c = board.marker_corners(id).unwrap();
+            (0.5 * (c[0].0 + c[1].0), 0.5 * (c[0].1 + c[2].1))
+        };
+        assert_eq!(center(10), (1.5, 0.5));
+        assert_eq!(center(11), (3.5, 0.5));
+        assert_eq!(center(12), (0.5, 1.5));
+        assert_eq!(center(14), (4.5, 1.5));
+        assert_eq!(center(19), (4.5, 3.5));
+        assert!(board.marker_corners(9).is_none());
+        assert!(board.marker_corners(20).is_none());
+    }
+
+    #[test]
+    fn test_detect_charuco_board() {
+        let (width, height) = (800, 600);
+        let (fx, fy, cx, cy) = (700.0, 700.0, 400.0, 300.0);
+        let (squares_x, squares_y, marker_size) = (6, 5, 0.7);
+        let board = CharucoBoard::new(squares_x, squares_y, marker_size, 0);
+        let n_markers = squares_x * squares_y / 2;
+
+        // The center of the board is 8 square sizes in front of the camera.
+        let rotation = Rotation3::from_euler_angles(0.25, -0.2, 0.3);
+        let center = Vector3::new(0.5 * squares_x as f64, 0.5 * squares_y as f64, 0.0);
+        let translation = Vector3::new(0.2, -0.3, 8.0) - rotation * center;
+        let kmat = Matrix3::new(fx, 0.0, cx, 0.0, fy, cy, 0.0, 0.0, 1.0);
+        let r = rotation.matrix();
+        let h = kmat
+            * Matrix3::from_columns(&[
+                r.column(0).clone_owned(),
+                r.column(1).clone_owned(),
+                translation,
+            ]);
+        let h_inv = h.try_inverse().unwrap();
+
+        // Render the board with 4x4 samples per pixel.
+        let family = Family::new_tag_36h11();
+        let tags: Vec<ImageU8Owned> = (0..n_markers)
+            .map(|i| family.to_image(i as u32).unwrap())
+            .collect();
+        let target_value = |x: f64, y: f64| -> f64 {
+            let (col, row) = (x.floor(), y.floor());
+            if col < 0.0 || row < 0.0 || col >= squares_x as f64 || row >= squares_y as f64 {
+                return 255.0;
+            }
+            let (col, row) = (col as usize, row as usize);
+            if (col + row) % 2 == 0 {
+                return 0.0;
+            }
+            let margin = 0.5 * (1.0 - marker_size);
+            let tx = (x - col as f64 - margin) / marker_size;
+            let ty = (y - row as f64 - margin) / marker_size;
+            if !(0.0..1.0).contains(&tx) || !(0.0..1.0).contains(&ty) {
+                return 255.0;
+            }
+            let idx = (row * squares_x + col) / 2;
+            let tag = &tags[idx];
+            let border = (tag.width() as f64 - WIDTH_AT_BORDER) / 2.0;
+            let bx = (tx * WIDTH_AT_BORDER + border) as usize;
+            let by = (ty * WIDTH_AT_BORDER + border) as usize;
+            tag.data()[by * tag.stride() as usize + bx] as f64
+        };
+        let mut data = vec![0u8; width * height];
+        for v in 0..height {
+            for u in 0..width {
+                let mut sum = 0.0;
+                for i in 0..4 {
+                    for j in 0..4 {
+                        let px = Vector3::new(
+                            u as f64 + (i as f64 - 1.5) / 4.0,
+                            v as f64 + (j as f64 - 1.5) / 4.0,
+                            1.0,
+                        );
+                        let pt = h_inv * px;
+                        sum += target_value(pt.x / pt.z, pt.y / pt.z);
+                    }
+                }
+                data[v * width + u] = (sum / 16.0).round() as u8;
+            }
+        }
+        let im =
+            ImageU8Owned::new(width as i32, height as i32, width as i32, data.clone()).unwrap();
+
+        let mut detector = Detector::new();
+        detector.add_family(Family::new_tag_36h11());
+        detector.as_mut().quad_decimate = 1.0;
+        let detections = detector.detect(im.inner());
+        let mut ids: Vec<i32> = detections.as_slice().iter().map(|d| d.id()).collect();
+        ids.sort();
+        assert_eq!(ids, (0..n_markers as i32).collect::<Vec<_>>());
+
+        // All inner corners are found at their projected location.
+        let view = board
+            .view(
+                &data,
+                width as u32,
+                height as u32,
+                width,
+                detections.as_slice(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(view.object_points.len(), (squares_x - 1) * (squares_y - 1));
+        for (obj, img) in view.object_points.iter().zip(view.image_points.iter()) {
+            let p = h * Vector3::new(obj.0, obj.1, 1.0);
+            let dist = ((p.x / p.z - img.0).powi(2) + (p.y / p.z - img.1).powi(2)).sqrt();
+            assert!(dist < 0.2, "corner {obj:?} detected {dist} pixels away");
+        }
+
+        // Markers not on the board are ignored.
+        let other = CharucoBoard::new(squares_x, squares_y, marker_size, 100);
+        let view = other.view(
+            &data,
+            width as u32,
+            height as u32,
+            width,
+            detections.as_slice(),
+        );
+        assert!(view.unwrap().is_none());
+    }
+}
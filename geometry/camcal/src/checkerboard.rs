@@ -0,0 +1,634 @@
+//! Checkerboard corner detection.
+//!
+//! Corners of the checkerboard are X-junctions, where the image intensity
+//! forms a saddle. Candidate corners are local maxima of a saddle measure
+//! computed from the Hessian of the smoothed image which also have the
+//! alternating dark-bright-dark-bright pattern on a small circle around them.
+//! Starting from a seed candidate, the grid of corners is grown by
+//! extrapolating to each neighbor. A grid with the size of the pattern is
+//! accepted if its squares alternate in color. Finally, the corner locations
+//! are refined to subpixel accuracy in the same way as OpenCV's
+//! `cornerSubPix`.
+//!
+//! If no board is found at full resolution, detection is repeated on
+//! downsampled images, which helps for blurry images with large squares.
+
+use crate::Error;
+
+/// Corners closer than this to the image border, in pixels, are rejected.
+///
+/// This is the same as in ROS `camera_calibration`.
+pub(crate) const BORDER: f32 = 8.0;
+
+/// Smoothing before computing the saddle measure, in pixels.
+const SIGMA: f32 = 1.5;
+
+/// Radius of the circle used to check for an X-junction, in pixels.
+const CIRCLE_RADIUS: f32 = 4.0;
+
+const CIRCLE_SAMPLES: usize = 16;
+
+/// Number of downsampled levels tried after the full resolution image.
+const MAX_PYRAMID_LEVELS: usize = 2;
+
+/// Number of candidates tried as seed of the grid.
+const MAX_SEEDS: usize = 30;
+
+const SUBPIX_MAX_ITER: usize = 30;
+const SUBPIX_EPS: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Pt {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+}
+
+impl Pt {
+    pub(crate) fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    fn add(self, o: Pt) -> Pt {
+        Pt::new(self.x + o.x, self.y + o.y)
+    }
+    fn sub(self, o: Pt) -> Pt {
+        Pt::new(self.x - o.x, self.y - o.y)
+    }
+    fn scale(self, s: f32) -> Pt {
+        Pt::new(self.x * s, self.y * s)
+    }
+    fn norm(self) -> f32 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+    pub(crate) fn dist(self, o: Pt) -> f32 {
+        self.sub(o).norm()
+    }
+}
+
+/// A single channel floating point image.
+#[derive(Clone)]
+pub(crate) struct Image {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Image {
+    pub(crate) fn from_mono8(mono: &[u8], width: usize, height: usize, stride: usize) -> Self {
+        let mut data = Vec::with_capacity(width * height);
+        for row in mono.chunks(stride).take(height) {
+            data.extend(row[..width].iter().map(|v| *v as f32));
+        }
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    #[inline]
+    fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    /// Bilinear interpolation, clamped to the image.
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let x = x.clamp(0.0, (self.width - 1) as f32);
+        let y = y.clamp(0.0, (self.height - 1) as f32);
+        let x0 = (x.floor() as usize).min(self.width - 2);
+        let y0 = (y.floor() as usize).min(self.height - 2);
+        let fx = x - x0 as f32;
+        let fy = y - y0 as f32;
+        let top = self.get(x0, y0) * (1.0 - fx) + self.get(x0 + 1, y0) * fx;
+        let bottom = self.get(x0, y0 + 1) * (1.0 - fx) + self.get(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Separable Gaussian smoothing with clamped borders.
+    fn blur(&self, sigma: f32) -> Image {
+        let radius = (3.0 * sigma).ceil() as isize;
+        let kernel: Vec<f32> = (-radius..=radius)
+            .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let sum: f32 = kernel.iter().sum();
+        let kernel: Vec<f32> = kernel.iter().map(|k| k / sum).collect();
+
+        let (w, h) = (self.width as isize, self.height as isize);
+        let mut tmp = vec![0.0; self.data.len()];
+        for y in 0..h {
+            for x in 0..w {
+                let mut acc = 0.0;
+                for (k, kv) in kernel.iter().enumerate() {
+                    let xx = (x + k as isize - radius).clamp(0, w - 1);
+                    acc += kv * self.data[(y * w + xx) as usize];
+                }
+                tmp[(y * w + x) as usize] = acc;
+            }
+        }
+        let mut data = vec![0.0; self.data.len()];
+        for y in 0..h {
+            for x in 0..w {
+                let mut acc = 0.0;
+                for (k, kv) in kernel.iter().enumerate() {
+                    let yy = (y + k as isize - radius).clamp(0, h - 1);
+                    acc += kv * tmp[(yy * w + x) as usize];
+                }
+                data[(y * w + x) as usize] = acc;
+            }
+        }
+        Image {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+
+    /// Downsample by a factor of two by averaging 2x2 blocks.
+    fn downsample(&self) -> Image {
+        let width = self.width / 2;
+        let height = self.height / 2;
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let sum = self.get(2 * x, 2 * y)
+                    + self.get(2 * x + 1, 2 * y)
+                    + self.get(2 * x, 2 * y + 1)
+                    + self.get(2 * x + 1, 2 * y + 1);
+                data.push(sum * 0.25);
+            }
+        }
+        Image {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+/// Find the inner corners of a checkerboard in a grayscale image.
+///
+/// `pattern_width` and `pattern_height` are the number of inner corners per
+/// row and column of the board (e.g. 8x8 checks would be 7x7 corners). On
+/// success, the corners are returned row by row, with `pattern_width` corners
+/// per row, as with OpenCV's `findChessboardCorners`. Returns `Ok(None)` if
+/// the board is not found.
+pub fn find_chessboard_corners(
+    mono: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    pattern_width: usize,
+    pattern_height: usize,
+) -> Result<Option<Vec<(f32, f32)>>, Error> {
+    let (width, height) = (width as usize, height as usize);
+    if width < 2 || height < 2 || stride < width || mono.len() < stride * (height - 1) + width {
+        return Err(Error::InvalidImage);
+    }
+    if pattern_width < 2 || pattern_height < 2 {
+        return Err(Error::InvalidPattern);
+    }
+
+    let full = Image::from_mono8(mono, width, height, stride);
+    let mut level_image = full.clone();
+    for level in 0..=MAX_PYRAMID_LEVELS {
+        if level > 0 {
+            level_image = level_image.downsample();
+            if level_image.width < 32 || level_image.height < 32 {
+                break;
+            }
+        }
+        let smoothed = level_image.blur(SIGMA);
+        let Some(grid) = find_grid(&smoothed, pattern_width, pattern_height) else {
+            continue;
+        };
+        // Back to full resolution pixel coordinates.
+        let scale = (1 << level) as f32;
+        let offset = (scale - 1.0) * 0.5;
+        let mut corners: Vec<Pt> = grid
+            .iter()
+            .map(|p| Pt::new(p.x * scale + offset, p.y * scale + offset))
+            .collect();
+
+        let half_win = subpix_half_window(&corners, pattern_width, pattern_height);
+        for c in corners.iter_mut() {
+            *c = corner_subpix(&full, *c, half_win);
+        }
+
+        let (w, h) = (width as f32, height as f32);
+        if corners
+            .iter()
+            .any(|c| !(BORDER < c.x && c.x < w - BORDER && BORDER < c.y && c.y < h - BORDER))
+        {
+            return Ok(None);
+        }
+
+        // Order the corners consistently: rows and columns are right-handed in
+        // image coordinates and the corners go from top to bottom.
+        let row_dir = corners[1].sub(corners[0]);
+        let col_dir = corners[pattern_width].sub(corners[0]);
+        if row_dir.x * col_dir.y - row_dir.y * col_dir.x < 0.0 {
+            for row in corners.chunks_mut(pattern_width) {
+                row.reverse();
+            }
+        }
+        if corners[0].y > corners[corners.len() - 1].y {
+            corners.reverse();
+        }
+        return Ok(Some(corners.iter().map(|c| (c.x, c.y)).collect()));
+    }
+    Ok(None)
+}
+
+/// Find the grid of corners in a smoothed image, in row-major order.
+fn find_grid(im: &Image, pattern_width: usize, pattern_height: usize) -> Option<Vec<Pt>> {
+    let candidates = find_candidates(im);
+    if candidates.len() < pattern_width * pattern_height {
+        return None;
+    }
+    let pts: Vec<Pt> = candidates.iter().map(|c| c.0).collect();
+    for seed in 0..pts.len().min(MAX_SEEDS) {
+        let Some(grid) = grow_grid(&pts, seed) else {
+            continue;
+        };
+        let Some(ordered) = to_pattern(&grid, &pts, pattern_width, pattern_height) else {
+            continue;
+        };
+        if squares_alternate(im, &ordered, pattern_width, pattern_height) {
+            return Some(ordered);
+        }
+    }
+    None
+}
+
+/// Find X-junctions, sorted by decreasing strength.
+fn find_candidates(im: &Image) -> Vec<(Pt, f32)> {
+    let (w, h) = (im.width, im.height);
+    let mut response = vec![0.0f32; w * h];
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let c = im.get(x, y);
+            let dxx = im.get(x + 1, y) - 2.0 * c + im.get(x - 1, y);
+            let dyy = im.get(x, y + 1) - 2.0 * c + im.get(x, y - 1);
+            let dxy = (im.get(x + 1, y + 1) - im.get(x + 1, y - 1) - im.get(x - 1, y + 1)
+                + im.get(x - 1, y - 1))
+                * 0.25;
+            // Positive at saddles, where the principal curvatures have
+            // opposite signs.
+            response[y * w + x] = dxy * dxy - dxx * dyy;
+        }
+    }
+    let max_response = response.iter().cloned().fold(0.0, f32::max);
+    if max_response <= 0.0 {
+        return Vec::new();
+    }
+    let threshold = 0.02 * max_response;
+
+    let margin = CIRCLE_RADIUS.ceil() as usize + 2;
+    let mut result = Vec::new();
+    for y in margin..h.saturating_sub(margin) {
+        for x in margin..w.saturating_sub(margin) {
+            let r = response[y * w + x];
+            if r <= threshold {
+                continue;
+            }
+            let mut is_max = true;
+            'nms: for yy in y - 2..=y + 2 {
+                for xx in x - 2..=x + 2 {
+                    let other = response[yy * w + xx];
+                    if other > r || (other == r && (yy, xx) < (y, x)) {
+                        is_max = false;
+                        break 'nms;
+                    }
+                }
+            }
+            if !is_max {
+                continue;
+            }
+            let pt = saddle_subpixel(&response, w, x, y);
+            if is_x_junction(im, pt) {
+                result.push((pt, r));
+            }
+        }
+    }
+    result.sort_by(|a, b| b.1.total_cmp(&a.1));
+    result
+}
+
+/// Refine the location of a maximum of the response with a parabola fit.
+fn saddle_subpixel(response: &[f32], w: usize, x: usize, y: usize) -> Pt {
+    let r = |xx: usize, yy: usize| response[yy * w + xx];
+    let c = r(x, y);
+    let fit = |m: f32, p: f32| {
+        let denom = m - 2.0 * c + p;
+        if denom < 0.0 {
+            (0.5 * (m - p) / denom).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+    let dx = fit(r(x - 1, y), r(x + 1, y));
+    let dy = fit(r(x, y - 1), r(x, y + 1));
+    Pt::new(x as f32 + dx, y as f32 + dy)
+}
+
+/// Check for the dark-bright-dark-bright pattern around an X-junction.
+fn is_x_junction(im: &Image, pt: Pt) -> bool {
+    let mut values = [0.0f32; CIRCLE_SAMPLES];
+    for (i, v) in values.iter_mut().enumerate() {
+        let angle = i as f32 * std::f32::consts::TAU / CIRCLE_SAMPLES as f32;
+        *v = im.sample(
+            pt.x + CIRCLE_RADIUS * angle.cos(),
+            pt.y + CIRCLE_RADIUS * angle.sin(),
+        );
+    }
+    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    if max - min < 10.0 {
+        return false;
+    }
+    let mid = 0.5 * (min + max);
+    let mut transitions = 0;
+    for i in 0..CIRCLE_SAMPLES {
+        let a = values[i] > mid;
+        let b = values[(i + 1) % CIRCLE_SAMPLES] > mid;
+        if a != b {
+            transitions += 1;
+        }
+    }
+    transitions == 4
+}
+
+/// Grow a grid of corners from a seed. Returns grid coordinates and point
+/// indices.
+fn grow_grid(pts: &[Pt], seed: usize) -> Option<Vec<((i32, i32), usize)>> {
+    let p0 = pts[seed];
+    let mut by_dist: Vec<usize> = (0..pts.len()).filter(|i| *i != seed).collect();
+    by_dist.sort_by(|a, b| pts[*a].dist(p0).total_cmp(&pts[*b].dist(p0)));
+
+    // The two grid directions from the nearest neighbors.
+    let a = pts[*by_dist.first()?].sub(p0);
+    let b = by_dist.iter().take(6).skip(1).find_map(|i| {
+        let v = pts[*i].sub(p0);
+        let cos = (v.x * a.x + v.y * a.y) / (v.norm() * a.norm());
+        (cos.abs() < 0.5 && v.norm() < 1.5 * a.norm()).then_some(v)
+    })?;
+
+    let mut grid: std::collections::HashMap<(i32, i32), usize> = Default::default();
+    let mut used = vec![false; pts.len()];
+    grid.insert((0, 0), seed);
+    used[seed] = true;
+    let mut queue = std::collections::VecDeque::from([(0, 0)]);
+    let basis = |d: (i32, i32)| a.scale(d.0 as f32).add(b.scale(d.1 as f32));
+
+    while let Some((i, j)) = queue.pop_front() {
+        let p = pts[grid[&(i, j)]];
+        for d in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let target = (i + d.0, j + d.1);
+            if grid.contains_key(&target) {
+                continue;
+            }
+            // Estimate the step to the neighbor from the known corners
+            // nearby, falling back to the initial basis.
+            let step = if let Some(prev) = grid.get(&(i - d.0, j - d.1)) {
+                p.sub(pts[*prev])
+            } else {
+                let side = (d.1, d.0);
+                [1, -1]
+                    .into_iter()
+                    .find_map(|s| {
+                        let from = grid.get(&(i + s * side.0, j + s * side.1))?;
+                        let to = grid.get(&(i + s * side.0 + d.0, j + s * side.1 + d.1))?;
+                        Some(pts[*to].sub(pts[*from]))
+                    })
+                    .unwrap_or_else(|| basis(d))
+            };
+            let predicted = p.add(step);
+            let tolerance = 0.35 * step.norm();
+            let nearest = pts
+                .iter()
+                .enumerate()
+                .filter(|(k, _)| !used[*k])
+                .map(|(k, q)| (k, q.dist(predicted)))
+                .min_by(|x, y| x.1.total_cmp(&y.1));
+            if let Some((k, dist)) = nearest {
+                if dist < tolerance {
+                    grid.insert(target, k);
+                    used[k] = true;
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+    Some(grid.into_iter().collect())
+}
+
+/// Order a grown grid as the pattern, if it has the size of the pattern.
+fn to_pattern(
+    grid: &[((i32, i32), usize)],
+    pts: &[Pt],
+    pattern_width: usize,
+    pattern_height: usize,
+) -> Option<Vec<Pt>> {
+    if grid.len() != pattern_width * pattern_height {
+        return None;
+    }
+    let imin = grid.iter().map(|g| g.0 .0).min()?;
+    let jmin = grid.iter().map(|g| g.0 .1).min()?;
+    let ni = (grid.iter().map(|g| g.0 .0).max()? - imin + 1) as usize;
+    let nj = (grid.iter().map(|g| g.0 .1).max()? - jmin + 1) as usize;
+    let transpose = if (ni, nj) == (pattern_width, pattern_height) {
+        false
+    } else if (ni, nj) == (pattern_height, pattern_width) {
+        true
+    } else {
+        return None;
+    };
+    let mut result = vec![Pt::new(0.0, 0.0); grid.len()];
+    for ((i, j), k) in grid {
+        let (i, j) = ((i - imin) as usize, (j - jmin) as usize);
+        let (row, col) = if transpose { (i, j) } else { (j, i) };
+        result[row * pattern_width + col] = pts[*k];
+    }
+    Some(result)
+}
+
+/// Check that the squares between the corners alternate in color.
+fn squares_alternate(
+    im: &Image,
+    corners: &[Pt],
+    pattern_width: usize,
+    pattern_height: usize,
+) -> bool {
+    let mut values = [Vec::new(), Vec::new()];
+    for row in 0..pattern_height - 1 {
+        for col in 0..pattern_width - 1 {
+            let idx = row * pattern_width + col;
+            let center = corners[idx]
+                .add(corners[idx + 1])
+                .add(corners[idx + pattern_width])
+                .add(corners[idx + pattern_width + 1])
+                .scale(0.25);
+            values[(row + col) % 2].push(im.sample(center.x, center.y));
+        }
+    }
+    let mean = |v: &Vec<f32>| v.iter().sum::<f32>() / v.len() as f32;
+    let (m0, m1) = (mean(&values[0]), mean(&values[1]));
+    if (m0 - m1).abs() < 20.0 {
+        return false;
+    }
+    let mid = 0.5 * (m0 + m1);
+    let total = values[0].len() + values[1].len();
+    let consistent = values[0]
+        .iter()
+        .filter(|v| (**v > mid) == (m0 > mid))
+        .count()
+        + values[1]
+            .iter()
+            .filter(|v| (**v > mid) == (m1 > mid))
+            .count();
+    consistent * 10 >= total * 9
+}
+
+/// Half size of the subpixel refinement window.
+///
+/// As in ROS `camera_calibration`, this is half the minimum distance between
+/// neighboring corners so that the window does not include other corners.
+fn subpix_half_window(corners: &[Pt], pattern_width: usize, pattern_height: usize) -> usize {
+    let mut min_distance = f32::INFINITY;
+    for row in 0..pattern_height {
+        for col in 0..pattern_width {
+            let idx = row * pattern_width + col;
+            if col + 1 < pattern_width {
+                min_distance = min_distance.min(corners[idx].dist(corners[idx + 1]));
+            }
+            if row + 1 < pattern_height {
+                min_distance = min_distance.min(corners[idx].dist(corners[idx + pattern_width]));
+            }
+        }
+    }
+    ((min_distance * 0.5) as usize).clamp(2, 11)
+}
+
+/// Refine a corner location as OpenCV's `cornerSubPix`.
+///
+/// At the corner, the image gradient at each nearby point is orthogonal to
+/// the vector from the corner to that point. The corner is found by solving
+/// the least squares problem for this condition, iterated.
+pub(crate) fn corner_subpix(im: &Image, initial: Pt, half_win: usize) -> Pt {
+    let hw = half_win as isize;
+    let inv_win2 = 1.0 / (half_win * half_win) as f32;
+    let mut current = initial;
+    for _ in 0..SUBPIX_MAX_ITER {
+        let (mut a, mut b, mut c, mut bb1, mut bb2) = (0.0f32, 0.0f32, 0.0f32, 0.0f32, 0.0f32);
+        for j in -hw..=hw {
+            for i in -hw..=hw {
+                let (px, py) = (i as f32, j as f32);
+                let weight = (-(px * px + py * py) * inv_win2).exp();
+                let (x, y) = (current.x + px, current.y + py);
+                let gx = 0.5 * (im.sample(x + 1.0, y) - im.sample(x - 1.0, y));
+                let gy = 0.5 * (im.sample(x, y + 1.0) - im.sample(x, y - 1.0));
+                let gxx = gx * gx * weight;
+                let gxy = gx * gy * weight;
+                let gyy = gy * gy * weight;
+                a += gxx;
+                b += gxy;
+                c += gyy;
+                bb1 += gxx * px + gxy * py;
+                bb2 += gxy * px + gyy * py;
+            }
+        }
+        let det = a * c - b * b;
+        if det.abs() <= f32::EPSILON * a * c {
+            break;
+        }
+        let shift = Pt::new((c * bb1 - b * bb2) / det, (a * bb2 - b * bb1) / det);
+        current = current.add(shift);
+        if shift.norm() < SUBPIX_EPS {
+            break;
+        }
+    }
+    if current.dist(initial) > half_win as f32 {
+        // Diverged, as in OpenCV keep the initial location.
+        return initial;
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render a checkerboard with `n_cols` x `n_rows` squares of `square`
+    /// pixels, rotated by `angle` about `origin`, with 4x4 supersampling.
+    fn render_checkerboard(
+        width: usize,
+        height: usize,
+        n_cols: usize,
+        n_rows: usize,
+        square: f32,
+        origin: Pt,
+        angle: f32,
+    ) -> Vec<u8> {
+        let (s, c) = angle.sin_cos();
+        let mut data = vec![0u8; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let mut acc = 0.0;
+                for sy in 0..4 {
+                    for sx in 0..4 {
+                        let px = x as f32 + (sx as f32 + 0.5) / 4.0 - 0.5 - origin.x;
+                        let py = y as f32 + (sy as f32 + 0.5) / 4.0 - 0.5 - origin.y;
+                        let u = (c * px + s * py) / square;
+                        let v = (-s * px + c * py) / square;
+                        let inside = u >= 0.0 && v >= 0.0 && u < n_cols as f32 && v < n_rows as f32;
+                        let dark = inside && (u.floor() as i64 + v.floor() as i64) % 2 == 0;
+                        acc += if dark { 30.0 } else { 220.0 };
+                    }
+                }
+                data[y * width + x] = (acc / 16.0) as u8;
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_find_chessboard_corners() {
+        let (width, height) = (320, 240);
+        let origin = Pt::new(60.3, 40.7);
+        let angle = 0.2f32;
+        let square = 20.0;
+        let (n_cols, n_rows) = (10, 7);
+        let data = render_checkerboard(width, height, n_cols, n_rows, square, origin, angle);
+
+        let corners = find_chessboard_corners(&data, width as u32, height as u32, width, 9, 6)
+            .unwrap()
+            .unwrap();
+        assert_eq!(corners.len(), 9 * 6);
+
+        let (s, c) = angle.sin_cos();
+        let mut expected: Vec<Pt> = Vec::new();
+        for row in 1..n_rows {
+            for col in 1..n_cols {
+                let (u, v) = (col as f32 * square, row as f32 * square);
+                expected.push(Pt::new(c * u - s * v + origin.x, s * u + c * v + origin.y));
+            }
+        }
+        // Rows may be returned in either direction.
+        let first = Pt::new(corners[0].0, corners[0].1);
+        if first.dist(expected[0]) > first.dist(expected[expected.len() - 1]) {
+            expected.reverse();
+        }
+        for (found, expected) in corners.iter().zip(expected.iter()) {
+            let found = Pt::new(found.0, found.1);
+            assert!(found.dist(*expected) < 0.1, "{found:?} {expected:?}");
+        }
+
+        // No board in a blank image.
+        let blank = vec![128u8; width * height];
+        let result = find_chessboard_corners(&blank, width as u32, height as u32, width, 9, 6);
+        assert!(result.unwrap().is_none());
+
+        // Wrong pattern size.
+        let result = find_chessboard_corners(&data, width as u32, height as u32, width, 8, 6);
+        assert!(result.unwrap().is_none());
+    }
+}
@@ -1,9 +1,55 @@
+//! Camera intrinsic calibration from views of a planar target.
+//!
+//! The calibration is done in pure Rust: [find_chessboard_corners] detects the
+//! corners of a checkerboard and [calibrate_checkerboards] (or, for other
+//! targets, [calibrate_planar_views]) estimates the intrinsic parameters
+//! including distortion. With the `apriltag` feature, grids of AprilTags and
+//! ChArUco boards with AprilTag markers can also be used as target, see
+//! `AprilGrid` and `CharucoBoard`.
+//!
+//! With the `opencv` feature, the calibration can also be done with OpenCV
+//! using `compute_intrinsics`.
+
+#[cfg(feature = "opencv")]
 use nalgebra::RealField;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "apriltag")]
+mod april_grid;
+#[cfg(feature = "apriltag")]
+mod charuco;
+mod checkerboard;
+mod refine;
+mod zhang;
+
+#[cfg(feature = "apriltag")]
+pub use april_grid::AprilGrid;
+#[cfg(feature = "apriltag")]
+pub use charuco::CharucoBoard;
+pub use checkerboard::find_chessboard_corners;
+
+#[cfg(feature = "opencv")]
 type Coords3D = (f64, f64, f64);
 type Coords2D = (f64, f64);
 
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid image dimensions or data")]
+    InvalidImage,
+    #[error("pattern must have at least 2x2 corners")]
+    InvalidPattern,
+    #[error("no views")]
+    NotEnoughViews,
+    #[error("each view must have at least 4 corresponding points")]
+    NotEnoughPoints,
+    #[error("number of corners does not match the checkerboard size")]
+    BoardSizeMismatch,
+    #[error("degenerate views")]
+    Degenerate,
+    #[error("calibration did not converge")]
+    NotConverged,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CheckerBoardData {
     n_rows: usize,
@@ -22,10 +68,57 @@ impl CheckerBoardData {
     }
 }
 
+#[cfg(feature = "opencv")]
 fn to_image_points(board: &CheckerBoardData) -> Vec<Coords2D> {
     board.points.clone()
 }
 
+/// Corresponding points in one view of a planar calibration target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanarView {
+    /// Location of each point on the target, in arbitrary units.
+    pub object_points: Vec<Coords2D>,
+    /// Location of each point in the image, in pixels.
+    pub image_points: Vec<Coords2D>,
+}
+
+impl From<&CheckerBoardData> for PlanarView {
+    fn from(board: &CheckerBoardData) -> Self {
+        let object_points = board_object_points(board)
+            .into_iter()
+            .map(|(x, y, _z)| (x, y))
+            .collect();
+        Self {
+            object_points,
+            image_points: board.points.clone(),
+        }
+    }
+}
+
+/// Result of intrinsic calibration.
+#[derive(Debug, Clone)]
+pub struct CalibrationResult {
+    pub intrinsics: opencv_ros_camera::RosOpenCvIntrinsics<f64>,
+    /// Root mean square reprojection distance, in pixels.
+    ///
+    /// As with OpenCV, this is the root mean square rather than the mean.
+    pub mean_reprojection_distance_pixels: f64,
+    pub image_width: u32,
+    pub image_height: u32,
+}
+
+#[cfg(feature = "opencv")]
+impl From<&opencv_calibrate::CalibrationResult> for CalibrationResult {
+    fn from(raw_opencv_cal: &opencv_calibrate::CalibrationResult) -> Self {
+        Self {
+            intrinsics: convert_to_cam_geom(raw_opencv_cal),
+            mean_reprojection_distance_pixels: raw_opencv_cal.mean_reprojection_distance_pixels,
+            image_width: raw_opencv_cal.image_width,
+            image_height: raw_opencv_cal.image_height,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PixelSize {
     width: usize,
@@ -42,16 +135,14 @@ pub fn save_yaml<P: AsRef<std::path::Path>>(
     cam_info_file_stamped: P,
     pkg_name: &str,
     local: chrono::DateTime<chrono::Local>,
-    raw_opencv_cal: &opencv_calibrate::CalibrationResult,
+    cal: &CalibrationResult,
     raw_cam_name: &str,
 ) -> eyre::Result<()> {
-    let intrinsics = convert_to_cam_geom::<f64>(&raw_opencv_cal);
-
     // Convert from braid_mvg to ROS format.
     let ci: opencv_ros_camera::RosCameraInfo<_> = opencv_ros_camera::NamedIntrinsicParameters {
-        intrinsics,
-        width: raw_opencv_cal.image_width as usize,
-        height: raw_opencv_cal.image_height as usize,
+        intrinsics: cal.intrinsics.clone(),
+        width: cal.image_width as usize,
+        height: cal.image_height as usize,
         name: raw_cam_name.into(),
     }
     .into();
@@ -62,7 +153,7 @@ pub fn save_yaml<P: AsRef<std::path::Path>>(
         format!(
             "# Saved by {} at {}\n\
                         # Mean reprojection distance: {:.2}\n",
-            pkg_name, local, raw_opencv_cal.mean_reprojection_distance_pixels
+            pkg_name, local, cal.mean_reprojection_distance_pixels
         )
         .as_bytes(),
     )?;
@@ -70,7 +161,64 @@ pub fn save_yaml<P: AsRef<std::path::Path>>(
     Ok(())
 }
 
+/// Given some views of a planar target, compute intrinsics.
+///
+/// The focal lengths, principal point and the distortion parameters `k1`,
+/// `k2`, `p1` and `p2` are estimated. As in ROS `camera_calibration`, `k3` and
+/// skew are zero.
+pub fn calibrate_planar_views(
+    size: PixelSize,
+    views: &[PlanarView],
+) -> Result<CalibrationResult, Error> {
+    if views.is_empty() {
+        return Err(Error::NotEnoughViews);
+    }
+    let (pinhole, poses) = zhang::initial_estimate(size.width, size.height, views)?;
+    let cam0 = refine::PlumbBob {
+        pinhole,
+        distortion: [0.0; 4],
+    };
+    let problem = refine::CalibrationProblem::new(views, &cam0, &poses);
+    let (problem, report) = levenberg_marquardt::LevenbergMarquardt::new().minimize(problem);
+    if !report.termination.was_successful() {
+        return Err(Error::NotConverged);
+    }
+
+    let cam = problem.camera();
+    let k = &cam.pinhole;
+    let [k1, k2, p1, p2] = cam.distortion;
+    let distortion =
+        opencv_ros_camera::Distortion::from_opencv_vec(nalgebra::Vector5::new(k1, k2, p1, p2, 0.0));
+    let intrinsics = opencv_ros_camera::RosOpenCvIntrinsics::from_params_with_distortion(
+        k.fx, 0.0, k.fy, k.cx, k.cy, distortion,
+    );
+    Ok(CalibrationResult {
+        intrinsics,
+        mean_reprojection_distance_pixels: problem.rms_reprojection_distance(),
+        image_width: size.width.try_into().unwrap(),
+        image_height: size.height.try_into().unwrap(),
+    })
+}
+
+/// Given some checkerboard corner locations, compute intrinsics.
+///
+/// This is the pure Rust equivalent of `compute_intrinsics`, which uses OpenCV.
+pub fn calibrate_checkerboards(
+    size: PixelSize,
+    data: &[CheckerBoardData],
+) -> Result<CalibrationResult, Error> {
+    if data
+        .iter()
+        .any(|board| board.points.len() != board.n_rows * board.n_cols)
+    {
+        return Err(Error::BoardSizeMismatch);
+    }
+    let views: Vec<PlanarView> = data.iter().map(PlanarView::from).collect();
+    calibrate_planar_views(size, &views)
+}
+
 /// Given some checkerboard corner locations, compute intrinsics using OpenCV.
+#[cfg(feature = "opencv")]
 pub fn compute_intrinsics_with_raw_opencv<R: RealField>(
     size: PixelSize,
     data: &[CheckerBoardData],
@@ -82,7 +230,7 @@ pub fn compute_intrinsics_with_raw_opencv<R: RealField>(
     msg = cal.as_message()
     */
 
-    let object_points: Vec<Vec<Coords3D>> = mk_object_points(data);
+    let object_points: Vec<Vec<Coords3D>> = data.iter().map(board_object_points).collect();
     let image_points: Vec<Vec<Coords2D>> = data.iter().map(to_image_points).collect();
 
     debug_assert!(object_points.len() == image_points.len());
@@ -110,6 +258,7 @@ pub fn compute_intrinsics_with_raw_opencv<R: RealField>(
     )?)
 }
 
+#[cfg(feature = "opencv")]
 pub fn convert_to_cam_geom<R: RealField>(
     opencv_results: &opencv_calibrate::CalibrationResult,
 ) -> opencv_ros_camera::RosOpenCvIntrinsics<R> {
@@ -136,6 +285,7 @@ pub fn convert_to_cam_geom<R: RealField>(
 /// that unlike ROS, which scales the image so that k and p matrices are
 /// different, the code here does not. ROS does this so that undistorted images
 /// fill the entire image area.
+#[cfg(feature = "opencv")]
 pub fn compute_intrinsics<R: RealField>(
     size: PixelSize,
     data: &[CheckerBoardData],
@@ -144,7 +294,7 @@ pub fn compute_intrinsics<R: RealField>(
     Ok(convert_to_cam_geom(&opencv_results))
 }
 
-fn mk_object_points(data: &[CheckerBoardData]) -> Vec<Vec<Coords3D>> {
+fn board_object_points(b: &CheckerBoardData) -> Vec<(f64, f64, f64)> {
     /*
 
     def mk_object_points(self, boards, use_board_size = False):
@@ -165,17 +315,79 @@ fn mk_object_points(data: &[CheckerBoardData]) -> Vec<Vec<Coords3D>> {
         return opts
 
     */
-    let mut result = Vec::with_capacity(data.len());
-    for b in data.iter() {
-        let num_pts = b.n_cols * b.n_rows;
-        let mut opts_loc: Vec<Coords3D> = Vec::with_capacity(num_pts);
-        for j in 0..num_pts {
-            let x = (j as f64 / b.n_cols as f64).trunc();
-            let y = j as f64 % b.n_cols as f64;
-            let z = 0.0;
-            opts_loc.push((x, y, z));
+    let num_pts = b.n_cols * b.n_rows;
+    let mut opts_loc = Vec::with_capacity(num_pts);
+    for j in 0..num_pts {
+        let x = (j as f64 / b.n_cols as f64).trunc();
+        let y = j as f64 % b.n_cols as f64;
+        let z = 0.0;
+        opts_loc.push((x, y, z));
+    }
+    opts_loc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Rotation3, Vector3};
+
+    #[test]
+    fn test_calibrate_checkerboards() {
+        let truth = refine::PlumbBob {
+            pinhole: zhang::Pinhole {
+                fx: 1200.0,
+                fy: 1190.0,
+                cx: 950.0,
+                cy: 590.0,
+            },
+            distortion: [-0.23, 0.075, 1e-4, -2e-4],
+        };
+        let (width, height) = (1920, 1200);
+        let (n_rows, n_cols) = (8, 18);
+
+        // Deterministic noise in [-0.1, 0.1) pixels.
+        let mut state = 1u32;
+        let mut noise = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            0.2 * ((state >> 8) as f64 / (1u32 << 24) as f64 - 0.5)
+        };
+
+        let mut boards = Vec::new();
+        for i in 0..12 {
+            let a = i as f64 * 0.5;
+            let rotation = Rotation3::from_euler_angles(0.4 * a.cos(), 0.4 * a.sin(), 0.1 * a);
+            let translation = rotation * Vector3::new(-3.5, -8.5, 0.0)
+                + Vector3::new(2.0 * a.sin(), 1.5 * a.cos(), 20.0 + a);
+            let board = CheckerBoardData::new(n_rows, n_cols, &[]);
+            let points: Vec<Coords2D> = PlanarView::from(&board)
+                .object_points
+                .iter()
+                .map(|o| {
+                    let pt = rotation * Vector3::new(o.0, o.1, 0.0) + translation;
+                    let (u, v) = truth.project(&pt);
+                    assert!(u > 0.0 && u < width as f64 && v > 0.0 && v < height as f64);
+                    (u + noise(), v + noise())
+                })
+                .collect();
+            boards.push(CheckerBoardData::new(n_rows, n_cols, &points));
         }
-        result.push(opts_loc);
+
+        let cal = calibrate_checkerboards(PixelSize::new(width, height), &boards).unwrap();
+        let i = &cal.intrinsics;
+        approx::assert_relative_eq!(i.fx(), 1200.0, epsilon = 1.0);
+        approx::assert_relative_eq!(i.fy(), 1190.0, epsilon = 1.0);
+        approx::assert_relative_eq!(i.cx(), 950.0, epsilon = 1.0);
+        approx::assert_relative_eq!(i.cy(), 590.0, epsilon = 1.0);
+        approx::assert_relative_eq!(i.skew(), 0.0);
+        let d = i.distortion.opencv_vec();
+        approx::assert_relative_eq!(d[0], -0.23, epsilon = 0.005);
+        approx::assert_relative_eq!(d[1], 0.075, epsilon = 0.005);
+        approx::assert_relative_eq!(d[4], 0.0);
+        assert!(cal.mean_reprojection_distance_pixels < 0.1);
+
+        assert!(matches!(
+            calibrate_checkerboards(PixelSize::new(width, height), &[]),
+            Err(Error::NotEnoughViews)
+        ));
     }
-    result
 }
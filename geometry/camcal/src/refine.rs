@@ -0,0 +1,200 @@
+//! Nonlinear refinement of the calibration.
+//!
+//! The focal lengths, principal point and the distortion parameters `k1`,
+//! `k2`, `p1` and `p2` of the Brown-Conrady ("plumb bob") model are estimated
+//! together with the pose of the target in each view by minimizing the
+//! reprojection error with Levenberg-Marquardt. As in the calibration done by
+//! ROS `camera_calibration`, `k3` is held at zero and skew is not estimated.
+
+use levenberg_marquardt::LeastSquaresProblem;
+use nalgebra::{DMatrix, DVector, Dyn, Owned, Rotation3, Vector3};
+
+use crate::{
+    zhang::{Pinhole, Pose},
+    PlanarView,
+};
+
+/// Number of intrinsic parameters: fx, fy, cx, cy, k1, k2, p1, p2.
+const NUM_INTRINSIC: usize = 8;
+const NUM_POSE: usize = 6;
+
+/// Camera intrinsics with plumb bob distortion (with `k3` zero).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PlumbBob {
+    pub(crate) pinhole: Pinhole,
+    /// `k1`, `k2`, `p1`, `p2`, in OpenCV order.
+    pub(crate) distortion: [f64; 4],
+}
+
+impl PlumbBob {
+    fn from_params(p: &[f64]) -> Self {
+        Self {
+            pinhole: Pinhole {
+                fx: p[0],
+                fy: p[1],
+                cx: p[2],
+                cy: p[3],
+            },
+            distortion: [p[4], p[5], p[6], p[7]],
+        }
+    }
+
+    /// Project a point in the camera frame to pixel coordinates.
+    pub(crate) fn project(&self, pt: &Vector3<f64>) -> (f64, f64) {
+        let [k1, k2, p1, p2] = self.distortion;
+        let x = pt.x / pt.z;
+        let y = pt.y / pt.z;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (k1 + r2 * k2);
+        let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+        let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+        let k = &self.pinhole;
+        (k.fx * xd + k.cx, k.fy * yd + k.cy)
+    }
+}
+
+fn pose_from_params(p: &[f64]) -> Pose {
+    Pose {
+        rotation: Rotation3::from_scaled_axis(Vector3::new(p[0], p[1], p[2])),
+        translation: Vector3::new(p[3], p[4], p[5]),
+    }
+}
+
+/// Residuals (observed minus predicted) of one view.
+fn view_residuals(cam: &PlumbBob, pose: &Pose, view: &PlanarView, out: &mut [f64]) {
+    for (k, (o, i)) in view
+        .object_points
+        .iter()
+        .zip(view.image_points.iter())
+        .enumerate()
+    {
+        let pt = pose.rotation * Vector3::new(o.0, o.1, 0.0) + pose.translation;
+        let (u, v) = cam.project(&pt);
+        out[2 * k] = i.0 - u;
+        out[2 * k + 1] = i.1 - v;
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CalibrationProblem<'a> {
+    views: &'a [PlanarView],
+    /// Start of the residuals of each view.
+    offsets: Vec<usize>,
+    num_residuals: usize,
+    params: DVector<f64>,
+}
+
+impl<'a> CalibrationProblem<'a> {
+    pub(crate) fn new(views: &'a [PlanarView], cam: &PlumbBob, poses: &[Pose]) -> Self {
+        let mut params = Vec::with_capacity(NUM_INTRINSIC + NUM_POSE * poses.len());
+        let k = &cam.pinhole;
+        params.extend([k.fx, k.fy, k.cx, k.cy]);
+        params.extend(cam.distortion);
+        for pose in poses {
+            params.extend(pose.rotation.scaled_axis().iter());
+            params.extend(pose.translation.iter());
+        }
+        let mut offsets = Vec::with_capacity(views.len());
+        let mut num_residuals = 0;
+        for view in views {
+            offsets.push(num_residuals);
+            num_residuals += 2 * view.image_points.len();
+        }
+        Self {
+            views,
+            offsets,
+            num_residuals,
+            params: params.into(),
+        }
+    }
+
+    pub(crate) fn camera(&self) -> PlumbBob {
+        PlumbBob::from_params(&self.params.as_slice()[..NUM_INTRINSIC])
+    }
+
+    fn pose_params(&self, view_idx: usize) -> &[f64] {
+        let start = NUM_INTRINSIC + NUM_POSE * view_idx;
+        &self.params.as_slice()[start..start + NUM_POSE]
+    }
+
+    fn eval(&self, cam_params: &[f64], pose_params: &[f64], view_idx: usize, out: &mut [f64]) {
+        let cam = PlumbBob::from_params(cam_params);
+        let pose = pose_from_params(pose_params);
+        view_residuals(&cam, &pose, &self.views[view_idx], out);
+    }
+
+    fn all_residuals(&self) -> DVector<f64> {
+        let mut r = DVector::zeros(self.num_residuals);
+        let cam_params = &self.params.as_slice()[..NUM_INTRINSIC];
+        for (view_idx, view) in self.views.iter().enumerate() {
+            let start = self.offsets[view_idx];
+            let out = &mut r.as_mut_slice()[start..start + 2 * view.image_points.len()];
+            self.eval(cam_params, self.pose_params(view_idx), view_idx, out);
+        }
+        r
+    }
+
+    /// Root mean square reprojection distance, in pixels.
+    pub(crate) fn rms_reprojection_distance(&self) -> f64 {
+        (self.all_residuals().norm_squared() / (self.num_residuals / 2) as f64).sqrt()
+    }
+}
+
+impl LeastSquaresProblem<f64, Dyn, Dyn> for CalibrationProblem<'_> {
+    type ParameterStorage = Owned<f64, Dyn>;
+    type ResidualStorage = Owned<f64, Dyn>;
+    type JacobianStorage = Owned<f64, Dyn, Dyn>;
+
+    fn set_params(&mut self, x: &DVector<f64>) {
+        self.params.copy_from(x);
+    }
+
+    fn params(&self) -> DVector<f64> {
+        self.params.clone()
+    }
+
+    fn residuals(&self) -> Option<DVector<f64>> {
+        let r = self.all_residuals();
+        r.iter().all(|v| v.is_finite()).then_some(r)
+    }
+
+    fn jacobian(&self) -> Option<DMatrix<f64>> {
+        // Central differences. The intrinsic parameters affect all residuals
+        // but the pose of each view only the residuals of that view.
+        let mut jac = DMatrix::zeros(self.num_residuals, self.params.len());
+        let cam_params = &self.params.as_slice()[..NUM_INTRINSIC];
+        for (view_idx, view) in self.views.iter().enumerate() {
+            let n = 2 * view.image_points.len();
+            let start = self.offsets[view_idx];
+            let mut plus = vec![0.0; n];
+            let mut minus = vec![0.0; n];
+            let mut local: Vec<f64> = cam_params
+                .iter()
+                .chain(self.pose_params(view_idx))
+                .copied()
+                .collect();
+
+            for k in 0..local.len() {
+                let orig = local[k];
+                let h = 1e-6 * orig.abs().max(1.0);
+                local[k] = orig + h;
+                let (c, p) = local.split_at(NUM_INTRINSIC);
+                self.eval(c, p, view_idx, &mut plus);
+                local[k] = orig - h;
+                let (c, p) = local.split_at(NUM_INTRINSIC);
+                self.eval(c, p, view_idx, &mut minus);
+                local[k] = orig;
+
+                let col = if k < NUM_INTRINSIC {
+                    k
+                } else {
+                    k + NUM_POSE * view_idx
+                };
+                for row in 0..n {
+                    jac[(start + row, col)] = (plus[row] - minus[row]) / (2.0 * h);
+                }
+            }
+        }
+        jac.iter().all(|v| v.is_finite()).then_some(jac)
+    }
+}
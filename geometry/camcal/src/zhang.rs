@@ -0,0 +1,254 @@
+//! Initial estimate of intrinsic and extrinsic parameters from views of a
+//! planar target.
+//!
+//! This follows Z. Zhang, "A flexible new technique for camera calibration",
+//! IEEE TPAMI 22(11), 2000, assuming zero skew. If that fails, as it does with
+//! few views, the principal point is taken as the image center and only the
+//! focal lengths are estimated, as done in OpenCV.
+
+use nalgebra::{DMatrix, DVector, Matrix3, Rotation3, SMatrix, SymmetricEigen, Vector3};
+
+use crate::{Coords2D, Error, PlanarView};
+
+/// Linear pinhole intrinsics with zero skew.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Pinhole {
+    pub(crate) fx: f64,
+    pub(crate) fy: f64,
+    pub(crate) cx: f64,
+    pub(crate) cy: f64,
+}
+
+impl Pinhole {
+    fn k(&self) -> Matrix3<f64> {
+        Matrix3::new(self.fx, 0.0, self.cx, 0.0, self.fy, self.cy, 0.0, 0.0, 1.0)
+    }
+}
+
+/// Pose of the target relative to the camera.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Pose {
+    pub(crate) rotation: Rotation3<f64>,
+    pub(crate) translation: Vector3<f64>,
+}
+
+/// Transform normalizing points to zero mean and mean distance `sqrt(2)`.
+fn normalizing_transform(pts: &[Coords2D]) -> Matrix3<f64> {
+    let n = pts.len() as f64;
+    let mx = pts.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = pts.iter().map(|p| p.1).sum::<f64>() / n;
+    let mean_dist = pts
+        .iter()
+        .map(|p| ((p.0 - mx).powi(2) + (p.1 - my).powi(2)).sqrt())
+        .sum::<f64>()
+        / n;
+    let s = if mean_dist > 0.0 {
+        std::f64::consts::SQRT_2 / mean_dist
+    } else {
+        1.0
+    };
+    Matrix3::new(s, 0.0, -s * mx, 0.0, s, -s * my, 0.0, 0.0, 1.0)
+}
+
+fn apply(t: &Matrix3<f64>, p: &Coords2D) -> Coords2D {
+    let v = t * Vector3::new(p.0, p.1, 1.0);
+    (v.x / v.z, v.y / v.z)
+}
+
+/// Eigenvector of the smallest eigenvalue of a symmetric matrix.
+fn smallest_eigenvector(m: DMatrix<f64>) -> DVector<f64> {
+    let eig = SymmetricEigen::new(m);
+    let imin = eig.eigenvalues.imin();
+    eig.eigenvectors.column(imin).into_owned()
+}
+
+/// Estimate the homography mapping object points to image points with the
+/// normalized direct linear transform.
+pub(crate) fn find_homography(view: &PlanarView) -> Result<Matrix3<f64>, Error> {
+    if view.object_points.len() < 4 || view.object_points.len() != view.image_points.len() {
+        return Err(Error::NotEnoughPoints);
+    }
+    let t_obj = normalizing_transform(&view.object_points);
+    let t_img = normalizing_transform(&view.image_points);
+    let mut ata = SMatrix::<f64, 9, 9>::zeros();
+    for (o, i) in view.object_points.iter().zip(view.image_points.iter()) {
+        let (x, y) = apply(&t_obj, o);
+        let (u, v) = apply(&t_img, i);
+        let r1 =
+            SMatrix::<f64, 1, 9>::from_row_slice(&[x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, -u]);
+        let r2 =
+            SMatrix::<f64, 1, 9>::from_row_slice(&[0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, -v]);
+        ata += r1.transpose() * r1 + r2.transpose() * r2;
+    }
+    let h = smallest_eigenvector(DMatrix::from_iterator(9, 9, ata.iter().copied()));
+    let hn = Matrix3::from_row_slice(h.as_slice());
+    let t_img_inv = t_img.try_inverse().ok_or(Error::Degenerate)?;
+    let hm = t_img_inv * hn * t_obj;
+    if !hm.iter().all(|v| v.is_finite()) || hm[(2, 2)] == 0.0 {
+        return Err(Error::Degenerate);
+    }
+    Ok(hm / hm[(2, 2)])
+}
+
+/// Zhang's constraint vector `v_ij`.
+fn v(h: &Matrix3<f64>, i: usize, j: usize) -> SMatrix<f64, 1, 6> {
+    let hi = h.column(i);
+    let hj = h.column(j);
+    SMatrix::<f64, 1, 6>::from_row_slice(&[
+        hi[0] * hj[0],
+        hi[0] * hj[1] + hi[1] * hj[0],
+        hi[1] * hj[1],
+        hi[2] * hj[0] + hi[0] * hj[2],
+        hi[2] * hj[1] + hi[1] * hj[2],
+        hi[2] * hj[2],
+    ])
+}
+
+/// Estimate the intrinsics from homographies, which have been normalized so
+/// that the image is centered on the origin and of unit size.
+fn zhang_intrinsics(homographies: &[Matrix3<f64>]) -> Option<Pinhole> {
+    if homographies.len() < 3 {
+        return None;
+    }
+    let mut vtv = SMatrix::<f64, 6, 6>::zeros();
+    for h in homographies {
+        let v12 = v(h, 0, 1);
+        let d = v(h, 0, 0) - v(h, 1, 1);
+        vtv += v12.transpose() * v12 + d.transpose() * d;
+    }
+    // Zero skew, `B12 = 0`.
+    vtv[(1, 1)] += 1.0;
+    let b = smallest_eigenvector(DMatrix::from_iterator(6, 6, vtv.iter().copied()));
+    let (b11, b12, b22, b13, b23, b33) = (b[0], b[1], b[2], b[3], b[4], b[5]);
+    let denom = b11 * b22 - b12 * b12;
+    let v0 = (b12 * b13 - b11 * b23) / denom;
+    let lambda = b33 - (b13 * b13 + v0 * (b12 * b13 - b11 * b23)) / b11;
+    let alpha2 = lambda / b11;
+    let beta2 = lambda * b11 / denom;
+    if !(alpha2 > 0.0 && beta2 > 0.0) {
+        return None;
+    }
+    let alpha = alpha2.sqrt();
+    let beta = beta2.sqrt();
+    let u0 = -b13 * alpha2 / lambda;
+    let result = Pinhole {
+        fx: alpha,
+        fy: beta,
+        cx: u0,
+        cy: v0,
+    };
+    // The principal point should be inside the image.
+    if u0.abs() > 0.5 || v0.abs() > 0.5 || !alpha.is_finite() || !beta.is_finite() {
+        return None;
+    }
+    Some(result)
+}
+
+/// Estimate the focal lengths from homographies, normalized as for
+/// [zhang_intrinsics], with the principal point at the image center.
+fn centered_intrinsics(homographies: &[Matrix3<f64>]) -> Option<Pinhole> {
+    // Unknowns are `1/fx^2` and `1/fy^2`.
+    let mut ata = nalgebra::Matrix2::<f64>::zeros();
+    let mut atb = nalgebra::Vector2::<f64>::zeros();
+    for h in homographies {
+        let h1 = h.column(0).normalize();
+        let h2 = h.column(1).normalize();
+        let rows = [
+            (h1[0] * h2[0], h1[1] * h2[1], -h1[2] * h2[2]),
+            (
+                h1[0] * h1[0] - h2[0] * h2[0],
+                h1[1] * h1[1] - h2[1] * h2[1],
+                -(h1[2] * h1[2] - h2[2] * h2[2]),
+            ),
+        ];
+        for (a0, a1, b) in rows {
+            let a = nalgebra::Vector2::new(a0, a1);
+            ata += a * a.transpose();
+            atb += a * b;
+        }
+    }
+    let x = ata.try_inverse()? * atb;
+    if !(x[0] > 0.0 && x[1] > 0.0) {
+        return None;
+    }
+    Some(Pinhole {
+        fx: 1.0 / x[0].sqrt(),
+        fy: 1.0 / x[1].sqrt(),
+        cx: 0.0,
+        cy: 0.0,
+    })
+}
+
+/// Pose of the target from its homography and the intrinsics.
+pub(crate) fn pose_from_homography(h: &Matrix3<f64>, k: &Pinhole) -> Result<Pose, Error> {
+    let kinv = k.k().try_inverse().ok_or(Error::Degenerate)?;
+    let m = kinv * h;
+    let mut lambda = 1.0 / m.column(0).norm();
+    // The target is in front of the camera.
+    if m[(2, 2)] * lambda < 0.0 {
+        lambda = -lambda;
+    }
+    let r1 = m.column(0) * lambda;
+    let r2 = m.column(1) * lambda;
+    let r3 = r1.cross(&r2);
+    let t = m.column(2) * lambda;
+    let r = Matrix3::from_columns(&[r1, r2, r3]);
+    // Nearest rotation matrix.
+    let svd = r.svd(true, true);
+    let (u, v_t) = (
+        svd.u.ok_or(Error::Degenerate)?,
+        svd.v_t.ok_or(Error::Degenerate)?,
+    );
+    let mut rot = u * v_t;
+    if rot.determinant() < 0.0 {
+        rot = u * Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, -1.0)) * v_t;
+    }
+    Ok(Pose {
+        rotation: Rotation3::from_matrix_unchecked(rot),
+        translation: t,
+    })
+}
+
+/// Estimate intrinsics and the pose of the target in each view.
+pub(crate) fn initial_estimate(
+    width: usize,
+    height: usize,
+    views: &[PlanarView],
+) -> Result<(Pinhole, Vec<Pose>), Error> {
+    let homographies = views
+        .iter()
+        .map(find_homography)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Normalize the image to be centered on the origin with unit size.
+    let s = width.max(height) as f64;
+    let (cx0, cy0) = (0.5 * (width as f64 - 1.0), 0.5 * (height as f64 - 1.0));
+    let norm = Matrix3::new(
+        1.0 / s,
+        0.0,
+        -cx0 / s,
+        0.0,
+        1.0 / s,
+        -cy0 / s,
+        0.0,
+        0.0,
+        1.0,
+    );
+    let normalized: Vec<_> = homographies.iter().map(|h| norm * h).collect();
+
+    let k = zhang_intrinsics(&normalized)
+        .or_else(|| centered_intrinsics(&normalized))
+        .ok_or(Error::Degenerate)?;
+    let k = Pinhole {
+        fx: k.fx * s,
+        fy: k.fy * s,
+        cx: k.cx * s + cx0,
+        cy: k.cy * s + cy0,
+    };
+
+    let poses = homographies
+        .iter()
+        .map(|h| pose_from_homography(h, &k))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((k, poses))
+}
@@ -1,7 +1,7 @@
 use nalgebra as na;
 use nalgebra::core::dimension::{U3, U4};
 use nalgebra::core::OMatrix;
-use nalgebra::RealField;
+use nalgebra::{RealField, Vector3};
 
 use serde::{Deserialize, Serialize};
 
@@ -15,10 +15,30 @@ pub struct FlydraReconstructor<R: RealField + serde::Serialize> {
     pub minimum_eccentricity: R,
     #[serde(default)]
     pub water: Option<R>,
+    #[serde(default, rename = "refractive_interface")]
+    pub refractive_interfaces: Vec<FlydraRefractiveInterface<R>>,
     #[serde(default)]
     pub comment: Option<String>,
 }
 
+/// A planar refractive boundary, see [crate::RefractiveInterface].
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields, rename = "refractive_interface")]
+pub struct FlydraRefractiveInterface<R: RealField + serde::Serialize> {
+    #[serde(
+        serialize_with = "serialize_vector3",
+        deserialize_with = "deserialize_vector3"
+    )]
+    pub normal: Vector3<R>,
+    pub offset: R,
+    pub n1: R,
+    pub n2: R,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wall_thickness: Option<R>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wall_n: Option<R>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields, rename = "single_camera_calibration")]
 pub struct SingleCameraCalibration<R: RealField + serde::Serialize> {
@@ -39,6 +59,10 @@ pub struct SingleCameraCalibration<R: RealField + serde::Serialize> {
     pub scale_factor: Option<R>,
     #[serde(serialize_with = "serialize_non_linear_parameters")]
     pub non_linear_parameters: FlydraDistortionModel<R>,
+    /// Time, in seconds, by which the images of this camera are exposed after
+    /// the synchronized frame time. See [crate::MultiCamera::time_offset].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_offset: Option<R>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -54,6 +78,14 @@ pub struct FlydraDistortionModel<R: RealField + serde::Serialize> {
     pub p2: R,
     #[serde(default, skip_serializing_if = "is_zero")]
     pub k3: R,
+    /// Only used by the Kannala-Brandt fisheye model.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub k4: R,
+    /// Absent for the Brown-Conrady model used by MultiCamSelfCal. If
+    /// [`EQUIDISTANT_DISTORTION_MODEL`], `k1` through `k4` are Kannala-Brandt
+    /// fisheye coefficients and `p1` and `p2` are zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distortion_model: Option<String>,
     pub alpha_c: R,
     #[serde(default, skip_serializing)]
     pub fc1p: Option<R>,
@@ -65,6 +97,9 @@ pub struct FlydraDistortionModel<R: RealField + serde::Serialize> {
     pub cc2p: Option<R>,
 }
 
+/// Value of [`FlydraDistortionModel::distortion_model`] for fisheye cameras.
+pub const EQUIDISTANT_DISTORTION_MODEL: &str = "equidistant";
+
 fn is_zero<R: RealField>(val: &R) -> bool {
     let zero: R = na::convert(0.0);
     val == &zero
@@ -93,6 +128,9 @@ where
     if let Some(ref w) = recon.water {
         v.push(format!("    <water>{w}</water>"));
     }
+    for iface in recon.refractive_interfaces.iter() {
+        v.push(format!("    {}", serde_xml_rs::to_string(iface)?));
+    }
     if let Some(ref c) = recon.comment {
         v.push(format!("    <comment>{c}</comment>"));
     }
@@ -158,6 +196,34 @@ where
     Ok(OMatrix::<R, U3, U4>::from_row_slice(elements.as_slice()))
 }
 
+fn serialize_vector3<S, R>(v: &Vector3<R>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    R: RealField + Serialize,
+{
+    let buf = format!("{} {} {}", v[0], v[1], v[2]);
+    serializer.serialize_str(&buf)
+}
+
+fn deserialize_vector3<'de, D, R>(deserializer: D) -> Result<Vector3<R>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    R: RealField,
+{
+    use std::str::FromStr;
+
+    let s = String::deserialize(deserializer)?;
+    let elements = s
+        .split_whitespace()
+        .map(|x| f64::from_str(x).map(na::convert))
+        .collect::<Result<Vec<R>, _>>()
+        .map_err(serde::de::Error::custom)?;
+    if elements.len() != 3 {
+        return Err(serde::de::Error::custom("expected exactly 3 numbers"));
+    }
+    Ok(Vector3::from_column_slice(&elements))
+}
+
 fn serialize_two_ints<S>(two_ints: &(usize, usize), serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
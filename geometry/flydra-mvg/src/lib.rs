@@ -15,17 +15,21 @@ use nalgebra::{
 use cam_geom::ExtrinsicParameters;
 use opencv_ros_camera::{Distortion, RosOpenCvIntrinsics};
 
+use braid_mvg::fisheye::FisheyeDistortion;
 use braid_mvg::{
     rq_decomposition, vec_sum, Camera, DistortedPixel, MultiCameraSystem, MvgError,
     PointWorldFrame, PointWorldFrameMaybeWithSumReprojError, PointWorldFrameWithSumReprojError,
     UndistortedPixel, WorldCoordAndUndistorted2D,
 };
 
-mod fermats_least_time;
-
 pub mod flydra_xml_support;
+mod refractive_interface;
+
+pub use refractive_interface::{RefractiveInterface, Wall};
 
-use crate::flydra_xml_support::{FlydraDistortionModel, SingleCameraCalibration};
+use crate::flydra_xml_support::{
+    FlydraDistortionModel, SingleCameraCalibration, EQUIDISTANT_DISTORTION_MODEL,
+};
 
 const AIR_REFRACTION: f64 = 1.0003;
 
@@ -43,6 +47,8 @@ pub enum FlydraMvgError {
     NotImplemented,
     #[error("no valid root found")]
     NoValidRootFound,
+    #[error("invalid refractive interface: {msg}")]
+    InvalidRefractiveInterface { msg: &'static str },
     #[error("No non-linear parameter file {0} found")]
     NoNonlinearParameters(PathBuf),
 }
@@ -144,10 +150,10 @@ where
 
 /// defines operations with Ray type
 ///
-/// Rays can be easier to work with when the camera system may have water as
-/// rays are defined from an origin (typically the camera center) in a
-/// direction rather than a point in 3D space, which may be on the other side
-/// of a refractive boundary.
+/// Rays can be easier to work with when the camera system may have refractive
+/// boundaries as rays are defined from an origin (typically the camera center)
+/// in a direction rather than a point in 3D space, which may be on the other
+/// side of a refractive boundary.
 trait RayCamera<R: RealField + Copy> {
     fn project_pixel_to_ray(&self, pt: &UndistortedPixel<R>) -> parry3d_f64::query::Ray;
     fn project_distorted_pixel_to_ray(&self, pt2d: &DistortedPixel<R>) -> parry3d_f64::query::Ray;
@@ -167,8 +173,16 @@ impl<R: RealField + Copy + Default + serde::Serialize> RayCamera<R> for Camera<R
     }
 
     fn project_distorted_pixel_to_ray(&self, pt2d: &DistortedPixel<R>) -> parry3d_f64::query::Ray {
-        let undistorted = self.intrinsics().undistort(&pt2d.into());
-        self.project_pixel_to_ray(&undistorted.into())
+        if self.fisheye_distortion().is_some() {
+            // Pixels at or beyond 90° from the optical axis have no
+            // undistorted pixel, so back-project directly.
+            let p2 = self.project_distorted_pixel_to_3d_with_dist(pt2d, na::convert(1.0));
+            let ray_origin = *self.extrinsics().camcenter();
+            let ray_dir = p2.coords - ray_origin;
+            return parry3d_f64::query::Ray::new(ray_origin.to_f64(), ray_dir.to_f64());
+        }
+        let undistorted = self.undistort(pt2d);
+        self.project_pixel_to_ray(&undistorted)
     }
 
     fn project_ray_to_distorted_pixel(&self, ray: &parry3d_f64::query::Ray) -> DistortedPixel<R> {
@@ -191,7 +205,7 @@ impl<R: RealField + Copy + Default + serde::Serialize> RayCamera<R> for Camera<R
 
 // MultiCamera -------------------------------------------------------
 
-/// A camera which may be looking at water
+/// A camera which may be looking through refractive boundaries, such as water
 ///
 /// Note that we specifically do not have the methods
 /// `project_distorted_pixel_to_3d_with_dist` and `project_pixel_to_3d_with_dist`
@@ -200,9 +214,10 @@ impl<R: RealField + Copy + Default + serde::Serialize> RayCamera<R> for Camera<R
 /// ray based methods.
 #[derive(Clone, Debug)]
 pub struct MultiCamera<R: RealField + Copy + Default + serde::Serialize> {
-    water: Option<R>,
+    interfaces: Vec<RefractiveInterface<R>>,
     name: String,
     cam: Camera<R>,
+    time_offset: R,
 }
 
 impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
@@ -210,6 +225,17 @@ impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
         self.cam
     }
 
+    /// Time, in seconds, by which the images of this camera are exposed after
+    /// the synchronized frame time.
+    ///
+    /// An object with position `x` and velocity `v` at the frame time is seen
+    /// by this camera at `x + v * time_offset`. This is zero unless estimated,
+    /// for example for cameras with rolling shutters or trigger delays.
+    #[inline]
+    pub fn time_offset(&self) -> R {
+        self.time_offset
+    }
+
     #[inline]
     pub fn project_pixel_to_ray(&self, pt: &UndistortedPixel<R>) -> parry3d_f64::query::Ray {
         self.cam.project_pixel_to_ray(pt)
@@ -238,44 +264,34 @@ impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
 
     /// projects a 3D point to a ray
     ///
-    /// If the point is under water, the ray is in the direction the camera
-    /// sees it (not the straight-line direction).
+    /// If the point is behind a refractive boundary, the ray is in the
+    /// direction the camera sees it (not the straight-line direction). If the
+    /// straight line from the camera to the point crosses several boundaries,
+    /// only the first one is taken into account.
     pub fn project_3d_to_ray(&self, pt3d: &PointWorldFrame<R>) -> parry3d_f64::query::Ray {
         let camcenter = self.extrinsics().camcenter();
+        let pt = &pt3d.coords.coords;
 
-        let dir: Vector3<R> = if self.water.is_some() && pt3d.coords[2] < na::convert(0.0) {
+        let crossed = self
+            .interfaces
+            .iter()
+            .filter(|iface| iface.separates(&camcenter.coords, pt))
+            .min_by(|a, b| {
+                let fa = a.crossing_fraction(&camcenter.coords, pt);
+                let fb = b.crossing_fraction(&camcenter.coords, pt);
+                fa.partial_cmp(&fb).unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+        let dir: Vector3<R> = if let Some(iface) = crossed {
             // this is tag "laksdfjasl".
-            let n1 = na::convert(AIR_REFRACTION);
-            let n2 = self.water.unwrap();
-
-            let camcenter_z0 =
-                Point3::from(Vector3::new(camcenter[0], camcenter[1], na::convert(0.0)));
-            let shifted_pt = pt3d.coords - camcenter_z0; // origin under cam at surface. cam at (0,0,z).
-            let theta = shifted_pt[1].atan2(shifted_pt[0]); // angles to points
-            let r = (shifted_pt[0].powi(2) + shifted_pt[1].powi(2)).sqrt(); // horizontal dist
-            let depth = -shifted_pt[2];
-            let height = camcenter[2];
-
-            let water_roots_eps = na::convert(1e-5);
-            let root_params =
-                fermats_least_time::RootParams::new(n1, n2, height, r, depth, water_roots_eps);
-            let r0 = match fermats_least_time::find_fastest_path_fermat(&root_params) {
-                Ok(r0) => r0,
+            let entry_pt = match iface.entry_point(&camcenter.coords, pt) {
+                Ok(entry_pt) => entry_pt,
                 Err(e) => {
-                    tracing::error!(
-                        "find_fastest_path_fermat {} with parameters: {:?}",
-                        e,
-                        root_params,
-                    );
-                    panic!("find_fastest_path_fermat {e} with parameters: {root_params:?}");
+                    tracing::error!("refraction {} with interface: {:?}", e, iface);
+                    panic!("refraction {e} with interface: {iface:?}");
                 }
             };
-
-            let shifted_water_surface_pt =
-                Vector3::new(r0 * theta.cos(), r0 * theta.sin(), na::convert(0.0));
-
-            let water_surface_pt = shifted_water_surface_pt + camcenter_z0.coords;
-            water_surface_pt - camcenter.coords
+            entry_pt - camcenter.coords
         } else {
             pt3d.coords - camcenter
         };
@@ -286,48 +302,77 @@ impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
         parry3d_f64::query::Ray::new(camcenter.to_f64(), dir.to_f64())
     }
 
+    /// Whether `a` and `b` are on the same side of all refractive boundaries.
+    fn same_medium(&self, a: &Vector3<R>, b: &Vector3<R>) -> bool {
+        self.interfaces
+            .iter()
+            .all(|iface| iface.is_beyond(a) == iface.is_beyond(b))
+    }
+
     #[allow(non_snake_case)]
     pub fn linearize_numerically_at(
         &self,
         center: &PointWorldFrame<R>,
         delta: R,
     ) -> Result<OMatrix<R, U2, U3>> {
-        let zero = na::convert(0.0);
-
-        let dx = Vector3::<R>::new(delta, zero, zero);
-        let dy = Vector3::<R>::new(zero, delta, zero);
-        let dz = Vector3::<R>::new(zero, zero, delta);
+        let F = self.project_3d_to_pixel(center).coords;
 
-        let center_x = PointWorldFrame {
-            coords: center.coords + dx,
-        };
-        let center_y = PointWorldFrame {
-            coords: center.coords + dy,
-        };
-        let center_z = PointWorldFrame {
-            coords: center.coords + dz,
+        // The projection is not differentiable at a refractive boundary, so
+        // take the step away from a boundary close to `center`.
+        let derivative = |axis: usize| {
+            let mut step = Vector3::<R>::zeros();
+            step[axis] = delta;
+            if !self.same_medium(&center.coords.coords, &(center.coords.coords + step)) {
+                step[axis] = -delta;
+            }
+            let moved = PointWorldFrame {
+                coords: center.coords + step,
+            };
+            (self.project_3d_to_pixel(&moved).coords - F) / step[axis]
         };
 
-        let F = self.project_3d_to_pixel(center).coords;
-        let Fx = self.project_3d_to_pixel(&center_x).coords;
-        let Fy = self.project_3d_to_pixel(&center_y).coords;
-        let Fz = self.project_3d_to_pixel(&center_z).coords;
-
-        let dF_dx = (Fx - F) / delta;
-        let dF_dy = (Fy - F) / delta;
-        let dF_dz = (Fz - F) / delta;
+        let dF_dx = derivative(0);
+        let dF_dy = derivative(1);
+        let dF_dz = derivative(2);
 
         Ok(OMatrix::<R, U2, U3>::new(
             dF_dx[0], dF_dy[0], dF_dz[0], dF_dx[1], dF_dy[1], dF_dz[1],
         ))
     }
 
+    /// Linearize the undistortion at the distorted pixel `center`.
+    ///
+    /// Returns the derivative of the undistorted pixel with respect to the
+    /// distorted pixel, using central differences with a step of `delta`
+    /// pixels.
+    pub fn linearize_undistortion_numerically_at(
+        &self,
+        center: &DistortedPixel<R>,
+        delta: R,
+    ) -> OMatrix<R, U2, U2> {
+        let two: R = na::convert(2.0);
+        let derivative = |axis: usize| {
+            let mut step = na::Vector2::<R>::zeros();
+            step[axis] = delta;
+            let plus = DistortedPixel {
+                coords: center.coords + step,
+            };
+            let minus = DistortedPixel {
+                coords: center.coords - step,
+            };
+            (self.undistort(&plus).coords - self.undistort(&minus).coords) / (two * delta)
+        };
+        let du_dx = derivative(0);
+        let du_dy = derivative(1);
+        OMatrix::<R, U2, U2>::new(du_dx[0], du_dy[0], du_dx[1], du_dy[1])
+    }
+
     pub fn project_3d_to_pixel(&self, pt3d: &PointWorldFrame<R>) -> UndistortedPixel<R> {
-        let ray = self.project_3d_to_ray(pt3d); // This handles water correctly
+        let ray = self.project_3d_to_ray(pt3d); // This handles refraction correctly
                                                 // (i.e. a 3D point is not necessarily seen with the ray direct from the cam center
                                                 // to that 3D point).
 
-        // From here, we use normal camera stuff (no need to know about water).
+        // From here, we use normal camera stuff (no need to know about refraction).
         let coords: Point3<R> = (ray.origin + ray.dir).to_r();
         let pt_air =
             cam_geom::Points::<cam_geom::WorldFrame, _, _, _>::new(coords.coords.transpose());
@@ -342,9 +387,14 @@ impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
     where
         DefaultAllocator: Allocator<U1, U2>,
     {
+        if self.cam.fisheye_distortion().is_some() {
+            // Go via the ray so that points without an undistorted pixel,
+            // at or beyond 90° from the optical axis, are handled.
+            let ray = self.project_3d_to_ray(pt3d);
+            return self.cam.project_ray_to_distorted_pixel(&ray);
+        }
         let undistorted = self.project_3d_to_pixel(pt3d);
-        let u2: opencv_ros_camera::UndistortedPixels<R, U1, _> = (&undistorted).into();
-        self.cam.intrinsics().distort(&u2).into()
+        self.cam.distort(&undistorted)
     }
 
     #[inline]
@@ -362,10 +412,13 @@ impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
     }
 
     pub fn undistort(&self, a: &braid_mvg::DistortedPixel<R>) -> braid_mvg::UndistortedPixel<R> {
-        let a2: cam_geom::Pixels<R, U1, _> = a.into();
-        let b1: opencv_ros_camera::UndistortedPixels<R, U1, _> =
-            self.cam.intrinsics().undistort(&a2);
-        b1.into()
+        self.cam.undistort(a)
+    }
+
+    /// Whether the camera has Kannala-Brandt fisheye distortion.
+    #[inline]
+    pub fn is_fisheye(&self) -> bool {
+        self.cam.fisheye_distortion().is_some()
     }
 
     #[inline]
@@ -390,21 +443,79 @@ impl<R: RealField + Copy + Default + serde::Serialize> MultiCamera<R> {
 pub struct FlydraMultiCameraSystem<R: RealField + Copy + serde::Serialize> {
     system: MultiCameraSystem<R>,
     water: Option<R>,
+    refractive_interfaces: Vec<RefractiveInterface<R>>,
+    time_offsets: BTreeMap<String, R>,
 }
 
 impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R> {
     pub fn from_system(system: MultiCameraSystem<R>, water: Option<R>) -> Self {
-        FlydraMultiCameraSystem { system, water }
+        FlydraMultiCameraSystem {
+            system,
+            water,
+            refractive_interfaces: Vec::new(),
+            time_offsets: BTreeMap::new(),
+        }
+    }
+
+    /// Set the planar refractive boundaries in addition to the water surface.
+    pub fn with_refractive_interfaces(mut self, interfaces: Vec<RefractiveInterface<R>>) -> Self {
+        self.refractive_interfaces = interfaces;
+        self
+    }
+
+    /// Set the time offsets, in seconds, of cameras by name. Cameras not
+    /// included have no time offset. See [MultiCamera::time_offset].
+    pub fn with_time_offsets(mut self, time_offsets: BTreeMap<String, R>) -> Self {
+        self.time_offsets = time_offsets;
+        self
+    }
+
+    /// Time offsets, in seconds, of the cameras with a non-zero time offset.
+    pub fn time_offsets(&self) -> &BTreeMap<String, R> {
+        &self.time_offsets
     }
 
     pub fn has_refractive_boundary(&self) -> bool {
-        self.water.is_some()
+        self.water.is_some() || !self.refractive_interfaces.is_empty()
     }
 
+    /// If `Some(n)`, water with refractive index `n` at z<0.
     pub fn water(&self) -> Option<R> {
         self.water
     }
 
+    /// Planar refractive boundaries in addition to the water surface.
+    pub fn refractive_interfaces(&self) -> &[RefractiveInterface<R>] {
+        &self.refractive_interfaces
+    }
+
+    /// Transform the system with the similarity transform `x' = s*rot*x + t`
+    /// as done by [MultiCameraSystem::align].
+    ///
+    /// The refractive interfaces are transformed along with the cameras. The
+    /// water surface is always at z=0 and is not transformed. The time offsets
+    /// are kept.
+    pub fn align(&self, s: R, rot: Matrix3<R>, t: Vector3<R>) -> Result<Self> {
+        let system = self.system.align(s, rot, t)?;
+        let interfaces = self
+            .refractive_interfaces
+            .iter()
+            .map(|iface| iface.align(s, rot, t))
+            .collect();
+        Ok(Self::from_system(system, self.water)
+            .with_refractive_interfaces(interfaces)
+            .with_time_offsets(self.time_offsets.clone()))
+    }
+
+    /// All refractive boundaries, including the water surface.
+    fn all_interfaces(&self) -> Vec<RefractiveInterface<R>> {
+        self.water
+            .map(RefractiveInterface::water_surface)
+            .into_iter()
+            .chain(self.refractive_interfaces.iter().cloned())
+            .collect()
+    }
+
     pub fn to_system(self) -> MultiCameraSystem<R> {
         self.system
     }
@@ -416,7 +527,7 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
     pub fn new(cams_by_name: BTreeMap<String, Camera<R>>, water: Option<R>) -> Self {
         let system = MultiCameraSystem::new(cams_by_name);
 
-        FlydraMultiCameraSystem { system, water }
+        Self::from_system(system, water)
     }
 
     pub fn len(&self) -> usize {
@@ -429,9 +540,10 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
 
     pub fn cam_by_name(&self, name: &str) -> Option<MultiCamera<R>> {
         self.system.cam_by_name(name).map(|cam| MultiCamera {
-            water: self.water,
+            interfaces: self.all_interfaces(),
             name: name.to_string(),
             cam: cam.clone(),
+            time_offset: self.time_offsets.get(name).copied().unwrap_or_else(R::zero),
         })
     }
 
@@ -466,10 +578,10 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
 
     /// Find 3D coordinate using pixel coordinates from cameras
     ///
-    /// If the system has water, two evaluations are done: one
-    /// for the case of the 3D point being under water, the other
-    /// for the case of the 3D point being in air. The evaluation
-    /// with the lowest mean reprojection error is selected.
+    /// If the system has refractive boundaries, two evaluations are done: one
+    /// for the case of the 3D point being behind the boundaries (e.g. under
+    /// water), the other for the case of the 3D point being in air. The
+    /// evaluation with the lowest mean reprojection error is selected.
     pub fn find3d(
         &self,
         points: &[(String, UndistortedPixel<R>)],
@@ -480,44 +592,43 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
 
         use crate::PointWorldFrameMaybeWithSumReprojError::*;
 
-        match self.water {
-            Some(n2) => {
-                // TODO: would it be possible to have a 3d reconstruction with
-                // lower reprojection error when it was z<0 but with the air
-                // based calculation? This would seem problematic...
-                let opt_water_3d_pt = match self.find3d_water(points, n2) {
-                    Ok(water_3d_pt) => Some(water_3d_pt),
-                    Err(FlydraMvgError::MvgError(MvgError::CamGeomError { .. })) => None,
-                    Err(e) => {
-                        return Err(e);
-                    }
-                };
-                let air_3d_pt = self.find3d_air(points)?;
-
-                let air_dists = self.get_reprojection_undistorted_dists(points, &air_3d_pt)?;
-                let air_dist_sum = vec_sum(&air_dists);
-
-                if let Some(water_3d_pt) = opt_water_3d_pt {
-                    let water_dists =
-                        self.get_reprojection_undistorted_dists(points, &water_3d_pt)?;
-                    let water_dist_sum = vec_sum(&water_dists);
-                    if water_dist_sum < air_dist_sum {
-                        Ok(WithSumReprojError(PointWorldFrameWithSumReprojError::new(
-                            water_3d_pt,
-                            water_dists,
-                        )))
-                    } else {
-                        Ok(WithSumReprojError(PointWorldFrameWithSumReprojError::new(
-                            air_3d_pt, air_dists,
-                        )))
-                    }
-                } else {
-                    Ok(WithSumReprojError(PointWorldFrameWithSumReprojError::new(
-                        air_3d_pt, air_dists,
-                    )))
-                }
+        if !self.has_refractive_boundary() {
+            return Ok(Point(self.system.find3d(points)?));
+        }
+
+        // TODO: would it be possible to have a 3d reconstruction with
+        // lower reprojection error when it was behind a boundary but with the
+        // air based calculation? This would seem problematic...
+        let opt_refracted_3d_pt = match self.find3d_refracted(points) {
+            Ok(refracted_3d_pt) => Some(refracted_3d_pt),
+            Err(FlydraMvgError::MvgError(MvgError::CamGeomError { .. })) => None,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        let air_3d_pt = self.find3d_air(points)?;
+
+        let air_dists = self.get_reprojection_undistorted_dists(points, &air_3d_pt)?;
+        let air_dist_sum = vec_sum(&air_dists);
+
+        if let Some(refracted_3d_pt) = opt_refracted_3d_pt {
+            let refracted_dists =
+                self.get_reprojection_undistorted_dists(points, &refracted_3d_pt)?;
+            let refracted_dist_sum = vec_sum(&refracted_dists);
+            if refracted_dist_sum < air_dist_sum {
+                Ok(WithSumReprojError(PointWorldFrameWithSumReprojError::new(
+                    refracted_3d_pt,
+                    refracted_dists,
+                )))
+            } else {
+                Ok(WithSumReprojError(PointWorldFrameWithSumReprojError::new(
+                    air_3d_pt, air_dists,
+                )))
             }
-            None => Ok(Point(self.system.find3d(points)?)),
+        } else {
+            Ok(WithSumReprojError(PointWorldFrameWithSumReprojError::new(
+                air_3d_pt, air_dists,
+            )))
         }
     }
 
@@ -541,66 +652,32 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
         ))
     }
 
-    fn find3d_water(
+    /// Find 3D coordinate assuming it is behind the refractive boundaries.
+    ///
+    /// The ray of each camera is refracted at the first boundary it crosses.
+    /// Cameras whose rays do not cross a boundary are ignored.
+    fn find3d_refracted(
         &self,
         points: &[(String, UndistortedPixel<R>)],
-        n2: R,
     ) -> Result<PointWorldFrame<R>> {
         use cam_geom::{Ray, WorldFrame};
-        let z0 = parry3d_f64::shape::HalfSpace::new(Vector3::z_axis());
 
+        let interfaces = self.all_interfaces();
         let mut rays: Vec<Ray<WorldFrame, _>> = Vec::with_capacity(points.len());
 
         for (name, xy) in points.iter() {
             let cam = self.cam_by_name(name).ok_or(MvgError::UnknownCamera)?;
             let air_ray = cam.project_pixel_to_ray(xy);
-            let solid = false; // will intersect either side of plane
-
-            let opt_surface_pt_toi: Option<f64> = parry3d_f64::query::RayCast::cast_local_ray(
-                &z0,
-                &air_ray,
-                f64::max_value().unwrap(),
-                solid,
-            );
-
-            let air_ray_origin = air_ray.origin.to_r();
-            let air_ray_dir = air_ray.dir.to_r();
-
-            if let Some(toi) = opt_surface_pt_toi {
-                let toi: R = na::convert(toi);
-                let surface_pt: Point3<R> = air_ray_origin + air_ray_dir * toi;
-
-                // closest point to camera on water surface, assumes water at z==0
-                let camcenter = &air_ray_origin;
-                let camcenter_z0: Point3<R> =
-                    Point3::from(Vector3::new(camcenter[0], camcenter[1], na::convert(0.0)));
-
-                let surface_pt_cam = surface_pt - camcenter_z0;
-
-                // Get underwater line from water surface (using Snell's Law).
-                let y = surface_pt_cam[1];
-                let x = surface_pt_cam[0];
-                let pt_angle = y.atan2(x);
-                let pt_horiz_dist = (x * x + y * y).sqrt(); // horizontal distance from camera to water surface
-                let theta_air = pt_horiz_dist.atan2(camcenter[2]);
-
-                // sin(theta_water)/sin(theta_air) = sin(n_air)/sin(n_water)
-                let n_air = na::convert(AIR_REFRACTION);
-                let sin_theta_water = theta_air.sin() * n_air / n2;
-                let theta_water = sin_theta_water.asin();
-                let horiz_dist_at_depth_1 = theta_water.tan();
-                let horiz_dist_cam_depth_1 = horiz_dist_at_depth_1 + pt_horiz_dist; // total horizontal distance
-                let deep_pt_cam: Vector3<R> = Vector3::new(
-                    horiz_dist_cam_depth_1 * pt_angle.cos(),
-                    horiz_dist_cam_depth_1 * pt_angle.sin(),
-                    na::convert(-1.0),
-                );
-                let deep_pt = deep_pt_cam + camcenter_z0.coords;
-                let water_ray_dir = deep_pt - surface_pt.coords;
-                rays.push(Ray::new(
-                    surface_pt.coords.transpose(),
-                    water_ray_dir.transpose(),
-                ));
+            let air_ray_origin: Vector3<R> = air_ray.origin.coords.to_r();
+            let air_ray_dir: Vector3<R> = air_ray.dir.to_r();
+
+            let refracted = interfaces
+                .iter()
+                .filter_map(|iface| iface.refract_ray(&air_ray_origin, &air_ray_dir))
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+            if let Some((_toi, surface_pt, water_ray_dir)) = refracted {
+                rays.push(Ray::new(surface_pt.transpose(), water_ray_dir.transpose()));
             }
         }
         let pt = cam_geom::best_intersection_of_rays(&rays).map_err(braid_mvg::MvgError::from)?;
@@ -639,12 +716,23 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
     ) -> Result<Self> {
         let water = recon.water;
         let mut cams = BTreeMap::new();
+        let mut time_offsets = BTreeMap::new();
         for flydra_cam in recon.cameras.iter() {
             let (name, cam) = Camera::from_flydra(flydra_cam)?;
+            if let Some(time_offset) = flydra_cam.time_offset {
+                time_offsets.insert(name.clone(), time_offset);
+            }
             cams.insert(name, cam);
         }
+        let refractive_interfaces = recon
+            .refractive_interfaces
+            .iter()
+            .map(RefractiveInterface::from_flydra)
+            .collect::<Result<Vec<_>>>()?;
         let _ = recon.minimum_eccentricity;
-        Ok(Self::new(cams, water))
+        Ok(Self::new(cams, water)
+            .with_refractive_interfaces(refractive_interfaces)
+            .with_time_offsets(time_offsets))
     }
 
     pub fn to_flydra_reconstructor(&self) -> Result<flydra_xml_support::FlydraReconstructor<R>> {
@@ -653,18 +741,25 @@ impl<R: RealField + Copy + Default + serde::Serialize> FlydraMultiCameraSystem<R
             .cams_by_name()
             .iter()
             .map(|(name, cam)| {
-                let flydra_cam: flydra_xml_support::SingleCameraCalibration<R> =
+                let mut flydra_cam: flydra_xml_support::SingleCameraCalibration<R> =
                     cam.to_flydra(name)?;
+                flydra_cam.time_offset = self.time_offsets.get(name).copied();
                 Ok(flydra_cam)
             })
             .collect();
         let cameras = cameras?;
         let water = self.water;
+        let refractive_interfaces = self
+            .refractive_interfaces
+            .iter()
+            .map(RefractiveInterface::to_flydra)
+            .collect();
 
         Ok(flydra_xml_support::FlydraReconstructor {
             cameras,
             comment: self.system.comment().cloned(),
             water,
+            refractive_interfaces,
             minimum_eccentricity: na::convert(0.0),
         })
     }
@@ -755,6 +850,8 @@ where
         p1: vars["kc3"],
         p2: vars["kc4"],
         k3: na::convert(0.0),
+        k4: na::convert(0.0),
+        distortion_model: None,
         fc1p: None,
         fc2p: None,
         cc1p: None,
@@ -806,6 +903,7 @@ where
             resolution: (w, h),
             scale_factor: None,
             non_linear_parameters,
+            time_offset: None,
         };
         cameras.push(cam);
 
@@ -891,6 +989,29 @@ impl<R: RealField + Copy + serde::Serialize> FlydraCamera<R> for Camera<R> {
         let k = self.intrinsics().k;
         let distortion = &self.intrinsics().distortion;
         let alpha_c = k[(0, 1)] / k[(0, 0)];
+        let zero = R::zero();
+
+        let ([k1, k2, p1, p2, k3], k4, distortion_model) = match self.fisheye_distortion() {
+            Some(fisheye) => {
+                let [k1, k2, k3, k4] = fisheye.opencv_vec();
+                (
+                    [k1, k2, zero, zero, k3],
+                    k4,
+                    Some(EQUIDISTANT_DISTORTION_MODEL.to_string()),
+                )
+            }
+            None => (
+                [
+                    distortion.radial1(),
+                    distortion.radial2(),
+                    distortion.tangential1(),
+                    distortion.tangential2(),
+                    distortion.radial3(),
+                ],
+                zero,
+                None,
+            ),
+        };
 
         let non_linear_parameters = FlydraDistortionModel {
             fc1: k[(0, 0)],
@@ -898,11 +1019,13 @@ impl<R: RealField + Copy + serde::Serialize> FlydraCamera<R> for Camera<R> {
             cc1: k[(0, 2)],
             cc2: k[(1, 2)],
             alpha_c,
-            k1: distortion.radial1(),
-            k2: distortion.radial2(),
-            p1: distortion.tangential1(),
-            p2: distortion.tangential2(),
-            k3: distortion.radial3(),
+            k1,
+            k2,
+            p1,
+            p2,
+            k3,
+            k4,
+            distortion_model,
             fc1p: None,
             fc2p: None,
             cc1p: None,
@@ -915,6 +1038,7 @@ impl<R: RealField + Copy + serde::Serialize> FlydraCamera<R> for Camera<R> {
             resolution: (self.width(), self.height()),
             scale_factor: None,
             non_linear_parameters,
+            time_offset: None,
         })
     }
 
@@ -1008,6 +1132,12 @@ pub fn from_flydra_with_limited_skew<R: RealField + Copy + serde::Serialize>(
         }
     }
 
+    match cam.non_linear_parameters.distortion_model.as_deref() {
+        None => {}
+        Some(EQUIDISTANT_DISTORTION_MODEL) => return from_flydra_fisheye(cam, rquat, p),
+        Some(_) => return Err(MvgError::UnknownDistortionModel.into()),
+    }
+
     // This craziness abuses the rectification matrix of the ROS/OpenCV
     // model to compensate for the issue that the intrinsic parameters used
     // in the MultiCamSelfCal (MCSC) distortion correction are independent
@@ -1047,6 +1177,52 @@ pub fn from_flydra_with_limited_skew<R: RealField + Copy + serde::Serialize>(
     Ok((name, cam2))
 }
 
+/// Convert a fisheye camera from flydra XML.
+///
+/// Unlike MultiCamSelfCal, the fisheye model has no separate intrinsic
+/// parameters for distortion, so these must match the calibration matrix.
+fn from_flydra_fisheye<R: RealField + Copy + serde::Serialize>(
+    cam: &SingleCameraCalibration<R>,
+    rquat: na::UnitQuaternion<R>,
+    p: OMatrix<R, U3, U4>,
+) -> Result<(String, Camera<R>)> {
+    let i = &cam.non_linear_parameters;
+    let zero = R::zero();
+    let k: Matrix3<R> = p.fixed_view::<3, 3>(0, 0).into_owned();
+    let tol: R = na::convert(1e-6);
+    for (expected, actual) in [
+        (k[(0, 0)], i.fc1),
+        (k[(1, 1)], i.fc2),
+        (k[(0, 2)], i.cc1),
+        (k[(1, 2)], i.cc2),
+    ] {
+        if (expected - actual).abs() > tol * expected.abs().max(R::one()) {
+            return Err(FlydraMvgError::FailedFlydraXmlConversion {
+                msg: "fisheye intrinsics do not match calibration matrix",
+            });
+        }
+    }
+    if i.p1 != zero || i.p2 != zero {
+        return Err(FlydraMvgError::FailedFlydraXmlConversion {
+            msg: "fisheye model has no tangential distortion",
+        });
+    }
+    let fisheye = FisheyeDistortion::new(i.k1, i.k2, i.k3, i.k4);
+    let intrinsics =
+        RosOpenCvIntrinsics::from_components(p, k, Distortion::zero(), Matrix3::identity())
+            .map_err(braid_mvg::MvgError::from)?;
+    let camcenter = pmat2cam_center(&cam.calibration_matrix);
+    let extrinsics = ExtrinsicParameters::from_rotation_and_camcenter(rquat, camcenter);
+    let cam2 = Camera::new_fisheye(
+        cam.resolution.0,
+        cam.resolution.1,
+        extrinsics,
+        intrinsics,
+        fisheye,
+    )?;
+    Ok((cam.cam_id.clone(), cam2))
+}
+
 /// helper function (duplicated from braid_mvg)
 #[allow(clippy::many_single_char_names)]
 fn pmat2cam_center<R: RealField + Copy>(p: &OMatrix<R, U3, U4>) -> Point3<R> {
@@ -0,0 +1,324 @@
+//! Planar boundaries between media of different refractive index.
+
+use nalgebra as na;
+use nalgebra::{Matrix3, RealField, Unit, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::flydra_xml_support::FlydraRefractiveInterface;
+use crate::{FlydraMvgError, Result, AIR_REFRACTION};
+
+/// A flat wall of transparent material, such as the glass of an aquarium.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Wall<R> {
+    /// Thickness of the wall
+    pub thickness: R,
+    /// Refractive index of the wall material
+    pub n: R,
+}
+
+/// A planar boundary between two media of different refractive index.
+///
+/// The boundary is the plane of points `x` with `normal · x = offset`. Cameras
+/// are on the side the normal points away from, in the medium with refractive
+/// index `n1`, and look through the boundary at points in the medium with
+/// refractive index `n2`. An optional [Wall] starts at the plane and extends in
+/// the direction of the normal.
+///
+/// The water surface of flydra calibrations, with water at z<0, is
+/// [RefractiveInterface::water_surface].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefractiveInterface<R: RealField + Copy> {
+    normal: Unit<Vector3<R>>,
+    offset: R,
+    n1: R,
+    n2: R,
+    wall: Option<Wall<R>>,
+}
+
+impl<R: RealField + Copy> RefractiveInterface<R> {
+    /// Create a boundary at the plane `normal · x = offset`.
+    ///
+    /// `normal` need not have unit length.
+    pub fn new(normal: Vector3<R>, offset: R, n1: R, n2: R) -> Result<Self> {
+        let norm = normal.norm();
+        if norm <= R::zero() || !norm.is_finite() {
+            return Err(FlydraMvgError::InvalidRefractiveInterface {
+                msg: "normal must not be zero",
+            });
+        }
+        if !(n1 > R::zero() && n2 > R::zero()) {
+            return Err(FlydraMvgError::InvalidRefractiveInterface {
+                msg: "refractive indices must be positive",
+            });
+        }
+        Ok(Self {
+            normal: Unit::new_unchecked(normal / norm),
+            offset: offset / norm,
+            n1,
+            n2,
+            wall: None,
+        })
+    }
+
+    /// The horizontal surface of water with refractive index `n2` at z<0,
+    /// seen from air.
+    pub fn water_surface(n2: R) -> Self {
+        Self {
+            normal: -Vector3::z_axis(),
+            offset: R::zero(),
+            n1: na::convert(AIR_REFRACTION),
+            n2,
+            wall: None,
+        }
+    }
+
+    /// Add a wall of thickness `thickness` and refractive index `n`.
+    pub fn with_wall(mut self, thickness: R, n: R) -> Result<Self> {
+        if !(thickness >= R::zero() && n > R::zero()) {
+            return Err(FlydraMvgError::InvalidRefractiveInterface {
+                msg: "wall thickness must not be negative and refractive index must be positive",
+            });
+        }
+        self.wall = Some(Wall { thickness, n });
+        Ok(self)
+    }
+
+    pub fn normal(&self) -> &Unit<Vector3<R>> {
+        &self.normal
+    }
+
+    pub fn offset(&self) -> R {
+        self.offset
+    }
+
+    pub fn n1(&self) -> R {
+        self.n1
+    }
+
+    pub fn n2(&self) -> R {
+        self.n2
+    }
+
+    pub fn wall(&self) -> Option<&Wall<R>> {
+        self.wall.as_ref()
+    }
+
+    /// Transform the boundary with the similarity transform `x' = s*rot*x + t`
+    /// as done by [braid_mvg::MultiCameraSystem::align].
+    pub fn align(&self, s: R, rot: Matrix3<R>, t: Vector3<R>) -> Self {
+        let normal = Unit::new_normalize(rot * self.normal.into_inner());
+        Self {
+            offset: s * self.offset + normal.dot(&t),
+            normal,
+            n1: self.n1,
+            n2: self.n2,
+            wall: self.wall.map(|wall| Wall {
+                thickness: s * wall.thickness,
+                n: wall.n,
+            }),
+        }
+    }
+
+    /// Signed distance of `pt` from the plane, positive in the direction of
+    /// the normal.
+    fn depth(&self, pt: &Vector3<R>) -> R {
+        self.normal.dot(pt) - self.offset
+    }
+
+    /// Whether `pt` is on the far side of the plane as seen from the cameras.
+    pub(crate) fn is_beyond(&self, pt: &Vector3<R>) -> bool {
+        self.depth(pt) > R::zero()
+    }
+
+    /// Whether light from `pt` to the camera at `camcenter` crosses the
+    /// boundary.
+    pub(crate) fn separates(&self, camcenter: &Vector3<R>, pt: &Vector3<R>) -> bool {
+        self.depth(camcenter) < R::zero() && self.is_beyond(pt)
+    }
+
+    /// Fraction of the straight line from `camcenter` to `pt` at which it
+    /// crosses the plane.
+    pub(crate) fn crossing_fraction(&self, camcenter: &Vector3<R>, pt: &Vector3<R>) -> R {
+        let dc = self.depth(camcenter);
+        dc / (dc - self.depth(pt))
+    }
+
+    /// Point on the plane through which light from `pt` reaches the camera at
+    /// `camcenter`.
+    ///
+    /// The path obeys Snell's law (equivalently, Fermat's principle of least
+    /// time) at each boundary.
+    pub(crate) fn entry_point(
+        &self,
+        camcenter: &Vector3<R>,
+        pt: &Vector3<R>,
+    ) -> Result<Vector3<R>> {
+        let zero = R::zero();
+        let height = -self.depth(camcenter);
+        let depth = self.depth(pt);
+        let delta = pt - camcenter;
+        let along = delta - self.normal.into_inner() * self.normal.dot(&delta);
+        let d = along.norm();
+
+        let mut layers = vec![refraction::Layer {
+            thickness: height,
+            n: self.n1,
+        }];
+        match &self.wall {
+            Some(wall) => {
+                layers.push(refraction::Layer {
+                    thickness: depth.min(wall.thickness),
+                    n: wall.n,
+                });
+                layers.push(refraction::Layer {
+                    thickness: (depth - wall.thickness).max(zero),
+                    n: self.n2,
+                });
+            }
+            None => layers.push(refraction::Layer {
+                thickness: depth,
+                n: self.n2,
+            }),
+        }
+        let eq = refraction::LayeredRefractionEq { d, layers };
+        let p = refraction::find_ray_parameter(&eq, na::convert(1e-12))
+            .ok_or(FlydraMvgError::NoValidRootFound)?;
+        let x1 = eq.distances(p)[0];
+
+        let mut result = camcenter + self.normal.into_inner() * height;
+        if d > zero {
+            result += along * (x1 / d);
+        }
+        Ok(result)
+    }
+
+    /// Refract the ray from `origin` in direction `dir`.
+    ///
+    /// Returns the distance along the ray (in units of `dir`) to the plane and
+    /// the ray after the boundary (and the wall, if any) or `None` if the ray
+    /// does not cross the boundary from the camera side.
+    pub(crate) fn refract_ray(
+        &self,
+        origin: &Vector3<R>,
+        dir: &Vector3<R>,
+    ) -> Option<(R, Vector3<R>, Vector3<R>)> {
+        let zero = R::zero();
+        let normal = self.normal.into_inner();
+        let dc = self.depth(origin);
+        let nd = normal.dot(dir);
+        if !(dc < zero && nd > zero) {
+            return None;
+        }
+        let toi = -dc / nd;
+        let mut surface_pt = origin + dir * toi;
+
+        // The component of `n * dir` along the boundary is preserved.
+        let u = dir.normalize();
+        let p_vec = (u - normal * u.dot(&normal)) * self.n1;
+        let direction_in = |n: R| {
+            let s = p_vec / n;
+            let cos2 = R::one() - s.norm_squared();
+            // Beyond the critical angle, there is total internal reflection.
+            (cos2 > zero).then(|| s + normal * cos2.sqrt())
+        };
+
+        if let Some(wall) = &self.wall {
+            let wall_dir = direction_in(wall.n)?;
+            surface_pt += wall_dir * (wall.thickness / wall_dir.dot(&normal));
+        }
+        let refracted_dir = direction_in(self.n2)?;
+        Some((toi, surface_pt, refracted_dir))
+    }
+
+    pub(crate) fn from_flydra(orig: &FlydraRefractiveInterface<R>) -> Result<Self> {
+        let result = Self::new(orig.normal, orig.offset, orig.n1, orig.n2)?;
+        match (orig.wall_thickness, orig.wall_n) {
+            (None, None) => Ok(result),
+            (Some(thickness), Some(n)) => result.with_wall(thickness, n),
+            _ => Err(FlydraMvgError::FailedFlydraXmlConversion {
+                msg: "wall_thickness and wall_n must be given together",
+            }),
+        }
+    }
+
+    pub(crate) fn to_flydra(&self) -> FlydraRefractiveInterface<R> {
+        FlydraRefractiveInterface {
+            normal: self.normal.into_inner(),
+            offset: self.offset,
+            n1: self.n1,
+            n2: self.n2,
+            wall_thickness: self.wall.map(|w| w.thickness),
+            wall_n: self.wall.map(|w| w.n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tank_wall() -> RefractiveInterface<f64> {
+        // Vertical glass wall at x=0.1 with water at x>0.1.
+        RefractiveInterface::new(Vector3::new(2.0, 0.0, 0.0), 0.2, AIR_REFRACTION, 1.333)
+            .unwrap()
+            .with_wall(0.006, 1.52)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_entry_point_and_refract_ray_agree() {
+        let camcenter = Vector3::new(-0.4, 0.1, 0.2);
+        for iface in [tank_wall(), RefractiveInterface::water_surface(1.333)] {
+            for pt in [
+                Vector3::new(0.3, -0.05, -0.1),
+                Vector3::new(0.15, 0.2, -0.02),
+                Vector3::new(0.5, 0.1, -0.3),
+            ] {
+                assert!(iface.separates(&camcenter, &pt));
+                let entry = iface.entry_point(&camcenter, &pt).unwrap();
+                approx::assert_relative_eq!(iface.depth(&entry), 0.0, epsilon = 1e-12);
+
+                // Tracing the ray through the entry point must reach `pt`.
+                let (_toi, origin, dir) =
+                    iface.refract_ray(&camcenter, &(entry - camcenter)).unwrap();
+                let to_pt = pt - origin;
+                approx::assert_relative_eq!(to_pt.normalize(), dir.normalize(), epsilon = 1e-8);
+            }
+        }
+    }
+
+    #[test]
+    fn test_water_surface() {
+        let iface = RefractiveInterface::water_surface(1.333);
+        let camcenter = Vector3::new(0.0, 0.0, 1.0);
+        assert!(iface.separates(&camcenter, &Vector3::new(0.1, 0.0, -0.1)));
+        assert!(!iface.separates(&camcenter, &Vector3::new(0.1, 0.0, 0.1)));
+        // Seen from above, points under water appear closer to the camera axis.
+        let entry = iface
+            .entry_point(&camcenter, &Vector3::new(1.0, 0.0, -1.0))
+            .unwrap();
+        assert!(entry.x > 0.5 && entry.x < 1.0);
+        approx::assert_relative_eq!(entry.y, 0.0);
+        approx::assert_relative_eq!(entry.z, 0.0);
+        // No refraction along the normal.
+        let entry = iface
+            .entry_point(&camcenter, &Vector3::new(0.0, 0.0, -1.0))
+            .unwrap();
+        approx::assert_relative_eq!(entry, Vector3::zeros());
+    }
+
+    #[test]
+    fn test_align() {
+        let iface = tank_wall();
+        let s = 2.0;
+        let rot = *nalgebra::Rotation3::from_euler_angles(0.1, 0.2, 0.3).matrix();
+        let t = Vector3::new(1.0, 2.0, 3.0);
+        let aligned = iface.align(s, rot, t);
+        let camcenter = Vector3::new(-0.4, 0.1, 0.2);
+        let pt = Vector3::new(0.3, -0.05, -0.1);
+        let xform = |x: Vector3<f64>| rot * x * s + t;
+        let entry = iface.entry_point(&camcenter, &pt).unwrap();
+        let aligned_entry = aligned.entry_point(&xform(camcenter), &xform(pt)).unwrap();
+        approx::assert_relative_eq!(xform(entry), aligned_entry, epsilon = 1e-9);
+    }
+}
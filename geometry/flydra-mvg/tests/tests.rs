@@ -236,3 +236,164 @@ fn test_jacobian() {
         }
     }
 }
+
+#[test]
+fn test_fisheye_flydra_xml() {
+    use braid_mvg::fisheye::FisheyeDistortion;
+    use nalgebra::{Unit, Vector3};
+
+    let extrinsics = cam_geom::ExtrinsicParameters::from_view(
+        &Vector3::new(0.0, 0.0, 0.5),
+        &Vector3::new(0.0, 0.0, 0.0),
+        &Unit::new_normalize(Vector3::new(0.0, 1.0, 0.0)),
+    );
+    let intrinsics =
+        opencv_ros_camera::RosOpenCvIntrinsics::from_params(350.0, 0.0, 351.0, 640.0, 512.0);
+    let distortion = FisheyeDistortion::new(-0.013, 0.021, -0.012, 0.002);
+    let cam =
+        braid_mvg::Camera::new_fisheye(1280, 1024, extrinsics, intrinsics, distortion).unwrap();
+    let cams_orig = FlydraMultiCameraSystem::new(
+        std::iter::once(("fisheye".to_string(), cam)).collect(),
+        None,
+    );
+
+    let mut flydra_xml: Vec<u8> = Vec::new();
+    cams_orig.to_flydra_xml(&mut flydra_xml).unwrap();
+    assert!(std::str::from_utf8(&flydra_xml)
+        .unwrap()
+        .contains("equidistant"));
+    let cams_new = FlydraMultiCameraSystem::<f64>::from_flydra_xml(flydra_xml.as_slice()).unwrap();
+
+    let cam_orig = cams_orig.cam_by_name("fisheye").unwrap();
+    let cam_new = cams_new.cam_by_name("fisheye").unwrap();
+    check_project_3d_roundtrip!(cam_new);
+
+    #[rustfmt::skip]
+    {
+        for pt in &[
+            PointWorldFrame { coords: Point3::new(0.01, 0.02, 0.03) },
+            PointWorldFrame { coords: Point3::new(-0.3, 0.2, 0.1) },
+            // More than 90° from the optical axis.
+            PointWorldFrame { coords: Point3::new(1.0, 0.5, 0.6) },
+        ] {
+            let expected = cam_orig.project_3d_to_distorted_pixel(pt);
+            let actual = cam_new.project_3d_to_distorted_pixel(pt);
+            assert_relative_eq!(actual.coords, expected.coords, max_relative = 1e-10);
+        }
+    };
+}
+
+#[test]
+fn test_tank_wall() {
+    use flydra_mvg::RefractiveInterface;
+    use nalgebra::{Unit, Vector3};
+
+    // Water at z<0 in a tank with a glass wall at x=0, seen from the side and
+    // from above.
+    let mut cams_by_name = std::collections::BTreeMap::new();
+    for (name, camcenter) in [
+        ("side1", Vector3::new(-0.5, 0.1, -0.05)),
+        ("side2", Vector3::new(-0.4, -0.2, -0.15)),
+        ("side3", Vector3::new(-0.6, 0.0, -0.1)),
+        ("top", Vector3::new(0.2, 0.0, 0.5)),
+    ] {
+        let extrinsics = cam_geom::ExtrinsicParameters::from_view(
+            &camcenter,
+            &Vector3::new(0.15, 0.0, -0.1),
+            &Unit::new_normalize(Vector3::new(0.0, 0.0, 1.0)),
+        );
+        let intrinsics =
+            opencv_ros_camera::RosOpenCvIntrinsics::from_params(800.0, 0.0, 800.0, 640.0, 512.0);
+        let cam = braid_mvg::Camera::new(1280, 1024, extrinsics, intrinsics).unwrap();
+        cams_by_name.insert(name.to_string(), cam);
+    }
+    let wall = RefractiveInterface::new(Vector3::x(), 0.0, 1.0003, 1.333)
+        .unwrap()
+        .with_wall(0.006, 1.52)
+        .unwrap();
+    let in_air = FlydraMultiCameraSystem::new(cams_by_name, Some(1.333));
+    let cams = in_air
+        .clone()
+        .with_refractive_interfaces(vec![wall.clone()]);
+    assert!(cams.has_refractive_boundary());
+
+    let pt = PointWorldFrame {
+        coords: Point3::new(0.15, 0.02, -0.1),
+    };
+    let mut points = Vec::new();
+    for cam in cams.cameras() {
+        let projected = cam.project_3d_to_distorted_pixel(&pt);
+        if cam.name().starts_with("side") {
+            // Refraction at the wall moves the image of the point.
+            let without_wall = in_air.cam_by_name(cam.name()).unwrap();
+            let straight = without_wall.project_3d_to_distorted_pixel(&pt);
+            assert!(nalgebra::distance(&projected.coords, &straight.coords) > 1.0);
+        }
+        points.push((cam.name().to_string(), projected));
+    }
+    let pt_actual = cams.find3d_distorted(&points).unwrap().point();
+    assert_relative_eq!(pt.coords, pt_actual.coords, max_relative = 1e-6);
+
+    // The interfaces are saved in the XML file.
+    let mut flydra_xml: Vec<u8> = Vec::new();
+    cams.to_flydra_xml(&mut flydra_xml).unwrap();
+    let cams_new = FlydraMultiCameraSystem::<f64>::from_flydra_xml(flydra_xml.as_slice()).unwrap();
+    assert_eq!(cams_new.water(), Some(1.333));
+    assert_eq!(cams_new.refractive_interfaces(), &[wall]);
+    for (cam_name, expected) in points.iter() {
+        let actual = cams_new
+            .cam_by_name(cam_name)
+            .unwrap()
+            .project_3d_to_distorted_pixel(&pt);
+        assert_relative_eq!(actual.coords, expected.coords, max_relative = 1e-10);
+    }
+
+    // Linearizing just in front of the wall does not step through it.
+    let center = PointWorldFrame {
+        coords: Point3::new(-0.0005, 0.02, -0.1),
+    };
+    for cam in cams.cameras() {
+        let expected = in_air
+            .cam_by_name(cam.name())
+            .unwrap()
+            .linearize_numerically_at(&center, 0.001)
+            .unwrap();
+        let actual = cam.linearize_numerically_at(&center, 0.001).unwrap();
+        assert_relative_eq!(actual, expected, epsilon = 10.0);
+    }
+}
+
+#[test]
+fn test_time_offsets() {
+    let buf = include_str!("flydra/sample_calibration.xml");
+    let cams = FlydraMultiCameraSystem::<f64>::from_flydra_xml(buf.as_bytes()).unwrap();
+    for cam in cams.cameras() {
+        assert_eq!(cam.time_offset(), 0.0);
+    }
+
+    let name = cams.cam_names().next().unwrap().to_string();
+    let time_offsets = std::collections::BTreeMap::from([(name.clone(), 0.0012)]);
+    let cams = cams.with_time_offsets(time_offsets.clone());
+    assert_eq!(cams.cam_by_name(&name).unwrap().time_offset(), 0.0012);
+
+    // The time offsets are saved in the XML file.
+    let mut flydra_xml: Vec<u8> = Vec::new();
+    cams.to_flydra_xml(&mut flydra_xml).unwrap();
+    assert!(String::from_utf8_lossy(&flydra_xml).contains("<time_offset>0.0012</time_offset>"));
+    let cams_new = FlydraMultiCameraSystem::<f64>::from_flydra_xml(flydra_xml.as_slice()).unwrap();
+    assert_eq!(cams_new.time_offsets(), &time_offsets);
+    for cam in cams_new.cameras() {
+        let expected = if cam.name() == name { 0.0012 } else { 0.0 };
+        assert_eq!(cam.time_offset(), expected);
+    }
+
+    // Aligning the calibration keeps the time offsets.
+    let aligned = cams_new
+        .align(
+            2.0,
+            nalgebra::Matrix3::identity(),
+            nalgebra::Vector3::zeros(),
+        )
+        .unwrap();
+    assert_eq!(aligned.time_offsets(), &time_offsets);
+}
@@ -0,0 +1,193 @@
+//! Outlier rejection using the epipolar geometry of camera pairs.
+
+use nalgebra as na;
+
+use crate::{
+    linalg::{hartley_normalization, null_vector, transform, Rng},
+    Measurements,
+};
+
+/// Minimum number of points seen by both cameras of a pair to estimate their
+/// epipolar geometry.
+pub(crate) const MIN_PAIR_POINTS: usize = 16;
+
+/// Probability of drawing at least one outlier-free sample in RANSAC.
+const RANSAC_CONFIDENCE: f64 = 0.999;
+
+/// Estimate the fundamental matrix `F` with `xb^T F xa = 0` using the
+/// normalized eight point algorithm.
+pub(crate) fn fundamental_8pt(
+    xa: &[na::Vector2<f64>],
+    xb: &[na::Vector2<f64>],
+) -> na::Matrix3<f64> {
+    debug_assert_eq!(xa.len(), xb.len());
+    debug_assert!(xa.len() >= 8);
+    let ta = hartley_normalization(xa);
+    let tb = hartley_normalization(xb);
+    let a = na::DMatrix::from_fn(xa.len(), 9, |i, j| {
+        let pa = transform(&ta, &xa[i]);
+        let pb = transform(&tb, &xb[i]);
+        let pa = [pa.x, pa.y, 1.0];
+        let pb = [pb.x, pb.y, 1.0];
+        pb[j / 3] * pa[j % 3]
+    });
+    let f = null_vector(&a);
+    let f = na::Matrix3::from_row_slice(f.as_slice());
+
+    // Enforce rank 2.
+    let mut svd = f.svd(true, true);
+    let (imin, _) = svd.singular_values.argmin();
+    svd.singular_values[imin] = 0.0;
+    let f = svd.recompose().unwrap();
+
+    tb.transpose() * f * ta
+}
+
+/// The squared Sampson distance of a correspondence to the epipolar geometry.
+pub(crate) fn sampson_distance2(
+    f: &na::Matrix3<f64>,
+    xa: &na::Vector2<f64>,
+    xb: &na::Vector2<f64>,
+) -> f64 {
+    let xa = na::Vector3::new(xa.x, xa.y, 1.0);
+    let xb = na::Vector3::new(xb.x, xb.y, 1.0);
+    let fxa = f * xa;
+    let ftxb = f.transpose() * xb;
+    let num = xb.dot(&fxa);
+    let denom = fxa.x * fxa.x + fxa.y * fxa.y + ftxb.x * ftxb.x + ftxb.y * ftxb.y;
+    if denom > 0.0 {
+        num * num / denom
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// Find the correspondences consistent with a single fundamental matrix.
+///
+/// Returns the inlier mask, or `None` if no consensus was found.
+fn ransac_fundamental(
+    xa: &[na::Vector2<f64>],
+    xb: &[na::Vector2<f64>],
+    threshold: f64,
+    max_iterations: usize,
+    rng: &mut Rng,
+) -> Option<Vec<bool>> {
+    let n = xa.len();
+    let threshold2 = threshold * threshold;
+    let inliers_of = |f: &na::Matrix3<f64>| -> Vec<bool> {
+        xa.iter()
+            .zip(xb.iter())
+            .map(|(a, b)| sampson_distance2(f, a, b) < threshold2)
+            .collect()
+    };
+
+    let mut best: Option<(usize, Vec<bool>)> = None;
+    let mut needed = max_iterations;
+    let mut iter = 0;
+    while iter < needed {
+        iter += 1;
+        let sample = rng.sample(n, 8);
+        let sa: Vec<_> = sample.iter().map(|i| xa[*i]).collect();
+        let sb: Vec<_> = sample.iter().map(|i| xb[*i]).collect();
+        let f = fundamental_8pt(&sa, &sb);
+        let inliers = inliers_of(&f);
+        let count = inliers.iter().filter(|x| **x).count();
+        if best.as_ref().map(|(c, _)| count > *c).unwrap_or(true) {
+            // Update the number of iterations needed for the desired
+            // confidence given the current inlier ratio.
+            let w = count as f64 / n as f64;
+            let p_good_sample = w.powi(8);
+            if p_good_sample > 0.0 {
+                let k = (1.0 - RANSAC_CONFIDENCE).ln() / (1.0 - p_good_sample).ln();
+                if k.is_finite() {
+                    needed = needed.min(k.ceil() as usize);
+                }
+            }
+            best = Some((count, inliers));
+        }
+    }
+
+    let (count, inliers) = best?;
+    if count < 8 {
+        return None;
+    }
+
+    // Refit using all inliers.
+    let (sa, sb): (Vec<_>, Vec<_>) = inliers
+        .iter()
+        .enumerate()
+        .filter(|(_, is_inlier)| **is_inlier)
+        .map(|(i, _)| (xa[i], xb[i]))
+        .unzip();
+    let f = fundamental_8pt(&sa, &sb);
+    let refit = inliers_of(&f);
+    if refit.iter().filter(|x| **x).count() >= count {
+        Some(refit)
+    } else {
+        Some(inliers)
+    }
+}
+
+/// Remove observations inconsistent with the epipolar geometry.
+///
+/// The epipolar geometry of each camera pair with enough common points is
+/// estimated with RANSAC. An observation is removed if it was an outlier in the
+/// majority of the pairs it took part in. `thresholds` gives the inlier
+/// threshold for each camera.
+///
+/// Returns the number of observations removed.
+pub(crate) fn reject_outliers(
+    meas: &mut Measurements,
+    thresholds: &[f64],
+    max_iterations: usize,
+) -> usize {
+    let mut rng = Rng::new(0x5eed);
+    let mut votes_ok = vec![0u32; meas.num_cams * meas.num_pts];
+    let mut votes_total = vec![0u32; meas.num_cams * meas.num_pts];
+
+    for a in 0..meas.num_cams {
+        for b in (a + 1)..meas.num_cams {
+            let mut pts = Vec::new();
+            let mut xa = Vec::new();
+            let mut xb = Vec::new();
+            for j in 0..meas.num_pts {
+                if let (Some(pa), Some(pb)) = (meas.get(a, j), meas.get(b, j)) {
+                    pts.push(j);
+                    xa.push(*pa);
+                    xb.push(*pb);
+                }
+            }
+            if pts.len() < MIN_PAIR_POINTS {
+                continue;
+            }
+            // The Sampson distance is approximately the distance in one image,
+            // so use the more tolerant threshold of the pair.
+            let threshold = thresholds[a].max(thresholds[b]);
+            let Some(inliers) = ransac_fundamental(&xa, &xb, threshold, max_iterations, &mut rng)
+            else {
+                continue;
+            };
+            for (j, is_inlier) in pts.iter().zip(inliers.iter()) {
+                for cam in [a, b] {
+                    let idx = cam * meas.num_pts + j;
+                    votes_total[idx] += 1;
+                    if *is_inlier {
+                        votes_ok[idx] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut n_removed = 0;
+    for cam in 0..meas.num_cams {
+        for j in 0..meas.num_pts {
+            let idx = cam * meas.num_pts + j;
+            if meas.get(cam, j).is_some() && 2 * votes_ok[idx] < votes_total[idx] {
+                meas.remove(cam, j);
+                n_removed += 1;
+            }
+        }
+    }
+    n_removed
+}
@@ -0,0 +1,216 @@
+//! Upgrade of a projective reconstruction to a Euclidean one.
+
+use nalgebra as na;
+
+use crate::{linalg::null_vector, projective::ProjectiveReconstruction, Measurements};
+
+/// Cameras and points known up to a similarity transformation.
+#[derive(Debug, Clone)]
+pub(crate) struct EuclideanReconstruction {
+    /// Camera matrices, `K [R | t]`, with `det(R) = 1`.
+    pub(crate) pmats: Vec<na::Matrix3x4<f64>>,
+    pub(crate) points: Vec<Option<na::Point3<f64>>>,
+}
+
+/// Index into the 10 distinct elements of a symmetric 4x4 matrix.
+fn sym_idx(k: usize, l: usize) -> usize {
+    let (k, l) = if k <= l { (k, l) } else { (l, k) };
+    // Row-major upper triangle.
+    k * 4 - k * (k + 1) / 2 + l
+}
+
+/// Coefficients of the element `(a, b)` of `P Q P^T` in terms of the distinct
+/// elements of the symmetric matrix `Q`.
+fn omega_coefs(p: &na::Matrix3x4<f64>, a: usize, b: usize) -> [f64; 10] {
+    let mut coefs = [0.0; 10];
+    for k in 0..4 {
+        for l in 0..4 {
+            coefs[sym_idx(k, l)] += p[(a, k)] * p[(b, l)];
+        }
+    }
+    coefs
+}
+
+/// Estimate the rectifying homography `H` with the absolute dual quadric.
+///
+/// In the normalized image coordinates used here, the calibration matrix of
+/// every camera is close to `diag(f, f, 1)`: zero skew, unit aspect ratio and
+/// principal point at the origin. The image of the absolute dual quadric, `P Q
+/// P^T`, is then proportional to `diag(f^2, f^2, 1)`, giving linear
+/// constraints on `Q`. If `known_focal` is set, `f = 1` is also enforced,
+/// otherwise it is only weakly favored.
+fn rectifying_homography(
+    pmats: &[na::Matrix3x4<f64>],
+    known_focal: bool,
+) -> Option<na::Matrix4<f64>> {
+    // Weights follow Pollefeys et al. (2002), "Visual modeling with a
+    // hand-held camera".
+    let w_focal = if known_focal { 1.0 } else { 1.0 / 9.0 };
+    let w_aspect = if known_focal { 1.0 } else { 1.0 / 0.2 };
+    let w_pp = if known_focal { 1.0 } else { 1.0 / 0.1 };
+    let w_skew = if known_focal { 1.0 } else { 1.0 / 0.01 };
+
+    let mut rows: Vec<[f64; 10]> = Vec::with_capacity(pmats.len() * 5);
+    for p in pmats.iter() {
+        let p = p / p.norm();
+        let w11 = omega_coefs(&p, 0, 0);
+        let w22 = omega_coefs(&p, 1, 1);
+        let w33 = omega_coefs(&p, 2, 2);
+        let w12 = omega_coefs(&p, 0, 1);
+        let w13 = omega_coefs(&p, 0, 2);
+        let w23 = omega_coefs(&p, 1, 2);
+        let mut r_focal = [0.0; 10];
+        let mut r_aspect = [0.0; 10];
+        let mut r_skew = [0.0; 10];
+        let mut r_pp_x = [0.0; 10];
+        let mut r_pp_y = [0.0; 10];
+        for i in 0..10 {
+            r_focal[i] = w_focal * (w11[i] - w33[i]);
+            r_aspect[i] = w_aspect * (w11[i] - w22[i]);
+            r_skew[i] = w_skew * w12[i];
+            r_pp_x[i] = w_pp * w13[i];
+            r_pp_y[i] = w_pp * w23[i];
+        }
+        rows.extend([r_focal, r_aspect, r_skew, r_pp_x, r_pp_y]);
+    }
+    let a = na::DMatrix::from_fn(rows.len(), 10, |i, j| rows[i][j]);
+    let q = null_vector(&a);
+    let q = na::Matrix4::from_fn(|k, l| q[sym_idx(k, l)]);
+
+    // `Q` is positive semi-definite of rank 3 up to scale, which may be
+    // negative. Enforce this.
+    let eig = na::SymmetricEigen::new(q);
+    let mut order: Vec<usize> = (0..4).collect();
+    order.sort_by(|i, j| {
+        eig.eigenvalues[*j]
+            .abs()
+            .partial_cmp(&eig.eigenvalues[*i].abs())
+            .unwrap()
+    });
+    let sign = order[..3]
+        .iter()
+        .map(|i| eig.eigenvalues[*i])
+        .sum::<f64>()
+        .signum();
+    let largest = eig.eigenvalues[order[0]].abs();
+    let mut h = na::Matrix4::zeros();
+    for (col, i) in order.iter().enumerate() {
+        let scale = if col < 3 {
+            // Clamp to keep `H` invertible if noise made an eigenvalue
+            // negative.
+            (sign * eig.eigenvalues[*i]).max(1e-6 * largest).sqrt()
+        } else {
+            1.0
+        };
+        h.set_column(col, &(eig.eigenvectors.column(*i) * scale));
+    }
+    if !h.iter().all(|x| x.is_finite()) {
+        return None;
+    }
+    Some(h)
+}
+
+/// Fraction of observations with the point in front of the camera.
+fn fraction_in_front(
+    meas: &Measurements,
+    pmats: &[na::Matrix3x4<f64>],
+    points: &[Option<na::Vector4<f64>>],
+) -> f64 {
+    let mut n_front = 0;
+    let mut n_total = 0;
+    for (cam, pmat) in pmats.iter().enumerate() {
+        let det_sign = pmat.fixed_view::<3, 3>(0, 0).determinant().signum();
+        for (j, pt) in points.iter().enumerate() {
+            let Some(pt) = pt else {
+                continue;
+            };
+            if meas.get(cam, j).is_none() {
+                continue;
+            }
+            let w = (pmat.row(2) * pt)[0];
+            n_total += 1;
+            if det_sign * w * pt.w > 0.0 {
+                n_front += 1;
+            }
+        }
+    }
+    if n_total == 0 {
+        return 0.0;
+    }
+    n_front as f64 / n_total as f64
+}
+
+/// Upgrade a projective reconstruction to a Euclidean one.
+///
+/// The result is centered on the points and scaled so that their root mean
+/// square distance from the centroid is one.
+pub(crate) fn upgrade(
+    meas: &Measurements,
+    proj: &ProjectiveReconstruction,
+    known_focal: bool,
+) -> Option<EuclideanReconstruction> {
+    let mut h = rectifying_homography(&proj.pmats, known_focal)?;
+    let mut h_inv = h.try_inverse()?;
+
+    let mut pmats: Vec<_> = proj.pmats.iter().map(|p| p * h).collect();
+    let mut points: Vec<_> = proj
+        .points
+        .iter()
+        .map(|pt| pt.map(|pt| h_inv * pt))
+        .collect();
+
+    // The rectifying homography is defined up to a point reflection. Choose
+    // the one placing the points in front of the cameras.
+    if fraction_in_front(meas, &pmats, &points) < 0.5 {
+        h.set_column(3, &-h.column(3));
+        h_inv = h.try_inverse()?;
+        pmats = proj.pmats.iter().map(|p| p * h).collect();
+        points = proj
+            .points
+            .iter()
+            .map(|pt| pt.map(|pt| h_inv * pt))
+            .collect();
+    }
+
+    // Normalize the similarity transformation.
+    let euclidean: Vec<Option<na::Point3<f64>>> = points
+        .iter()
+        .map(|pt| pt.and_then(na::Point3::from_homogeneous))
+        .collect();
+    let valid: Vec<_> = euclidean.iter().flatten().collect();
+    if valid.is_empty() {
+        return None;
+    }
+    let centroid = valid
+        .iter()
+        .fold(na::Vector3::zeros(), |acc, pt| acc + pt.coords)
+        / valid.len() as f64;
+    let rms = (valid
+        .iter()
+        .map(|pt| (pt.coords - centroid).norm_squared())
+        .sum::<f64>()
+        / valid.len() as f64)
+        .sqrt();
+    let s = 1.0 / rms;
+    let mut t_inv = na::Matrix4::identity();
+    t_inv.fixed_view_mut::<3, 3>(0, 0).fill_diagonal(rms);
+    t_inv.fixed_view_mut::<3, 1>(0, 3).copy_from(&centroid);
+
+    let pmats = pmats
+        .iter()
+        .map(|p| {
+            let p = p * t_inv;
+            // Make `det(R)` positive.
+            if p.fixed_view::<3, 3>(0, 0).determinant() < 0.0 {
+                -p
+            } else {
+                p
+            }
+        })
+        .collect();
+    let points = euclidean
+        .iter()
+        .map(|pt| pt.map(|pt| na::Point3::from((pt.coords - centroid) * s)))
+        .collect();
+    Some(EuclideanReconstruction { pmats, points })
+}
@@ -0,0 +1,516 @@
+//! Multi-camera self-calibration from a single point seen by many cameras.
+//!
+//! This is a native replacement for the linear stages of the [MultiCamSelfCal
+//! (MCSC)](https://github.com/strawlab/MultiCamSelfCal) toolbox. Given the 2D
+//! observations of a moving point (typically an LED wand) by several cameras,
+//! [self_calibrate] estimates the cameras and the 3D points up to a similarity
+//! transformation:
+//!
+//! 1. Outliers are rejected using the epipolar geometry of each camera pair.
+//! 2. A projective reconstruction is made and refined by iterative
+//!    factorization, which allows for points missing in some cameras.
+//! 3. The projective reconstruction is upgraded to a Euclidean one using the
+//!    absolute dual quadric.
+//!
+//! The result is a good starting point for bundle adjustment, e.g. with the
+//! `bundle-adj` crate.
+//!
+//! If the intrinsic parameters of the cameras are known (e.g. from a
+//! checkerboard calibration), they are used and only the extrinsic parameters
+//! are estimated. Otherwise, zero skew, square pixels and a principal point
+//! near the image center are assumed. Without known intrinsic parameters,
+//! configurations in which the optical axes of all cameras meet in a single
+//! point are degenerate and the focal lengths cannot be recovered reliably.
+
+use nalgebra as na;
+use opencv_ros_camera::RosOpenCvIntrinsics;
+
+mod epipolar;
+mod euclidean;
+mod linalg;
+mod projective;
+
+use projective::{InitError, ProjectiveReconstruction};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("inconsistent data: {0}")]
+    InconsistentData(&'static str),
+    #[error("at least 3 cameras are required")]
+    TooFewCameras,
+    #[error("no pair of cameras has enough points in common")]
+    NoInitialPair,
+    #[error("camera \"{0}\" has too few points in common with the other cameras")]
+    CameraNotConnected(String),
+    #[error("upgrade to a Euclidean reconstruction failed")]
+    UpgradeFailed,
+    #[error(transparent)]
+    Mvg(#[from] braid_mvg::MvgError),
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Options for [self_calibrate].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Maximum reprojection error, in pixels, of an inlier observation.
+    pub inlier_threshold: f64,
+    /// Points observed by fewer cameras (after outlier rejection) are not used.
+    pub min_cameras_per_point: usize,
+    /// Maximum number of RANSAC iterations per camera pair.
+    pub ransac_iterations: usize,
+    /// Maximum number of iterations of the projective factorization.
+    pub factorization_iterations: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            inlier_threshold: 3.0,
+            min_cameras_per_point: 3,
+            ransac_iterations: 1000,
+            factorization_iterations: 100,
+        }
+    }
+}
+
+/// A camera to be calibrated.
+#[derive(Debug, Clone)]
+pub struct CameraSpec {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// Known intrinsic parameters, for example from a checkerboard
+    /// calibration.
+    ///
+    /// Either all cameras or none must have known intrinsic parameters.
+    pub intrinsics: Option<RosOpenCvIntrinsics<f64>>,
+}
+
+/// The result of [self_calibrate].
+#[derive(Debug, Clone)]
+pub struct SelfCalibration {
+    /// The calibrated cameras, in the order given.
+    pub cameras: Vec<braid_mvg::Camera<f64>>,
+    /// The 3D location of each point, if it was reconstructed.
+    pub points: Vec<Option<na::Point3<f64>>>,
+    /// For each point, whether the observation by each camera was used.
+    ///
+    /// Observations rejected as outliers and observations of points which were
+    /// not reconstructed are `false`.
+    pub inliers: Vec<Vec<bool>>,
+}
+
+impl SelfCalibration {
+    /// The root mean square reprojection error, in pixels, of the inliers.
+    pub fn rms_reprojection_error(&self, observations: &[Vec<Option<(f64, f64)>>]) -> f64 {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for ((obs, inliers), pt) in observations
+            .iter()
+            .zip(self.inliers.iter())
+            .zip(self.points.iter())
+        {
+            let Some(pt) = pt else {
+                continue;
+            };
+            let pt = braid_mvg::PointWorldFrame { coords: *pt };
+            for ((x, is_inlier), cam) in obs.iter().zip(inliers.iter()).zip(self.cameras.iter()) {
+                let (Some((u, v)), true) = (x, is_inlier) else {
+                    continue;
+                };
+                let predicted = cam.project_3d_to_distorted_pixel(&pt).coords;
+                sum += (predicted.x - u).powi(2) + (predicted.y - v).powi(2);
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return 0.0;
+        }
+        (sum / count as f64).sqrt()
+    }
+}
+
+/// Image points of all cameras in normalized coordinates.
+///
+/// The normalized coordinates of each camera are chosen so that its focal
+/// length is about one and its principal point is near the origin.
+pub(crate) struct Measurements {
+    pub(crate) num_cams: usize,
+    pub(crate) num_pts: usize,
+    /// The image points, camera by camera.
+    data: Vec<Option<na::Vector2<f64>>>,
+}
+
+impl Measurements {
+    fn new(num_cams: usize, num_pts: usize) -> Self {
+        Self {
+            num_cams,
+            num_pts,
+            data: vec![None; num_cams * num_pts],
+        }
+    }
+
+    pub(crate) fn get(&self, cam: usize, pt: usize) -> Option<&na::Vector2<f64>> {
+        self.data[cam * self.num_pts + pt].as_ref()
+    }
+
+    fn set(&mut self, cam: usize, pt: usize, x: na::Vector2<f64>) {
+        self.data[cam * self.num_pts + pt] = Some(x);
+    }
+
+    pub(crate) fn remove(&mut self, cam: usize, pt: usize) {
+        self.data[cam * self.num_pts + pt] = None;
+    }
+
+    /// Remove all observations of points seen by fewer than `min_views`
+    /// cameras.
+    fn remove_sparse_points(&mut self, min_views: usize) {
+        for pt in 0..self.num_pts {
+            let views = (0..self.num_cams)
+                .filter(|cam| self.get(*cam, pt).is_some())
+                .count();
+            if views < min_views {
+                for cam in 0..self.num_cams {
+                    self.remove(cam, pt);
+                }
+            }
+        }
+    }
+}
+
+/// Calibrate cameras from the observations of a single moving point.
+///
+/// `observations` contains, for each point, the distorted pixel coordinates of
+/// the point in each camera, in the order of `cameras`, or `None` if the camera
+/// did not see the point.
+pub fn self_calibrate(
+    cameras: &[CameraSpec],
+    observations: &[Vec<Option<(f64, f64)>>],
+    cfg: &Config,
+) -> Result<SelfCalibration> {
+    if cameras.len() < 3 {
+        return Err(Error::TooFewCameras);
+    }
+    if observations.iter().any(|obs| obs.len() != cameras.len()) {
+        return Err(Error::InconsistentData("observations shape"));
+    }
+    let known_intrinsics = cameras.iter().all(|cam| cam.intrinsics.is_some());
+    if !known_intrinsics && cameras.iter().any(|cam| cam.intrinsics.is_some()) {
+        return Err(Error::InconsistentData(
+            "intrinsics must be given for all cameras or none",
+        ));
+    }
+
+    // Transform the image points to normalized coordinates.
+    let mut normalizations = Vec::with_capacity(cameras.len());
+    let mut thresholds = Vec::with_capacity(cameras.len());
+    for cam in cameras.iter() {
+        let t = match &cam.intrinsics {
+            Some(i) => {
+                let k =
+                    na::Matrix3::new(i.fx(), i.skew(), i.cx(), 0.0, i.fy(), i.cy(), 0.0, 0.0, 1.0);
+                k.try_inverse()
+                    .ok_or(Error::InconsistentData("singular intrinsics"))?
+            }
+            None => {
+                let s = 2.0 / (cam.width + cam.height) as f64;
+                let (cx, cy) = (cam.width as f64 / 2.0, cam.height as f64 / 2.0);
+                na::Matrix3::new(s, 0.0, -s * cx, 0.0, s, -s * cy, 0.0, 0.0, 1.0)
+            }
+        };
+        thresholds.push(cfg.inlier_threshold * (t[(0, 0)] + t[(1, 1)]) / 2.0);
+        normalizations.push(t);
+    }
+
+    let mut meas = Measurements::new(cameras.len(), observations.len());
+    for (j, obs) in observations.iter().enumerate() {
+        for (cam_idx, (x, cam)) in obs.iter().zip(cameras.iter()).enumerate() {
+            let Some((u, v)) = x else {
+                continue;
+            };
+            let undistorted = match &cam.intrinsics {
+                Some(i) => {
+                    let distorted = cam_geom::Pixels::new(na::Vector2::new(*u, *v).transpose());
+                    let undistorted = i.undistort(&distorted);
+                    na::Vector2::new(undistorted.data[(0, 0)], undistorted.data[(0, 1)])
+                }
+                None => na::Vector2::new(*u, *v),
+            };
+            meas.set(
+                cam_idx,
+                j,
+                linalg::transform(&normalizations[cam_idx], &undistorted),
+            );
+        }
+    }
+    meas.remove_sparse_points(cfg.min_cameras_per_point);
+
+    let n_removed = epipolar::reject_outliers(&mut meas, &thresholds, cfg.ransac_iterations);
+    tracing::info!("Rejected {n_removed} observations inconsistent with epipolar geometry.");
+    meas.remove_sparse_points(cfg.min_cameras_per_point);
+
+    let mut proj = ProjectiveReconstruction::initialize(&meas).map_err(|e| match e {
+        InitError::NoInitialPair => Error::NoInitialPair,
+        InitError::CameraNotConnected(i) => Error::CameraNotConnected(cameras[i].name.clone()),
+    })?;
+    proj.factorize(&meas, cfg.factorization_iterations);
+
+    // Reject the observations not fitting the projective reconstruction and
+    // refine again.
+    let mut n_removed = 0;
+    for (cam, threshold) in thresholds.iter().enumerate() {
+        for j in 0..meas.num_pts {
+            if let Some(err) = proj.reprojection_error(&meas, cam, j) {
+                if err > *threshold {
+                    meas.remove(cam, j);
+                    n_removed += 1;
+                }
+            }
+        }
+    }
+    tracing::info!(
+        "Rejected {n_removed} observations inconsistent with projective reconstruction."
+    );
+    meas.remove_sparse_points(cfg.min_cameras_per_point);
+    proj.retriangulate(&meas);
+    proj.factorize(&meas, cfg.factorization_iterations);
+
+    let euclidean =
+        euclidean::upgrade(&meas, &proj, known_intrinsics).ok_or(Error::UpgradeFailed)?;
+
+    let mut calibrated = Vec::with_capacity(cameras.len());
+    for ((spec, t), pmat) in cameras
+        .iter()
+        .zip(normalizations.iter())
+        .zip(euclidean.pmats.iter())
+    {
+        // Undo the normalization of the image coordinates.
+        let pmat = t.try_inverse().unwrap() * pmat;
+        let m = pmat.fixed_view::<3, 3>(0, 0).into_owned();
+        let (rquat, k) = braid_mvg::rq_decomposition(m)?;
+        let camcenter =
+            na::Point3::from(-m.try_inverse().ok_or(Error::UpgradeFailed)? * pmat.column(3));
+        let intrinsics = match &spec.intrinsics {
+            Some(i) => i.clone(),
+            None => {
+                let k = k / k[(2, 2)];
+                let f = (k[(0, 0)] + k[(1, 1)]) / 2.0;
+                RosOpenCvIntrinsics::from_params(f, 0.0, f, k[(0, 2)], k[(1, 2)])
+            }
+        };
+        let extrinsics =
+            cam_geom::ExtrinsicParameters::from_rotation_and_camcenter(rquat, camcenter);
+        calibrated.push(braid_mvg::Camera::new(
+            spec.width,
+            spec.height,
+            extrinsics,
+            intrinsics,
+        )?);
+    }
+
+    let inliers = (0..meas.num_pts)
+        .map(|j| {
+            (0..meas.num_cams)
+                .map(|cam| meas.get(cam, j).is_some() && euclidean.points[j].is_some())
+                .collect()
+        })
+        .collect();
+
+    Ok(SelfCalibration {
+        cameras: calibrated,
+        points: euclidean.points,
+        inliers,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use braid_mvg::test_util::camera_looking_at;
+    use rand::{Rng, SeedableRng};
+    use rand_distr::{Distribution, Normal};
+
+    const WIDTH: usize = 1280;
+    const HEIGHT: usize = 1024;
+
+    /// Cameras on a ring around the origin looking at the tracking volume.
+    ///
+    /// If `look_at_center` is set, all optical axes meet in a single point. The
+    /// cameras have radial distortion `k1`.
+    fn make_cameras(look_at_center: bool, k1: f64) -> Vec<braid_mvg::Camera<f64>> {
+        let n = 6;
+        (0..n)
+            .map(|i| {
+                let fi = i as f64;
+                let angle = fi / n as f64 * 2.0 * std::f64::consts::PI;
+                let camcenter = na::Vector3::new(
+                    3.0 * angle.cos(),
+                    3.0 * angle.sin(),
+                    1.0 + 0.5 * (i % 2) as f64,
+                );
+                let lookat = if look_at_center {
+                    na::Vector3::new(0.0, 0.0, 0.2)
+                } else {
+                    na::Vector3::new(
+                        0.3 * (fi * 1.7).sin(),
+                        0.3 * (fi * 2.3).cos(),
+                        0.2 + 0.2 * (fi * 0.9).sin(),
+                    )
+                };
+                let f = 900.0 + 50.0 * fi;
+                let distortion = opencv_ros_camera::Distortion::from_opencv_vec(na::Vector5::new(
+                    k1, 0.0, 0.0, 0.0, 0.0,
+                ));
+                let intrinsics = RosOpenCvIntrinsics::from_params_with_distortion(
+                    f,
+                    0.0,
+                    f,
+                    WIDTH as f64 / 2.0 + 10.0,
+                    HEIGHT as f64 / 2.0 - 8.0,
+                    distortion,
+                );
+                camera_looking_at(WIDTH, HEIGHT, camcenter, lookat, intrinsics)
+            })
+            .collect()
+    }
+
+    /// Observations of random points with noise, missing data and outliers.
+    ///
+    /// Also returns which observations are outliers.
+    fn observe(
+        cams: &[braid_mvg::Camera<f64>],
+        num_points: usize,
+    ) -> (Vec<Vec<Option<(f64, f64)>>>, Vec<Vec<bool>>) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1234);
+        let noise = Normal::new(0.0, 0.3).unwrap();
+        let mut observations = Vec::new();
+        let mut outliers = Vec::new();
+        for _ in 0..num_points {
+            let pt = braid_mvg::PointWorldFrame {
+                coords: na::Point3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(0.0..1.0),
+                ),
+            };
+            let mut obs = Vec::new();
+            let mut is_outlier = Vec::new();
+            for cam in cams.iter() {
+                let x = cam.project_3d_to_distorted_pixel(&pt).coords;
+                let (x, outlier) = if rng.gen_bool(0.03) {
+                    let x = (
+                        rng.gen_range(0.0..WIDTH as f64),
+                        rng.gen_range(0.0..HEIGHT as f64),
+                    );
+                    (x, true)
+                } else {
+                    let x = (x.x + noise.sample(&mut rng), x.y + noise.sample(&mut rng));
+                    (x, false)
+                };
+                let visible = x.0 >= 0.0
+                    && x.1 >= 0.0
+                    && x.0 < WIDTH as f64
+                    && x.1 < HEIGHT as f64
+                    && rng.gen_bool(0.75);
+                obs.push(visible.then_some(x));
+                is_outlier.push(visible && outlier);
+            }
+            observations.push(obs);
+            outliers.push(is_outlier);
+        }
+        (observations, outliers)
+    }
+
+    /// Check that the camera centers match up to a similarity transformation.
+    fn check_camera_centers(
+        expected: &[braid_mvg::Camera<f64>],
+        actual: &[braid_mvg::Camera<f64>],
+        epsilon: f64,
+    ) {
+        let dist = |cams: &[braid_mvg::Camera<f64>], i: usize, j: usize| {
+            (cams[i].extrinsics().camcenter() - cams[j].extrinsics().camcenter()).norm()
+        };
+        let expected_scale = dist(expected, 0, 1);
+        let actual_scale = dist(actual, 0, 1);
+        for i in 0..expected.len() {
+            for j in (i + 1)..expected.len() {
+                approx::assert_relative_eq!(
+                    dist(expected, i, j) / expected_scale,
+                    dist(actual, i, j) / actual_scale,
+                    max_relative = epsilon
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_intrinsics() {
+        // Cameras pointing at a single point are fine with known intrinsics.
+        let cams = make_cameras(true, -0.1);
+        let (observations, outliers) = observe(&cams, 600);
+        let specs: Vec<_> = cams
+            .iter()
+            .enumerate()
+            .map(|(i, cam)| CameraSpec {
+                name: format!("cam{i}"),
+                width: WIDTH,
+                height: HEIGHT,
+                intrinsics: Some(cam.intrinsics().clone()),
+            })
+            .collect();
+
+        let cal = self_calibrate(&specs, &observations, &Config::default()).unwrap();
+
+        // All outliers are rejected.
+        for (is_outlier, is_inlier) in outliers.iter().flatten().zip(cal.inliers.iter().flatten()) {
+            assert!(!(*is_outlier && *is_inlier));
+        }
+        let rms = cal.rms_reprojection_error(&observations);
+        assert!(rms < 1.0, "rms reprojection error: {rms}");
+        check_camera_centers(&cams, &cal.cameras, 0.01);
+    }
+
+    #[test]
+    fn test_unknown_intrinsics() {
+        let cams = make_cameras(false, 0.0);
+        let (observations, _outliers) = observe(&cams, 600);
+        let specs: Vec<_> = (0..cams.len())
+            .map(|i| CameraSpec {
+                name: format!("cam{i}"),
+                width: WIDTH,
+                height: HEIGHT,
+                intrinsics: None,
+            })
+            .collect();
+
+        let cal = self_calibrate(&specs, &observations, &Config::default()).unwrap();
+
+        // This is only a starting point for bundle adjustment.
+        for (expected, actual) in cams.iter().zip(cal.cameras.iter()) {
+            approx::assert_relative_eq!(
+                expected.intrinsics().fx(),
+                actual.intrinsics().fx(),
+                max_relative = 0.15
+            );
+        }
+        check_camera_centers(&cams, &cal.cameras, 0.15);
+    }
+
+    #[test]
+    fn test_too_few_cameras() {
+        let specs: Vec<_> = (0..2)
+            .map(|i| CameraSpec {
+                name: format!("cam{i}"),
+                width: WIDTH,
+                height: HEIGHT,
+                intrinsics: None,
+            })
+            .collect();
+        let observations = vec![vec![Some((1.0, 2.0)), None]];
+        assert!(matches!(
+            self_calibrate(&specs, &observations, &Config::default()),
+            Err(Error::TooFewCameras)
+        ));
+    }
+}
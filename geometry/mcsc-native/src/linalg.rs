@@ -0,0 +1,133 @@
+//! Small linear algebra helpers.
+
+use nalgebra as na;
+
+/// Find the unit vector `x` minimizing `|A x|`.
+pub(crate) fn null_vector(a: &na::DMatrix<f64>) -> na::DVector<f64> {
+    // Pad with zero rows so that the SVD returns the full right singular
+    // vectors.
+    let a = if a.nrows() < a.ncols() {
+        let mut padded = na::DMatrix::zeros(a.ncols(), a.ncols());
+        padded.view_mut((0, 0), a.shape()).copy_from(a);
+        padded
+    } else {
+        a.clone()
+    };
+    let svd = a.svd(false, true);
+    let v_t = svd.v_t.unwrap();
+    let (imin, _) = svd.singular_values.argmin();
+    v_t.row(imin).transpose()
+}
+
+/// Triangulate a 3D point by the direct linear transformation.
+///
+/// `views` contains pairs of camera matrix and observed image point.
+pub(crate) fn triangulate<'a, I>(views: I) -> Option<na::Vector4<f64>>
+where
+    I: IntoIterator<Item = (&'a na::Matrix3x4<f64>, &'a na::Vector2<f64>)>,
+{
+    let mut rows = Vec::new();
+    for (pmat, x) in views {
+        rows.push(pmat.row(2) * x.x - pmat.row(0));
+        rows.push(pmat.row(2) * x.y - pmat.row(1));
+    }
+    if rows.len() < 4 {
+        return None;
+    }
+    let a = na::DMatrix::from_fn(rows.len(), 4, |i, j| rows[i][j]);
+    let x = null_vector(&a);
+    Some(na::Vector4::new(x[0], x[1], x[2], x[3]))
+}
+
+/// Estimate a camera matrix from 3D points and their image points by the
+/// direct linear transformation.
+pub(crate) fn resect<'a, I>(views: I) -> Option<na::Matrix3x4<f64>>
+where
+    I: IntoIterator<Item = (&'a na::Vector4<f64>, &'a na::Vector2<f64>)>,
+{
+    let mut rows: Vec<[f64; 12]> = Vec::new();
+    for (pt, x) in views {
+        let pt = pt.normalize();
+        let mut r0 = [0.0; 12];
+        let mut r1 = [0.0; 12];
+        for k in 0..4 {
+            r0[k] = pt[k];
+            r0[8 + k] = -x.x * pt[k];
+            r1[4 + k] = pt[k];
+            r1[8 + k] = -x.y * pt[k];
+        }
+        rows.push(r0);
+        rows.push(r1);
+    }
+    if rows.len() < 12 {
+        return None;
+    }
+    let a = na::DMatrix::from_fn(rows.len(), 12, |i, j| rows[i][j]);
+    let p = null_vector(&a);
+    Some(na::Matrix3x4::from_row_slice(p.as_slice()))
+}
+
+/// Project a homogeneous 3D point, returning `None` if the point projects to
+/// infinity.
+pub(crate) fn project(
+    pmat: &na::Matrix3x4<f64>,
+    pt: &na::Vector4<f64>,
+) -> Option<na::Vector2<f64>> {
+    let x = pmat * pt;
+    if x.z.abs() < f64::EPSILON * x.norm() {
+        return None;
+    }
+    Some(na::Vector2::new(x.x / x.z, x.y / x.z))
+}
+
+/// Similarity transform moving the centroid of `pts` to the origin and scaling
+/// the mean distance from it to `sqrt(2)`.
+pub(crate) fn hartley_normalization(pts: &[na::Vector2<f64>]) -> na::Matrix3<f64> {
+    let n = pts.len() as f64;
+    let c = pts.iter().fold(na::Vector2::zeros(), |acc, p| acc + p) / n;
+    let mean_dist = pts.iter().map(|p| (p - c).norm()).sum::<f64>() / n;
+    let s = if mean_dist > 0.0 {
+        std::f64::consts::SQRT_2 / mean_dist
+    } else {
+        1.0
+    };
+    na::Matrix3::new(s, 0.0, -s * c.x, 0.0, s, -s * c.y, 0.0, 0.0, 1.0)
+}
+
+/// Apply a 2D homography to an inhomogeneous point.
+pub(crate) fn transform(t: &na::Matrix3<f64>, x: &na::Vector2<f64>) -> na::Vector2<f64> {
+    let y = t * na::Vector3::new(x.x, x.y, 1.0);
+    na::Vector2::new(y.x / y.z, y.y / y.z)
+}
+
+/// A small, deterministic pseudo-random number generator (SplitMix64).
+///
+/// Calibration results should be reproducible, so a fixed seed is used.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Choose `k` distinct indices in `0..n`.
+    pub(crate) fn sample(&mut self, n: usize, k: usize) -> Vec<usize> {
+        debug_assert!(k <= n);
+        let mut chosen = Vec::with_capacity(k);
+        while chosen.len() < k {
+            let i = (self.next_u64() % n as u64) as usize;
+            if !chosen.contains(&i) {
+                chosen.push(i);
+            }
+        }
+        chosen
+    }
+}
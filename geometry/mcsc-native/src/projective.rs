@@ -0,0 +1,257 @@
+//! Projective reconstruction from image points with missing data.
+
+use nalgebra as na;
+
+use crate::{
+    epipolar::fundamental_8pt,
+    linalg::{project, resect, triangulate},
+    Measurements,
+};
+
+/// Minimum number of reconstructed points a camera must see to be added to
+/// the reconstruction.
+const MIN_RESECTION_POINTS: usize = 8;
+
+/// Cameras and points known up to a projective transformation.
+#[derive(Debug, Clone)]
+pub(crate) struct ProjectiveReconstruction {
+    pub(crate) pmats: Vec<na::Matrix3x4<f64>>,
+    /// The homogeneous 3D points. `None` if the point could not be
+    /// reconstructed.
+    pub(crate) points: Vec<Option<na::Vector4<f64>>>,
+}
+
+/// Why an initial projective reconstruction could not be made.
+#[derive(Debug)]
+pub(crate) enum InitError {
+    /// No camera pair shares enough points.
+    NoInitialPair,
+    /// The camera with this index shares too few points with the others.
+    CameraNotConnected(usize),
+}
+
+impl ProjectiveReconstruction {
+    /// Build an initial reconstruction.
+    ///
+    /// The two cameras sharing the most points are placed in a canonical
+    /// projective frame from their fundamental matrix. The remaining cameras are
+    /// added one at a time by resection, triangulating new points as they
+    /// become visible in two cameras.
+    pub(crate) fn initialize(meas: &Measurements) -> Result<Self, InitError> {
+        let n = meas.num_pts;
+
+        // Find the pair of cameras with the most common points.
+        let mut best_pair = None;
+        let mut best_count = 0;
+        for a in 0..meas.num_cams {
+            for b in (a + 1)..meas.num_cams {
+                let count = (0..n)
+                    .filter(|j| meas.get(a, *j).is_some() && meas.get(b, *j).is_some())
+                    .count();
+                if count > best_count {
+                    best_count = count;
+                    best_pair = Some((a, b));
+                }
+            }
+        }
+        let (a, b) = match best_pair {
+            Some(pair) if best_count >= crate::epipolar::MIN_PAIR_POINTS => pair,
+            _ => return Err(InitError::NoInitialPair),
+        };
+
+        // Canonical cameras from the fundamental matrix.
+        let (xa, xb): (Vec<_>, Vec<_>) = (0..n)
+            .filter_map(|j| Some((*meas.get(a, j)?, *meas.get(b, j)?)))
+            .unzip();
+        let f = fundamental_8pt(&xa, &xb);
+        let e = {
+            // The epipole in the second image, `e^T F = 0`.
+            let svd = f.transpose().svd(false, true);
+            let (imin, _) = svd.singular_values.argmin();
+            svd.v_t.unwrap().row(imin).transpose()
+        };
+        let pa = na::Matrix3x4::identity();
+        let mut pb = na::Matrix3x4::zeros();
+        pb.fixed_view_mut::<3, 3>(0, 0)
+            .copy_from(&(e.cross_matrix() * f));
+        pb.set_column(3, &e);
+
+        let mut pmats: Vec<Option<na::Matrix3x4<f64>>> = vec![None; meas.num_cams];
+        pmats[a] = Some(pa);
+        pmats[b] = Some(pb);
+        let mut points = vec![None; n];
+        triangulate_new(meas, &pmats, &mut points);
+
+        loop {
+            // Choose the unregistered camera seeing the most reconstructed
+            // points.
+            let mut best = None;
+            let mut best_count = 0;
+            for (cam, pmat) in pmats.iter().enumerate() {
+                if pmat.is_some() {
+                    continue;
+                }
+                let count = (0..n)
+                    .filter(|j| points[*j].is_some() && meas.get(cam, *j).is_some())
+                    .count();
+                if count > best_count || best.is_none() {
+                    best_count = count;
+                    best = Some(cam);
+                }
+            }
+            let Some(cam) = best else {
+                break;
+            };
+            if best_count < MIN_RESECTION_POINTS {
+                return Err(InitError::CameraNotConnected(cam));
+            }
+            let views = (0..n).filter_map(|j| Some((points[j].as_ref()?, meas.get(cam, j)?)));
+            let pmat = resect(views).ok_or(InitError::CameraNotConnected(cam))?;
+            pmats[cam] = Some(pmat);
+            triangulate_new(meas, &pmats, &mut points);
+        }
+
+        Ok(Self {
+            pmats: pmats.into_iter().map(Option::unwrap).collect(),
+            points,
+        })
+    }
+
+    /// Refine the reconstruction by iterative factorization.
+    ///
+    /// The visible image points, scaled by their projective depths, form a
+    /// measurement matrix which should have rank 4. Its factorization into
+    /// cameras and points is found by alternating least squares over the known
+    /// entries only, updating the projective depths after each iteration.
+    /// Returns the final root mean square reprojection error.
+    pub(crate) fn factorize(&mut self, meas: &Measurements, max_iterations: usize) -> f64 {
+        let mut prev_err = self.rms_reprojection_error(meas);
+        for _ in 0..max_iterations {
+            self.balance();
+
+            // Update the cameras with fixed points and depths.
+            for cam in 0..meas.num_cams {
+                let mut ata = na::Matrix4::<f64>::zeros();
+                let mut atb = na::Matrix4x3::<f64>::zeros();
+                let p3 = self.pmats[cam].row(2).transpose();
+                for j in 0..meas.num_pts {
+                    let (Some(x), Some(pt)) = (meas.get(cam, j), self.points[j].as_ref()) else {
+                        continue;
+                    };
+                    let depth = p3.dot(pt);
+                    let target = na::RowVector3::new(depth * x.x, depth * x.y, depth);
+                    ata += pt * pt.transpose();
+                    atb += pt * target;
+                }
+                if let Some(ata_inv) = ata.try_inverse() {
+                    self.pmats[cam] = (ata_inv * atb).transpose();
+                }
+            }
+
+            // Update the points with fixed cameras and depths.
+            for j in 0..meas.num_pts {
+                let Some(pt) = self.points[j] else {
+                    continue;
+                };
+                let mut ata = na::Matrix4::<f64>::zeros();
+                let mut atb = na::Vector4::<f64>::zeros();
+                for cam in 0..meas.num_cams {
+                    let Some(x) = meas.get(cam, j) else {
+                        continue;
+                    };
+                    let pmat = &self.pmats[cam];
+                    let depth = pmat.row(2).transpose().dot(&pt);
+                    let target = na::Vector3::new(depth * x.x, depth * x.y, depth);
+                    ata += pmat.transpose() * pmat;
+                    atb += pmat.transpose() * target;
+                }
+                if let Some(ata_inv) = ata.try_inverse() {
+                    self.points[j] = Some(ata_inv * atb);
+                }
+            }
+
+            let err = self.rms_reprojection_error(meas);
+            let converged = prev_err - err < 1e-6 * prev_err;
+            prev_err = err;
+            if converged {
+                break;
+            }
+        }
+        self.balance();
+        prev_err
+    }
+
+    /// Rescale cameras and points to unit norm.
+    ///
+    /// This keeps the projective depths from drifting towards zero or
+    /// infinity without changing the reconstruction.
+    fn balance(&mut self) {
+        for pmat in self.pmats.iter_mut() {
+            let norm = pmat.norm();
+            *pmat /= norm;
+        }
+        for pt in self.points.iter_mut().flatten() {
+            let norm = pt.norm();
+            *pt /= norm;
+        }
+    }
+
+    /// The reprojection error of an observation.
+    pub(crate) fn reprojection_error(
+        &self,
+        meas: &Measurements,
+        cam: usize,
+        pt: usize,
+    ) -> Option<f64> {
+        let x = meas.get(cam, pt)?;
+        let predicted = project(&self.pmats[cam], self.points[pt].as_ref()?)?;
+        Some((predicted - x).norm())
+    }
+
+    /// The root mean square reprojection error of all reconstructed points.
+    pub(crate) fn rms_reprojection_error(&self, meas: &Measurements) -> f64 {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for cam in 0..meas.num_cams {
+            for j in 0..meas.num_pts {
+                if let Some(err) = self.reprojection_error(meas, cam, j) {
+                    sum += err * err;
+                    count += 1;
+                }
+            }
+        }
+        if count == 0 {
+            return 0.0;
+        }
+        (sum / count as f64).sqrt()
+    }
+
+    /// Triangulate all points again from the current cameras.
+    pub(crate) fn retriangulate(&mut self, meas: &Measurements) {
+        let pmats: Vec<_> = self.pmats.iter().cloned().map(Some).collect();
+        self.points.iter_mut().for_each(|pt| *pt = None);
+        triangulate_new(meas, &pmats, &mut self.points);
+    }
+}
+
+/// Triangulate the points not yet reconstructed which are seen by at least two
+/// cameras of known `pmats`.
+fn triangulate_new(
+    meas: &Measurements,
+    pmats: &[Option<na::Matrix3x4<f64>>],
+    points: &mut [Option<na::Vector4<f64>>],
+) {
+    for (j, point) in points.iter_mut().enumerate() {
+        if point.is_some() {
+            continue;
+        }
+        let views: Vec<_> = pmats
+            .iter()
+            .enumerate()
+            .filter_map(|(cam, pmat)| Some((pmat.as_ref()?, meas.get(cam, j)?)))
+            .collect();
+        if views.len() >= 2 {
+            *point = triangulate(views);
+        }
+    }
+}
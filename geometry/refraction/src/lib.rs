@@ -78,6 +78,114 @@ where
     Some(*bisect.interval.a())
 }
 
+/// A layer of homogeneous medium between parallel refractive boundaries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer<T> {
+    /// Thickness of the layer
+    pub thickness: T,
+    /// Refractive index of the layer
+    pub n: T,
+}
+
+/// Define the parameters required to solve refraction through parallel layers.
+///
+/// Light travels from a point above the first layer to a point below the last
+/// layer. By Snell's law, `p = n_i sin(a_i)` is the same in every layer `i`,
+/// where `a_i` is the angle of the ray to the normal of the boundaries. We want
+/// to find this ray parameter `p` when we know the thickness and refractive
+/// index of each layer and the distance `d` between the two points along the
+/// boundaries.
+///
+/// ```text
+///                                                      ---
+///                                                 ----/a1|
+///               n1                           ----/       | t1
+///                                       ----/            |
+///      |--------------------------|----/-----------------|
+///      |        n2               /a2|                      t2
+///      |-----------------------/----|--------------------|
+///      |                      /                          |
+///      |        n3           |                           | t3
+///      |                    /a3                          |
+///      |                   /                             |
+///      |                  /                              |
+///                                  d
+///      |<------------------------------------------------>|
+/// ```
+///
+/// With two layers, this is the problem solved by [RefractionEq]. With a wall
+/// between two media, such as the glass of an aquarium, there are three.
+#[derive(Clone, Debug)]
+pub struct LayeredRefractionEq<T> {
+    /// Distance along the refractive boundaries
+    pub d: T,
+    /// The layers, in the order they are traversed
+    pub layers: Vec<Layer<T>>,
+}
+
+impl<T: RealField + Copy> LayeredRefractionEq<T> {
+    /// Distance along the boundaries travelled in each layer for ray parameter
+    /// `p`.
+    pub fn distances(&self, p: T) -> Vec<T> {
+        self.layers.iter().map(|layer| layer.distance(p)).collect()
+    }
+
+    /// Evaluate the refraction equation for ray parameter `p`.
+    pub fn f(&self, p: T) -> T {
+        self.layers
+            .iter()
+            .fold(T::zero(), |acc, layer| acc + layer.distance(p))
+            - self.d
+    }
+}
+
+impl<T: RealField + Copy> Layer<T> {
+    fn distance(&self, p: T) -> T {
+        if self.thickness == T::zero() {
+            T::zero()
+        } else {
+            self.thickness * p / (self.n * self.n - p * p).sqrt()
+        }
+    }
+}
+
+/// Find the ray parameter `p` solving a [LayeredRefractionEq].
+///
+/// Returns `None` if there is no solution, which is the case if no layer has
+/// positive thickness but `d` is not zero.
+pub fn find_ray_parameter<T>(eq: &LayeredRefractionEq<T>, tolerance: T) -> Option<T>
+where
+    T: RealField + Copy,
+{
+    if eq.d == T::zero() {
+        return Some(T::zero());
+    }
+    // The distance along the boundaries increases monotonically with `p` and
+    // is unbounded as `p` approaches the smallest refractive index.
+    let n_min = eq
+        .layers
+        .iter()
+        .filter(|layer| layer.thickness > T::zero())
+        .map(|layer| layer.n)
+        .reduce(|a, b| a.min(b))?;
+    if eq.d < T::zero() || n_min <= T::zero() {
+        return None;
+    }
+    let interval = Interval::new(T::zero(), n_min)?;
+
+    let mut bisect = BisectionSearch::new(interval, |p| eq.f(*p));
+    // Limit the number of steps in case the tolerance is below the precision
+    // of `T`.
+    for _ in 0..200 {
+        bisect = bisect.step();
+        if bisect.interval.size() < tolerance {
+            break;
+        }
+    }
+    let two = T::one() + T::one();
+    Some((*bisect.interval.a() + *bisect.interval.b()) / two)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -112,4 +220,106 @@ mod tests {
         let eps = 0.01;
         assert!(f64::abs(val - 5.7) < eps);
     }
+
+    #[test]
+    fn test_layered_two_layers() {
+        // Same as `test_derivative` above.
+        let eq = crate::LayeredRefractionEq {
+            d: 30.0,
+            layers: vec![
+                crate::Layer {
+                    thickness: 1.0,
+                    n: 1.0,
+                },
+                crate::Layer {
+                    thickness: 5.0,
+                    n: 1.33,
+                },
+            ],
+        };
+        let p = crate::find_ray_parameter(&eq, 1e-14).unwrap();
+        let distances = eq.distances(p);
+        let x = crate::find_root(
+            0.0,
+            30.0,
+            crate::RefractionEq {
+                d: 30.0,
+                h: 5.0,
+                w: 1.0,
+                n: 1.33,
+            },
+            1e-10,
+        )
+        .unwrap();
+        assert!(f64::abs(distances[1] - x) < 1e-8);
+        assert!(f64::abs(distances[0] + distances[1] - 30.0) < 1e-8);
+
+        // Values from flydra.
+        for (z2, h1_expected) in [(0.1, 9.881096304310466), (1.0, 8.814678829560554)] {
+            let eq = crate::LayeredRefractionEq {
+                d: 10.0,
+                layers: vec![
+                    crate::Layer {
+                        thickness: 1.0,
+                        n: 1.0,
+                    },
+                    crate::Layer {
+                        thickness: z2,
+                        n: 1.3,
+                    },
+                ],
+            };
+            let p = crate::find_ray_parameter(&eq, 1e-15).unwrap();
+            assert!(f64::abs(eq.distances(p)[0] - h1_expected) < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_layered_wall() {
+        let layers = vec![
+            crate::Layer {
+                thickness: 0.5,
+                n: 1.0003,
+            },
+            crate::Layer {
+                thickness: 0.01,
+                n: 1.52,
+            },
+            crate::Layer {
+                thickness: 0.2,
+                n: 1.333,
+            },
+        ];
+        let eq = crate::LayeredRefractionEq { d: 0.4, layers };
+        let p = crate::find_ray_parameter(&eq, 1e-14).unwrap();
+        let distances = eq.distances(p);
+        assert!(f64::abs(distances.iter().sum::<f64>() - 0.4) < 1e-10);
+        // Snell's law holds at each boundary.
+        for (layer, x) in eq.layers.iter().zip(distances.iter()) {
+            let sin_a = x / f64::sqrt(x * x + layer.thickness * layer.thickness);
+            assert!(f64::abs(layer.n * sin_a - p) < 1e-10);
+        }
+        // The wall shifts the path compared to no wall.
+        let mut no_wall = eq.clone();
+        no_wall.layers[1].n = 1.333;
+        let p_no_wall = crate::find_ray_parameter(&no_wall, 1e-14).unwrap();
+        assert!(p_no_wall != p);
+
+        // A layer of zero thickness has no effect.
+        let mut thin = eq.clone();
+        thin.layers[1].thickness = 0.0;
+        thin.layers[2].thickness = 0.21;
+        let p_thin = crate::find_ray_parameter(&thin, 1e-14).unwrap();
+        assert!(f64::abs(p_thin - p_no_wall) < 1e-10);
+
+        // Degenerate cases.
+        let mut same_point = eq.clone();
+        same_point.d = 0.0;
+        assert_eq!(crate::find_ray_parameter(&same_point, 1e-14), Some(0.0));
+        let empty = crate::LayeredRefractionEq {
+            d: 1.0,
+            layers: vec![],
+        };
+        assert_eq!(crate::find_ray_parameter(&empty, 1e-14), None);
+    }
 }
@@ -15,14 +15,38 @@ impl UndistortionCache {
         width: usize,
         height: usize,
     ) -> eyre::Result<Self> {
-        use kornia_imgproc::interpolation::grid::meshgrid_from_fn;
-
-        let (mapx, mapy) = meshgrid_from_fn(width, height, |u, v| {
+        Self::from_distort_fn(width, height, |u, v| {
             let undist = opencv_ros_camera::UndistortedPixels {
-                data: nalgebra::RowVector2::<f64>::new(u as f64, v as f64),
+                data: nalgebra::RowVector2::<f64>::new(u, v),
             };
             let dist = intrinsics.distort(&undist).data;
-            Ok((dist[(0, 0)] as f32, dist[(0, 1)] as f32))
+            (dist[(0, 0)], dist[(0, 1)])
+        })
+    }
+
+    /// Create the cache for a camera, including fisheye cameras.
+    ///
+    /// Undistorted pixels without a corresponding distorted pixel are left
+    /// black.
+    pub fn from_camera(cam: &braid_mvg::Camera<f64>) -> eyre::Result<Self> {
+        Self::from_distort_fn(cam.width(), cam.height(), |u, v| {
+            let undist = braid_mvg::UndistortedPixel {
+                coords: nalgebra::Point2::new(u, v),
+            };
+            let dist = cam.distort(&undist).coords;
+            (dist.x, dist.y)
+        })
+    }
+
+    fn from_distort_fn<F>(width: usize, height: usize, distort: F) -> eyre::Result<Self>
+    where
+        F: Fn(f64, f64) -> (f64, f64),
+    {
+        use kornia_imgproc::interpolation::grid::meshgrid_from_fn;
+
+        let (mapx, mapy) = meshgrid_from_fn(width, height, |u, v| {
+            let (x, y) = distort(u as f64, v as f64);
+            Ok((x as f32, y as f32))
         })
         .unwrap();
 
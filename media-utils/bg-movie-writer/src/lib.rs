@@ -3,6 +3,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use strand_cam_remote_control::RecordingStats;
 use strand_dynamic_frame::DynamicFrameOwned;
 
 mod movie_writer_thread;
@@ -31,6 +32,8 @@ pub enum Error {
     FilenameDoesNotEndWithMp4,
     #[error("ffmpeg rewriter error {0}")]
     FfmpegReWriterError(#[from] ffmpeg_rewriter::Error),
+    #[error("OME-TIFF writer error: {0}")]
+    OmeTiffWriterError(#[from] ome_tiff_writer::Error),
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -53,6 +56,7 @@ pub struct BgMovieWriter {
     tx: std::sync::mpsc::SyncSender<Msg>,
     is_done: bool,
     err_from_worker: Arc<Mutex<Option<Error>>>,
+    stats: Arc<Mutex<RecordingStats>>,
 }
 
 impl BgMovieWriter {
@@ -74,20 +78,37 @@ impl BgMovieWriter {
         // the to-be-spawned writer thread.
         let err_to_launcher = Arc::new(Mutex::new(None));
         let err_from_worker = err_to_launcher.clone();
+        let stats = Arc::new(Mutex::new(RecordingStats::default()));
+        let worker_stats = stats.clone();
         // Create a channel to send data into the writer thread.
         let (tx, rx) = std::sync::mpsc::sync_channel::<Msg>(queue_size);
         // Spawn the writer thread
         std::thread::spawn(move || {
             // Runs until the movie is done.
-            movie_writer_thread::writer_thread_loop(recording_config, err_to_launcher, rx, mp4_path)
+            movie_writer_thread::writer_thread_loop(
+                recording_config,
+                err_to_launcher,
+                rx,
+                mp4_path,
+                worker_stats,
+            )
         });
         Self {
             tx,
             is_done: false,
             err_from_worker,
+            stats,
         }
     }
 
+    /// Get the statistics of this recording.
+    ///
+    /// Frames are dropped if the writer cannot keep up with the incoming frame
+    /// rate.
+    pub fn stats(&self) -> RecordingStats {
+        self.stats.lock().unwrap().clone()
+    }
+
     /// Enqueue the frame and timestamp for writing to the background thread.
     ///
     /// If the background writer thread has previously encountered an error,
@@ -104,8 +125,11 @@ impl BgMovieWriter {
         let msg = Msg::Write((frame, timestamp));
         // This will only succeed if the channel is not full. It will not block.
         match self.tx.try_send(msg) {
-            Ok(()) => {}
+            Ok(()) => {
+                self.stats.lock().unwrap().frames_queued += 1;
+            }
             Err(std::sync::mpsc::TrySendError::Full(_msg)) => {
+                self.stats.lock().unwrap().frames_dropped += 1;
                 tracing::warn!("Dropping frame to save: channel full");
             }
             Err(std::sync::mpsc::TrySendError::Disconnected(_msg)) => {
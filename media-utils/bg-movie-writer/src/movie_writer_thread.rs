@@ -11,7 +11,8 @@ use std::{
 
 use chrono::{DateTime, Local};
 use mp4_writer::Mp4Writer;
-use strand_cam_remote_control::FfmpegRecordingConfig;
+use ome_tiff_writer::OmeTiffWriter;
+use strand_cam_remote_control::{FfmpegRecordingConfig, RecordingStats};
 use strand_dynamic_frame::DynamicFrame;
 
 use crate::{Error, Msg, Result};
@@ -38,6 +39,7 @@ where
 {
     Mp4Writer(Mp4Writer<'lib, T>),
     FfmpegReWriter(Box<MyFfmpegWriter>),
+    OmeTiffWriter(OmeTiffWriter<T>),
 }
 
 struct MyFfmpegWriter {
@@ -153,8 +155,12 @@ fn create_writer<'a>(
             )?)
         }
         Ffmpeg(c) => RawWriter::FfmpegReWriter(Box::new(MyFfmpegWriter::new(&mp4_path, c)?)),
+        Tiff(c) => {
+            let tiff_file = std::fs::File::create(&mp4_path)?;
+            RawWriter::OmeTiffWriter(OmeTiffWriter::new(tiff_file, c.metadata.clone())?)
+        }
     };
-    tracing::info!("Saving MP4 to \"{}\"", mp4_path.display());
+    tracing::info!("Saving movie to \"{}\"", mp4_path.display());
 
     Ok(raw)
 }
@@ -175,10 +181,28 @@ fn save_frame(
             r.write_dynamic(frame, stamp)?;
             *last_saved_stamp = Some(stamp);
         }
+        RawWriter::OmeTiffWriter(ref mut r) => {
+            r.write_dynamic(frame, stamp)?;
+            *last_saved_stamp = Some(stamp);
+        }
     }
     Ok(())
 }
 
+/// Update the recording statistics after saving a frame. Runs inside writer
+/// thread loop.
+fn update_stats(raw: &RawWriter<'_, File>, stats: &Mutex<RecordingStats>) {
+    let mut stats = stats.lock().unwrap();
+    stats.frames_written += 1;
+    if let RawWriter::Mp4Writer(r) = raw {
+        if let Some(pool_stats) = r.less_avc_pool_stats() {
+            stats.encoder_threads = Some(pool_stats.num_threads);
+            stats.encoder_in_flight = pool_stats.in_flight();
+            stats.encoder_blocked_secs = pool_stats.blocked.as_secs_f64();
+        }
+    }
+}
+
 /// Finish the writer. Runs inside writer thread loop.
 fn finish_writer(raw: &mut RawWriter<'_, File>) -> Result<()> {
     match raw {
@@ -188,6 +212,9 @@ fn finish_writer(raw: &mut RawWriter<'_, File>) -> Result<()> {
         RawWriter::FfmpegReWriter(ffmpeg_wtr) => {
             ffmpeg_wtr.finish()?;
         }
+        RawWriter::OmeTiffWriter(tiff_wtr) => {
+            tiff_wtr.finish()?;
+        }
     }
     Ok(())
 }
@@ -197,6 +224,7 @@ pub(crate) fn writer_thread_loop(
     err_tx: Arc<Mutex<Option<Error>>>,
     rx: std::sync::mpsc::Receiver<Msg>,
     mp4_path: PathBuf,
+    stats: Arc<Mutex<RecordingStats>>,
 ) {
     {
         // Load CUDA and nvidia-encode shared libs, but do not return error
@@ -233,6 +261,7 @@ pub(crate) fn writer_thread_loop(
                             err_tx,
                             save_frame(raw_ref, &frame.borrow(), stamp, &mut last_saved_stamp)
                         );
+                        update_stats(raw_ref, &stats);
                     }
                 }
                 Ok(Msg::Finish) | Err(std::sync::mpsc::RecvError) => {
@@ -240,9 +269,9 @@ pub(crate) fn writer_thread_loop(
                     // closed the channel. In either case, close the MP4 file.
                     if let Some(raw_ref) = raw.as_mut() {
                         thread_try!(err_tx, finish_writer(raw_ref));
-                        tracing::info!("MP4 saving complete.");
+                        tracing::info!("Movie saving complete.");
                     } else {
-                        tracing::error!("MP4 never started, but finish command received.");
+                        tracing::error!("Movie never started, but finish command received.");
                     }
                     return; // end the thread
                 }
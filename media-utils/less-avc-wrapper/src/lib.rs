@@ -6,6 +6,9 @@ use strand_dynamic_frame::DynamicFrame;
 
 use less_avc::ycbcr_image::*;
 
+mod pool;
+pub use pool::{EncoderPool, PoolStats};
+
 /// An H.264 encoding error.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -21,6 +24,10 @@ pub enum Error {
     },
     #[error("y4m writer error: {0}")]
     Y4mError(#[from] y4m_writer::Error),
+    #[error("encoder pool already finished")]
+    PoolClosed,
+    #[error("encoder thread disconnected")]
+    PoolDisconnected,
 }
 
 type Result<T> = std::result::Result<T, Error>;
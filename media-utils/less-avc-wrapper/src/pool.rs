@@ -0,0 +1,192 @@
+//! Encode frames with LessAVC in a pool of threads.
+//!
+//! LessAVC encodes every frame as an independent intra-coded picture, so
+//! frames can be encoded concurrently. Each worker thread holds its own
+//! [WrappedLessEncoder] and the encoded frames are returned in the order in
+//! which they were submitted.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        mpsc::{channel, sync_channel, Receiver, Sender, SyncSender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use strand_dynamic_frame::DynamicFrameOwned;
+
+use crate::{Error, Result, WrappedLessEncoder};
+
+type Job = (u64, DynamicFrameOwned);
+type JobResult = (u64, Result<Vec<Vec<u8>>>);
+
+/// Statistics about an [EncoderPool].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolStats {
+    /// Number of encoder threads.
+    pub num_threads: usize,
+    /// Maximum number of frames being encoded at once.
+    pub max_in_flight: usize,
+    /// Number of frames submitted for encoding.
+    pub frames_submitted: u64,
+    /// Number of encoded frames returned.
+    pub frames_completed: u64,
+    /// Total duration [EncoderPool::submit] blocked waiting for a free slot.
+    pub blocked: Duration,
+}
+
+impl PoolStats {
+    /// Number of frames currently being encoded or awaiting reordering.
+    pub fn in_flight(&self) -> usize {
+        (self.frames_submitted - self.frames_completed) as usize
+    }
+}
+
+/// A pool of threads encoding frames with LessAVC.
+///
+/// Each submitted frame carries a `tag` (e.g. its timestamp) which is
+/// returned alongside its encoded NAL units.
+pub struct EncoderPool<T> {
+    job_tx: Option<SyncSender<Job>>,
+    result_rx: Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+    tags: VecDeque<T>,
+    reorder: BTreeMap<u64, Result<Vec<Vec<u8>>>>,
+    next_seq: u64,
+    stats: PoolStats,
+}
+
+impl<T> EncoderPool<T> {
+    /// Spawn `num_threads` encoder threads.
+    ///
+    /// At most `max_in_flight` frames are encoded at once. When this many
+    /// frames are in flight, [Self::submit] blocks until the oldest frame is
+    /// encoded.
+    pub fn new(num_threads: usize, max_in_flight: usize) -> Self {
+        let num_threads = num_threads.max(1);
+        let max_in_flight = max_in_flight.max(num_threads);
+        let (job_tx, job_rx) = sync_channel::<Job>(max_in_flight);
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let (result_tx, result_rx) = channel();
+        let workers = (0..num_threads)
+            .map(|i| {
+                let job_rx = job_rx.clone();
+                let result_tx = result_tx.clone();
+                std::thread::Builder::new()
+                    .name(format!("less-avc-{i}"))
+                    .spawn(move || worker_loop(job_rx, result_tx))
+                    .unwrap()
+            })
+            .collect();
+        Self {
+            job_tx: Some(job_tx),
+            result_rx,
+            workers,
+            tags: VecDeque::new(),
+            reorder: BTreeMap::new(),
+            next_seq: 0,
+            stats: PoolStats {
+                num_threads,
+                max_in_flight,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Get the current statistics.
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    /// Submit a frame for encoding.
+    ///
+    /// Returns the frames which have finished encoding, in submission order.
+    pub fn submit(&mut self, frame: DynamicFrameOwned, tag: T) -> Result<Vec<(T, Vec<Vec<u8>>)>> {
+        let mut done = Vec::new();
+        if self.stats.in_flight() >= self.stats.max_in_flight {
+            let start = Instant::now();
+            while self.stats.in_flight() >= self.stats.max_in_flight {
+                self.recv_one()?;
+                self.drain_ready(&mut done)?;
+            }
+            self.stats.blocked += start.elapsed();
+        }
+
+        let job_tx = self.job_tx.as_ref().ok_or(Error::PoolClosed)?;
+        job_tx
+            .send((self.next_seq, frame))
+            .map_err(|_| Error::PoolDisconnected)?;
+        self.next_seq += 1;
+        self.tags.push_back(tag);
+        self.stats.frames_submitted += 1;
+
+        while let Ok((seq, result)) = self.result_rx.try_recv() {
+            self.reorder.insert(seq, result);
+        }
+        self.drain_ready(&mut done)?;
+        Ok(done)
+    }
+
+    /// Wait for all submitted frames to be encoded and stop the threads.
+    ///
+    /// Returns the remaining encoded frames, in submission order.
+    pub fn finish(&mut self) -> Result<Vec<(T, Vec<Vec<u8>>)>> {
+        let mut done = Vec::new();
+        while self.stats.in_flight() > 0 {
+            self.recv_one()?;
+            self.drain_ready(&mut done)?;
+        }
+        self.shutdown();
+        Ok(done)
+    }
+
+    fn recv_one(&mut self) -> Result<()> {
+        let (seq, result) = self.result_rx.recv().map_err(|_| Error::PoolDisconnected)?;
+        self.reorder.insert(seq, result);
+        Ok(())
+    }
+
+    /// Move consecutive finished frames into `done`.
+    fn drain_ready(&mut self, done: &mut Vec<(T, Vec<Vec<u8>>)>) -> Result<()> {
+        while let Some(result) = self.reorder.remove(&self.stats.frames_completed) {
+            let tag = self.tags.pop_front().ok_or(Error::PoolDisconnected)?;
+            self.stats.frames_completed += 1;
+            done.push((tag, result?));
+        }
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        // Closing the job channel ends the worker loops.
+        self.job_tx.take();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                tracing::error!("LessAVC encoder thread panicked");
+            }
+        }
+    }
+}
+
+impl<T> Drop for EncoderPool<T> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn worker_loop(job_rx: Arc<Mutex<Receiver<Job>>>, result_tx: Sender<JobResult>) {
+    let mut encoder = WrappedLessEncoder::default();
+    loop {
+        // Hold the lock only while waiting for the next job.
+        let job = job_rx.lock().unwrap().recv();
+        let Ok((seq, frame)) = job else {
+            // Channel closed.
+            return;
+        };
+        let result = encoder.encode_dynamic_to_nal_units(&frame.borrow());
+        if result_tx.send((seq, result)).is_err() {
+            return;
+        }
+    }
+}
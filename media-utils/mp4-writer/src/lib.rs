@@ -64,6 +64,8 @@ pub enum Error {
     InconsistentState {},
     #[error("timestamp too large")]
     TimestampTooLarge {},
+    #[error("timestamp before first frame")]
+    TimestampBeforeFirstFrame {},
     #[error("convert image error")]
     ConvertImageError(#[from] convert_image::Error),
     #[cfg(feature = "openh264")]
@@ -117,6 +119,7 @@ enum MyEncoder<'lib> {
     #[cfg(feature = "openh264")]
     OpenH264(OpenH264Encoder),
     LessH264(LessEncoderWrapper),
+    LessH264Pool(LessEncoderPool),
 }
 
 pub struct Mp4Writer<'lib, T>
@@ -154,6 +157,17 @@ where
         self.first_pps = first_pps;
     }
 
+    /// Statistics of the LessAVC encoder thread pool, if in use.
+    pub fn less_avc_pool_stats(&self) -> Option<less_avc_wrapper::PoolStats> {
+        match &self.inner {
+            Some(WriteState::Recording(state)) => match &state.my_encoder {
+                MyEncoder::LessH264Pool(encoder) => Some(encoder.pool.stats()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Low-level writer which saves a buffer which is already h264 encoded.
     ///
     /// This skips the automatic encoding which would normally be done.
@@ -318,6 +332,7 @@ where
                 match &cfg.codec {
                     strand_cam_remote_control::Mp4Codec::H264RawStream => {}
                     strand_cam_remote_control::Mp4Codec::H264LessAvc => {}
+                    strand_cam_remote_control::Mp4Codec::H264LessAvcThreaded(_) => {}
                     strand_cam_remote_control::Mp4Codec::H264OpenH264(_) => {}
                     #[cfg(not(feature = "nv-encode"))]
                     strand_cam_remote_control::Mp4Codec::H264NvEnc(_) => {
@@ -438,8 +453,25 @@ where
                     strand_cam_remote_control::Mp4Codec::H264LessAvc => {
                         MyEncoder::LessH264(LessEncoderWrapper {
                             encoder: Default::default(),
-                            h264_parser,
-                            first_timestamp: timestamp,
+                            output: LessAvcOutput {
+                                h264_parser,
+                                first_timestamp: timestamp,
+                            },
+                        })
+                    }
+                    strand_cam_remote_control::Mp4Codec::H264LessAvcThreaded(opts) => {
+                        let num_threads = opts.num_threads.unwrap_or_else(|| {
+                            std::thread::available_parallelism()
+                                .map(|n| n.get())
+                                .unwrap_or(1)
+                        });
+                        debug!("Using {num_threads} LessAVC encoder threads.");
+                        MyEncoder::LessH264Pool(LessEncoderPool {
+                            pool: less_avc_wrapper::EncoderPool::new(num_threads, 2 * num_threads),
+                            output: LessAvcOutput {
+                                h264_parser,
+                                first_timestamp: timestamp,
+                            },
                         })
                     }
                     #[allow(unused_variables)]
@@ -558,6 +590,22 @@ where
                 match state.my_encoder {
                     MyEncoder::CopyRawH264 { h264_parser: _ } | MyEncoder::LessH264(_) => { /* nothing to do */
                     }
+                    MyEncoder::LessH264Pool(ref mut encoder) => {
+                        // Now done with all frames, drain the pending data.
+                        let encoded = encoder.pool.finish()?;
+                        if let Some(state_inner) = state.inner.as_ref() {
+                            for (pts, nals) in encoded {
+                                encoder.output.inner_save_data(
+                                    &mut state.mp4_segment,
+                                    less_avc_sample(pts, nals)?,
+                                    state_inner.trim_width,
+                                    state_inner.trim_height,
+                                )?;
+                            }
+                        } else {
+                            return Err(Error::InconsistentState {});
+                        }
+                    }
                     #[cfg(feature = "openh264")]
                     MyEncoder::OpenH264(_encoder) => { /* nothing to do */ }
                     #[cfg(not(feature = "nv-encode"))]
@@ -657,27 +705,27 @@ where
             return Err(Error::RawH264CopyCannotEncodeFrame {});
         }
         (MyEncoder::LessH264(encoder), Some(state_inner)) => {
+            let pts = encoder.output.pts(timestamp)?;
             let nals = encoder.encoder.encode_dynamic_to_nal_units(raw_frame)?;
-
-            let is_keyframe = true;
-
-            let pts = timestamp - encoder.first_timestamp;
-            let mp4_sample_start_time = dur2raw(&pts.to_std().unwrap());
-
-            let sample = EbspNals {
-                pts,
-                mp4_sample_start_time,
-                is_keyframe,
-                nals,
-            };
-
-            encoder.inner_save_data(
+            encoder.output.inner_save_data(
                 &mut state.mp4_segment,
-                sample,
+                less_avc_sample(pts, nals)?,
                 state_inner.trim_width,
                 state_inner.trim_height,
             )?;
         }
+        (MyEncoder::LessH264Pool(encoder), Some(state_inner)) => {
+            let pts = encoder.output.pts(timestamp)?;
+            let encoded = encoder.pool.submit(raw_frame.copy_to_owned(), pts)?;
+            for (pts, nals) in encoded {
+                encoder.output.inner_save_data(
+                    &mut state.mp4_segment,
+                    less_avc_sample(pts, nals)?,
+                    state_inner.trim_width,
+                    state_inner.trim_height,
+                )?;
+            }
+        }
         #[cfg(feature = "openh264")]
         (MyEncoder::OpenH264(encoder), Some(state_inner)) => {
             // todo: bitrate, keyframes, timestamp check and duration finding.
@@ -805,11 +853,34 @@ struct RecordingStateInner {
 
 struct LessEncoderWrapper {
     encoder: less_avc_wrapper::WrappedLessEncoder,
+    output: LessAvcOutput,
+}
+
+/// LessAVC encoding in a pool of threads.
+///
+/// Frames are submitted to the pool and saved once they have been encoded,
+/// which may be several frames later.
+struct LessEncoderPool {
+    /// The tag of each frame is its PTS.
+    pool: less_avc_wrapper::EncoderPool<chrono::Duration>,
+    output: LessAvcOutput,
+}
+
+/// Saves the frames encoded by [LessEncoderWrapper] or [LessEncoderPool].
+struct LessAvcOutput {
     h264_parser: H264Parser,
     first_timestamp: chrono::DateTime<chrono::Local>,
 }
 
-impl LessEncoderWrapper {
+impl LessAvcOutput {
+    /// The PTS of a frame, which must not be before the first frame.
+    fn pts(&self, timestamp: chrono::DateTime<chrono::Local>) -> Result<chrono::Duration> {
+        let pts = timestamp - self.first_timestamp;
+        if pts < chrono::Duration::zero() {
+            return Err(Error::TimestampBeforeFirstFrame {});
+        }
+        Ok(pts)
+    }
     fn compute_local_timestamp(&self, sample: &EbspNals) -> chrono::DateTime<chrono::Local> {
         self.first_timestamp + sample.pts
     }
@@ -834,7 +905,7 @@ impl LessEncoderWrapper {
                 start_mp4_writer(fd, sps, pps, trim_width, trim_height)?
             }
             MaybeMp4Writer::Nothing => {
-                panic!("inconsistent state");
+                return inconsistent_state_err();
             }
         };
 
@@ -847,6 +918,19 @@ impl LessEncoderWrapper {
     }
 }
 
+/// Every LessAVC frame is an intra-coded keyframe.
+fn less_avc_sample(pts: chrono::Duration, nals: Vec<Vec<u8>>) -> Result<EbspNals> {
+    let start = pts
+        .to_std()
+        .map_err(|_| Error::TimestampBeforeFirstFrame {})?;
+    Ok(EbspNals {
+        pts,
+        mp4_sample_start_time: dur2raw(&start),
+        is_keyframe: true,
+        nals,
+    })
+}
+
 #[cfg(feature = "nv-encode")]
 struct NvEncoder<'lib> {
     encoder: Rc<nvenc::Encoder<'lib>>,
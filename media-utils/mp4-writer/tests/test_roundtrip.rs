@@ -23,7 +23,7 @@ fn test_save_then_read_with_ffmpeg() -> Result<()> {
         std::mem::forget(tmpdir); // do not drop it, so do not delete it
     }
 
-    let mut codecs = vec!["less_avc"];
+    let mut codecs = vec!["less_avc", "less_avc_threaded"];
 
     #[cfg(feature = "openh264")]
     codecs.push("open-h264");
@@ -91,6 +91,11 @@ fn test_save_then_read_with_ffmpeg() -> Result<()> {
                 )
             }
             "less_avc" => (strand_cam_remote_control::Mp4Codec::H264LessAvc, None, 0),
+            "less_avc_threaded" => (
+                strand_cam_remote_control::Mp4Codec::H264LessAvcThreaded(Default::default()),
+                None,
+                0,
+            ),
             _ => {
                 panic!("unknown codec str");
             }
@@ -146,6 +151,61 @@ fn test_save_then_read_with_ffmpeg() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_less_avc_threaded_frame_order() -> Result<()> {
+    let start = chrono::DateTime::from_timestamp(61, 0).unwrap();
+    let tmpdir = tempfile::tempdir()?;
+    let output_name = tmpdir.path().join("test-movie-threaded.mp4");
+
+    let cfg = Mp4RecordingConfig {
+        codec: strand_cam_remote_control::Mp4Codec::H264LessAvcThreaded(
+            strand_cam_remote_control::LessAvcThreadedOptions {
+                num_threads: Some(3),
+            },
+        ),
+        max_framerate: Default::default(),
+        h264_metadata: None,
+    };
+
+    let n_frames = 20;
+    let frame = generate_image("mono8", 64, 32)?;
+    {
+        let out_fd = std::fs::File::create(&output_name)?;
+        #[cfg(feature = "nv-encode")]
+        let mut my_mp4_writer = mp4_writer::Mp4Writer::new(out_fd, cfg, None)?;
+        #[cfg(not(feature = "nv-encode"))]
+        let mut my_mp4_writer = mp4_writer::Mp4Writer::new(out_fd, cfg)?;
+        for i in 0..n_frames {
+            let timestamp = start + chrono::Duration::milliseconds(10 * i);
+            my_mp4_writer.write_dynamic(&frame, timestamp)?;
+        }
+        my_mp4_writer.finish()?;
+    }
+
+    // Frames must be saved in the order written, despite being encoded in
+    // parallel.
+    let mut src = frame_source::FrameSourceBuilder::new(&output_name)
+        .do_decode_h264(false)
+        .timestamp_source(frame_source::TimestampSource::MispMicrosectime)
+        .build_source()?;
+    assert_eq!(start, src.frame0_time().unwrap());
+    let mut count = 0;
+    for (i, frame) in src.iter().enumerate() {
+        let pts = frame?.timestamp().unwrap_duration();
+        let expected = std::time::Duration::from_millis(10 * i as u64);
+        let diff = if pts > expected {
+            pts - expected
+        } else {
+            expected - pts
+        };
+        assert!(diff < std::time::Duration::from_micros(10));
+        count += 1;
+    }
+    assert_eq!(count, n_frames);
+
+    Ok(())
+}
+
 fn are_images_similar<FMT>(
     frame1: &dyn machine_vision_formats::iter::HasRowChunksExact<FMT>,
     frame2: &dyn machine_vision_formats::iter::HasRowChunksExact<FMT>,
@@ -0,0 +1,536 @@
+//! Write lossless multi-page [OME-TIFF](https://ome-model.readthedocs.io/en/stable/ome-tiff/)
+//! files.
+//!
+//! Each frame is stored uncompressed as one page of a BigTIFF file, so 8-bit,
+//! 16-bit (e.g. 12-bit sensor data) and floating point images are kept at full
+//! precision. When the writer is finished, an OME-XML document is stored in the
+//! `ImageDescription` tag of the first page. This contains the timestamp of
+//! each frame (as `DeltaT` relative to the `AcquisitionDate` of the first
+//! frame) and the camera metadata.
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::{DateTime, TimeZone, Utc};
+use machine_vision_formats::{
+    pixel_format::{Mono32f, Mono8, RGB8},
+    ImageStride, PixFmt, PixelFormat,
+};
+use strand_cam_remote_control::H264Metadata;
+use strand_dynamic_frame::DynamicFrame;
+
+mod ome_xml;
+pub use ome_xml::STRAND_METADATA_NAMESPACE;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error: {source}")]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
+    #[error("format or size changed")]
+    FormatOrSizeChanged,
+    #[error("unsupported pixel format: {0}")]
+    UnsupportedPixelFormat(PixFmt),
+    #[error("buffer size {actual} does not match expected size {expected}")]
+    UnexpectedBufferSize { expected: usize, actual: usize },
+    #[error("already closed")]
+    AlreadyClosed,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// TIFF field types
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_LONG8: u16 = 16;
+
+// TIFF tags (must be written in ascending order)
+const TAG_IMAGE_WIDTH: u16 = 256;
+const TAG_IMAGE_LENGTH: u16 = 257;
+const TAG_BITS_PER_SAMPLE: u16 = 258;
+const TAG_COMPRESSION: u16 = 259;
+const TAG_PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const TAG_IMAGE_DESCRIPTION: u16 = 270;
+const TAG_STRIP_OFFSETS: u16 = 273;
+const TAG_SAMPLES_PER_PIXEL: u16 = 277;
+const TAG_ROWS_PER_STRIP: u16 = 278;
+const TAG_STRIP_BYTE_COUNTS: u16 = 279;
+const TAG_PLANAR_CONFIGURATION: u16 = 284;
+const TAG_SAMPLE_FORMAT: u16 = 339;
+
+/// Size of a BigTIFF IFD entry in bytes.
+const ENTRY_SIZE: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SampleKind {
+    Gray8,
+    Gray16,
+    Gray32f,
+    Rgb8,
+}
+
+impl SampleKind {
+    pub(crate) fn samples_per_pixel(&self) -> u16 {
+        match self {
+            Self::Rgb8 => 3,
+            _ => 1,
+        }
+    }
+    fn bits_per_sample(&self) -> u16 {
+        match self {
+            Self::Gray8 | Self::Rgb8 => 8,
+            Self::Gray16 => 16,
+            Self::Gray32f => 32,
+        }
+    }
+    fn bytes_per_pixel(&self) -> usize {
+        self.samples_per_pixel() as usize * self.bits_per_sample() as usize / 8
+    }
+    /// TIFF `SampleFormat` value: 1 is unsigned integer, 3 is IEEE float.
+    fn sample_format(&self) -> u16 {
+        match self {
+            Self::Gray32f => 3,
+            _ => 1,
+        }
+    }
+    /// TIFF `PhotometricInterpretation` value: 1 is BlackIsZero, 2 is RGB.
+    fn photometric(&self) -> u16 {
+        match self {
+            Self::Rgb8 => 2,
+            _ => 1,
+        }
+    }
+    pub(crate) fn ome_type(&self) -> &'static str {
+        match self {
+            Self::Gray8 | Self::Rgb8 => "uint8",
+            Self::Gray16 => "uint16",
+            Self::Gray32f => "float",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StackInfo {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) kind: SampleKind,
+    pub(crate) significant_bits: Option<u8>,
+}
+
+/// Writes a stack of frames to a multi-page OME-TIFF file.
+///
+/// All frames must have the same size and pixel format. Call [Self::finish]
+/// once all frames are written to store the OME-XML metadata. (Dropping the
+/// writer will also do this, but silently ignores any error.)
+pub struct OmeTiffWriter<F: Write + Seek> {
+    f: Option<BufWriter<F>>,
+    metadata: Option<H264Metadata>,
+    info: Option<StackInfo>,
+    /// File position of the next IFD offset field to update when the next
+    /// page is written.
+    next_ifd_offset_pos: u64,
+    /// File position of the count field of the `ImageDescription` entry of
+    /// the first IFD.
+    description_count_pos: Option<u64>,
+    frame0_time: Option<DateTime<Utc>>,
+    /// Time of each frame, in seconds, relative to `frame0_time`.
+    delta_t: Vec<f64>,
+}
+
+impl<F: Write + Seek> OmeTiffWriter<F> {
+    /// Open a new writer.
+    ///
+    /// If given, `metadata` is stored in the OME-XML description.
+    pub fn new(f: F, metadata: Option<H264Metadata>) -> Result<Self> {
+        let mut f = BufWriter::new(f);
+        // BigTIFF header, little endian.
+        f.write_all(b"II")?;
+        f.write_u16::<LittleEndian>(43)?; // version
+        f.write_u16::<LittleEndian>(8)?; // bytesize of offsets
+        f.write_u16::<LittleEndian>(0)?;
+        let next_ifd_offset_pos = f.stream_position()?;
+        f.write_u64::<LittleEndian>(0)?; // offset of first IFD, updated later
+        Ok(Self {
+            f: Some(f),
+            metadata,
+            info: None,
+            next_ifd_offset_pos,
+            description_count_pos: None,
+            frame0_time: None,
+            delta_t: Vec::new(),
+        })
+    }
+
+    /// The number of frames written so far.
+    pub fn frame_count(&self) -> usize {
+        self.delta_t.len()
+    }
+
+    /// Write a frame.
+    ///
+    /// Supported pixel formats are `Mono8`, `Mono32f` and `RGB8`. Use
+    /// [Self::write_gray16] for high bit depth monochrome images.
+    pub fn write_dynamic<TZ: TimeZone>(
+        &mut self,
+        frame: &DynamicFrame,
+        timestamp: DateTime<TZ>,
+    ) -> Result<()> {
+        let pixfmt = frame.pixel_format();
+        let kind = match pixfmt {
+            PixFmt::Mono8 => SampleKind::Gray8,
+            PixFmt::Mono32f => SampleKind::Gray32f,
+            PixFmt::RGB8 => SampleKind::Rgb8,
+            _ => return Err(Error::UnsupportedPixelFormat(pixfmt)),
+        };
+        let info = StackInfo {
+            width: frame.width(),
+            height: frame.height(),
+            kind,
+            significant_bits: match kind {
+                SampleKind::Gray32f => None,
+                _ => Some(8),
+            },
+        };
+        let row_bytes = frame.width() as usize * kind.bytes_per_pixel();
+        let timestamp = timestamp.with_timezone(&Utc);
+        match kind {
+            SampleKind::Gray8 => self.write_page(info, timestamp, |f| {
+                write_rows(f, &frame.as_static::<Mono8>().unwrap(), row_bytes)
+            }),
+            SampleKind::Rgb8 => self.write_page(info, timestamp, |f| {
+                write_rows(f, &frame.as_static::<RGB8>().unwrap(), row_bytes)
+            }),
+            SampleKind::Gray32f => self.write_page(info, timestamp, |f| {
+                write_rows_f32(f, &frame.as_static::<Mono32f>().unwrap(), row_bytes)
+            }),
+            SampleKind::Gray16 => unreachable!(),
+        }
+    }
+
+    /// Write a 16-bit monochrome frame.
+    ///
+    /// `data` is in row-major order without padding. `significant_bits` is the
+    /// bit depth of the sensor data (e.g. 12) and is stored in the OME-XML
+    /// description.
+    pub fn write_gray16<TZ: TimeZone>(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[u16],
+        significant_bits: u8,
+        timestamp: DateTime<TZ>,
+    ) -> Result<()> {
+        let expected = width as usize * height as usize;
+        if data.len() != expected {
+            return Err(Error::UnexpectedBufferSize {
+                expected,
+                actual: data.len(),
+            });
+        }
+        let info = StackInfo {
+            width,
+            height,
+            kind: SampleKind::Gray16,
+            significant_bits: Some(significant_bits),
+        };
+        self.write_page(info, timestamp.with_timezone(&Utc), |f| {
+            for val in data.iter() {
+                f.write_u16::<LittleEndian>(*val)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn write_page<W>(
+        &mut self,
+        info: StackInfo,
+        timestamp: DateTime<Utc>,
+        write_strip: W,
+    ) -> Result<()>
+    where
+        W: FnOnce(&mut BufWriter<F>) -> std::io::Result<()>,
+    {
+        let f = self.f.as_mut().ok_or(Error::AlreadyClosed)?;
+        match &self.info {
+            None => self.info = Some(info.clone()),
+            Some(prev) => {
+                if prev != &info {
+                    return Err(Error::FormatOrSizeChanged);
+                }
+            }
+        }
+
+        // Image data. The whole image is a single strip.
+        let strip_offset = f.stream_position()?;
+        write_strip(f)?;
+        let mut ifd_pos = f.stream_position()?;
+        let strip_byte_count = ifd_pos - strip_offset;
+        if ifd_pos % 2 == 1 {
+            // IFDs must begin on a word boundary.
+            f.write_u8(0)?;
+            ifd_pos += 1;
+        }
+
+        let is_first = self.description_count_pos.is_none();
+        let spp = info.kind.samples_per_pixel();
+        let mut entries = vec![
+            Entry::long(TAG_IMAGE_WIDTH, info.width),
+            Entry::long(TAG_IMAGE_LENGTH, info.height),
+            Entry::shorts(
+                TAG_BITS_PER_SAMPLE,
+                &vec![info.kind.bits_per_sample(); spp.into()],
+            ),
+            Entry::shorts(TAG_COMPRESSION, &[1]),
+            Entry::shorts(TAG_PHOTOMETRIC_INTERPRETATION, &[info.kind.photometric()]),
+        ];
+        let description_idx = entries.len();
+        if is_first {
+            // Placeholder of a single NUL byte. The OME-XML is written at the
+            // end of the file and this entry updated by `finish()`.
+            entries.push(Entry {
+                tag: TAG_IMAGE_DESCRIPTION,
+                typ: TYPE_ASCII,
+                count: 1,
+                value: [0; 8],
+            });
+        }
+        entries.extend([
+            Entry::long8(TAG_STRIP_OFFSETS, strip_offset),
+            Entry::shorts(TAG_SAMPLES_PER_PIXEL, &[spp]),
+            Entry::long(TAG_ROWS_PER_STRIP, info.height),
+            Entry::long8(TAG_STRIP_BYTE_COUNTS, strip_byte_count),
+            Entry::shorts(TAG_PLANAR_CONFIGURATION, &[1]),
+            Entry::shorts(
+                TAG_SAMPLE_FORMAT,
+                &vec![info.kind.sample_format(); spp.into()],
+            ),
+        ]);
+
+        f.write_u64::<LittleEndian>(entries.len() as u64)?;
+        for entry in entries.iter() {
+            entry.write(f)?;
+        }
+        let this_next_ifd_offset_pos = f.stream_position()?;
+        f.write_u64::<LittleEndian>(0)?;
+        let end_pos = f.stream_position()?;
+
+        // Link this IFD from the header or the previous IFD.
+        f.seek(SeekFrom::Start(self.next_ifd_offset_pos))?;
+        f.write_u64::<LittleEndian>(ifd_pos)?;
+        f.seek(SeekFrom::Start(end_pos))?;
+        self.next_ifd_offset_pos = this_next_ifd_offset_pos;
+
+        if is_first {
+            // Skip the entry count, then the tag and type of the entry.
+            self.description_count_pos =
+                Some(ifd_pos + 8 + description_idx as u64 * ENTRY_SIZE + 4);
+        }
+
+        let frame0_time = *self.frame0_time.get_or_insert(timestamp);
+        let delta_t = (timestamp - frame0_time).num_nanoseconds().unwrap_or(i64::MAX);
+        self.delta_t.push(delta_t as f64 * 1e-9);
+        Ok(())
+    }
+
+    /// Write the OME-XML metadata and flush the file.
+    ///
+    /// Returns the underlying writer.
+    pub fn finish(&mut self) -> Result<F> {
+        let mut f = self.f.take().ok_or(Error::AlreadyClosed)?;
+        if let (Some(info), Some(frame0_time), Some(description_count_pos)) = (
+            &self.info,
+            self.frame0_time,
+            self.description_count_pos,
+        ) {
+            let xml = ome_xml::build(&ome_xml::OmeXmlInfo {
+                info,
+                metadata: self.metadata.as_ref(),
+                frame0_time,
+                delta_t: &self.delta_t,
+            });
+            let xml_offset = f.seek(SeekFrom::End(0))?;
+            f.write_all(xml.as_bytes())?;
+            f.write_u8(0)?;
+            f.seek(SeekFrom::Start(description_count_pos))?;
+            f.write_u64::<LittleEndian>(xml.len() as u64 + 1)?;
+            f.write_u64::<LittleEndian>(xml_offset)?;
+        }
+        f.flush()?;
+        f.into_inner().map_err(|e| e.into_error().into())
+    }
+}
+
+/// This will silently ignore any error.
+impl<F: Write + Seek> Drop for OmeTiffWriter<F> {
+    fn drop(&mut self) {
+        if self.f.is_some() {
+            // We silently drop error.
+            let _ = self.finish();
+        }
+    }
+}
+
+struct Entry {
+    tag: u16,
+    typ: u16,
+    count: u64,
+    /// Value, left-justified, if it fits in 8 bytes.
+    value: [u8; 8],
+}
+
+impl Entry {
+    fn shorts(tag: u16, vals: &[u16]) -> Self {
+        assert!(vals.len() <= 4);
+        let mut value = [0; 8];
+        for (chunk, val) in value.chunks_exact_mut(2).zip(vals.iter()) {
+            chunk.copy_from_slice(&val.to_le_bytes());
+        }
+        Self {
+            tag,
+            typ: TYPE_SHORT,
+            count: vals.len() as u64,
+            value,
+        }
+    }
+    fn long(tag: u16, val: u32) -> Self {
+        let mut value = [0; 8];
+        value[..4].copy_from_slice(&val.to_le_bytes());
+        Self {
+            tag,
+            typ: TYPE_LONG,
+            count: 1,
+            value,
+        }
+    }
+    fn long8(tag: u16, val: u64) -> Self {
+        Self {
+            tag,
+            typ: TYPE_LONG8,
+            count: 1,
+            value: val.to_le_bytes(),
+        }
+    }
+    fn write<W: Write>(&self, f: &mut W) -> std::io::Result<()> {
+        f.write_u16::<LittleEndian>(self.tag)?;
+        f.write_u16::<LittleEndian>(self.typ)?;
+        f.write_u64::<LittleEndian>(self.count)?;
+        f.write_all(&self.value)
+    }
+}
+
+/// Write rows of `frame` without any padding from the stride.
+fn write_rows<W: Write, FMT: PixelFormat>(
+    f: &mut W,
+    frame: &dyn ImageStride<FMT>,
+    row_bytes: usize,
+) -> std::io::Result<()> {
+    for row in frame
+        .image_data()
+        .chunks(frame.stride())
+        .take(frame.height() as usize)
+    {
+        f.write_all(&row[..row_bytes])?;
+    }
+    Ok(())
+}
+
+/// Like [write_rows] but ensure little endian byte order of 32-bit floats.
+fn write_rows_f32<W: Write, FMT: PixelFormat>(
+    f: &mut W,
+    frame: &dyn ImageStride<FMT>,
+    row_bytes: usize,
+) -> std::io::Result<()> {
+    for row in frame
+        .image_data()
+        .chunks(frame.stride())
+        .take(frame.height() as usize)
+    {
+        for val in row[..row_bytes].chunks_exact(4) {
+            f.write_f32::<LittleEndian>(f32::from_ne_bytes(val.try_into().unwrap()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoder(buf: &[u8]) -> tiff::decoder::Decoder<std::io::Cursor<&[u8]>> {
+        tiff::decoder::Decoder::new(std::io::Cursor::new(buf)).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip_mono8() {
+        let (w, h, stride) = (6, 4, 8);
+        let t0 = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut metadata = H264Metadata::new("test-app", t0.into());
+        metadata.camera_name = Some("cam<1>".into());
+
+        let mut writer = OmeTiffWriter::new(std::io::Cursor::new(Vec::new()), Some(metadata))
+            .unwrap();
+        for i in 0..3u8 {
+            let buf: Vec<u8> = (0..(stride * h as usize) as u8).map(|x| x.wrapping_add(i)).collect();
+            let frame = DynamicFrame::from_buf(w, h, stride, buf, PixFmt::Mono8).unwrap();
+            let ts = t0 + chrono::Duration::milliseconds(10 * i as i64);
+            writer.write_dynamic(&frame, ts).unwrap();
+        }
+        assert_eq!(writer.frame_count(), 3);
+        let buf = writer.finish().unwrap().into_inner();
+
+        let mut decoder = decoder(&buf);
+        let description = decoder
+            .get_tag_ascii_string(tiff::tags::Tag::ImageDescription)
+            .unwrap();
+        assert!(description.contains("SizeT=\"3\""));
+        assert!(description.contains("Type=\"uint8\""));
+        assert!(description.contains("DeltaT=\"0.020000000\""));
+        assert!(description.contains("Model=\"cam&lt;1&gt;\""));
+
+        for i in 0..3u8 {
+            assert_eq!(decoder.dimensions().unwrap(), (w, h));
+            assert_eq!(decoder.colortype().unwrap(), tiff::ColorType::Gray(8));
+            let expected: Vec<u8> = (0..h as usize)
+                .flat_map(|row| (0..w as usize).map(move |col| (row * stride + col) as u8))
+                .map(|x| x.wrapping_add(i))
+                .collect();
+            match decoder.read_image().unwrap() {
+                tiff::decoder::DecodingResult::U8(actual) => assert_eq!(actual, expected),
+                _ => panic!("unexpected decoding result"),
+            }
+            assert_eq!(decoder.more_images(), i < 2);
+            if decoder.more_images() {
+                decoder.next_image().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_gray16() {
+        let (w, h) = (5, 3);
+        let t0 = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let data: Vec<u16> = (0..(w * h) as u16).map(|x| x * 273).collect();
+
+        let mut writer = OmeTiffWriter::new(std::io::Cursor::new(Vec::new()), None).unwrap();
+        writer.write_gray16(w, h, &data, 12, t0).unwrap();
+        assert!(matches!(
+            writer.write_gray16(w + 1, h, &vec![0; ((w + 1) * h) as usize], 12, t0),
+            Err(Error::FormatOrSizeChanged)
+        ));
+        let buf = writer.finish().unwrap().into_inner();
+
+        let mut decoder = decoder(&buf);
+        let description = decoder
+            .get_tag_ascii_string(tiff::tags::Tag::ImageDescription)
+            .unwrap();
+        assert!(description.contains("Type=\"uint16\" SignificantBits=\"12\""));
+        assert_eq!(decoder.colortype().unwrap(), tiff::ColorType::Gray(16));
+        match decoder.read_image().unwrap() {
+            tiff::decoder::DecodingResult::U16(actual) => assert_eq!(actual, data),
+            _ => panic!("unexpected decoding result"),
+        }
+        assert!(!decoder.more_images());
+    }
+}
@@ -0,0 +1,141 @@
+//! Build the OME-XML document stored in the `ImageDescription` of the first
+//! page of an OME-TIFF file.
+//!
+//! See https://ome-model.readthedocs.io/en/stable/ome-tiff/specification.html
+
+use std::fmt::Write;
+
+use strand_cam_remote_control::H264Metadata;
+
+use crate::StackInfo;
+
+const OME_NS: &str = "http://www.openmicroscopy.org/Schemas/OME/2016-06";
+
+/// Namespace of the key-value annotation with our camera metadata.
+pub const STRAND_METADATA_NAMESPACE: &str = "https://strawlab.org/ome-tiff-metadata/v1/";
+
+pub(crate) struct OmeXmlInfo<'a> {
+    pub(crate) info: &'a StackInfo,
+    pub(crate) metadata: Option<&'a H264Metadata>,
+    pub(crate) frame0_time: chrono::DateTime<chrono::Utc>,
+    /// Time of each frame, in seconds, relative to `frame0_time`.
+    pub(crate) delta_t: &'a [f64],
+}
+
+pub(crate) fn build(x: &OmeXmlInfo) -> String {
+    // Writing to a String cannot fail, so the `fmt::Result` values are
+    // ignored below.
+    let mut s = String::new();
+    let info = x.info;
+    let n_frames = x.delta_t.len();
+    let creator = x
+        .metadata
+        .map(|m| m.writing_app.as_str())
+        .unwrap_or(env!("CARGO_PKG_NAME"));
+    let camera_name = x.metadata.and_then(|m| m.camera_name.as_deref());
+
+    s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        s,
+        "<OME xmlns=\"{OME_NS}\" \
+        xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+        xsi:schemaLocation=\"{OME_NS} {OME_NS}/ome.xsd\" \
+        Creator=\"{}\">",
+        escape(creator)
+    );
+
+    if let Some(camera_name) = camera_name {
+        let _ = writeln!(
+            s,
+            "<Instrument ID=\"Instrument:0\"><Detector ID=\"Detector:0\" Model=\"{}\"/></Instrument>",
+            escape(camera_name)
+        );
+    }
+
+    let name = camera_name.unwrap_or("image");
+    let _ = writeln!(s, "<Image ID=\"Image:0\" Name=\"{}\">", escape(name));
+    let _ = writeln!(
+        s,
+        "<AcquisitionDate>{}</AcquisitionDate>",
+        x.frame0_time
+            .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+    );
+    if camera_name.is_some() {
+        s.push_str("<InstrumentRef ID=\"Instrument:0\"/>\n");
+    }
+
+    let significant_bits = info
+        .significant_bits
+        .map(|b| format!(" SignificantBits=\"{b}\""))
+        .unwrap_or_default();
+    let _ = writeln!(
+        s,
+        "<Pixels ID=\"Pixels:0\" DimensionOrder=\"XYCZT\" Type=\"{}\"{significant_bits} \
+        SizeX=\"{}\" SizeY=\"{}\" SizeC=\"{}\" SizeZ=\"1\" SizeT=\"{n_frames}\" \
+        Interleaved=\"true\" BigEndian=\"false\">",
+        info.kind.ome_type(),
+        info.width,
+        info.height,
+        info.kind.samples_per_pixel(),
+    );
+    let detector_settings = if camera_name.is_some() {
+        "<DetectorSettings ID=\"Detector:0\"/>"
+    } else {
+        ""
+    };
+    let _ = writeln!(
+        s,
+        "<Channel ID=\"Channel:0:0\" SamplesPerPixel=\"{}\">{detector_settings}</Channel>",
+        info.kind.samples_per_pixel(),
+    );
+    let _ = writeln!(s, "<TiffData IFD=\"0\" PlaneCount=\"{n_frames}\"/>");
+    for (the_t, delta_t) in x.delta_t.iter().enumerate() {
+        let _ = writeln!(
+            s,
+            "<Plane TheC=\"0\" TheT=\"{the_t}\" TheZ=\"0\" DeltaT=\"{delta_t:.9}\" DeltaTUnit=\"s\"/>"
+        );
+    }
+    s.push_str("</Pixels>\n");
+
+    if x.metadata.is_some() {
+        s.push_str("<AnnotationRef ID=\"Annotation:0\"/>\n");
+    }
+    s.push_str("</Image>\n");
+
+    if let Some(metadata) = x.metadata {
+        let _ = writeln!(
+            s,
+            "<StructuredAnnotations><MapAnnotation ID=\"Annotation:0\" Namespace=\"{STRAND_METADATA_NAMESPACE}\"><Value>"
+        );
+        let mut kv = |k: &str, v: &str| {
+            let _ = writeln!(s, "<M K=\"{k}\">{}</M>", escape(v));
+        };
+        kv("writing_app", &metadata.writing_app);
+        kv("creation_time", &metadata.creation_time.to_rfc3339());
+        if let Some(camera_name) = &metadata.camera_name {
+            kv("camera_name", camera_name);
+        }
+        if let Some(gamma) = &metadata.gamma {
+            kv("gamma", &format!("{gamma}"));
+        }
+        s.push_str("</Value></MapAnnotation></StructuredAnnotations>\n");
+    }
+
+    s.push_str("</OME>\n");
+    s
+}
+
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
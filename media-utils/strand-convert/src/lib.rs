@@ -1,6 +1,7 @@
 // Copyright 2022-2023 Andrew D. Straw.
 //! Convert MKV videos saved by Strand Cam and Tiff Images saved by Micromanager
-//! from Photometrics cameras into MP4 videos of the format saved by Strand Cam.
+//! from Photometrics cameras into MP4 videos of the format saved by Strand Cam
+//! or into lossless OME-TIFF files.
 use std::{
     path::{Path, PathBuf},
     time::Duration,
@@ -21,7 +22,8 @@ use tiff_decoder::HdrConfig;
 const N_FRAMES_TO_COMPUTE_FPS: usize = 100;
 
 /// This program converts an input frame source into an output MP4 file (or a
-/// PNG sequence if --export-pngs option is used).
+/// PNG sequence if --export-pngs option is used or an OME-TIFF file if
+/// --export-ome-tiff is used).
 ///
 /// It assumes that the input has a fixed framerate and encodes this into the
 /// output file. Skipped frames are filled to maintain original timing. The
@@ -30,8 +32,8 @@ const N_FRAMES_TO_COMPUTE_FPS: usize = 100;
 /// The --skip and --take options can adjust which frames go into the output
 /// movie.
 ///
-/// Metadata from Strand Camera is preserved when saving to MP4, but lost when
-/// saving to a PNG sequence.
+/// Metadata from Strand Camera is preserved when saving to MP4 or OME-TIFF, but
+/// lost when saving to a PNG sequence.
 ///
 /// Large deviations of the data from the nominal framerate result in an error.
 #[derive(Parser, Debug)]
@@ -44,8 +46,8 @@ pub struct Cli {
     #[arg(short, long)]
     input: String,
 
-    /// Output filename when the output is an mp4 file or output directory when the output
-    /// is a image sequence of PNG files.
+    /// Output filename when the output is an mp4 or OME-TIFF file or output
+    /// directory when the output is a image sequence of PNG files.
     #[arg(short, long)]
     output: Option<PathBuf>,
 
@@ -55,6 +57,14 @@ pub struct Cli {
     #[arg(long)]
     export_pngs: bool,
 
+    /// Export frames as a lossless OME-TIFF file (instead of mp4).
+    ///
+    /// Source frames are saved with their original timestamps and without
+    /// filling skipped frames. High bit depth TIFF input is saved at full bit
+    /// depth when `--hdr-config preserve` is used.
+    #[arg(long)]
+    export_ome_tiff: bool,
+
     /// Set the H264 encoder
     ///
     /// (This is ignored when --export-pngs is set.)
@@ -166,6 +176,7 @@ impl ImageSequenceWriter {
 enum FrameWriter<'a, T: std::io::Write + std::io::Seek> {
     Mp4(mp4_writer::Mp4Writer<'a, T>),
     Image(ImageSequenceWriter),
+    OmeTiff(ome_tiff_writer::OmeTiffWriter<T>),
 }
 
 impl<'a, T: std::io::Write + std::io::Seek> FrameWriter<'a, T> {
@@ -177,6 +188,7 @@ impl<'a, T: std::io::Write + std::io::Seek> FrameWriter<'a, T> {
         match self {
             Self::Mp4(x) => x.write_dynamic(frame, timestamp)?,
             Self::Image(x) => x.write_dynamic(frame)?,
+            Self::OmeTiff(x) => x.write_dynamic(frame, timestamp)?,
         }
         Ok(())
     }
@@ -198,7 +210,7 @@ impl<'a, T: std::io::Write + std::io::Seek> FrameWriter<'a, T> {
                 frame0_time,
                 insert_precision_timestamp,
             )?,
-            Self::Image(_) => {
+            Self::Image(_) | Self::OmeTiff(_) => {
                 anyhow::bail!("cannot decode individual h264 frame to image");
             }
         }
@@ -208,6 +220,9 @@ impl<'a, T: std::io::Write + std::io::Seek> FrameWriter<'a, T> {
         match self {
             Self::Mp4(x) => x.finish()?,
             Self::Image(_) => {}
+            Self::OmeTiff(x) => {
+                x.finish()?;
+            }
         }
         Ok(())
     }
@@ -393,8 +408,11 @@ pub fn main() -> Result<()> {
 }
 
 pub fn run_cli(cli: Cli) -> Result<()> {
-    if cli.encoder.is_some() && cli.export_pngs {
-        anyhow::bail!("Cannot specify both mp4 encoder and export image sequence.");
+    if cli.encoder.is_some() && (cli.export_pngs || cli.export_ome_tiff) {
+        anyhow::bail!("Cannot specify both mp4 encoder and export images.");
+    }
+    if cli.export_pngs && cli.export_ome_tiff {
+        anyhow::bail!("Cannot specify both export image sequence and export OME-TIFF.");
     }
 
     #[allow(unused_assignments)]
@@ -442,7 +460,7 @@ pub fn run_cli(cli: Cli) -> Result<()> {
             }
         }
 
-        let do_decode_h264 = cli.export_pngs || cli.skip.is_some();
+        let do_decode_h264 = cli.export_pngs || cli.export_ome_tiff || cli.skip.is_some();
         match ext {
             Some("mkv") => {
                 let mkv_video = frame_source::FrameSourceBuilder::new(&input_path)
@@ -536,7 +554,7 @@ pub fn run_cli(cli: Cli) -> Result<()> {
         h264_metadata.gamma = Some(*gamma);
     }
 
-    let ignore_timing = cli.export_pngs || cli.ignore_timing;
+    let ignore_timing = cli.export_pngs || cli.export_ome_tiff || cli.ignore_timing;
 
     let timing_info = if !ignore_timing {
         let desired_interval = if let Some(frame_interval_msec) = cli.frame_interval_msec {
@@ -607,13 +625,24 @@ pub fn run_cli(cli: Cli) -> Result<()> {
 
     let output_fname = if let Some(cli_output) = cli.output {
         cli_output
+    } else if cli.export_ome_tiff {
+        append_extension(output_basename.as_path(), ".ome.tif")
     } else if !cli.export_pngs {
         append_extension(output_basename.as_path(), ".mp4")
     } else {
         output_basename.as_path().into()
     };
 
-    if !cli.export_pngs && output_fname.as_path().extension() != Some(std::ffi::OsStr::new("mp4")) {
+    let output_ext = output_fname
+        .as_path()
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_lowercase());
+    if cli.export_ome_tiff {
+        if output_ext != Some("tif".into()) && output_ext != Some("tiff".into()) {
+            anyhow::bail!("Will not continue. Output extension not .tif or .tiff");
+        }
+    } else if !cli.export_pngs && output_ext != Some("mp4".into()) {
         anyhow::bail!("Will not continue. Output extension not .mp4");
     }
 
@@ -646,7 +675,7 @@ pub fn run_cli(cli: Cli) -> Result<()> {
 
     h264_metadata.creation_time = frame0_time;
 
-    let encoder = if cli.export_pngs {
+    let encoder = if cli.export_pngs || cli.export_ome_tiff {
         Encoder::LessAvc // this is a dummy value.
     } else {
         cli.encoder.clone().unwrap_or(default_encoder)
@@ -747,6 +776,13 @@ pub fn run_cli(cli: Cli) -> Result<()> {
             dirname: output_fname.clone(),
             index: 0,
         })
+    } else if cli.export_ome_tiff {
+        let out_fd = std::fs::File::create(&output_fname)
+            .with_context(|| format!("writing to {}", output_fname.display()))?;
+        FrameWriter::OmeTiff(ome_tiff_writer::OmeTiffWriter::new(
+            out_fd,
+            Some(h264_metadata.clone()),
+        )?)
     } else {
         tracing::debug!(
             "Saving metadata: {}",
@@ -887,15 +923,43 @@ pub fn run_cli(cli: Cli) -> Result<()> {
         let frame_timestamp_tz = frame0_time + chrono::Duration::from_std(save_elapsed)?;
         let frame_timestamp_utc = frame_timestamp_tz.with_timezone(&chrono::Utc);
         match &save_frame {
-            ImageData::Tiff(tiff_image) => {
-                let frame = tiff_decoder::read_tiff_image(
-                    tiff_image,
-                    &cli.hdr_config,
-                    hdr_lum_range,
-                    &mut val_histogram,
-                )?;
-                output_writer.write_dynamic(&frame.borrow(), frame_timestamp_utc)?;
-            }
+            ImageData::Tiff(tiff_image) => match &mut output_writer {
+                FrameWriter::OmeTiff(wtr)
+                    if cli.hdr_config == HdrConfig::Preserve
+                        && tiff_image.metadata.bit_depth > 8 =>
+                {
+                    let (w, h, vals) =
+                        tiff_decoder::read_tiff_image_gray16(tiff_image, &mut val_histogram)?;
+                    wtr.write_gray16(
+                        w,
+                        h,
+                        &vals,
+                        tiff_image.metadata.bit_depth,
+                        frame_timestamp_utc,
+                    )?;
+                }
+                FrameWriter::Mp4(_)
+                    if cli.hdr_config == HdrConfig::Preserve
+                        && tiff_image.metadata.bit_depth > 8 =>
+                {
+                    anyhow::bail!(
+                        "{}-bit input cannot be saved to MP4: H.264 encoding supports only 8-bit \
+                        input. (Hint: use --export-ome-tiff to keep the full bit depth or \
+                        --hdr-config {} to reduce to 8 bits.)",
+                        tiff_image.metadata.bit_depth,
+                        HdrConfig::Downscale_To_8Bit
+                    );
+                }
+                _ => {
+                    let frame = tiff_decoder::read_tiff_image(
+                        tiff_image,
+                        &cli.hdr_config,
+                        hdr_lum_range,
+                        &mut val_histogram,
+                    )?;
+                    output_writer.write_dynamic(&frame.borrow(), frame_timestamp_utc)?;
+                }
+            },
             ImageData::Decoded(frame) => {
                 output_writer.write_dynamic(&frame.borrow(), frame_timestamp_utc)?;
             }
@@ -966,9 +1030,14 @@ pub fn run_cli(cli: Cli) -> Result<()> {
         let out_bytes = std::fs::metadata(&output_fname)?.len();
         let out_bytes_per_second = out_bytes as f64 / next_dest_pts.as_secs_f64();
         let fps = out_fno as f64 / prev_dest_pts.unwrap_duration().as_secs_f64();
+        let codec = if cli.export_ome_tiff {
+            "OME-TIFF (uncompressed)".to_string()
+        } else {
+            format!("H264, encoder: {encoder}")
+        };
 
         tracing::info!(
-            "Saved movie statistics: {out_fno} frames, codec: H264, encoder: {encoder}, size: {}, duration: {}, fps: {:.1}, byterate: {}, filename: {}",
+            "Saved movie statistics: {out_fno} frames, codec: {codec}, size: {}, duration: {}, fps: {:.1}, byterate: {}, filename: {}",
             HumanBytes(out_bytes),
             HumanDuration(prev_dest_pts.unwrap_duration()),
             fps,
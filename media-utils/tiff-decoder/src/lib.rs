@@ -158,3 +158,30 @@ pub fn read_tiff_image(
     )
     .unwrap())
 }
+
+/// Read a 16-bit monochrome TIFF image without conversion.
+///
+/// Returns the width, height and the pixel values in row-major order.
+pub fn read_tiff_image_gray16(
+    tiff_image: &TiffImage,
+    histogram: &mut ValHistogram,
+) -> Result<(u32, u32, Vec<u16>)> {
+    use tiff::decoder::DecodingResult;
+
+    let mut decoder = tiff::decoder::Decoder::new(Cursor::new(&tiff_image.buf))?;
+    let buf = decoder.read_image()?;
+    let color = decoder.colortype()?;
+    let (width, height) = decoder.dimensions()?;
+
+    let vals = match (color, buf) {
+        (tiff::ColorType::Gray(16), DecodingResult::U16(vals)) => vals,
+        _ => {
+            anyhow::bail!("expected 16-bit monochrome tiff image");
+        }
+    };
+    if vals.len() != width as usize * height as usize {
+        anyhow::bail!("actual image size different than expected");
+    }
+    histogram.update(&vals);
+    Ok((width, height, vals))
+}
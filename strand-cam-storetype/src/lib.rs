@@ -41,7 +41,9 @@ use strand_cam_bui_types::RecordingPath;
 use strand_http_video_streaming_types::{CircleParams, Shape};
 
 use flydra_feature_detector_types::ImPtDetectCfg;
-use strand_cam_remote_control::{BitrateSelection, CodecSelection, RecordingFrameRate, TagFamily};
+use strand_cam_remote_control::{
+    BitrateSelection, CodecSelection, RecordingFrameRate, RecordingStats, TagFamily,
+};
 
 /// A numeric value with associated metadata for user interface controls.
 ///
@@ -162,6 +164,8 @@ pub struct StoreType {
     pub mp4_codec: CodecSelection,
     /// CUDA device number (only used if using nvidia encoder)
     pub mp4_cuda_device: String,
+    /// Statistics of the ongoing MP4 recording (None if not recording).
+    pub mp4_recording_stats: Option<RecordingStats>,
     /// Automatic gain control mode.
     pub gain_auto: Option<strand_cam_types::AutoMode>,
     /// Camera gain settings and range.
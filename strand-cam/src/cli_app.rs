@@ -55,6 +55,16 @@ struct DerivedArgs {
     #[arg(long)]
     v4l2loopback: Option<PathBuf>,
 
+    /// If set, serve the annotated frames as MJPEG over HTTP at this address
+    /// (e.g. `127.0.0.1:8081`). The stream is at `http://<addr>/mjpeg`.
+    #[arg(long)]
+    mjpeg_addr: Option<std::net::SocketAddr>,
+
+    /// If set, serve raw frames at this address. This is either a TCP address
+    /// (e.g. `127.0.0.1:8082`) or `unix:<path>` for a Unix socket.
+    #[arg(long)]
+    raw_frame_addr: Option<crate::frame_server::RawFrameAddr>,
+
     /// If set, .mp4 videos and log files are saved to this directory.
     #[arg(long)]
     data_dir: Option<PathBuf>,
@@ -375,6 +385,8 @@ fn parse_args(app_name: &str) -> Result<StrandCamArgs> {
         apriltag_csv_filename_template,
         #[cfg(target_os = "linux")]
         v4l2loopback: derived_matches.v4l2loopback,
+        mjpeg_addr: derived_matches.mjpeg_addr,
+        raw_frame_addr: derived_matches.raw_frame_addr,
         data_dir: derived_matches.data_dir,
         ..Default::default()
     })
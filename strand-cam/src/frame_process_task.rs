@@ -55,6 +55,7 @@ pub(crate) async fn frame_process_task<'a>(
     #[cfg(feature = "flydra_feat_detect")] im_pt_detect_cfg: ImPtDetectCfg,
     #[cfg(feature = "flydra_feat_detect")] csv_save_pathbuf: std::path::PathBuf,
     firehose_tx: tokio::sync::mpsc::Sender<AnnotatedFrame>,
+    mut frame_broadcaster: Option<crate::frame_server::FrameBroadcaster>,
     #[cfg(feature = "flydratrax")] led_box_tx_std: tokio::sync::mpsc::Sender<crate::ToLedBoxDevice>,
     #[cfg(feature = "flydratrax")]
     http_camserver_info: strand_bui_backend_session_types::BuiServerAddrInfo,
@@ -511,13 +512,21 @@ pub(crate) async fn frame_process_task<'a>(
                     (shared.format_str_mp4.clone(), mp4_recording_config)
                 };
 
-                let filename = creation_time.format(format_str_mp4.as_str()).to_string();
+                // The filename template ends with ".mp4", but other recording
+                // methods may save another type of file.
+                let extension = mp4_recording_config.final_cfg.file_extension();
+                let filename = std::path::Path::new(
+                    &creation_time.format(format_str_mp4.as_str()).to_string(),
+                )
+                .with_extension(extension)
+                .display()
+                .to_string();
                 let is_recording_mp4 = Some(RecordingPath::new(filename.clone()));
 
                 let mp4_path = {
                     let local = chrono::Local::now();
                     let formatted_filename = local.format(&format_str_mp4).to_string();
-                    data_dir.join(formatted_filename)
+                    data_dir.join(formatted_filename).with_extension(extension)
                 };
 
                 let mut raw = bg_movie_writer::BgMovieWriter::new(
@@ -567,6 +576,7 @@ pub(crate) async fn frame_process_task<'a>(
                     let mut tracker = store.write().unwrap();
                     tracker.modify(|tracker| {
                         tracker.is_recording_mp4 = is_recording_mp4;
+                        tracker.mp4_recording_stats = Some(Default::default());
                     });
                 }
             }
@@ -640,6 +650,7 @@ pub(crate) async fn frame_process_task<'a>(
                         let mut tracker = store.write().unwrap();
                         tracker.modify(|tracker| {
                             tracker.measured_fps = new_fps as f32;
+                            tracker.mp4_recording_stats = my_mp4_writer.as_ref().map(|w| w.stats());
                         });
                     }
 
@@ -734,16 +745,17 @@ pub(crate) async fn frame_process_task<'a>(
                                 &frame_ref,
                                 x,
                                 {
-                                    let rgb: Box<
-                                        dyn formats::ImageStride<formats::pixel_format::RGB8>,
+                                    let mono: Box<
+                                        dyn formats::ImageStride<formats::pixel_format::Mono8>,
                                     > = Box::new(convert_image::convert_ref::<
                                         _,
-                                        formats::pixel_format::RGB8,
+                                        formats::pixel_format::Mono8,
                                     >(&x)?);
-                                    let corners = opencv_calibrate::find_chessboard_corners(
-                                        rgb.image_data(),
-                                        rgb.width(),
-                                        rgb.height(),
+                                    let corners = camcal::find_chessboard_corners(
+                                        mono.image_data(),
+                                        mono.width(),
+                                        mono.height(),
+                                        mono.stride(),
                                         checkerboard_data.width as usize,
                                         checkerboard_data.height as usize,
                                     )?;
@@ -1330,17 +1342,21 @@ pub(crate) async fn frame_process_task<'a>(
                 #[cfg(not(feature = "flydratrax"))]
                 let annotations = vec![];
 
+                let annotated_frame = AnnotatedFrame {
+                    frame: frame.image,
+                    found_points,
+                    valid_display,
+                    annotations,
+                };
+
+                if let Some(frame_broadcaster) = frame_broadcaster.as_mut() {
+                    frame_broadcaster.send(annotated_frame.clone());
+                }
+
                 if firehose_tx.capacity() == 0 {
                     trace!("cannot transmit frame for viewing: channel full");
                 } else {
-                    let result = firehose_tx
-                        .send(AnnotatedFrame {
-                            frame: frame.image,
-                            found_points,
-                            valid_display,
-                            annotations,
-                        })
-                        .await;
+                    let result = firehose_tx.send(annotated_frame).await;
                     match result {
                         Ok(()) => {}
                         Err(e) => {
@@ -1390,6 +1406,7 @@ pub(crate) async fn frame_process_task<'a>(
                                     per_cam_data,
                                     print_stats: false,
                                     save_performance_histograms: true,
+                                    resume: false,
                                 };
                                 if let Some(braidz_write_tx) = braidz_write_tx_weak.upgrade() {
                                     // `braidz_write_tx` will be dropped after this scope.
@@ -1467,6 +1484,7 @@ pub(crate) async fn frame_process_task<'a>(
                     let mut tracker = store.write().unwrap();
                     tracker.modify(|tracker| {
                         tracker.is_recording_mp4 = None;
+                        tracker.mp4_recording_stats = None;
                     });
                 }
             }
@@ -1690,7 +1708,7 @@ fn calc_braid_timestamp(
             }
             Some(ptp_stamp.try_into().unwrap())
         }
-        Some(TriggerType::DeviceTimestamp) => {
+        Some(TriggerType::DeviceTimestamp(_)) => {
             let cm = device_clock_model.as_ref().unwrap();
             let this_local_and_cam_time0 = local_and_cam_time0.as_ref().unwrap();
             let (local_time0, cam_time0) = this_local_and_cam_time0;
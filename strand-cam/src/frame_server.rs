@@ -0,0 +1,489 @@
+//! Serve the annotated frame stream to external consumers.
+//!
+//! Two transports are available:
+//!
+//! - Multipart MJPEG over HTTP at `http://<addr>/mjpeg`. This can be viewed in
+//!   a browser or added as a media source in OBS. Detected points are drawn as
+//!   green circles.
+//! - Raw, uncompressed frames over a TCP or Unix socket.
+//!
+//! Each client may limit the rate at which it receives frames. HTTP clients
+//! use the `max_fps` query parameter (e.g. `/mjpeg?max_fps=10`). Socket
+//! clients send a single line of JSON such as `{"max_fps":10}` (or an empty
+//! line for every frame) after connecting. Clients which cannot keep up skip
+//! frames rather than slowing down the camera.
+//!
+//! No authentication is performed, so these servers should only be bound to
+//! trusted interfaces.
+//!
+//! ## Raw frame format
+//!
+//! Each frame is sent as a header followed by the image data. All integers
+//! are little endian.
+//!
+//! | field        | type                         |
+//! |--------------|------------------------------|
+//! | magic        | `b"SCRF"`                    |
+//! | version      | `u32` (currently 1)          |
+//! | frame number | `u64`                        |
+//! | sent time    | `i64` (microseconds since the UNIX epoch) |
+//! | width        | `u32`                        |
+//! | height       | `u32`                        |
+//! | stride       | `u32`                        |
+//! | pixel format | `u8` length + UTF-8 name (e.g. `Mono8`) |
+//! | points       | `u32` count + count × (`f32` x, `f32` y) |
+//! | image data   | `u64` length + bytes         |
+
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use eyre::Result;
+use machine_vision_formats::{owned::OImage, pixel_format::RGB8, ImageData, ImageMutData, Stride};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::{debug, error, info};
+
+use strand_dynamic_frame::match_all_dynamic_fmts;
+use strand_http_video_streaming::AnnotatedFrame;
+
+const RAW_FRAME_MAGIC: &[u8; 4] = b"SCRF";
+const RAW_FRAME_VERSION: u32 = 1;
+const MJPEG_BOUNDARY: &str = "strandcamframe";
+const JPEG_QUALITY: u8 = 80;
+const POINT_RADIUS: f32 = 10.0;
+
+type FrameWatchRx = tokio::sync::watch::Receiver<Option<(u64, Arc<AnnotatedFrame>)>>;
+
+/// Address of the raw frame server.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RawFrameAddr {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl std::str::FromStr for RawFrameAddr {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(Self::Unix(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(format!("Unix sockets are not supported: {path}"));
+        }
+        let addr = s.strip_prefix("tcp:").unwrap_or(s);
+        addr.parse()
+            .map(Self::Tcp)
+            .map_err(|e| format!("invalid address \"{s}\": {e}"))
+    }
+}
+
+/// Options sent by each client.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+struct ClientOptions {
+    /// Maximum rate at which frames are sent. All frames are sent if `None`.
+    max_fps: Option<f64>,
+}
+
+/// Distributes the most recent frame to all connected clients.
+pub(crate) struct FrameBroadcaster {
+    tx: tokio::sync::watch::Sender<Option<(u64, Arc<AnnotatedFrame>)>>,
+    fno: u64,
+}
+
+impl FrameBroadcaster {
+    pub(crate) fn new() -> Self {
+        let (tx, _rx) = tokio::sync::watch::channel(None);
+        Self { tx, fno: 0 }
+    }
+
+    pub(crate) fn send(&mut self, frame: AnnotatedFrame) {
+        self.fno += 1;
+        self.tx.send_replace(Some((self.fno, Arc::new(frame))));
+    }
+
+    fn subscribe(&self) -> FrameWatchRx {
+        self.tx.subscribe()
+    }
+
+    /// Start the servers given in the command-line arguments.
+    pub(crate) fn spawn_servers(
+        &self,
+        mjpeg_addr: Option<SocketAddr>,
+        raw_frame_addr: Option<RawFrameAddr>,
+    ) {
+        if let Some(addr) = mjpeg_addr {
+            let rx = self.subscribe();
+            tokio::spawn(async move {
+                if let Err(e) = serve_mjpeg(addr, rx).await {
+                    error!("MJPEG server at {addr}: {e}");
+                }
+            });
+        }
+        if let Some(addr) = raw_frame_addr {
+            let rx = self.subscribe();
+            tokio::spawn(async move {
+                if let Err(e) = serve_raw(addr.clone(), rx).await {
+                    error!("raw frame server at {addr:?}: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// Decides which frames are sent to a client limiting its frame rate.
+#[derive(Debug)]
+struct RateLimiter {
+    min_interval: Option<Duration>,
+    next_due: Option<Instant>,
+}
+
+impl RateLimiter {
+    fn new(max_fps: Option<f64>) -> Self {
+        let min_interval = max_fps
+            .filter(|fps| *fps > 0.0 && fps.is_finite())
+            .map(|fps| Duration::from_secs_f64(1.0 / fps));
+        Self {
+            min_interval,
+            next_due: None,
+        }
+    }
+
+    /// Returns `true` if a frame arriving at `now` should be sent.
+    fn ready(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.min_interval else {
+            return true;
+        };
+        match self.next_due {
+            Some(due) if now < due => false,
+            due => {
+                // Schedule from the previous due time to avoid drifting below
+                // the requested rate, unless we fell behind.
+                let base = match due {
+                    Some(due) if now < due + interval => due,
+                    _ => now,
+                };
+                self.next_due = Some(base + interval);
+                true
+            }
+        }
+    }
+}
+
+/// Per-client frame source.
+struct ClientFrames {
+    rx: FrameWatchRx,
+    limiter: RateLimiter,
+}
+
+impl ClientFrames {
+    fn new(rx: FrameWatchRx, opts: ClientOptions) -> Self {
+        Self {
+            rx,
+            limiter: RateLimiter::new(opts.max_fps),
+        }
+    }
+
+    /// Wait for the next frame to send. Returns `None` when the camera stops.
+    async fn next(&mut self) -> Option<(u64, Arc<AnnotatedFrame>)> {
+        loop {
+            self.rx.changed().await.ok()?;
+            let Some(latest) = self.rx.borrow_and_update().clone() else {
+                continue;
+            };
+            if self.limiter.ready(Instant::now()) {
+                return Some(latest);
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MJPEG over HTTP
+
+async fn serve_mjpeg(addr: SocketAddr, rx: FrameWatchRx) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("MJPEG server at http://{}/mjpeg", listener.local_addr()?);
+    let router = axum::Router::new()
+        .route("/mjpeg", axum::routing::get(mjpeg_handler))
+        .with_state(rx);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+async fn mjpeg_handler(
+    axum::extract::State(rx): axum::extract::State<FrameWatchRx>,
+    axum::extract::Query(opts): axum::extract::Query<ClientOptions>,
+) -> impl axum::response::IntoResponse {
+    let client = ClientFrames::new(rx, opts);
+    let stream = futures::stream::unfold(client, |mut client| async move {
+        let (_fno, frame) = client.next().await?;
+        match tokio::task::spawn_blocking(move || encode_jpeg(&frame)).await {
+            Ok(Ok(jpeg)) => Some((Ok::<_, std::convert::Infallible>(mjpeg_part(&jpeg)), client)),
+            Ok(Err(e)) => {
+                error!("encoding JPEG: {e}");
+                None
+            }
+            Err(e) => {
+                error!("JPEG encoding task: {e}");
+                None
+            }
+        }
+    });
+    let content_type = format!("multipart/x-mixed-replace; boundary={MJPEG_BOUNDARY}");
+    (
+        [
+            (http::header::CONTENT_TYPE, content_type),
+            (http::header::CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        axum::body::Body::from_stream(stream),
+    )
+}
+
+fn mjpeg_part(jpeg: &[u8]) -> bytes::Bytes {
+    let mut buf = format!(
+        "--{MJPEG_BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+        jpeg.len()
+    )
+    .into_bytes();
+    buf.extend_from_slice(jpeg);
+    buf.extend_from_slice(b"\r\n");
+    buf.into()
+}
+
+/// Encode the frame as JPEG with the found points drawn.
+fn encode_jpeg(frame: &AnnotatedFrame) -> Result<Vec<u8>> {
+    let mut rgb: OImage<RGB8> = frame
+        .frame
+        .borrow()
+        .copy_to_owned()
+        .into_pixel_format::<RGB8>()?;
+    for pt in frame.found_points.iter() {
+        draw_circle(&mut rgb, pt.x, pt.y, POINT_RADIUS, [0x7F, 0xFF, 0x7F]);
+    }
+    Ok(convert_image::frame_to_encoded_buffer(
+        &rgb,
+        convert_image::EncoderOptions::Jpeg(JPEG_QUALITY),
+    )?)
+}
+
+fn draw_circle(image: &mut OImage<RGB8>, cx: f32, cy: f32, radius: f32, color: [u8; 3]) {
+    let (width, height, stride) = (image.width() as i64, image.height() as i64, image.stride());
+    let data = image.buffer_mut_ref().data;
+    let n_steps = (2.0 * std::f32::consts::PI * radius).ceil().max(8.0) as usize;
+    for i in 0..n_steps {
+        let theta = i as f32 / n_steps as f32 * 2.0 * std::f32::consts::PI;
+        let x = (cx + radius * theta.cos()).round() as i64;
+        let y = (cy + radius * theta.sin()).round() as i64;
+        if x < 0 || y < 0 || x >= width || y >= height {
+            continue;
+        }
+        let start = y as usize * stride + x as usize * 3;
+        data[start..start + 3].copy_from_slice(&color);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Raw frames over a socket
+
+async fn serve_raw(addr: RawFrameAddr, rx: FrameWatchRx) -> Result<()> {
+    match addr {
+        RawFrameAddr::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("raw frame server at {}", listener.local_addr()?);
+            loop {
+                let (stream, peer) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                tokio::spawn(handle_raw_client(stream, rx.clone(), peer.to_string()));
+            }
+        }
+        #[cfg(unix)]
+        RawFrameAddr::Unix(path) => {
+            remove_stale_socket(&path)?;
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            info!("raw frame server at {}", path.display());
+            loop {
+                let (stream, _) = listener.accept().await?;
+                tokio::spawn(handle_raw_client(
+                    stream,
+                    rx.clone(),
+                    path.display().to_string(),
+                ));
+            }
+        }
+    }
+}
+
+/// Remove a socket file left over from a previous run.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            eyre::bail!("{} exists and is not a socket", path.display());
+        }
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+async fn handle_raw_client<S>(stream: S, rx: FrameWatchRx, peer: String)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    debug!("raw frame client connected: {peer}");
+    match raw_client_loop(stream, rx).await {
+        Ok(()) => debug!("raw frame client {peer} done"),
+        Err(e) => debug!("raw frame client {peer} disconnected: {e}"),
+    }
+}
+
+async fn raw_client_loop<S>(stream: S, rx: FrameWatchRx) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+
+    // Read the client's options.
+    let mut line = String::new();
+    tokio::io::BufReader::new(reader)
+        .read_line(&mut line)
+        .await?;
+    let opts: ClientOptions = if line.trim().is_empty() {
+        Default::default()
+    } else {
+        serde_json::from_str(line.trim())?
+    };
+
+    let mut client = ClientFrames::new(rx, opts);
+    while let Some((fno, frame)) = client.next().await {
+        let buf = encode_raw(fno, &frame, chrono::Utc::now())?;
+        writer.write_all(&buf).await?;
+    }
+    Ok(())
+}
+
+fn encode_raw(
+    fno: u64,
+    frame: &AnnotatedFrame,
+    sent: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<u8>> {
+    let image = frame.frame.borrow();
+    let pixfmt = image.pixel_format();
+    let image_data: Vec<u8> = match_all_dynamic_fmts!(
+        &image,
+        x,
+        x.image_data().to_vec(),
+        eyre::eyre!("unsupported pixel format {pixfmt:?}")
+    );
+    let pixfmt_name = format!("{pixfmt:?}");
+
+    let mut buf = Vec::with_capacity(image_data.len() + 64);
+    buf.extend_from_slice(RAW_FRAME_MAGIC);
+    buf.write_u32::<LittleEndian>(RAW_FRAME_VERSION)?;
+    buf.write_u64::<LittleEndian>(fno)?;
+    buf.write_i64::<LittleEndian>(sent.timestamp_micros())?;
+    buf.write_u32::<LittleEndian>(image.width())?;
+    buf.write_u32::<LittleEndian>(image.height())?;
+    buf.write_u32::<LittleEndian>(image.stride().try_into()?)?;
+    buf.write_u8(pixfmt_name.len().try_into()?)?;
+    buf.extend_from_slice(pixfmt_name.as_bytes());
+    buf.write_u32::<LittleEndian>(frame.found_points.len().try_into()?)?;
+    for pt in frame.found_points.iter() {
+        buf.write_f32::<LittleEndian>(pt.x)?;
+        buf.write_f32::<LittleEndian>(pt.y)?;
+    }
+    buf.write_u64::<LittleEndian>(image_data.len().try_into()?)?;
+    buf.extend_from_slice(&image_data);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machine_vision_formats::PixFmt;
+    use strand_dynamic_frame::DynamicFrameOwned;
+    use strand_http_video_streaming::Point;
+
+    #[test]
+    fn test_rate_limiter() {
+        let t0 = Instant::now();
+        // Frames arrive at 100 fps for one second.
+        let times: Vec<_> = (0..100)
+            .map(|i| t0 + Duration::from_millis(i * 10))
+            .collect();
+
+        let mut unlimited = RateLimiter::new(None);
+        assert_eq!(times.iter().filter(|t| unlimited.ready(**t)).count(), 100);
+
+        let mut limited = RateLimiter::new(Some(25.0));
+        assert_eq!(times.iter().filter(|t| limited.ready(**t)).count(), 25);
+
+        // A long pause does not cause a burst of frames afterwards.
+        let mut limited = RateLimiter::new(Some(10.0));
+        assert!(limited.ready(t0));
+        let later = t0 + Duration::from_secs(10);
+        assert!(limited.ready(later));
+        assert!(!limited.ready(later + Duration::from_millis(50)));
+        assert!(limited.ready(later + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_raw_frame_addr() {
+        assert_eq!(
+            "127.0.0.1:1234".parse::<RawFrameAddr>(),
+            Ok(RawFrameAddr::Tcp("127.0.0.1:1234".parse().unwrap()))
+        );
+        assert_eq!(
+            "tcp:127.0.0.1:1234".parse::<RawFrameAddr>(),
+            Ok(RawFrameAddr::Tcp("127.0.0.1:1234".parse().unwrap()))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:/tmp/frames.sock".parse::<RawFrameAddr>(),
+            Ok(RawFrameAddr::Unix("/tmp/frames.sock".into()))
+        );
+        assert!("nonsense".parse::<RawFrameAddr>().is_err());
+    }
+
+    #[test]
+    fn test_encode_raw() {
+        let image = DynamicFrameOwned::from_buf(2, 2, 4, (0..8).collect(), PixFmt::Mono8).unwrap();
+        let frame = AnnotatedFrame {
+            frame: Arc::new(image),
+            found_points: vec![Point {
+                x: 1.0,
+                y: 0.5,
+                theta: None,
+                area: None,
+            }],
+            valid_display: None,
+            annotations: vec![],
+        };
+        let buf = encode_raw(7, &frame, chrono::DateTime::from_timestamp(1, 0).unwrap()).unwrap();
+
+        let mut expected = b"SCRF".to_vec();
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(7u64.to_le_bytes());
+        expected.extend(1_000_000i64.to_le_bytes());
+        expected.extend(2u32.to_le_bytes());
+        expected.extend(2u32.to_le_bytes());
+        expected.extend(4u32.to_le_bytes());
+        expected.push(5);
+        expected.extend(b"Mono8");
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(1.0f32.to_le_bytes());
+        expected.extend(0.5f32.to_le_bytes());
+        expected.extend(8u64.to_le_bytes());
+        expected.extend(0u8..8);
+        assert_eq!(buf, expected);
+    }
+}
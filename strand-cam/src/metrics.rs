@@ -0,0 +1,111 @@
+//! Metrics in the Prometheus text format, served at `/metrics`.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use strand_metrics::{MetricType, MetricsEncoder};
+
+use crate::StrandCamAppState;
+
+/// Counts of frames from the camera.
+#[derive(Clone, Default)]
+pub(crate) struct FrameCounters {
+    received: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FrameCounters {
+    pub(crate) fn frame_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A frame was dropped because frame processing did not keep up.
+    pub(crate) fn frame_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) async fn metrics_handler(
+    axum::extract::State(app_state): axum::extract::State<StrandCamAppState>,
+    session_key: axum_token_auth::SessionKey,
+) -> impl axum::response::IntoResponse {
+    session_key.is_present();
+    let labels = [("camera", app_state.cam_name.as_str())];
+    let mut enc = MetricsEncoder::new();
+
+    let counters = &app_state.frame_counters;
+    enc.family(
+        "strand_cam_frames_total",
+        "Number of frames received from the camera.",
+        MetricType::Counter,
+    );
+    enc.sample(
+        "strand_cam_frames_total",
+        &labels,
+        counters.received.load(Ordering::Relaxed) as f64,
+    );
+    enc.family(
+        "strand_cam_dropped_frames_total",
+        "Number of frames dropped because frame processing did not keep up.",
+        MetricType::Counter,
+    );
+    enc.sample(
+        "strand_cam_dropped_frames_total",
+        &labels,
+        counters.dropped.load(Ordering::Relaxed) as f64,
+    );
+
+    let tx_frame = &app_state.callback_senders.tx_frame;
+    enc.family(
+        "strand_cam_frame_queue_depth",
+        "Number of frames waiting to be processed.",
+        MetricType::Gauge,
+    );
+    enc.sample(
+        "strand_cam_frame_queue_depth",
+        &labels,
+        (tx_frame.max_capacity() - tx_frame.capacity()) as f64,
+    );
+
+    let (measured_fps, mp4_recording_stats) = {
+        let tracker = app_state.shared_store_arc.read().unwrap();
+        let shared = tracker.as_ref();
+        (shared.measured_fps, shared.mp4_recording_stats.clone())
+    };
+    enc.family(
+        "strand_cam_measured_fps",
+        "Measured frame rate of the camera.",
+        MetricType::Gauge,
+    );
+    enc.sample("strand_cam_measured_fps", &labels, measured_fps.into());
+
+    if let Some(stats) = mp4_recording_stats {
+        enc.family(
+            "strand_cam_mp4_write_queue_depth",
+            "Number of frames waiting to be written to the MP4 file.",
+            MetricType::Gauge,
+        );
+        enc.sample(
+            "strand_cam_mp4_write_queue_depth",
+            &labels,
+            stats.frames_queued.saturating_sub(stats.frames_written) as f64,
+        );
+        enc.family(
+            "strand_cam_mp4_dropped_frames_total",
+            "Number of frames not saved to the current MP4 file because the writer did not keep up.",
+            MetricType::Counter,
+        );
+        enc.sample(
+            "strand_cam_mp4_dropped_frames_total",
+            &labels,
+            stats.frames_dropped as f64,
+        );
+    }
+
+    (
+        [(http::header::CONTENT_TYPE, strand_metrics::CONTENT_TYPE)],
+        enc.finish(),
+    )
+}
@@ -43,7 +43,7 @@ use std::fs::File;
 use strand_cam_remote_control::CsvSaveConfig;
 use strand_cam_remote_control::{
     CamArg, CodecSelection, FfmpegRecordingConfig, Mp4Codec, Mp4RecordingConfig, NvidiaH264Options,
-    RecordingFrameRate,
+    RecordingFrameRate, TiffRecordingConfig,
 };
 
 use braid_types::{BuiServerInfo, RawCamName, StartSoftwareFrameRateLimit, TriggerType};
@@ -102,6 +102,8 @@ mod flydratrax_handle_msg;
 
 mod clock_model;
 mod datagram_socket;
+mod frame_server;
+mod metrics;
 mod post_trigger_buffer;
 
 #[cfg(feature = "eframe-gui")]
@@ -331,7 +333,6 @@ struct StrandCamCallbackSenders {
     firehose_callback_tx: tokio::sync::mpsc::Sender<ConnectionKey>,
     cam_args_tx: tokio::sync::mpsc::Sender<CamArg>,
     led_box_tx_std: tokio::sync::mpsc::Sender<ToLedBoxDevice>,
-    #[allow(dead_code)]
     tx_frame: tokio::sync::mpsc::Sender<Msg>,
 }
 
@@ -342,6 +343,7 @@ struct StrandCamAppState {
     callback_senders: StrandCamCallbackSenders,
     tx_new_connection: tokio::sync::mpsc::Sender<event_stream_types::ConnectionEvent>,
     shared_store_arc: Arc<RwLock<ChangeTracker<StoreType>>>,
+    frame_counters: metrics::FrameCounters,
 }
 
 type MyBody = http_body_util::combinators::BoxBody<bytes::Bytes, strand_bui_backend_session::Error>;
@@ -560,6 +562,10 @@ pub struct StrandCamArgs {
     pub write_buffer_size_num_messages: usize,
     #[cfg(target_os = "linux")]
     v4l2loopback: Option<PathBuf>,
+    /// If set, serve the annotated frames as MJPEG over HTTP at this address.
+    mjpeg_addr: Option<SocketAddr>,
+    /// If set, serve raw frames at this address.
+    raw_frame_addr: Option<frame_server::RawFrameAddr>,
     data_dir: Option<PathBuf>,
 }
 
@@ -601,6 +607,8 @@ impl Default for StrandCamArgs {
                 braid_config_data::default_write_buffer_size_num_messages(),
             #[cfg(target_os = "linux")]
             v4l2loopback: None,
+            mjpeg_addr: None,
+            raw_frame_addr: None,
             data_dir: Default::default(),
         }
     }
@@ -1567,6 +1575,15 @@ where
         .unwrap();
     // .map_err(|e| anhow::anyhow!("failed to send frame"))?;
 
+    // Optionally serve frames to external consumers.
+    let frame_broadcaster = if args.mjpeg_addr.is_some() || args.raw_frame_addr.is_some() {
+        let frame_broadcaster = frame_server::FrameBroadcaster::new();
+        frame_broadcaster.spawn_servers(args.mjpeg_addr, args.raw_frame_addr.clone());
+        Some(frame_broadcaster)
+    } else {
+        None
+    };
+
     let image_width = frame.width();
     let image_height = frame.height();
 
@@ -1607,7 +1624,7 @@ where
     let mut cam_time0 = None;
     let mut device_clock_model = None;
 
-    if trigger_type == Some(TriggerType::DeviceTimestamp) {
+    if matches!(trigger_type, Some(TriggerType::DeviceTimestamp(_))) {
         // Attempt to relate camera timestamps to our clock
         tracing::info!("Reading camera timestamps to fit initial clock model.");
 
@@ -1939,6 +1956,7 @@ where
         mp4_codec,
         mp4_max_framerate: Default::default(),
         mp4_cuda_device,
+        mp4_recording_stats: None,
         gain: gain_ranged,
         gain_auto,
         exposure_time: exposure_ranged,
@@ -1997,12 +2015,15 @@ where
     let shared_store_arc = shared_state.clone();
 
     // Create our app state.
+    let frame_counters = metrics::FrameCounters::default();
+
     let app_state = StrandCamAppState {
         cam_name: cam.name().to_string(),
         event_broadcaster: Default::default(),
         callback_senders,
         tx_new_connection,
         shared_store_arc,
+        frame_counters: frame_counters.clone(),
     };
 
     let shared_store_arc = shared_state.clone();
@@ -2065,6 +2086,7 @@ where
         .route("/strand-cam-events", axum::routing::get(events_handler))
         .route("/cam-name", axum::routing::get(cam_name_handler))
         .route("/callback", axum::routing::post(callback_handler))
+        .route("/metrics", axum::routing::get(metrics::metrics_handler))
         .fallback_service(serve_dir)
         .layer(
             tower::ServiceBuilder::new()
@@ -2152,7 +2174,7 @@ where
         let model_server_data_tx = {
             info!("send_pose server at {model_server_addr}");
             let (model_server_data_tx, data_rx) = tokio::sync::mpsc::channel(50);
-            let model_server_future = flydra2::new_model_server(data_rx, model_server_addr);
+            let model_server_future = flydra2::new_model_server(data_rx, None, model_server_addr);
             tokio::spawn(async { model_server_future.await });
             model_server_data_tx
         };
@@ -2174,6 +2196,7 @@ where
             #[cfg(feature = "flydra_feat_detect")]
             std::path::Path::new(&csv_save_dir).to_path_buf(),
             firehose_tx,
+            frame_broadcaster,
             #[cfg(feature = "flydratrax")]
             led_box_tx_std,
             #[cfg(feature = "flydratrax")]
@@ -2220,6 +2243,7 @@ where
             while let Some(frame_msg) = frame_stream.next().await {
                 match &frame_msg {
                     ci2_async::FrameResult::Frame(fframe) => {
+                        frame_counters.frame_received();
                         {
                             let frame: &DynamicFrame = &fframe.image.borrow();
                             trace!(
@@ -2269,6 +2293,7 @@ where
                                     }
                                 }
                             });
+                            frame_counters.frame_dropped();
                             error!("Channel full sending frame to process thread. Dropping frame data.");
                         } else {
                             tx_frame
@@ -3123,11 +3148,8 @@ where
                             let size =
                                 camcal::PixelSize::new(image_width as usize, image_height as usize);
 
-                            match camcal::compute_intrinsics_with_raw_opencv::<f64>(
-                                size,
-                                &goodcorners,
-                            ) {
-                                Ok(raw_opencv_cal) => {
+                            match camcal::calibrate_checkerboards(size, &goodcorners) {
+                                Ok(cal) => {
                                     let cal_dir = directories::BaseDirs::new()
                                         .as_ref()
                                         .map(|bd| {
@@ -3159,7 +3181,7 @@ where
                                         &cam_info_file_stamped,
                                         env!["CARGO_PKG_NAME"],
                                         local,
-                                        &raw_opencv_cal,
+                                        &cal,
                                         raw_cam_name.as_str(),
                                     )?;
 
@@ -3539,6 +3561,14 @@ impl FinalMp4RecordingConfig {
                     },
                 ))
             }
+            CodecSelection::H264LessAvc => {
+                if shared.mp4_bitrate
+                    != strand_cam_remote_control::BitrateSelection::BitrateUnlimited
+                {
+                    warn!("ignoring mp4 bitrate with lossless LessAVC codec");
+                }
+                Some(Mp4Codec::H264LessAvcThreaded(Default::default()))
+            }
             _ => None,
         };
         let h264_metadata = {
@@ -3557,17 +3587,22 @@ impl FinalMp4RecordingConfig {
             strand_cam_remote_control::RecordingConfig::Mp4(final_cfg)
         } else {
             use strand_cam_remote_control::CodecSelection::*;
-            let codec = match &shared.mp4_codec {
-                H264Nvenc | H264OpenH264 => {
+            match &shared.mp4_codec {
+                H264Nvenc | H264OpenH264 | H264LessAvc => {
                     unreachable!();
                 }
-                Ffmpeg(args) => args.clone(),
-            };
-            strand_cam_remote_control::RecordingConfig::Ffmpeg(FfmpegRecordingConfig {
-                codec_args: codec,
-                max_framerate: shared.mp4_max_framerate.clone(),
-                h264_metadata,
-            })
+                Ffmpeg(args) => {
+                    strand_cam_remote_control::RecordingConfig::Ffmpeg(FfmpegRecordingConfig {
+                        codec_args: args.clone(),
+                        max_framerate: shared.mp4_max_framerate.clone(),
+                        h264_metadata,
+                    })
+                }
+                OmeTiff => strand_cam_remote_control::RecordingConfig::Tiff(TiffRecordingConfig {
+                    max_framerate: shared.mp4_max_framerate.clone(),
+                    metadata: h264_metadata,
+                }),
+            }
         };
         FinalMp4RecordingConfig { final_cfg }
     }
@@ -1,10 +1,10 @@
 use std::path::PathBuf;
 
+use camcal::CalibrationResult;
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::Parser;
 use eyre::{self as anyhow, Context, Result};
 use image::GenericImageView;
-use opencv_calibrate::CalibrationResult;
 use tracing::info;
 
 #[derive(Parser, Debug)]
@@ -88,12 +88,13 @@ pub fn run_cal(cli: Cli) -> Result<CalibrationResult> {
         let (w, h) = img.dimensions();
         image_width = w;
         image_height = h;
-        let rgb = img.to_rgb8().into_raw();
+        let mono = img.to_luma8().into_raw();
 
-        let corners = opencv_calibrate::find_chessboard_corners(
-            &rgb,
+        let corners = camcal::find_chessboard_corners(
+            &mono,
             w,
             h,
+            w as usize,
             checkerboard_data.width as usize,
             checkerboard_data.height as usize,
         )?;
@@ -115,16 +116,13 @@ pub fn run_cal(cli: Cli) -> Result<CalibrationResult> {
         .collect();
 
     let size = camcal::PixelSize::new(image_width as usize, image_height as usize);
-    let raw_opencv_cal = match camcal::compute_intrinsics_with_raw_opencv::<f64>(size, &goodcorners)
-    {
-        Ok(raw_opencv_cal) => {
-            let intrinsics = camcal::convert_to_cam_geom::<f64>(&raw_opencv_cal);
-
+    let cal = match camcal::calibrate_checkerboards(size, &goodcorners) {
+        Ok(cal) => {
             info!(
                 "Mean reprojection error: {}",
-                raw_opencv_cal.mean_reprojection_distance_pixels
+                cal.mean_reprojection_distance_pixels
             );
-            info!("got calibrated intrinsics: {:?}", intrinsics);
+            info!("got calibrated intrinsics: {:?}", cal.intrinsics);
 
             let cam_name = dirname.to_string();
 
@@ -140,7 +138,7 @@ pub fn run_cal(cli: Cli) -> Result<CalibrationResult> {
                 &cam_info_file_stamped,
                 env!["CARGO_PKG_NAME"],
                 local,
-                &raw_opencv_cal,
+                &cal,
                 &cam_name,
             )?;
 
@@ -150,12 +148,12 @@ pub fn run_cal(cli: Cli) -> Result<CalibrationResult> {
                 .with_context(|| format!("Copying to file {cam_info_file}"))?;
 
             info!("Saved camera calibration to file: {cam_info_file}");
-            raw_opencv_cal
+            cal
         }
         Err(e) => {
             eyre::bail!("failed doing calibration {:?} {}", e, e);
         }
     };
 
-    Ok(raw_opencv_cal)
+    Ok(cal)
 }
@@ -64,21 +64,19 @@ fn test_checkerboard() -> Result<()> {
     // Test results against those from a successful run. (Some deviation is
     // expected.)
 
-    // camera_matrix is stored in row major order.
-    approx::assert_relative_eq!(cal.camera_matrix[0], 1188.8, epsilon = 0.1); // fx
-    approx::assert_relative_eq!(cal.camera_matrix[1], 0.0); // skew
-    approx::assert_relative_eq!(cal.camera_matrix[2], 939.0, epsilon = 1.0); // cx
-    approx::assert_relative_eq!(cal.camera_matrix[3], 0.0);
-    approx::assert_relative_eq!(cal.camera_matrix[4], 1188.8, epsilon = 0.1); // fy
-    approx::assert_relative_eq!(cal.camera_matrix[5], 583.0, epsilon = 1.0); // cy
-    approx::assert_relative_eq!(cal.camera_matrix[6], 0.0);
-    approx::assert_relative_eq!(cal.camera_matrix[7], 0.0);
-    approx::assert_relative_eq!(cal.camera_matrix[8], 1.0);
+    let intrinsics = &cal.intrinsics;
+    approx::assert_relative_eq!(intrinsics.fx(), 1188.8, epsilon = 1.0);
+    approx::assert_relative_eq!(intrinsics.skew(), 0.0);
+    approx::assert_relative_eq!(intrinsics.cx(), 939.0, epsilon = 1.0);
+    approx::assert_relative_eq!(intrinsics.fy(), 1188.8, epsilon = 1.0);
+    approx::assert_relative_eq!(intrinsics.cy(), 583.0, epsilon = 1.0);
 
-    approx::assert_relative_eq!(cal.distortion_coeffs[0], -0.234, epsilon = 0.01);
-    approx::assert_relative_eq!(cal.distortion_coeffs[1], 0.0754987651101312, epsilon = 0.01);
-    approx::assert_relative_eq!(cal.distortion_coeffs[2], -7.954e-6, epsilon = 1e-5);
-    approx::assert_relative_eq!(cal.distortion_coeffs[3], 6.39e-5, epsilon = 1e-5);
+    let distortion = intrinsics.distortion.opencv_vec();
+    approx::assert_relative_eq!(distortion[0], -0.234, epsilon = 0.01);
+    approx::assert_relative_eq!(distortion[1], 0.0754987651101312, epsilon = 0.01);
+    approx::assert_relative_eq!(distortion[2], -7.954e-6, epsilon = 1e-4);
+    approx::assert_relative_eq!(distortion[3], 6.39e-5, epsilon = 1e-4);
+    approx::assert_relative_eq!(distortion[4], 0.0);
 
     Ok(())
 }
@@ -800,6 +800,28 @@ impl Model {
                 }
             };
 
+            let recording_stats = if let Some(stats) = &shared.mp4_recording_stats {
+                let encoder = if let Some(n_threads) = stats.encoder_threads {
+                    format!(
+                        ", encoder threads: {n_threads}, frames in encoder: {}, \
+                        waited for encoder: {:.1} s",
+                        stats.encoder_in_flight, stats.encoder_blocked_secs,
+                    )
+                } else {
+                    "".to_string()
+                };
+                html! {
+                    <div>
+                        {format!(
+                            "Frames queued: {}, dropped: {}, saved: {}{encoder}",
+                            stats.frames_queued, stats.frames_dropped, stats.frames_written,
+                        )}
+                    </div>
+                }
+            } else {
+                html! {}
+            };
+
             html! {
                 <div class="wrap-collapsible">
                     <CheckboxLabel label="MP4 Recording Options" initially_checked=true />
@@ -815,6 +837,7 @@ impl Model {
                                 ontoggle={ctx.link().callback(|checked| {Msg::ToggleMp4Save(checked)})}
                                 />
                         </div>
+                        {recording_stats}
                         <div>
                             <h5>{"MP4 Max Framerate"}</h5>
                             <EnumToggle<RecordingFrameRate>
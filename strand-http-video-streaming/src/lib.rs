@@ -24,7 +24,7 @@ pub enum Error {
 
 // future: use MediaSource API? https://w3c.github.io/media-source
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AnnotatedFrame {
     pub frame: Arc<DynamicFrameOwned>,
     pub found_points: Vec<Point>,
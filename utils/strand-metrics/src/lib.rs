@@ -0,0 +1,239 @@
+//! Encode metrics in the [Prometheus text
+//! format](https://prometheus.io/docs/instrumenting/exposition_formats/) as
+//! served on the `/metrics` endpoint of [Strand
+//! Camera](https://strawlab.org/strand-cam) and
+//! [Braid](https://strawlab.org/braid).
+//!
+//! ```
+//! use strand_metrics::{Histogram, MetricType, MetricsEncoder};
+//!
+//! let mut latency = Histogram::new(&[0.01, 0.1]);
+//! latency.observe(0.05);
+//!
+//! let mut enc = MetricsEncoder::new();
+//! enc.family("frames_total", "Number of frames.", MetricType::Counter);
+//! enc.sample("frames_total", &[("camera", "cam1")], 42.0);
+//! enc.histogram("latency_seconds", "Latency.", &[], &latency);
+//! let text = enc.finish();
+//! assert!(text.contains("frames_total{camera=\"cam1\"} 42\n"));
+//! assert!(text.contains("latency_seconds_bucket{le=\"0.1\"} 1\n"));
+//! ```
+
+// Copyright 2026 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT
+// or http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::Write;
+
+/// The value of the `Content-Type` header for the text format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The type of a metric family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    /// A value which only increases (until restart).
+    Counter,
+    /// A value which may go up and down.
+    Gauge,
+    /// A distribution of observed values in buckets.
+    Histogram,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+/// A histogram with fixed bucket upper bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    /// Number of observations in each bucket (not cumulative). The last
+    /// element is the `+Inf` bucket.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    /// Create a histogram with the given bucket upper bounds, which must be
+    /// increasing. A `+Inf` bucket is added.
+    pub fn new(bounds: &[f64]) -> Self {
+        assert!(
+            bounds.windows(2).all(|w| w[0] < w[1]),
+            "histogram bounds must be increasing"
+        );
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    /// Record a value. NaN values are ignored.
+    pub fn observe(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        let idx = self
+            .bounds
+            .iter()
+            .position(|b| value <= *b)
+            .unwrap_or(self.bounds.len());
+        self.counts[idx] += 1;
+        self.sum += value;
+    }
+
+    /// Total number of observations.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Sum of all observations.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+}
+
+/// Builds the text of a `/metrics` response.
+#[derive(Debug, Default)]
+pub struct MetricsEncoder {
+    buf: String,
+}
+
+impl MetricsEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a metric family. Call this once per name, before its samples.
+    pub fn family(&mut self, name: &str, help: &str, metric_type: MetricType) {
+        let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+        writeln!(self.buf, "# HELP {name} {help}").unwrap();
+        writeln!(self.buf, "# TYPE {name} {}", metric_type.as_str()).unwrap();
+    }
+
+    /// Add a sample of a counter or gauge.
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.line(name, labels, None, value);
+    }
+
+    /// Start a family with a single sample.
+    pub fn single(&mut self, name: &str, help: &str, metric_type: MetricType, value: f64) {
+        self.family(name, help, metric_type);
+        self.sample(name, &[], value);
+    }
+
+    /// Start a histogram family and add the samples of `histogram`.
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+        histogram: &Histogram,
+    ) {
+        self.family(name, help, MetricType::Histogram);
+        let bucket = format!("{name}_bucket");
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(histogram.counts.iter()) {
+            cumulative += count;
+            let le = format_value(*bound);
+            self.line(&bucket, labels, Some(&le), cumulative as f64);
+        }
+        self.line(&bucket, labels, Some("+Inf"), histogram.count() as f64);
+        self.line(&format!("{name}_sum"), labels, None, histogram.sum);
+        self.line(
+            &format!("{name}_count"),
+            labels,
+            None,
+            histogram.count() as f64,
+        );
+    }
+
+    /// The encoded metrics.
+    pub fn finish(self) -> String {
+        self.buf
+    }
+
+    fn line(&mut self, name: &str, labels: &[(&str, &str)], le: Option<&str>, value: f64) {
+        self.buf.push_str(name);
+        let le = le.map(|le| ("le", le));
+        let mut labels = labels.iter().copied().chain(le).peekable();
+        if labels.peek().is_some() {
+            self.buf.push('{');
+            for (i, (key, value)) in labels.enumerate() {
+                if i > 0 {
+                    self.buf.push(',');
+                }
+                write!(self.buf, "{key}=\"{}\"", escape_label_value(value)).unwrap();
+            }
+            self.buf.push('}');
+        }
+        writeln!(self.buf, " {}", format_value(value)).unwrap();
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        format!("{value}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let mut hist = Histogram::new(&[0.5, 1.0]);
+        hist.observe(0.1);
+        hist.observe(0.7);
+        hist.observe(3.0);
+        hist.observe(f64::NAN);
+        assert_eq!(hist.count(), 3);
+
+        let mut enc = MetricsEncoder::new();
+        enc.family("fps", "Frame rate.", MetricType::Gauge);
+        enc.sample("fps", &[("camera", "a\"b\\c")], 100.5);
+        enc.sample("fps", &[("camera", "d")], f64::NAN);
+        enc.single("objects", "Live objects.", MetricType::Gauge, 2.0);
+        enc.histogram("lat", "Latency.", &[("x", "y")], &hist);
+
+        let expected = r#"# HELP fps Frame rate.
+# TYPE fps gauge
+fps{camera="a\"b\\c"} 100.5
+fps{camera="d"} NaN
+# HELP objects Live objects.
+# TYPE objects gauge
+objects 2
+# HELP lat Latency.
+# TYPE lat histogram
+lat_bucket{x="y",le="0.5"} 1
+lat_bucket{x="y",le="1"} 2
+lat_bucket{x="y",le="+Inf"} 3
+lat_sum{x="y"} 3.8
+lat_count{x="y"} 3
+"#;
+        assert_eq!(enc.finish(), expected);
+    }
+}